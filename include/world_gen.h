@@ -0,0 +1,41 @@
+/* The C interface of world_gen, built with
+ * `cargo rustc --release --lib --features ffi --crate-type cdylib`. */
+#ifndef WORLD_GEN_H
+#define WORLD_GEN_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A loaded map, freed with world_gen_map_free */
+typedef struct WorldGenMap WorldGenMap;
+
+/* Loads the map of a Hearts of Iron IV directory or mod from a UTF-8 path, or returns NULL */
+WorldGenMap *world_gen_map_load(const char *root_path);
+
+/* Loads a map like world_gen_map_load, skipping unreadable CSV rows if lenient_csv is true */
+WorldGenMap *world_gen_map_load_with(const char *root_path, bool lenient_csv);
+
+/* Frees a loaded map */
+void world_gen_map_free(WorldGenMap *map);
+
+/* Runs the checks of the map, returning a report freed with world_gen_string_free */
+char *world_gen_map_validate(const WorldGenMap *map);
+
+/* Gets the id of the province at a pixel of the provinces image, or 0 if there is none */
+int32_t world_gen_map_province_at(const WorldGenMap *map, uint32_t x, uint32_t y);
+
+/* Gets the error of the last failed call on this thread, owned by the library, or NULL */
+const char *world_gen_last_error(void);
+
+/* Frees a string returned by the library */
+void world_gen_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
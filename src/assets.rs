@@ -0,0 +1,238 @@
+use crate::{ErrorContext, MapError};
+use image::{ImageFormat, Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The extensions of the graphics the game draws the map with besides the BMP images, such as the
+/// terrain textures and colormaps
+pub const ASSET_EXTENSIONS: [&str; 2] = ["dds", "tga"];
+
+/// The first bytes of a DDS file
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+
+/// The length of the magic and header of a DDS file, after which the pixels start
+const DDS_HEADER_LENGTH: usize = 128;
+
+/// The pixel format flag of a DDS file with an alpha mask
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+
+/// The pixel format flag of a DDS file compressed in the format of its four character code
+const DDPF_FOURCC: u32 = 0x4;
+
+/// The pixel format flag of a DDS file holding a single greyscale channel in its red mask
+const DDPF_LUMINANCE: u32 = 0x2_0000;
+
+/// Finds the DDS and TGA files in the map directory and the directories within it.  The paths are
+/// relative to the root directory and sorted.
+/// # Errors
+/// * If a directory in the map directory cannot be read
+#[inline]
+pub fn find_assets(root_path: &Path) -> Result<Vec<PathBuf>, MapError> {
+    let mut assets = Vec::new();
+    let mut directories = vec![root_path.join("map")];
+    while let Some(directory) = directories.pop() {
+        if !directory.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&directory).in_file(&directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if is_asset(&path) {
+                if let Ok(relative) = path.strip_prefix(root_path) {
+                    assets.push(relative.to_path_buf());
+                }
+            }
+        }
+    }
+    assets.sort();
+    Ok(assets)
+}
+
+/// Whether a file is a DDS or TGA file, whatever the case of its extension
+fn is_asset(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| {
+            ASSET_EXTENSIONS
+                .iter()
+                .any(|asset| e.eq_ignore_ascii_case(asset))
+        })
+}
+
+/// Loads a DDS or TGA file.  DDS files compressed with DXT1, DXT3 or DXT5 are decoded by the
+/// `image` crate, and uncompressed DDS files with RGB, RGBA or luminance masks of up to 32 bits,
+/// which the game uses for its colormaps, are decoded here.  Only the largest mipmap is loaded.
+/// # Errors
+/// * If the file cannot be read
+/// * If the file is not a DDS or TGA file in a format that can be decoded
+#[inline]
+pub fn load_asset(path: &Path) -> Result<RgbaImage, MapError> {
+    let data = fs::read(path).in_file(path)?;
+    let format = if data.starts_with(DDS_MAGIC) {
+        if dds_u32(&data, 80).map_or(false, |flags| flags & DDPF_FOURCC == 0) {
+            return decode_uncompressed_dds(&data)
+                .ok_or_else(|| MapError::InvalidImageType(path.to_path_buf()));
+        }
+        ImageFormat::Dds
+    } else {
+        ImageFormat::Tga
+    };
+    Ok(image::load_from_memory_with_format(&data, format)
+        .in_file(path)?
+        .to_rgba8())
+}
+
+/// Reads a little endian field of the header of a DDS file, at its offset from the start of the
+/// file
+#[allow(clippy::integer_arithmetic)]
+fn dds_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Decodes the pixels of an uncompressed DDS file through the masks of its channels.  Returns
+/// `None` if the pixels are not whole bytes or the file ends before them.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn decode_uncompressed_dds(data: &[u8]) -> Option<RgbaImage> {
+    let height = dds_u32(data, 12)?;
+    let width = dds_u32(data, 16)?;
+    let flags = dds_u32(data, 80)?;
+    let bits = dds_u32(data, 88)?;
+    let [red, green, blue, alpha] = [
+        dds_u32(data, 92)?,
+        dds_u32(data, 96)?,
+        dds_u32(data, 100)?,
+        dds_u32(data, 104)?,
+    ];
+    if bits == 0 || bits > 32 || bits % 8 != 0 {
+        return None;
+    }
+    let bytes_per_pixel = (bits / 8) as usize;
+    let length = (width as usize)
+        .checked_mul(height as usize)?
+        .checked_mul(bytes_per_pixel)?;
+    let pixels = data.get(DDS_HEADER_LENGTH..DDS_HEADER_LENGTH.checked_add(length)?)?;
+    let alpha = if flags & DDPF_ALPHAPIXELS == 0 {
+        0
+    } else {
+        alpha
+    };
+    let luminance = flags & DDPF_LUMINANCE != 0;
+    let mut image = RgbaImage::new(width, height);
+    for (pixel, bytes) in image.pixels_mut().zip(pixels.chunks_exact(bytes_per_pixel)) {
+        let value = bytes
+            .iter()
+            .rev()
+            .fold(0_u32, |packed, byte| packed << 8 | u32::from(*byte));
+        let r = channel(value, red, 0);
+        let (g, b) = if luminance {
+            (r, r)
+        } else {
+            (channel(value, green, 0), channel(value, blue, 0))
+        };
+        *pixel = Rgba([r, g, b, channel(value, alpha, u8::MAX)]);
+    }
+    Some(image)
+}
+
+/// The value of the channel of a pixel under a mask, scaled to 8 bits, or the default if the
+/// pixels have no such channel
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn channel(value: u32, mask: u32, default: u8) -> u8 {
+    if mask == 0 {
+        return default;
+    }
+    let max = u64::from(mask >> mask.trailing_zeros());
+    let bits = u64::from((value & mask) >> mask.trailing_zeros());
+    ((bits * 255 + max / 2) / max) as u8
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The header of an uncompressed DDS file with the given size, flags and masks
+    fn dds_header(width: u32, height: u32, flags: u32, bits: u32, masks: [u32; 4]) -> Vec<u8> {
+        let mut data = vec![0; DDS_HEADER_LENGTH];
+        data[..4].copy_from_slice(DDS_MAGIC);
+        let mut put = |offset: usize, value: u32| {
+            data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        put(4, 124);
+        put(12, height);
+        put(16, width);
+        put(76, 32);
+        put(80, flags);
+        put(88, bits);
+        for (i, mask) in masks.into_iter().enumerate() {
+            put(92 + i * 4, mask);
+        }
+        data
+    }
+
+    #[test]
+    fn it_decodes_uncompressed_dds_files() {
+        let masks = [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000];
+        let mut data = dds_header(2, 1, 0x40 | DDPF_ALPHAPIXELS, 32, masks);
+        data.extend_from_slice(&0x80ff_2010_u32.to_le_bytes());
+        data.extend_from_slice(&0xff00_00ff_u32.to_le_bytes());
+        let image = decode_uncompressed_dds(&data).expect("Failed to decode");
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 32, 16, 128]));
+        assert_eq!(*image.get_pixel(1, 0), Rgba([0, 0, 255, 255]));
+
+        // Five bits of red, six of green and five of blue, without alpha
+        let mut data = dds_header(1, 1, 0x40, 16, [0xf800, 0x07e0, 0x001f, 0]);
+        data.extend_from_slice(&0xf81f_u16.to_le_bytes());
+        let image = decode_uncompressed_dds(&data).expect("Failed to decode");
+        assert_eq!(*image.get_pixel(0, 0), Rgba([255, 0, 255, 255]));
+
+        let mut data = dds_header(1, 1, DDPF_LUMINANCE, 8, [0xff, 0, 0, 0]);
+        data.push(100);
+        let image = decode_uncompressed_dds(&data).expect("Failed to decode");
+        assert_eq!(*image.get_pixel(0, 0), Rgba([100, 100, 100, 255]));
+
+        // The file ends before its pixels
+        let data = dds_header(4, 4, 0x40, 32, masks);
+        assert!(decode_uncompressed_dds(&data).is_none());
+    }
+
+    #[test]
+    fn it_finds_and_loads_the_assets_of_the_map() {
+        let root = std::env::temp_dir().join("world_gen_assets_test");
+        let _result = fs::remove_dir_all(&root);
+        let terrain = root.join("map/terrain");
+        fs::create_dir_all(&terrain).expect("Failed to create directories");
+        let masks = [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0];
+        let mut data = dds_header(1, 1, 0x40, 32, masks);
+        data.extend_from_slice(&0x0010_2030_u32.to_le_bytes());
+        fs::write(terrain.join("colormap_water_0.DDS"), data).expect("Failed to write DDS");
+        RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255]))
+            .save(root.join("map/sea.tga"))
+            .expect("Failed to write TGA");
+        fs::write(root.join("map/provinces.bmp"), b"").expect("Failed to write BMP");
+
+        let assets = find_assets(&root).expect("Failed to find assets");
+        assert_eq!(
+            assets,
+            [
+                PathBuf::from("map/sea.tga"),
+                PathBuf::from("map/terrain/colormap_water_0.DDS")
+            ]
+        );
+        let colormap = load_asset(&root.join(&assets[1])).expect("Failed to load DDS");
+        assert_eq!(*colormap.get_pixel(0, 0), Rgba([16, 32, 48, 255]));
+        let sea = load_asset(&root.join(&assets[0])).expect("Failed to load TGA");
+        fs::remove_dir_all(&root).expect("Failed to remove directory");
+        assert_eq!(sea.dimensions(), (2, 2));
+        assert_eq!(*sea.get_pixel(1, 1), Rgba([1, 2, 3, 255]));
+    }
+}
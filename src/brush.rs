@@ -0,0 +1,466 @@
+use crate::components::river::RiverColor;
+use crate::generation::terrain::TERRAIN_PALETTE;
+use image::math::Rect;
+use image::{imageops, Rgb, RgbImage};
+
+/// The largest change in height a single application of a full strength brush can make.
+const MAX_HEIGHT_STEP: f32 = 8.0;
+
+/// The operation a height brush performs on the heightmap.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HeightBrushMode {
+    /// Raises the terrain under the brush
+    #[default]
+    Raise,
+    /// Lowers the terrain under the brush
+    Lower,
+    /// Blends the terrain under the brush with its neighbors
+    Smooth,
+}
+
+/// A circular brush for sculpting the heightmap.
+/// The effect of the brush falls off linearly from the center to the edge of the radius.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct HeightBrush {
+    /// The operation the brush performs
+    pub mode: HeightBrushMode,
+    /// The radius of the brush in pixels
+    pub radius: u32,
+    /// The strength of the brush, from 0.0 to 1.0
+    pub strength: f32,
+}
+
+impl Default for HeightBrush {
+    #[inline]
+    fn default() -> Self {
+        Self::new(HeightBrushMode::Raise, 16, 0.5)
+    }
+}
+
+impl HeightBrush {
+    /// Creates a new height brush
+    #[inline]
+    #[must_use]
+    pub const fn new(mode: HeightBrushMode, radius: u32, strength: f32) -> Self {
+        Self {
+            mode,
+            radius,
+            strength,
+        }
+    }
+
+    /// Applies the brush to the heightmap centered on the given pixel.  Points outside of the
+    /// heightmap are ignored.
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(&self, heightmap: &mut RgbImage, center_x: u32, center_y: u32) {
+        if center_x >= heightmap.width() || center_y >= heightmap.height() {
+            return;
+        }
+        let radius = self.radius.max(1);
+        let strength = self.strength.clamp(0.0, 1.0);
+        let min_x = center_x.saturating_sub(radius);
+        let min_y = center_y.saturating_sub(radius);
+        let max_x = center_x.saturating_add(radius).min(heightmap.width() - 1);
+        let max_y = center_y.saturating_add(radius).min(heightmap.height() - 1);
+
+        // Smoothing reads from an unmodified copy of the area around the brush, including a one
+        // pixel margin so the edges of the brush can sample their neighbors.
+        let snapshot_x = min_x.saturating_sub(1);
+        let snapshot_y = min_y.saturating_sub(1);
+        let snapshot = match self.mode {
+            HeightBrushMode::Smooth => {
+                let width = (max_x + 2).min(heightmap.width()) - snapshot_x;
+                let height = (max_y + 2).min(heightmap.height()) - snapshot_y;
+                Some(
+                    imageops::crop_imm(heightmap, snapshot_x, snapshot_y, width, height).to_image(),
+                )
+            }
+            HeightBrushMode::Raise | HeightBrushMode::Lower => None,
+        };
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let dx = x as f32 - center_x as f32;
+                let dy = y as f32 - center_y as f32;
+                let distance = dx.hypot(dy);
+                if distance > radius as f32 {
+                    continue;
+                }
+                let falloff = 1.0 - distance / radius as f32;
+                let current = f32::from(heightmap.get_pixel(x, y).0[0]);
+                let new_height = match (&self.mode, &snapshot) {
+                    (HeightBrushMode::Raise, _) => {
+                        (MAX_HEIGHT_STEP * strength).mul_add(falloff, current)
+                    }
+                    (HeightBrushMode::Lower, _) => {
+                        (-MAX_HEIGHT_STEP * strength).mul_add(falloff, current)
+                    }
+                    (HeightBrushMode::Smooth, Some(s)) => {
+                        let average = neighborhood_average(s, x - snapshot_x, y - snapshot_y);
+                        (average - current).mul_add(strength * falloff, current)
+                    }
+                    (HeightBrushMode::Smooth, None) => current,
+                };
+                let value = new_height.round().clamp(0.0, 255.0) as u8;
+                heightmap.put_pixel(x, y, Rgb([value, value, value]));
+            }
+        }
+    }
+}
+
+/// The operation a river brush performs on the rivers image.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RiverBrushMode {
+    /// Draws a river one pixel wide
+    #[default]
+    Draw,
+    /// Places a source marker
+    Source,
+    /// Places a flow-in marker, where a river merges into another
+    FlowIn,
+    /// Places a flow-out marker, where a river branches from another
+    FlowOut,
+    /// Erases rivers and markers
+    Erase,
+}
+
+/// A brush for drawing rivers.  Only colors from the rivers palette are ever written, and strokes
+/// are kept one pixel wide and connected along the edges of the pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RiverBrush {
+    /// The operation the brush performs
+    pub mode: RiverBrushMode,
+    /// The width of the rivers drawn, from 0 (narrowest) to 8 (widest)
+    pub width: u8,
+}
+
+impl RiverBrush {
+    /// Creates a new river brush
+    #[inline]
+    #[must_use]
+    pub const fn new(mode: RiverBrushMode, width: u8) -> Self {
+        Self { mode, width }
+    }
+
+    /// Applies the brush to the rivers image.  Drawing and erasing follow the stroke from the
+    /// previous point to the current one, while markers are placed on the current point only.
+    /// Returns the area of the image that was touched by the brush, if any.
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn apply(
+        &self,
+        rivers: &mut RgbImage,
+        from: Option<(u32, u32)>,
+        to: (u32, u32),
+    ) -> Option<Rect> {
+        let from = match self.mode {
+            RiverBrushMode::Draw | RiverBrushMode::Erase => from.unwrap_or(to),
+            RiverBrushMode::Source | RiverBrushMode::FlowIn | RiverBrushMode::FlowOut => to,
+        };
+        let mut touched: Option<Rect> = None;
+        for (x, y) in four_connected_line(from, to) {
+            if x >= rivers.width() || y >= rivers.height() {
+                continue;
+            }
+            let current = RiverColor::from_rgb(*rivers.get_pixel(x, y));
+            let color = match self.mode {
+                RiverBrushMode::Draw => {
+                    if current.map_or(false, RiverColor::is_river) || would_thicken(rivers, x, y) {
+                        continue;
+                    }
+                    RiverColor::River(self.width)
+                }
+                RiverBrushMode::Source => RiverColor::Source,
+                RiverBrushMode::FlowIn => RiverColor::FlowIn,
+                RiverBrushMode::FlowOut => RiverColor::FlowOut,
+                RiverBrushMode::Erase => {
+                    if !current.map_or(true, RiverColor::is_river) {
+                        continue;
+                    }
+                    RiverColor::Land
+                }
+            };
+            rivers.put_pixel(x, y, color.to_rgb());
+            touched = Some(touched.map_or(
+                Rect {
+                    x,
+                    y,
+                    width: 1,
+                    height: 1,
+                },
+                |r| {
+                    let min_x = r.x.min(x);
+                    let min_y = r.y.min(y);
+                    Rect {
+                        x: min_x,
+                        y: min_y,
+                        width: (r.x + r.width).max(x + 1) - min_x,
+                        height: (r.y + r.height).max(y + 1) - min_y,
+                    }
+                },
+            ));
+        }
+        touched
+    }
+}
+
+/// A circular brush for painting graphical terrain onto terrain.bmp.  Only colors of the
+/// `TERRAIN_PALETTE` are ever written, so the image can still be saved with the palette.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TerrainBrush {
+    /// The palette index of the graphical terrain to paint
+    pub index: u8,
+    /// The radius of the brush in pixels
+    pub radius: u32,
+}
+
+impl Default for TerrainBrush {
+    #[inline]
+    fn default() -> Self {
+        Self::new(3, 8)
+    }
+}
+
+impl TerrainBrush {
+    /// Creates a new terrain brush, snapping the index to the last entry of the palette if it is
+    /// out of range
+    #[inline]
+    #[must_use]
+    pub const fn new(index: u8, radius: u32) -> Self {
+        Self {
+            index: snap_terrain_index(index),
+            radius,
+        }
+    }
+
+    /// The color the brush paints with
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::indexing_slicing)]
+    pub const fn color(&self) -> Rgb<u8> {
+        Rgb(TERRAIN_PALETTE[snap_terrain_index(self.index) as usize])
+    }
+
+    /// Applies the brush to the terrain image centered on the given pixel.  Points outside of the
+    /// image are ignored.
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn apply(&self, terrain: &mut RgbImage, center_x: u32, center_y: u32) {
+        if center_x >= terrain.width() || center_y >= terrain.height() {
+            return;
+        }
+        let color = self.color();
+        let radius = self.radius.max(1);
+        let max_x = center_x.saturating_add(radius).min(terrain.width() - 1);
+        let max_y = center_y.saturating_add(radius).min(terrain.height() - 1);
+        for y in center_y.saturating_sub(radius)..=max_y {
+            for x in center_x.saturating_sub(radius)..=max_x {
+                let dx = x as f32 - center_x as f32;
+                let dy = y as f32 - center_y as f32;
+                if dx.hypot(dy) <= radius as f32 {
+                    terrain.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Snaps a terrain index to the palette, so that indices past its end paint the last entry
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::integer_arithmetic)]
+const fn snap_terrain_index(index: u8) -> u8 {
+    let last = (TERRAIN_PALETTE.len() - 1) as u8;
+    if index > last {
+        last
+    } else {
+        index
+    }
+}
+
+/// Gets the pixels on a line between two points, where each pixel shares an edge with the next.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn four_connected_line(from: (u32, u32), to: (u32, u32)) -> Vec<(u32, u32)> {
+    let (mut x, mut y) = (i64::from(from.0), i64::from(from.1));
+    let (end_x, end_y) = (i64::from(to.0), i64::from(to.1));
+    let dx = (end_x - x).abs();
+    let dy = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = dx + dy;
+    let mut line = Vec::new();
+    loop {
+        line.push((x as u32, y as u32));
+        if x == end_x && y == end_y {
+            break;
+        }
+        let e2 = 2 * error;
+        if e2 - dy > dx - e2 {
+            error += dy;
+            x += step_x;
+        } else {
+            error += dx;
+            y += step_y;
+        }
+    }
+    line
+}
+
+/// Whether drawing a river on the given pixel would complete a 2x2 block of river pixels.
+#[allow(clippy::integer_arithmetic)]
+fn would_thicken(rivers: &RgbImage, x: u32, y: u32) -> bool {
+    let is_river = |dx: i64, dy: i64| {
+        let (nx, ny) = (i64::from(x) + dx, i64::from(y) + dy);
+        match (u32::try_from(nx), u32::try_from(ny)) {
+            (Ok(nx), Ok(ny)) => rivers
+                .get_pixel_checked(nx, ny)
+                .and_then(|p| RiverColor::from_rgb(*p))
+                .map_or(false, RiverColor::is_river),
+            _ => false,
+        }
+    };
+    [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .iter()
+        .any(|(dx, dy)| is_river(*dx, 0) && is_river(0, *dy) && is_river(*dx, *dy))
+}
+
+/// Gets the average height of the 3x3 neighborhood around a pixel, ignoring pixels outside of the
+/// image.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+fn neighborhood_average(image: &RgbImage, x: u32, y: u32) -> f32 {
+    let mut total = 0.0;
+    let mut count = 0_u32;
+    for ny in y.saturating_sub(1)..=(y + 1).min(image.height() - 1) {
+        for nx in x.saturating_sub(1)..=(x + 1).min(image.width() - 1) {
+            total += f32::from(image.get_pixel(nx, ny).0[0]);
+            count += 1;
+        }
+    }
+    total / count as f32
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_raises_the_heightmap_under_the_brush() {
+        let mut heightmap = RgbImage::from_pixel(32, 32, Rgb([100, 100, 100]));
+        let brush = HeightBrush::new(HeightBrushMode::Raise, 4, 1.0);
+        brush.apply(&mut heightmap, 16, 16);
+        assert_eq!(heightmap.get_pixel(16, 16).0, [108, 108, 108]);
+        assert_eq!(heightmap.get_pixel(16, 21).0, [100, 100, 100]);
+        assert!(heightmap.get_pixel(16, 18).0[0] > 100);
+    }
+
+    #[test]
+    fn it_lowers_the_heightmap_without_underflowing() {
+        let mut heightmap = RgbImage::from_pixel(8, 8, Rgb([3, 3, 3]));
+        let brush = HeightBrush::new(HeightBrushMode::Lower, 2, 1.0);
+        brush.apply(&mut heightmap, 0, 0);
+        assert_eq!(heightmap.get_pixel(0, 0).0, [0, 0, 0]);
+    }
+
+    #[test]
+    fn it_smooths_a_spike() {
+        let mut heightmap = RgbImage::from_pixel(9, 9, Rgb([90, 90, 90]));
+        heightmap.put_pixel(4, 4, Rgb([180, 180, 180]));
+        let brush = HeightBrush::new(HeightBrushMode::Smooth, 3, 1.0);
+        brush.apply(&mut heightmap, 4, 4);
+        assert_eq!(heightmap.get_pixel(4, 4).0, [100, 100, 100]);
+    }
+
+    #[test]
+    fn it_draws_connected_one_pixel_wide_rivers() {
+        let mut rivers = RgbImage::from_pixel(8, 8, RiverColor::Land.to_rgb());
+        let brush = RiverBrush::new(RiverBrushMode::Draw, 2);
+        let touched = brush
+            .apply(&mut rivers, Some((0, 0)), (3, 3))
+            .expect("Failed to draw river");
+        assert_eq!(
+            (touched.x, touched.y, touched.width, touched.height),
+            (0, 0, 4, 4)
+        );
+        let river_pixels = rivers
+            .pixels()
+            .filter(|p| **p == RiverColor::River(2).to_rgb())
+            .count();
+        assert_eq!(river_pixels, 7);
+
+        // A parallel stroke right next to the river must not thicken it.
+        brush.apply(&mut rivers, Some((1, 0)), (4, 3));
+        for y in 0..7 {
+            for x in 0..7 {
+                let block = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)];
+                assert!(!block
+                    .iter()
+                    .all(|(bx, by)| *rivers.get_pixel(*bx, *by) != RiverColor::Land.to_rgb()));
+            }
+        }
+    }
+
+    #[test]
+    fn it_places_markers_and_erases_rivers() {
+        let mut rivers = RgbImage::from_pixel(8, 8, RiverColor::Land.to_rgb());
+        RiverBrush::new(RiverBrushMode::Draw, 0).apply(&mut rivers, Some((0, 4)), (7, 4));
+        RiverBrush::new(RiverBrushMode::Source, 0).apply(&mut rivers, Some((0, 4)), (7, 4));
+        assert_eq!(*rivers.get_pixel(7, 4), RiverColor::Source.to_rgb());
+        assert_eq!(*rivers.get_pixel(0, 4), RiverColor::River(0).to_rgb());
+        RiverBrush::new(RiverBrushMode::Erase, 0).apply(&mut rivers, Some((0, 4)), (7, 4));
+        assert!(rivers.pixels().all(|p| *p == RiverColor::Land.to_rgb()));
+    }
+
+    #[test]
+    fn it_ignores_points_outside_the_heightmap() {
+        let mut heightmap = RgbImage::from_pixel(4, 4, Rgb([50, 50, 50]));
+        let brush = HeightBrush::new(HeightBrushMode::Raise, 8, 1.0);
+        brush.apply(&mut heightmap, 10, 10);
+        assert!(heightmap.pixels().all(|p| p.0 == [50, 50, 50]));
+    }
+
+    #[test]
+    fn it_paints_terrain_palette_colors() {
+        let mut terrain = RgbImage::from_pixel(16, 16, Rgb(TERRAIN_PALETTE[0]));
+        let brush = TerrainBrush::new(8, 3);
+        brush.apply(&mut terrain, 8, 8);
+        assert_eq!(*terrain.get_pixel(8, 8), Rgb(TERRAIN_PALETTE[8]));
+        assert_eq!(*terrain.get_pixel(8, 11), Rgb(TERRAIN_PALETTE[8]));
+        assert_eq!(*terrain.get_pixel(11, 11), Rgb(TERRAIN_PALETTE[0]));
+        assert!(terrain.pixels().all(|p| TERRAIN_PALETTE.contains(&p.0)));
+    }
+
+    #[test]
+    fn it_snaps_terrain_indices_to_the_palette() {
+        let brush = TerrainBrush::new(200, 1);
+        assert_eq!(usize::from(brush.index), TERRAIN_PALETTE.len() - 1);
+        let brush = TerrainBrush {
+            index: 255,
+            ..TerrainBrush::default()
+        };
+        assert_eq!(
+            brush.color(),
+            Rgb(TERRAIN_PALETTE[TERRAIN_PALETTE.len() - 1])
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::province_index::ProvinceIndex;
+use crate::validation::buildings::building_pixel;
+use derive_more::Display;
+use image::{Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// The color of the provinces without a counted building
+const EMPTY: Rgb<u8> = Rgb([48, 48, 48]);
+
+/// The colors of the heat map from the fewest buildings to the most, spread evenly between them
+const HEAT_RAMP: [Rgb<u8>; 4] = [
+    Rgb([32, 32, 128]),
+    Rgb([32, 160, 96]),
+    Rgb([240, 208, 32]),
+    Rgb([224, 32, 32]),
+];
+
+/// What the buildings of the heat map are counted by
+#[allow(clippy::exhaustive_enums)]
+#[derive(Default, Display, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DensityScope {
+    /// Each state is colored by the buildings it is given in `buildings.txt`
+    #[default]
+    #[display(fmt = "Per state")]
+    State,
+    /// Each province is colored by the buildings placed on it, as the game places provincial
+    /// buildings by their position
+    #[display(fmt = "Per province")]
+    Province,
+}
+
+/// Which buildings the heat map counts and what it counts them by
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BuildingDensity {
+    /// What the buildings are counted by
+    pub scope: DensityScope,
+    /// The only type of building counted, or `None` to count every building
+    pub building: Option<BuildingId>,
+}
+
+impl BuildingDensity {
+    /// Creates new settings for the heat map of the buildings
+    #[inline]
+    #[must_use]
+    pub const fn new(scope: DensityScope, building: Option<BuildingId>) -> Self {
+        Self { scope, building }
+    }
+
+    /// Counts the buildings of each province.  Counted per state, each province of a state has
+    /// the count of the whole state.
+    #[inline]
+    #[must_use]
+    pub fn counts(
+        &self,
+        buildings: &Buildings,
+        states: &HashMap<StateId, State>,
+        province_index: &ProvinceIndex,
+    ) -> HashMap<ProvinceId, usize> {
+        let counted = buildings.buildings.iter().filter(|b| {
+            self.building
+                .as_ref()
+                .map_or(true, |id| b.building_id == *id)
+        });
+        let mut counts: HashMap<ProvinceId, usize> = HashMap::new();
+        match self.scope {
+            DensityScope::State => {
+                let mut by_state: HashMap<StateId, usize> = HashMap::new();
+                for building in counted {
+                    let count = by_state.entry(building.state_id).or_default();
+                    *count = count.saturating_add(1);
+                }
+                for (state_id, count) in by_state {
+                    if let Some(state) = states.get(&state_id) {
+                        counts.extend(state.provinces.iter().map(|p| (*p, count)));
+                    }
+                }
+            }
+            DensityScope::Province => {
+                let dimensions = province_index.dimensions();
+                for building in counted {
+                    let province = building_pixel(building, dimensions)
+                        .and_then(|(x, y)| province_index.province_at(x, y));
+                    if let Some(province) = province {
+                        let count = counts.entry(province).or_default();
+                        *count = count.saturating_add(1);
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    /// Draws a heat map of the buildings, coloring each province from blue for the fewest
+    /// buildings to red for the most.  Provinces without a counted building are dark grey, and
+    /// pixels without a province are black.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn render(
+        &self,
+        buildings: &Buildings,
+        states: &HashMap<StateId, State>,
+        province_index: &ProvinceIndex,
+    ) -> RgbImage {
+        let counts = self.counts(buildings, states, province_index);
+        let most = counts.values().copied().max().unwrap_or_default().max(1) as f64;
+        // The square root spreads out the few provinces with many buildings from the many with a
+        // few, which would otherwise all be drawn in the coldest color
+        let colors = counts
+            .into_iter()
+            .map(|(province, count)| (province, heat_color((count as f64 / most).sqrt())))
+            .collect::<HashMap<_, _>>();
+        province_index.paint(|id| Some(colors.get(&id).copied().unwrap_or(EMPTY)))
+    }
+}
+
+/// The color of the heat map at a fraction from 0.0 to 1.0 of the most buildings
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn heat_color(fraction: f64) -> Rgb<u8> {
+    let position = fraction.clamp(0.0, 1.0) * (HEAT_RAMP.len().saturating_sub(1)) as f64;
+    let lower = position.floor() as usize;
+    let weight = position - position.floor();
+    match (HEAT_RAMP.get(lower), HEAT_RAMP.get(lower.saturating_add(1))) {
+        (Some(from), Some(to)) => {
+            let mut color = *from;
+            for (channel, (a, b)) in color.0.iter_mut().zip(from.0.iter().zip(to.0)) {
+                let value = (f64::from(b) - f64::from(*a)).mul_add(weight, f64::from(*a));
+                *channel = value.round().clamp(0.0, 255.0) as u8;
+            }
+            color
+        }
+        (Some(color), None) => *color,
+        _ => EMPTY,
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn building(state: i32, id: &str, x: f32, z: f32) -> StateBuilding {
+        StateBuilding {
+            state_id: StateId(state),
+            building_id: BuildingId(id.to_owned()),
+            x,
+            y: 0.0,
+            z,
+            rotation: 0.0,
+            adjacent_sea_province: ProvinceId(0),
+        }
+    }
+
+    fn state(id: i32, provinces: &[i32]) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: None,
+            provinces: provinces
+                .iter()
+                .map(|p| ProvinceId(*p))
+                .collect::<HashSet<_>>(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    #[test]
+    fn it_counts_the_buildings_per_state_and_per_province() {
+        // Provinces 1, 2 and 3 are the left, middle and right thirds of the map
+        let provinces = RgbImage::from_fn(6, 2, |x, _| Rgb([(x / 2 + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=3)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let states = HashMap::from([
+            (StateId(1), state(1, &[1, 2])),
+            (StateId(2), state(2, &[3])),
+        ]);
+        let buildings = Buildings {
+            types: HashSet::new(),
+            buildings: vec![
+                building(1, "bunker", 0.5, 1.5),
+                building(1, "bunker", 0.5, 0.5),
+                building(1, "naval_base", 2.5, 1.5),
+                building(2, "bunker", 4.5, 1.5),
+            ],
+            lines: Vec::new(),
+        };
+
+        let per_state = BuildingDensity::default().counts(&buildings, &states, &index);
+        assert_eq!(per_state[&ProvinceId(1)], 3);
+        assert_eq!(per_state[&ProvinceId(2)], 3);
+        assert_eq!(per_state[&ProvinceId(3)], 1);
+
+        let bunkers = BuildingDensity::new(
+            DensityScope::Province,
+            Some(BuildingId("bunker".to_owned())),
+        );
+        let per_province = bunkers.counts(&buildings, &states, &index);
+        assert_eq!(per_province[&ProvinceId(1)], 2);
+        assert!(!per_province.contains_key(&ProvinceId(2)));
+        assert_eq!(per_province[&ProvinceId(3)], 1);
+
+        let map = bunkers.render(&buildings, &states, &index);
+        assert_eq!(*map.get_pixel(0, 0), HEAT_RAMP[3]);
+        assert_eq!(*map.get_pixel(2, 0), EMPTY);
+        assert_ne!(*map.get_pixel(4, 0), EMPTY);
+    }
+}
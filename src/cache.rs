@@ -0,0 +1,295 @@
+use crate::encoding::{forced_encoding, TextEncoding};
+use crate::{CsvParsing, MapError};
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// The version of the cache files, raised whenever a cached component changes its layout so
+/// that files written by older builds are read as stale
+const CACHE_VERSION: u32 = 3;
+
+/// The state of a source file of a cached component when the component was cached
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SourceStamp {
+    /// The path of the file
+    path: PathBuf,
+    /// The time the file was last modified, in nanoseconds since the Unix epoch
+    modified: u128,
+    /// The length of the file in bytes
+    len: u64,
+}
+
+/// What a cached component was parsed from, stored ahead of the component so that a cache file
+/// is only read when its sources are unchanged
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheKey {
+    /// The version of the cache file
+    version: u32,
+    /// The source files of the component
+    sources: Vec<SourceStamp>,
+    /// The encoding the load or `WORLD_GEN_ENCODING` forced the text files to be read with, as
+    /// the same files decode to different components under another encoding
+    encoding: Option<String>,
+    /// How the rows of the CSV files that could not be read were handled, so that a component
+    /// parsed leniently is never read back by a strict load
+    parsing: CsvParsing,
+}
+
+impl CacheKey {
+    /// Stamps the source files of a component as they are on disk now, read with an encoding if
+    /// one is forced and with the rows that cannot be read handled as `parsing` says
+    fn of(
+        sources: &[PathBuf],
+        encoding: Option<TextEncoding>,
+        parsing: CsvParsing,
+    ) -> Result<Self, MapError> {
+        let sources = sources
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path)?;
+                let modified = metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_nanos());
+                Ok(SourceStamp {
+                    path: path.clone(),
+                    modified,
+                    len: metadata.len(),
+                })
+            })
+            .collect::<Result<_, MapError>>()?;
+        Ok(Self {
+            version: CACHE_VERSION,
+            sources,
+            encoding: encoding.map(|e| e.to_string()),
+            parsing,
+        })
+    }
+}
+
+/// A directory of parsed map components, each kept in a `<name>.bin` file with the modification
+/// times of the files it was parsed from.  A component is parsed again when any of its files
+/// changed, and a cache that cannot be read or written only costs the time of parsing, so its
+/// errors are logged rather than returned.  A directory holds the components of one map at a
+/// time; loading another map replaces them.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ComponentCache {
+    /// The directory the cache files are kept in
+    dir: PathBuf,
+}
+
+impl ComponentCache {
+    /// Creates a cache in a directory, which is created when the first component is cached
+    #[inline]
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The directory the cache files are kept in
+    #[inline]
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reads a component from the cache if its source files are unchanged, or loads it and caches
+    /// it otherwise
+    /// # Arguments
+    /// * `name` - the name of the cache file of the component
+    /// * `sources` - the files the component is parsed from
+    /// * `parsing` - how `load` handles the rows of the CSV files that cannot be read
+    /// * `encoding` - the encoding `load` reads the text files in, if it forces one
+    /// * `load` - parses the component from its source files
+    /// # Errors
+    /// If the component is not cached and fails to load
+    #[inline]
+    pub fn load_or_insert<T, F>(
+        &self,
+        name: &str,
+        sources: &[PathBuf],
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+        load: F,
+    ) -> Result<T, MapError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T, MapError>,
+    {
+        let encoding = encoding.or_else(forced_encoding);
+        let key = match CacheKey::of(sources, encoding, parsing) {
+            Ok(key) => key,
+            // A missing source fails the load with a better error than the cache has
+            Err(_) => return load(),
+        };
+        let path = self.dir.join(format!("{name}.bin"));
+        if let Some(value) = read_cached(&path, &key) {
+            return Ok(value);
+        }
+        let value = load()?;
+        if let Err(e) = self.write_cached(&path, &key, &value) {
+            warn!("Failed to cache {name} in {}: {e}", path.display());
+        }
+        Ok(value)
+    }
+
+    /// Writes a component and its key to a cache file, through a temporary file so that a load
+    /// running at the same time never reads half a file
+    fn write_cached<T: Serialize>(
+        &self,
+        path: &Path,
+        key: &CacheKey,
+        value: &T,
+    ) -> Result<(), MapError> {
+        fs::create_dir_all(&self.dir)?;
+        let partial = path.with_extension("partial");
+        {
+            let mut writer = BufWriter::new(File::create(&partial)?);
+            bincode::serialize_into(&mut writer, key)?;
+            bincode::serialize_into(&mut writer, value)?;
+            writer.flush()?;
+        }
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+}
+
+/// Reads a component from a cache file if the file was written for the same key
+fn read_cached<T: DeserializeOwned>(path: &Path, key: &CacheKey) -> Option<T> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let cached_key: CacheKey = bincode::deserialize_from(&mut reader).ok()?;
+    if cached_key != *key {
+        return None;
+    }
+    match bincode::deserialize_from(reader) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Failed to read the cache file {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Loads a component through the cache if there is one, or straight from its files otherwise
+pub(crate) fn cached<T, F>(
+    cache: Option<&ComponentCache>,
+    name: &str,
+    sources: &[PathBuf],
+    parsing: CsvParsing,
+    encoding: Option<TextEncoding>,
+    load: F,
+) -> Result<T, MapError>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Result<T, MapError>,
+{
+    match cache {
+        Some(cache) => cache.load_or_insert(name, sources, parsing, encoding, load),
+        None => load(),
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::panic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_a_component_back_until_its_source_changes() {
+        let dir = std::env::temp_dir().join("world_gen_component_cache");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create dir");
+        let source = dir.join("source.txt");
+        fs::write(&source, "1 2 3").expect("Failed to write source");
+        let cache = ComponentCache::new(dir.join("cache"));
+        let sources = [source.clone()];
+
+        let parse = || {
+            fs::read_to_string(&source)?
+                .split_whitespace()
+                .map(|n| n.parse::<i32>().map_err(MapError::from))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let loaded = cache
+            .load_or_insert("numbers", &sources, CsvParsing::Strict, None, parse)
+            .expect("Failed to load");
+        assert_eq!(loaded, vec![1, 2, 3]);
+        assert!(cache.dir().join("numbers.bin").exists());
+
+        let cached: Vec<i32> = cache
+            .load_or_insert("numbers", &sources, CsvParsing::Strict, None, || {
+                panic!("Parsed a cached component")
+            })
+            .expect("Failed to read cache");
+        assert_eq!(cached, vec![1, 2, 3]);
+
+        fs::write(&source, "4 5 6 7").expect("Failed to change source");
+        let reloaded = cache
+            .load_or_insert("numbers", &sources, CsvParsing::Strict, None, parse)
+            .expect("Failed to reload");
+        assert_eq!(reloaded, vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn it_keys_a_component_by_the_encoding_it_is_read_with() {
+        let dir = std::env::temp_dir().join("world_gen_component_cache_encoding");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create dir");
+        let source = dir.join("source.txt");
+        fs::write(&source, b"Z\xfcrich").expect("Failed to write source");
+        let cache = ComponentCache::new(dir.join("cache"));
+        let sources = [source];
+        let windows_1252 = Some(TextEncoding::Windows1252);
+
+        let loaded = cache
+            .load_or_insert("names", &sources, CsvParsing::Strict, windows_1252, || {
+                Ok("Z\u{fc}rich".to_owned())
+            })
+            .expect("Failed to load");
+        let cached: String = cache
+            .load_or_insert("names", &sources, CsvParsing::Strict, windows_1252, || {
+                panic!("Parsed a cached component")
+            })
+            .expect("Failed to read cache");
+        assert_eq!(cached, loaded);
+        let reloaded = cache
+            .load_or_insert(
+                "names",
+                &sources,
+                CsvParsing::Strict,
+                Some(TextEncoding::Utf8),
+                || Ok("Z\u{fffd}rich".to_owned()),
+            )
+            .expect("Failed to reload");
+        assert_eq!(reloaded, "Z\u{fffd}rich");
+    }
+
+    #[test]
+    fn it_never_reads_a_lenient_parse_back_for_a_strict_load() {
+        let dir = std::env::temp_dir().join("world_gen_component_cache_parsing");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create dir");
+        let source = dir.join("source.txt");
+        fs::write(&source, "1 2 x").expect("Failed to write source");
+        let cache = ComponentCache::new(dir.join("cache"));
+        let sources = [source];
+
+        let lenient = cache
+            .load_or_insert("numbers", &sources, CsvParsing::Lenient, None, || {
+                Ok(vec![1, 2])
+            })
+            .expect("Failed to load leniently");
+        assert_eq!(lenient, vec![1, 2]);
+        let strict: Result<Vec<i32>, _> =
+            cache.load_or_insert("numbers", &sources, CsvParsing::Strict, None, || {
+                Err(MapError::InvalidValue("x".to_owned()))
+            });
+        assert!(strict.is_err());
+    }
+}
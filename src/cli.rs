@@ -0,0 +1,118 @@
+//! The commands run from the command line without opening the editor window.
+use indicatif::InMemoryTerm;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tokio::runtime::Runtime;
+use world_gen::generation::import::{GisImporter, ImportConfig};
+use world_gen::map::{Map, MapLoadOptions};
+use world_gen::MapError;
+
+/// How to run the commands of the command line
+pub const USAGE: &str =
+    "Usage: world_gen [validate <root> | export-json <root> <output> | import <config> <root>]
+
+Without a command, opens the editor.
+
+Commands:
+  validate <root>              Loads the map of the root Hearts of Iron IV directory, prints every
+                               problem the validation finds and exits with 1 if any of them is an
+                               error
+  export-json <root> <output>  Loads the map of the root Hearts of Iron IV directory and writes its
+                               definitions, adjacencies, states, strategic regions, railways and
+                               other components to a single JSON file
+  import <config> <root>       Imports the heightfield and administrative regions named by the TOML
+                               config, writing the heightmap, provinces and definitions to the map
+                               folder of the root directory";
+
+/// A command given on the command line
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Validate the map of a root directory and print the report
+    Validate(PathBuf),
+    /// Export the components of the map of a root directory to a JSON file
+    ExportJson(PathBuf, PathBuf),
+    /// Import a map from real-world data with the settings of a config into a root directory
+    Import(PathBuf, PathBuf),
+}
+
+/// Reads the command from the arguments after the program name.  Returns `Ok(None)` without
+/// arguments, when the editor should open, and the usage when the arguments are not understood.
+pub fn parse_command(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Option<Command>, &'static str> {
+    let command = match args.next() {
+        None => return Ok(None),
+        Some(command) => command,
+    };
+    let command = match (command.as_str(), args.next()) {
+        ("validate", Some(root)) => Command::Validate(PathBuf::from(root)),
+        ("export-json", Some(root)) => match args.next() {
+            Some(output) => Command::ExportJson(PathBuf::from(root), PathBuf::from(output)),
+            None => return Err(USAGE),
+        },
+        ("import", Some(config)) => match args.next() {
+            Some(root) => Command::Import(PathBuf::from(config), PathBuf::from(root)),
+            None => return Err(USAGE),
+        },
+        _ => return Err(USAGE),
+    };
+    if args.next().is_some() {
+        return Err(USAGE);
+    }
+    Ok(Some(command))
+}
+
+/// Runs a command, returning the exit code of the program
+#[allow(clippy::print_stderr)]
+pub fn run(command: Command) -> ExitCode {
+    let result = match command {
+        Command::Validate(root) => validate(root),
+        Command::ExportJson(root, output) => export_json(root, &output),
+        Command::Import(config, root) => import(&config, &root),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        ExitCode::from(2)
+    })
+}
+
+/// Loads the map of a root directory and prints its validation report.  Fails with 1 if the
+/// report has errors.
+#[allow(clippy::print_stdout)]
+fn validate(root: PathBuf) -> Result<ExitCode, MapError> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let map = load_map(&rt, root)?;
+    let report = map.validate();
+    print!("{report}");
+    if report.has_errors() {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+/// Loads the map of a root directory and writes its components to a JSON file
+fn export_json(root: PathBuf, output: &Path) -> Result<ExitCode, MapError> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    let mut map = load_map(&rt, root)?;
+    map.export_json(output)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Imports a map from real-world data into a root directory
+fn import(config_path: &Path, root: &Path) -> Result<ExitCode, MapError> {
+    let config = ImportConfig::from_file(config_path)?;
+    GisImporter::new(config).import(root)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Loads the map of a root directory on the blocking threads of a runtime
+fn load_map(rt: &Runtime, root: PathBuf) -> Result<Map, MapError> {
+    let handle =
+        rt.spawn_blocking(move || Map::new::<InMemoryTerm>(&root, &None, &MapLoadOptions::new()));
+    rt.block_on(handle)?
+}
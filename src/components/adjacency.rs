@@ -1,12 +1,19 @@
 use crate::components::wrappers::{AdjacencyRuleName, Icon, ProvinceId, XCoord, YCoord};
-use crate::{LoadCsv, LoadObject, MapError};
+use crate::encoding::TextEncoding;
+use crate::{CsvParsing, LoadCsv, LoadObject, MapError};
 use derive_more::Display;
 use jomini::JominiDeserialize;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Write};
+use std::fs;
 use std::path::Path;
 
+/// The header of adjacencies.csv
+pub const ADJACENCIES_HEADER: &str =
+    "From;To;Type;Through;start_x;start_y;stop_x;stop_y;adjacency_rule_name;Comment\n";
+
 /// An adjacency rule
 #[derive(Clone, Debug, JominiDeserialize, Serialize, PartialEq)]
 #[non_exhaustive]
@@ -31,6 +38,43 @@ pub struct AdjacencyRule {
     pub is_disabled: Option<IsDisabled>,
 }
 
+impl AdjacencyRule {
+    /// The logic of the rule when its required provinces have the given controllers.  The logic
+    /// for friends, enemies or neutrals applies when every required province has that controller,
+    /// and the contested logic applies when they differ.  Required provinces without a controller
+    /// are neutral.  The `is_disabled` trigger is not taken into account.
+    #[inline]
+    #[must_use]
+    pub fn logic(&self, controllers: &HashMap<ProvinceId, Controller>) -> AdjacencyLogic {
+        let mut required = self
+            .required_provinces
+            .iter()
+            .map(|p| controllers.get(p).copied().unwrap_or_default());
+        let first = required.next().unwrap_or_default();
+        if required.any(|c| c != first) {
+            return self.contested;
+        }
+        match first {
+            Controller::Friend => self.friend,
+            Controller::Enemy => self.enemy,
+            Controller::Neutral => self.neutral,
+        }
+    }
+}
+
+/// Who controls a province, as seen by the country crossing an adjacency
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, Display, PartialEq, Eq, Hash)]
+pub enum Controller {
+    /// The country itself or one of its allies
+    Friend,
+    /// A country at war with the country
+    Enemy,
+    /// Any other country
+    #[default]
+    Neutral,
+}
+
 /// An adjacency rule
 #[derive(Clone, Debug, JominiDeserialize, Serialize)]
 #[non_exhaustive]
@@ -121,27 +165,166 @@ pub struct Adjacency {
     pub comment: Option<String>,
 }
 
+impl Adjacency {
+    /// Creates a sea adjacency between two provinces, blocked by the given sea province and drawn
+    /// without adjusting the graphic
+    #[inline]
+    #[must_use]
+    pub const fn sea(from: ProvinceId, to: ProvinceId, through: ProvinceId) -> Self {
+        Self {
+            from,
+            to,
+            adjacency_type: Some(AdjacencyType::Sea),
+            through: Some(through),
+            start_x: XCoord(-1),
+            stop_x: XCoord(-1),
+            start_y: YCoord(-1),
+            stop_y: YCoord(-1),
+            adjacency_rule_name: None,
+            comment: None,
+        }
+    }
+
+    /// Creates the row ending adjacencies.csv, as the game stops reading the file at it
+    #[inline]
+    #[must_use]
+    pub const fn terminator() -> Self {
+        Self {
+            from: ProvinceId(-1),
+            to: ProvinceId(-1),
+            adjacency_type: None,
+            through: Some(ProvinceId(-1)),
+            start_x: XCoord(-1),
+            stop_x: XCoord(-1),
+            start_y: YCoord(-1),
+            stop_y: YCoord(-1),
+            adjacency_rule_name: None,
+            comment: None,
+        }
+    }
+
+    /// Whether the row is the one ending adjacencies.csv
+    #[inline]
+    #[must_use]
+    pub fn is_terminator(&self) -> bool {
+        self.from == ProvinceId(-1)
+    }
+
+    /// The province that can block the adjacency, or `None` if the adjacency cannot be blocked
+    #[inline]
+    #[must_use]
+    pub fn through_province(&self) -> Option<ProvinceId> {
+        self.through.and_then(ProvinceId::reference)
+    }
+}
+
 /// The adjacencies from the adjacency csv file.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct Adjacencies {
     /// The adjacencies between provinces
     pub adjacencies: Vec<Adjacency>,
+    /// The lines of the adjacencies file the adjacencies were read from, by their index.
+    /// Adjacencies added since have no line.
+    #[serde(skip)]
+    pub lines: Vec<usize>,
 }
 
 impl Adjacencies {
-    /// Loads the adjacencies from the given path.
+    /// Loads the adjacencies from the given path, handling the rows that cannot be read as
+    /// `parsing` says.
     /// # Errors
     /// Returns an error if the file could not be loaded.
     #[inline]
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
-        let adjacencies = Adjacency::load_csv(path, true)?;
-        Ok(Self { adjacencies })
+    #[allow(clippy::else_if_without_else)]
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let (lines, adjacencies): (Vec<_>, Vec<_>) =
+            Adjacency::load_csv_lines(path, true, parsing, encoding)?
+                .into_iter()
+                .unzip();
+        for adjacency in adjacencies.iter().filter(|a| !a.is_terminator()) {
+            if !adjacency.from.is_province() || !adjacency.to.is_province() {
+                warn!(
+                    "Adjacency from {} to {} does not connect two provinces",
+                    adjacency.from, adjacency.to
+                );
+            } else if adjacency.adjacency_type == Some(AdjacencyType::Sea)
+                && adjacency.through_province().is_none()
+            {
+                warn!(
+                    "Sea adjacency from {} to {} has no province it passes through",
+                    adjacency.from, adjacency.to
+                );
+            }
+        }
+        Ok(Self { adjacencies, lines })
+    }
+
+    /// Whether the last row is the one the game stops reading the file at
+    #[inline]
+    #[must_use]
+    pub fn is_terminated(&self) -> bool {
+        self.adjacencies
+            .last()
+            .map_or(false, Adjacency::is_terminator)
+    }
+
+    /// Adds an adjacency, keeping the row ending the file last.  That row loses its line when the
+    /// adjacency is added before it.
+    #[inline]
+    pub fn push(&mut self, adjacency: Adjacency) {
+        if self.is_terminated() {
+            let end = self.adjacencies.len().saturating_sub(1);
+            self.adjacencies.insert(end, adjacency);
+            self.lines.truncate(end);
+        } else {
+            self.adjacencies.push(adjacency);
+        }
+    }
+
+    /// Writes the adjacencies to the given path in the format of adjacencies.csv
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        let mut data = ADJACENCIES_HEADER.to_owned();
+        for adjacency in &self.adjacencies {
+            let adjacency_type = match adjacency.adjacency_type {
+                None => "",
+                Some(AdjacencyType::Impassable) => "impassable",
+                Some(AdjacencyType::Sea) => "sea",
+                Some(AdjacencyType::River) => "river",
+                Some(AdjacencyType::LargeRiver) => "large_river",
+            };
+            let _result = writeln!(
+                data,
+                "{};{};{adjacency_type};{};{};{};{};{};{};{}",
+                adjacency.from,
+                adjacency.to,
+                adjacency.through.map(|p| p.to_string()).unwrap_or_default(),
+                adjacency.start_x,
+                adjacency.start_y,
+                adjacency.stop_x,
+                adjacency.stop_y,
+                adjacency
+                    .adjacency_rule_name
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                adjacency.comment.as_deref().unwrap_or_default(),
+            );
+        }
+        fs::write(path, data)?;
+        Ok(())
     }
 }
 
 /// The adjacency rules from the adjacency rule file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct AdjacencyRules {
     /// The adjacency rules
@@ -153,9 +336,9 @@ impl AdjacencyRules {
     /// # Errors
     /// Returns an error if the file could not be loaded.
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
         let mut adjacency_rules = HashMap::new();
-        let rules = RawAdjacencyRules::load_object(path)?;
+        let rules = RawAdjacencyRules::load_object(path, encoding)?;
         for rule in rules.adjacency_rule {
             adjacency_rules.insert(rule.name.clone(), rule);
         }
@@ -175,13 +358,16 @@ mod tests {
 
     #[test]
     fn it_reads_adjacencies_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let adjacency_rules_path =
             append_dir(&map.adjacencies, "./test/map").expect("Failed to find adjacency rules");
-        let adjacencies = Adjacency::load_csv(adjacency_rules_path, true)
+        let adjacencies = Adjacency::load_csv(adjacency_rules_path, true, CsvParsing::Strict, None)
             .expect("Failed to read adjacencies.csv");
-        let adjacencies = Adjacencies { adjacencies };
+        let adjacencies = Adjacencies {
+            adjacencies,
+            lines: Vec::new(),
+        };
         assert_eq!(adjacencies.adjacencies.len(), 486);
         assert_eq!(
             adjacencies.adjacencies[0],
@@ -200,13 +386,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_records_the_lines_of_the_adjacencies() {
+        let adjacencies =
+            Adjacencies::from_file("./test/map/adjacencies.csv", CsvParsing::Strict, None)
+                .expect("Failed to read adjacencies.csv");
+        assert_eq!(adjacencies.lines.len(), adjacencies.adjacencies.len());
+        // The first line is the header
+        assert_eq!(adjacencies.lines[..2], [2, 3]);
+    }
+
+    #[test]
+    fn it_writes_adjacencies_that_read_back_the_same() {
+        let mut adjacencies =
+            Adjacencies::from_file("./test/map/adjacencies.csv", CsvParsing::Strict, None)
+                .expect("Failed to read adjacencies.csv");
+        adjacencies.push(Adjacency::terminator());
+        adjacencies.push(Adjacency::sea(ProvinceId(1), ProvinceId(3), ProvinceId(2)));
+        assert!(adjacencies.is_terminated());
+        let path = std::env::temp_dir().join("world_gen_adjacencies_test.csv");
+        adjacencies
+            .to_file(&path)
+            .expect("Failed to write adjacencies");
+        let read = Adjacencies::from_file(&path, CsvParsing::Strict, None)
+            .expect("Failed to read adjacencies");
+        fs::remove_file(&path).expect("Failed to remove adjacencies");
+        assert_eq!(read.adjacencies, adjacencies.adjacencies);
+    }
+
+    #[test]
+    fn it_reports_the_line_of_a_malformed_row_unless_lenient() {
+        let path = std::env::temp_dir().join("world_gen_malformed_adjacencies_test.csv");
+        let data = format!(
+            "{ADJACENCIES_HEADER}1;2;sea;3;-1;-1;-1;-1;;\"Strait\n\
+             4;x;;-1;-1;-1;-1;-1;;Border\n\
+             6;7;;-1;-1;-1;-1;-1;;Border\n"
+        );
+        fs::write(&path, data).expect("Failed to write adjacencies");
+        let strict = Adjacency::load_csv(&path, true, CsvParsing::Strict, None);
+        let lenient = Adjacency::load_csv(&path, true, CsvParsing::Lenient, None);
+        fs::remove_file(&path).expect("Failed to remove adjacencies");
+
+        match strict {
+            Err(MapError::InvalidCsvRow(_, line, _)) => assert_eq!(line, 3),
+            other => panic!("Expected the malformed row to be reported, got {other:?}"),
+        }
+        let lenient = lenient.expect("Failed to read adjacencies");
+        let ends = lenient.iter().map(|a| (a.from, a.to)).collect::<Vec<_>>();
+        assert_eq!(
+            ends,
+            vec![
+                (ProvinceId(1), ProvinceId(2)),
+                (ProvinceId(6), ProvinceId(7))
+            ]
+        );
+        assert_eq!(lenient[0].comment.as_deref(), Some("\"Strait"));
+    }
+
+    #[test]
+    fn it_treats_empty_through_columns_as_no_province() {
+        let mut adjacency = Adjacency {
+            from: ProvinceId(1),
+            to: ProvinceId(2),
+            adjacency_type: Some(AdjacencyType::Sea),
+            through: Some(ProvinceId(-1)),
+            start_x: XCoord(-1),
+            stop_x: XCoord(-1),
+            start_y: YCoord(-1),
+            stop_y: YCoord(-1),
+            adjacency_rule_name: None,
+            comment: None,
+        };
+        assert_eq!(adjacency.through_province(), None);
+        adjacency.through = Some(ProvinceId(0));
+        assert_eq!(adjacency.through_province(), None);
+        adjacency.through = None;
+        assert_eq!(adjacency.through_province(), None);
+        adjacency.through = Some(ProvinceId(3));
+        assert_eq!(adjacency.through_province(), Some(ProvinceId(3)));
+    }
+
+    #[test]
+    fn it_picks_the_logic_for_the_controllers() {
+        let logic = |army: bool| AdjacencyLogic {
+            army,
+            navy: false,
+            submarine: false,
+            trade: true,
+        };
+        let rule = AdjacencyRule {
+            name: AdjacencyRuleName("Canal".to_owned()),
+            contested: logic(false),
+            enemy: logic(false),
+            friend: logic(true),
+            neutral: logic(false),
+            required_provinces: vec![ProvinceId(1), ProvinceId(2)],
+            icon: Icon(ProvinceId(1)),
+            offset: Vec::new(),
+            is_disabled: None,
+        };
+        let mut controllers = HashMap::new();
+        assert_eq!(rule.logic(&controllers), rule.neutral);
+        controllers.insert(ProvinceId(1), Controller::Friend);
+        assert_eq!(rule.logic(&controllers), rule.contested);
+        controllers.insert(ProvinceId(2), Controller::Friend);
+        assert!(rule.logic(&controllers).army);
+        controllers.insert(ProvinceId(2), Controller::Enemy);
+        assert!(!rule.logic(&controllers).army);
+    }
+
     #[test]
     fn it_reads_adjacency_rules_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let adjacency_rules_path =
             append_dir(&map.adjacency_rules, "./test/map").expect("Failed to find adjacency rules");
-        let adjacency_rules = AdjacencyRules::from_file(&adjacency_rules_path)
+        let adjacency_rules = AdjacencyRules::from_file(&adjacency_rules_path, None)
             .expect("Failed to read adjacency rules");
         assert_eq!(adjacency_rules.adjacency_rules.len(), 11);
         assert_eq!(
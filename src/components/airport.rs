@@ -1,10 +1,12 @@
 use crate::components::wrappers::{ProvinceId, StateId};
-use crate::{load_map, MapError};
+use crate::encoding::TextEncoding;
+use crate::{load_map, save_map, MapError};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::path::Path;
 
 /// The list of airports in each state
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct Airports {
     /// The airports by state
@@ -16,10 +18,18 @@ impl Airports {
     /// # Errors
     /// If the file cannot be read, or if it is invalid.
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
-        let airports = load_map(path)?;
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let airports = load_map(path, encoding)?;
         Ok(Self { airports })
     }
+
+    /// Writes the airports to the given path in the format of airports.txt
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        save_map(path, &self.airports)
+    }
 }
 
 #[allow(clippy::expect_used)]
@@ -31,7 +41,7 @@ mod tests {
 
     #[test]
     fn it_reads_the_airports_file() {
-        let airports = Airports::from_file(Path::new("./test/map/airports.txt"))
+        let airports = Airports::from_file(Path::new("./test/map/airports.txt"), None)
             .expect("Failed to read airports.txt");
         assert_eq!(airports.airports.len(), 1388);
         assert_eq!(
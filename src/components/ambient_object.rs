@@ -0,0 +1,260 @@
+use crate::encoding::{read_text, TextEncoding};
+use crate::{parse_error, ErrorContext, MapError};
+use jomini::text::{ObjectReader, ValueReader};
+use jomini::{TextTape, Utf8Encoding};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// The objects of `map/ambient_object.txt`, such as the frame around the map and the decorations
+/// placed on it.  The text of each object is kept as it was read, so that saving the file after
+/// removing objects leaves the others exactly as they were.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct AmbientObjects {
+    /// The objects, in the order they are written
+    pub objects: Vec<AmbientObject>,
+    /// The text after the last object, such as a closing comment
+    #[serde(skip)]
+    trailing: String,
+}
+
+/// An object of `map/ambient_object.txt`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct AmbientObject {
+    /// The entity of the object, such as `map_frame_entity`
+    pub object_type: String,
+    /// The positions the object is placed at, as the x, y and z coordinates of the map where y
+    /// is the height and z runs from the bottom of the map
+    pub positions: Vec<[f32; 3]>,
+    /// The text of the object as it was read, along with the comments and blank lines before it
+    #[serde(skip)]
+    text: String,
+}
+
+impl AmbientObjects {
+    /// Loads the `AmbientObjects` from a given path
+    /// # Errors
+    /// If the file cannot be read, or if it is invalid
+    #[inline]
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        Self::from_text(path, &data).in_file(path)
+    }
+
+    /// Writes the objects to a given path
+    /// # Errors
+    /// If the file cannot be written
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        fs::write(path, self.to_text()).in_file(path)?;
+        Ok(())
+    }
+
+    /// Removes an object, returning it if there was one at the index.  The comments before the
+    /// object are removed with it.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> Option<AmbientObject> {
+        (index < self.objects.len()).then(|| self.objects.remove(index))
+    }
+
+    /// The text of the file, with each object written as it was read
+    #[inline]
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let mut text = self
+            .objects
+            .iter()
+            .map(|object| object.text.as_str())
+            .collect::<String>();
+        text.push_str(&self.trailing);
+        text
+    }
+
+    /// Reads the objects from the text of their file.  Each `object` block at the root of the
+    /// file is an object, and the text between two objects, including any other keys, is kept
+    /// with the object after it.
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let mut objects = Vec::new();
+        let mut start = 0;
+        for (key, key_start, end) in root_entries(data) {
+            if key != "object" {
+                continue;
+            }
+            let block = data.get(key_start..end).unwrap_or_default();
+            let tape = TextTape::from_slice(block.as_bytes())
+                .map_err(|e| parse_error(e, path, block.as_bytes()))?;
+            let reader = tape.utf8_reader();
+            for (_key, _op, value) in reader.fields() {
+                let mut object = read_object(&value.read_object()?);
+                object.text = data.get(start..end).unwrap_or_default().to_owned();
+                objects.push(object);
+            }
+            start = end;
+        }
+        Ok(Self {
+            objects,
+            trailing: data.get(start..).unwrap_or_default().to_owned(),
+        })
+    }
+}
+
+/// Reads the type and positions of an object.  A position is either a `pos` or `position` of the
+/// object, or the `position` of each of its `instances`, given as `{ x y z }` or as
+/// `{ x = 0 y = 0 z = 0 }`.
+fn read_object(reader: &ObjectReader<'_, '_, Utf8Encoding>) -> AmbientObject {
+    let mut object = AmbientObject {
+        object_type: String::new(),
+        positions: Vec::new(),
+        text: String::new(),
+    };
+    for (key, _op, value) in reader.fields() {
+        match &*key.read_str() {
+            "type" => object.object_type = value.read_string().unwrap_or_default(),
+            "pos" | "position" => object.positions.extend(read_position(&value)),
+            "instances" => {
+                let instances = value.read_array().ok();
+                for instance in instances.iter().flat_map(|i| i.values()) {
+                    let position = instance.read_object().ok().and_then(|i| {
+                        i.fields()
+                            .find(|(key, _op, _value)| key.read_str() == "position")
+                            .and_then(|(_key, _op, position)| read_position(&position))
+                    });
+                    object.positions.extend(position);
+                }
+            }
+            _ => {}
+        }
+    }
+    object
+}
+
+/// Reads a position given as `{ x y z }` or as `{ x = 0 y = 0 z = 0 }`
+fn read_position(value: &ValueReader<'_, '_, Utf8Encoding>) -> Option<[f32; 3]> {
+    let number = |v: &ValueReader<'_, '_, Utf8Encoding>| v.read_string().ok()?.parse::<f32>().ok();
+    if let Ok(object) = value.read_object() {
+        let mut position = [None; 3];
+        for (key, _op, coordinate) in object.fields() {
+            let axis = match &*key.read_str() {
+                "x" => position.get_mut(0),
+                "y" => position.get_mut(1),
+                "z" => position.get_mut(2),
+                _ => None,
+            };
+            if let Some(axis) = axis {
+                *axis = number(&coordinate);
+            }
+        }
+        if let [Some(x), Some(y), Some(z)] = position {
+            return Some([x, y, z]);
+        }
+    }
+    let values = value
+        .read_array()
+        .ok()?
+        .values()
+        .map(|v| number(&v))
+        .collect::<Option<Vec<_>>>()?;
+    <[f32; 3]>::try_from(values.get(..3)?).ok()
+}
+
+/// The keys at the root of a file in the game's text format, along with where each key starts and
+/// where its value ends.  Quoted strings and comments are skipped over, so that braces within
+/// them do not count.
+fn root_entries(data: &str) -> Vec<(&str, usize, usize)> {
+    let bytes = data.as_bytes();
+    let mut entries = Vec::new();
+    let mut position = 0;
+    let mut depth = 0_usize;
+    let mut entry: Option<(usize, usize)> = None;
+    while let Some(byte) = bytes.get(position) {
+        match byte {
+            b'#' => {
+                while bytes.get(position).map_or(false, |b| *b != b'\n') {
+                    position = position.saturating_add(1);
+                }
+                continue;
+            }
+            b'"' => {
+                position = position.saturating_add(1);
+                while bytes.get(position).map_or(false, |b| *b != b'"') {
+                    position = position.saturating_add(1);
+                }
+            }
+            b'{' => depth = depth.saturating_add(1),
+            b'}' => {
+                depth = depth.saturating_sub(1);
+                if let (0, Some((start, key_end))) = (depth, entry) {
+                    let key = data.get(start..key_end).unwrap_or_default();
+                    entries.push((key.trim(), start, position.saturating_add(1)));
+                    entry = None;
+                }
+            }
+            // A key with a value that is not a block is replaced by the key after it
+            b'=' if depth == 0 => {
+                let line_start = data
+                    .get(..position)
+                    .and_then(|before| before.rfind(['\n', '}']))
+                    .map_or(0, |index| index.saturating_add(1));
+                let key = data.get(line_start..position).unwrap_or_default();
+                let start =
+                    line_start.saturating_add(key.len().saturating_sub(key.trim_start().len()));
+                entry = Some((start, position));
+            }
+            _ => {}
+        }
+        position = position.saturating_add(1);
+    }
+    entries
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::float_cmp)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBIENT_OBJECTS: &str = "# The frame around the map\n\
+                                   object = {\n\
+                                   \ttype = \"map_frame_entity\"\n\
+                                   \tpersistent = yes\n\
+                                   \tinstances = {\n\
+                                   \t\t{ position = { 2816.0 0.0 1024.0 } scale = { 1 1 1 } }\n\
+                                   \t\t{ position = { 10.5 2.0 20.0 } }\n\
+                                   \t}\n\
+                                   }\n\
+                                   \n\
+                                   object={ type=\"whale\" pos={ x=100 y=9.5 z=300 } }\n\
+                                   # The end\n";
+
+    #[test]
+    fn it_reads_the_positions_of_the_objects() {
+        let objects = AmbientObjects::from_text(Path::new("ambient_object.txt"), AMBIENT_OBJECTS)
+            .expect("Failed to read ambient objects");
+        assert_eq!(objects.objects.len(), 2);
+        assert_eq!(objects.objects[0].object_type, "map_frame_entity");
+        assert_eq!(
+            objects.objects[0].positions,
+            vec![[2816.0, 0.0, 1024.0], [10.5, 2.0, 20.0]]
+        );
+        assert_eq!(objects.objects[1].object_type, "whale");
+        assert_eq!(objects.objects[1].positions, vec![[100.0, 9.5, 300.0]]);
+        assert_eq!(objects.to_text(), AMBIENT_OBJECTS);
+    }
+
+    #[test]
+    fn it_keeps_the_other_objects_as_they_were_when_removing_one() {
+        let mut objects =
+            AmbientObjects::from_text(Path::new("ambient_object.txt"), AMBIENT_OBJECTS)
+                .expect("Failed to read ambient objects");
+        let removed = objects.remove(1).expect("No object to remove");
+        assert_eq!(removed.object_type, "whale");
+        assert!(objects.remove(1).is_none());
+        let text = objects.to_text();
+        assert!(text.starts_with("# The frame around the map\nobject = {\n\ttype"));
+        assert!(text.ends_with("\t}\n}\n# The end\n"));
+        assert!(!text.contains("whale"));
+    }
+}
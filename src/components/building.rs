@@ -1,5 +1,6 @@
 use crate::components::wrappers::{BuildingId, ProvinceId, StateId};
-use crate::{LoadCsv, LoadKeys, MapError};
+use crate::encoding::TextEncoding;
+use crate::{CsvParsing, LoadCsv, LoadKeys, MapError};
 use log::warn;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -51,43 +52,68 @@ pub struct StateBuilding {
     pub adjacent_sea_province: ProvinceId,
 }
 
+impl StateBuilding {
+    /// The sea province ships can reach the building from, or `None` if the building is not a
+    /// naval base or floating harbor
+    #[inline]
+    #[must_use]
+    pub const fn sea_province(&self) -> Option<ProvinceId> {
+        self.adjacent_sea_province.reference()
+    }
+}
+
 /// The buildings on the map
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct Buildings {
     /// The building types
     pub types: HashSet<BuildingId>,
     /// The buildings
     pub buildings: Vec<StateBuilding>,
+    /// The lines of `buildings.txt` the buildings were read from, by their index
+    pub lines: Vec<usize>,
 }
 
 impl Buildings {
-    /// Creates a new `BuildingTypes` from a file
+    /// Creates a new `BuildingTypes` from a file, with the building types of a file or of every
+    /// `.txt` file of `common/buildings`.  The rows of the buildings that cannot be read are
+    /// handled as `parsing` says.
     /// # Errors
     /// If the file cannot be read, or if it is invalid, returns an error.
     #[inline]
-    pub fn from_files(types_path: &Path, buildings_path: &Path) -> Result<Self, MapError> {
-        let mut types = BuildingId::load_keys(types_path, "buildings")?;
+    pub fn from_files(
+        types_path: &Path,
+        buildings_path: &Path,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let mut types = BuildingId::load_keys(types_path, "buildings", encoding)?;
         // Floating harbors appear to be a building type that is hard coded into the game.
         types.insert(BuildingId("floating_harbor".to_owned()));
-        let raw_buildings = StateBuilding::load_csv(buildings_path, false)?;
+        let raw_buildings =
+            StateBuilding::load_csv_lines(buildings_path, false, parsing, encoding)?;
 
         // Verify that all building ids are defined in types
-        for building in &raw_buildings {
+        for (line, building) in &raw_buildings {
             if !types.contains(&building.building_id) {
                 warn!(
-                    "BuildingId {:?} is not defined in types",
+                    "{}:{line}: BuildingId {:?} is not defined in types",
+                    buildings_path.display(),
                     building.building_id
                 );
             }
         }
 
-        let buildings = raw_buildings
+        let (lines, buildings) = raw_buildings
             .into_iter()
-            .filter(|b| types.contains(&b.building_id))
-            .collect();
+            .filter(|(_line, b)| types.contains(&b.building_id))
+            .unzip();
 
-        Ok(Self { types, buildings })
+        Ok(Self {
+            types,
+            buildings,
+            lines,
+        })
     }
 }
 
@@ -104,7 +130,7 @@ mod tests {
     fn it_reads_buildings_from_files() {
         let types_path = Path::new("./test/common/buildings/00_buildings.txt");
         let buildings_path = Path::new("./test/map/buildings.txt");
-        let buildings = Buildings::from_files(types_path, buildings_path)
+        let buildings = Buildings::from_files(types_path, buildings_path, CsvParsing::Strict, None)
             .expect("Failed to read building types");
         assert_eq!(buildings.types.len(), 17);
         assert!(buildings
@@ -120,5 +146,7 @@ mod tests {
         assert!((buildings.buildings[12].z - 1559.0_f32).abs() < f32::EPSILON);
         assert!((buildings.buildings[12].rotation - -3.93_f32).abs() < f32::EPSILON);
         assert_eq!(buildings.buildings[12].adjacent_sea_province, ProvinceId(0));
+        assert_eq!(buildings.buildings[12].sea_province(), None);
+        assert_eq!(buildings.lines[12], 13);
     }
 }
@@ -1,6 +1,9 @@
 use crate::components::prelude::*;
+use crate::format::{Block, FileFormat, FormatValue};
+use crate::MapError;
 use jomini::JominiDeserialize;
 use serde::Serialize;
+use std::fs;
 use std::path::Path;
 
 /// The graphical information for depicting large cities on the map.
@@ -18,6 +21,33 @@ pub struct Cities {
     pub city_group: Vec<CityGroup>,
 }
 
+impl Cities {
+    /// Saves the cities to a file in the format of cities.txt
+    /// # Errors
+    /// * If the file could not be written
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        let mut root = Block::new(&["types_source", "pixel_step_x", "pixel_step_y", "city_group"]);
+        root.push(
+            "types_source",
+            FormatValue::Token(format!("\"{}\"", self.types_source.display())),
+        );
+        root.push(
+            "pixel_step_x",
+            FormatValue::Token(self.pixel_step_x.to_string()),
+        );
+        root.push(
+            "pixel_step_y",
+            FormatValue::Token(self.pixel_step_y.to_string()),
+        );
+        for group in &self.city_group {
+            root.push("city_group", FormatValue::Block(group.to_block()));
+        }
+        fs::write(path, FileFormat::default().write(&root))?;
+        Ok(())
+    }
+}
+
 /// A city group
 #[derive(Debug, Clone, JominiDeserialize, Serialize)]
 #[non_exhaustive]
@@ -31,6 +61,31 @@ pub struct CityGroup {
     pub building: Vec<BuildingMesh>,
 }
 
+impl CityGroup {
+    /// Converts the city group into the keys and values of its block
+    fn to_block(&self) -> Block {
+        let mut block = Block::new(&["color_index", "density", "building"]);
+        block.push(
+            "color_index",
+            FormatValue::Token(self.color_index.to_string()),
+        );
+        block.push("density", FormatValue::Token(self.density.to_string()));
+        for building in &self.building {
+            let mut building_block = Block::new(&["distance", "mesh"]);
+            building_block.push(
+                "distance",
+                FormatValue::Token(building.distance.to_string()),
+            );
+            building_block.push(
+                "mesh",
+                FormatValue::List(building.mesh.iter().map(|m| format!("\"{m}\"")).collect()),
+            );
+            block.push("building", FormatValue::Block(building_block));
+        }
+        block
+    }
+}
+
 /// The meshes to use for an urban area
 #[derive(Debug, Clone, JominiDeserialize, Serialize)]
 #[non_exhaustive]
@@ -51,10 +106,35 @@ mod tests {
     use super::*;
     use crate::LoadObject;
 
+    #[test]
+    fn it_saves_cities_to_a_file() {
+        let cities = Cities {
+            types_source: Path::new("map/cities.bmp").into(),
+            pixel_step_x: PixelStep(2),
+            pixel_step_y: PixelStep(2),
+            city_group: vec![CityGroup {
+                color_index: ColorIndex(1),
+                density: PixelDensity(0.5),
+                building: vec![BuildingMesh {
+                    distance: Distance(3.0),
+                    mesh: vec![MeshId("western_citiy_1_entity".to_owned())],
+                }],
+            }],
+        };
+        let path = std::env::temp_dir().join("world_gen_cities_test.txt");
+        cities.save(&path).expect("Failed to save cities");
+        let text = std::fs::read_to_string(&path).expect("Failed to read cities");
+        std::fs::remove_file(&path).expect("Failed to remove cities");
+        assert!(text.starts_with("types_source = \"map/cities.bmp\"\n"));
+        assert!(text.contains("pixel_step_y = 2\n"));
+        assert!(text.contains("\tcolor_index = 1\n\tdensity = 0.5\n"));
+        assert!(text.contains("\t\tdistance = 3\n\t\tmesh = { \"western_citiy_1_entity\" }\n"));
+    }
+
     #[test]
     fn it_loads_cities_from_a_file() {
         let cities_path = Path::new("./test/map/cities.txt");
-        let cities = Cities::load_object(&cities_path).expect("Failed to read cities");
+        let cities = Cities::load_object(&cities_path, None).expect("Failed to read cities");
         assert_eq!(
             cities.types_source.to_path_buf(),
             Path::new("map/cities.bmp").to_path_buf()
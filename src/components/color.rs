@@ -30,7 +30,7 @@ mod tests {
     #[test]
     fn it_loads_colors_from_file() {
         let colors_path = Path::new("./test/map/colors.txt");
-        let colors = Colors::load_object(&colors_path).expect("Failed to read colors");
+        let colors = Colors::load_object(&colors_path, None).expect("Failed to read colors");
         assert_eq!(colors.color.len(), 200);
         assert_eq!(colors.color[0], Color(Red(4), Green(144), Blue(178)));
         assert_eq!(colors.color[75], Color(Red(107), Green(170), Blue(77)));
@@ -1,6 +1,9 @@
 use crate::components::wrappers::Continent;
+use crate::MapError;
 use jomini::JominiDeserialize;
 use serde::Serialize;
+use std::fs;
+use std::path::Path;
 
 /// The list of continents
 #[derive(Debug, Clone, JominiDeserialize, Serialize)]
@@ -10,6 +13,22 @@ pub struct Continents {
     pub continents: Vec<Continent>,
 }
 
+impl Continents {
+    /// Writes the continents to the given path in the format of continent.txt
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        let names = self
+            .continents
+            .iter()
+            .map(|c| format!("\t{c}\n"))
+            .collect::<String>();
+        fs::write(path, format!("continents = {{\n{names}}}\n"))?;
+        Ok(())
+    }
+}
+
 #[allow(clippy::expect_used)]
 #[allow(clippy::indexing_slicing)]
 #[cfg(test)]
@@ -17,16 +36,15 @@ mod tests {
     use super::*;
     use crate::components::default_map::DefaultMap;
     use crate::{append_dir, LoadObject};
-    use std::path::Path;
 
     #[test]
     fn it_reads_continents_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let continents_path =
             append_dir(&map.continent, "./test/map").expect("Failed to find continents");
         let continents =
-            Continents::load_object(&continents_path).expect("Failed to read continents");
+            Continents::load_object(&continents_path, None).expect("Failed to read continents");
         assert_eq!(continents.continents.len(), 6);
         assert_eq!(continents.continents[0], Continent("west_coast".to_owned()));
         assert_eq!(
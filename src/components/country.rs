@@ -0,0 +1,101 @@
+use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
+use crate::legend::hsv_to_rgb;
+use crate::{parse_error, ErrorContext, MapError};
+use image::Rgb;
+use jomini::text::ValueReader;
+use jomini::{TextTape, Utf8Encoding};
+use log::warn;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The colors of the countries from `common/countries/colors.txt`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CountryColors {
+    /// The map color of each country
+    pub colors: HashMap<CountryTag, Rgb<u8>>,
+}
+
+impl CountryColors {
+    /// Loads the colors of the countries from a given path
+    /// # Errors
+    /// If the file cannot be read, or if it is invalid
+    #[inline]
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        Self::from_text(path, &data).in_file(path)
+    }
+
+    /// Reads the colors of the countries from the text of their file.  Each country has a block
+    /// with a `color` given as `rgb { 0 0 0 }`, as `hsv { 0.0 0.0 0.0 }` or as a bare list of
+    /// red, green and blue.  Countries with a color that cannot be read are left out.
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        let mut colors = HashMap::new();
+        for (tag, _op, value) in reader.fields() {
+            let tag = CountryTag(tag.read_string());
+            let color = value
+                .read_object()?
+                .fields()
+                .find(|(key, _op, _value)| key.read_str() == "color")
+                .and_then(|(_key, _op, color)| read_color(&color));
+            match color {
+                Some(color) => {
+                    colors.insert(tag, color);
+                }
+                None => warn!("No color could be read for country {tag}"),
+            }
+        }
+        Ok(Self { colors })
+    }
+}
+
+/// Reads a color given as `rgb { 0 0 0 }`, `hsv { 0.0 0.0 0.0 }` or `{ 0 0 0 }`
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn read_color(value: &ValueReader<'_, '_, Utf8Encoding>) -> Option<Rgb<u8>> {
+    // A header such as `rgb` is read as an array of the header and the values after it
+    let values = value.read_array().ok()?.values().collect::<Vec<_>>();
+    let (header, components) = match values.as_slice() {
+        [header, components] => (header.read_string().ok()?, components.read_array().ok()?),
+        _ => ("rgb".to_owned(), value.read_array().ok()?),
+    };
+    let components = components
+        .values()
+        .map(|c| c.read_string().ok()?.parse::<f64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let [a, b, c] = <[f64; 3]>::try_from(components).ok()?;
+    match header.as_str() {
+        "hsv" | "HSV" => Some(hsv_to_rgb(a.fract(), b, c)),
+        _ => {
+            let channel = |v: f64| v.round().clamp(0.0, 255.0) as u8;
+            Some(Rgb([channel(a), channel(b), channel(c)]))
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_rgb_and_hsv_country_colors() {
+        let data = "GER = {\n\tcolor = rgb { 85 92 74 }\n\tcolor_ui = rgb { 110 120 96 }\n}\n\
+                    ENG = {\n\tcolor = hsv { 0.0 1.0 1.0 }\n}\n\
+                    SOV = {\n\tcolor = { 160 20 20 }\n}\n\
+                    BAD = {\n\tcolor_ui = rgb { 1 2 3 }\n}\n";
+        let colors =
+            CountryColors::from_text(Path::new("colors.txt"), data).expect("Failed to read colors");
+        let tag = |t: &str| CountryTag(t.to_owned());
+        assert_eq!(colors.colors[&tag("GER")], Rgb([85, 92, 74]));
+        assert_eq!(colors.colors[&tag("ENG")], Rgb([255, 0, 0]));
+        assert_eq!(colors.colors[&tag("SOV")], Rgb([160, 20, 20]));
+        assert!(!colors.colors.contains_key(&tag("BAD")));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{parse_error, ErrorContext, MapError};
+use jomini::TextTape;
+use log::warn;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// The history of a country from `history/countries`, as it stands at the start of the game
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CountryHistory {
+    /// The tag of the country, from the start of the name of its file
+    pub tag: CountryTag,
+    /// The state the capital of the country is in
+    pub capital: Option<StateId>,
+}
+
+/// The histories of the countries of the `history/countries/*.txt` files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CountryHistories {
+    /// Each country by its tag
+    pub countries: HashMap<CountryTag, CountryHistory>,
+}
+
+impl CountryHistories {
+    /// Loads the histories of every `.txt` file in a directory.  The tag of a country is the part
+    /// of the name of its file before the first `-`, as in `GER - Germany.txt`.  The files are
+    /// read in order of their names, so a country with two files takes the history of the later
+    /// one, as in the game.
+    /// # Errors
+    /// If the directory or one of its files cannot be read, or if a file is invalid
+    #[inline]
+    pub fn from_dir(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).in_file(path)? {
+            let file = entry?.path();
+            if file.extension().map_or(false, |e| e == "txt") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        let mut histories = Self::default();
+        for file in files {
+            let tag = file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.split('-').next())
+                .map(str::trim)
+                .filter(|t| !t.is_empty());
+            let tag = match tag {
+                Some(tag) => CountryTag(tag.to_owned()),
+                None => {
+                    warn!("No country tag in the name of {}", file.display());
+                    continue;
+                }
+            };
+            let data = read_text(&file, encoding).in_file(&file)?;
+            let history = CountryHistory::from_text(&file, tag, &data).in_file(&file)?;
+            histories.countries.insert(history.tag.clone(), history);
+        }
+        Ok(histories)
+    }
+
+    /// Whether a country has a history
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, tag: &CountryTag) -> bool {
+        self.countries.contains_key(tag)
+    }
+
+    /// The states each country owns at the start of the game, from the histories of the states.
+    /// Countries with a history but no states are listed without any.
+    #[inline]
+    #[must_use]
+    pub fn owned_states<'tag>(
+        &'tag self,
+        states: &'tag HashMap<StateId, State>,
+    ) -> BTreeMap<&'tag CountryTag, Vec<StateId>> {
+        let mut owned: BTreeMap<&CountryTag, Vec<StateId>> =
+            self.countries.keys().map(|tag| (tag, Vec::new())).collect();
+        for state in states.values() {
+            if let Some(history) = &state.history {
+                owned.entry(&history.owner).or_default().push(state.id);
+            }
+        }
+        for ids in owned.values_mut() {
+            ids.sort_unstable();
+        }
+        owned
+    }
+}
+
+impl CountryHistory {
+    /// Reads the history of a country from the text of its file.  Only the `capital` at the root
+    /// of the file is read, as the blocks under a date only apply once the game reaches it.
+    fn from_text(path: &Path, tag: CountryTag, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        let mut capital = None;
+        for (key, _op, value) in reader.fields() {
+            if key.read_str() == "capital" {
+                capital = value.read_string().ok().and_then(|s| s.parse().ok());
+                if capital.is_none() {
+                    warn!("No capital could be read for country {tag}");
+                }
+            }
+        }
+        Ok(Self { tag, capital })
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::state::StateHistory;
+    use std::collections::HashSet;
+
+    #[test]
+    fn it_reads_the_capital_at_the_start_of_the_game() {
+        let data = "capital = 64\n\
+                    oob = \"GER_1936\"\n\
+                    set_politics = {\n\truling_party = fascism\n}\n\
+                    1939.1.1 = {\n\tcapital = 65\n}\n";
+        let tag = CountryTag("GER".to_owned());
+        let history = CountryHistory::from_text(Path::new("GER - Germany.txt"), tag, data)
+            .expect("Failed to read country history");
+        assert_eq!(history.tag, CountryTag("GER".to_owned()));
+        assert_eq!(history.capital, Some(StateId(64)));
+    }
+
+    #[test]
+    fn it_lists_the_states_of_each_country() {
+        let state = |id: i32, owner: &str| State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: Some(StateHistory {
+                owner: CountryTag(owner.to_owned()),
+                controller: None,
+                victory_points: Vec::new(),
+            }),
+            provinces: HashSet::new(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        };
+        let states = HashMap::from([
+            (StateId(3), state(3, "GER")),
+            (StateId(1), state(1, "GER")),
+            (StateId(2), state(2, "XXX")),
+        ]);
+        let tag = |t: &str| CountryTag(t.to_owned());
+        let histories = CountryHistories {
+            countries: ["GER", "POL"]
+                .into_iter()
+                .map(|t| {
+                    let history = CountryHistory {
+                        tag: tag(t),
+                        capital: None,
+                    };
+                    (tag(t), history)
+                })
+                .collect(),
+        };
+        let owned = histories.owned_states(&states);
+        assert_eq!(owned[&tag("GER")], vec![StateId(1), StateId(3)]);
+        assert_eq!(owned[&tag("XXX")], vec![StateId(2)]);
+        assert!(owned[&tag("POL")].is_empty());
+        assert!(histories.contains(&tag("POL")));
+        assert!(!histories.contains(&tag("XXX")));
+    }
+}
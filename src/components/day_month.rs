@@ -5,6 +5,12 @@ use std::fmt::{Debug, Display, Formatter};
 use std::num::ParseIntError;
 use std::str::FromStr;
 
+/// The zero-indexed last day of each month, as the game has no leap years
+pub const LAST_DAYS: [u8; 12] = [30, 27, 30, 29, 30, 29, 30, 30, 29, 30, 29, 30];
+
+/// The number of days in a year of the game
+pub const DAYS_IN_YEAR: u16 = 365;
+
 /// Zero-indexed day of the month (0-30) and month of the year (0-11).
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 #[non_exhaustive]
@@ -15,6 +21,41 @@ pub struct DayMonth {
     pub month: u8,
 }
 
+impl DayMonth {
+    /// The zero-indexed day of the year, counting a day past the end of its month as the last day
+    /// of the month
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn day_of_year(self) -> u16 {
+        let months = LAST_DAYS.iter().take(usize::from(self.month));
+        let before = months.map(|last| u16::from(*last) + 1).sum::<u16>();
+        let last = LAST_DAYS
+            .get(usize::from(self.month))
+            .copied()
+            .unwrap_or_default();
+        before + u16::from(self.day.min(last))
+    }
+
+    /// The day and month of a zero-indexed day of the year, or `None` past the end of the year
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn from_day_of_year(day_of_year: u16) -> Option<Self> {
+        let mut day = day_of_year;
+        for (month, last) in (0_u8..).zip(LAST_DAYS) {
+            if day <= u16::from(last) {
+                return Some(Self {
+                    day: u8::try_from(day).ok()?,
+                    month,
+                });
+            }
+            day -= u16::from(last) + 1;
+        }
+        None
+    }
+}
+
 impl Display for DayMonth {
     #[inline]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -107,3 +148,24 @@ impl<'de> Deserialize<'de> for DayMonth {
         deserializer.deserialize_str(DayMonthVisitor)
     }
 }
+
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_the_days_of_the_year() {
+        let first = DayMonth { day: 0, month: 0 };
+        let leap = DayMonth { day: 28, month: 1 };
+        let last = DayMonth { day: 30, month: 11 };
+        assert_eq!(first.day_of_year(), 0);
+        assert_eq!(leap.day_of_year(), 58);
+        assert_eq!(last.day_of_year(), DAYS_IN_YEAR - 1);
+        for day in 0..DAYS_IN_YEAR {
+            let date = DayMonth::from_day_of_year(day);
+            assert_eq!(date.map(DayMonth::day_of_year), Some(day));
+        }
+        assert_eq!(DayMonth::from_day_of_year(DAYS_IN_YEAR), None);
+    }
+}
@@ -127,7 +127,7 @@ mod tests {
 
     #[test]
     fn it_reads_a_default_map_file() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read map");
         assert_eq!(
             map.definitions
@@ -185,7 +185,7 @@ mod tests {
 
     #[test]
     fn it_loads_provinces_bmp_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let provinces_bmp_path =
             append_dir(&map.provinces, "./test/map").expect("Failed to get path");
@@ -202,7 +202,7 @@ mod tests {
 
     #[test]
     fn it_reads_terrain_bmp_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let terrain_bmp_path = append_dir(&map.terrain, "./test/map").expect("Failed to get path");
         let terrain_bmp: DynamicImage =
@@ -218,7 +218,7 @@ mod tests {
 
     #[test]
     fn it_reads_rivers_bmp_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let rivers_bmp_path = append_dir(&map.rivers, "./test/map").expect("Failed to get path");
         let rivers_bmp: DynamicImage = open(&rivers_bmp_path).expect("Failed to read rivers.bmp");
@@ -233,7 +233,7 @@ mod tests {
 
     #[test]
     fn it_reads_heightmap_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let heightmap_bmp_path =
             append_dir(&map.heightmap, "./test/map").expect("Failed to get path");
@@ -250,7 +250,7 @@ mod tests {
 
     #[test]
     fn it_reads_trees_bmp_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let tree_bmp_path =
             append_dir(&map.tree_definition, "./test/map").expect("Failed to get path");
@@ -0,0 +1,236 @@
+use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{ErrorContext, MapError};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The language of the localisation the editor loads
+pub const DEFAULT_LANGUAGE: &str = "english";
+
+/// The byte order mark the game expects at the start of a localisation file
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// The text of the localisation keys of a language, from the `localisation/**/*_l_<language>.yml`
+/// files.  The names of states such as `STATE_1` and of victory points such as
+/// `VICTORY_POINTS_11506` are keys into it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Localisation {
+    /// The language of the files, such as `english`
+    pub language: String,
+    /// The text of each key
+    pub entries: HashMap<String, String>,
+    /// The keys held by the file of the editor, those edited in the editor and those read back
+    /// from the file, along with their text
+    pub edits: BTreeMap<String, String>,
+    /// Whether there are edits that have not been written to the file of the editor
+    pub unsaved: bool,
+}
+
+impl Default for Localisation {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            language: DEFAULT_LANGUAGE.to_owned(),
+            entries: HashMap::new(),
+            edits: BTreeMap::new(),
+            unsaved: false,
+        }
+    }
+}
+
+impl Localisation {
+    /// Loads the localisation of a language from the `localisation` directory of the root
+    /// directory and the directories within it.  As in the game, the files in a `replace`
+    /// directory are read last so that their keys take the place of the others.  A missing
+    /// `localisation` directory has no keys.
+    /// # Errors
+    /// * If a directory or file of the localisation cannot be read
+    #[inline]
+    pub fn load(
+        root_path: &Path,
+        language: &str,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let mut localisation = Self {
+            language: language.to_owned(),
+            ..Self::default()
+        };
+        let edits_path = localisation.edits_path(root_path);
+        let suffix = format!("_l_{language}.yml");
+        let mut files = Vec::new();
+        let mut directories = vec![root_path.join("localisation")];
+        while let Some(directory) = directories.pop() {
+            if !directory.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&directory).in_file(&directory)? {
+                let path = entry?.path();
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if path.is_dir() {
+                    directories.push(path);
+                } else if name.ends_with(&suffix) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort_by_key(|path| {
+            (
+                path.components().any(|c| c.as_os_str() == "replace"),
+                path.clone(),
+            )
+        });
+        for path in files {
+            let data = read_text(&path, encoding).in_file(&path)?;
+            let entries = parse_localisation(&data);
+            if path == edits_path {
+                localisation.edits.extend(entries.iter().cloned());
+            }
+            localisation.entries.extend(entries);
+        }
+        Ok(localisation)
+    }
+
+    /// The text of a key, if it has one
+    #[inline]
+    #[must_use]
+    pub fn text(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(String::as_str)
+    }
+
+    /// Sets the text of a key, adding the key if it is new, to be written by `save`
+    #[inline]
+    pub fn set_text(&mut self, key: String, text: String) {
+        self.entries.insert(key.clone(), text.clone());
+        self.edits.insert(key, text);
+        self.unsaved = true;
+    }
+
+    /// Writes the edited keys to the file of the editor,
+    /// `localisation/<language>/replace/world_gen_l_<language>.yml`, along with the keys already
+    /// in it.  Being in a `replace` directory, its keys take the place of those of the game.
+    /// Nothing is written without unsaved edits.
+    /// # Errors
+    /// * If the file cannot be written
+    #[inline]
+    pub fn save(&mut self, root_path: &Path) -> Result<(), MapError> {
+        if !self.unsaved {
+            return Ok(());
+        }
+        let path = self.edits_path(root_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut data = format!("{BYTE_ORDER_MARK}l_{}:\n", self.language);
+        for (key, text) in &self.edits {
+            let _result = writeln!(data, " {key}:0 \"{}\"", text.replace('"', "\\\""));
+        }
+        fs::write(&path, data).in_file(&path)?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    /// The file the edited keys are written to
+    fn edits_path(&self, root_path: &Path) -> PathBuf {
+        root_path
+            .join("localisation")
+            .join(&self.language)
+            .join("replace")
+            .join(format!("world_gen_l_{}.yml", self.language))
+    }
+}
+
+/// The localisation key of the name of the victory points of a province
+#[inline]
+#[must_use]
+pub fn victory_points_key(province: ProvinceId) -> String {
+    format!("VICTORY_POINTS_{province}")
+}
+
+/// Reads the keys and text of a localisation file.  The files are not quite YAML: each line after
+/// the `l_<language>:` header is a key, an optional version number and quoted text, as in
+/// ` STATE_1:0 "Corsica"`, and the text may hold quotes of its own.  The text runs from the first
+/// quote to the last, and escaped quotes within it are unescaped.  Lines that are not keys, such
+/// as comments, are left out.
+fn parse_localisation(data: &str) -> Vec<(String, String)> {
+    data.lines()
+        .filter_map(|line| {
+            let line = line.trim_start_matches(BYTE_ORDER_MARK).trim();
+            if line.starts_with('#') {
+                return None;
+            }
+            let (key, rest) = line.split_once(':')?;
+            let quoted = rest
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start()
+                .strip_prefix('"')?;
+            let text = quoted.get(..quoted.rfind('"')?)?;
+            Some((key.trim().to_owned(), text.replace("\\\"", "\"")))
+        })
+        .collect()
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_keys_of_a_localisation_file() {
+        let data = "\u{feff}l_english:\n \
+                    # The states of France\n \
+                    STATE_1:0 \"Corsica\"\n \
+                    STATE_16: \"Île-de-France\" # Paris\n \
+                    VICTORY_POINTS_11506:0 \"The \\\"City of Light\\\"\"\n \
+                    BROKEN_KEY:0 missing quotes\n";
+        let entries = parse_localisation(data)
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries["STATE_1"], "Corsica");
+        assert_eq!(entries["STATE_16"], "Île-de-France");
+        assert_eq!(
+            entries[&victory_points_key(ProvinceId(11506))],
+            "The \"City of Light\""
+        );
+    }
+
+    #[test]
+    fn it_writes_edits_that_replace_the_game_keys() {
+        let root = std::env::temp_dir().join("world_gen_localisation_test");
+        let _result = fs::remove_dir_all(&root);
+        let english = root.join("localisation/english");
+        fs::create_dir_all(&english).expect("Failed to create directories");
+        fs::write(
+            english.join("state_names_l_english.yml"),
+            "l_english:\n STATE_1:0 \"Corsica\"\n STATE_2:0 \"Sardinia\"\n",
+        )
+        .expect("Failed to write localisation");
+        fs::write(
+            english.join("state_names_l_french.yml"),
+            "l_french:\n STATE_1:0 \"Corse\"\n",
+        )
+        .expect("Failed to write localisation");
+
+        let mut localisation =
+            Localisation::load(&root, DEFAULT_LANGUAGE, None).expect("Failed to load localisation");
+        assert_eq!(localisation.text("STATE_1"), Some("Corsica"));
+        localisation.set_text("STATE_1".to_owned(), "Corse \"du Sud\"".to_owned());
+        localisation.set_text("STATE_3".to_owned(), "Elba".to_owned());
+        localisation
+            .save(&root)
+            .expect("Failed to save localisation");
+        assert!(!localisation.unsaved);
+
+        let loaded =
+            Localisation::load(&root, DEFAULT_LANGUAGE, None).expect("Failed to load localisation");
+        fs::remove_dir_all(&root).expect("Failed to remove directory");
+        assert_eq!(loaded.text("STATE_1"), Some("Corse \"du Sud\""));
+        assert_eq!(loaded.text("STATE_2"), Some("Sardinia"));
+        assert_eq!(loaded.text("STATE_3"), Some("Elba"));
+        assert_eq!(loaded.edits, localisation.edits);
+    }
+}
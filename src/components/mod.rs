@@ -2,6 +2,8 @@
 pub mod adjacency;
 /// Holds the airports
 pub mod airport;
+/// Holds the ambient objects
+pub mod ambient_object;
 /// Holds the buildings
 pub mod building;
 /// Holds the cities
@@ -10,28 +12,42 @@ pub mod city;
 pub mod color;
 /// Holds the continents
 pub mod continent;
+/// Holds the colors of the countries
+pub mod country;
+/// Holds the histories of the countries
+pub mod country_history;
 /// Holds the `DayMonth`
 pub mod day_month;
 /// Holds the default.map information
 pub mod default_map;
+/// Holds the localisation of the names of states and victory points
+pub mod localisation;
 /// Holds the public exports for the prelude
 pub mod prelude;
 /// Holds the province definitions
 pub mod province;
 /// Holds the railways
 pub mod railway;
+/// Holds the rivers palette
+pub mod river;
 /// Holds the rocket sites
 pub mod rocket_site;
 /// Holds the seasons
 pub mod season;
 /// Holds the states
 pub mod state;
+/// Holds the state categories
+pub mod state_category;
 /// Holds the strategic regions
 pub mod strategic_region;
+/// Holds the supply areas of maps from before 1.11
+pub mod supply_area;
 /// Holds the supply nodes
 pub mod supply_node;
 /// Holds the unit stacks
 pub mod unit_stack;
+/// Holds the weather states
+pub mod weather;
 /// Holds the weather positions
 pub mod weather_position;
 /// Holds wrappers around primitive types
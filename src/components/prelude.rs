@@ -8,6 +8,7 @@ pub use super::day_month::*;
 pub use super::default_map::*;
 pub use super::province::*;
 pub use super::railway::*;
+pub use super::river::*;
 pub use super::rocket_site::*;
 pub use super::season::*;
 pub use super::strategic_region::*;
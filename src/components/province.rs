@@ -1,7 +1,12 @@
 use crate::components::wrappers::{Blue, Coastal, ContinentIndex, Green, ProvinceId, Red, Terrain};
-use crate::{LoadCsv, LoadKeys, MapError};
+use crate::encoding::{read_text, TextEncoding};
+use crate::validation::report::{Finding, FindingKind, Severity};
+use crate::{parse_error, text_files, CsvParsing, ErrorContext, LoadCsv, LoadKeys, MapError};
+use jomini::TextTape;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
 use std::path::Path;
 
 /// An entry in the definitions file.
@@ -51,61 +56,230 @@ pub struct Definitions {
     pub terrain: HashSet<Terrain>,
 }
 
+/// Loads the terrain types of a terrain file, or of every `.txt` file of `common/terrain`, that
+/// are water, being marked with `is_water` or `naval_terrain`.  A category defined again in a
+/// later file takes the place of the earlier one, as in the game.
+/// # Errors
+/// If a file cannot be read, or if it is not a valid terrain file
+#[inline]
+pub fn load_water_terrain(
+    terrain_path: &Path,
+    encoding: Option<TextEncoding>,
+) -> Result<HashSet<Terrain>, MapError> {
+    let mut categories = HashMap::new();
+    for file in text_files(terrain_path)? {
+        let data = read_text(&file, encoding).in_file(&file)?;
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, &file, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        for (key, _op, value) in reader.fields() {
+            if key.read_str() != "categories" {
+                continue;
+            }
+            for (category, _op, properties) in value.read_object().in_file(&file)?.fields() {
+                let is_water = properties.read_object().map_or(false, |p| {
+                    p.fields().any(|(property, _op, value)| {
+                        matches!(property.read_str().as_ref(), "is_water" | "naval_terrain")
+                            && value.read_str().map_or(false, |v| v == "yes")
+                    })
+                });
+                categories.insert(Terrain(category.read_string()), is_water);
+            }
+        }
+    }
+    Ok(categories
+        .into_iter()
+        .filter(|(_category, is_water)| *is_water)
+        .map(|(category, _is_water)| category)
+        .collect())
+}
+
 impl Definitions {
-    /// Load the definitions from the given path.
+    /// Load the definitions from the given path, with the terrain of a terrain file or of every
+    /// `.txt` file of `common/terrain`.  The rows that cannot be read are handled as `parsing`
+    /// says.
     /// # Errors
     /// If the file cannot be read, or if the file is not a valid csv file, then an error is returned.
     #[inline]
-    pub fn from_files(definitions_path: &Path, terrain_path: &Path) -> Result<Self, MapError> {
-        let definitions = Definition::load_csv(definitions_path, false)?
+    pub fn from_files(
+        definitions_path: &Path,
+        terrain_path: &Path,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let definitions = Definition::load_csv(definitions_path, false, parsing, encoding)?
             .into_iter()
             .map(|definition| (definition.id, definition))
             .collect();
-        let terrain = Terrain::load_keys(terrain_path, "categories")?;
+        let terrain = Terrain::load_keys(terrain_path, "categories", encoding)?;
         Ok(Self {
             definitions,
             terrain,
         })
     }
 
-    /// Verifies the province terrain types against the `common/terrain/00_terrain.txt` file
+    /// Writes the definitions to the given path in the format of definition.csv, in order of
+    /// province id
     /// # Errors
-    /// * If the provinces contain terrain not defined in the `common/terrain/00_terrain.txt` file
+    /// If the file cannot be written.
     #[inline]
-    pub fn verify_province_terrain(&self) -> Result<(), Vec<MapError>> {
-        let errors = self
-            .definitions
-            .iter()
-            .filter(|(_id, def)| !self.terrain.contains(&def.terrain))
-            .map(|(_id, def)| MapError::InvalidProvinceTerrain(def.clone()))
-            .collect::<Vec<_>>();
-        if !errors.is_empty() {
-            return Err(errors);
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        let mut ids = self.definitions.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let mut data = String::new();
+        for definition in ids.into_iter().filter_map(|id| self.definitions.get(id)) {
+            let province_type = match definition.province_type {
+                ProvinceType::Land => "land",
+                ProvinceType::Sea => "sea",
+                ProvinceType::Lake => "lake",
+            };
+            let _result = writeln!(
+                data,
+                "{};{};{};{};{province_type};{};{};{}",
+                definition.id,
+                definition.r,
+                definition.g,
+                definition.b,
+                definition.coastal,
+                definition.terrain,
+                definition.continent
+            );
         }
+        fs::write(path, data)?;
         Ok(())
     }
+
+    /// Verifies the province terrain types against the terrain of the `common/terrain` files,
+    /// returning a finding for each province with a terrain that is not defined there.  The
+    /// `0;0;0;0;land;false;unknown;0` row that starts the definitions is not a province, so its
+    /// terrain is not checked.
+    #[inline]
+    #[must_use]
+    pub fn verify_province_terrain(&self) -> Vec<Finding> {
+        self.definitions
+            .values()
+            .filter(|def| def.id.is_province() && !self.terrain.contains(&def.terrain))
+            .map(|def| {
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::UndefinedTerrain,
+                    format!("Province has the undefined terrain {}", def.terrain),
+                )
+                .in_province(def.id)
+                .with_fix("Give the province a terrain from common/terrain")
+            })
+            .collect()
+    }
+
+    /// Verifies that no two provinces share a color, returning a finding for each province whose
+    /// color is already used by a province with a lower id.
+    #[inline]
+    #[must_use]
+    pub fn verify_unique_colors(&self) -> Vec<Finding> {
+        let mut definitions = self.definitions.values().collect::<Vec<_>>();
+        definitions.sort_unstable_by_key(|d| d.id);
+        let mut first_ids = HashMap::new();
+        let mut findings = Vec::new();
+        for def in definitions {
+            let color = (def.r.0, def.g.0, def.b.0);
+            match first_ids.get(&color) {
+                Some(first) => findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::DuplicateProvinceColor,
+                        format!("Province has the same color {color:?} as province {first}"),
+                    )
+                    .in_province(def.id)
+                    .with_fix("Give the province a color of its own"),
+                ),
+                None => {
+                    first_ids.insert(color, def.id);
+                }
+            }
+        }
+        findings
+    }
 }
 
 #[allow(clippy::expect_used)]
 #[allow(clippy::indexing_slicing)]
-#[allow(clippy::panic)]
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::components::default_map::DefaultMap;
+    use crate::fixtures::DefinitionBuilder;
     use crate::{append_dir, LoadObject};
+    use image::Rgb;
     use std::path::Path;
 
+    #[test]
+    fn it_reads_the_water_terrain() {
+        let water = load_water_terrain(Path::new("./test/common/terrain/00_terrain.txt"), None)
+            .expect("Failed to read water terrain");
+        let mut water = water.into_iter().map(|t| t.0).collect::<Vec<_>>();
+        water.sort_unstable();
+        assert_eq!(
+            water,
+            vec![
+                "lakes",
+                "ocean",
+                "water_deep_ocean",
+                "water_fjords",
+                "water_shallow_sea"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_merges_the_terrain_files_of_a_directory() {
+        let dir = std::env::temp_dir().join("world_gen_terrain_dir_test");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create terrain directory");
+        fs::write(
+            dir.join("00_terrain.txt"),
+            "categories = {\n\tplains = { }\n\tlakes = { is_water = yes }\n}\n",
+        )
+        .expect("Failed to write terrain");
+        fs::write(
+            dir.join("01_custom.txt"),
+            "categories = {\n\tlakes = { }\n\tswamp_sea = { naval_terrain = yes }\n}\n",
+        )
+        .expect("Failed to write terrain");
+        fs::write(dir.join("02_graphics.txt"), "terrain = { }\n").expect("Failed to write");
+
+        let water = load_water_terrain(&dir, None).expect("Failed to read water terrain");
+        assert_eq!(water, HashSet::from([Terrain("swamp_sea".to_owned())]));
+        let terrain = Terrain::load_keys(&dir, "categories", None).expect("Failed to read terrain");
+        assert_eq!(terrain.len(), 3);
+        assert!(terrain.contains(&Terrain("plains".to_owned())));
+    }
+
+    #[test]
+    fn it_reads_water_terrain_written_in_windows_1252() {
+        let dir = std::env::temp_dir().join("world_gen_terrain_encoding_test");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create terrain directory");
+        fs::write(
+            dir.join("00_terrain.txt"),
+            b"# Mar\xe9cages\ncategories = {\n\tmarsh_sea = { is_water = yes }\n}\n",
+        )
+        .expect("Failed to write terrain");
+
+        let water = load_water_terrain(&dir, None).expect("Failed to read water terrain");
+        assert_eq!(water, HashSet::from([Terrain("marsh_sea".to_owned())]));
+    }
+
     #[test]
     fn it_reads_definitions_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read map");
         let definitions_path = map.definitions.to_path_buf();
         let definitions_path =
             append_dir(&definitions_path, "./test/map").expect("Failed to find definitions");
         let terrain_path = Path::new("./test/common/terrain/00_terrain.txt");
-        let definitions = Definitions::from_files(&definitions_path, terrain_path)
-            .expect("Failed to read definitions");
+        let definitions =
+            Definitions::from_files(&definitions_path, terrain_path, CsvParsing::Strict, None)
+                .expect("Failed to read definitions");
         assert_eq!(definitions.definitions.len(), 17007);
         assert_eq!(
             definitions.definitions[&ProvinceId(0)].clone(),
@@ -138,19 +312,76 @@ mod tests {
 
     #[test]
     fn it_verifies_province_terrain() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read map");
         let definitions_path = map.definitions.to_path_buf();
         let definitions_path =
             append_dir(&definitions_path, "./test/map").expect("Failed to find definitions");
         let terrain_path = Path::new("./test/common/terrain/00_terrain.txt");
-        let definitions = Definitions::from_files(&definitions_path, terrain_path)
+        let definitions =
+            Definitions::from_files(&definitions_path, terrain_path, CsvParsing::Strict, None)
+                .expect("Failed to read definitions");
+        let findings = definitions.verify_province_terrain();
+        assert_eq!(findings.len(), 32);
+        assert!(findings
+            .iter()
+            .all(|f| f.kind == FindingKind::UndefinedTerrain && f.province.is_some()));
+        assert_eq!(definitions.verify_unique_colors(), Vec::new());
+    }
+
+    #[test]
+    fn it_skips_the_terrain_of_the_first_row() {
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (
+                    ProvinceId(0),
+                    DefinitionBuilder::new(0).terrain("unknown").build(),
+                ),
+                (
+                    ProvinceId(1),
+                    DefinitionBuilder::new(1).terrain("unknown").build(),
+                ),
+            ]),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        };
+        let findings = definitions.verify_province_terrain();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].province, Some(ProvinceId(1)));
+    }
+
+    #[test]
+    fn it_writes_definitions_that_can_be_read_back() {
+        let definition = |id: i32, province_type: ProvinceType, terrain: &str| {
+            let blue = u8::try_from(id).expect("Failed to convert id");
+            DefinitionBuilder::new(id)
+                .color(Rgb([10, 20, blue]))
+                .province_type(province_type)
+                .coastal(true)
+                .terrain(terrain)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(2), definition(2, ProvinceType::Sea, "ocean")),
+                (ProvinceId(1), definition(1, ProvinceType::Land, "plains")),
+                (ProvinceId(3), definition(3, ProvinceType::Lake, "lakes")),
+            ]),
+            terrain: HashSet::new(),
+        };
+        let path = std::env::temp_dir().join("world_gen_definitions_test.csv");
+        definitions
+            .to_file(&path)
+            .expect("Failed to write definitions");
+        let read = Definition::load_csv(&path, false, CsvParsing::Strict, None)
             .expect("Failed to read definitions");
-        if let Err(errors) = definitions.verify_province_terrain() {
-            println!("{:#?}", errors);
-            assert_eq!(errors.len(), 32);
-        } else {
-            panic!("Failed to detect invalid terrain in provinces");
-        }
+        fs::remove_file(&path).expect("Failed to remove definitions");
+        assert_eq!(
+            read,
+            vec![
+                definitions.definitions[&ProvinceId(1)].clone(),
+                definitions.definitions[&ProvinceId(2)].clone(),
+                definitions.definitions[&ProvinceId(3)].clone(),
+            ]
+        );
     }
 }
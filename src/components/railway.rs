@@ -1,5 +1,7 @@
 use crate::components::wrappers::{ProvinceId, RailLevel};
-use crate::MapError;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{ErrorContext, MapError};
+use serde::Serialize;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
@@ -16,7 +18,7 @@ use std::str::FromStr;
 /// are added together.  
 /// Rivers can act as supply routes, as long as there is a supply node (or port) in a province
 /// adjacent to the river.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 #[non_exhaustive]
 pub struct Railway {
     /// The level of the railway
@@ -58,7 +60,7 @@ impl FromStr for Railway {
 }
 
 /// The collection of railways on the map.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[non_exhaustive]
 pub struct Railways {
     /// The railways
@@ -70,11 +72,37 @@ impl Railways {
     /// # Errors
     /// If the file cannot be read, an error is returned.
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
-        let data = fs::read_to_string(path)?;
-        let railways = data.parse()?;
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        let railways = data.parse().in_file(path)?;
         Ok(railways)
     }
+
+    /// Writes the railways to the given path in the format of railways.txt
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        let data = self
+            .railways
+            .iter()
+            .map(|railway| {
+                let provinces = railway
+                    .provinces
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                format!(
+                    "{} {} {}\n",
+                    railway.level,
+                    railway.provinces.len(),
+                    provinces.join(" ")
+                )
+            })
+            .collect::<String>();
+        fs::write(path, data)?;
+        Ok(())
+    }
 }
 
 impl FromStr for Railways {
@@ -100,7 +128,23 @@ mod tests {
     #[test]
     fn it_reads_railways_from_a_file() {
         let path = Path::new("./test/map/railways.txt");
-        let railways = Railways::from_file(path).expect("Failed to read railways");
+        let railways = Railways::from_file(path, None).expect("Failed to read railways");
         assert_eq!(railways.railways.len(), 1520);
     }
+
+    #[test]
+    fn it_writes_railways_that_can_be_read_back() {
+        let railways = Railways {
+            railways: vec![Railway {
+                level: RailLevel(2),
+                length: 3,
+                provinces: vec![ProvinceId(10), ProvinceId(21), ProvinceId(32)],
+            }],
+        };
+        let path = std::env::temp_dir().join("world_gen_railways_test.txt");
+        railways.to_file(&path).expect("Failed to write railways");
+        let read = Railways::from_file(&path, None).expect("Failed to read railways");
+        fs::remove_file(&path).expect("Failed to remove railways");
+        assert_eq!(read.railways, railways.railways);
+    }
 }
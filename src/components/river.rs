@@ -0,0 +1,114 @@
+use image::Rgb;
+
+/// The colors of the rivers of each width, from narrowest to widest.
+const RIVER_WIDTH_COLORS: [[u8; 3]; 9] = [
+    [0, 225, 255],
+    [0, 200, 255],
+    [0, 155, 255],
+    [0, 100, 255],
+    [0, 0, 255],
+    [0, 0, 225],
+    [0, 0, 200],
+    [0, 0, 150],
+    [0, 0, 100],
+];
+
+/// The number of river widths in the rivers palette
+pub const RIVER_WIDTHS: u8 = 9;
+
+/// A color in the indexed palette of the rivers.bmp image.
+/// See [`DefaultMap::rivers`](crate::components::default_map::DefaultMap::rivers) for the
+/// meaning of each index.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RiverColor {
+    /// The source of a river, index 0
+    Source,
+    /// A flow-in source joining a river into another, index 1
+    FlowIn,
+    /// A flow-out source branching a river from another, index 2
+    FlowOut,
+    /// A river pixel with the given width, from 0 (narrowest, index 3) to 8 (widest, index 11)
+    River(u8),
+    /// Land without a river, index 255
+    Land,
+    /// Water without a river, index 254
+    Water,
+}
+
+impl RiverColor {
+    /// Gets the river color for a pixel of the rivers image, if it is in the palette.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn from_rgb(color: Rgb<u8>) -> Option<Self> {
+        match color.0 {
+            [0, 255, 0] => Some(Self::Source),
+            [255, 0, 0] => Some(Self::FlowIn),
+            [255, 252, 0] => Some(Self::FlowOut),
+            [255, 255, 255] => Some(Self::Land),
+            [122, 122, 122] => Some(Self::Water),
+            c => RIVER_WIDTH_COLORS
+                .iter()
+                .position(|w| *w == c)
+                .map(|width| Self::River(width as u8)),
+        }
+    }
+
+    /// Gets the pixel color of the river color.  Widths beyond the widest river are clamped.
+    #[inline]
+    #[must_use]
+    pub fn to_rgb(self) -> Rgb<u8> {
+        match self {
+            Self::Source => Rgb([0, 255, 0]),
+            Self::FlowIn => Rgb([255, 0, 0]),
+            Self::FlowOut => Rgb([255, 252, 0]),
+            Self::River(width) => {
+                let index = usize::from(width.min(RIVER_WIDTHS - 1));
+                Rgb(RIVER_WIDTH_COLORS
+                    .get(index)
+                    .copied()
+                    .unwrap_or([0, 0, 100]))
+            }
+            Self::Land => Rgb([255, 255, 255]),
+            Self::Water => Rgb([122, 122, 122]),
+        }
+    }
+
+    /// Whether the color is part of a river, including the markers
+    #[inline]
+    #[must_use]
+    pub const fn is_river(self) -> bool {
+        !matches!(self, Self::Land | Self::Water)
+    }
+
+    /// Whether the color is a source, flow-in or flow-out marker
+    #[inline]
+    #[must_use]
+    pub const fn is_marker(self) -> bool {
+        matches!(self, Self::Source | Self::FlowIn | Self::FlowOut)
+    }
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_the_rivers_palette() {
+        let mut colors = vec![
+            RiverColor::Source,
+            RiverColor::FlowIn,
+            RiverColor::FlowOut,
+            RiverColor::Land,
+            RiverColor::Water,
+        ];
+        colors.extend((0..RIVER_WIDTHS).map(RiverColor::River));
+        for color in colors {
+            assert_eq!(RiverColor::from_rgb(color.to_rgb()), Some(color));
+        }
+        assert_eq!(RiverColor::from_rgb(Rgb([1, 2, 3])), None);
+    }
+}
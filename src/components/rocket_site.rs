@@ -1,9 +1,11 @@
-use crate::{load_map, MapError, ProvinceId, StateId};
+use crate::encoding::TextEncoding;
+use crate::{load_map, save_map, MapError, ProvinceId, StateId};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::path::Path;
 
 /// The rocket sites on the map
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct RocketSites {
     /// The rocket sites by state
@@ -15,10 +17,18 @@ impl RocketSites {
     /// # Errors
     /// If the file cannot be read, or if it is invalid.
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
-        let rocket_sites = load_map(path)?;
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let rocket_sites = load_map(path, encoding)?;
         Ok(Self { rocket_sites })
     }
+
+    /// Writes the rocket sites to the given path in the format of rocketsites.txt
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        save_map(path, &self.rocket_sites)
+    }
 }
 
 #[allow(clippy::expect_used)]
@@ -30,7 +40,7 @@ mod tests {
 
     #[test]
     fn it_reads_the_rocket_sites_file() {
-        let rocket_sites = RocketSites::from_file(Path::new("./test/map/rocketsites.txt"))
+        let rocket_sites = RocketSites::from_file(Path::new("./test/map/rocketsites.txt"), None)
             .expect("Failed to read rocket_sites.txt");
         assert_eq!(rocket_sites.rocket_sites.len(), 1388);
         assert_eq!(
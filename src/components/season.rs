@@ -77,10 +77,10 @@ mod tests {
 
     #[test]
     fn it_loads_seasons_from_the_map() {
-        let map = DefaultMap::load_object(Path::new("./test/map/default.map"))
+        let map = DefaultMap::load_object(Path::new("./test/map/default.map"), None)
             .expect("Failed to read default.map");
         let seasons_path = append_dir(&map.seasons, "./test/map").expect("Failed to append dir");
-        let seasons = Seasons::load_object(&seasons_path).expect("Failed to read seasons");
+        let seasons = Seasons::load_object(&seasons_path, None).expect("Failed to read seasons");
         assert_eq!(
             seasons.winter,
             Season {
@@ -1,5 +1,7 @@
 use crate::components::prelude::*;
-use crate::{LoadObject, MapError};
+use crate::encoding::{read_text, TextEncoding};
+use crate::format::{Block, FileFormat, FormatValue, SourceFile};
+use crate::{ErrorContext, LoadObject, MapError};
 use jomini::JominiDeserialize;
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
@@ -12,22 +14,73 @@ use std::path::Path;
 pub struct States {
     /// The collection of states
     pub states: HashMap<StateId, State>,
+    /// The files the states were loaded from, used to keep their formatting when saving
+    pub sources: HashMap<StateId, SourceFile>,
+    /// The files defining a state id that an earlier file already defined, which are not loaded
+    pub duplicates: Vec<(StateId, String)>,
 }
 
 impl States {
-    /// Loads the states from the `history/states/` directory.
+    /// Loads the states from the `history/states/` directory in the order of their file names.
+    /// A file defining a state id already loaded from an earlier file is recorded as a duplicate
+    /// instead of replacing the earlier state.
     /// # Errors
     /// If the states directory does not exist, or if any of the states fail to load.
     #[inline]
-    pub fn from_dir(path: &Path) -> Result<Self, MapError> {
-        let state_files = fs::read_dir(path)?;
+    pub fn from_dir(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let mut state_files = fs::read_dir(path)
+            .in_file(path)?
+            .flatten()
+            .collect::<Vec<_>>();
+        state_files.sort_by_key(fs::DirEntry::file_name);
         let mut states = HashMap::new();
-        for state_file in state_files.flatten() {
-            let state_path = state_file.path();
-            let state = RawState::load_object(&state_path)?.state;
+        let mut sources = HashMap::new();
+        let mut duplicates = Vec::new();
+        for state_file in state_files {
+            let (state, source) = Self::load_file(&state_file.path(), encoding)?;
+            if states.contains_key(&state.id) {
+                duplicates.push((state.id, source.file_name));
+                continue;
+            }
+            sources.insert(state.id, source);
             states.insert(state.id, state);
         }
-        Ok(States { states })
+        Ok(States {
+            states,
+            sources,
+            duplicates,
+        })
+    }
+
+    /// Loads the state of a single file of the `history/states/` directory, along with the file it
+    /// was loaded from
+    /// # Errors
+    /// If the file cannot be read, or if the state fails to load.
+    #[inline]
+    pub fn load_file(
+        path: &Path,
+        encoding: Option<TextEncoding>,
+    ) -> Result<(State, SourceFile), MapError> {
+        let state = RawState::load_object(path, encoding)?.state;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let text = read_text(path, encoding).in_file(path)?;
+        Ok((state, SourceFile::new(file_name, FileFormat::detect(&text))))
+    }
+
+    /// Saves the states to the `history/states/` directory.  States loaded from a file are
+    /// written back to the same file in the format they were read in, with hand written keys and
+    /// comments kept.  New states are written to `<id>-State.txt`.
+    /// # Errors
+    /// If any of the state files fail to be written.
+    #[inline]
+    pub fn to_dir(&self, path: &Path) -> Result<(), MapError> {
+        for (id, state) in &self.states {
+            state.save(path, self.sources.get(id))?;
+        }
+        Ok(())
     }
 }
 
@@ -82,6 +135,134 @@ pub struct State {
     pub buildings_max_level_factor: Option<BuildingsMaxLevelFactor>,
 }
 
+impl State {
+    /// Saves the state to its file in the `history/states/` directory, keeping the formatting of
+    /// the file it was loaded from if there is one.
+    /// # Errors
+    /// If the file fails to be written.
+    #[inline]
+    pub fn save(&self, path: &Path, source: Option<&SourceFile>) -> Result<(), MapError> {
+        let file_name =
+            source.map_or_else(|| format!("{}-State.txt", self.id), |s| s.file_name.clone());
+        let default_format = FileFormat::default();
+        let format = source.map_or(&default_format, |s| &s.format);
+        let mut root = Block::new(&["state"]);
+        root.push("state", FormatValue::Block(self.to_block()));
+        fs::write(path.join(file_name), format.write(&root))?;
+        Ok(())
+    }
+
+    /// The manpower the state starts with, which is the last of its `manpower` entries
+    #[inline]
+    #[must_use]
+    pub fn current_manpower(&self) -> Option<Manpower> {
+        self.manpower.last().copied()
+    }
+
+    /// The category of the state, which is the last of its `state_category` entries
+    #[inline]
+    #[must_use]
+    pub fn current_category(&self) -> Option<&StateCategoryName> {
+        self.state_category.last()
+    }
+
+    /// Applies an edit to the state.  The duplicated entries of the edited key are replaced by a
+    /// single entry, so that the file no longer holds values the game ignores.
+    #[inline]
+    pub fn apply(&mut self, edit: StateEdit) {
+        match edit {
+            StateEdit::Manpower(manpower) => self.manpower = vec![manpower],
+            StateEdit::Category(category) => self.state_category = vec![category],
+        }
+    }
+
+    /// Sets the victory points of a province in the state's history, replacing every
+    /// `victory_points` block of the province in place, or removes them for `None`.
+    /// # Errors
+    /// * If the province does not belong to the state
+    /// * If the state has no history to hold the victory points
+    #[inline]
+    pub fn set_victory_points(
+        &mut self,
+        province: ProvinceId,
+        points: Option<VictoryPoints>,
+    ) -> Result<(), MapError> {
+        if !self.provinces.contains(&province) {
+            return Err(MapError::ProvinceNotInState(province, self.id));
+        }
+        let history = self
+            .history
+            .as_mut()
+            .ok_or(MapError::StateHistoryNotFound(self.id))?;
+        let position = history
+            .victory_points
+            .iter()
+            .position(|(p, _)| *p == province);
+        history.victory_points.retain(|(p, _)| *p != province);
+        if let Some(points) = points {
+            let index = position.unwrap_or(history.victory_points.len());
+            history.victory_points.insert(index, (province, points));
+        }
+        Ok(())
+    }
+
+    /// Converts the state into the keys and values of its file
+    fn to_block(&self) -> Block {
+        let mut block = Block::new(&[
+            "id",
+            "name",
+            "manpower",
+            "state_category",
+            "history",
+            "provinces",
+            "local_supplies",
+            "impassable",
+            "buildings_max_level_factor",
+        ]);
+        block.push("id", FormatValue::Token(self.id.to_string()));
+        block.push("name", FormatValue::Token(format!("\"{}\"", self.name)));
+        for manpower in &self.manpower {
+            block.push("manpower", FormatValue::Token(manpower.to_string()));
+        }
+        for category in &self.state_category {
+            block.push("state_category", FormatValue::Token(category.to_string()));
+        }
+        if let Some(history) = &self.history {
+            block.push("history", FormatValue::Block(history.to_block()));
+        }
+        let mut provinces = self.provinces.iter().collect::<Vec<_>>();
+        provinces.sort_unstable();
+        block.push(
+            "provinces",
+            FormatValue::Set(provinces.iter().map(ToString::to_string).collect()),
+        );
+        if let Some(supplies) = self.local_supplies {
+            block.push("local_supplies", FormatValue::Token(supplies.to_string()));
+        }
+        if let Some(impassable) = self.impassable {
+            let value = if impassable { "yes" } else { "no" };
+            block.push("impassable", FormatValue::Token(value.to_owned()));
+        }
+        if let Some(factor) = self.buildings_max_level_factor {
+            block.push(
+                "buildings_max_level_factor",
+                FormatValue::Token(factor.to_string()),
+            );
+        }
+        block
+    }
+}
+
+/// An edit of the values of a state shown in the editor.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateEdit {
+    /// Sets the manpower the state starts with
+    Manpower(Manpower),
+    /// Sets the category of the state
+    Category(StateCategoryName),
+}
+
 /// A state's history.
 #[derive(Debug, Clone, JominiDeserialize, Serialize)]
 #[non_exhaustive]
@@ -102,6 +283,25 @@ pub struct StateHistory {
     // TODO: State resources
 }
 
+impl StateHistory {
+    /// Converts the history into the keys and values of its block.  Keys such as buildings and
+    /// cores are not read, so they are kept as they were in the file.
+    fn to_block(&self) -> Block {
+        let mut block = Block::new(&["owner", "controller", "victory_points"]);
+        block.push("owner", FormatValue::Token(self.owner.to_string()));
+        if let Some(controller) = &self.controller {
+            block.push("controller", FormatValue::Token(controller.to_string()));
+        }
+        for (province, points) in &self.victory_points {
+            block.push(
+                "victory_points",
+                FormatValue::List(vec![province.to_string(), points.to_string()]),
+            );
+        }
+        block
+    }
+}
+
 #[allow(clippy::expect_used)]
 #[allow(clippy::unwrap_used)]
 #[cfg(test)]
@@ -111,7 +311,7 @@ mod tests {
 
     #[test]
     fn it_loads_a_state() {
-        let state = RawState::load_object(Path::new("./test/history/states/1-State.txt"))
+        let state = RawState::load_object(Path::new("./test/history/states/1-State.txt"), None)
             .expect("Failed to load state")
             .state;
 
@@ -149,10 +349,84 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_sets_victory_points_of_a_province_in_the_state() {
+        let mut state = RawState::load_object(Path::new("./test/history/states/1-State.txt"), None)
+            .expect("Failed to load state")
+            .state;
+        state
+            .set_victory_points(ProvinceId(951), Some(VictoryPoints(5.0)))
+            .expect("Failed to add victory points");
+        state
+            .set_victory_points(ProvinceId(2409), Some(VictoryPoints(30.0)))
+            .expect("Failed to edit victory points");
+        assert_eq!(
+            state.history.as_ref().unwrap().victory_points,
+            vec![
+                (ProvinceId(2409), VictoryPoints(30.0)),
+                (ProvinceId(951), VictoryPoints(5.0))
+            ]
+        );
+
+        state
+            .set_victory_points(ProvinceId(2409), None)
+            .expect("Failed to remove victory points");
+        assert_eq!(
+            state.history.as_ref().unwrap().victory_points,
+            vec![(ProvinceId(951), VictoryPoints(5.0))]
+        );
+        assert!(matches!(
+            state.set_victory_points(ProvinceId(1), Some(VictoryPoints(1.0))),
+            Err(MapError::ProvinceNotInState(ProvinceId(1), StateId(1)))
+        ));
+    }
+
+    #[test]
+    fn it_writes_a_victory_points_block_per_province() {
+        let mut state = RawState::load_object(Path::new("./test/history/states/1-State.txt"), None)
+            .expect("Failed to load state")
+            .state;
+        state
+            .set_victory_points(ProvinceId(951), Some(VictoryPoints(5.0)))
+            .expect("Failed to add victory points");
+        let path = std::env::temp_dir().join("world_gen_victory_points");
+        fs::create_dir_all(&path).expect("Failed to create directory");
+        state.save(&path, None).expect("Failed to save state");
+        let saved = RawState::load_object(&path.join("1-State.txt"), None)
+            .expect("Failed to load saved state")
+            .state;
+        fs::remove_dir_all(&path).expect("Failed to remove directory");
+
+        assert_eq!(
+            saved.history.unwrap().victory_points,
+            vec![
+                (ProvinceId(2409), VictoryPoints(25.0)),
+                (ProvinceId(951), VictoryPoints(5.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn it_replaces_duplicated_entries_when_edited() {
+        let mut state = RawState::load_object(Path::new("./test/history/states/1-State.txt"), None)
+            .expect("Failed to load state")
+            .state;
+        state.manpower.push(Manpower(1));
+        state.apply(StateEdit::Manpower(Manpower(30000)));
+        state.apply(StateEdit::Category(StateCategoryName("town".to_owned())));
+
+        assert_eq!(state.manpower, vec![Manpower(30000)]);
+        assert_eq!(state.current_manpower(), Some(Manpower(30000)));
+        assert_eq!(
+            state.current_category(),
+            Some(&StateCategoryName("town".to_owned()))
+        );
+    }
+
     #[test]
     fn it_loads_states() {
-        let states =
-            States::from_dir(Path::new("./test/history/states")).expect("Failed to load states");
+        let states = States::from_dir(Path::new("./test/history/states"), None)
+            .expect("Failed to load states");
         assert_eq!(states.states.len(), 1388);
     }
 }
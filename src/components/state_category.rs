@@ -0,0 +1,138 @@
+use crate::components::country::read_color;
+use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{parse_error, ErrorContext, MapError};
+use image::Rgb;
+use jomini::TextTape;
+use log::warn;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A category of state from `common/state_category`, such as `rural` or `megalopolis`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StateCategory {
+    /// The name of the category, which the `state_category` of a state refers to
+    pub name: StateCategoryName,
+    /// The number of shared building slots the states of the category have
+    pub building_slots: Option<u32>,
+    /// The color of the category on the map of state categories
+    pub color: Option<Rgb<u8>>,
+}
+
+/// The state categories of the `common/state_category/*.txt` files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StateCategories {
+    /// Each category by its name
+    pub categories: HashMap<StateCategoryName, StateCategory>,
+}
+
+impl StateCategories {
+    /// Loads the state categories of every `.txt` file in a directory.  The files are read in
+    /// order of their names, so a category defined again in a later file takes the place of the
+    /// earlier one, as in the game.
+    /// # Errors
+    /// If the directory or one of its files cannot be read, or if a file is invalid
+    #[inline]
+    pub fn from_dir(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).in_file(path)? {
+            let file = entry?.path();
+            if file.extension().map_or(false, |e| e == "txt") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        let mut categories = Self::default();
+        for file in files {
+            let data = read_text(&file, encoding).in_file(&file)?;
+            let loaded = Self::from_text(&file, &data).in_file(&file)?;
+            categories.categories.extend(loaded.categories);
+        }
+        Ok(categories)
+    }
+
+    /// The category of a name, if it is defined
+    #[inline]
+    #[must_use]
+    pub fn get(&self, name: &StateCategoryName) -> Option<&StateCategory> {
+        self.categories.get(name)
+    }
+
+    /// Reads the state categories from the text of a file.  Each category is a block within the
+    /// `state_categories` block, with its `local_building_slots` and `color`.  Categories whose
+    /// building slots or color cannot be read are kept without them.
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        let mut categories = HashMap::new();
+        for (key, _op, value) in reader.fields() {
+            if key.read_str() != "state_categories" {
+                continue;
+            }
+            for (name, _op, block) in value.read_object()?.fields() {
+                let name = StateCategoryName(name.read_string());
+                let mut category = StateCategory {
+                    name: name.clone(),
+                    building_slots: None,
+                    color: None,
+                };
+                for (field, _op, field_value) in block.read_object()?.fields() {
+                    match &*field.read_str() {
+                        "local_building_slots" => {
+                            category.building_slots =
+                                field_value.read_string().ok().and_then(|s| s.parse().ok());
+                            if category.building_slots.is_none() {
+                                warn!("No building slots could be read for state category {name}");
+                            }
+                        }
+                        "color" => {
+                            category.color = read_color(&field_value);
+                            if category.color.is_none() {
+                                warn!("No color could be read for state category {name}");
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                categories.insert(name, category);
+            }
+        }
+        Ok(Self { categories })
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_state_categories() {
+        let data = "state_categories = {\n\
+                    \trural = {\n\t\tlocal_building_slots = 4\n\t\tcolor = { 95 146 52 }\n\t}\n\
+                    \tmegalopolis = {\n\t\tlocal_building_slots = 12\n\t\t\
+                    color = rgb { 7 7 7 }\n\t}\n\
+                    \twasteland = {\n\t\tlocal_building_slots = none\n\t}\n\
+                    }\n";
+        let categories = StateCategories::from_text(Path::new("00_state_category.txt"), data)
+            .expect("Failed to read state categories");
+        let name = |n: &str| StateCategoryName(n.to_owned());
+        assert_eq!(categories.categories.len(), 3);
+        let rural = categories.get(&name("rural")).expect("No rural category");
+        assert_eq!(rural.building_slots, Some(4));
+        assert_eq!(rural.color, Some(Rgb([95, 146, 52])));
+        assert_eq!(
+            categories.categories[&name("megalopolis")].color,
+            Some(Rgb([7, 7, 7]))
+        );
+        let wasteland = &categories.categories[&name("wasteland")];
+        assert_eq!(wasteland.building_slots, None);
+        assert_eq!(wasteland.color, None);
+        assert!(categories.get(&name("town")).is_none());
+    }
+}
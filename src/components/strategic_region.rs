@@ -1,8 +1,10 @@
 use crate::components::day_month::DayMonth;
 use crate::components::prelude::*;
-use crate::MapError;
+use crate::encoding::{read_text, TextEncoding};
+use crate::format::{Block, FileFormat, FormatValue, SourceFile};
+use crate::{parse_error, ErrorContext, MapError};
 use jomini::text::ObjectReader;
-use jomini::{JominiDeserialize, TextTape, Windows1252Encoding};
+use jomini::{JominiDeserialize, TextTape, Utf8Encoding};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
@@ -30,10 +32,16 @@ impl StrategicRegion {
     /// # Errors
     /// If the file cannot be read, or if it is invalid
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
-        let data = fs::read_to_string(path)?;
-        let tape = TextTape::from_slice(data.as_bytes())?;
-        let reader = tape.windows1252_reader();
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        Self::from_text(path, &data).in_file(path)
+    }
+
+    /// Reads the strategic region from the text of its file
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
         let raw_fields = {
             let fields = reader
                 .fields()
@@ -90,7 +98,10 @@ impl StrategicRegion {
                     }
                 }
                 _ => {
-                    warn!("Unknown key in strategic region: {}", key_string);
+                    warn!(
+                        "Unknown key in strategic region, it will be kept as written: {}",
+                        key_string
+                    );
                 }
             }
         }
@@ -102,6 +113,44 @@ impl StrategicRegion {
             weather,
         })
     }
+
+    /// Saves the strategic region to its file in the `strategicregions` directory, keeping the
+    /// formatting of the file it was loaded from if there is one.
+    /// # Errors
+    /// If the file fails to be written.
+    #[inline]
+    pub fn save(&self, path: &Path, source: Option<&SourceFile>) -> Result<(), MapError> {
+        let file_name = source.map_or_else(
+            || format!("{}-StrategicRegion.txt", self.id),
+            |s| s.file_name.clone(),
+        );
+        let default_format = FileFormat::default();
+        let format = source.map_or(&default_format, |s| &s.format);
+        let mut root = Block::new(&["strategic_region"]);
+        root.push("strategic_region", FormatValue::Block(self.to_block()));
+        fs::write(path.join(file_name), format.write(&root))?;
+        Ok(())
+    }
+
+    /// Converts the strategic region into the keys and values of its file.  Keys such as naval
+    /// terrain and static modifiers are not read, so they are kept as they were in the file.
+    fn to_block(&self) -> Block {
+        let mut block = Block::new(&["id", "name", "provinces", "weather"]);
+        block.push("id", FormatValue::Token(self.id.to_string()));
+        block.push("name", FormatValue::Token(format!("\"{}\"", self.name)));
+        let mut provinces = self.provinces.iter().collect::<Vec<_>>();
+        provinces.sort_unstable();
+        block.push(
+            "provinces",
+            FormatValue::Set(provinces.iter().map(ToString::to_string).collect()),
+        );
+        let mut weather = Block::new(&["period"]);
+        for period in &self.weather.period {
+            weather.push("period", FormatValue::Block(period.to_block()));
+        }
+        block.push("weather", FormatValue::Block(weather));
+        block
+    }
 }
 
 /// Container for the weather periods
@@ -146,9 +195,7 @@ impl Period {
     /// # Errors
     /// If the given reader is invalid
     #[inline]
-    pub fn from_reader(
-        reader: &ObjectReader<'_, '_, Windows1252Encoding>,
-    ) -> Result<Self, MapError> {
+    pub fn from_reader(reader: &ObjectReader<'_, '_, Utf8Encoding>) -> Result<Self, MapError> {
         let fields = reader.fields().collect::<Vec<_>>();
         let mut between_vec = vec![];
         let mut temperature_vec = vec![];
@@ -226,6 +273,41 @@ impl Period {
             min_snow_level,
         })
     }
+
+    /// Converts the period into the keys and values of its block.  Every key that is not
+    /// otherwise known is a weather effect, so no keys are kept from the file.
+    fn to_block(&self) -> Block {
+        let mut block = Block::exhaustive();
+        block.push(
+            "between",
+            FormatValue::Words(
+                self.between
+                    .iter()
+                    .map(|d| format!("{}.{}", d.day, d.month))
+                    .collect(),
+            ),
+        );
+        block.push(
+            "temperature",
+            FormatValue::List(self.temperature.iter().map(ToString::to_string).collect()),
+        );
+        if let Some(temperatures) = &self.temperature_day_night {
+            block.push(
+                "temperature_day_night",
+                FormatValue::List(temperatures.iter().map(ToString::to_string).collect()),
+            );
+        }
+        let mut effects = self.weather_effects.iter().collect::<Vec<_>>();
+        effects.sort_unstable_by_key(|(effect, _weight)| *effect);
+        for (effect, weight) in effects {
+            block.push(&effect.0, FormatValue::Token(weight.to_string()));
+        }
+        block.push(
+            "min_snow_level",
+            FormatValue::Token(self.min_snow_level.to_string()),
+        );
+        block
+    }
 }
 
 impl FromStr for Period {
@@ -234,17 +316,21 @@ impl FromStr for Period {
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let tape = TextTape::from_slice(s.as_bytes())?;
-        let reader = tape.windows1252_reader();
+        let reader = tape.utf8_reader();
         Period::from_reader(&reader)
     }
 }
 
 /// A map of the strategic regions by id
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[non_exhaustive]
 pub struct StrategicRegions {
     /// The strategic regions
     pub strategic_regions: HashMap<StrategicRegionId, StrategicRegion>,
+    /// The files the strategic regions were loaded from, used to keep their formatting and the
+    /// keys that are not read when saving
+    #[serde(skip)]
+    pub sources: HashMap<StrategicRegionId, SourceFile>,
 }
 
 impl StrategicRegions {
@@ -297,16 +383,17 @@ impl StrategicRegions {
     /// # Errors
     /// If the directory cannot be read.
     #[inline]
-    pub fn from_dir(path: &Path) -> Result<Self, MapError> {
-        let strategic_region_files = fs::read_dir(path)?;
+    pub fn from_dir(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let strategic_region_files = fs::read_dir(path).in_file(path)?;
         let mut strategic_regions = HashMap::new();
+        let mut sources = HashMap::new();
         for strategic_region_file in strategic_region_files.flatten() {
             let strategic_region_path = strategic_region_file.path(); // Check if the file looks like a strategic region
             Self::verify_strategic_region_file_name(&strategic_region_path)?;
             let (filename_id, _) =
                 Self::get_strategic_region_id_and_filename(&strategic_region_file.file_name())?;
 
-            let strategic_region = StrategicRegion::from_file(&strategic_region_path)?;
+            let strategic_region = StrategicRegion::from_file(&strategic_region_path, encoding)?;
             let id = strategic_region.id;
 
             if id == StrategicRegionId(0) {
@@ -322,10 +409,36 @@ impl StrategicRegions {
                 ));
             }
 
+            let text =
+                read_text(&strategic_region_path, encoding).in_file(&strategic_region_path)?;
+            let source = SourceFile::new(
+                strategic_region_file
+                    .file_name()
+                    .to_string_lossy()
+                    .into_owned(),
+                FileFormat::detect(&text),
+            );
+            sources.insert(id, source);
             strategic_regions.insert(id, strategic_region);
         }
 
-        Ok(Self { strategic_regions })
+        Ok(Self {
+            strategic_regions,
+            sources,
+        })
+    }
+
+    /// Saves the strategic regions to the `strategicregions` directory.  Strategic regions loaded
+    /// from a file are written back to the same file, keeping its formatting, comments and the keys
+    /// that are not read.  New strategic regions are written to `<id>-StrategicRegion.txt`.
+    /// # Errors
+    /// If any of the strategic region files fail to be written.
+    #[inline]
+    pub fn to_dir(&self, path: &Path) -> Result<(), MapError> {
+        for (id, strategic_region) in &self.strategic_regions {
+            strategic_region.save(path, self.sources.get(id))?;
+        }
+        Ok(())
     }
 }
 
@@ -344,7 +457,7 @@ mod tests {
     fn it_reads_a_strategic_region_from_a_file() {
         let path = Path::new("./test/map/strategicregions/1-StrategicRegion.txt");
         let strategic_region =
-            StrategicRegion::from_file(path).expect("Failed to load strategic region");
+            StrategicRegion::from_file(path, None).expect("Failed to load strategic region");
         assert_eq!(
             strategic_region,
             StrategicRegion {
@@ -671,7 +784,7 @@ mod tests {
     fn it_reads_strategic_regions_from_a_directory() {
         env_logger::init();
         let strategicregions_path = Path::new("./test/map/strategicregions");
-        let strategicregions = StrategicRegions::from_dir(strategicregions_path)
+        let strategicregions = StrategicRegions::from_dir(strategicregions_path, None)
             .expect("failed to read strategicregions");
         assert_eq!(strategicregions.strategic_regions.len(), 177);
         assert_eq!(
@@ -683,4 +796,44 @@ mod tests {
             StrategicRegionName("GWW".to_owned())
         );
     }
+
+    #[test]
+    fn it_writes_a_strategic_region_back_as_it_was_read() {
+        let region_path = Path::new("./test/map/strategicregions/1-StrategicRegion.txt");
+        let text = fs::read_to_string(region_path).expect("Failed to read strategic region");
+        let strategic_region =
+            StrategicRegion::from_file(region_path, None).expect("Failed to load strategic region");
+        let source = SourceFile::new(
+            "1-StrategicRegion.txt".to_owned(),
+            FileFormat::detect(&text),
+        );
+        let dir = std::env::temp_dir().join("world_gen_strategic_region_round_trip");
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        strategic_region
+            .save(&dir, Some(&source))
+            .expect("Failed to save strategic region");
+        let written =
+            fs::read_to_string(dir.join("1-StrategicRegion.txt")).expect("Failed to read back");
+        assert_eq!(written, text);
+    }
+
+    #[test]
+    fn it_keeps_unknown_keys_and_comments() {
+        let dir = std::env::temp_dir().join("world_gen_strategic_region_unknown_keys");
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let region_path = dir.join("2-StrategicRegion.txt");
+        let text = "strategic_region = {\n\tid = 2\n\tname = \"REGION_2\"\n\
+            \tprovinces = {\n\t\t1 2\n\t}\n\t# Added by hand\n\
+            \tnaval_terrain = water_fjords\n\tweather = {\n\t}\n}\n";
+        fs::write(&region_path, text).expect("Failed to write strategic region");
+        let mut strategic_region = StrategicRegion::from_file(&region_path, None)
+            .expect("Failed to load strategic region");
+        strategic_region.provinces.insert(ProvinceId(3));
+        let source = SourceFile::new("2-StrategicRegion.txt".to_owned(), FileFormat::detect(text));
+        strategic_region
+            .save(&dir, Some(&source))
+            .expect("Failed to save strategic region");
+        let written = fs::read_to_string(&region_path).expect("Failed to read back");
+        assert_eq!(written, text.replace("\t\t1 2\n", "\t\t1 2 3\n"));
+    }
 }
@@ -0,0 +1,146 @@
+use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{parse_error, ErrorContext, MapError};
+use jomini::TextTape;
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A supply area of `map/supplyareas`, which maps from before version 1.11 of the game use in
+/// place of supply nodes and railways
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct SupplyArea {
+    /// The id of the supply area
+    pub id: SupplyAreaId,
+    /// The localisation key of the name of the supply area
+    pub name: String,
+    /// The supply the area gives to the units within it
+    pub value: Option<f32>,
+    /// The states that make up the supply area
+    pub states: Vec<StateId>,
+}
+
+/// The supply areas of the `map/supplyareas/*.txt` files
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct SupplyAreas {
+    /// Each supply area by its id
+    pub areas: HashMap<SupplyAreaId, SupplyArea>,
+}
+
+impl SupplyAreas {
+    /// Loads the supply areas of every `.txt` file in a directory.  The files are read in order of
+    /// their names, so a supply area defined again in a later file takes the place of the earlier
+    /// one.
+    /// # Errors
+    /// If the directory or one of its files cannot be read, or if a file is invalid
+    #[inline]
+    pub fn from_dir(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).in_file(path)? {
+            let file = entry?.path();
+            if file.extension().map_or(false, |e| e == "txt") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        let mut areas = Self::default();
+        for file in files {
+            let data = read_text(&file, encoding).in_file(&file)?;
+            let loaded = Self::from_text(&file, &data).in_file(&file)?;
+            areas.areas.extend(loaded.areas);
+        }
+        Ok(areas)
+    }
+
+    /// The supply area each state belongs to
+    #[inline]
+    #[must_use]
+    pub fn areas_by_state(&self) -> HashMap<StateId, SupplyAreaId> {
+        self.areas
+            .values()
+            .flat_map(|area| area.states.iter().map(|state| (*state, area.id)))
+            .collect()
+    }
+
+    /// Reads the supply areas from the text of a file.  Each `supply_area` block has an `id`, a
+    /// `name`, a `value` and the `states` within it.  Blocks without an id are left out.
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        let mut areas = HashMap::new();
+        for (key, _op, value) in reader.fields() {
+            if key.read_str() != "supply_area" {
+                continue;
+            }
+            let mut id = None;
+            let mut area = SupplyArea {
+                id: SupplyAreaId(0),
+                name: String::new(),
+                value: None,
+                states: Vec::new(),
+            };
+            for (field, _op, field_value) in value.read_object()?.fields() {
+                match &*field.read_str() {
+                    "id" => id = field_value.read_string().ok().and_then(|s| s.parse().ok()),
+                    "name" => area.name = field_value.read_string().unwrap_or_default(),
+                    "value" => {
+                        area.value = field_value.read_string().ok().and_then(|s| s.parse().ok());
+                    }
+                    "states" => {
+                        area.states = field_value
+                            .read_array()?
+                            .values()
+                            .filter_map(|s| s.read_string().ok()?.parse().ok())
+                            .collect();
+                    }
+                    _ => {}
+                }
+            }
+            match id {
+                Some(id) => {
+                    area.id = id;
+                    areas.insert(id, area);
+                }
+                None => warn!("No id could be read for supply area {}", area.name),
+            }
+        }
+        Ok(Self { areas })
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::float_cmp)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_supply_areas() {
+        let data = "supply_area={\n\tid=1\n\tname=\"SUPPLYAREA_1\"\n\tvalue=12\n\
+                    \tstates={\n\t\t64 65\n\t}\n}\n\
+                    supply_area={\n\tname=\"SUPPLYAREA_BROKEN\"\n\tstates={ 3 }\n}\n";
+        let areas = SupplyAreas::from_text(Path::new("1-Germany.txt"), data)
+            .expect("Failed to read supply areas");
+        assert_eq!(areas.areas.len(), 1);
+        let area = &areas.areas[&SupplyAreaId(1)];
+        assert_eq!(area.name, "SUPPLYAREA_1");
+        assert_eq!(area.value, Some(12.0));
+        assert_eq!(area.states, vec![StateId(64), StateId(65)]);
+        assert_eq!(areas.areas_by_state()[&StateId(65)], SupplyAreaId(1));
+    }
+
+    #[test]
+    fn it_reads_the_decoded_text_as_utf8() {
+        // Loading decodes Windows-1252 files, so the text is always UTF-8 by the time it is read
+        let data = "supply_area={\n\tid=2\n\tname=\"Z\u{fc}rich\"\n\tstates={ 7 }\n}\n";
+        let areas = SupplyAreas::from_text(Path::new("2-Zurich.txt"), data)
+            .expect("Failed to read supply areas");
+        assert_eq!(areas.areas[&SupplyAreaId(2)].name, "Z\u{fc}rich");
+    }
+}
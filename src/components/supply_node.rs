@@ -1,5 +1,7 @@
 use crate::components::wrappers::ProvinceId;
-use crate::MapError;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{ErrorContext, MapError};
+use serde::Serialize;
 use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
@@ -18,7 +20,7 @@ use std::str::FromStr;
 /// ```
 /// Note also that ports count as supply nodes and that if no supply node is designated in any of a
 /// country's states, the capital victory point will be used as a supply node.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[non_exhaustive]
 pub struct SupplyNodes {
     /// The supply nodes
@@ -30,11 +32,27 @@ impl SupplyNodes {
     /// # Errors
     /// If the file cannot be read, an error is returned.
     #[inline]
-    pub fn from_file(path: &Path) -> Result<Self, MapError> {
-        let data = fs::read_to_string(path)?;
-        let supply_nodes = data.parse()?;
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        let supply_nodes = data.parse().in_file(path)?;
         Ok(supply_nodes)
     }
+
+    /// Writes the supply nodes to the given path in the format of supply_nodes.txt, in order of
+    /// province id
+    /// # Errors
+    /// If the file cannot be written.
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        let mut nodes = self.nodes.iter().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        let data = nodes
+            .into_iter()
+            .map(|node| format!("1 {node}\n"))
+            .collect::<String>();
+        fs::write(path, data)?;
+        Ok(())
+    }
 }
 
 impl FromStr for SupplyNodes {
@@ -44,18 +62,8 @@ impl FromStr for SupplyNodes {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut nodes = HashSet::new();
 
-        for line in s.lines() {
-            let parts = line.trim().split(' ').collect::<Vec<_>>();
-            let one = parts
-                .first()
-                .ok_or_else(|| MapError::InvalidSupplyNode(line.to_owned()))?;
-            if parts.len() != 2 || *one != "1" {
-                return Err(MapError::InvalidSupplyNode(line.to_owned()));
-            }
-            let province_id = parts
-                .get(1)
-                .ok_or_else(|| MapError::InvalidSupplyNode(line.to_owned()))?
-                .parse()?;
+        for (index, line) in s.lines().enumerate() {
+            let province_id = parse_supply_node(line).at_line(index.saturating_add(1))?;
             nodes.insert(province_id);
         }
 
@@ -63,6 +71,21 @@ impl FromStr for SupplyNodes {
     }
 }
 
+/// Reads the province of a line of supply_nodes.txt, such as `1 1234`
+fn parse_supply_node(line: &str) -> Result<ProvinceId, MapError> {
+    let parts = line.trim().split(' ').collect::<Vec<_>>();
+    let one = parts
+        .first()
+        .ok_or_else(|| MapError::InvalidSupplyNode(line.to_owned()))?;
+    if parts.len() != 2 || *one != "1" {
+        return Err(MapError::InvalidSupplyNode(line.to_owned()));
+    }
+    Ok(parts
+        .get(1)
+        .ok_or_else(|| MapError::InvalidSupplyNode(line.to_owned()))?
+        .parse()?)
+}
+
 #[allow(clippy::expect_used)]
 #[allow(clippy::indexing_slicing)]
 #[allow(clippy::panic)]
@@ -75,9 +98,44 @@ mod tests {
     #[test]
     fn it_reads_supply_nodes_from_a_file() {
         let path = Path::new("./test/map/supply_nodes.txt");
-        let supply_nodes = SupplyNodes::from_file(path).expect("Failed to read supply nodes");
+        let supply_nodes = SupplyNodes::from_file(path, None).expect("Failed to read supply nodes");
         assert_eq!(supply_nodes.nodes.len(), 1049);
         assert!(supply_nodes.nodes.contains(&ProvinceId(15116)));
         assert!(supply_nodes.nodes.contains(&ProvinceId(6603)));
     }
+
+    #[test]
+    fn it_writes_supply_nodes_that_can_be_read_back() {
+        let supply_nodes = SupplyNodes {
+            nodes: HashSet::from([ProvinceId(12), ProvinceId(3)]),
+        };
+        let path = std::env::temp_dir().join("world_gen_supply_nodes_test.txt");
+        supply_nodes
+            .to_file(&path)
+            .expect("Failed to write supply nodes");
+        let text = fs::read_to_string(&path).expect("Failed to read supply nodes");
+        let read = SupplyNodes::from_file(&path, None).expect("Failed to read supply nodes");
+        fs::remove_file(&path).expect("Failed to remove supply nodes");
+        assert_eq!(text, "1 3\n1 12\n");
+        assert_eq!(read.nodes, supply_nodes.nodes);
+    }
+
+    #[test]
+    fn it_reports_the_file_and_line_of_an_invalid_supply_node() {
+        let path = std::env::temp_dir().join("world_gen_invalid_supply_nodes_test.txt");
+        fs::write(&path, "1 3\n1 12\n2 5\n").expect("Failed to write supply nodes");
+        let read = SupplyNodes::from_file(&path, None);
+        fs::remove_file(&path).expect("Failed to remove supply nodes");
+        match read {
+            Err(MapError::Contextual {
+                path: Some(error_path),
+                line: Some(3),
+                source,
+            }) => {
+                assert_eq!(error_path, path);
+                assert!(matches!(*source, MapError::InvalidSupplyNode(_)));
+            }
+            other => panic!("Expected the line of the supply node, got {other:?}"),
+        }
+    }
 }
@@ -1,10 +1,11 @@
 use crate::components::wrappers::ModelIndex;
-use crate::{LoadCsv, MapError, ProvinceId};
+use crate::encoding::TextEncoding;
+use crate::{CsvParsing, LoadCsv, MapError, ProvinceId};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 /// The unit stack information for displaying units on the map.
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[non_exhaustive]
 pub struct UnitStacks {
     /// The unit stacks
@@ -32,12 +33,17 @@ pub struct UnitStack {
 }
 
 impl UnitStacks {
-    /// Loads the `UnitStacks` from a given path
+    /// Loads the `UnitStacks` from a given path, handling the rows that cannot be read as
+    /// `parsing` says
     /// # Errors
     /// If the file cannot be read, or if it is invalid
     #[inline]
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
-        let stacks = UnitStack::load_csv(path, false)?;
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let stacks = UnitStack::load_csv(path, false, parsing, encoding)?;
         Ok(Self { stacks })
     }
 }
@@ -55,8 +61,8 @@ mod tests {
     #[test]
     fn it_loads_unit_stacks_from_file() {
         let unit_stacks_path = Path::new("./test/map/unitstacks.txt");
-        let unit_stacks =
-            UnitStacks::from_file(unit_stacks_path).expect("Failed to load unit stacks");
+        let unit_stacks = UnitStacks::from_file(unit_stacks_path, CsvParsing::Strict, None)
+            .expect("Failed to load unit stacks");
         assert_eq!(unit_stacks.stacks.len(), 307_834);
         assert_eq!(unit_stacks.stacks[307_592].province_id, ProvinceId(16765));
         assert_eq!(unit_stacks.stacks[307_592].model_index, ModelIndex(38));
@@ -0,0 +1,118 @@
+use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
+use crate::{parse_error, ErrorContext, MapError};
+use jomini::text::ObjectReader;
+use jomini::{TextTape, Utf8Encoding};
+use std::path::Path;
+
+/// The weather states of the base game, used when the root directory has no `common/weather.txt`
+pub const VANILLA_WEATHER_STATES: [&str; 8] = [
+    "no_phenomenon",
+    "rain_light",
+    "rain_heavy",
+    "snow",
+    "blizzard",
+    "arctic_water",
+    "mud",
+    "sandstorm",
+];
+
+/// The weather states defined in `common/weather.txt`, which the periods of the strategic regions
+/// give their weights to
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WeatherStates {
+    /// The weather states in the order they are defined
+    pub states: Vec<WeatherEffect>,
+}
+
+impl Default for WeatherStates {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            states: VANILLA_WEATHER_STATES
+                .iter()
+                .map(|s| WeatherEffect((*s).to_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl WeatherStates {
+    /// Loads the weather states from a given path
+    /// # Errors
+    /// If the file cannot be read, or if it is invalid
+    #[inline]
+    pub fn from_file(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        Self::from_text(path, &data).in_file(path)
+    }
+
+    /// Whether a weather state is defined
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, effect: &WeatherEffect) -> bool {
+        self.states.contains(effect)
+    }
+
+    /// Reads the weather states from the text of their file.  Each state is a `state` block with
+    /// a `name`, found at any depth so that the blocks may sit inside the `weather` block of the
+    /// file.  A state defined twice is kept once, where it is first defined.
+    fn from_text(path: &Path, data: &str) -> Result<Self, MapError> {
+        let tape = TextTape::from_slice(data.as_bytes())
+            .map_err(|e| parse_error(e, path, data.as_bytes()))?;
+        let reader = tape.utf8_reader();
+        let mut states = Vec::new();
+        read_states(&reader, &mut states);
+        Ok(Self { states })
+    }
+}
+
+/// Adds the names of the `state` blocks of an object and of the objects within it
+fn read_states(reader: &ObjectReader<'_, '_, Utf8Encoding>, states: &mut Vec<WeatherEffect>) {
+    for (key, _op, value) in reader.fields() {
+        let object = match value.read_object() {
+            Ok(object) => object,
+            Err(_e) => continue,
+        };
+        if key.read_str() == "state" {
+            let name = object
+                .fields()
+                .find(|(field, _op, _value)| field.read_str() == "name")
+                .and_then(|(_field, _op, name)| name.read_string().ok())
+                .map(WeatherEffect);
+            if let Some(name) = name.filter(|n| !states.contains(n)) {
+                states.push(name);
+            }
+        } else {
+            read_states(&object, states);
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_weather_states() {
+        let data = "weather = {\n\
+                    \tcloud_speed = 2\n\
+                    \tstate = {\n\t\tname = no_phenomenon\n\t\tdebug_color = { 1 1 1 }\n\t}\n\
+                    \tstate = {\n\t\tname = \"acid_rain\"\n\t\tmin_temp = 5\n\t}\n\
+                    \tstate = {\n\t\tname = no_phenomenon\n\t}\n\
+                    \tstate = {\n\t\tdebug_color = { 0 0 0 }\n\t}\n\
+                    }\n";
+        let states = WeatherStates::from_text(Path::new("weather.txt"), data)
+            .expect("Failed to read weather states");
+        let effect = |e: &str| WeatherEffect(e.to_owned());
+        assert_eq!(
+            states.states,
+            vec![effect("no_phenomenon"), effect("acid_rain")]
+        );
+        assert!(states.contains(&effect("acid_rain")));
+        assert!(!states.contains(&effect("snow")));
+        assert!(WeatherStates::default().contains(&effect("snow")));
+    }
+}
@@ -1,9 +1,16 @@
-use crate::{LoadCsv, MapError, StrategicRegionId};
+use crate::components::prelude::*;
+use crate::components::strategic_region::StrategicRegions;
+use crate::encoding::TextEncoding;
+use crate::{CsvParsing, LoadCsv, MapError, StrategicRegionId};
+use image::{Rgb, RgbImage};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
 use std::path::Path;
 
 /// The positions for weather effects on the map.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct WeatherPositions {
     /// The weather positions
@@ -11,12 +18,137 @@ pub struct WeatherPositions {
 }
 
 impl WeatherPositions {
-    /// Loads the `WeatherPositions` from a given path
+    /// Loads the `WeatherPositions` from a given path, handling the rows that cannot be read as
+    /// `parsing` says
     /// # Errors
     /// If the file cannot be read, or if it is invalid
     #[inline]
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, MapError> {
-        let positions = WeatherPosition::load_csv(path, false)?;
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Self, MapError> {
+        let positions = WeatherPosition::load_csv(path, false, parsing, encoding)?;
+        Ok(Self { positions })
+    }
+
+    /// Writes the `WeatherPositions` to a given path in the format of weatherpositions.txt
+    /// # Errors
+    /// If the file cannot be written
+    #[inline]
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), MapError> {
+        let mut data = String::new();
+        for position in &self.positions {
+            let weather_type = match position.weather_type {
+                WeatherType::Big => "big",
+                WeatherType::Small => "small",
+            };
+            let _result = writeln!(
+                data,
+                "{};{:.2};{:.2};{:.2};{weather_type}",
+                position.id, position.x, position.y, position.z
+            );
+        }
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Places a weather position in each strategic region with provinces on the map.
+    ///
+    /// The position is the pixel of the region closest to the center of the region, so that it
+    /// stays within regions that curve around others.  Its height is that of the heightmap at the
+    /// pixel on the scale of 0 to 25.5, and the vertical position is counted from the bottom of
+    /// the map as in the other position files.  Regions at least as large as the median region
+    /// get the big effect, the others the small one.  The positions are ordered by region id.
+    /// # Errors
+    /// * If the heightmap is not the same size as the provinces image
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn generate(
+        strategic_regions: &StrategicRegions,
+        provinces: &RgbImage,
+        heightmap: &RgbImage,
+        definitions: &Definitions,
+    ) -> Result<Self, MapError> {
+        let (width, height) = provinces.dimensions();
+        if heightmap.dimensions() != (width, height) {
+            return Err(MapError::ImageSizeMismatch(
+                "heightmap does not match provinces map".to_owned(),
+            ));
+        }
+        let region_by_province = strategic_regions
+            .strategic_regions
+            .values()
+            .flat_map(|r| r.provinces.iter().map(|p| (*p, r.id)))
+            .collect::<HashMap<_, _>>();
+        let region_by_color = definitions
+            .definitions
+            .values()
+            .filter_map(|d| {
+                let region = region_by_province.get(&d.id)?;
+                Some((Rgb([d.r.0, d.g.0, d.b.0]), *region))
+            })
+            .collect::<HashMap<_, _>>();
+
+        // The pixel count and the sums of the coordinates of each region
+        let mut sums: HashMap<StrategicRegionId, (u64, u64, u64)> = HashMap::new();
+        for (x, y, pixel) in provinces.enumerate_pixels() {
+            if let Some(region) = region_by_color.get(pixel) {
+                let (count, sum_x, sum_y) = sums.entry(*region).or_default();
+                *count += 1;
+                *sum_x += u64::from(x);
+                *sum_y += u64::from(y);
+            }
+        }
+        let centers = sums
+            .iter()
+            .map(|(region, (count, sum_x, sum_y))| {
+                let count = *count as f64;
+                (*region, (*sum_x as f64 / count, *sum_y as f64 / count))
+            })
+            .collect::<HashMap<_, _>>();
+        let mut closest: HashMap<StrategicRegionId, (f64, u32, u32)> = HashMap::new();
+        for (x, y, pixel) in provinces.enumerate_pixels() {
+            let region = match region_by_color.get(pixel) {
+                Some(r) => *r,
+                None => continue,
+            };
+            let (center_x, center_y) = centers.get(&region).copied().unwrap_or_default();
+            let distance = (f64::from(x) - center_x).hypot(f64::from(y) - center_y);
+            let best = closest.entry(region).or_insert((f64::INFINITY, x, y));
+            if distance < best.0 {
+                *best = (distance, x, y);
+            }
+        }
+
+        let mut areas = sums
+            .values()
+            .map(|(count, _x, _y)| *count)
+            .collect::<Vec<_>>();
+        areas.sort_unstable();
+        let median = areas.get(areas.len() / 2).copied().unwrap_or_default();
+        let mut positions = closest
+            .into_iter()
+            .map(|(id, (_distance, x, y))| {
+                let area = sums.get(&id).map_or(0, |(count, _x, _y)| *count);
+                let elevation = f32::from(heightmap.get_pixel(x, y).0[0]) / 10.0;
+                WeatherPosition {
+                    id,
+                    x: x as f32,
+                    y: elevation,
+                    z: (height - y) as f32,
+                    weather_type: if area >= median {
+                        WeatherType::Big
+                    } else {
+                        WeatherType::Small
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        positions.sort_by_key(|p| p.id);
         Ok(Self { positions })
     }
 }
@@ -53,15 +185,112 @@ pub enum WeatherType {
 #[allow(clippy::indexing_slicing)]
 #[allow(clippy::panic)]
 #[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
 #[allow(clippy::too_many_lines)]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::components::strategic_region::{StrategicRegion, Weather};
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashSet;
+
+    /// A map split into a large region on the left and a small region on the right, with the
+    /// heightmap rising from left to right
+    fn map() -> (StrategicRegions, RgbImage, RgbImage, Definitions) {
+        let provinces = RgbImage::from_fn(40, 20, |x, _y| {
+            if x < 30 {
+                Rgb([1, 0, 0])
+            } else {
+                Rgb([2, 0, 0])
+            }
+        });
+        let heightmap = RgbImage::from_fn(40, 20, |x, _y| {
+            let value = u8::try_from(x * 5).expect("Height out of range");
+            Rgb([value, value, value])
+        });
+        let definition = |id: i32| {
+            let red = u8::try_from(id).expect("Color out of range");
+            DefinitionBuilder::new(id).color(Rgb([red, 0, 0])).build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1)),
+                (ProvinceId(2), definition(2)),
+            ]),
+            terrain: HashSet::new(),
+        };
+        let region = |id: i32, province: i32| StrategicRegion {
+            id: StrategicRegionId(id),
+            name: StrategicRegionName(format!("REGION_{id}")),
+            provinces: HashSet::from([ProvinceId(province)]),
+            weather: Weather { period: Vec::new() },
+        };
+        let strategic_regions = StrategicRegions {
+            strategic_regions: HashMap::from([
+                (StrategicRegionId(1), region(1, 1)),
+                (StrategicRegionId(2), region(2, 2)),
+            ]),
+            sources: HashMap::new(),
+        };
+        (strategic_regions, provinces, heightmap, definitions)
+    }
 
     #[test]
-    fn it_loads_weather_positions_from_a_file() {
-        let weather_positions = WeatherPositions::from_file("./test/map/weatherpositions.txt")
+    fn it_generates_weather_positions_for_each_region() {
+        let (strategic_regions, provinces, heightmap, definitions) = map();
+        let weather_positions =
+            WeatherPositions::generate(&strategic_regions, &provinces, &heightmap, &definitions)
+                .expect("Failed to generate weather positions");
+        assert_eq!(weather_positions.positions.len(), 2);
+        let large = weather_positions.positions[0];
+        assert_eq!(large.id, StrategicRegionId(1));
+        // The center at (14.5, 9.5) is between pixels, so the first closest pixel is taken
+        assert!((large.x - 14.0).abs() < f32::EPSILON);
+        assert!((large.y - 7.0).abs() < f32::EPSILON);
+        assert!((large.z - 11.0).abs() < f32::EPSILON);
+        assert_eq!(large.weather_type, WeatherType::Big);
+        let small = weather_positions.positions[1];
+        assert_eq!(small.id, StrategicRegionId(2));
+        assert!((small.x - 34.0).abs() < f32::EPSILON);
+        assert_eq!(small.weather_type, WeatherType::Small);
+    }
+
+    #[test]
+    fn it_writes_weather_positions_that_load_again() {
+        let (strategic_regions, provinces, heightmap, definitions) = map();
+        let weather_positions =
+            WeatherPositions::generate(&strategic_regions, &provinces, &heightmap, &definitions)
+                .expect("Failed to generate weather positions");
+        let path = std::env::temp_dir().join("world_gen_weatherpositions_test.txt");
+        weather_positions
+            .to_file(&path)
+            .expect("Failed to write weather positions");
+        let loaded = WeatherPositions::from_file(&path, CsvParsing::Strict, None)
             .expect("Failed to load weather positions");
+        std::fs::remove_file(&path).expect("Failed to remove weather positions");
+        assert_eq!(loaded.positions, weather_positions.positions);
+    }
+
+    #[test]
+    fn it_rejects_heightmaps_of_another_size() {
+        let (strategic_regions, provinces, _heightmap, definitions) = map();
+        let result = WeatherPositions::generate(
+            &strategic_regions,
+            &provinces,
+            &RgbImage::new(10, 10),
+            &definitions,
+        );
+        assert!(matches!(result, Err(MapError::ImageSizeMismatch(_))));
+    }
+
+    #[test]
+    fn it_loads_weather_positions_from_a_file() {
+        let weather_positions = WeatherPositions::from_file(
+            "./test/map/weatherpositions.txt",
+            CsvParsing::Strict,
+            None,
+        )
+        .expect("Failed to load weather positions");
         assert_eq!(weather_positions.positions.len(), 265);
         assert_eq!(weather_positions.positions[0].id, StrategicRegionId(1));
         assert!((weather_positions.positions[0].x - 3339.0).abs() < f32::EPSILON);
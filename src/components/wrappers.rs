@@ -66,6 +66,29 @@ impl From<String> for BuildingId {
 #[non_exhaustive]
 pub struct ProvinceId(pub i32);
 
+impl ProvinceId {
+    /// Whether the id refers to a province when it is read from a column that may be left empty.
+    /// The map files fill such columns with 0 or -1, like the `Through` column of the adjacencies
+    /// or the adjacent sea province of the buildings.
+    #[inline]
+    #[must_use]
+    pub const fn is_province(self) -> bool {
+        self.0 > 0_i32
+    }
+
+    /// The province the id refers to, or `None` if it is one of the values the map files use to
+    /// leave a province column empty
+    #[inline]
+    #[must_use]
+    pub const fn reference(self) -> Option<Self> {
+        if self.is_province() {
+            Some(self)
+        } else {
+            None
+        }
+    }
+}
+
 /// A temperature value.
 #[derive(
     Copy, Clone, Debug, Default, Display, PartialEq, PartialOrd, Deserialize, Serialize, FromStr,
@@ -119,6 +142,24 @@ pub struct StateId(pub i32);
 #[non_exhaustive]
 pub struct StrategicRegionId(pub i32);
 
+/// The ID for a supply area of a map from before version 1.11 of the game.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Display,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Deserialize,
+    Serialize,
+    Hash,
+    FromStr,
+)]
+#[non_exhaustive]
+pub struct SupplyAreaId(pub i32);
+
 /// The level of the railroad.
 #[derive(
     Copy,
@@ -368,11 +409,14 @@ pub struct ModelIndex(pub u32);
     Serialize,
     Hash,
     FromStr,
+    From,
 )]
 #[non_exhaustive]
 pub struct Manpower(pub u32);
 
 /// The amount of victory points in a province
-#[derive(Copy, Clone, Debug, Display, PartialEq, PartialOrd, Deserialize, Serialize, FromStr)]
+#[derive(
+    Copy, Clone, Debug, Display, PartialEq, PartialOrd, Deserialize, Serialize, FromStr, From,
+)]
 #[non_exhaustive]
 pub struct VictoryPoints(pub f32);
@@ -0,0 +1,188 @@
+use derive_more::Display;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The environment variable that forces the encoding text files are read with, such as
+/// `windows-1252` or `utf-8`, when a load does not choose one.  Without either the encoding of
+/// each file is detected.
+pub const ENCODING_VARIABLE: &str = "WORLD_GEN_ENCODING";
+
+/// The characters of bytes 0x80 to 0x9F in Windows-1252.  The bytes it leaves undefined are read
+/// as the control characters of the same value, as Windows does.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// An encoding the text files of a map can be written in
+#[allow(clippy::exhaustive_enums)]
+#[derive(Display, Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TextEncoding {
+    /// UTF-8, with or without a byte order mark
+    #[display(fmt = "utf-8")]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    /// UTF-16, little or big endian as given by its byte order mark
+    #[display(fmt = "utf-16")]
+    #[serde(rename = "utf-16")]
+    Utf16,
+    /// Windows-1252, the encoding the game writes most of its files in
+    #[display(fmt = "windows-1252")]
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+impl FromStr for TextEncoding {
+    type Err = String;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "utf-16" | "utf16" => Ok(Self::Utf16),
+            "windows-1252" | "windows1252" | "cp1252" | "latin1" => Ok(Self::Windows1252),
+            other => Err(format!("Unknown encoding {other}")),
+        }
+    }
+}
+
+/// The encoding forced by `WORLD_GEN_ENCODING`, if it is set to a known encoding
+#[inline]
+#[must_use]
+pub fn forced_encoding() -> Option<TextEncoding> {
+    let value = env::var(ENCODING_VARIABLE).ok()?;
+    match value.parse() {
+        Ok(encoding) => Some(encoding),
+        Err(e) => {
+            warn!("Ignoring {ENCODING_VARIABLE}: {e}");
+            None
+        }
+    }
+}
+
+/// Reads a text file into a string in `encoding`, or in the encoding `WORLD_GEN_ENCODING` forces
+/// when it is `None`.  Without either the encoding is detected as `decode` describes.
+/// # Errors
+/// If the file cannot be read
+#[inline]
+pub fn read_text(path: &Path, encoding: Option<TextEncoding>) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    let (text, encoding) = decode(&bytes, encoding.or_else(forced_encoding));
+    if encoding != TextEncoding::Utf8 {
+        debug!("Read {} as {encoding}", path.display());
+    }
+    Ok(text)
+}
+
+/// Decodes the bytes of a text file, returning the text without its byte order mark along with
+/// the encoding it was read as.  A forced encoding is always used.  Otherwise a byte order mark
+/// decides the encoding, and without one the file is read as UTF-8 if it is valid UTF-8 and as
+/// Windows-1252 if it is not.  Bytes that are invalid in the encoding are replaced.
+#[inline]
+#[must_use]
+pub fn decode(bytes: &[u8], forced: Option<TextEncoding>) -> (String, TextEncoding) {
+    let utf16_bom = match bytes {
+        [0xff, 0xfe, ..] => Some(false),
+        [0xfe, 0xff, ..] => Some(true),
+        _ => None,
+    };
+    let encoding = match (forced, bytes, utf16_bom) {
+        (Some(encoding), _, _) => encoding,
+        (None, [0xef, 0xbb, 0xbf, ..], _) => TextEncoding::Utf8,
+        (None, _, Some(_big_endian)) => TextEncoding::Utf16,
+        (None, _, None) if std::str::from_utf8(bytes).is_ok() => TextEncoding::Utf8,
+        (None, _, None) => TextEncoding::Windows1252,
+    };
+    let text = match encoding {
+        TextEncoding::Utf8 => {
+            let body = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+            String::from_utf8_lossy(body).into_owned()
+        }
+        TextEncoding::Utf16 => {
+            let big_endian = utf16_bom.unwrap_or(false);
+            let body = bytes
+                .get(2..)
+                .filter(|_| utf16_bom.is_some())
+                .unwrap_or(bytes);
+            let units = body
+                .chunks_exact(2)
+                .map(|pair| match (pair, big_endian) {
+                    ([high, low], true) | ([low, high], false) => u16::from_be_bytes([*high, *low]),
+                    _ => 0,
+                })
+                .collect::<Vec<_>>();
+            String::from_utf16_lossy(&units)
+        }
+        TextEncoding::Windows1252 => bytes.iter().map(|b| windows_1252_char(*b)).collect(),
+    };
+    (text, encoding)
+}
+
+/// The character of a byte in Windows-1252
+fn windows_1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252_HIGH
+            .get(usize::from(byte.saturating_sub(0x80)))
+            .copied()
+            .unwrap_or(char::REPLACEMENT_CHARACTER),
+        _ => char::from(byte),
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_detects_the_encoding_of_text() {
+        let utf8_bom = b"\xef\xbb\xbfname = \"Bo\xc3\xabt\"";
+        assert_eq!(
+            decode(utf8_bom, None),
+            ("name = \"Bo\u{eb}t\"".to_owned(), TextEncoding::Utf8)
+        );
+        assert_eq!(
+            decode("Z\u{fc}rich".as_bytes(), None),
+            ("Z\u{fc}rich".to_owned(), TextEncoding::Utf8)
+        );
+        assert_eq!(
+            decode(b"Z\xfcrich \x80", None),
+            ("Z\u{fc}rich \u{20ac}".to_owned(), TextEncoding::Windows1252)
+        );
+        assert_eq!(
+            decode(b"\xff\xfea\x00\xfc\x00", None),
+            ("a\u{fc}".to_owned(), TextEncoding::Utf16)
+        );
+        assert_eq!(
+            decode(b"\xfe\xff\x00a\x00\xfc", None),
+            ("a\u{fc}".to_owned(), TextEncoding::Utf16)
+        );
+    }
+
+    #[test]
+    fn it_uses_a_forced_encoding() {
+        let text = "Z\u{fc}rich".as_bytes();
+        assert_eq!(
+            decode(text, Some(TextEncoding::Windows1252)),
+            ("Z\u{c3}\u{bc}rich".to_owned(), TextEncoding::Windows1252)
+        );
+        assert_eq!("CP1252".parse(), Ok(TextEncoding::Windows1252));
+        assert!("ebcdic".parse::<TextEncoding>().is_err());
+    }
+
+    #[test]
+    fn it_reads_a_file_in_the_encoding_it_is_given() {
+        let path = env::temp_dir().join("world_gen_encoding_given.txt");
+        fs::write(&path, "Z\u{fc}rich").expect("Failed to write file");
+        let text = read_text(&path, Some(TextEncoding::Windows1252)).expect("Failed to read file");
+        fs::remove_file(&path).expect("Failed to remove file");
+        assert_eq!(text, "Z\u{c3}\u{bc}rich");
+    }
+}
@@ -0,0 +1,220 @@
+use crate::map::{Map, MapLoadOptions};
+use crate::support::diagnostics_report;
+use crate::{CsvParsing, MapError};
+use indicatif::InMemoryTerm;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use tokio::runtime::Runtime;
+
+thread_local! {
+    /// The error of the last call on this thread that failed
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A map loaded through the C interface.
+///
+/// The map is opaque to the caller and must be freed with `world_gen_map_free`.  Strings returned
+/// by the interface must be freed with `world_gen_string_free`.  When a call fails,
+/// `world_gen_last_error` describes why.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct WorldGenMap {
+    /// The loaded map
+    map: Map,
+}
+
+/// Loads the map of a Hearts of Iron IV directory or mod, given as a UTF-8 path.  Returns null if
+/// the map could not be loaded or the load panicked.
+/// # Safety
+/// `root_path` must be null or a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_map_load(root_path: *const c_char) -> *mut WorldGenMap {
+    // SAFETY: the caller guarantees the same of the path
+    unsafe { world_gen_map_load_with(root_path, false) }
+}
+
+/// Loads a map like `world_gen_map_load`, skipping the rows of its CSV files that cannot be read
+/// rather than failing if `lenient_csv` is true.
+/// # Safety
+/// `root_path` must be null or a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_map_load_with(
+    root_path: *const c_char,
+    lenient_csv: bool,
+) -> *mut WorldGenMap {
+    catch_panic(ptr::null_mut(), || {
+        if root_path.is_null() {
+            set_last_error("The root path is null");
+            return ptr::null_mut();
+        }
+        // SAFETY: the caller guarantees that a non-null path is a valid null terminated string
+        let root_path = match unsafe { CStr::from_ptr(root_path) }.to_str() {
+            Ok(p) => p,
+            Err(e) => {
+                set_last_error(&format!("The root path is not valid UTF-8: {e}"));
+                return ptr::null_mut();
+            }
+        };
+        let options = MapLoadOptions::new().with_csv_parsing(if lenient_csv {
+            CsvParsing::Lenient
+        } else {
+            CsvParsing::Strict
+        });
+        match load_map(Path::new(root_path), &options) {
+            Ok(map) => Box::into_raw(Box::new(WorldGenMap { map })),
+            Err(e) => {
+                set_last_error(&e.to_string());
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a map loaded with `world_gen_map_load`.  Freeing null does nothing.
+/// # Safety
+/// `map` must be null or a map returned by `world_gen_map_load` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_map_free(map: *mut WorldGenMap) {
+    if !map.is_null() {
+        // SAFETY: the caller guarantees that the map came from `Box::into_raw` and is not freed
+        drop(unsafe { Box::from_raw(map) });
+    }
+}
+
+/// Runs the checks of the map, returning the same report as a support bundle.  The report must be
+/// freed with `world_gen_string_free`.  Returns null if the map is null or the checks panicked.
+/// # Safety
+/// `map` must be null or a map returned by `world_gen_map_load` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_map_validate(map: *const WorldGenMap) -> *mut c_char {
+    catch_panic(ptr::null_mut(), || {
+        // SAFETY: the caller guarantees that a non-null map is valid
+        let map = match unsafe { map.as_ref() } {
+            Some(m) => &m.map,
+            None => {
+                set_last_error("The map is null");
+                return ptr::null_mut();
+            }
+        };
+        into_c_string(&diagnostics_report(map))
+    })
+}
+
+/// Gets the id of the province at a pixel of the provinces image.  Returns 0 if the map is null,
+/// the pixel is outside of the map, its color is not defined or the lookup panicked.
+/// # Safety
+/// `map` must be null or a map returned by `world_gen_map_load` that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_map_province_at(map: *const WorldGenMap, x: u32, y: u32) -> i32 {
+    catch_panic(0, || {
+        // SAFETY: the caller guarantees that a non-null map is valid
+        let map = match unsafe { map.as_ref() } {
+            Some(m) => &m.map,
+            None => return 0,
+        };
+        map.province_index.province_at(x, y).map_or(0, |id| id.0)
+    })
+}
+
+/// Gets the error of the last call on this thread that failed, or null if none has.  The error
+/// is owned by the library and stays valid until the next call that fails on this thread.
+#[no_mangle]
+pub extern "C" fn world_gen_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Frees a string returned by the library.  Freeing null does nothing.
+/// # Safety
+/// `string` must be null or a string returned by the library that has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn world_gen_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        // SAFETY: the caller guarantees that the string came from `CString::into_raw`
+        drop(unsafe { CString::from_raw(string) });
+    }
+}
+
+/// Loads a map on a runtime of its own, as the C interface is called from outside of one
+fn load_map(root_path: &Path, options: &MapLoadOptions) -> Result<Map, MapError> {
+    let runtime = Runtime::new()?;
+    let _guard = runtime.enter();
+    Map::new(root_path, &Some(InMemoryTerm::new(1, 80)), options)
+}
+
+/// Runs the body of a call, recording a panic as the last error and returning `failed` in place
+/// of the result, as unwinding across the C boundary is undefined behaviour
+fn catch_panic<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    // A panic only leaves behind state the call created, which is dropped with the unwinding
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| (*message).to_owned())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown error".to_owned());
+            set_last_error(&format!("The library panicked: {message}"));
+            failed
+        }
+    }
+}
+
+/// Records the error of a failed call
+fn set_last_error(error: &str) {
+    // Errors are not expected to contain nulls, but they are dropped rather than losing the error
+    let error = CString::new(error.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(error));
+}
+
+/// Hands a string over to the caller, dropping any nulls within it
+fn into_c_string(string: &str) -> *mut c_char {
+    CString::new(string.replace('\0', ""))
+        .unwrap_or_default()
+        .into_raw()
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_failures_to_load() {
+        let path = CString::new("./test/missing").expect("Failed to create path");
+        // SAFETY: the path is a valid null terminated string
+        let map = unsafe { world_gen_map_load(path.as_ptr()) };
+        assert!(map.is_null());
+        let error = world_gen_last_error();
+        assert!(!error.is_null());
+
+        // SAFETY: null maps and strings are accepted
+        let province = unsafe { world_gen_map_province_at(ptr::null(), 0, 0) };
+        assert_eq!(province, 0_i32);
+        // SAFETY: null maps and strings are accepted
+        let report = unsafe { world_gen_map_validate(ptr::null()) };
+        assert!(report.is_null());
+        // SAFETY: null maps and strings are accepted
+        unsafe { world_gen_map_free(ptr::null_mut()) };
+        // SAFETY: null maps and strings are accepted
+        unsafe { world_gen_string_free(report) };
+    }
+
+    #[test]
+    #[allow(clippy::panic)]
+    fn it_reports_panics_instead_of_unwinding_into_the_caller() {
+        let province = catch_panic(0_i32, || panic!("Pixel out of bounds"));
+        assert_eq!(province, 0_i32);
+        let error = world_gen_last_error();
+        assert!(!error.is_null());
+        // SAFETY: the error is a valid null terminated string owned by the library
+        let error = unsafe { CStr::from_ptr(error) };
+        assert_eq!(
+            error.to_str().expect("Error is not UTF-8"),
+            "The library panicked: Pixel out of bounds"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::generation::world::{GeneratedWorld, WorldConfig, WorldGenerator};
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// The number of provinces in each state of a synthetic map
+const PROVINCES_PER_STATE: usize = 8;
+
+/// A map built from a grid of rectangular land provinces, for testing how the editor copes with
+/// map sizes beyond the base game, such as a 16384x8192 map with 60000 provinces.
+///
+/// The provinces fill the map in rows, and every `PROVINCES_PER_STATE` neighboring provinces form
+/// a state.  Each province has a unique color derived from its id, so building a map of any size
+/// takes a single pass over its pixels.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SyntheticMap {
+    /// The provinces image
+    pub provinces: RgbImage,
+    /// The definitions of the provinces
+    pub definitions: Definitions,
+    /// The map of colors to province ids
+    pub provinces_by_color: HashMap<Rgb<u8>, ProvinceId>,
+    /// The states of the map
+    pub states: HashMap<StateId, State>,
+    /// The map of province ids to states
+    pub states_by_province: HashMap<ProvinceId, StateId>,
+}
+
+impl SyntheticMap {
+    /// Builds a synthetic map with the given size and number of provinces
+    /// # Errors
+    /// * If the width or height is 0
+    /// * If there are no provinces, or more provinces than pixels or unique colors
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(width: u32, height: u32, province_count: u32) -> Result<Self, MapError> {
+        let pixels = u64::from(width) * u64::from(height);
+        if pixels == 0 || province_count == 0 || u64::from(province_count) > pixels {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "A {width}x{height} map cannot hold {province_count} provinces"
+            )));
+        }
+        if province_count >= 1_u32 << 24 {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "{province_count} provinces do not have unique colors"
+            )));
+        }
+        // Lay the provinces out in a grid with cells as close to square as the map allows
+        let columns = ((f64::from(province_count) * f64::from(width) / f64::from(height)).sqrt()
+            as u32)
+            .clamp(1, province_count.min(width));
+        let rows = ((province_count + columns - 1) / columns).min(height);
+        let cell_width = width / columns;
+        let cell_height = height / rows;
+
+        let province_at = |x: u32, y: u32| {
+            let column = (x / cell_width.max(1)).min(columns - 1);
+            let row = (y / cell_height.max(1)).min(rows - 1);
+            // Cells past the last province belong to the last province
+            (row * columns + column).min(province_count - 1) + 1
+        };
+        let provinces = RgbImage::from_fn(width, height, |x, y| province_color(province_at(x, y)));
+
+        let mut definitions = HashMap::new();
+        let mut provinces_by_color = HashMap::new();
+        let mut states = HashMap::new();
+        let mut states_by_province = HashMap::new();
+        for index in 1..=province_count {
+            let id = ProvinceId(index as i32);
+            let color = province_color(index);
+            definitions.insert(
+                id,
+                Definition {
+                    id,
+                    r: Red(color.0[0]),
+                    g: Green(color.0[1]),
+                    b: Blue(color.0[2]),
+                    province_type: ProvinceType::Land,
+                    coastal: Coastal(false),
+                    terrain: Terrain("plains".to_owned()),
+                    continent: ContinentIndex(1),
+                },
+            );
+            provinces_by_color.insert(color, id);
+            let state_id = StateId(((index as usize - 1) / PROVINCES_PER_STATE) as i32 + 1);
+            states
+                .entry(state_id)
+                .or_insert_with(|| State {
+                    id: state_id,
+                    name: StateName(format!("STATE_{}", state_id.0)),
+                    manpower: vec![Manpower(1000)],
+                    state_category: vec![StateCategoryName("rural".to_owned())],
+                    history: None,
+                    provinces: HashSet::new(),
+                    local_supplies: None,
+                    impassable: None,
+                    buildings_max_level_factor: None,
+                })
+                .provinces
+                .insert(id);
+            states_by_province.insert(id, state_id);
+        }
+        let terrain = HashSet::from([Terrain("plains".to_owned())]);
+
+        Ok(Self {
+            provinces,
+            definitions: Definitions {
+                definitions,
+                terrain,
+            },
+            provinces_by_color,
+            states,
+            states_by_province,
+        })
+    }
+
+    /// Writes the provinces image and definitions to `provinces.bmp` and `definition.csv` in the
+    /// given directory, so the map can be read back by the loaders.
+    /// # Errors
+    /// * If the files could not be written
+    #[inline]
+    pub fn save(&self, dir: &Path) -> Result<(), MapError> {
+        fs::create_dir_all(dir)?;
+        self.provinces.save(dir.join("provinces.bmp"))?;
+        let mut ids = self.definitions.definitions.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let mut csv = "0;0;0;0;land;false;unknown;0\n".to_owned();
+        for definition in ids
+            .into_iter()
+            .filter_map(|id| self.definitions.definitions.get(id))
+        {
+            // Writing to a `String` cannot fail
+            let _result = writeln!(
+                csv,
+                "{};{};{};{};land;{};{};{}",
+                definition.id,
+                definition.r,
+                definition.g,
+                definition.b,
+                definition.coastal,
+                definition.terrain,
+                definition.continent
+            );
+        }
+        fs::write(dir.join("definition.csv"), csv)?;
+        Ok(())
+    }
+}
+
+/// The settings of the mini map, a small world generated in well under a second that covers
+/// every file the map loads, so that the map can be tested without the files of the base game.
+#[inline]
+#[must_use]
+pub fn mini_map_config() -> WorldConfig {
+    WorldConfig {
+        seed: 7,
+        width: 256,
+        height: 256,
+        land_fraction: 0.5,
+        province_count: 80,
+        provinces_per_state: 4,
+        states_per_region: 3,
+        ..WorldConfig::default()
+    }
+}
+
+/// Generates the mini map and writes it to the given root directory as a mod the map loads from.
+/// The same map is generated every time, as its settings include the seed.
+/// # Errors
+/// * If the files could not be written
+#[inline]
+pub fn write_mini_map(root_path: &Path) -> Result<GeneratedWorld, MapError> {
+    WorldGenerator::new(mini_map_config()).generate(root_path)
+}
+
+/// A unique color for each province id below 2^24.  Multiplying by an odd number spreads
+/// neighboring ids across the colors while never mapping two ids to the same color, and only
+/// the id 0 maps to black.
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+const fn province_color(id: u32) -> Rgb<u8> {
+    let color = id.wrapping_mul(2_654_435_761) & 0x00FF_FFFF;
+    Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8])
+}
+
+/// A builder of the province definitions the unit tests are written with.  A definition starts as
+/// a land province of plains on continent 1 that is not coastal, with the color `(0, 0, id)`.
+#[cfg(test)]
+#[derive(Clone, Debug)]
+pub(crate) struct DefinitionBuilder(Definition);
+
+#[cfg(test)]
+impl DefinitionBuilder {
+    /// Starts the definition of a province with an id, which must fit in the blue channel of the
+    /// default color
+    pub(crate) fn new(id: i32) -> Self {
+        Self(Definition {
+            id: ProvinceId(id),
+            r: Red(0),
+            g: Green(0),
+            b: Blue(u8::try_from(id).unwrap_or_default()),
+            province_type: ProvinceType::Land,
+            coastal: Coastal(false),
+            terrain: Terrain("plains".to_owned()),
+            continent: ContinentIndex(1),
+        })
+    }
+
+    /// Sets the color of the province in the provinces image
+    pub(crate) const fn color(mut self, color: Rgb<u8>) -> Self {
+        let [r, g, b] = color.0;
+        self.0.r = Red(r);
+        self.0.g = Green(g);
+        self.0.b = Blue(b);
+        self
+    }
+
+    /// Sets whether the province is land, sea or a lake
+    pub(crate) const fn province_type(mut self, province_type: ProvinceType) -> Self {
+        self.0.province_type = province_type;
+        self
+    }
+
+    /// Sets whether the province is coastal
+    pub(crate) const fn coastal(mut self, coastal: bool) -> Self {
+        self.0.coastal = Coastal(coastal);
+        self
+    }
+
+    /// Sets the terrain of the province
+    pub(crate) fn terrain(mut self, terrain: &str) -> Self {
+        self.0.terrain = Terrain(terrain.to_owned());
+        self
+    }
+
+    /// Sets the continent of the province
+    pub(crate) const fn continent(mut self, continent: usize) -> Self {
+        self.0.continent = ContinentIndex(continent);
+        self
+    }
+
+    /// The definition that was built
+    pub(crate) const fn build(self) -> Definition {
+        self.0
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_a_consistent_synthetic_map() {
+        let map = SyntheticMap::new(512, 256, 1000).expect("Failed to build map");
+        assert_eq!(map.definitions.definitions.len(), 1000);
+        assert_eq!(map.provinces_by_color.len(), 1000);
+        let used = map.provinces.pixels().copied().collect::<HashSet<_>>();
+        assert_eq!(used.len(), 1000);
+        assert!(used.iter().all(|c| map.provinces_by_color.contains_key(c)));
+        assert_eq!(map.states.len(), 125);
+        assert!(map
+            .states
+            .values()
+            .all(|s| s.provinces.len() == PROVINCES_PER_STATE));
+    }
+
+    #[test]
+    fn it_generates_the_same_mini_map_every_time() {
+        let first = WorldGenerator::new(mini_map_config())
+            .build()
+            .expect("Failed to build mini map");
+        let second = WorldGenerator::new(mini_map_config())
+            .build()
+            .expect("Failed to build mini map");
+        assert_eq!(first.provinces, second.provinces);
+        assert_eq!(first.heightmap, second.heightmap);
+        assert_eq!(first.states.states.len(), second.states.states.len());
+    }
+
+    #[test]
+    fn it_rejects_more_provinces_than_pixels() {
+        let result = SyntheticMap::new(4, 4, 17);
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::HashMap;
+
+/// A value to write to a file in the game's text format.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum FormatValue {
+    /// A single value, such as a number, a word or a quoted string
+    Token(String),
+    /// A list of values where the order matters, written as `{ a b c }`
+    List(Vec<String>),
+    /// A list of values where the order does not matter, such as the provinces of a state
+    Set(Vec<String>),
+    /// A list of values compared as they are written rather than as numbers, such as the dates
+    /// of a weather period where `0.1` and `0.10` are different days
+    Words(Vec<String>),
+    /// A block of keys and values
+    Block(Block),
+}
+
+/// A block of keys and values to write to a file in the game's text format.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Block {
+    /// The keys and values of the block, keys may be repeated
+    pub entries: Vec<(String, FormatValue)>,
+    /// The keys understood by the writer of the block.  Keys read from the file that are not
+    /// known are kept as they were, while known keys missing from the entries are left out.
+    pub known_keys: Vec<String>,
+    /// Whether the writer of the block understands every key, so none are kept from the file
+    pub exhaustive: bool,
+}
+
+impl Block {
+    /// Creates a new empty block that knows the given keys
+    #[inline]
+    #[must_use]
+    pub fn new(known_keys: &[&str]) -> Self {
+        Self {
+            entries: Vec::new(),
+            known_keys: known_keys.iter().map(|k| (*k).to_owned()).collect(),
+            exhaustive: false,
+        }
+    }
+
+    /// Creates a new empty block that knows every key, such as a block where any key is read as
+    /// a modifier
+    #[inline]
+    #[must_use]
+    pub const fn exhaustive() -> Self {
+        Self {
+            entries: Vec::new(),
+            known_keys: Vec::new(),
+            exhaustive: true,
+        }
+    }
+
+    /// Adds an entry to the block
+    #[inline]
+    pub fn push(&mut self, key: &str, value: FormatValue) {
+        self.entries.push((key.to_owned(), value));
+    }
+}
+
+/// How a key of a block was written in a file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct KeyFormat {
+    /// The key
+    pub key: String,
+    /// The text between the key and its value, such as ` = ` or `=`
+    pub assignment: String,
+    /// The comment lines before the key
+    pub comments: Vec<String>,
+    /// The number of blank lines before the key and its comments
+    pub blank_lines_before: usize,
+    /// The value as it was written
+    pub raw: String,
+    /// The text after the value on the same line, such as a comment
+    pub trailing: String,
+}
+
+/// How the keys of a block were written in a file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BlockFormat {
+    /// The keys of the block, in the order they were written
+    pub keys: Vec<KeyFormat>,
+    /// The comment lines at the end of the block
+    pub closing_comments: Vec<String>,
+}
+
+/// The formatting of a file in the game's text format, recorded when the file is loaded so that
+/// saving the file changes as little of it as possible.
+///
+/// Blocks are identified by the path of keys leading to them, such as `state.history`.  When
+/// writing a block, its keys are written in the order they were read along with their comments,
+/// spacing and blank lines, and values that have not changed are written exactly as they were
+/// read.  Keys the writer does not know about are kept, so that hand written additions survive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FileFormat {
+    /// The line ending of the file
+    pub line_ending: String,
+    /// The indentation of each level of nesting
+    pub indent: String,
+    /// The text between new keys and their values
+    pub assignment: String,
+    /// Whether the file ends with a line ending
+    pub trailing_newline: bool,
+    /// The format of each block by the path of keys leading to it
+    pub blocks: HashMap<String, BlockFormat>,
+}
+
+impl Default for FileFormat {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            line_ending: "\n".to_owned(),
+            indent: "\t".to_owned(),
+            assignment: " = ".to_owned(),
+            trailing_newline: true,
+            blocks: HashMap::new(),
+        }
+    }
+}
+
+/// A file loaded from the game's directories, kept so it can be saved in the same place and
+/// format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SourceFile {
+    /// The name of the file
+    pub file_name: String,
+    /// The formatting of the file
+    pub format: FileFormat,
+}
+
+impl SourceFile {
+    /// Creates a new source file
+    #[inline]
+    #[must_use]
+    pub const fn new(file_name: String, format: FileFormat) -> Self {
+        Self { file_name, format }
+    }
+}
+
+impl FileFormat {
+    /// Records the formatting of a file in the game's text format
+    #[inline]
+    #[must_use]
+    pub fn detect(text: &str) -> Self {
+        let mut parser = Parser {
+            text,
+            pos: 0,
+            indent: None,
+            assignment: None,
+        };
+        let mut blocks = HashMap::new();
+        parser.parse_block("", 0, &mut blocks);
+        let defaults = Self::default();
+        Self {
+            line_ending: if text.contains("\r\n") {
+                "\r\n".to_owned()
+            } else {
+                defaults.line_ending
+            },
+            indent: parser.indent.unwrap_or(defaults.indent),
+            assignment: parser.assignment.unwrap_or(defaults.assignment),
+            trailing_newline: text.is_empty() || text.ends_with('\n'),
+            blocks,
+        }
+    }
+
+    /// Writes the root block of a file in the recorded format
+    #[inline]
+    #[must_use]
+    pub fn write(&self, root: &Block) -> String {
+        let mut out = String::new();
+        self.write_block(&mut out, "", root, 0);
+        if !self.trailing_newline && out.ends_with(&self.line_ending) {
+            out.truncate(out.len().saturating_sub(self.line_ending.len()));
+        }
+        out
+    }
+
+    /// Writes the entries of a block, following the recorded order of its keys
+    #[allow(clippy::integer_arithmetic)]
+    fn write_block(&self, out: &mut String, path: &str, block: &Block, depth: usize) {
+        let recorded = self.blocks.get(path);
+        let mut written = vec![false; block.entries.len()];
+        // Repeated keys are matched to the file by how many times the key came before
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let entry_occurrences = block
+            .entries
+            .iter()
+            .map(|(key, _value)| {
+                let count = occurrences.entry(key).or_default();
+                *count += 1;
+                *count - 1
+            })
+            .collect::<Vec<_>>();
+        occurrences.clear();
+        for key_format in recorded.map(|b| b.keys.as_slice()).unwrap_or_default() {
+            let count = occurrences.entry(&key_format.key).or_default();
+            let occurrence = *count;
+            *count += 1;
+            let supplied = block
+                .entries
+                .iter()
+                .zip(&entry_occurrences)
+                .position(|((key, _value), n)| *key == key_format.key && *n == occurrence);
+            match supplied.and_then(|i| block.entries.get(i).map(|entry| (i, entry))) {
+                Some((i, (key, value))) => {
+                    if let Some(w) = written.get_mut(i) {
+                        *w = true;
+                    }
+                    let child = child_path(path, key, occurrence);
+                    self.write_entry(out, &child, depth, key, value, Some(key_format));
+                }
+                None if !block.exhaustive && !block.known_keys.contains(&key_format.key) => {
+                    self.write_trivia(out, depth, key_format);
+                    self.write_indent(out, depth);
+                    out.push_str(&key_format.key);
+                    out.push_str(&key_format.assignment);
+                    out.push_str(&key_format.raw);
+                    out.push_str(&key_format.trailing);
+                    out.push_str(&self.line_ending);
+                }
+                None => {}
+            }
+        }
+        for (((key, value), written), occurrence) in
+            block.entries.iter().zip(written).zip(entry_occurrences)
+        {
+            if !written {
+                let child = child_path(path, key, occurrence);
+                self.write_entry(out, &child, depth, key, value, None);
+            }
+        }
+        for comment in recorded
+            .map(|b| b.closing_comments.as_slice())
+            .unwrap_or_default()
+        {
+            self.write_indent(out, depth);
+            out.push_str(comment);
+            out.push_str(&self.line_ending);
+        }
+    }
+
+    /// Writes a key and its value, reusing the recorded text of the value if it has not changed.
+    /// The path is the path of the value if it is a block.
+    #[allow(clippy::integer_arithmetic)]
+    fn write_entry(
+        &self,
+        out: &mut String,
+        path: &str,
+        depth: usize,
+        key: &str,
+        value: &FormatValue,
+        format: Option<&KeyFormat>,
+    ) {
+        if let Some(f) = format {
+            self.write_trivia(out, depth, f);
+        }
+        self.write_indent(out, depth);
+        out.push_str(key);
+        out.push_str(format.map_or(&self.assignment, |f| &f.assignment));
+        let raw = format.map(|f| f.raw.as_str());
+        match value {
+            FormatValue::Token(token) => match raw {
+                Some(r) if same_token(r, token) => out.push_str(r),
+                _ => out.push_str(token),
+            },
+            FormatValue::List(items) | FormatValue::Set(items) | FormatValue::Words(items) => {
+                match raw {
+                    Some(r) if same_items(r, value) => out.push_str(r),
+                    _ if items.is_empty() => out.push_str("{ }"),
+                    _ if !matches!(value, FormatValue::Set(_)) => {
+                        out.push_str("{ ");
+                        out.push_str(&items.join(" "));
+                        out.push_str(" }");
+                    }
+                    _ => {
+                        out.push('{');
+                        out.push_str(&self.line_ending);
+                        self.write_indent(out, depth + 1);
+                        out.push_str(&items.join(" "));
+                        out.push_str(&self.line_ending);
+                        self.write_indent(out, depth);
+                        out.push('}');
+                    }
+                }
+            }
+            FormatValue::Block(block) => {
+                out.push('{');
+                out.push_str(&self.line_ending);
+                self.write_block(out, path, block, depth + 1);
+                self.write_indent(out, depth);
+                out.push('}');
+            }
+        }
+        if let Some(f) = format {
+            out.push_str(&f.trailing);
+        }
+        out.push_str(&self.line_ending);
+    }
+
+    /// Writes the blank lines and comments before a key
+    fn write_trivia(&self, out: &mut String, depth: usize, format: &KeyFormat) {
+        for _ in 0..format.blank_lines_before {
+            out.push_str(&self.line_ending);
+        }
+        for comment in &format.comments {
+            self.write_indent(out, depth);
+            out.push_str(comment);
+            out.push_str(&self.line_ending);
+        }
+    }
+
+    /// Writes the indentation for the given level of nesting
+    fn write_indent(&self, out: &mut String, depth: usize) {
+        for _ in 0..depth {
+            out.push_str(&self.indent);
+        }
+    }
+}
+
+/// Reads the formatting of a file while skipping over its values.
+#[derive(Debug)]
+struct Parser<'text> {
+    /// The text of the file
+    text: &'text str,
+    /// The position of the next byte to read
+    pos: usize,
+    /// The indentation of the first nested key
+    indent: Option<String>,
+    /// The text between the first key and its value
+    assignment: Option<String>,
+}
+
+impl<'text> Parser<'text> {
+    /// The byte at the current position
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    /// Skips spaces and line breaks, returning the number of line breaks
+    #[allow(clippy::integer_arithmetic)]
+    fn skip_whitespace(&mut self) -> usize {
+        let mut lines = 0;
+        while let Some(c) = self.peek() {
+            match c {
+                b'\n' => lines += 1,
+                b' ' | b'\t' | b'\r' => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        lines
+    }
+
+    /// Skips spaces on the current line
+    #[allow(clippy::integer_arithmetic)]
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads up to the end of the line
+    #[allow(clippy::integer_arithmetic)]
+    fn read_line(&mut self) -> &'text str {
+        let start = self.pos;
+        while !matches!(self.peek(), None | Some(b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+        self.text.get(start..self.pos).unwrap_or_default()
+    }
+
+    /// Reads a quoted string or a word
+    #[allow(clippy::integer_arithmetic)]
+    fn read_token(&mut self) -> &'text str {
+        let start = self.pos;
+        if self.peek() == Some(b'"') {
+            self.pos += 1;
+            while let Some(c) = self.peek() {
+                self.pos += 1;
+                match c {
+                    b'\\' => self.pos += 1,
+                    b'"' => break,
+                    _ => {}
+                }
+            }
+            self.pos = self.pos.min(self.text.len());
+        } else {
+            while !matches!(
+                self.peek(),
+                None | Some(
+                    b' ' | b'\t' | b'\r' | b'\n' | b'{' | b'}' | b'=' | b'<' | b'>' | b'#' | b'"'
+                )
+            ) {
+                self.pos += 1;
+            }
+        }
+        self.text.get(start..self.pos).unwrap_or_default()
+    }
+
+    /// The whitespace from the start of the line up to the current position
+    fn line_indent(&self) -> Option<&'text str> {
+        let before = self.text.get(..self.pos)?;
+        let start = before.rfind('\n').map_or(0, |i| i.saturating_add(1));
+        let indent = before.get(start..)?;
+        indent
+            .bytes()
+            .all(|c| c == b' ' || c == b'\t')
+            .then_some(indent)
+    }
+
+    /// Parses the entries of a block up to its closing brace, or the end of the file for the root
+    /// block, recording the format of the block and the blocks within it.  Returns whether the
+    /// block holds keys and values rather than a list of values.
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::too_many_lines)]
+    fn parse_block(
+        &mut self,
+        path: &str,
+        depth: usize,
+        blocks: &mut HashMap<String, BlockFormat>,
+    ) -> bool {
+        let mut block = BlockFormat::default();
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        let mut is_object = true;
+        let mut comments = Vec::new();
+        let mut blank_lines = None;
+        let mut last_value_end = None;
+        loop {
+            // The first line break ends the previous line, unless there is no previous line
+            let starts_file = self.pos == 0;
+            let lines = self.skip_whitespace();
+            let blank = if starts_file {
+                lines
+            } else {
+                lines.saturating_sub(1)
+            };
+            match self.peek() {
+                None => break,
+                Some(b'}') => {
+                    self.pos += 1;
+                    if depth > 0 {
+                        break;
+                    }
+                }
+                Some(b'#') => {
+                    if let (0, true, Some(end), Some(key)) = (
+                        lines,
+                        comments.is_empty(),
+                        last_value_end,
+                        block.keys.last_mut(),
+                    ) {
+                        self.read_line();
+                        self.text
+                            .get(end..self.pos)
+                            .unwrap_or_default()
+                            .clone_into(&mut key.trailing);
+                        continue;
+                    }
+                    blank_lines.get_or_insert(blank);
+                    comments.push(self.read_line().to_owned());
+                }
+                Some(b'{') => {
+                    // A list of blocks
+                    is_object = false;
+                    self.pos += 1;
+                    self.parse_block(path, depth + 1, &mut HashMap::new());
+                }
+                Some(_) => {
+                    let blank_lines_before = blank_lines.take().unwrap_or(blank);
+                    if depth == 1 && self.indent.is_none() {
+                        self.indent = self
+                            .line_indent()
+                            .filter(|i| !i.is_empty())
+                            .map(str::to_owned);
+                    }
+                    let key = self.read_token();
+                    if key.is_empty() {
+                        // A stray operator
+                        self.pos += 1;
+                        continue;
+                    }
+                    let key_end = self.pos;
+                    self.skip_spaces();
+                    if !matches!(self.peek(), Some(b'=' | b'<' | b'>')) {
+                        is_object = false;
+                        comments.clear();
+                        last_value_end = None;
+                        continue;
+                    }
+                    while matches!(self.peek(), Some(b'=' | b'<' | b'>')) {
+                        self.pos += 1;
+                    }
+                    self.skip_spaces();
+                    let assignment = self.text.get(key_end..self.pos).unwrap_or_default();
+                    self.assignment.get_or_insert_with(|| assignment.to_owned());
+                    let count = occurrences.entry(key).or_default();
+                    let occurrence = *count;
+                    *count += 1;
+                    let value_start = self.pos;
+                    if self.peek() == Some(b'{') {
+                        self.pos += 1;
+                        let mut child_blocks = HashMap::new();
+                        let child = child_path(path, key, occurrence);
+                        if self.parse_block(&child, depth + 1, &mut child_blocks) {
+                            for (child, format) in child_blocks {
+                                blocks.entry(child).or_insert(format);
+                            }
+                        }
+                    } else {
+                        self.read_token();
+                    }
+                    block.keys.push(KeyFormat {
+                        key: key.to_owned(),
+                        assignment: assignment.to_owned(),
+                        comments: std::mem::take(&mut comments),
+                        blank_lines_before,
+                        raw: self
+                            .text
+                            .get(value_start..self.pos)
+                            .unwrap_or_default()
+                            .to_owned(),
+                        trailing: String::new(),
+                    });
+                    last_value_end = Some(self.pos);
+                }
+            }
+        }
+        block.closing_comments = comments;
+        if is_object {
+            blocks.entry(path.to_owned()).or_insert(block);
+        }
+        is_object
+    }
+}
+
+/// The path of a block within another block, such as `state.history`.  Blocks under a repeated
+/// key after the first are told apart by their position, such as `strategic_region.weather.period[1]`.
+fn child_path(path: &str, key: &str, occurrence: usize) -> String {
+    let key = if occurrence == 0 {
+        key.to_owned()
+    } else {
+        format!("{key}[{occurrence}]")
+    };
+    if path.is_empty() {
+        key
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// Whether a value read from a file is the same as a value to write, such as `1.000` and `1`
+fn same_token(raw: &str, token: &str) -> bool {
+    if raw == token {
+        return true;
+    }
+    let unquote = |s: &str| s.trim_matches('"').to_owned();
+    match (unquote(raw).parse::<f64>(), unquote(token).parse::<f64>()) {
+        (Ok(a), Ok(b)) => (a - b).abs() < f64::EPSILON,
+        _ => unquote(raw) == unquote(token),
+    }
+}
+
+/// Whether a list read from a file holds the same values as a list to write
+fn same_items(raw: &str, list: &FormatValue) -> bool {
+    let inner = raw.trim().trim_start_matches('{').trim_end_matches('}');
+    let mut raw_items = inner.split_whitespace().collect::<Vec<_>>();
+    let (items, exact) = match list {
+        FormatValue::List(items) | FormatValue::Set(items) => (items, false),
+        FormatValue::Words(items) => (items, true),
+        FormatValue::Token(_) | FormatValue::Block(_) => return false,
+    };
+    let mut items = items.iter().map(String::as_str).collect::<Vec<_>>();
+    if matches!(list, FormatValue::Set(_)) {
+        raw_items.sort_unstable();
+        items.sort_unstable();
+    }
+    raw_items.len() == items.len()
+        && raw_items.iter().zip(items).all(|(raw, item)| {
+            if exact {
+                *raw == item
+            } else {
+                same_token(raw, item)
+            }
+        })
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: &str = "state = {\r\n\tid = 1\r\n\tname = \"STATE_1\" # The capital\r\n\r\n\
+        \t# Resources are not read\r\n\tresources = {\r\n\t\tsteel = 8\r\n\t}\r\n\
+        \tprovinces={\r\n\t\t3 1 2\r\n\t}\r\n\tmanpower=25000\r\n\
+        \tbuildings_max_level_factor=1.000\r\n}\r\n";
+
+    fn state(provinces: &[&str], manpower: &str) -> Block {
+        let mut state = Block::new(&[
+            "id",
+            "name",
+            "provinces",
+            "manpower",
+            "buildings_max_level_factor",
+            "impassable",
+        ]);
+        state.push("id", FormatValue::Token("1".to_owned()));
+        state.push("name", FormatValue::Token("\"STATE_1\"".to_owned()));
+        state.push("manpower", FormatValue::Token(manpower.to_owned()));
+        state.push(
+            "buildings_max_level_factor",
+            FormatValue::Token("1".to_owned()),
+        );
+        state.push(
+            "provinces",
+            FormatValue::Set(provinces.iter().map(|p| (*p).to_owned()).collect()),
+        );
+        let mut root = Block::new(&["state"]);
+        root.push("state", FormatValue::Block(state));
+        root
+    }
+
+    #[test]
+    fn it_records_the_format_of_a_file() {
+        let format = FileFormat::detect(STATE);
+        assert_eq!(format.line_ending, "\r\n");
+        assert_eq!(format.indent, "\t");
+        assert_eq!(format.assignment, " = ");
+        let keys = format.blocks["state"]
+            .keys
+            .iter()
+            .map(|k| k.key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                "id",
+                "name",
+                "resources",
+                "provinces",
+                "manpower",
+                "buildings_max_level_factor"
+            ]
+        );
+        let resources = &format.blocks["state"].keys[2];
+        assert_eq!(resources.blank_lines_before, 1);
+        assert_eq!(resources.comments, vec!["# Resources are not read"]);
+        assert_eq!(format.blocks["state"].keys[1].trailing, " # The capital");
+        assert!(format.blocks.contains_key("state.resources"));
+        assert!(!format.blocks.contains_key("state.provinces"));
+    }
+
+    #[test]
+    fn it_writes_an_unchanged_file_as_it_was() {
+        let format = FileFormat::detect(STATE);
+        assert_eq!(format.write(&state(&["1", "2", "3"], "25000")), STATE);
+    }
+
+    #[test]
+    fn it_only_changes_the_edited_values() {
+        let format = FileFormat::detect(STATE);
+        let mut root = state(&["1", "2", "4"], "30000");
+        if let Some((_key, FormatValue::Block(state))) = root.entries.first_mut() {
+            state.push("impassable", FormatValue::Token("yes".to_owned()));
+        }
+        let expected = STATE
+            .replace("\t\t3 1 2", "\t\t1 2 4")
+            .replace("manpower=25000", "manpower=30000")
+            .replace("1.000\r\n}", "1.000\r\n\timpassable = yes\r\n}");
+        assert_eq!(format.write(&root), expected);
+    }
+
+    #[test]
+    fn it_writes_new_files_in_the_default_format() {
+        let text = FileFormat::default().write(&state(&["2", "1"], "10"));
+        assert_eq!(
+            text,
+            "state = {\n\tid = 1\n\tname = \"STATE_1\"\n\tmanpower = 10\n\t\
+            buildings_max_level_factor = 1\n\tprovinces = {\n\t\t2 1\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn it_tells_repeated_blocks_apart() {
+        let text = "weather={\n\tperiod={\n\t\tbetween={ 0.1 27.1 }\n\t\tsnow=0.000\n\t}\n\
+            \tperiod={\n\t\tsnow=0.500 # Winter\n\t\tbetween={ 0.10 30.10 }\n\t}\n}\n";
+        let format = FileFormat::detect(text);
+        assert!(format.blocks.contains_key("weather.period"));
+        assert!(format.blocks.contains_key("weather.period[1]"));
+        let period = |between: [&str; 2], snow: &str| {
+            let mut block = Block::exhaustive();
+            block.push(
+                "between",
+                FormatValue::Words(between.iter().map(|d| (*d).to_owned()).collect()),
+            );
+            block.push("snow", FormatValue::Token(snow.to_owned()));
+            FormatValue::Block(block)
+        };
+        let mut weather = Block::new(&["period"]);
+        weather.push("period", period(["0.1", "27.1"], "0"));
+        weather.push("period", period(["0.1", "30.1"], "0.5"));
+        let mut root = Block::new(&["weather"]);
+        root.push("weather", FormatValue::Block(weather));
+        assert_eq!(
+            format.write(&root),
+            text.replace("{ 0.10 30.10 }", "{ 0.1 30.1 }")
+        );
+    }
+}
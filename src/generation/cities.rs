@@ -0,0 +1,289 @@
+use crate::components::city::{BuildingMesh, Cities, CityGroup};
+use crate::components::prelude::*;
+use crate::components::state::States;
+use crate::generation::save_indexed_bmp;
+use crate::MapError;
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The palette of the generated cities.bmp, with a color for each city group followed by the
+/// color of land without cities.  The game only reads the indices.
+pub const CITY_PALETTE: [[u8; 3]; 4] = [[255, 0, 0], [255, 160, 0], [255, 255, 0], [0, 0, 0]];
+
+/// The cities.bmp index of a pixel without cities, which has no city group
+const NO_CITY_INDEX: u8 = 3;
+
+/// How large the settlement of a province is, each drawn with a city group of its own
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Settlement {
+    Metropolis,
+    City,
+    Town,
+}
+
+impl Settlement {
+    /// The index of the city group of the settlement in cities.bmp
+    const fn index(self) -> u8 {
+        match self {
+            Self::Metropolis => 0,
+            Self::City => 1,
+            Self::Town => 2,
+        }
+    }
+
+    /// The fraction of the pixels of the settlement with buildings
+    const fn density(self) -> f32 {
+        match self {
+            Self::Metropolis => 0.9,
+            Self::City => 0.5,
+            Self::Town => 0.2,
+        }
+    }
+
+    /// The meshes of the settlement by their distance to the edge of the urban area, from the
+    /// western cities of the base game
+    const fn buildings(self) -> &'static [(f32, &'static str)] {
+        match self {
+            Self::Metropolis => &[
+                (1.0, "western_citiy_3_entity"),
+                (3.0, "western_citiy_5_entity"),
+                (5.0, "western_citiy_4_entity"),
+                (9.0, "western_citiy_2_entity"),
+            ],
+            Self::City => &[
+                (1.0, "western_citiy_4_entity"),
+                (4.0, "western_citiy_2_entity"),
+                (8.0, "western_citiy_1_entity"),
+            ],
+            Self::Town => &[(2.0, "western_citiy_1_entity")],
+        }
+    }
+
+    /// The city group of the settlement in cities.txt
+    fn city_group(self) -> CityGroup {
+        CityGroup {
+            color_index: ColorIndex(u32::from(self.index())),
+            density: PixelDensity(self.density()),
+            building: self
+                .buildings()
+                .iter()
+                .map(|(distance, mesh)| BuildingMesh {
+                    distance: Distance(*distance),
+                    mesh: vec![MeshId((*mesh).to_owned())],
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The generated cities.bmp and the matching cities.txt
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct GeneratedCities {
+    /// The city group index of each pixel, using the `CITY_PALETTE`
+    pub indices: GrayImage,
+    /// The city groups drawn in the image
+    pub cities: Cities,
+}
+
+impl GeneratedCities {
+    /// Converts the indexes to the colors of the palette
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    pub fn to_rgb(&self) -> RgbImage {
+        RgbImage::from_fn(self.indices.width(), self.indices.height(), |x, y| {
+            let index = self.indices.get_pixel(x, y).0[0];
+            Rgb(CITY_PALETTE
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default())
+        })
+    }
+
+    /// Saves the image as cities.bmp, with the city group of each pixel as its palette index
+    /// # Errors
+    /// * If the file could not be written
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        save_indexed_bmp(path, &self.indices, &CITY_PALETTE)
+    }
+}
+
+/// Draws cities.bmp from the urban provinces and the victory points of the states.
+///
+/// Urban provinces with at least `metropolis_victory_points` become metropolises and other urban
+/// provinces cities, while provinces of any other terrain with at least `town_victory_points`
+/// become towns.  Every pixel of such a province is given the city group of its settlement, and
+/// the density of the group decides how many of them the game builds on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct CityGenerator {
+    /// The victory points an urban province needs to be drawn as a metropolis
+    pub metropolis_victory_points: f32,
+    /// The victory points a province that is not urban needs to be drawn as a town
+    pub town_victory_points: f32,
+}
+
+impl Default for CityGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(10.0, 1.0)
+    }
+}
+
+impl CityGenerator {
+    /// Creates a new city generator
+    #[inline]
+    #[must_use]
+    pub const fn new(metropolis_victory_points: f32, town_victory_points: f32) -> Self {
+        Self {
+            metropolis_victory_points,
+            town_victory_points,
+        }
+    }
+
+    /// Draws the cities for the provinces from their terrain and the victory points of the states
+    #[inline]
+    #[must_use]
+    pub fn generate(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+        states: &States,
+    ) -> GeneratedCities {
+        let victory_points = states
+            .states
+            .values()
+            .filter_map(|s| s.history.as_ref())
+            .flat_map(|h| h.victory_points.iter())
+            .map(|(province, points)| (*province, points.0))
+            .collect::<HashMap<_, _>>();
+        let settlements = definitions
+            .definitions
+            .values()
+            .filter(|d| d.id.0 != 0_i32 && d.province_type == ProvinceType::Land)
+            .filter_map(|d| {
+                let points = victory_points.get(&d.id).copied().unwrap_or_default();
+                let settlement = self.settlement(&d.terrain, points)?;
+                Some((Rgb([d.r.0, d.g.0, d.b.0]), settlement))
+            })
+            .collect::<HashMap<_, _>>();
+        let indices = GrayImage::from_fn(provinces.width(), provinces.height(), |x, y| {
+            let index = settlements
+                .get(provinces.get_pixel(x, y))
+                .map_or(NO_CITY_INDEX, |s| s.index());
+            Luma([index])
+        });
+        let cities = Cities {
+            types_source: Path::new("map/cities.bmp").into(),
+            pixel_step_x: PixelStep(2),
+            pixel_step_y: PixelStep(2),
+            city_group: [Settlement::Metropolis, Settlement::City, Settlement::Town]
+                .into_iter()
+                .map(Settlement::city_group)
+                .collect(),
+        };
+        GeneratedCities { indices, cities }
+    }
+
+    /// Picks the settlement of a province with the given terrain and victory points
+    fn settlement(self, terrain: &Terrain, victory_points: f32) -> Option<Settlement> {
+        if terrain.0 == "urban" {
+            if victory_points >= self.metropolis_victory_points {
+                Some(Settlement::Metropolis)
+            } else {
+                Some(Settlement::City)
+            }
+        } else if victory_points >= self.town_victory_points {
+            Some(Settlement::Town)
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::state::{State, StateHistory};
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashSet;
+
+    /// Four provinces in a row: a capital, a city, a town and farmland
+    fn map() -> (RgbImage, Definitions, States) {
+        let provinces = RgbImage::from_fn(8, 2, |x, _y| Rgb([(x / 2 + 1) as u8, 0, 0]));
+        let definition = |id: i32, terrain: &str| {
+            DefinitionBuilder::new(id)
+                .color(Rgb([id as u8, 0, 0]))
+                .terrain(terrain)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, "urban")),
+                (ProvinceId(2), definition(2, "urban")),
+                (ProvinceId(3), definition(3, "plains")),
+                (ProvinceId(4), definition(4, "plains")),
+            ]),
+            terrain: HashSet::new(),
+        };
+        let state = State {
+            id: StateId(1),
+            name: StateName("STATE_1".to_owned()),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: Some(StateHistory {
+                owner: CountryTag("TAG".to_owned()),
+                controller: None,
+                victory_points: vec![
+                    (ProvinceId(1), VictoryPoints(20.0)),
+                    (ProvinceId(3), VictoryPoints(1.0)),
+                ],
+            }),
+            provinces: HashSet::from([ProvinceId(1), ProvinceId(2), ProvinceId(3), ProvinceId(4)]),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        };
+        let states = States {
+            states: HashMap::from([(StateId(1), state)]),
+            sources: HashMap::new(),
+            duplicates: Vec::new(),
+        };
+        (provinces, definitions, states)
+    }
+
+    #[test]
+    fn it_draws_settlements_by_terrain_and_victory_points() {
+        let (provinces, definitions, states) = map();
+        let generated = CityGenerator::default().generate(&provinces, &definitions, &states);
+        let row = (0..8)
+            .map(|x| generated.indices.get_pixel(x, 1).0[0])
+            .collect::<Vec<_>>();
+        assert_eq!(row, vec![0, 0, 1, 1, 2, 2, NO_CITY_INDEX, NO_CITY_INDEX]);
+        let groups = &generated.cities.city_group;
+        assert_eq!(groups.len(), 3);
+        for (index, group) in groups.iter().enumerate() {
+            assert_eq!(group.color_index, ColorIndex(index as u32));
+            assert!(!group.building.is_empty());
+        }
+    }
+
+    #[test]
+    fn it_saves_an_indexed_cities_image() {
+        let (provinces, definitions, states) = map();
+        let generated = CityGenerator::default().generate(&provinces, &definitions, &states);
+        let path = std::env::temp_dir().join("world_gen_cities_test.bmp");
+        generated.save(&path).expect("Failed to save cities");
+        let loaded = image::open(&path).expect("Failed to open cities");
+        std::fs::remove_file(&path).expect("Failed to remove cities");
+        assert_eq!(loaded.to_rgb8(), generated.to_rgb());
+    }
+}
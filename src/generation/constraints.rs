@@ -0,0 +1,588 @@
+use crate::components::continent::Continents;
+use crate::components::prelude::*;
+use crate::topology::Wrapping;
+use crate::validation::province_sizes::MINIMUM_PROVINCE_SIZE_IN_PIXELS;
+use crate::validation::x_crossings::{crossing_fix, find_x_crossings_in, CrossingFix};
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// A problem left on the map that makes the game crash or complain, which the
+/// `ConstraintSolver` could not fix.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnsolvedConstraint {
+    /// Four provinces meet at the bottom right corner of the pixel at the coordinates
+    XCrossing(u32, u32),
+    /// A province smaller than the minimum size without a neighbor of the same type to merge into
+    SmallProvince(ProvinceId),
+    /// A province split into pieces, where a piece has no neighbor of the same type to join
+    DiscontiguousProvince(ProvinceId),
+    /// A land province without a continent, on a map where no land province has one
+    MissingContinent(ProvinceId),
+}
+
+/// What the `ConstraintSolver` changed, and what it could not fix.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConstraintReport {
+    /// The number of corners where four provinces met that were fixed
+    pub x_crossings_fixed: usize,
+    /// The number of pixels given to a neighbor to make their provinces contiguous
+    pub pixels_reassigned: usize,
+    /// The number of provinces merged into a neighbor for being too small
+    pub provinces_merged: usize,
+    /// The number of land provinces given a continent
+    pub continents_assigned: usize,
+    /// The problems left on the map
+    pub unsolved: Vec<UnsolvedConstraint>,
+}
+
+/// Fixes the conditions that crash the game, or that it warns about in debug mode, after a map
+/// has been generated.
+///
+/// * Where four provinces meet at a corner, one of the pixels is given to a neighboring province
+///   of the same type.
+/// * Every piece of a province apart from its largest is given to the neighboring province of the
+///   same type it shares the longest border with.
+/// * Provinces smaller than `min_province_size` pixels are merged into the neighboring province of
+///   the same type they share the longest border with.  The remaining provinces are renumbered so
+///   that the ids stay contiguous, so the solver should run before states and strategic regions
+///   are generated.
+/// * Land provinces without a valid continent take the continent of the nearest province that has
+///   one.
+///
+/// Fixing one problem can cause another, so the fixes are repeated for up to `passes` passes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ConstraintSolver {
+    /// The smallest number of pixels in a province
+    pub min_province_size: u32,
+    /// The most times the fixes are repeated
+    pub passes: u32,
+    /// Whether provinces continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for ConstraintSolver {
+    #[inline]
+    fn default() -> Self {
+        Self::new(MINIMUM_PROVINCE_SIZE_IN_PIXELS)
+    }
+}
+
+/// The provinces of the pixels of a map, as they are being fixed
+struct Labels {
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    /// The province of each pixel, if it has one
+    pixels: Vec<Option<ProvinceId>>,
+    /// The type of each province
+    types: HashMap<ProvinceId, ProvinceType>,
+}
+
+impl Labels {
+    /// The provinces of the pixels sharing an edge with the pixel at the index
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = (usize, Option<ProvinceId>)> + '_ {
+        self.wrapping
+            .neighbors(index, self.width, self.height)
+            .into_iter()
+            .flatten()
+            .filter_map(|n| Some((n, *self.pixels.get(n)?)))
+    }
+
+    /// Whether two provinces have the same type, with pixels without a province only matching
+    /// each other
+    fn same_type(&self, a: Option<ProvinceId>, b: Option<ProvinceId>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.types.get(&a) == self.types.get(&b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// The neighboring province of the same type sharing the most pixel edges with the pixels
+    fn best_neighbor(&self, id: ProvinceId, pixels: &[usize]) -> Option<ProvinceId> {
+        let mut borders: HashMap<ProvinceId, usize> = HashMap::new();
+        for pixel in pixels {
+            for neighbor in self.neighbors(*pixel).filter_map(|(_n, neighbor)| neighbor) {
+                if neighbor != id && self.same_type(Some(id), Some(neighbor)) {
+                    *borders.entry(neighbor).or_default() += 1;
+                }
+            }
+        }
+        borders
+            .into_iter()
+            .max_by(|(a_id, a), (b_id, b)| a.cmp(b).then(b_id.cmp(a_id)))
+            .map(|(neighbor, _count)| neighbor)
+    }
+}
+
+impl ConstraintSolver {
+    /// Creates a new constraint solver
+    #[inline]
+    #[must_use]
+    pub const fn new(min_province_size: u32) -> Self {
+        Self {
+            min_province_size,
+            passes: 4,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Fixes the provinces image and definitions, returning what was changed and what is left
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn solve(
+        &self,
+        provinces: &mut RgbImage,
+        definitions: &mut Definitions,
+        continents: &Continents,
+    ) -> ConstraintReport {
+        let (width, height) = provinces.dimensions();
+        let colors = definitions
+            .definitions
+            .values()
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.id))
+            .collect::<HashMap<_, _>>();
+        let mut labels = Labels {
+            width,
+            height,
+            wrapping: self.wrapping,
+            pixels: provinces.pixels().map(|p| colors.get(p).copied()).collect(),
+            types: definitions
+                .definitions
+                .values()
+                .map(|d| (d.id, d.province_type))
+                .collect(),
+        };
+
+        let mut report = ConstraintReport::default();
+        let mut removed = HashSet::new();
+        for _pass in 0..self.passes.max(1) {
+            let (crossings, mut unsolved) = fix_x_crossings(&mut labels);
+            let (reassigned, discontiguous) = join_pieces(&mut labels);
+            let (merged, small) = self.merge_small_provinces(&mut labels, definitions, &removed);
+            let merged_count = merged.len();
+            report.x_crossings_fixed += crossings;
+            report.pixels_reassigned += reassigned;
+            report.provinces_merged += merged_count;
+            removed.extend(merged);
+            unsolved.extend(discontiguous);
+            unsolved.extend(small);
+            report.unsolved = unsolved;
+            if crossings == 0 && reassigned == 0 && merged_count == 0 {
+                break;
+            }
+        }
+
+        let by_id = colors
+            .iter()
+            .map(|(color, id)| (*id, *color))
+            .collect::<HashMap<_, _>>();
+        for (pixel, label) in provinces.pixels_mut().zip(&labels.pixels) {
+            if let Some(color) = label.and_then(|id| by_id.get(&id)) {
+                *pixel = *color;
+            }
+        }
+        let (assigned, missing) = assign_continents(&labels, definitions, continents);
+        report.continents_assigned = assigned;
+        report.unsolved.extend(missing);
+        if !removed.is_empty() {
+            for id in &removed {
+                definitions.definitions.remove(id);
+            }
+            renumber(definitions);
+        }
+        report
+    }
+
+    /// Merges the provinces smaller than the minimum size into a neighbor, returning the merged
+    /// provinces and the small provinces that could not be merged
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    fn merge_small_provinces(
+        &self,
+        labels: &mut Labels,
+        definitions: &Definitions,
+        removed: &HashSet<ProvinceId>,
+    ) -> (Vec<ProvinceId>, Vec<UnsolvedConstraint>) {
+        let mut pixels_of: HashMap<ProvinceId, Vec<usize>> = HashMap::new();
+        for (index, label) in labels.pixels.iter().enumerate() {
+            if let Some(id) = label {
+                pixels_of.entry(*id).or_default().push(index);
+            }
+        }
+        let mut small = definitions
+            .definitions
+            .keys()
+            .filter(|id| id.0 != 0_i32 && !removed.contains(id))
+            .map(|id| (pixels_of.get(id).map_or(0, Vec::len), *id))
+            .filter(|(pixels, _id)| *pixels < self.min_province_size as usize)
+            .collect::<Vec<_>>();
+        small.sort_unstable();
+
+        let mut merged = Vec::new();
+        let mut unsolved = Vec::new();
+        for (_pixels, id) in small {
+            let pixels = pixels_of.remove(&id).unwrap_or_default();
+            if pixels.is_empty() {
+                merged.push(id);
+                continue;
+            }
+            match labels.best_neighbor(id, &pixels) {
+                Some(neighbor) => {
+                    for pixel in &pixels {
+                        if let Some(label) = labels.pixels.get_mut(*pixel) {
+                            *label = Some(neighbor);
+                        }
+                    }
+                    pixels_of.entry(neighbor).or_default().extend(pixels);
+                    merged.push(id);
+                }
+                None => {
+                    unsolved.push(UnsolvedConstraint::SmallProvince(id));
+                    pixels_of.insert(id, pixels);
+                }
+            }
+        }
+        (merged, unsolved)
+    }
+}
+
+/// Gives one pixel of each corner where four provinces meet to a neighbor of the same type,
+/// returning the number of corners fixed and the corners that could not be.  The corners created
+/// by a fix are left to the next pass of the solver.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+fn fix_x_crossings(labels: &mut Labels) -> (usize, Vec<UnsolvedConstraint>) {
+    let width = labels.width;
+    let index = move |x: u32, y: u32| y as usize * width as usize + x as usize;
+    let province = |labels: &Labels, x, y| labels.pixels.get(index(x, y)).copied().flatten();
+    let crossings = find_x_crossings_in(width, labels.height, labels.wrapping, |x, y| {
+        province(labels, x, y)
+    });
+    let mut fixed = 0;
+    let mut unsolved = Vec::new();
+    for crossing in crossings {
+        let fix = crossing_fix(
+            crossing,
+            width,
+            |x, y| province(labels, x, y),
+            |a, b| labels.same_type(a, b),
+        );
+        match fix {
+            CrossingFix::Resolved => {}
+            CrossingFix::Pixel((x, y), to) => {
+                if let Some(label) = labels.pixels.get_mut(index(x, y)) {
+                    *label = to;
+                }
+                fixed += 1;
+            }
+            CrossingFix::Unsolved(..) => {
+                unsolved.push(UnsolvedConstraint::XCrossing(crossing.x, crossing.y));
+            }
+        }
+    }
+    (fixed, unsolved)
+}
+
+/// Gives every piece of a province apart from its largest to the neighbor of the same type it
+/// shares the longest border with, returning the number of pixels given away and the provinces
+/// with pieces that could not be
+fn join_pieces(labels: &mut Labels) -> (usize, Vec<UnsolvedConstraint>) {
+    let mut pieces: HashMap<ProvinceId, Vec<Vec<usize>>> = HashMap::new();
+    let mut visited = vec![false; labels.pixels.len()];
+    for start in 0..labels.pixels.len() {
+        let id = match labels.pixels.get(start) {
+            Some(Some(id)) if visited.get(start) == Some(&false) => *id,
+            _ => continue,
+        };
+        if let Some(v) = visited.get_mut(start) {
+            *v = true;
+        }
+        let mut piece = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            piece.push(i);
+            for (n, neighbor) in labels.neighbors(i) {
+                if neighbor == Some(id) {
+                    if let Some(v) = visited.get_mut(n) {
+                        if !*v {
+                            *v = true;
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+        }
+        pieces.entry(id).or_default().push(piece);
+    }
+
+    let mut ids = pieces.keys().copied().collect::<Vec<_>>();
+    ids.sort_unstable();
+    let mut reassigned = 0;
+    let mut unsolved = Vec::new();
+    for id in ids {
+        let mut province_pieces = pieces.remove(&id).unwrap_or_default();
+        if province_pieces.len() < 2 {
+            continue;
+        }
+        province_pieces.sort_by_key(|piece| std::cmp::Reverse(piece.len()));
+        let mut stuck = false;
+        for piece in province_pieces.into_iter().skip(1) {
+            match labels.best_neighbor(id, &piece) {
+                Some(neighbor) => {
+                    reassigned += piece.len();
+                    for pixel in piece {
+                        if let Some(label) = labels.pixels.get_mut(pixel) {
+                            *label = Some(neighbor);
+                        }
+                    }
+                }
+                None => stuck = true,
+            }
+        }
+        if stuck {
+            unsolved.push(UnsolvedConstraint::DiscontiguousProvince(id));
+        }
+    }
+    (reassigned, unsolved)
+}
+
+/// Gives each land province without a valid continent the continent of the nearest province
+/// that has one, counting the provinces crossed to reach it.  Returns the number of provinces
+/// given a continent and those that could not be.
+#[allow(clippy::integer_arithmetic)]
+fn assign_continents(
+    labels: &Labels,
+    definitions: &mut Definitions,
+    continents: &Continents,
+) -> (usize, Vec<UnsolvedConstraint>) {
+    let count = continents.continents.len();
+    let is_valid = |d: &Definition| (1..=count).contains(&d.continent.0);
+    let missing = definitions
+        .definitions
+        .values()
+        .filter(|d| d.id.0 != 0_i32 && d.province_type == ProvinceType::Land && !is_valid(d))
+        .map(|d| d.id)
+        .collect::<HashSet<_>>();
+    if missing.is_empty() {
+        return (0, Vec::new());
+    }
+
+    let mut neighbors: HashMap<ProvinceId, HashSet<ProvinceId>> = HashMap::new();
+    for (index, label) in labels.pixels.iter().enumerate() {
+        if let Some(id) = label {
+            for (_n, neighbor) in labels.neighbors(index) {
+                if let Some(neighbor) = neighbor.filter(|n| n != id) {
+                    neighbors.entry(*id).or_default().insert(neighbor);
+                }
+            }
+        }
+    }
+    let mut continent_of = definitions
+        .definitions
+        .values()
+        .filter(|d| d.id.0 != 0_i32 && d.province_type == ProvinceType::Land && is_valid(d))
+        .map(|d| (d.id, d.continent))
+        .collect::<HashMap<_, _>>();
+    let mut starts = continent_of.keys().copied().collect::<Vec<_>>();
+    starts.sort_unstable();
+    let mut queue = starts.into_iter().collect::<VecDeque<_>>();
+    let mut visited = queue.iter().copied().collect::<HashSet<_>>();
+    while let Some(id) = queue.pop_front() {
+        let continent = continent_of.get(&id).copied();
+        let mut next = neighbors
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        next.sort_unstable();
+        for neighbor in next {
+            if visited.insert(neighbor) {
+                if let Some(c) = continent {
+                    continent_of.insert(neighbor, c);
+                }
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    let mut assigned = 0;
+    let mut unsolved = Vec::new();
+    let mut missing = missing.into_iter().collect::<Vec<_>>();
+    missing.sort_unstable();
+    for id in missing {
+        let continent = continent_of.get(&id).copied();
+        match (definitions.definitions.get_mut(&id), continent) {
+            (Some(definition), Some(c)) => {
+                definition.continent = c;
+                assigned += 1;
+            }
+            _ => unsolved.push(UnsolvedConstraint::MissingContinent(id)),
+        }
+    }
+    (assigned, unsolved)
+}
+
+/// Renumbers the provinces in order of their ids so that the ids count up from 1 without gaps.
+/// The province 0 keeps its id.
+#[allow(clippy::integer_arithmetic)]
+fn renumber(definitions: &mut Definitions) {
+    let mut provinces = definitions
+        .definitions
+        .drain()
+        .map(|(_id, definition)| definition)
+        .collect::<Vec<_>>();
+    provinces.sort_unstable_by_key(|definition| definition.id);
+    let mut next = 1;
+    for mut definition in provinces {
+        if definition.id.0 != 0_i32 {
+            definition.id = ProvinceId(next);
+            next += 1;
+        }
+        definitions.definitions.insert(definition.id, definition);
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+
+    fn definition(id: i32, province_type: ProvinceType, continent: usize) -> Definition {
+        DefinitionBuilder::new(id)
+            .color(Rgb([id as u8 * 40, 0, 0]))
+            .province_type(province_type)
+            .continent(continent)
+            .build()
+    }
+
+    fn color(id: i32) -> Rgb<u8> {
+        Rgb([id as u8 * 40, 0, 0])
+    }
+
+    fn definitions(list: Vec<Definition>) -> Definitions {
+        Definitions {
+            definitions: list.into_iter().map(|d| (d.id, d)).collect(),
+            terrain: HashSet::new(),
+        }
+    }
+
+    fn continents() -> Continents {
+        Continents {
+            continents: vec![Continent("continent_1".to_owned())],
+        }
+    }
+
+    #[test]
+    fn it_fixes_a_corner_where_four_provinces_meet() {
+        let mut provinces = RgbImage::from_fn(32, 32, |x, y| match (x < 16, y < 16) {
+            (true, true) => color(1),
+            (false, true) => color(2),
+            (true, false) => color(3),
+            (false, false) => color(4),
+        });
+        let mut definitions = definitions(
+            (1..=4)
+                .map(|id| definition(id, ProvinceType::Land, 1))
+                .collect(),
+        );
+        let report =
+            ConstraintSolver::default().solve(&mut provinces, &mut definitions, &continents());
+        assert_eq!(report.x_crossings_fixed, 1);
+        assert!(report.unsolved.is_empty(), "{:?}", report.unsolved);
+        assert_eq!(*provinces.get_pixel(16, 15), color(1));
+    }
+
+    #[test]
+    fn it_merges_small_provinces_and_renumbers_the_rest() {
+        let mut provinces = RgbImage::from_fn(32, 32, |x, y| {
+            if x == 20 && y == 20 {
+                color(2)
+            } else if x < 16 {
+                color(1)
+            } else {
+                color(3)
+            }
+        });
+        let mut definitions = definitions(
+            (1..=3)
+                .map(|id| definition(id, ProvinceType::Land, 1))
+                .collect(),
+        );
+        let report =
+            ConstraintSolver::default().solve(&mut provinces, &mut definitions, &continents());
+        assert_eq!(report.provinces_merged, 1);
+        assert_eq!(*provinces.get_pixel(20, 20), color(3));
+        let mut ids = definitions.definitions.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        assert_eq!(ids, vec![ProvinceId(1), ProvinceId(2)]);
+        assert_eq!(definitions.definitions[&ProvinceId(2)].r, Red(120));
+    }
+
+    #[test]
+    fn it_joins_far_flung_pieces_to_their_neighbors() {
+        let mut provinces = RgbImage::from_fn(32, 32, |x, y| {
+            if x < 16 || (x >= 28 && y >= 28) {
+                color(1)
+            } else {
+                color(2)
+            }
+        });
+        let mut definitions = definitions(
+            (1..=2)
+                .map(|id| definition(id, ProvinceType::Land, 1))
+                .collect(),
+        );
+        let report =
+            ConstraintSolver::default().solve(&mut provinces, &mut definitions, &continents());
+        assert_eq!(report.pixels_reassigned, 16);
+        assert_eq!(*provinces.get_pixel(30, 30), color(2));
+    }
+
+    #[test]
+    fn it_reports_pieces_without_a_neighbor_of_the_same_type() {
+        let mut provinces = RgbImage::from_fn(32, 32, |x, y| {
+            if x < 8 || (x >= 28 && y >= 28) {
+                color(1)
+            } else {
+                color(2)
+            }
+        });
+        let mut definitions = definitions(vec![
+            definition(1, ProvinceType::Land, 1),
+            definition(2, ProvinceType::Sea, 0),
+        ]);
+        let report =
+            ConstraintSolver::default().solve(&mut provinces, &mut definitions, &continents());
+        assert_eq!(
+            report.unsolved,
+            vec![UnsolvedConstraint::DiscontiguousProvince(ProvinceId(1))]
+        );
+    }
+
+    #[test]
+    fn it_gives_land_provinces_the_continent_of_their_neighbors() {
+        let mut provinces =
+            RgbImage::from_fn(32, 32, |x, _y| if x < 16 { color(1) } else { color(2) });
+        let mut definitions = definitions(vec![
+            definition(1, ProvinceType::Land, 1),
+            definition(2, ProvinceType::Land, 0),
+        ]);
+        let report =
+            ConstraintSolver::default().solve(&mut provinces, &mut definitions, &continents());
+        assert_eq!(report.continents_assigned, 1);
+        assert_eq!(
+            definitions.definitions[&ProvinceId(2)].continent,
+            ContinentIndex(1)
+        );
+    }
+}
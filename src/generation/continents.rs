@@ -0,0 +1,243 @@
+use crate::components::continent::Continents;
+use crate::components::prelude::*;
+use crate::generation::{measure_provinces, ProvinceInfo};
+use crate::topology::Wrapping;
+use image::RgbImage;
+use std::collections::{HashMap, HashSet};
+
+/// Assigns land provinces to continents from the landmasses they belong to.
+///
+/// Neighboring land provinces form a landmass.  Each landmass of at least `min_landmass_size`
+/// pixels becomes a continent, with the largest landmass first.  Smaller landmasses join the
+/// continent of the nearest landmass that is large enough, so that islands belong to the continent
+/// off whose coast they lie.  If no landmass is large enough, the largest becomes the only
+/// continent.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ContinentGenerator {
+    /// The smallest landmass in pixels that becomes a continent of its own
+    pub min_landmass_size: u32,
+    /// Whether landmasses continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for ContinentGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+/// A group of neighboring land provinces
+#[derive(Clone, Debug)]
+struct Landmass {
+    /// The provinces of the landmass
+    provinces: Vec<ProvinceId>,
+    /// The number of pixels in the landmass
+    pixels: u32,
+    /// The x coordinate of the center of the landmass
+    x: f64,
+    /// The y coordinate of the center of the landmass
+    y: f64,
+}
+
+impl ContinentGenerator {
+    /// Creates a new continent generator
+    #[inline]
+    #[must_use]
+    pub const fn new(min_landmass_size: u32) -> Self {
+        Self {
+            min_landmass_size,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Sets the continent of each province in the definitions, returning the continents.  The
+    /// continents are named `continent_<index>`, with indices counting up from 1.  Sea and lake
+    /// provinces are given the continent 0, and the province 0 is left as it is.
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn generate(&self, provinces: &RgbImage, definitions: &mut Definitions) -> Continents {
+        let land = measure_provinces(provinces, definitions, self.wrapping, |d| {
+            d.province_type == ProvinceType::Land
+        });
+        let width = f64::from(provinces.width());
+        let landmasses = self.landmasses(&land, width);
+
+        let mut centers: Vec<(f64, f64)> = Vec::new();
+        let mut continent_of = HashMap::new();
+        for (index, landmass) in landmasses.iter().enumerate() {
+            let continent = if index == 0 || landmass.pixels >= self.min_landmass_size {
+                centers.push((landmass.x, landmass.y));
+                centers.len()
+            } else {
+                // Landmasses are sorted by size, so every continent exists by now
+                centers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, center)| {
+                        let distance =
+                            self.wrapping
+                                .distance(*center, (landmass.x, landmass.y), width);
+                        (i + 1, distance)
+                    })
+                    .min_by(|(_a, a), (_b, b)| a.total_cmp(b))
+                    .map_or(1, |(continent, _distance)| continent)
+            };
+            for id in &landmass.provinces {
+                continent_of.insert(*id, continent);
+            }
+        }
+
+        for definition in definitions.definitions.values_mut() {
+            if definition.id.0 == 0_i32 {
+                continue;
+            }
+            definition.continent = if definition.province_type == ProvinceType::Land {
+                // Land provinces without any pixels join the first continent
+                ContinentIndex(continent_of.get(&definition.id).copied().unwrap_or(1))
+            } else {
+                ContinentIndex(0)
+            };
+        }
+
+        Continents {
+            continents: (1..=centers.len())
+                .map(|i| Continent(format!("continent_{i}")))
+                .collect(),
+        }
+    }
+
+    /// Groups the land provinces into landmasses, sorted from largest to smallest
+    fn landmasses(self, land: &HashMap<ProvinceId, ProvinceInfo>, width: f64) -> Vec<Landmass> {
+        let mut ids = land.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let mut assigned = HashSet::new();
+        let mut landmasses = Vec::new();
+        for start in ids {
+            if !assigned.insert(start) {
+                continue;
+            }
+            let mut stack = vec![start];
+            let mut members = Vec::new();
+            while let Some(id) = stack.pop() {
+                members.push(id);
+                for neighbor in land
+                    .get(&id)
+                    .map(|info| &info.neighbors)
+                    .into_iter()
+                    .flatten()
+                {
+                    if assigned.insert(*neighbor) {
+                        stack.push(*neighbor);
+                    }
+                }
+            }
+            landmasses.push(self.measure(members, land, width));
+        }
+        landmasses.sort_by(|a, b| {
+            b.pixels
+                .cmp(&a.pixels)
+                .then(a.provinces.first().cmp(&b.provinces.first()))
+        });
+        landmasses
+    }
+
+    /// Finds the size and center of a landmass, weighting each province by its pixels
+    fn measure(
+        self,
+        mut provinces: Vec<ProvinceId>,
+        land: &HashMap<ProvinceId, ProvinceInfo>,
+        width: f64,
+    ) -> Landmass {
+        provinces.sort_unstable();
+        let members = provinces.iter().filter_map(|id| land.get(id));
+        let reference = members.clone().next().map_or(0.0_f64, |info| info.x);
+        let (mut pixels, mut sum_x, mut sum_y) = (0_u32, 0.0_f64, 0.0_f64);
+        for info in members {
+            let weight = f64::from(info.pixels);
+            pixels = pixels.saturating_add(info.pixels);
+            sum_x += self.wrapping.delta_x(reference, info.x, width) * weight;
+            sum_y += info.y * weight;
+        }
+        let total = f64::from(pixels.max(1));
+        Landmass {
+            provinces,
+            pixels,
+            x: self.wrapping.wrap_x(reference + sum_x / total, width),
+            y: sum_y / total,
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::provinces::ProvinceGenerator;
+    use image::Rgb;
+
+    /// A heightmap with a large continent on the left, a smaller one on the right and an island
+    /// just off the coast of the smaller one
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |x, y| {
+            let island = (230..240).contains(&x) && (20..30).contains(&y);
+            let small = (150..220).contains(&x) && (20..100).contains(&y);
+            if x < 100 || small || island {
+                Rgb([150, 150, 150])
+            } else {
+                Rgb([50, 50, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn it_gives_each_large_landmass_a_continent() {
+        let mut generated = ProvinceGenerator::new(1, 40)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let continents = ContinentGenerator::new(2_000)
+            .generate(&generated.provinces, &mut generated.definitions);
+        assert_eq!(
+            continents.continents,
+            vec![
+                Continent("continent_1".to_owned()),
+                Continent("continent_2".to_owned())
+            ]
+        );
+        let land = measure_provinces(
+            &generated.provinces,
+            &generated.definitions,
+            Wrapping::None,
+            |d| d.province_type == ProvinceType::Land,
+        );
+        for definition in generated.definitions.definitions.values() {
+            if definition.id.0 == 0 {
+                continue;
+            }
+            let expected = match land.get(&definition.id) {
+                Some(info) if info.x < 100.0 => ContinentIndex(1),
+                Some(_) => ContinentIndex(2),
+                None => ContinentIndex(0),
+            };
+            assert_eq!(definition.continent, expected, "{}", definition.id);
+        }
+    }
+
+    #[test]
+    fn it_makes_one_continent_when_no_landmass_is_large_enough() {
+        let mut generated = ProvinceGenerator::new(1, 40)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let continents = ContinentGenerator::new(u32::MAX)
+            .generate(&generated.provinces, &mut generated.definitions);
+        assert_eq!(continents.continents.len(), 1);
+        assert!(generated
+            .definitions
+            .definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .all(|d| d.continent == ContinentIndex(1)));
+    }
+}
@@ -0,0 +1,268 @@
+use crate::generation::noise::{Fractal, Noise, NoiseKind};
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+
+/// The number of buckets used to find the height of the ocean level
+const HISTOGRAM_BUCKETS: usize = 1024;
+
+/// Generates greyscale heightmaps from layered noise.
+/// Heights below `SEA_LEVEL` are under water, and the fraction of the map under water is set by
+/// the ocean level.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct HeightmapGenerator {
+    /// The seed for the noise
+    pub seed: u64,
+    /// The kind of noise used for the terrain
+    pub noise: NoiseKind,
+    /// The layering of the noise used for the terrain
+    pub fractal: Fractal,
+    /// The fraction of the map below sea level, from 0.0 to 1.0
+    pub ocean_level: f64,
+    /// How much of the land is shaped by ridged noise, forming mountain ranges, from 0.0 to 1.0
+    pub ridging: f64,
+}
+
+impl Default for HeightmapGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl HeightmapGenerator {
+    /// Creates a new heightmap generator with the default settings
+    #[inline]
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            noise: NoiseKind::default(),
+            fractal: Fractal::default(),
+            ocean_level: 0.6,
+            ridging: 0.5,
+        }
+    }
+
+    /// Generates a heightmap of the given size
+    /// # Errors
+    /// * If the width or height is not a positive multiple of 256
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn generate(&self, width: u32, height: u32) -> Result<RgbImage, MapError> {
+        if width == 0 || height == 0 || width % 256 != 0 || height % 256 != 0 {
+            return Err(MapError::InvalidMapSize(width, height));
+        }
+        let terrain = Noise::new(self.noise, self.seed);
+        // The ridges use a different seed so that they do not line up with the coasts.
+        let ridges = Noise::new(self.noise, self.seed.wrapping_add(1));
+        let ridging = self.ridging.clamp(0.0, 1.0);
+
+        let elevations = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (f64::from(x), f64::from(y))))
+            .map(|(x, y)| {
+                let base = terrain.fbm(x, y, &self.fractal).mul_add(0.5, 0.5);
+                let ridge = ridges.ridged(x, y, &self.fractal);
+                (base, ridge)
+            })
+            .collect::<Vec<_>>();
+
+        let ocean_level = ocean_threshold(
+            elevations.iter().map(|(base, _)| *base),
+            elevations.len(),
+            self.ocean_level,
+        );
+
+        let sea_level = f64::from(SEA_LEVEL);
+        let pixels = elevations.into_iter().map(|(base, ridge)| {
+            let value = if base < ocean_level {
+                // The sea floor falls away from the coast towards the deepest point
+                let depth: f64 = if ocean_level > 0.0 {
+                    base / ocean_level
+                } else {
+                    0.0
+                };
+                depth * (sea_level - 1.0_f64)
+            } else {
+                let elevation: f64 = if ocean_level < 1.0 {
+                    (base - ocean_level) / (1.0 - ocean_level)
+                } else {
+                    0.0
+                };
+                // Mountains rise further from the coast
+                let mountains = ridge * elevation.sqrt();
+                let land = (mountains - elevation).mul_add(ridging, elevation);
+                land.mul_add(255.0 - sea_level, sea_level)
+            };
+            value.round().clamp(0.0, 255.0) as u8
+        });
+
+        let mut image = RgbImage::new(width, height);
+        for (pixel, value) in image.pixels_mut().zip(pixels) {
+            *pixel = Rgb([value, value, value]);
+        }
+        Ok(image)
+    }
+}
+
+/// The colors of the land from sea level up to the highest height, spread evenly over the heights
+/// between them
+const LAND_RAMP: [Rgb<u8>; 5] = [
+    Rgb([64, 128, 64]),
+    Rgb([160, 184, 96]),
+    Rgb([208, 176, 112]),
+    Rgb([144, 104, 72]),
+    Rgb([244, 244, 244]),
+];
+
+/// The colors of the sea from the deepest height up to just below sea level
+const SEA_RAMP: [Rgb<u8>; 2] = [Rgb([16, 32, 96]), Rgb([96, 160, 216])];
+
+/// Colors a greyscale heightmap by height, so that elevation can be read at a glance.  Heights
+/// below `sea_level` are shaded in blues that lighten towards the coast, and the land runs from
+/// green lowlands through browns to white peaks.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn hypsometric_tint(heightmap: &RgbImage, sea_level: u8) -> RgbImage {
+    let colors = (0..=u8::MAX)
+        .map(|height| {
+            if height < sea_level {
+                ramp_color(&SEA_RAMP, f64::from(height) / f64::from(sea_level))
+            } else {
+                let land = f64::from(height - sea_level);
+                let span = f64::from(u8::MAX - sea_level).max(1.0);
+                ramp_color(&LAND_RAMP, land / span)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut tinted = RgbImage::new(heightmap.width(), heightmap.height());
+    // The pixels are colored in parallel, as the largest maps have tens of millions of them
+    tinted
+        .par_chunks_exact_mut(3)
+        .zip(heightmap.par_chunks_exact(3))
+        .for_each(|(pixel, height)| {
+            let color = height
+                .first()
+                .and_then(|h| colors.get(usize::from(*h)))
+                .copied()
+                .unwrap_or(Rgb([0, 0, 0]));
+            pixel.copy_from_slice(&color.0);
+        });
+    tinted
+}
+
+/// The color at a fraction from 0.0 to 1.0 along a ramp of evenly spaced colors
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn ramp_color(ramp: &[Rgb<u8>], fraction: f64) -> Rgb<u8> {
+    let position = fraction.clamp(0.0, 1.0) * ramp.len().saturating_sub(1) as f64;
+    let lower = position.floor() as usize;
+    let weight = position - position.floor();
+    match (ramp.get(lower), ramp.get(lower.saturating_add(1))) {
+        (Some(from), Some(to)) => {
+            let mut color = *from;
+            for (channel, (a, b)) in color.0.iter_mut().zip(from.0.iter().zip(to.0)) {
+                let value = (f64::from(b) - f64::from(*a)).mul_add(weight, f64::from(*a));
+                *channel = value.round().clamp(0.0, 255.0) as u8;
+            }
+            color
+        }
+        (Some(color), None) => *color,
+        _ => Rgb([0, 0, 0]),
+    }
+}
+
+/// Finds the elevation below which the given fraction of the elevations lie.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn ocean_threshold(elevations: impl Iterator<Item = f64>, count: usize, fraction: f64) -> f64 {
+    let mut histogram = vec![0_usize; HISTOGRAM_BUCKETS];
+    for elevation in elevations {
+        let bucket = ((elevation.clamp(0.0, 1.0) * (HISTOGRAM_BUCKETS - 1) as f64) as usize)
+            .min(HISTOGRAM_BUCKETS - 1);
+        if let Some(b) = histogram.get_mut(bucket) {
+            *b += 1;
+        }
+    }
+    let target = (count as f64 * fraction.clamp(0.0, 1.0)) as usize;
+    let mut seen = 0;
+    for (bucket, bucket_count) in histogram.iter().enumerate() {
+        if seen >= target {
+            return bucket as f64 / (HISTOGRAM_BUCKETS - 1) as f64;
+        }
+        seen += bucket_count;
+    }
+    1.0
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_generates_a_heightmap_with_the_requested_ocean_level() {
+        let generator = HeightmapGenerator::new(1234);
+        let heightmap = generator
+            .generate(512, 256)
+            .expect("Failed to generate heightmap");
+        assert_eq!(heightmap.dimensions(), (512, 256));
+        assert!(heightmap
+            .pixels()
+            .all(|p| p.0[0] == p.0[1] && p.0[1] == p.0[2]));
+        let below = heightmap.pixels().filter(|p| p.0[0] < SEA_LEVEL).count();
+        let fraction = below as f64 / f64::from(512 * 256);
+        assert!((fraction - generator.ocean_level).abs() < 0.02);
+    }
+
+    #[test]
+    fn it_generates_the_same_heightmap_from_the_same_seed() {
+        let generator = HeightmapGenerator::new(99);
+        let first = generator.generate(256, 256).expect("Failed to generate");
+        let second = generator.generate(256, 256).expect("Failed to generate");
+        assert_eq!(first, second);
+        let other = HeightmapGenerator::new(100)
+            .generate(256, 256)
+            .expect("Failed to generate");
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn it_tints_heights_below_sea_level_blue() {
+        let heightmap = RgbImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgb([0, 0, 0]),
+            1 => Rgb([94, 94, 94]),
+            _ => Rgb([255, 255, 255]),
+        });
+        let tinted = hypsometric_tint(&heightmap, SEA_LEVEL);
+        assert_eq!(*tinted.get_pixel(0, 0), SEA_RAMP[0]);
+        let shallows = tinted.get_pixel(1, 0);
+        assert!(shallows.0[2] > shallows.0[0] && shallows.0[2] > shallows.0[1]);
+        assert_eq!(*tinted.get_pixel(2, 0), LAND_RAMP[4]);
+
+        let raised = hypsometric_tint(&heightmap, 90);
+        let lowlands = raised.get_pixel(1, 0);
+        assert!(lowlands.0[1] > lowlands.0[0] && lowlands.0[1] > lowlands.0[2]);
+    }
+
+    #[test]
+    fn it_rejects_sizes_that_are_not_multiples_of_256() {
+        let generator = HeightmapGenerator::default();
+        assert!(generator.generate(300, 256).is_err());
+        assert!(generator.generate(0, 256).is_err());
+    }
+}
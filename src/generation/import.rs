@@ -0,0 +1,592 @@
+use crate::components::prelude::*;
+use crate::generation::provinces::ProvinceGenerator;
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::{ErrorContext, MapError};
+use image::{ImageBuffer, Luma, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A greyscale image with 16 bit samples
+type HeightfieldImage = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+/// A closed outline of longitudes and latitudes
+type GeoRing = Vec<(f64, f64)>;
+
+/// The area of the Earth covered by a map or heightfield, in degrees of longitude and latitude
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct GeoBounds {
+    /// The longitude of the left edge
+    pub west: f64,
+    /// The latitude of the bottom edge
+    pub south: f64,
+    /// The longitude of the right edge
+    pub east: f64,
+    /// The latitude of the top edge
+    pub north: f64,
+}
+
+impl Default for GeoBounds {
+    /// The whole Earth, as the outlines exported to GeoJSON are placed
+    #[inline]
+    fn default() -> Self {
+        Self::new(-180.0, -90.0, 180.0, 90.0)
+    }
+}
+
+impl GeoBounds {
+    /// Creates the bounds of an area
+    #[inline]
+    #[must_use]
+    pub const fn new(west: f64, south: f64, east: f64, north: f64) -> Self {
+        Self {
+            west,
+            south,
+            east,
+            north,
+        }
+    }
+
+    /// The point of an image of the given size covering the bounds that a longitude and latitude
+    /// fall on, in pixels from the top left corner
+    fn project(
+        &self,
+        (longitude, latitude): (f64, f64),
+        (width, height): (u32, u32),
+    ) -> (f64, f64) {
+        (
+            (longitude - self.west) / (self.east - self.west) * f64::from(width),
+            (self.north - latitude) / (self.north - self.south) * f64::from(height),
+        )
+    }
+
+    /// The longitude and latitude at the center of a pixel of an image of the given size
+    /// covering the bounds
+    fn unproject(&self, (x, y): (u32, u32), (width, height): (u32, u32)) -> (f64, f64) {
+        (
+            ((f64::from(x) + 0.5) / f64::from(width)).mul_add(self.east - self.west, self.west),
+            ((f64::from(y) + 0.5) / f64::from(height)).mul_add(self.south - self.north, self.north),
+        )
+    }
+
+    /// Whether the bounds cover any area
+    fn is_valid(&self) -> bool {
+        self.east > self.west && self.north > self.south
+    }
+}
+
+/// The settings for importing a map from real-world data, usually read from a TOML file such as
+///
+/// ```toml
+/// heightfield = "europe.tif"
+/// regions = "europe_admin.geojson"
+/// width = 4096
+/// height = 2048
+///
+/// [bounds]
+/// west = -25.0
+/// south = 34.0
+/// east = 45.0
+/// north = 72.0
+/// ```
+///
+/// Any setting left out takes its default.  The map and the heightfield are both taken to be in
+/// the equirectangular projection, where longitude and latitude are spread evenly across the
+/// image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ImportConfig {
+    /// The greyscale heightfield, such as a GeoTIFF with 8 or 16 bit unsigned samples.  Its
+    /// georeferencing is not read, the area it covers is set by `heightfield_bounds`.
+    pub heightfield: PathBuf,
+    /// The GeoJSON of the administrative regions, each feature with a polygon or multipolygon
+    /// becoming a province.  Shapefiles can be converted to GeoJSON with `ogr2ogr`.
+    pub regions: PathBuf,
+    /// The width of the map in pixels, a multiple of 256
+    pub width: u32,
+    /// The height of the map in pixels, a multiple of 256
+    pub height: u32,
+    /// The area of the Earth the map covers
+    pub bounds: GeoBounds,
+    /// The area of the Earth the heightfield covers, the bounds of the map if it is left out
+    pub heightfield_bounds: Option<GeoBounds>,
+    /// The elevation in metres of the darkest sample of the heightfield
+    pub heightfield_min: f64,
+    /// The elevation in metres of the brightest sample of the heightfield
+    pub heightfield_max: f64,
+    /// The elevation in metres of the sea, at or below which the map is under water
+    pub sea_level: f64,
+    /// The elevation in metres drawn white on the heightmap, higher ground is drawn white too
+    pub summit: f64,
+    /// The elevation in metres drawn black on the heightmap, lower ground is drawn black too
+    pub sea_floor: f64,
+    /// The seed for the placement of the sea provinces and the colors of the provinces
+    pub seed: u64,
+    /// The smallest number of pixels in a province, smaller provinces are merged into a neighbor
+    pub min_province_size: u32,
+}
+
+impl Default for ImportConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            heightfield: PathBuf::from("heightfield.tif"),
+            regions: PathBuf::from("regions.geojson"),
+            width: 2048,
+            height: 1024,
+            bounds: GeoBounds::default(),
+            heightfield_bounds: None,
+            // A 16 bit sample for each metre above sea level
+            heightfield_min: 0.0,
+            heightfield_max: f64::from(u16::MAX),
+            sea_level: 0.0,
+            summit: 5000.0,
+            sea_floor: -5000.0,
+            seed: 0,
+            min_province_size: ProvinceGenerator::default().min_province_size,
+        }
+    }
+}
+
+impl ImportConfig {
+    /// Reads the config from a TOML file.  The heightfield and regions are found relative to the
+    /// directory of the file.
+    /// # Errors
+    /// * If the file cannot be read or is not a valid config
+    #[inline]
+    pub fn from_file(path: &Path) -> Result<Self, MapError> {
+        let data = fs::read_to_string(path).in_file(path)?;
+        let mut config: Self = toml::from_str(&data).in_file(path)?;
+        if let Some(directory) = path.parent() {
+            config.heightfield = directory.join(&config.heightfield);
+            config.regions = directory.join(&config.regions);
+        }
+        Ok(config)
+    }
+}
+
+/// The files of a map imported from real-world data, before they are written to a mod directory
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ImportedMap {
+    /// The heightmap, resampled from the heightfield
+    pub heightmap: RgbImage,
+    /// The provinces image, with a province for each administrative region
+    pub provinces: RgbImage,
+    /// The definitions of the provinces
+    pub definitions: Definitions,
+}
+
+impl ImportedMap {
+    /// Writes heightmap.bmp, provinces.bmp and definition.csv to the map directory of the given
+    /// root directory
+    /// # Errors
+    /// * If any of the files could not be written
+    #[inline]
+    pub fn save(&self, root_path: &Path) -> Result<(), MapError> {
+        let map = root_path.join("map");
+        fs::create_dir_all(&map)?;
+        self.heightmap.save(map.join("heightmap.bmp"))?;
+        self.provinces.save(map.join("provinces.bmp"))?;
+        self.definitions.to_file(&map.join("definition.csv"))?;
+        Ok(())
+    }
+}
+
+/// Imports a map from a heightfield and the administrative regions of real-world data.
+///
+/// The heightfield is resampled to the size of the map and its elevations drawn on the heightmap,
+/// with the sea level at `SEA_LEVEL`.  The regions are drawn onto the map and each becomes a land
+/// province, with the sea partitioned into provinces around them by a `ProvinceGenerator`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct GisImporter {
+    /// The settings of the import
+    pub config: ImportConfig,
+}
+
+impl GisImporter {
+    /// Creates a new importer
+    #[inline]
+    #[must_use]
+    pub const fn new(config: ImportConfig) -> Self {
+        Self { config }
+    }
+
+    /// Imports the map and writes its heightmap, provinces and definitions to the given root
+    /// directory
+    /// # Errors
+    /// * If the map could not be imported
+    /// * If any of the files could not be written
+    #[inline]
+    pub fn import(&self, root_path: &Path) -> Result<ImportedMap, MapError> {
+        let imported = self.build()?;
+        imported.save(root_path)?;
+        Ok(imported)
+    }
+
+    /// Imports the map without writing it
+    /// # Errors
+    /// * If the width or height is not a positive multiple of 256
+    /// * If the bounds or elevations cover nothing
+    /// * If the heightfield cannot be read
+    /// * If the regions cannot be read or are not valid GeoJSON
+    #[inline]
+    pub fn build(&self) -> Result<ImportedMap, MapError> {
+        let config = &self.config;
+        let (width, height) = (config.width, config.height);
+        if width == 0 || height == 0 || width % 256 != 0 || height % 256 != 0 {
+            return Err(MapError::InvalidMapSize(width, height));
+        }
+        let heightfield_bounds = config.heightfield_bounds.unwrap_or(config.bounds);
+        if !config.bounds.is_valid() || !heightfield_bounds.is_valid() {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The bounds must be wider and taller than nothing".to_owned(),
+            ));
+        }
+        if config.heightfield_max <= config.heightfield_min
+            || config.summit <= config.sea_level
+            || config.sea_floor >= config.sea_level
+        {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The elevations must rise from the sea floor through the sea level to the summit"
+                    .to_owned(),
+            ));
+        }
+
+        let heightfield = image::open(&config.heightfield)
+            .in_file(&config.heightfield)?
+            .to_luma16();
+        let heightmap = self.resample(&heightfield, heightfield_bounds);
+        let data = fs::read_to_string(&config.regions).in_file(&config.regions)?;
+        let geojson: Value = serde_json::from_str(&data).in_file(&config.regions)?;
+        let outlines = read_regions(&geojson).in_file(&config.regions)?;
+        let regions = rasterize(&outlines, &config.bounds, (width, height));
+
+        let mut province_generator = ProvinceGenerator::new(config.seed, 0);
+        province_generator.min_province_size = config.min_province_size;
+        let generated = province_generator.generate_from_regions(&heightmap, &regions)?;
+        Ok(ImportedMap {
+            heightmap,
+            provinces: generated.provinces,
+            definitions: generated.definitions,
+        })
+    }
+
+    /// Draws the heightmap from the elevations of the heightfield at the center of each pixel of
+    /// the map.  The map outside the heightfield is under the sea.
+    fn resample(&self, heightfield: &HeightfieldImage, bounds: GeoBounds) -> RgbImage {
+        let config = &self.config;
+        let size = (config.width, config.height);
+        RgbImage::from_fn(config.width, config.height, |x, y| {
+            let point = config.bounds.unproject((x, y), size);
+            let (u, v) = bounds.project(point, heightfield.dimensions());
+            let elevation = sample(heightfield, u, v).map_or(config.sea_floor, |s| {
+                (config.heightfield_max - config.heightfield_min).mul_add(s, config.heightfield_min)
+            });
+            let value = self.heightmap_value(elevation);
+            Rgb([value, value, value])
+        })
+    }
+
+    /// The value an elevation is drawn with on the heightmap, with the ground above the sea level
+    /// at or above `SEA_LEVEL` and the sea below it
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn heightmap_value(&self, elevation: f64) -> u8 {
+        let config = &self.config;
+        let land = f64::from(u8::MAX.saturating_sub(SEA_LEVEL));
+        let sea = f64::from(SEA_LEVEL.saturating_sub(1));
+        if elevation > config.sea_level {
+            let fraction = (elevation - config.sea_level) / (config.summit - config.sea_level);
+            SEA_LEVEL.saturating_add((fraction.min(1.0) * land).round() as u8)
+        } else {
+            let fraction = (config.sea_level - elevation) / (config.sea_level - config.sea_floor);
+            ((1.0 - fraction.min(1.0)) * sea).round() as u8
+        }
+    }
+}
+
+/// The sample of the heightfield at a point, from 0.0 to 1.0, blended between the four samples
+/// around it.  Points outside the heightfield have no sample.
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn sample(heightfield: &HeightfieldImage, u: f64, v: f64) -> Option<f64> {
+    let (width, height) = heightfield.dimensions();
+    if !(0.0..f64::from(width)).contains(&u) || !(0.0..f64::from(height)).contains(&v) {
+        return None;
+    }
+    // Samples are at the centers of the pixels of the heightfield
+    let (column, row) = ((u - 0.5).max(0.0), (v - 0.5).max(0.0));
+    let (x, y) = (column.floor() as u32, row.floor() as u32);
+    let (next_x, next_y) = (
+        x.saturating_add(1).min(width.saturating_sub(1)),
+        y.saturating_add(1).min(height.saturating_sub(1)),
+    );
+    let at = |sample_x: u32, sample_y: u32| {
+        f64::from(heightfield.get_pixel(sample_x, sample_y).0[0]) / f64::from(u16::MAX)
+    };
+    let (fx, fy) = (column.fract(), row.fract());
+    let top = (at(next_x, y) - at(x, y)).mul_add(fx, at(x, y));
+    let bottom = (at(next_x, next_y) - at(x, next_y)).mul_add(fx, at(x, next_y));
+    Some((bottom - top).mul_add(fy, top))
+}
+
+/// Reads the outlines of each region of a GeoJSON feature collection, feature or geometry.  The
+/// rings of the polygons of a region are kept together, the holes among them.  Features without
+/// a polygon or multipolygon are left out.
+fn read_regions(geojson: &Value) -> Result<Vec<Vec<GeoRing>>, MapError> {
+    let geometries: Vec<&Value> = match geojson.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => geojson
+            .get("features")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("A feature collection has no features"))?
+            .iter()
+            .filter_map(|feature| feature.get("geometry"))
+            .collect(),
+        Some("Feature") => geojson.get("geometry").into_iter().collect(),
+        _ => vec![geojson],
+    };
+    let mut regions = Vec::new();
+    for geometry in geometries {
+        let coordinates = geometry.get("coordinates");
+        let polygons = match geometry.get("type").and_then(Value::as_str) {
+            Some("Polygon") => vec![coordinates],
+            Some("MultiPolygon") => coordinates
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("A multipolygon has no polygons"))?
+                .iter()
+                .map(Some)
+                .collect(),
+            _ => continue,
+        };
+        let mut rings = Vec::new();
+        for polygon in polygons {
+            for ring in polygon
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("A polygon has no rings"))?
+            {
+                rings.push(read_ring(ring)?);
+            }
+        }
+        regions.push(rings);
+    }
+    Ok(regions)
+}
+
+/// Reads the longitudes and latitudes of a ring of a polygon
+fn read_ring(ring: &Value) -> Result<GeoRing, MapError> {
+    ring.as_array()
+        .ok_or_else(|| invalid("A ring of a polygon has no positions"))?
+        .iter()
+        .map(|position| {
+            let coordinate = |i: usize| position.get(i).and_then(Value::as_f64);
+            match (coordinate(0), coordinate(1)) {
+                (Some(longitude), Some(latitude)) => Ok((longitude, latitude)),
+                _ => Err(invalid("A position has no longitude and latitude")),
+            }
+        })
+        .collect()
+}
+
+/// The error for GeoJSON that is not understood
+fn invalid(message: &str) -> MapError {
+    MapError::InvalidGeoJson(message.to_owned())
+}
+
+/// Draws the regions onto a map of the given size covering the bounds, giving the index of the
+/// region at the center of each pixel, row by row.  A pixel is inside a region when a line from
+/// it crosses the rings of the region an odd number of times, which leaves out the holes.  Where
+/// regions overlap, the later region is drawn over the earlier.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn rasterize(
+    regions: &[Vec<GeoRing>],
+    bounds: &GeoBounds,
+    (width, height): (u32, u32),
+) -> Vec<Option<u32>> {
+    let mut pixels = vec![None; width as usize * height as usize];
+    for (region, rings) in (0_u32..).zip(regions) {
+        let edges = rings
+            .iter()
+            .flat_map(|ring| {
+                let points = ring
+                    .iter()
+                    .map(|point| bounds.project(*point, (width, height)))
+                    .collect::<Vec<_>>();
+                // A ring is closed whether or not its last position repeats its first
+                let next = points
+                    .iter()
+                    .cycle()
+                    .skip(1)
+                    .take(points.len())
+                    .copied()
+                    .collect::<Vec<_>>();
+                points.into_iter().zip(next)
+            })
+            .collect::<Vec<_>>();
+        let (top, bottom) = edges
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(top, bottom), (a, b)| {
+                (top.min(a.1).min(b.1), bottom.max(a.1).max(b.1))
+            });
+        let first_row = top.max(0.0).floor() as u32;
+        let last_row = bottom.min(f64::from(height)).ceil() as u32;
+        for y in first_row..last_row.min(height) {
+            let center = f64::from(y) + 0.5;
+            let mut crossings = edges
+                .iter()
+                .filter(|(a, b)| (a.1 <= center) != (b.1 <= center))
+                .map(|(a, b)| ((center - a.1) / (b.1 - a.1)).mul_add(b.0 - a.0, a.0))
+                .collect::<Vec<_>>();
+            crossings.sort_by(f64::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                if let [start, end] = span {
+                    // The pixels whose centers lie between the crossings
+                    let first = (start - 0.5).ceil().clamp(0.0, f64::from(width)) as u32;
+                    let last = (end - 0.5).ceil().clamp(0.0, f64::from(width)) as u32;
+                    let row = y as usize * width as usize;
+                    for x in first..last {
+                        if let Some(pixel) = pixels.get_mut(row + x as usize) {
+                            *pixel = Some(region);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pixels
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    /// A square from the given corner, in degrees
+    fn square(west: f64, south: f64, side: f64) -> Value {
+        json!([
+            [west, south],
+            [west + side, south],
+            [west + side, south + side],
+            [west, south + side],
+            [west, south]
+        ])
+    }
+
+    #[test]
+    fn it_draws_regions_without_their_holes() {
+        let geojson = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "name": "Outer" },
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [square(0.0, 0.0, 8.0), square(2.0, 2.0, 4.0)]
+                    }
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "name": "Islands" },
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": [[square(10.0, 0.0, 2.0)], [square(14.0, 0.0, 2.0)]]
+                    }
+                },
+                { "type": "Feature", "geometry": { "type": "Point", "coordinates": [1.0, 1.0] } }
+            ]
+        });
+        let regions = read_regions(&geojson).expect("Failed to read regions");
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[1].len(), 2);
+
+        let bounds = GeoBounds::new(0.0, 0.0, 16.0, 8.0);
+        let pixels = rasterize(&regions, &bounds, (16, 8));
+        let at = |x: usize, y: usize| pixels[y * 16 + x];
+        assert_eq!(at(0, 0), Some(0));
+        assert_eq!(at(7, 7), Some(0));
+        assert_eq!(at(4, 4), None);
+        assert_eq!(at(8, 7), None);
+        assert_eq!(at(11, 7), Some(1));
+        assert_eq!(at(15, 6), Some(1));
+        assert_eq!(at(13, 7), None);
+        assert_eq!(pixels.iter().filter(|p| p.is_some()).count(), 64 - 16 + 8);
+    }
+
+    #[test]
+    fn it_imports_a_province_for_each_region() {
+        let dir = std::env::temp_dir().join("world_gen_import_test");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        // Land 100 metres high in the west, sea in the east
+        let heightfield: HeightfieldImage =
+            ImageBuffer::from_fn(64, 32, |x, _y| Luma([if x < 32 { 100 } else { 0 }]));
+        heightfield
+            .save(dir.join("heightfield.tif"))
+            .expect("Failed to write heightfield");
+        let regions = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Polygon", "coordinates": [square(-10.0, 0.0, 10.0)] }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Polygon", "coordinates": [square(-10.0, -10.0, 10.0)] }
+                }
+            ]
+        });
+        fs::write(dir.join("regions.geojson"), regions.to_string())
+            .expect("Failed to write regions");
+        let config_path = dir.join("import.toml");
+        fs::write(
+            &config_path,
+            "width = 256\nheight = 256\n\n\
+             [bounds]\nwest = -10.0\nsouth = -10.0\neast = 10.0\nnorth = 10.0\n",
+        )
+        .expect("Failed to write config");
+
+        let config = ImportConfig::from_file(&config_path).expect("Failed to read config");
+        let root = dir.join("mod");
+        let imported = GisImporter::new(config)
+            .import(&root)
+            .expect("Failed to import");
+        let exists = |file: &str| root.join("map").join(file).exists();
+        assert!(exists("heightmap.bmp") && exists("provinces.bmp") && exists("definition.csv"));
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+
+        assert!(imported.heightmap.get_pixel(10, 10).0[0] > SEA_LEVEL);
+        assert!(imported.heightmap.get_pixel(250, 10).0[0] < SEA_LEVEL);
+        let definitions = &imported.definitions.definitions;
+        let land = definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .count();
+        assert_eq!(land, 2);
+        assert!(definitions
+            .values()
+            .any(|d| d.province_type == ProvinceType::Sea));
+        let north = *imported.provinces.get_pixel(10, 10);
+        let south = *imported.provinces.get_pixel(10, 245);
+        assert_ne!(north, south);
+        let colors = imported.provinces.pixels().collect::<HashSet<_>>();
+        assert_eq!(colors.len(), definitions.len() - 1);
+    }
+}
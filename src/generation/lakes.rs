@@ -0,0 +1,191 @@
+use crate::components::prelude::*;
+use crate::generation::terrain::TerrainKind;
+use crate::topology::Wrapping;
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, HashSet};
+
+/// Sets the type of water provinces from the bodies of water they belong to.
+///
+/// Neighboring sea and lake provinces form a body of water.  A body of water that is enclosed by
+/// land, without reaching an edge of the map that does not wrap around, is a lake as long as it is no larger than
+/// `max_lake_size`.  Every other body of water is sea.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LakeClassifier {
+    /// The largest body of water in pixels that can be a lake
+    pub max_lake_size: u32,
+    /// Whether bodies of water continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for LakeClassifier {
+    #[inline]
+    fn default() -> Self {
+        Self::new(u32::MAX)
+    }
+}
+
+impl LakeClassifier {
+    /// Creates a new lake classifier
+    #[inline]
+    #[must_use]
+    pub const fn new(max_lake_size: u32) -> Self {
+        Self {
+            max_lake_size,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Sets each water province in the definitions to a lake or sea, returning the ids of the
+    /// provinces whose type changed.  Lakes are given the continent 0 and new lakes are given the
+    /// lakes terrain, while former lakes are given the ocean terrain.
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn classify(&self, provinces: &RgbImage, definitions: &mut Definitions) -> Vec<ProvinceId> {
+        let (width, height) = provinces.dimensions();
+        let water = definitions
+            .definitions
+            .values()
+            .filter(|d| matches!(d.province_type, ProvinceType::Sea | ProvinceType::Lake))
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.id))
+            .collect::<HashMap<_, _>>();
+        let pixel_provinces = provinces
+            .pixels()
+            .map(|p| water.get(p).copied())
+            .collect::<Vec<_>>();
+
+        // Whether each province is in a body of water that can be a lake
+        let mut enclosed: HashMap<ProvinceId, bool> = HashMap::new();
+        let mut visited = vec![false; pixel_provinces.len()];
+        let mut stack = Vec::new();
+        for start in 0..pixel_provinces.len() {
+            if visited.get(start) != Some(&false) || pixel_provinces.get(start) == Some(&None) {
+                continue;
+            }
+            if let Some(v) = visited.get_mut(start) {
+                *v = true;
+            }
+            stack.push(start);
+            let mut size = 0_u32;
+            let mut touches_edge = false;
+            let mut body = HashSet::new();
+            while let Some(i) = stack.pop() {
+                size = size.saturating_add(1);
+                if let Some(Some(id)) = pixel_provinces.get(i) {
+                    body.insert(*id);
+                }
+                let x = (i % width as usize) as u32;
+                let y = (i / width as usize) as u32;
+                if self.wrapping.is_on_edge(x, y, width, height) {
+                    touches_edge = true;
+                }
+                for n in self
+                    .wrapping
+                    .neighbors(i, width, height)
+                    .into_iter()
+                    .flatten()
+                {
+                    if pixel_provinces.get(n) == Some(&None) {
+                        continue;
+                    }
+                    if let Some(v) = visited.get_mut(n) {
+                        if !*v {
+                            *v = true;
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+            let is_lake = !touches_edge && size <= self.max_lake_size;
+            for id in body {
+                // A province split across bodies of water is only a lake if all of them are lakes
+                let entry = enclosed.entry(id).or_insert(true);
+                *entry = *entry && is_lake;
+            }
+        }
+
+        let mut changed = Vec::new();
+        for (id, is_lake) in enclosed {
+            let definition = match definitions.definitions.get_mut(&id) {
+                Some(d) => d,
+                None => continue,
+            };
+            let (province_type, terrain) = if is_lake {
+                (ProvinceType::Lake, TerrainKind::Lakes)
+            } else {
+                (ProvinceType::Sea, TerrainKind::Ocean)
+            };
+            if definition.province_type == province_type {
+                continue;
+            }
+            definition.province_type = province_type;
+            if is_lake {
+                definition.continent = ContinentIndex(0);
+            }
+            if is_lake || definition.terrain.0 == TerrainKind::Lakes.category() {
+                definition.terrain = Terrain(terrain.category().to_owned());
+            }
+            changed.push(id);
+        }
+        changed.sort();
+        changed
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+
+    fn definition(id: i32, color: u8, province_type: ProvinceType) -> Definition {
+        DefinitionBuilder::new(id)
+            .color(Rgb([color, color, color]))
+            .province_type(province_type)
+            .terrain("ocean")
+            .build()
+    }
+
+    #[test]
+    fn it_classifies_enclosed_water_as_lakes() {
+        // A sea along the right edge, and an enclosed pond typed as sea in the land on the left
+        let provinces = RgbImage::from_fn(16, 16, |x, y| {
+            if x >= 12 {
+                Rgb([2, 2, 2])
+            } else if (3..6).contains(&x) && (3..6).contains(&y) {
+                Rgb([3, 3, 3])
+            } else {
+                Rgb([1, 1, 1])
+            }
+        });
+        let mut definitions = Definitions {
+            definitions: [
+                definition(1, 1, ProvinceType::Land),
+                definition(2, 2, ProvinceType::Lake),
+                definition(3, 3, ProvinceType::Sea),
+            ]
+            .into_iter()
+            .map(|d| (d.id, d))
+            .collect(),
+            terrain: HashSet::new(),
+        };
+        let changed = LakeClassifier::default().classify(&provinces, &mut definitions);
+        assert_eq!(changed, vec![ProvinceId(2), ProvinceId(3)]);
+        let pond = &definitions.definitions[&ProvinceId(3)];
+        assert_eq!(pond.province_type, ProvinceType::Lake);
+        assert_eq!(pond.continent, ContinentIndex(0));
+        assert_eq!(pond.terrain.0, "lakes");
+        let sea = &definitions.definitions[&ProvinceId(2)];
+        assert_eq!(sea.province_type, ProvinceType::Sea);
+        assert_eq!(
+            definitions.definitions[&ProvinceId(1)].province_type,
+            ProvinceType::Land
+        );
+
+        let changed = LakeClassifier::new(4).classify(&provinces, &mut definitions);
+        assert_eq!(changed, vec![ProvinceId(3)]);
+    }
+}
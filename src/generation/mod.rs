@@ -0,0 +1,136 @@
+use crate::components::prelude::*;
+use crate::topology::Wrapping;
+use crate::MapError;
+use image::codecs::bmp::BmpEncoder;
+use image::{ColorType, GrayImage, Rgb, RgbImage};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Holds the placement of cities
+pub mod cities;
+/// Holds the fixing of conditions that crash the game after generation
+pub mod constraints;
+/// Holds the assignment of continents
+pub mod continents;
+/// Holds the generation of heightmaps
+pub mod heightmap;
+/// Holds the import of maps from real-world heightfields and administrative regions
+pub mod import;
+/// Holds the classification of lakes
+pub mod lakes;
+/// Holds the classification of naval terrain
+pub mod naval_terrain;
+/// Holds the noise functions used by the generators
+pub mod noise;
+/// Holds the generation of provinces
+pub mod provinces;
+/// Holds the generation of rivers
+pub mod rivers;
+/// Holds the generation of states
+pub mod states;
+/// Holds the generation of strategic regions
+pub mod strategic_regions;
+/// Holds the generation of supply nodes and railways
+pub mod supply;
+/// Holds the classification of terrain
+pub mod terrain;
+/// Holds the generation of trees
+pub mod trees;
+/// Holds the generation of whole worlds from a config
+pub mod world;
+
+/// The measurements of a province used to group it into a state, strategic region or continent.
+#[derive(Clone, Debug, Default)]
+struct ProvinceInfo {
+    /// The number of pixels in the province
+    pixels: u32,
+    /// The x coordinate of the center of the province
+    x: f64,
+    /// The y coordinate of the center of the province
+    y: f64,
+    /// The terrain of the province
+    terrain: Option<Terrain>,
+    /// The measured provinces sharing a border with the province
+    neighbors: BTreeSet<ProvinceId>,
+}
+
+/// Measures the provinces accepted by the filter and finds their neighbors among them.  The
+/// province 0 is never measured.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn measure_provinces<F: Fn(&Definition) -> bool>(
+    provinces: &RgbImage,
+    definitions: &Definitions,
+    wrapping: Wrapping,
+    filter: F,
+) -> HashMap<ProvinceId, ProvinceInfo> {
+    let (width, height) = provinces.dimensions();
+    let colors = definitions
+        .definitions
+        .values()
+        .filter(|d| d.id.0 != 0_i32 && filter(d))
+        .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d))
+        .collect::<HashMap<_, _>>();
+    let pixel_provinces = provinces
+        .pixels()
+        .map(|p| colors.get(p).map(|d| d.id))
+        .collect::<Vec<_>>();
+
+    // Horizontal positions are summed as offsets from the first pixel of each province, so that a
+    // province crossing the edge of a wrapping map has its center next to it.
+    let mut first_x: HashMap<ProvinceId, f64> = HashMap::new();
+    let mut measured: HashMap<ProvinceId, ProvinceInfo> = HashMap::new();
+    for (i, province) in pixel_provinces.iter().enumerate() {
+        let id = match province {
+            Some(id) => *id,
+            None => continue,
+        };
+        let x = f64::from((i % width as usize) as u32);
+        let reference = *first_x.entry(id).or_insert(x);
+        let info = measured.entry(id).or_default();
+        info.pixels += 1;
+        info.x += wrapping.delta_x(reference, x, f64::from(width));
+        info.y += f64::from((i / width as usize) as u32);
+        for n in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            if let Some(Some(neighbor)) = pixel_provinces.get(n) {
+                if *neighbor != id {
+                    info.neighbors.insert(*neighbor);
+                }
+            }
+        }
+    }
+    for definition in colors.values() {
+        if let Some(info) = measured.get_mut(&definition.id) {
+            let pixels = f64::from(info.pixels.max(1));
+            let reference = first_x.get(&definition.id).copied().unwrap_or_default();
+            info.x = wrapping.wrap_x(reference + info.x / pixels, f64::from(width));
+            info.y /= pixels;
+            info.terrain = Some(definition.terrain.clone());
+        }
+    }
+    measured
+}
+
+/// Saves the indices of an image as an 8-bit indexed BMP with a palette, the format the game
+/// reads terrain.bmp, trees.bmp and cities.bmp in
+/// # Errors
+/// * If the file could not be written
+pub(crate) fn save_indexed_bmp(
+    path: &Path,
+    indices: &GrayImage,
+    palette: &[[u8; 3]],
+) -> Result<(), MapError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    BmpEncoder::new(&mut writer).encode_with_palette(
+        indices.as_raw(),
+        indices.width(),
+        indices.height(),
+        ColorType::L8,
+        Some(palette),
+    )?;
+    writer.flush()?;
+    Ok(())
+}
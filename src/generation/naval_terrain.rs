@@ -0,0 +1,204 @@
+use crate::components::prelude::*;
+use crate::generation::terrain::TerrainKind;
+use crate::topology::Wrapping;
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, VecDeque};
+
+/// Splits sea provinces into shallow sea and deep ocean from the depth of the water in the
+/// heightmap and their distance from the coast.
+///
+/// A sea province is shallow sea if its average depth is at most `shallow_depth`, or if it is on
+/// average at most `shallow_distance` pixels from land.  All other sea provinces are deep ocean.
+/// Land and lake provinces are left as they are.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NavalTerrainClassifier {
+    /// The greatest average depth below `SEA_LEVEL` of a shallow sea province
+    pub shallow_depth: u8,
+    /// The greatest average distance in pixels from land of a shallow sea province
+    pub shallow_distance: u32,
+    /// Whether the distance from land is measured across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for NavalTerrainClassifier {
+    #[inline]
+    fn default() -> Self {
+        Self::new(20, 24)
+    }
+}
+
+impl NavalTerrainClassifier {
+    /// Creates a new naval terrain classifier
+    #[inline]
+    #[must_use]
+    pub const fn new(shallow_depth: u8, shallow_distance: u32) -> Self {
+        Self {
+            shallow_depth,
+            shallow_distance,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Assigns shallow sea or deep ocean to each sea province in the definitions, returning the
+    /// number of provinces whose terrain changed.
+    /// # Errors
+    /// * If the heightmap is not the same size as the provinces image
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn classify(
+        &self,
+        heightmap: &RgbImage,
+        provinces: &RgbImage,
+        definitions: &mut Definitions,
+    ) -> Result<usize, MapError> {
+        let (width, height) = provinces.dimensions();
+        if heightmap.dimensions() != (width, height) {
+            return Err(MapError::ImageSizeMismatch(
+                "heightmap does not match provinces map".to_owned(),
+            ));
+        }
+        let types = definitions
+            .definitions
+            .values()
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), (d.id, d.province_type)))
+            .collect::<HashMap<_, _>>();
+        let pixel_types = provinces
+            .pixels()
+            .map(|p| types.get(p).copied())
+            .collect::<Vec<_>>();
+        let distances = coast_distances(width, height, self.wrapping, |i| {
+            matches!(pixel_types.get(i), Some(Some((_id, ProvinceType::Land))))
+        });
+
+        // The total depth, distance from land and pixels of each sea province
+        let mut totals: HashMap<ProvinceId, (u64, u64, u64)> = HashMap::new();
+        let pixels = pixel_types.iter().zip(&distances).zip(heightmap.pixels());
+        for ((pixel_type, distance), height) in pixels {
+            let id = match pixel_type {
+                Some((id, ProvinceType::Sea)) => *id,
+                _ => continue,
+            };
+            let depth = SEA_LEVEL.saturating_sub(height.0[0]);
+            let total = totals.entry(id).or_default();
+            total.0 += u64::from(depth);
+            total.1 += u64::from(*distance);
+            total.2 += 1;
+        }
+
+        let mut changed = 0;
+        for (id, (depth, distance, pixels)) in totals {
+            let pixels = pixels.max(1) as f64;
+            let shallow = depth as f64 / pixels <= f64::from(self.shallow_depth)
+                || distance as f64 / pixels <= f64::from(self.shallow_distance);
+            let kind = if shallow {
+                TerrainKind::ShallowSea
+            } else {
+                TerrainKind::DeepOcean
+            };
+            let terrain = Terrain(kind.category().to_owned());
+            if let Some(definition) = definitions.definitions.get_mut(&id) {
+                if definition.terrain != terrain {
+                    definition.terrain = terrain.clone();
+                    changed += 1;
+                }
+            }
+            definitions.terrain.insert(terrain);
+        }
+        Ok(changed)
+    }
+}
+
+/// Finds the distance of each pixel from the nearest land pixel, walking along rows and columns.
+/// Pixels that cannot reach land have a distance of `u32::MAX`.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+fn coast_distances<F: Fn(usize) -> bool>(
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    is_land: F,
+) -> Vec<u32> {
+    let size = width as usize * height as usize;
+    let mut distances = vec![u32::MAX; size];
+    let mut queue = VecDeque::new();
+    for (i, distance) in distances.iter_mut().enumerate() {
+        if is_land(i) {
+            *distance = 0;
+            queue.push_back(i);
+        }
+    }
+    while let Some(i) = queue.pop_front() {
+        let next = distances
+            .get(i)
+            .copied()
+            .unwrap_or(u32::MAX)
+            .saturating_add(1);
+        for n in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            if let Some(distance) = distances.get_mut(n) {
+                if *distance > next {
+                    *distance = next;
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+    distances
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::provinces::ProvinceGenerator;
+
+    #[test]
+    fn it_finds_the_distance_from_the_coast() {
+        let distances = coast_distances(4, 1, Wrapping::None, |i| i == 0);
+        assert_eq!(distances, vec![0, 1, 2, 3]);
+        let distances = coast_distances(4, 1, Wrapping::Horizontal, |i| i == 0);
+        assert_eq!(distances, vec![0, 1, 2, 1]);
+        let distances = coast_distances(2, 2, Wrapping::None, |_i| false);
+        assert!(distances.iter().all(|d| *d == u32::MAX));
+    }
+
+    #[test]
+    fn it_splits_shallow_and_deep_sea() {
+        // Land on the left, a shelf of shallow water and then a deep ocean on the right
+        let heightmap = RgbImage::from_fn(512, 256, |x, _y| {
+            if x < 64 {
+                Rgb([150, 150, 150])
+            } else if x < 128 {
+                Rgb([85, 85, 85])
+            } else {
+                Rgb([10, 10, 10])
+            }
+        });
+        let mut generated = ProvinceGenerator::new(9, 60)
+            .generate(&heightmap)
+            .expect("Failed to generate provinces");
+        let changed = NavalTerrainClassifier::new(20, 16)
+            .classify(&heightmap, &generated.provinces, &mut generated.definitions)
+            .expect("Failed to classify naval terrain");
+        let sea = generated
+            .definitions
+            .definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Sea)
+            .collect::<Vec<_>>();
+        assert_eq!(changed, sea.len());
+        assert!(sea.iter().any(|d| d.terrain.0 == "water_shallow_sea"));
+        assert!(sea.iter().any(|d| d.terrain.0 == "water_deep_ocean"));
+        assert!(generated
+            .definitions
+            .definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .all(|d| d.terrain.0 == "plains"));
+    }
+}
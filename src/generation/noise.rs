@@ -0,0 +1,271 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// The gradients used by the noise functions
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+];
+
+/// The skew factor for 2D simplex noise, `(sqrt(3) - 1) / 2`
+const SIMPLEX_SKEW: f64 = 0.366_025_403_784_438_6;
+/// The unskew factor for 2D simplex noise, `(3 - sqrt(3)) / 6`
+const SIMPLEX_UNSKEW: f64 = 0.211_324_865_405_187_1;
+
+/// The kind of gradient noise to sample.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NoiseKind {
+    /// Classic Perlin noise on a square grid
+    #[default]
+    Perlin,
+    /// Simplex noise on a triangular grid, with fewer directional artifacts
+    Simplex,
+}
+
+/// The parameters of fractal Brownian motion, layering several octaves of noise.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Fractal {
+    /// The number of octaves to layer
+    pub octaves: u32,
+    /// The frequency of the first octave, in cycles per pixel
+    pub frequency: f64,
+    /// The multiplier for the frequency of each subsequent octave
+    pub lacunarity: f64,
+    /// The multiplier for the amplitude of each subsequent octave
+    pub persistence: f64,
+}
+
+impl Default for Fractal {
+    #[inline]
+    fn default() -> Self {
+        Self::new(6, 1.0 / 512.0, 2.0, 0.5)
+    }
+}
+
+impl Fractal {
+    /// Creates new fractal parameters
+    #[inline]
+    #[must_use]
+    pub const fn new(octaves: u32, frequency: f64, lacunarity: f64, persistence: f64) -> Self {
+        Self {
+            octaves,
+            frequency,
+            lacunarity,
+            persistence,
+        }
+    }
+}
+
+/// A seeded source of gradient noise.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Noise {
+    /// The kind of noise sampled
+    pub kind: NoiseKind,
+    permutation: Vec<u8>,
+}
+
+impl Noise {
+    /// Creates a new noise source from a seed
+    #[inline]
+    #[must_use]
+    pub fn new(kind: NoiseKind, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut table = (0..=u8::MAX).collect::<Vec<_>>();
+        table.shuffle(&mut rng);
+        let permutation = table.iter().chain(table.iter()).copied().collect();
+        Self { kind, permutation }
+    }
+
+    /// Samples the noise at a point, in the range of about -1.0 to 1.0
+    #[inline]
+    #[must_use]
+    pub fn get(&self, x: f64, y: f64) -> f64 {
+        match self.kind {
+            NoiseKind::Perlin => self.perlin(x, y),
+            NoiseKind::Simplex => self.simplex(x, y),
+        }
+    }
+
+    /// Samples fractal Brownian motion at a point, normalized to the range of about -1.0 to 1.0
+    #[inline]
+    #[must_use]
+    pub fn fbm(&self, x: f64, y: f64, fractal: &Fractal) -> f64 {
+        let mut total = 0.0_f64;
+        let mut amplitude = 1.0_f64;
+        let mut max_amplitude = 0.0_f64;
+        let mut frequency = fractal.frequency;
+        for _ in 0..fractal.octaves {
+            total += self.get(x * frequency, y * frequency) * amplitude;
+            max_amplitude += amplitude;
+            amplitude *= fractal.persistence;
+            frequency *= fractal.lacunarity;
+        }
+        if max_amplitude > 0.0 {
+            total / max_amplitude
+        } else {
+            0.0
+        }
+    }
+
+    /// Samples ridged multifractal noise at a point, in the range of 0.0 to 1.0.  The noise forms
+    /// sharp crests where the underlying noise crosses zero, which resemble mountain ridges.
+    #[inline]
+    #[must_use]
+    pub fn ridged(&self, x: f64, y: f64, fractal: &Fractal) -> f64 {
+        let mut total = 0.0_f64;
+        let mut amplitude = 1.0_f64;
+        let mut max_amplitude = 0.0_f64;
+        let mut frequency = fractal.frequency;
+        let mut weight = 1.0_f64;
+        for _ in 0..fractal.octaves {
+            let ridge = 1.0_f64 - self.get(x * frequency, y * frequency).abs();
+            let signal = ridge * ridge * weight;
+            weight = signal.clamp(0.0, 1.0);
+            total += signal * amplitude;
+            max_amplitude += amplitude;
+            amplitude *= fractal.persistence;
+            frequency *= fractal.lacunarity;
+        }
+        if max_amplitude > 0.0 {
+            (total / max_amplitude).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Gets the gradient for a lattice point
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn gradient(&self, x: i64, y: i64) -> (f64, f64) {
+        let xi = (x & 255) as usize;
+        let yi = (y & 255) as usize;
+        let inner = self.permutation.get(yi).copied().unwrap_or_default();
+        let hash = self
+            .permutation
+            .get(xi + usize::from(inner))
+            .copied()
+            .unwrap_or_default();
+        GRADIENTS
+            .get(usize::from(hash) % GRADIENTS.len())
+            .copied()
+            .unwrap_or((1.0, 0.0))
+    }
+
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    fn perlin(&self, x: f64, y: f64) -> f64 {
+        let x0 = x.floor() as i64;
+        let y0 = y.floor() as i64;
+        let fx = x - x0 as f64;
+        let fy = y - y0 as f64;
+        let dot = |gx: i64, gy: i64, dx: f64, dy: f64| {
+            let (grad_x, grad_y) = self.gradient(gx, gy);
+            grad_x.mul_add(dx, grad_y * dy)
+        };
+        let n00 = dot(x0, y0, fx, fy);
+        let n10 = dot(x0 + 1, y0, fx - 1.0_f64, fy);
+        let n01 = dot(x0, y0 + 1, fx, fy - 1.0_f64);
+        let n11 = dot(x0 + 1, y0 + 1, fx - 1.0_f64, fy - 1.0_f64);
+        let u = fade(fx);
+        let v = fade(fy);
+        // The largest value of 2D Perlin noise with unit gradients is sqrt(0.5)
+        (lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+    }
+
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    fn simplex(&self, x: f64, y: f64) -> f64 {
+        let skew = (x + y) * SIMPLEX_SKEW;
+        let i = (x + skew).floor() as i64;
+        let j = (y + skew).floor() as i64;
+        let unskew = (i + j) as f64 * SIMPLEX_UNSKEW;
+        let x0 = x - (i as f64 - unskew);
+        let y0 = y - (j as f64 - unskew);
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let x1 = x0 - i1 as f64 + SIMPLEX_UNSKEW;
+        let y1 = y0 - j1 as f64 + SIMPLEX_UNSKEW;
+        let x2 = 2.0_f64.mul_add(SIMPLEX_UNSKEW, x0 - 1.0_f64);
+        let y2 = 2.0_f64.mul_add(SIMPLEX_UNSKEW, y0 - 1.0_f64);
+        let corner = |gx: i64, gy: i64, dx: f64, dy: f64| {
+            let t = 0.5_f64 - dx.mul_add(dx, dy * dy);
+            if t < 0.0 {
+                0.0_f64
+            } else {
+                let (grad_x, grad_y) = self.gradient(gx, gy);
+                t.powi(4) * grad_x.mul_add(dx, grad_y * dy)
+            }
+        };
+        let total =
+            corner(i, j, x0, y0) + corner(i + i1, j + j1, x1, y1) + corner(i + 1, j + 1, x2, y2);
+        // Scales the result to about -1.0 to 1.0
+        (total * 70.0).clamp(-1.0, 1.0)
+    }
+}
+
+/// The quintic smoothing curve used to interpolate Perlin noise
+fn fade(t: f64) -> f64 {
+    t * t * t * t.mul_add(t.mul_add(6.0, -15.0), 10.0)
+}
+
+/// Linearly interpolates between two values
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    (b - a).mul_add(t, a)
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_samples_deterministic_noise_in_range() {
+        for kind in [NoiseKind::Perlin, NoiseKind::Simplex] {
+            let noise = Noise::new(kind, 42);
+            let other = Noise::new(kind, 42);
+            let fractal = Fractal::new(4, 1.0 / 32.0, 2.0, 0.5);
+            for i in 0..500 {
+                let x = f64::from(i) * 0.37;
+                let y = f64::from(i) * 1.13;
+                let value = noise.get(x, y);
+                assert!((-1.0..=1.0).contains(&value));
+                assert!((value - other.get(x, y)).abs() < f64::EPSILON);
+                assert!((-1.0..=1.0).contains(&noise.fbm(x, y, &fractal)));
+                assert!((0.0..=1.0).contains(&noise.ridged(x, y, &fractal)));
+            }
+        }
+    }
+
+    #[test]
+    fn it_is_zero_on_the_perlin_lattice() {
+        let noise = Noise::new(NoiseKind::Perlin, 7);
+        assert!(noise.get(3.0, 5.0).abs() < f64::EPSILON);
+    }
+}
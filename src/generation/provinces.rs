@@ -0,0 +1,946 @@
+use crate::components::prelude::*;
+use crate::topology::Wrapping;
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Marks a pixel that has not been assigned to a province yet
+const UNASSIGNED: u32 = u32::MAX;
+
+/// The kind of surface a pixel of the heightmap lies on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Surface {
+    Land,
+    Sea,
+    Lake,
+}
+
+/// Partitions a heightmap into provinces using Voronoi cells relaxed with Lloyd's algorithm.
+/// Land and sea are partitioned separately so that no province straddles the coast, and each
+/// lake becomes a province of its own.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ProvinceGenerator {
+    /// The seed for the placement of the provinces
+    pub seed: u64,
+    /// The number of land and sea provinces to aim for
+    pub province_count: u32,
+    /// How many times larger a sea province is than a land province
+    pub sea_province_scale: f64,
+    /// The smallest number of pixels in a province, smaller provinces are merged into a neighbor
+    pub min_province_size: u32,
+    /// The number of iterations of Lloyd's algorithm, more iterations make the provinces more even
+    pub relaxation_iterations: u32,
+    /// The largest enclosed body of water that becomes a lake rather than a sea
+    pub max_lake_size: u32,
+    /// Whether provinces and bodies of water continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+/// The provinces generated from a heightmap.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct GeneratedProvinces {
+    /// The provinces image
+    pub provinces: RgbImage,
+    /// The definitions of the provinces, with a unique color for each province
+    pub definitions: Definitions,
+}
+
+impl Default for ProvinceGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0, 10000)
+    }
+}
+
+impl ProvinceGenerator {
+    /// Creates a new province generator with the default settings
+    #[inline]
+    #[must_use]
+    pub const fn new(seed: u64, province_count: u32) -> Self {
+        Self {
+            seed,
+            province_count,
+            sea_province_scale: 4.0,
+            min_province_size: 8,
+            relaxation_iterations: 3,
+            max_lake_size: 2000,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Generates the provinces for a heightmap
+    /// # Errors
+    /// * If the province count is 0
+    /// * If the heightmap is empty
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn generate(&self, heightmap: &RgbImage) -> Result<GeneratedProvinces, MapError> {
+        if self.province_count == 0 {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The province count must be at least 1".to_owned(),
+            ));
+        }
+        let (width, height) = heightmap.dimensions();
+        if width == 0 || height == 0 {
+            return Err(MapError::InvalidMapSize(width, height));
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let surfaces = classify_surfaces(heightmap, self.max_lake_size, self.wrapping);
+
+        let land_area = surfaces.iter().filter(|s| **s == Surface::Land).count() as f64;
+        let sea_area = surfaces.iter().filter(|s| **s == Surface::Sea).count() as f64;
+        let weighted_sea_area = sea_area / self.sea_province_scale.max(1.0);
+        let total_area = land_area + weighted_sea_area;
+        let land_count = if total_area > 0.0 {
+            (f64::from(self.province_count) * land_area / total_area).round() as usize
+        } else {
+            0
+        };
+        let sea_count = (self.province_count as usize).saturating_sub(land_count);
+
+        let mut labels = vec![UNASSIGNED; surfaces.len()];
+        let mut next_label = 0;
+        for (surface, count) in [(Surface::Land, land_count), (Surface::Sea, sea_count)] {
+            let seeds = self.place_seeds(&surfaces, width, height, surface, count, &mut rng);
+            let cells = relax(
+                &seeds,
+                &surfaces,
+                width,
+                height,
+                surface,
+                self.relaxation_iterations,
+            );
+            for (label, cell) in labels.iter_mut().zip(cells) {
+                if cell != UNASSIGNED {
+                    *label = next_label + cell;
+                }
+            }
+            next_label += seeds.len() as u32;
+        }
+        Ok(self.finish(&surfaces, &mut labels, next_label, width, height, &mut rng))
+    }
+
+    /// Generates the provinces for a heightmap whose land is already divided into regions, such
+    /// as the administrative regions of real-world data.  `regions` holds the region of each
+    /// pixel of the heightmap, row by row.  Each region becomes a province of the land it covers,
+    /// land outside every region joins the region it is connected to through the fewest pixels,
+    /// and islands outside every region become provinces of their own.  The sea is partitioned
+    /// as in `generate`, into provinces `sea_province_scale` times the average size of the land
+    /// provinces, so the province count is not used.
+    /// # Errors
+    /// * If the heightmap is empty
+    /// * If there is not a region for each pixel of the heightmap
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn generate_from_regions(
+        &self,
+        heightmap: &RgbImage,
+        regions: &[Option<u32>],
+    ) -> Result<GeneratedProvinces, MapError> {
+        let (width, height) = heightmap.dimensions();
+        if width == 0 || height == 0 {
+            return Err(MapError::InvalidMapSize(width, height));
+        }
+        let pixels = width as usize * height as usize;
+        if regions.len() != pixels {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "{} regions were given for the {pixels} pixels of the heightmap",
+                regions.len()
+            )));
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let surfaces = classify_surfaces(heightmap, self.max_lake_size, self.wrapping);
+
+        let mut labels = surfaces
+            .iter()
+            .zip(regions)
+            .map(|(surface, region)| match (surface, region) {
+                (Surface::Land, Some(index)) => *index,
+                _ => UNASSIGNED,
+            })
+            .collect::<Vec<_>>();
+        grow_regions(&surfaces, &mut labels, width, height, self.wrapping);
+        // The land left over is not connected to any region, and is split into its islands
+        // along with the regions
+        let outside = labels
+            .iter()
+            .filter(|l| **l != UNASSIGNED)
+            .max()
+            .map_or(0, |max| max.saturating_add(1));
+        for (label, surface) in labels.iter_mut().zip(&surfaces) {
+            if *surface == Surface::Land && *label == UNASSIGNED {
+                *label = outside;
+            }
+        }
+
+        let land_area = surfaces.iter().filter(|s| **s == Surface::Land).count() as f64;
+        let sea_area = surfaces.iter().filter(|s| **s == Surface::Sea).count() as f64;
+        let land_count = labels
+            .iter()
+            .filter(|l| **l != UNASSIGNED)
+            .collect::<HashSet<_>>()
+            .len();
+        let sea_province_area =
+            land_area / land_count.max(1) as f64 * self.sea_province_scale.max(1.0);
+        let sea_count = (sea_area / sea_province_area.max(1.0)).ceil() as usize;
+        let seeds = self.place_seeds(&surfaces, width, height, Surface::Sea, sea_count, &mut rng);
+        let cells = relax(
+            &seeds,
+            &surfaces,
+            width,
+            height,
+            Surface::Sea,
+            self.relaxation_iterations,
+        );
+        let first_sea = outside.saturating_add(1);
+        for (label, cell) in labels.iter_mut().zip(cells) {
+            if cell != UNASSIGNED {
+                *label = first_sea + cell;
+            }
+        }
+        let next_label = first_sea + seeds.len() as u32;
+        Ok(self.finish(&surfaces, &mut labels, next_label, width, height, &mut rng))
+    }
+
+    /// Labels the lakes after the land and sea are labelled, then splits the labels into
+    /// connected provinces of at least the minimum size
+    fn finish(
+        &self,
+        surfaces: &[Surface],
+        labels: &mut [u32],
+        next_label: u32,
+        width: u32,
+        height: u32,
+        rng: &mut StdRng,
+    ) -> GeneratedProvinces {
+        label_lakes(surfaces, labels, width, height, self.wrapping, next_label);
+
+        let regions = split_regions(labels, width, height, self.wrapping);
+        let (regions, region_surfaces) = merge_small_regions(
+            &regions,
+            surfaces,
+            width,
+            height,
+            self.wrapping,
+            self.min_province_size,
+        );
+        build_provinces(
+            &regions,
+            &region_surfaces,
+            width,
+            height,
+            self.wrapping,
+            rng,
+        )
+    }
+
+    /// Places seeds randomly on the pixels with the given surface
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    fn place_seeds(
+        &self,
+        surfaces: &[Surface],
+        width: u32,
+        height: u32,
+        surface: Surface,
+        count: usize,
+        rng: &mut StdRng,
+    ) -> Vec<(f64, f64)> {
+        let candidates = surfaces
+            .iter()
+            .enumerate()
+            .filter(|(_i, s)| **s == surface)
+            .map(|(i, _s)| i)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() || width == 0 || height == 0 {
+            return Vec::new();
+        }
+        let mut chosen = HashSet::new();
+        // Don't place more seeds than there are pixels for provinces of the minimum size
+        let max_seeds = (candidates.len() / self.min_province_size.max(1) as usize).max(1);
+        let count = count.min(max_seeds);
+        let mut attempts = 0;
+        while chosen.len() < count && attempts < count * 10 {
+            let index = candidates.get(rng.gen_range(0..candidates.len())).copied();
+            if let Some(i) = index {
+                chosen.insert(i);
+            }
+            attempts += 1;
+        }
+        let mut seeds = chosen.into_iter().collect::<Vec<_>>();
+        seeds.sort_unstable();
+        seeds
+            .into_iter()
+            .map(|i| {
+                let x = i % width as usize;
+                let y = i / width as usize;
+                (x as f64, y as f64)
+            })
+            .collect()
+    }
+}
+
+/// Classifies each pixel of the heightmap as land, sea or lake.  Bodies of water that do not
+/// touch the edge of the map and are no larger than the maximum lake size are lakes.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+fn classify_surfaces(heightmap: &RgbImage, max_lake_size: u32, wrapping: Wrapping) -> Vec<Surface> {
+    let (width, height) = heightmap.dimensions();
+    let mut surfaces = heightmap
+        .pixels()
+        .map(|p| {
+            if p.0[0] < SEA_LEVEL {
+                Surface::Sea
+            } else {
+                Surface::Land
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut visited = vec![false; surfaces.len()];
+    for start in 0..surfaces.len() {
+        if visited.get(start).copied().unwrap_or(true) || surfaces.get(start) != Some(&Surface::Sea)
+        {
+            continue;
+        }
+        let body = flood_fill(start, width, height, wrapping, &mut visited, |i| {
+            surfaces.get(i) == Some(&Surface::Sea)
+        });
+        let touches_edge = body
+            .iter()
+            .any(|i| wrapping.is_on_edge(*i as u32 % width, *i as u32 / width, width, height));
+        if !touches_edge && body.len() <= max_lake_size as usize {
+            for i in body {
+                if let Some(s) = surfaces.get_mut(i) {
+                    *s = Surface::Lake;
+                }
+            }
+        }
+    }
+    surfaces
+}
+
+/// Assigns each pixel with the given surface to its nearest seed, moving each seed to the center
+/// of its cell for the given number of iterations.  Returns the index of the seed of each pixel.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn relax(
+    seeds: &[(f64, f64)],
+    surfaces: &[Surface],
+    width: u32,
+    height: u32,
+    surface: Surface,
+    iterations: u32,
+) -> Vec<u32> {
+    let mut seeds = seeds.to_vec();
+    let mut cells = assign_cells(&seeds, surfaces, width, height, surface);
+    for _ in 0..iterations {
+        let mut sums = vec![(0.0_f64, 0.0_f64, 0_u32); seeds.len()];
+        for (i, cell) in cells.iter().enumerate() {
+            if let Some(sum) = sums.get_mut(*cell as usize) {
+                sum.0 += (i % width as usize) as f64;
+                sum.1 += (i / width as usize) as f64;
+                sum.2 += 1;
+            }
+        }
+        for (seed, (sum_x, sum_y, count)) in seeds.iter_mut().zip(sums) {
+            if count == 0 {
+                continue;
+            }
+            let x = (sum_x / f64::from(count)).round();
+            let y = (sum_y / f64::from(count)).round();
+            // A cell's center may lie outside of the cell, such as for a crescent shaped coast.
+            let index = y as usize * width as usize + x as usize;
+            if surfaces.get(index) == Some(&surface) {
+                *seed = (x, y);
+            }
+        }
+        cells = assign_cells(&seeds, surfaces, width, height, surface);
+    }
+    cells
+}
+
+/// Assigns each pixel with the given surface to its nearest seed
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_precision_loss)]
+fn assign_cells(
+    seeds: &[(f64, f64)],
+    surfaces: &[Surface],
+    width: u32,
+    height: u32,
+    surface: Surface,
+) -> Vec<u32> {
+    let grid = SeedGrid::new(seeds, width, height);
+    surfaces
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            if *s != surface {
+                return UNASSIGNED;
+            }
+            let x = (i % width as usize) as f64;
+            let y = (i / width as usize) as f64;
+            grid.nearest(seeds, x, y)
+                .map_or(UNASSIGNED, |seed| seed as u32)
+        })
+        .collect()
+}
+
+/// Gives each lake a label of its own, starting from the given label
+#[allow(clippy::integer_arithmetic)]
+fn label_lakes(
+    surfaces: &[Surface],
+    labels: &mut [u32],
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    first: u32,
+) {
+    let mut visited = vec![false; surfaces.len()];
+    let mut next_label = first;
+    for start in 0..surfaces.len() {
+        if visited.get(start).copied().unwrap_or(true)
+            || surfaces.get(start) != Some(&Surface::Lake)
+        {
+            continue;
+        }
+        let lake = flood_fill(start, width, height, wrapping, &mut visited, |i| {
+            surfaces.get(i) == Some(&Surface::Lake)
+        });
+        for i in lake {
+            if let Some(label) = labels.get_mut(i) {
+                *label = next_label;
+            }
+        }
+        next_label += 1;
+    }
+}
+
+/// Spreads the labels of the land into the unlabelled land connected to it, each pixel taking the
+/// label of the labelled pixel it is the fewest steps from
+fn grow_regions(
+    surfaces: &[Surface],
+    labels: &mut [u32],
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+) {
+    let mut queue = labels
+        .iter()
+        .enumerate()
+        .filter(|(_i, label)| **label != UNASSIGNED)
+        .map(|(i, _label)| i)
+        .collect::<VecDeque<_>>();
+    while let Some(i) = queue.pop_front() {
+        let label = labels.get(i).copied().unwrap_or(UNASSIGNED);
+        for neighbor in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            if surfaces.get(neighbor) != Some(&Surface::Land) {
+                continue;
+            }
+            if let Some(l) = labels.get_mut(neighbor) {
+                if *l == UNASSIGNED {
+                    *l = label;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
+
+/// Splits the labelled cells into connected regions, as a cell may be split by the coast.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn split_regions(labels: &[u32], width: u32, height: u32, wrapping: Wrapping) -> Vec<u32> {
+    let mut regions = vec![UNASSIGNED; labels.len()];
+    let mut visited = vec![false; labels.len()];
+    let mut next_region = 0;
+    for start in 0..labels.len() {
+        let label = labels.get(start).copied().unwrap_or(UNASSIGNED);
+        if visited.get(start).copied().unwrap_or(true) || label == UNASSIGNED {
+            continue;
+        }
+        let region = flood_fill(start, width, height, wrapping, &mut visited, |i| {
+            labels.get(i) == Some(&label)
+        });
+        for i in region {
+            if let Some(r) = regions.get_mut(i) {
+                *r = next_region;
+            }
+        }
+        next_region += 1;
+    }
+    regions
+}
+
+/// Merges regions smaller than the minimum size into the neighbor they share the longest border
+/// with, preferring neighbors on the same surface.  Returns the merged region of each pixel and
+/// the surface of each region.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+fn merge_small_regions(
+    regions: &[u32],
+    surfaces: &[Surface],
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    min_size: u32,
+) -> (Vec<u32>, Vec<Surface>) {
+    let region_count = regions
+        .iter()
+        .filter(|r| **r != UNASSIGNED)
+        .max()
+        .map_or(0, |max| *max as usize + 1);
+    let mut sizes = vec![0_u32; region_count];
+    let mut region_surfaces = vec![Surface::Land; region_count];
+    let mut borders: HashMap<(u32, u32), u32> = HashMap::new();
+    for (i, region) in regions.iter().enumerate() {
+        if let (Some(size), Some(surface)) = (
+            sizes.get_mut(*region as usize),
+            region_surfaces.get_mut(*region as usize),
+        ) {
+            *size += 1;
+            *surface = surfaces.get(i).copied().unwrap_or(Surface::Land);
+        }
+        for neighbor in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            let other = regions.get(neighbor).copied().unwrap_or(UNASSIGNED);
+            if other != *region && other != UNASSIGNED && *region != UNASSIGNED {
+                *borders.entry((*region, other)).or_default() += 1;
+            }
+        }
+    }
+    let mut neighbors_by_region: HashMap<u32, Vec<(u32, u32)>> = HashMap::new();
+    for ((region, other), length) in borders {
+        neighbors_by_region
+            .entry(region)
+            .or_default()
+            .push((other, length));
+    }
+
+    let mut parents = (0..region_count as u32).collect::<Vec<_>>();
+    let mut order = (0..region_count as u32).collect::<Vec<_>>();
+    order.sort_by_key(|r| (sizes.get(*r as usize).copied().unwrap_or_default(), *r));
+    for region in order {
+        let root = find_root(&mut parents, region);
+        let size = sizes.get(root as usize).copied().unwrap_or_default();
+        if size >= min_size {
+            continue;
+        }
+        let surface = region_surfaces.get(root as usize).copied();
+        let mut candidates = neighbors_by_region
+            .get(&region)
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(other, length)| (find_root(&mut parents, other), length))
+            .filter(|(other, _length)| *other != root)
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(other, length)| {
+            let same_surface = region_surfaces.get(*other as usize).copied() == surface;
+            (same_surface, *length, std::cmp::Reverse(*other))
+        });
+        if let Some((target, _length)) = candidates.last() {
+            if let Some(p) = parents.get_mut(root as usize) {
+                *p = *target;
+            }
+            if let Some(target_size) = sizes.get_mut(*target as usize) {
+                *target_size += size;
+            }
+        }
+    }
+    let merged = regions
+        .iter()
+        .map(|region| {
+            if *region == UNASSIGNED {
+                UNASSIGNED
+            } else {
+                find_root(&mut parents, *region)
+            }
+        })
+        .collect();
+    (merged, region_surfaces)
+}
+
+/// Builds the provinces image and definitions from the regions
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+fn build_provinces(
+    regions: &[u32],
+    region_surfaces: &[Surface],
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    rng: &mut StdRng,
+) -> GeneratedProvinces {
+    // Number the provinces in the order they appear so ids are stable for a seed.
+    let mut ids: HashMap<u32, ProvinceId> = HashMap::new();
+    let mut province_surfaces = Vec::new();
+    for region in regions {
+        if *region != UNASSIGNED && !ids.contains_key(region) {
+            ids.insert(*region, ProvinceId(ids.len() as i32 + 1));
+            province_surfaces.push(
+                region_surfaces
+                    .get(*region as usize)
+                    .copied()
+                    .unwrap_or(Surface::Land),
+            );
+        }
+    }
+    let mut used_colors = HashSet::from([Rgb([0_u8, 0, 0])]);
+    let mut colors = Vec::with_capacity(province_surfaces.len());
+    while colors.len() < province_surfaces.len() {
+        let color = Rgb([rng.gen(), rng.gen(), rng.gen()]);
+        if used_colors.insert(color) {
+            colors.push(color);
+        }
+    }
+
+    let province_index = |i: usize| {
+        regions
+            .get(i)
+            .and_then(|r| ids.get(r))
+            .map(|id| id.0 as usize - 1)
+    };
+    let mut coastal = vec![false; province_surfaces.len()];
+    let mut provinces = RgbImage::new(width, height);
+    for (i, pixel) in provinces.pixels_mut().enumerate() {
+        let index = match province_index(i) {
+            Some(index) => index,
+            None => continue,
+        };
+        if let Some(color) = colors.get(index) {
+            *pixel = *color;
+        }
+        if province_surfaces.get(index) != Some(&Surface::Land) {
+            continue;
+        }
+        let touches_sea = wrapping
+            .neighbors(i, width, height)
+            .into_iter()
+            .flatten()
+            .filter_map(province_index)
+            .any(|n| province_surfaces.get(n) == Some(&Surface::Sea));
+        if touches_sea {
+            if let Some(c) = coastal.get_mut(index) {
+                *c = true;
+            }
+        }
+    }
+
+    let mut definitions = HashMap::new();
+    definitions.insert(
+        ProvinceId(0),
+        Definition {
+            id: ProvinceId(0),
+            r: Red(0),
+            g: Green(0),
+            b: Blue(0),
+            province_type: ProvinceType::Land,
+            coastal: Coastal(false),
+            terrain: Terrain("unknown".to_owned()),
+            continent: ContinentIndex(0),
+        },
+    );
+    for (index, (surface, color)) in province_surfaces.iter().zip(&colors).enumerate() {
+        let id = ProvinceId(index as i32 + 1);
+        let (province_type, terrain, continent) = match surface {
+            Surface::Land => (ProvinceType::Land, "plains", ContinentIndex(1)),
+            Surface::Sea => (ProvinceType::Sea, "ocean", ContinentIndex(0)),
+            Surface::Lake => (ProvinceType::Lake, "lakes", ContinentIndex(0)),
+        };
+        definitions.insert(
+            id,
+            Definition {
+                id,
+                r: Red(color.0[0]),
+                g: Green(color.0[1]),
+                b: Blue(color.0[2]),
+                province_type,
+                coastal: Coastal(coastal.get(index).copied().unwrap_or_default()),
+                terrain: Terrain(terrain.to_owned()),
+                continent,
+            },
+        );
+    }
+    let terrain = definitions
+        .values()
+        .map(|definition| definition.terrain.clone())
+        .collect();
+    GeneratedProvinces {
+        provinces,
+        definitions: Definitions {
+            definitions,
+            terrain,
+        },
+    }
+}
+
+/// Finds the root of a region after merging, compressing the path along the way
+#[allow(clippy::as_conversions)]
+fn find_root(parents: &mut [u32], region: u32) -> u32 {
+    let mut root = region;
+    while let Some(parent) = parents.get(root as usize).copied() {
+        if parent == root {
+            break;
+        }
+        root = parent;
+    }
+    let mut current = region;
+    while current != root {
+        match parents.get_mut(current as usize) {
+            Some(parent) => {
+                current = *parent;
+                *parent = root;
+            }
+            None => break,
+        }
+    }
+    root
+}
+
+/// Finds the pixels connected to the start pixel for which the predicate holds, marking them as
+/// visited.
+fn flood_fill<F: Fn(usize) -> bool>(
+    start: usize,
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    visited: &mut [bool],
+    predicate: F,
+) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut queue = VecDeque::from([start]);
+    if let Some(v) = visited.get_mut(start) {
+        *v = true;
+    }
+    while let Some(i) = queue.pop_front() {
+        found.push(i);
+        for neighbor in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            match visited.get_mut(neighbor) {
+                Some(v) if !*v && predicate(neighbor) => {
+                    *v = true;
+                    queue.push_back(neighbor);
+                }
+                _ => {}
+            }
+        }
+    }
+    found
+}
+
+/// A grid of buckets for finding the nearest seed to a pixel.
+#[derive(Debug)]
+struct SeedGrid {
+    cell_size: f64,
+    columns: usize,
+    rows: usize,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl SeedGrid {
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn new(seeds: &[(f64, f64)], width: u32, height: u32) -> Self {
+        let area = f64::from(width) * f64::from(height);
+        let cell_size = (area / seeds.len().max(1) as f64).sqrt().max(4.0);
+        let columns = (f64::from(width) / cell_size).ceil().max(1.0) as usize;
+        let rows = (f64::from(height) / cell_size).ceil().max(1.0) as usize;
+        let mut buckets = vec![Vec::new(); columns * rows];
+        for (i, (x, y)) in seeds.iter().enumerate() {
+            let column = ((x / cell_size) as usize).min(columns - 1);
+            let row = ((y / cell_size) as usize).min(rows - 1);
+            if let Some(bucket) = buckets.get_mut(row * columns + column) {
+                bucket.push(i);
+            }
+        }
+        Self {
+            cell_size,
+            columns,
+            rows,
+            buckets,
+        }
+    }
+
+    /// Finds the nearest seed to a point, searching rings of buckets outwards from the point.
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    fn nearest(&self, seeds: &[(f64, f64)], x: f64, y: f64) -> Option<usize> {
+        let column = ((x / self.cell_size) as usize).min(self.columns - 1);
+        let row = ((y / self.cell_size) as usize).min(self.rows - 1);
+        let mut best: Option<(usize, f64)> = None;
+        for ring in 0..self.columns.max(self.rows) {
+            let min_column = column.saturating_sub(ring);
+            let max_column = (column + ring).min(self.columns - 1);
+            let min_row = row.saturating_sub(ring);
+            let max_row = (row + ring).min(self.rows - 1);
+            for r in min_row..=max_row {
+                for c in min_column..=max_column {
+                    let on_ring = r + ring == row
+                        || r == row + ring
+                        || c + ring == column
+                        || c == column + ring;
+                    if !on_ring {
+                        continue;
+                    }
+                    let bucket = self.buckets.get(r * self.columns + c);
+                    for seed in bucket.into_iter().flatten() {
+                        if let Some((sx, sy)) = seeds.get(*seed) {
+                            let distance = (sx - x).mul_add(sx - x, (sy - y) * (sy - y));
+                            if best.map_or(true, |(_s, d)| distance < d) {
+                                best = Some((*seed, distance));
+                            }
+                        }
+                    }
+                }
+            }
+            // Any seed beyond this ring is at least this far away
+            let reach = ring as f64 * self.cell_size;
+            if let Some((_seed, distance)) = best {
+                if distance <= reach * reach {
+                    break;
+                }
+            }
+        }
+        best.map(|(seed, _distance)| seed)
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A heightmap with land on the left, sea on the right and a lake in the land
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |x, y| {
+            if (40..50).contains(&x) && (40..50).contains(&y) {
+                Rgb([50, 50, 50])
+            } else if x < 128 {
+                Rgb([150, 150, 150])
+            } else {
+                Rgb([50, 50, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn it_generates_provinces_matching_the_definitions() {
+        let generator = ProvinceGenerator::new(7, 40);
+        let generated = generator
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let definitions = &generated.definitions.definitions;
+        let colors = definitions
+            .values()
+            .map(|d| Rgb([d.r.0, d.g.0, d.b.0]))
+            .collect::<HashSet<_>>();
+        assert_eq!(colors.len(), definitions.len());
+        assert!(generated.provinces.pixels().all(|p| colors.contains(p)));
+        let lakes = definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Lake)
+            .count();
+        assert_eq!(lakes, 1);
+        let land = definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .count();
+        let sea = definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Sea)
+            .count();
+        // Sea provinces are larger, so most of the provinces are on land
+        assert!(land > sea);
+        assert!(sea > 0);
+        assert!(definitions
+            .values()
+            .any(|d| d.province_type == ProvinceType::Land && d.coastal.0));
+    }
+
+    #[test]
+    fn it_keeps_provinces_above_the_minimum_size() {
+        let generator = ProvinceGenerator::new(3, 60);
+        let generated = generator
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let mut sizes: HashMap<Rgb<u8>, u32> = HashMap::new();
+        for pixel in generated.provinces.pixels() {
+            *sizes.entry(*pixel).or_default() += 1;
+        }
+        assert!(sizes
+            .values()
+            .all(|size| *size >= generator.min_province_size));
+    }
+
+    #[test]
+    fn it_makes_a_province_of_each_region() {
+        let regions = (0..256 * 256)
+            .map(|i| {
+                let (x, y) = (i % 256, i / 256);
+                match (x < 128, y < 128) {
+                    (true, true) => Some(7),
+                    // The land left of the second region joins it
+                    (true, false) if x >= 20 => Some(3),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        let generated = ProvinceGenerator::new(5, 0)
+            .generate_from_regions(&heightmap(), &regions)
+            .expect("Failed to generate provinces");
+        let definitions = &generated.definitions.definitions;
+        let count = |province_type: ProvinceType| {
+            definitions
+                .values()
+                .filter(|d| d.province_type == province_type && d.id.0 != 0)
+                .count()
+        };
+        assert_eq!(count(ProvinceType::Land), 2);
+        assert_eq!(count(ProvinceType::Lake), 1);
+        assert!(count(ProvinceType::Sea) > 0);
+        let top = *generated.provinces.get_pixel(0, 0);
+        let bottom = *generated.provinces.get_pixel(0, 255);
+        assert_ne!(top, bottom);
+        assert_eq!(*generated.provinces.get_pixel(127, 127), top);
+        assert_eq!(*generated.provinces.get_pixel(127, 128), bottom);
+    }
+
+    #[test]
+    fn it_generates_the_same_provinces_from_the_same_seed() {
+        let generator = ProvinceGenerator::new(11, 30);
+        let first = generator
+            .generate(&heightmap())
+            .expect("Failed to generate");
+        let second = generator
+            .generate(&heightmap())
+            .expect("Failed to generate");
+        assert_eq!(first.provinces, second.provinces);
+        assert_eq!(first.definitions, second.definitions);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::components::river::{RiverColor, RIVER_WIDTHS};
+use crate::topology::Wrapping;
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::MapError;
+use image::RgbImage;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Marks a pixel without a pixel downstream of it
+const NO_PIXEL: usize = usize::MAX;
+
+/// Generates rivers by tracing the flow of water down the heightmap to the sea.
+///
+/// Water flows from each land pixel to the neighbor it drains into, with depressions filled so
+/// that all water reaches the sea or a lake.  Wherever enough land drains through a pixel it
+/// becomes part of a river, and rivers widen as more land drains into them.  The main stem of
+/// each river starts with a source marker, and each tributary starts with a flow-in marker where
+/// it joins the river it flows into, so that every river has exactly one marker.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RiverGenerator {
+    /// The number of pixels that must drain through a pixel for it to be part of a river
+    pub min_flow: u32,
+    /// How many times the flow must grow for a river to widen by one index
+    pub widening: f64,
+    /// The shortest river or tributary in pixels, shorter ones are left out
+    pub min_length: u32,
+    /// Whether rivers can flow across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for RiverGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(400)
+    }
+}
+
+impl RiverGenerator {
+    /// Creates a new river generator with the default settings
+    #[inline]
+    #[must_use]
+    pub const fn new(min_flow: u32) -> Self {
+        Self {
+            min_flow,
+            widening: 2.0,
+            min_length: 8,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Generates the rivers image for a heightmap
+    /// # Errors
+    /// * If the minimum flow is 0
+    /// * If the widening is not greater than 1
+    /// * If the heightmap is empty
+    #[inline]
+    pub fn generate(&self, heightmap: &RgbImage) -> Result<RgbImage, MapError> {
+        if self.min_flow == 0 {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The minimum flow must be at least 1".to_owned(),
+            ));
+        }
+        if self.widening <= 1.0_f64 {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The widening must be greater than 1".to_owned(),
+            ));
+        }
+        let (width, height) = heightmap.dimensions();
+        if width == 0 || height == 0 {
+            return Err(MapError::InvalidMapSize(width, height));
+        }
+        let heights = heightmap.pixels().map(|p| p.0[0]).collect::<Vec<_>>();
+        let is_water = heights.iter().map(|h| *h < SEA_LEVEL).collect::<Vec<_>>();
+        let (downstream, order) = drainage(&heights, &is_water, width, height, self.wrapping);
+
+        let mut flow = is_water
+            .iter()
+            .map(|water| u32::from(!*water))
+            .collect::<Vec<_>>();
+        for i in order.iter().rev() {
+            let down = downstream.get(*i).copied().unwrap_or(NO_PIXEL);
+            let pixel_flow = flow.get(*i).copied().unwrap_or_default();
+            if let Some(f) = flow.get_mut(down) {
+                *f = f.saturating_add(pixel_flow);
+            }
+        }
+        let colors = self.trace_rivers(&downstream, &flow, &is_water, width, height);
+
+        let mut rivers = RgbImage::new(width, height);
+        for ((pixel, color), water) in rivers.pixels_mut().zip(colors).zip(is_water) {
+            *pixel = match (color, water) {
+                (Some(c), _) => c.to_rgb(),
+                (None, true) => RiverColor::Water.to_rgb(),
+                (None, false) => RiverColor::Land.to_rgb(),
+            };
+        }
+        Ok(rivers)
+    }
+
+    /// Picks the pixels of the rivers from the flow through each pixel, splitting each river
+    /// into a main stem and tributaries that do not touch each other except where they join.
+    #[allow(clippy::as_conversions)]
+    fn trace_rivers(
+        &self,
+        downstream: &[usize],
+        flow: &[u32],
+        is_water: &[bool],
+        width: u32,
+        height: u32,
+    ) -> Vec<Option<RiverColor>> {
+        let is_river = |i: usize| {
+            !is_water.get(i).copied().unwrap_or(true)
+                && flow.get(i).map_or(false, |f| *f >= self.min_flow)
+        };
+
+        // The upstream pixel carrying the most water continues a river, the others are tributaries.
+        let mut main_upstream = vec![NO_PIXEL; flow.len()];
+        for i in (0..flow.len()).filter(|i| is_river(*i)) {
+            let down = downstream.get(i).copied().unwrap_or(NO_PIXEL);
+            if !is_river(down) {
+                continue;
+            }
+            let current = main_upstream.get(down).copied().unwrap_or(NO_PIXEL);
+            let current_flow = flow.get(current).copied().unwrap_or_default();
+            if current == NO_PIXEL || flow.get(i).copied().unwrap_or_default() > current_flow {
+                if let Some(m) = main_upstream.get_mut(down) {
+                    *m = i;
+                }
+            }
+        }
+        let mut starts = (0..flow.len())
+            .filter(|i| is_river(*i))
+            .filter(|i| {
+                let down = downstream.get(*i).copied().unwrap_or(NO_PIXEL);
+                !is_river(down) || main_upstream.get(down) != Some(i)
+            })
+            .collect::<Vec<_>>();
+        // Larger rivers are drawn first, so tributaries are cut short rather than the rivers
+        // they flow into.
+        starts.sort_by_key(|i| Reverse(flow.get(*i).copied().unwrap_or_default()));
+
+        let mut colors: Vec<Option<RiverColor>> = vec![None; flow.len()];
+        for start in starts {
+            let down = downstream.get(start).copied().unwrap_or(NO_PIXEL);
+            let is_tributary = is_river(down);
+            if is_tributary && !matches!(colors.get(down), Some(Some(RiverColor::River(_)))) {
+                continue;
+            }
+            let mut segment = Vec::new();
+            let mut previous = down;
+            let mut current = start;
+            while current != NO_PIXEL {
+                let touches_other = self
+                    .wrapping
+                    .neighbors(current, width, height)
+                    .into_iter()
+                    .flatten()
+                    .any(|n| n != previous && matches!(colors.get(n), Some(Some(_))));
+                if touches_other {
+                    break;
+                }
+                let river_width = self.river_width(flow.get(current).copied().unwrap_or_default());
+                if let Some(c) = colors.get_mut(current) {
+                    *c = Some(RiverColor::River(river_width));
+                }
+                segment.push(current);
+                previous = current;
+                current = main_upstream.get(current).copied().unwrap_or(NO_PIXEL);
+            }
+            if segment.len() < self.min_length as usize {
+                for i in segment {
+                    if let Some(c) = colors.get_mut(i) {
+                        *c = None;
+                    }
+                }
+                continue;
+            }
+            let marker = if is_tributary {
+                segment.first().map(|i| (*i, RiverColor::FlowIn))
+            } else {
+                segment.last().map(|i| (*i, RiverColor::Source))
+            };
+            if let Some((i, color)) = marker {
+                if let Some(c) = colors.get_mut(i) {
+                    *c = Some(color);
+                }
+            }
+        }
+        colors
+    }
+
+    /// The width index of a river with the given flow
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn river_width(&self, flow: u32) -> u8 {
+        (f64::from(flow) / f64::from(self.min_flow))
+            .log(self.widening)
+            .floor()
+            .clamp(0.0, f64::from(RIVER_WIDTHS - 1)) as u8
+    }
+}
+
+/// Finds the pixel each pixel drains into, filling depressions by flooding the map from the
+/// water inwards.  If there is no water the map drains off the edges that do not wrap around.
+/// Returns the downstream pixel of each pixel, and the order the pixels were flooded in, from
+/// downstream to upstream.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn drainage(
+    heights: &[u8],
+    is_water: &[bool],
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut downstream = vec![NO_PIXEL; heights.len()];
+    let mut visited = vec![false; heights.len()];
+    let mut order = Vec::with_capacity(heights.len());
+    // The counter breaks ties in the order pixels were reached, spreading flow across flats
+    let mut queue = BinaryHeap::new();
+    let mut counter = 0_usize;
+    let has_water = is_water.iter().any(|w| *w);
+    for (i, water) in is_water.iter().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let on_edge = wrapping.is_on_edge(x, y, width, height);
+        if *water || (!has_water && on_edge) {
+            if let Some(v) = visited.get_mut(i) {
+                *v = true;
+            }
+            queue.push(Reverse((
+                heights.get(i).copied().unwrap_or_default(),
+                counter,
+                i,
+            )));
+            counter += 1;
+        }
+    }
+    while let Some(Reverse((level, _counter, i))) = queue.pop() {
+        order.push(i);
+        for n in wrapping.neighbors(i, width, height).into_iter().flatten() {
+            match visited.get_mut(n) {
+                Some(v) if !*v => *v = true,
+                _ => continue,
+            }
+            if let Some(d) = downstream.get_mut(n) {
+                *d = i;
+            }
+            let n_level = heights.get(n).copied().unwrap_or_default().max(level);
+            queue.push(Reverse((n_level, counter, n)));
+            counter += 1;
+        }
+    }
+    (downstream, order)
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::heightmap::HeightmapGenerator;
+    use crate::validation::rivers::verify_rivers;
+    use image::Rgb;
+    use std::collections::VecDeque;
+
+    /// Counts the markers of each connected river
+    fn markers_per_river(rivers: &RgbImage) -> Vec<usize> {
+        let (width, height) = rivers.dimensions();
+        let color = |i: usize| {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            RiverColor::from_rgb(*rivers.get_pixel(x, y)).expect("Invalid river color")
+        };
+        let mut visited = vec![false; (width * height) as usize];
+        let mut counts = Vec::new();
+        for start in 0..visited.len() {
+            if visited[start] || !color(start).is_river() {
+                continue;
+            }
+            visited[start] = true;
+            let mut queue = VecDeque::from([start]);
+            let mut markers = 0;
+            while let Some(i) = queue.pop_front() {
+                markers += usize::from(color(i).is_marker());
+                for n in Wrapping::None
+                    .neighbors(i, width, height)
+                    .into_iter()
+                    .flatten()
+                {
+                    if !visited[n] && color(n).is_river() {
+                        visited[n] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            counts.push(markers);
+        }
+        counts
+    }
+
+    #[test]
+    fn it_generates_valid_rivers() {
+        let heightmap = HeightmapGenerator::new(4)
+            .generate(256, 256)
+            .expect("Failed to generate heightmap");
+        let rivers = RiverGenerator::new(150)
+            .generate(&heightmap)
+            .expect("Failed to generate rivers");
+        assert!(verify_rivers(&rivers, Wrapping::None).is_empty());
+        let counts = markers_per_river(&rivers);
+        assert!(!counts.is_empty());
+        // Each river connected to tributaries has a source, and one flow-in per tributary
+        let sources = rivers
+            .pixels()
+            .filter(|p| **p == RiverColor::Source.to_rgb())
+            .count();
+        assert_eq!(sources, counts.len());
+    }
+
+    #[test]
+    fn it_traces_rivers_down_to_the_sea() {
+        // A slope falling from the left edge to a sea on the right
+        let heightmap = RgbImage::from_fn(64, 16, |x, _y| {
+            let h = if x < 48 { 200 - x as u8 * 2 } else { 50 };
+            Rgb([h, h, h])
+        });
+        let mut generator = RiverGenerator::new(40);
+        generator.min_length = 4;
+        let rivers = generator
+            .generate(&heightmap)
+            .expect("Failed to generate rivers");
+        assert_eq!(*rivers.get_pixel(60, 8), RiverColor::Water.to_rgb());
+        let mouths = (0..16)
+            .filter(|y| {
+                RiverColor::from_rgb(*rivers.get_pixel(47, *y)).map_or(false, RiverColor::is_river)
+            })
+            .count();
+        assert!(mouths > 0);
+        assert!(verify_rivers(&rivers, Wrapping::None).is_empty());
+        assert!(markers_per_river(&rivers).iter().all(|m| *m >= 1));
+    }
+
+    #[test]
+    fn it_rejects_invalid_settings() {
+        let heightmap = RgbImage::new(16, 16);
+        let mut generator = RiverGenerator::new(0);
+        assert!(matches!(
+            generator.generate(&heightmap),
+            Err(MapError::InvalidGeneratorSettings(_))
+        ));
+        generator.min_flow = 10;
+        generator.widening = 1.0;
+        assert!(matches!(
+            generator.generate(&heightmap),
+            Err(MapError::InvalidGeneratorSettings(_))
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+use crate::components::prelude::*;
+use crate::components::state::{State, States};
+use crate::generation::{measure_provinces, ProvinceInfo};
+use crate::topology::Wrapping;
+use crate::MapError;
+use image::RgbImage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Groups land provinces into states.
+///
+/// States are grown one at a time from the top left of the map, adding the neighboring province
+/// that is closest to the middle of the state until it holds `provinces_per_state` provinces.
+/// Provinces with a different terrain than the first province of the state count as further
+/// away, so states tend to cover a single kind of terrain.  States left with fewer than half of
+/// `provinces_per_state` provinces are merged into a neighboring state where there is one.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct StateGenerator {
+    /// The number of provinces to aim for in each state
+    pub provinces_per_state: u32,
+    /// How much a different terrain counts against adding a province, in province widths
+    pub terrain_weight: f64,
+    /// The manpower of each pixel of land in a state
+    pub manpower_per_pixel: f64,
+    /// The category given to every state
+    pub state_category: StateCategoryName,
+    /// Whether states can continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for StateGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl StateGenerator {
+    /// Creates a new state generator with the default settings
+    #[inline]
+    #[must_use]
+    pub fn new(provinces_per_state: u32) -> Self {
+        Self {
+            provinces_per_state,
+            terrain_weight: 1.0,
+            manpower_per_pixel: 50.0,
+            state_category: StateCategoryName("rural".to_owned()),
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Generates the states for the land provinces of the map.  The states are named
+    /// `STATE_<id>`, with ids counting up from 1.
+    /// # Errors
+    /// * If the provinces per state is 0
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn generate(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+    ) -> Result<States, MapError> {
+        if self.provinces_per_state == 0 {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The provinces per state must be at least 1".to_owned(),
+            ));
+        }
+        let land = measure_provinces(provinces, definitions, self.wrapping, |d| {
+            d.province_type == ProvinceType::Land
+        });
+        let width = f64::from(provinces.width());
+        let groups = self.merge_small_groups(self.grow_groups(&land, width), &land);
+
+        let mut states = HashMap::new();
+        for (index, group) in groups.into_iter().enumerate() {
+            let id = StateId(index as i32 + 1);
+            let pixels = group
+                .iter()
+                .filter_map(|p| land.get(p))
+                .map(|info| f64::from(info.pixels))
+                .sum::<f64>();
+            let manpower = (pixels * self.manpower_per_pixel).round().max(1.0) as u32;
+            let state = State {
+                id,
+                name: StateName(format!("STATE_{}", id.0)),
+                manpower: vec![Manpower(manpower)],
+                state_category: vec![self.state_category.clone()],
+                history: None,
+                provinces: group.into_iter().collect(),
+                local_supplies: None,
+                impassable: None,
+                buildings_max_level_factor: None,
+            };
+            states.insert(id, state);
+        }
+        Ok(States {
+            states,
+            sources: HashMap::new(),
+            duplicates: Vec::new(),
+        })
+    }
+
+    /// Grows groups of neighboring provinces up to the target size
+    #[allow(clippy::as_conversions)]
+    fn grow_groups(
+        &self,
+        land: &HashMap<ProvinceId, ProvinceInfo>,
+        width: f64,
+    ) -> Vec<Vec<ProvinceId>> {
+        // Start each state from the top left-most province left, so states tile the map in rows
+        let mut order = land.iter().collect::<Vec<_>>();
+        order.sort_by(|(a_id, a), (b_id, b)| {
+            a.y.total_cmp(&b.y)
+                .then(a.x.total_cmp(&b.x))
+                .then(a_id.0.cmp(&b_id.0))
+        });
+        let average_pixels = land.values().map(|p| f64::from(p.pixels)).sum::<f64>()
+            / f64::from(u32::try_from(land.len().max(1)).unwrap_or(u32::MAX));
+        let province_width = average_pixels.sqrt().max(1.0);
+
+        let mut assigned = HashSet::new();
+        let mut groups = Vec::new();
+        for (seed, seed_info) in order {
+            if !assigned.insert(*seed) {
+                continue;
+            }
+            let mut group = vec![*seed];
+            // The middle of the state is tracked as an offset from the seed, as it may cross the
+            // edge of a wrapping map
+            let (mut sum_x, mut sum_y, mut count) = (0.0_f64, seed_info.y, 1.0_f64);
+            let mut frontier = seed_info.neighbors.clone();
+            while group.len() < self.provinces_per_state as usize {
+                let best = frontier
+                    .iter()
+                    .filter(|id| !assigned.contains(*id))
+                    .filter_map(|id| land.get(id).map(|info| (*id, info)))
+                    .map(|(id, info)| {
+                        let middle = (seed_info.x + sum_x / count, sum_y / count);
+                        let distance = self.wrapping.distance(middle, (info.x, info.y), width);
+                        let terrain_cost = if info.terrain == seed_info.terrain {
+                            0.0_f64
+                        } else {
+                            self.terrain_weight
+                        };
+                        (id, info, distance / province_width + terrain_cost)
+                    })
+                    .min_by(|(a_id, _a, a), (b_id, _b, b)| {
+                        a.total_cmp(b).then(a_id.0.cmp(&b_id.0))
+                    });
+                let (id, info) = match best {
+                    Some((id, info, _cost)) => (id, info),
+                    None => break,
+                };
+                assigned.insert(id);
+                group.push(id);
+                sum_x += self.wrapping.delta_x(seed_info.x, info.x, width);
+                sum_y += info.y;
+                count += 1.0_f64;
+                frontier.extend(info.neighbors.iter().copied());
+            }
+            groups.push(group);
+        }
+        groups
+    }
+
+    /// Merges groups with fewer than half the target provinces into the smallest neighboring
+    /// group, preferring groups of the same terrain.
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    fn merge_small_groups(
+        &self,
+        mut groups: Vec<Vec<ProvinceId>>,
+        land: &HashMap<ProvinceId, ProvinceInfo>,
+    ) -> Vec<Vec<ProvinceId>> {
+        let min_size = (self.provinces_per_state as usize + 1) / 2;
+        let mut group_of = HashMap::new();
+        for (index, group) in groups.iter().enumerate() {
+            for id in group {
+                group_of.insert(*id, index);
+            }
+        }
+        for index in 0..groups.len() {
+            let group = match groups.get(index) {
+                Some(g) if !g.is_empty() && g.len() < min_size => g.clone(),
+                _ => continue,
+            };
+            let terrain = group
+                .first()
+                .and_then(|id| land.get(id))
+                .and_then(|info| info.terrain.clone());
+            let target = group
+                .iter()
+                .filter_map(|id| land.get(id))
+                .flat_map(|info| info.neighbors.iter())
+                .filter_map(|id| group_of.get(id).copied())
+                .filter(|other| *other != index)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .min_by_key(|other| {
+                    let other_group = groups.get(*other).map(Vec::as_slice).unwrap_or_default();
+                    let other_terrain = other_group
+                        .first()
+                        .and_then(|id| land.get(id))
+                        .and_then(|info| info.terrain.clone());
+                    (other_terrain != terrain, other_group.len())
+                });
+            if let Some(target) = target {
+                for id in &group {
+                    group_of.insert(*id, target);
+                }
+                if let Some(g) = groups.get_mut(target) {
+                    g.extend(group);
+                }
+                if let Some(g) = groups.get_mut(index) {
+                    g.clear();
+                }
+            }
+        }
+        groups.retain(|g| !g.is_empty());
+        groups
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::provinces::ProvinceGenerator;
+    use image::Rgb;
+
+    /// A heightmap with land on the left and a small island on the right
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |x, y| {
+            let island = (200..220).contains(&x) && (100..120).contains(&y);
+            if x < 128 || island {
+                Rgb([150, 150, 150])
+            } else {
+                Rgb([50, 50, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn it_assigns_each_land_province_to_one_state() {
+        let generated = ProvinceGenerator::new(1, 80)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let states = StateGenerator::new(5)
+            .generate(&generated.provinces, &generated.definitions)
+            .expect("Failed to generate states");
+        let mut seen = HashSet::new();
+        for state in states.states.values() {
+            assert_eq!(state.name.0, format!("STATE_{}", state.id.0));
+            assert!(state.manpower.iter().all(|m| m.0 > 0));
+            for province in &state.provinces {
+                assert!(seen.insert(*province), "{province} is in two states");
+            }
+        }
+        let land = generated
+            .definitions
+            .definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .map(|d| d.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(seen, land);
+        assert!(states.states.len() > 1);
+    }
+
+    #[test]
+    fn it_keeps_states_contiguous() {
+        let generated = ProvinceGenerator::new(2, 80)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let land = measure_provinces(
+            &generated.provinces,
+            &generated.definitions,
+            Wrapping::None,
+            |d| d.province_type == ProvinceType::Land,
+        );
+        let states = StateGenerator::new(6)
+            .generate(&generated.provinces, &generated.definitions)
+            .expect("Failed to generate states");
+        for state in states.states.values() {
+            let start = *state.provinces.iter().next().expect("Empty state");
+            let mut reached = HashSet::from([start]);
+            let mut stack = vec![start];
+            while let Some(id) = stack.pop() {
+                for neighbor in &land[&id].neighbors {
+                    if state.provinces.contains(neighbor) && reached.insert(*neighbor) {
+                        stack.push(*neighbor);
+                    }
+                }
+            }
+            assert_eq!(reached, state.provinces);
+        }
+    }
+
+    #[test]
+    fn it_rejects_empty_states() {
+        let generated = ProvinceGenerator::new(2, 10)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let result = StateGenerator::new(0).generate(&generated.provinces, &generated.definitions);
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+}
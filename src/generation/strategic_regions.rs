@@ -0,0 +1,526 @@
+use crate::components::day_month::{DayMonth, LAST_DAYS};
+use crate::components::prelude::*;
+use crate::components::state::States;
+use crate::components::strategic_region::{Period, StrategicRegion, StrategicRegions, Weather};
+use crate::generation::{measure_provinces, ProvinceInfo};
+use crate::topology::Wrapping;
+use crate::MapError;
+use image::RgbImage;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::f64::consts::PI;
+
+/// The default weather of a strategic region, picked from the latitude of its center.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WeatherTemplate {
+    /// Frozen for most of the year, with the sea freezing over in winter
+    Arctic,
+    /// Long snowy winters and short summers
+    Cold,
+    /// Wet weather with mild winters
+    Temperate,
+    /// Hot summers and short rainy winters
+    Subtropical,
+    /// Hot and wet all year
+    Tropical,
+}
+
+impl WeatherTemplate {
+    /// Gets the template for the given latitude in degrees, positive to the north
+    #[inline]
+    #[must_use]
+    pub fn from_latitude(latitude: f64) -> Self {
+        match latitude.abs() {
+            l if l >= 66.0 => Self::Arctic,
+            l if l >= 50.0 => Self::Cold,
+            l if l >= 35.0 => Self::Temperate,
+            l if l >= 23.0 => Self::Subtropical,
+            _ => Self::Tropical,
+        }
+    }
+
+    /// The average temperature over the year, how far the temperature moves from it with the
+    /// seasons, and the chance of rain or snow.
+    const fn climate(self) -> (f64, f64, f64) {
+        match self {
+            Self::Arctic => (-12.0, 14.0, 0.15),
+            Self::Cold => (3.0, 12.0, 0.25),
+            Self::Temperate => (11.0, 9.0, 0.3),
+            Self::Subtropical => (19.0, 6.0, 0.25),
+            Self::Tropical => (26.0, 2.0, 0.4),
+        }
+    }
+
+    /// Creates the weather with a period for each month.  The seasons of the southern hemisphere
+    /// are the reverse of those of the northern hemisphere.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn weather(self, southern: bool) -> Weather {
+        let (average, seasons, precipitation) = self.climate();
+        let hemisphere = if southern { -1.0_f64 } else { 1.0_f64 };
+        let period = LAST_DAYS
+            .iter()
+            .zip(0_u8..)
+            .map(|(last_day, month)| {
+                // The middle of July is the height of summer in the north
+                let season = (2.0 * PI * (f64::from(month) - 6.5) / 12.0).cos() * hemisphere;
+                let temperature = seasons.mul_add(season, average);
+                let (rain, snow) = if temperature < -2.0_f64 {
+                    (0.0_f64, precipitation)
+                } else if temperature < 2.0_f64 {
+                    (precipitation / 2.0_f64, precipitation / 2.0_f64)
+                } else {
+                    (precipitation, 0.0_f64)
+                };
+                let effects = [
+                    ("no_phenomenon", 1.0_f64 - precipitation),
+                    ("rain_light", rain * 0.6_f64),
+                    ("rain_heavy", rain * 0.4_f64),
+                    ("snow", snow * 0.7_f64),
+                    ("blizzard", snow * 0.3_f64),
+                    ("arctic_water", f64::from(u8::from(temperature < -10.0_f64))),
+                    ("mud", 1.0_f64),
+                    ("sandstorm", 0.0_f64),
+                ];
+                Period {
+                    between: [
+                        DayMonth { day: 0, month },
+                        DayMonth {
+                            day: *last_day,
+                            month,
+                        },
+                    ],
+                    temperature: [
+                        Temperature((temperature - 3.0) as f32),
+                        Temperature((temperature + 3.0) as f32),
+                    ],
+                    temperature_day_night: None,
+                    weather_effects: effects
+                        .into_iter()
+                        .map(|(effect, weight)| {
+                            (WeatherEffect(effect.to_owned()), Weight(weight as f32))
+                        })
+                        .collect(),
+                    min_snow_level: SnowLevel(if temperature + 3.0 < 0.0 { 0.5 } else { 0.0 }),
+                }
+            })
+            .collect();
+        Weather { period }
+    }
+}
+
+/// A state, or a province outside of any state, that is kept whole in a single strategic region.
+#[derive(Clone, Debug, Default)]
+struct Unit {
+    provinces: Vec<ProvinceId>,
+    pixels: u32,
+    x: f64,
+    y: f64,
+    water: bool,
+    neighbors: BTreeSet<usize>,
+}
+
+/// Groups states into strategic regions, and sea provinces into naval strategic regions.
+///
+/// A state is never split across strategic regions.  Regions are grown from the top left of the
+/// map, adding the neighboring state closest to the middle of the region until it holds
+/// `states_per_region` states, while sea and lake provinces are grouped the same way into
+/// regions of `sea_provinces_per_region` provinces.  Land provinces outside of any state are
+/// grouped as if they were states of their own.  Each region is given the weather of the
+/// latitude of its center, with the latitude running from `north_latitude` at the top of the map
+/// to `south_latitude` at the bottom.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct StrategicRegionGenerator {
+    /// The number of states to aim for in each land region
+    pub states_per_region: u32,
+    /// The number of sea provinces to aim for in each naval region
+    pub sea_provinces_per_region: u32,
+    /// The latitude in degrees at the top of the map
+    pub north_latitude: f64,
+    /// The latitude in degrees at the bottom of the map
+    pub south_latitude: f64,
+    /// Whether regions can continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for StrategicRegionGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(6, 12)
+    }
+}
+
+impl StrategicRegionGenerator {
+    /// Creates a new strategic region generator with the default settings
+    #[inline]
+    #[must_use]
+    pub const fn new(states_per_region: u32, sea_provinces_per_region: u32) -> Self {
+        Self {
+            states_per_region,
+            sea_provinces_per_region,
+            north_latitude: 75.0,
+            south_latitude: -75.0,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Generates the strategic regions covering every province of the map.  The regions are
+    /// named `REGION_<id>`, with ids counting up from 1 for the land regions and then the naval
+    /// regions.
+    /// # Errors
+    /// * If the states or sea provinces per region is 0
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn generate(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+        states: &States,
+    ) -> Result<StrategicRegions, MapError> {
+        if self.states_per_region == 0 || self.sea_provinces_per_region == 0 {
+            return Err(MapError::InvalidGeneratorSettings(
+                "The states and sea provinces per region must be at least 1".to_owned(),
+            ));
+        }
+        let measured = measure_provinces(provinces, definitions, self.wrapping, |_d| true);
+        let width = f64::from(provinces.width());
+        let units = self.units(&measured, definitions, states, width);
+
+        let mut strategic_regions = HashMap::new();
+        for water in [false, true] {
+            let target = if water {
+                self.sea_provinces_per_region
+            } else {
+                self.states_per_region
+            };
+            for group in self.group_units(&units, water, target as usize, width) {
+                let id = StrategicRegionId(strategic_regions.len() as i32 + 1);
+                let members = group.iter().filter_map(|u| units.get(*u));
+                let (_x, y) = self.center(members.clone().map(|u| (u.x, u.y, u.pixels)), width);
+                let latitude = self.latitude(y, f64::from(provinces.height()));
+                let region = StrategicRegion {
+                    id,
+                    name: StrategicRegionName(format!("REGION_{}", id.0)),
+                    provinces: members.flat_map(|u| u.provinces.iter().copied()).collect(),
+                    weather: WeatherTemplate::from_latitude(latitude).weather(latitude < 0.0_f64),
+                };
+                strategic_regions.insert(id, region);
+            }
+        }
+        Ok(StrategicRegions {
+            strategic_regions,
+            sources: HashMap::new(),
+        })
+    }
+
+    /// The latitude of a row of the map
+    fn latitude(&self, y: f64, height: f64) -> f64 {
+        let fraction = if height > 0.0_f64 {
+            y / height
+        } else {
+            0.0_f64
+        };
+        (self.south_latitude - self.north_latitude).mul_add(fraction, self.north_latitude)
+    }
+
+    /// The center of a set of weighted points, taking the shorter way around a wrapping map
+    fn center<I: Iterator<Item = (f64, f64, u32)>>(&self, points: I, width: f64) -> (f64, f64) {
+        let mut reference = None;
+        let (mut sum_x, mut sum_y, mut total) = (0.0_f64, 0.0_f64, 0.0_f64);
+        for (x, y, pixels) in points {
+            let origin = *reference.get_or_insert(x);
+            let weight = f64::from(pixels.max(1));
+            sum_x += self.wrapping.delta_x(origin, x, width) * weight;
+            sum_y += y * weight;
+            total += weight;
+        }
+        let total = total.max(1.0);
+        let origin = reference.unwrap_or_default();
+        (
+            self.wrapping.wrap_x(origin + sum_x / total, width),
+            sum_y / total,
+        )
+    }
+
+    /// Gathers the provinces into the states and provinces that are kept whole
+    fn units(
+        &self,
+        measured: &HashMap<ProvinceId, ProvinceInfo>,
+        definitions: &Definitions,
+        states: &States,
+        width: f64,
+    ) -> Vec<Unit> {
+        let mut state_ids = states.states.keys().copied().collect::<Vec<_>>();
+        state_ids.sort();
+        let mut members = state_ids
+            .iter()
+            .filter_map(|id| states.states.get(id))
+            .map(|state| {
+                let mut provinces = state
+                    .provinces
+                    .iter()
+                    .filter(|p| measured.contains_key(p))
+                    .copied()
+                    .collect::<Vec<_>>();
+                provinces.sort();
+                (provinces, false)
+            })
+            .filter(|(provinces, _water)| !provinces.is_empty())
+            .collect::<Vec<_>>();
+        let in_states = members
+            .iter()
+            .flat_map(|(provinces, _water)| provinces.iter().copied())
+            .collect::<HashSet<_>>();
+        let mut others = measured
+            .keys()
+            .filter(|id| !in_states.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        others.sort();
+        for id in others {
+            let water = definitions
+                .definitions
+                .get(&id)
+                .map_or(false, |d| d.province_type != ProvinceType::Land);
+            members.push((vec![id], water));
+        }
+
+        let mut unit_of = HashMap::new();
+        for (index, (provinces, _water)) in members.iter().enumerate() {
+            for id in provinces {
+                unit_of.insert(*id, index);
+            }
+        }
+        members
+            .into_iter()
+            .enumerate()
+            .map(|(index, (provinces, water))| {
+                let infos = provinces.iter().filter_map(|p| measured.get(p));
+                let (x, y) = self.center(infos.clone().map(|i| (i.x, i.y, i.pixels)), width);
+                let neighbors = infos
+                    .clone()
+                    .flat_map(|i| i.neighbors.iter())
+                    .filter_map(|n| unit_of.get(n).copied())
+                    .filter(|n| *n != index)
+                    .collect();
+                Unit {
+                    pixels: infos.map(|i| i.pixels).sum(),
+                    provinces,
+                    x,
+                    y,
+                    water,
+                    neighbors,
+                }
+            })
+            .collect()
+    }
+
+    /// Grows groups of neighboring units of land or water up to the target size, then merges
+    /// groups with fewer than half the target units into the smallest neighboring group.
+    #[allow(clippy::integer_arithmetic)]
+    fn group_units(
+        &self,
+        units: &[Unit],
+        water: bool,
+        target: usize,
+        width: f64,
+    ) -> Vec<Vec<usize>> {
+        let same_kind = |u: &usize| units.get(*u).map_or(false, |unit| unit.water == water);
+        // Start each region from the top left-most unit left, so regions tile the map in rows
+        let mut order = units
+            .iter()
+            .enumerate()
+            .filter(|(_index, unit)| unit.water == water)
+            .collect::<Vec<_>>();
+        order.sort_by(|(a, a_unit), (b, b_unit)| {
+            a_unit
+                .y
+                .total_cmp(&b_unit.y)
+                .then(a_unit.x.total_cmp(&b_unit.x))
+                .then(a.cmp(b))
+        });
+
+        let mut group_of = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (seed, _unit) in order {
+            if group_of.contains_key(&seed) {
+                continue;
+            }
+            group_of.insert(seed, groups.len());
+            let mut group = vec![seed];
+            while group.len() < target {
+                let (x, y) = self.center(
+                    group
+                        .iter()
+                        .filter_map(|u| units.get(*u))
+                        .map(|u| (u.x, u.y, u.pixels)),
+                    width,
+                );
+                let best = group
+                    .iter()
+                    .filter_map(|u| units.get(*u))
+                    .flat_map(|u| u.neighbors.iter().copied())
+                    .filter(|n| same_kind(n) && !group_of.contains_key(n))
+                    .filter_map(|n| units.get(n).map(|unit| (n, unit)))
+                    .map(|(n, unit)| (n, self.wrapping.distance((x, y), (unit.x, unit.y), width)))
+                    .min_by(|(a, a_distance), (b, b_distance)| {
+                        a_distance.total_cmp(b_distance).then(a.cmp(b))
+                    });
+                match best {
+                    Some((n, _distance)) => {
+                        group_of.insert(n, groups.len());
+                        group.push(n);
+                    }
+                    None => break,
+                }
+            }
+            groups.push(group);
+        }
+
+        let min_size = (target + 1) / 2;
+        for index in 0..groups.len() {
+            let group = match groups.get(index) {
+                Some(g) if !g.is_empty() && g.len() < min_size => g.clone(),
+                _ => continue,
+            };
+            let merge_into = group
+                .iter()
+                .filter_map(|u| units.get(*u))
+                .flat_map(|u| u.neighbors.iter())
+                .filter(|n| same_kind(n))
+                .filter_map(|n| group_of.get(n).copied())
+                .filter(|other| *other != index)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .min_by_key(|other| groups.get(*other).map_or(0, Vec::len));
+            if let Some(other) = merge_into {
+                for u in &group {
+                    group_of.insert(*u, other);
+                }
+                if let Some(g) = groups.get_mut(other) {
+                    g.extend(group);
+                }
+                if let Some(g) = groups.get_mut(index) {
+                    g.clear();
+                }
+            }
+        }
+        groups.retain(|g| !g.is_empty());
+        groups
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::provinces::ProvinceGenerator;
+    use crate::generation::states::StateGenerator;
+    use image::Rgb;
+
+    /// A heightmap with land on the left and sea on the right
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |x, _y| {
+            if x < 128 {
+                Rgb([150, 150, 150])
+            } else {
+                Rgb([50, 50, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn it_keeps_states_whole() {
+        let generated = ProvinceGenerator::new(3, 120)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let states = StateGenerator::new(4)
+            .generate(&generated.provinces, &generated.definitions)
+            .expect("Failed to generate states");
+        let regions = StrategicRegionGenerator::new(3, 6)
+            .generate(&generated.provinces, &generated.definitions, &states)
+            .expect("Failed to generate strategic regions");
+        assert!(regions.strategic_regions.len() > 2);
+
+        let mut region_of = HashMap::new();
+        for region in regions.strategic_regions.values() {
+            assert_eq!(region.name.0, format!("REGION_{}", region.id.0));
+            assert_eq!(region.weather.period.len(), 12);
+            for province in &region.provinces {
+                assert!(region_of.insert(*province, region.id).is_none());
+            }
+        }
+        let all = generated
+            .definitions
+            .definitions
+            .keys()
+            .filter(|id| id.0 != 0)
+            .copied()
+            .collect::<HashSet<_>>();
+        assert_eq!(region_of.keys().copied().collect::<HashSet<_>>(), all);
+        for state in states.states.values() {
+            let state_regions = state
+                .provinces
+                .iter()
+                .map(|p| region_of[p])
+                .collect::<HashSet<_>>();
+            assert_eq!(state_regions.len(), 1, "{} is split", state.name.0);
+        }
+        for region in regions.strategic_regions.values() {
+            let types = region
+                .provinces
+                .iter()
+                .map(|p| generated.definitions.definitions[p].province_type)
+                .map(|t| t == ProvinceType::Land)
+                .collect::<HashSet<_>>();
+            assert_eq!(types.len(), 1, "{} mixes land and sea", region.name.0);
+        }
+    }
+
+    #[test]
+    fn it_picks_the_weather_by_latitude() {
+        assert_eq!(
+            WeatherTemplate::from_latitude(80.0),
+            WeatherTemplate::Arctic
+        );
+        assert_eq!(
+            WeatherTemplate::from_latitude(-40.0),
+            WeatherTemplate::Temperate
+        );
+        assert_eq!(
+            WeatherTemplate::from_latitude(5.0),
+            WeatherTemplate::Tropical
+        );
+
+        let north = WeatherTemplate::Cold.weather(false);
+        let south = WeatherTemplate::Cold.weather(true);
+        let january = |w: &Weather| w.period[0].temperature[0].0;
+        assert!(january(&north) < january(&south));
+        assert_eq!(north.period[1].between[1], DayMonth { day: 27, month: 1 });
+        assert!(north.period[0].weather_effects[&WeatherEffect("snow".to_owned())].0 > 0.0);
+    }
+
+    #[test]
+    fn it_rejects_empty_regions() {
+        let generated = ProvinceGenerator::new(3, 20)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let states = StateGenerator::default()
+            .generate(&generated.provinces, &generated.definitions)
+            .expect("Failed to generate states");
+        let result = StrategicRegionGenerator::new(0, 4).generate(
+            &generated.provinces,
+            &generated.definitions,
+            &states,
+        );
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::components::prelude::*;
+use crate::components::railway::{Railway, Railways};
+use crate::components::state::States;
+use crate::components::supply_node::SupplyNodes;
+use crate::generation::{measure_provinces, ProvinceInfo};
+use crate::topology::Wrapping;
+use crate::MapError;
+use image::RgbImage;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// The supply nodes and the railways connecting them
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct GeneratedSupply {
+    /// A supply node in each state
+    pub supply_nodes: SupplyNodes,
+    /// The railways between the supply nodes
+    pub railways: Railways,
+}
+
+/// Places a supply node in each state and connects them with railways.
+///
+/// The supply node of a state is placed in its province with the most victory points, or in its
+/// largest land province if it has none.  The railways are laid as a network grown from the
+/// first supply node, each time adding the cheapest path over land from the network to a supply
+/// node not yet connected.  The cost of a path is the sum of the terrain costs of the provinces
+/// it enters, so that railways go around mountains and marshes where they can.  Supply nodes
+/// that cannot be reached over land, such as those on islands, start a network of their own.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SupplyGenerator {
+    /// The level of the generated railways, from 1 to 5
+    pub rail_level: RailLevel,
+    /// Whether railways can continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for SupplyGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(RailLevel(1))
+    }
+}
+
+impl SupplyGenerator {
+    /// Creates a new supply generator
+    #[inline]
+    #[must_use]
+    pub const fn new(rail_level: RailLevel) -> Self {
+        Self {
+            rail_level,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Generates the supply nodes of the states and the railways between them
+    /// # Errors
+    /// * If the rail level is not between 1 and 5
+    #[inline]
+    pub fn generate(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+        states: &States,
+    ) -> Result<GeneratedSupply, MapError> {
+        if !(1_i32..=5_i32).contains(&self.rail_level.0) {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "The rail level must be between 1 and 5, not {}",
+                self.rail_level
+            )));
+        }
+        let measured = measure_provinces(provinces, definitions, self.wrapping, |d| {
+            d.province_type == ProvinceType::Land
+        });
+        let nodes = supply_nodes(&measured, states);
+
+        let mut railways = Vec::new();
+        let mut network: HashSet<ProvinceId> = HashSet::new();
+        let mut unconnected = nodes.iter().copied().collect::<HashSet<_>>();
+        for start in &nodes {
+            if !unconnected.remove(start) {
+                continue;
+            }
+            network.insert(*start);
+            while let Some(path) = cheapest_path(&measured, &network, &unconnected) {
+                // Supply nodes the railway passes through are connected along with its end
+                for province in &path {
+                    unconnected.remove(province);
+                    network.insert(*province);
+                }
+                railways.push(Railway {
+                    level: self.rail_level,
+                    length: path.len(),
+                    provinces: path,
+                });
+            }
+        }
+        Ok(GeneratedSupply {
+            supply_nodes: SupplyNodes {
+                nodes: nodes.into_iter().collect(),
+            },
+            railways: Railways { railways },
+        })
+    }
+}
+
+/// Picks the supply node of each state, in order of state id
+fn supply_nodes(measured: &HashMap<ProvinceId, ProvinceInfo>, states: &States) -> Vec<ProvinceId> {
+    let mut state_ids = states.states.keys().copied().collect::<Vec<_>>();
+    state_ids.sort();
+    state_ids
+        .iter()
+        .filter_map(|id| states.states.get(id))
+        .filter_map(|state| {
+            let victory_point = state
+                .history
+                .iter()
+                .flat_map(|h| h.victory_points.iter())
+                .filter(|(province, _points)| measured.contains_key(province))
+                .max_by(|(a, a_points), (b, b_points)| {
+                    a_points.0.total_cmp(&b_points.0).then_with(|| b.cmp(a))
+                })
+                .map(|(province, _points)| *province);
+            victory_point.or_else(|| {
+                state
+                    .provinces
+                    .iter()
+                    .filter_map(|p| measured.get(p).map(|info| (p, info.pixels)))
+                    .max_by_key(|(p, pixels)| (*pixels, Reverse(**p)))
+                    .map(|(p, _pixels)| *p)
+            })
+        })
+        .collect()
+}
+
+/// Finds the cheapest path from the network to any of the targets, starting with the province of
+/// the network it leaves from.  Returns `None` if no target can be reached.
+#[allow(clippy::integer_arithmetic)]
+fn cheapest_path(
+    measured: &HashMap<ProvinceId, ProvinceInfo>,
+    network: &HashSet<ProvinceId>,
+    targets: &HashSet<ProvinceId>,
+) -> Option<Vec<ProvinceId>> {
+    let mut costs: HashMap<ProvinceId, u32> = HashMap::new();
+    let mut previous: HashMap<ProvinceId, ProvinceId> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for province in network {
+        costs.insert(*province, 0);
+        queue.push(Reverse((0_u32, *province)));
+    }
+    while let Some(Reverse((cost, province))) = queue.pop() {
+        if costs.get(&province).map_or(false, |c| cost > *c) {
+            continue;
+        }
+        if targets.contains(&province) {
+            let mut path = vec![province];
+            while let Some(p) = path.last().and_then(|p| previous.get(p)) {
+                path.push(*p);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let neighbors = measured.get(&province).map(|info| &info.neighbors);
+        for neighbor in neighbors.into_iter().flatten() {
+            let terrain = measured
+                .get(neighbor)
+                .and_then(|info| info.terrain.as_ref());
+            let next = cost + terrain_cost(terrain);
+            if costs.get(neighbor).map_or(true, |c| next < *c) {
+                costs.insert(*neighbor, next);
+                previous.insert(*neighbor, province);
+                queue.push(Reverse((next, *neighbor)));
+            }
+        }
+    }
+    None
+}
+
+/// The cost of laying a railway into a province with the given terrain
+fn terrain_cost(terrain: Option<&Terrain>) -> u32 {
+    match terrain.map(|t| t.0.as_str()) {
+        Some("plains" | "urban") => 2,
+        Some("forest" | "desert") => 3,
+        Some("hills" | "jungle") => 4,
+        Some("marsh") => 5,
+        Some("mountain") => 6,
+        _ => 3,
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generation::provinces::ProvinceGenerator;
+    use crate::generation::states::StateGenerator;
+    use image::Rgb;
+
+    /// A heightmap with land on the left and sea on the right
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |x, _y| {
+            if x < 128 {
+                Rgb([150, 150, 150])
+            } else {
+                Rgb([50, 50, 50])
+            }
+        })
+    }
+
+    #[test]
+    fn it_connects_every_supply_node() {
+        let generated = ProvinceGenerator::new(3, 120)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let states = StateGenerator::new(4)
+            .generate(&generated.provinces, &generated.definitions)
+            .expect("Failed to generate states");
+        let supply = SupplyGenerator::default()
+            .generate(&generated.provinces, &generated.definitions, &states)
+            .expect("Failed to generate supply");
+        assert_eq!(supply.supply_nodes.nodes.len(), states.states.len());
+        for state in states.states.values() {
+            assert_eq!(
+                state
+                    .provinces
+                    .iter()
+                    .filter(|p| supply.supply_nodes.nodes.contains(p))
+                    .count(),
+                1,
+                "{} should have one supply node",
+                state.name.0
+            );
+        }
+
+        let measured = measure_provinces(
+            &generated.provinces,
+            &generated.definitions,
+            Wrapping::None,
+            |_d| true,
+        );
+        let mut railed = HashSet::new();
+        for railway in &supply.railways.railways {
+            assert_eq!(railway.length, railway.provinces.len());
+            assert!(railway.length >= 2);
+            for pair in railway.provinces.windows(2) {
+                assert!(measured[&pair[0]].neighbors.contains(&pair[1]));
+            }
+            railed.extend(railway.provinces.iter().copied());
+        }
+        // The land is in one piece, so every supply node is on the network
+        assert!(supply.supply_nodes.nodes.iter().all(|n| railed.contains(n)));
+    }
+
+    #[test]
+    fn it_rejects_invalid_rail_levels() {
+        let generated = ProvinceGenerator::new(3, 40)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let states = States {
+            states: HashMap::new(),
+            sources: HashMap::new(),
+            duplicates: Vec::new(),
+        };
+        let result = SupplyGenerator::new(RailLevel(6)).generate(
+            &generated.provinces,
+            &generated.definitions,
+            &states,
+        );
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+}
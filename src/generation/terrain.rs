@@ -0,0 +1,686 @@
+use crate::components::prelude::*;
+use crate::generation::noise::{Fractal, Noise, NoiseKind};
+use crate::generation::provinces::GeneratedProvinces;
+use crate::generation::save_indexed_bmp;
+use crate::province_index::ProvinceIndex;
+use crate::validation::province_heights::SEA_LEVEL;
+use crate::MapError;
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The palette of terrain.bmp.  The indexes are the colors of the graphical terrains at the bottom
+/// of `common/terrain/00_terrain.txt`.
+pub const TERRAIN_PALETTE: [[u8; 3]; 21] = [
+    [0, 0, 0],
+    [8, 31, 130],
+    [255, 255, 255],
+    [6, 248, 40],
+    [0, 179, 24],
+    [73, 244, 96],
+    [122, 244, 138],
+    [183, 244, 191],
+    [0, 86, 6],
+    [0, 44, 0],
+    [75, 147, 174],
+    [174, 0, 255],
+    [98, 32, 145],
+    [255, 255, 0],
+    [255, 252, 76],
+    [209, 209, 209],
+    [179, 179, 179],
+    [255, 253, 128],
+    [255, 254, 191],
+    [255, 0, 24],
+    [160, 84, 76],
+];
+
+/// The terrain.bmp index of `terrain_filler`
+const FILLER_INDEX: u8 = 0;
+/// The terrain.bmp index of `terrain_ocean`
+const OCEAN_INDEX: u8 = 1;
+
+/// The color of the provinces whose terrain is not a known category on the map of the terrain of
+/// the definitions.  It is not one of the colors of terrain.bmp.
+pub const UNKNOWN_TERRAIN_COLOR: Rgb<u8> = Rgb([255, 0, 255]);
+
+/// A terrain category from `common/terrain/00_terrain.txt` that can be assigned to a province.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[allow(clippy::exhaustive_enums)]
+pub enum TerrainKind {
+    /// Sea provinces
+    Ocean,
+    /// Lake provinces
+    Lakes,
+    /// Flat, temperate or cold land
+    Plains,
+    /// Flat land with a lot of rain
+    Forest,
+    /// Rough or raised land
+    Hills,
+    /// Steep or high land
+    Mountain,
+    /// Warm land with little rain
+    Desert,
+    /// Warm land with a lot of rain
+    Jungle,
+    /// Low land with a lot of rain
+    Marsh,
+    /// Sea provinces with shallow water or close to the coast
+    ShallowSea,
+    /// Sea provinces with deep water far from the coast
+    DeepOcean,
+}
+
+impl TerrainKind {
+    /// The name of the terrain category
+    #[inline]
+    #[must_use]
+    pub const fn category(&self) -> &'static str {
+        match self {
+            Self::Ocean => "ocean",
+            Self::Lakes => "lakes",
+            Self::Plains => "plains",
+            Self::Forest => "forest",
+            Self::Hills => "hills",
+            Self::Mountain => "mountain",
+            Self::Desert => "desert",
+            Self::Jungle => "jungle",
+            Self::Marsh => "marsh",
+            Self::ShallowSea => "water_shallow_sea",
+            Self::DeepOcean => "water_deep_ocean",
+        }
+    }
+
+    /// Gets the terrain category with the given name, if it is one of the known categories
+    #[inline]
+    #[must_use]
+    pub fn from_category(category: &str) -> Option<Self> {
+        match category {
+            "ocean" => Some(Self::Ocean),
+            "lakes" => Some(Self::Lakes),
+            "plains" => Some(Self::Plains),
+            "forest" => Some(Self::Forest),
+            "hills" => Some(Self::Hills),
+            "mountain" => Some(Self::Mountain),
+            "desert" => Some(Self::Desert),
+            "jungle" => Some(Self::Jungle),
+            "marsh" => Some(Self::Marsh),
+            "water_shallow_sea" => Some(Self::ShallowSea),
+            "water_deep_ocean" => Some(Self::DeepOcean),
+            _ => None,
+        }
+    }
+
+    /// The color of terrain.bmp the category is drawn with in a temperate climate
+    #[inline]
+    #[must_use]
+    pub fn color(self) -> Rgb<u8> {
+        let index = self
+            .graphical_indices()
+            .first()
+            .copied()
+            .unwrap_or(FILLER_INDEX);
+        Rgb(TERRAIN_PALETTE
+            .get(usize::from(index))
+            .copied()
+            .unwrap_or_default())
+    }
+
+    /// The terrain.bmp indices of the graphical terrains drawn for the category in any climate,
+    /// starting with the one drawn in a temperate climate
+    const fn graphical_indices(self) -> &'static [u8] {
+        match self {
+            Self::Ocean | Self::Lakes | Self::ShallowSea | Self::DeepOcean => &[OCEAN_INDEX],
+            Self::Plains => &[3, 2],
+            Self::Hills => &[5, 14],
+            Self::Mountain => &[7, 17],
+            Self::Forest => &[8],
+            Self::Marsh => &[10],
+            Self::Jungle => &[11],
+            Self::Desert => &[13],
+        }
+    }
+}
+
+/// The climate of a province, which picks the graphical terrain drawn on the map.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Climate {
+    Cold,
+    Temperate,
+    Arid,
+}
+
+/// The terrain assigned to a province, along with the climate it was assigned in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ProvinceTerrain {
+    kind: TerrainKind,
+    climate: Climate,
+}
+
+impl ProvinceTerrain {
+    /// The index of the graphical terrain drawn in terrain.bmp
+    const fn index(self) -> u8 {
+        match (self.kind, self.climate) {
+            (
+                TerrainKind::Ocean
+                | TerrainKind::Lakes
+                | TerrainKind::ShallowSea
+                | TerrainKind::DeepOcean,
+                _,
+            ) => OCEAN_INDEX,
+            (TerrainKind::Plains, Climate::Cold) => 2,
+            (TerrainKind::Plains, _) => 3,
+            (TerrainKind::Hills, Climate::Arid) => 14,
+            (TerrainKind::Hills, _) => 5,
+            (TerrainKind::Mountain, Climate::Arid) => 17,
+            (TerrainKind::Mountain, _) => 7,
+            (TerrainKind::Forest, _) => 8,
+            (TerrainKind::Marsh, _) => 10,
+            (TerrainKind::Jungle, _) => 11,
+            (TerrainKind::Desert, _) => 13,
+        }
+    }
+}
+
+/// The measurements of a province used to classify its terrain.
+#[derive(Copy, Clone, Debug, Default)]
+struct ProvinceStats {
+    pixels: u32,
+    height: f64,
+    slope: f64,
+    x: f64,
+    y: f64,
+}
+
+/// An indexed terrain image using the `TERRAIN_PALETTE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TerrainImage {
+    /// The palette index of each pixel
+    pub indices: GrayImage,
+}
+
+impl TerrainImage {
+    /// Converts the indexes to the colors of the palette, as terrain.bmp is read by the map
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    pub fn to_rgb(&self) -> RgbImage {
+        RgbImage::from_fn(self.indices.width(), self.indices.height(), |x, y| {
+            let index = self.indices.get_pixel(x, y).0[0];
+            Rgb(TERRAIN_PALETTE
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default())
+        })
+    }
+
+    /// Saves the image as an 8-bit indexed BMP
+    /// # Errors
+    /// * If the file could not be written
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        save_indexed_bmp(path, &self.indices, &TERRAIN_PALETTE)
+    }
+}
+
+/// Assigns a terrain to each province from its elevation, slope, latitude and moisture.
+///
+/// The top and bottom of the map are the poles and the middle is the equator.  Moisture is
+/// sampled from noise, so the same seed always gives the same deserts and forests.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TerrainClassifier {
+    /// The seed for the moisture noise
+    pub seed: u64,
+    /// The layering of the moisture noise
+    pub moisture: Fractal,
+    /// The average change in height per pixel above which a province is hills
+    pub hills_slope: f64,
+    /// The average change in height per pixel above which a province is mountains
+    pub mountain_slope: f64,
+    /// The average height above which a province is hills
+    pub hills_height: u8,
+    /// The average height above which a province is mountains
+    pub mountain_height: u8,
+    /// The temperature below which a province is cold, from 0.0 at the poles to 1.0 at the equator
+    pub cold_temperature: f64,
+    /// The temperature above which a province is warm enough for deserts and jungles
+    pub warm_temperature: f64,
+    /// The moisture below which a warm province is desert, from 0.0 to 1.0
+    pub desert_moisture: f64,
+    /// The moisture above which a province is forest, jungle or marsh, from 0.0 to 1.0
+    pub forest_moisture: f64,
+    /// The moisture above which a low-lying province is marsh, from 0.0 to 1.0
+    pub marsh_moisture: f64,
+}
+
+impl Default for TerrainClassifier {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl TerrainClassifier {
+    /// Creates a new terrain classifier with the default settings
+    #[inline]
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            moisture: Fractal::new(4, 1.0 / 1024.0, 2.0, 0.5),
+            hills_slope: 1.5,
+            mountain_slope: 3.0,
+            hills_height: 150,
+            mountain_height: 190,
+            cold_temperature: 0.2,
+            warm_temperature: 0.6,
+            desert_moisture: 0.35,
+            forest_moisture: 0.55,
+            marsh_moisture: 0.75,
+        }
+    }
+
+    /// Assigns a terrain to each of the generated provinces, updating their definitions, and
+    /// draws the matching terrain.bmp.
+    /// # Errors
+    /// * If the heightmap is not the same size as the provinces image
+    #[inline]
+    pub fn classify(
+        &self,
+        heightmap: &RgbImage,
+        generated: &mut GeneratedProvinces,
+    ) -> Result<TerrainImage, MapError> {
+        let (width, height) = generated.provinces.dimensions();
+        if heightmap.dimensions() != (width, height) {
+            return Err(MapError::ImageSizeMismatch(
+                "heightmap does not match provinces map".to_owned(),
+            ));
+        }
+        let ids = generated
+            .definitions
+            .definitions
+            .values()
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.id))
+            .collect::<HashMap<_, _>>();
+        let stats = province_stats(heightmap, &generated.provinces, &ids);
+        let moisture = Noise::new(NoiseKind::default(), self.seed);
+
+        let mut terrains = HashMap::new();
+        for definition in generated.definitions.definitions.values_mut() {
+            if definition.id.0 == 0_i32 {
+                continue;
+            }
+            let province_stats = stats.get(&definition.id).copied().unwrap_or_default();
+            let terrain = match definition.province_type {
+                ProvinceType::Sea => ProvinceTerrain {
+                    kind: TerrainKind::Ocean,
+                    climate: Climate::Temperate,
+                },
+                ProvinceType::Lake => ProvinceTerrain {
+                    kind: TerrainKind::Lakes,
+                    climate: Climate::Temperate,
+                },
+                ProvinceType::Land => {
+                    self.classify_land(&province_stats, &moisture, f64::from(height.max(1)))
+                }
+            };
+            definition.terrain = Terrain(terrain.kind.category().to_owned());
+            terrains.insert(definition.id, terrain);
+        }
+        generated.definitions.terrain = generated
+            .definitions
+            .definitions
+            .values()
+            .map(|d| d.terrain.clone())
+            .collect();
+
+        let indices = GrayImage::from_fn(width, height, |x, y| {
+            let index = ids
+                .get(generated.provinces.get_pixel(x, y))
+                .and_then(|id| terrains.get(id))
+                .map_or(FILLER_INDEX, |t| t.index());
+            Luma([index])
+        });
+        Ok(TerrainImage { indices })
+    }
+
+    /// Classifies a land province from its measurements
+    #[allow(clippy::integer_arithmetic)]
+    fn classify_land(
+        &self,
+        stats: &ProvinceStats,
+        moisture_noise: &Noise,
+        map_height: f64,
+    ) -> ProvinceTerrain {
+        let latitude = ((stats.y / map_height) - 0.5_f64).abs() * 2.0_f64;
+        let elevation =
+            ((stats.height - f64::from(SEA_LEVEL)) / f64::from(u8::MAX - SEA_LEVEL)).max(0.0);
+        // Higher land is colder, as if the equator were at the top of a mountain
+        let temperature = (1.0 - latitude - elevation * 0.5).clamp(0.0, 1.0);
+        let moisture = moisture_noise
+            .fbm(stats.x, stats.y, &self.moisture)
+            .mul_add(0.5, 0.5);
+        let climate = if temperature < self.cold_temperature {
+            Climate::Cold
+        } else if temperature > self.warm_temperature && moisture < self.desert_moisture {
+            Climate::Arid
+        } else {
+            Climate::Temperate
+        };
+
+        let kind = if stats.height >= f64::from(self.mountain_height)
+            || stats.slope >= self.mountain_slope
+        {
+            TerrainKind::Mountain
+        } else if stats.height >= f64::from(self.hills_height) || stats.slope >= self.hills_slope {
+            TerrainKind::Hills
+        } else if climate == Climate::Cold {
+            TerrainKind::Plains
+        } else if climate == Climate::Arid {
+            TerrainKind::Desert
+        } else if moisture > self.marsh_moisture && elevation < 0.1_f64 {
+            TerrainKind::Marsh
+        } else if moisture > self.forest_moisture && temperature > self.warm_temperature {
+            TerrainKind::Jungle
+        } else if moisture > self.forest_moisture {
+            TerrainKind::Forest
+        } else {
+            TerrainKind::Plains
+        };
+        ProvinceTerrain { kind, climate }
+    }
+}
+
+/// Repaints terrain.bmp within each province to match the terrain of its definition, returning
+/// the number of pixels changed.
+///
+/// Pixels already drawn with a graphical terrain of the category of their province are kept, so
+/// that the climate variants of plains, hills and mountains survive.  Other pixels are drawn with
+/// the temperate graphical terrain of the category.  Provinces with a category that has no
+/// graphical terrain, such as `urban`, are left as they are.  With `dither` set, every other pixel
+/// along the edge of a province takes the terrain of its neighbor, softening the border.
+/// # Errors
+/// * If the terrain image is not the same size as the provinces image
+#[inline]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+pub fn sync_terrain(
+    terrain: &mut RgbImage,
+    provinces: &RgbImage,
+    definitions: &Definitions,
+    dither: bool,
+) -> Result<usize, MapError> {
+    let (width, height) = provinces.dimensions();
+    if terrain.dimensions() != (width, height) {
+        return Err(MapError::ImageSizeMismatch(
+            "terrain map does not match provinces map".to_owned(),
+        ));
+    }
+    let kinds = definitions
+        .definitions
+        .values()
+        .filter(|d| d.id.0 != 0_i32)
+        .filter_map(|d| {
+            let kind = TerrainKind::from_category(&d.terrain.0)?;
+            Some((Rgb([d.r.0, d.g.0, d.b.0]), kind))
+        })
+        .collect::<HashMap<_, _>>();
+    let palette = TERRAIN_PALETTE
+        .iter()
+        .enumerate()
+        .map(|(index, color)| (Rgb(*color), index as u8))
+        .collect::<HashMap<_, _>>();
+
+    let mut changed = 0;
+    for y in 0..height {
+        for x in 0..width {
+            let province = provinces.get_pixel(x, y);
+            let kind = match kinds.get(province) {
+                Some(k) => *k,
+                None => continue,
+            };
+            let neighbor = dither && (x + y) % 2 == 1;
+            let edge = [
+                (x.wrapping_sub(1), y),
+                (x, y.wrapping_sub(1)),
+                (x + 1, y),
+                (x, y + 1),
+            ]
+            .into_iter()
+            .filter(|_| neighbor)
+            .filter_map(|(nx, ny)| provinces.get_pixel_checked(nx, ny))
+            .filter(|p| *p != province)
+            .find_map(|p| kinds.get(p))
+            .copied();
+            let indices = edge.unwrap_or(kind).graphical_indices();
+            let current = palette.get(terrain.get_pixel(x, y)).copied();
+            let index = match (current, indices.first()) {
+                (Some(c), _) if indices.contains(&c) => continue,
+                (_, Some(i)) => *i,
+                (_, None) => continue,
+            };
+            let color = TERRAIN_PALETTE
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default();
+            terrain.put_pixel(x, y, Rgb(color));
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Draws each province in the color terrain.bmp draws the terrain of its definition with in a
+/// temperate climate, so that the terrain the game uses can be compared with terrain.bmp.
+/// Provinces whose terrain is not a known category are drawn in `UNKNOWN_TERRAIN_COLOR`, and
+/// pixels without a province are black.
+#[inline]
+#[must_use]
+pub fn definition_terrain_map(
+    province_index: &ProvinceIndex,
+    definitions: &Definitions,
+) -> RgbImage {
+    let colors = definitions
+        .definitions
+        .values()
+        .map(|d| {
+            let color = TerrainKind::from_category(&d.terrain.0)
+                .map_or(UNKNOWN_TERRAIN_COLOR, TerrainKind::color);
+            (d.id, color)
+        })
+        .collect::<HashMap<_, _>>();
+    province_index.paint(|id| colors.get(&id).copied())
+}
+
+/// Measures the average height, slope and position of each province
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_wrap)]
+fn province_stats(
+    heightmap: &RgbImage,
+    provinces: &RgbImage,
+    ids: &HashMap<Rgb<u8>, ProvinceId>,
+) -> HashMap<ProvinceId, ProvinceStats> {
+    let (width, height) = heightmap.dimensions();
+    let height_at = |x: u32, y: u32| f64::from(heightmap.get_pixel(x, y).0[0]);
+    let mut stats: HashMap<ProvinceId, ProvinceStats> = HashMap::new();
+    for (x, y, color) in provinces.enumerate_pixels() {
+        let id = match ids.get(color) {
+            Some(id) => *id,
+            None => continue,
+        };
+        let dx = height_at((x + 1).min(width - 1), y) - height_at(x.saturating_sub(1), y);
+        let dy = height_at(x, (y + 1).min(height - 1)) - height_at(x, y.saturating_sub(1));
+        let entry = stats.entry(id).or_default();
+        entry.pixels += 1;
+        entry.height += height_at(x, y);
+        entry.slope += dx.hypot(dy) / 2.0_f64;
+        entry.x += f64::from(x);
+        entry.y += f64::from(y);
+    }
+    for entry in stats.values_mut() {
+        let pixels = f64::from(entry.pixels.max(1));
+        entry.height /= pixels;
+        entry.slope /= pixels;
+        entry.x /= pixels;
+        entry.y /= pixels;
+    }
+    stats
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use crate::generation::provinces::ProvinceGenerator;
+
+    /// A heightmap with a sea at the top, a mountain range in the middle and flat land below
+    fn heightmap() -> RgbImage {
+        RgbImage::from_fn(256, 256, |_x, y| {
+            if y < 64 {
+                Rgb([50, 50, 50])
+            } else if (128..160).contains(&y) {
+                Rgb([230, 230, 230])
+            } else {
+                Rgb([110, 110, 110])
+            }
+        })
+    }
+
+    #[test]
+    fn it_classifies_provinces_by_height() {
+        let heightmap = heightmap();
+        let mut generated = ProvinceGenerator::new(5, 40)
+            .generate(&heightmap)
+            .expect("Failed to generate provinces");
+        let terrain = TerrainClassifier::new(5)
+            .classify(&heightmap, &mut generated)
+            .expect("Failed to classify terrain");
+        let definitions = &generated.definitions.definitions;
+        assert!(definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Sea)
+            .all(|d| d.terrain.0 == "ocean"));
+        assert!(definitions.values().any(|d| d.terrain.0 == "mountain"));
+        assert!(definitions
+            .values()
+            .filter(|d| d.province_type == ProvinceType::Land && d.id.0 != 0)
+            .all(|d| d.terrain.0 != "ocean" && d.terrain.0 != "lakes"));
+        assert!(generated
+            .definitions
+            .terrain
+            .contains(&Terrain("mountain".to_owned())));
+        assert_eq!(terrain.indices.get_pixel(0, 0).0[0], OCEAN_INDEX);
+        assert_eq!(terrain.to_rgb().get_pixel(0, 0), &Rgb([8, 31, 130]));
+    }
+
+    #[test]
+    fn it_rejects_mismatched_images() {
+        let mut generated = ProvinceGenerator::new(5, 10)
+            .generate(&heightmap())
+            .expect("Failed to generate provinces");
+        let result = TerrainClassifier::new(5).classify(&RgbImage::new(512, 256), &mut generated);
+        assert!(matches!(result, Err(MapError::ImageSizeMismatch(_))));
+    }
+
+    #[test]
+    fn it_saves_an_indexed_terrain_image() {
+        let heightmap = heightmap();
+        let mut generated = ProvinceGenerator::new(2, 20)
+            .generate(&heightmap)
+            .expect("Failed to generate provinces");
+        let terrain = TerrainClassifier::new(2)
+            .classify(&heightmap, &mut generated)
+            .expect("Failed to classify terrain");
+        let path = std::env::temp_dir().join("world_gen_terrain_test.bmp");
+        terrain.save(&path).expect("Failed to save terrain");
+        let loaded = image::open(&path).expect("Failed to open terrain");
+        std::fs::remove_file(&path).expect("Failed to remove terrain");
+        assert_eq!(loaded.to_rgb8(), terrain.to_rgb());
+    }
+
+    /// Two provinces side by side, plains on the left and forest on the right
+    fn synced_map() -> (RgbImage, Definitions) {
+        let provinces = RgbImage::from_fn(8, 4, |x, _y| {
+            if x < 4 {
+                Rgb([1, 0, 0])
+            } else {
+                Rgb([2, 0, 0])
+            }
+        });
+        let definition = |id: i32, terrain: &str| {
+            DefinitionBuilder::new(id)
+                .color(Rgb([id as u8, 0, 0]))
+                .terrain(terrain)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, "plains")),
+                (ProvinceId(2), definition(2, "forest")),
+            ]),
+            terrain: std::collections::HashSet::new(),
+        };
+        (provinces, definitions)
+    }
+
+    #[test]
+    fn it_syncs_terrain_with_the_definitions() {
+        let (provinces, definitions) = synced_map();
+        let mut terrain = RgbImage::from_pixel(8, 4, Rgb(TERRAIN_PALETTE[13]));
+        // Cold plains are kept, as they are still plains
+        terrain.put_pixel(0, 0, Rgb(TERRAIN_PALETTE[2]));
+        let changed = sync_terrain(&mut terrain, &provinces, &definitions, false)
+            .expect("Failed to sync terrain");
+        assert_eq!(changed, 31);
+        assert_eq!(*terrain.get_pixel(0, 0), Rgb(TERRAIN_PALETTE[2]));
+        assert_eq!(*terrain.get_pixel(3, 1), Rgb(TERRAIN_PALETTE[3]));
+        assert_eq!(*terrain.get_pixel(4, 1), Rgb(TERRAIN_PALETTE[8]));
+        let changed = sync_terrain(&mut terrain, &provinces, &definitions, false)
+            .expect("Failed to sync terrain");
+        assert_eq!(changed, 0);
+    }
+
+    #[test]
+    fn it_dithers_the_edges_of_provinces() {
+        let (provinces, definitions) = synced_map();
+        let mut terrain = RgbImage::new(8, 4);
+        sync_terrain(&mut terrain, &provinces, &definitions, true).expect("Failed to sync terrain");
+        // Every other pixel along the border takes the terrain across it
+        assert_eq!(*terrain.get_pixel(3, 0), Rgb(TERRAIN_PALETTE[8]));
+        assert_eq!(*terrain.get_pixel(3, 1), Rgb(TERRAIN_PALETTE[3]));
+        assert_eq!(*terrain.get_pixel(4, 0), Rgb(TERRAIN_PALETTE[8]));
+        assert_eq!(*terrain.get_pixel(4, 1), Rgb(TERRAIN_PALETTE[3]));
+        assert_eq!(*terrain.get_pixel(1, 0), Rgb(TERRAIN_PALETTE[3]));
+
+        let result = sync_terrain(&mut RgbImage::new(4, 4), &provinces, &definitions, true);
+        assert!(matches!(result, Err(MapError::ImageSizeMismatch(_))));
+    }
+
+    #[test]
+    fn it_draws_the_terrain_of_the_definitions() {
+        let (provinces, mut definitions) = synced_map();
+        if let Some(forest) = definitions.definitions.get_mut(&ProvinceId(2)) {
+            forest.terrain = Terrain("swamp".to_owned());
+        }
+        let provinces_by_color = HashMap::from([
+            (Rgb([1, 0, 0]), ProvinceId(1)),
+            (Rgb([2, 0, 0]), ProvinceId(2)),
+        ]);
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let map = definition_terrain_map(&index, &definitions);
+        assert_eq!(map.dimensions(), (8, 4));
+        assert_eq!(*map.get_pixel(1, 2), TerrainKind::Plains.color());
+        assert_eq!(TerrainKind::Plains.color(), Rgb(TERRAIN_PALETTE[3]));
+        assert_eq!(*map.get_pixel(6, 0), UNKNOWN_TERRAIN_COLOR);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::components::prelude::*;
+use crate::generation::noise::{Fractal, Noise, NoiseKind};
+use crate::generation::save_indexed_bmp;
+use crate::MapError;
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+/// The start of the palette of trees.bmp in the base game.  The tree meshes come in groups of
+/// three indices, from sparse to dense.
+pub const TREE_PALETTE: [[u8; 3]; 31] = [
+    [0, 0, 0],
+    [255, 0, 0],
+    [30, 139, 109],
+    [18, 100, 78],
+    [8, 58, 44],
+    [76, 156, 51],
+    [47, 120, 24],
+    [20, 85, 0],
+    [154, 156, 51],
+    [118, 120, 24],
+    [83, 85, 0],
+    [255, 255, 0],
+    [213, 160, 0],
+    [0, 183, 0],
+    [0, 128, 0],
+    [0, 60, 0],
+    [16, 16, 16],
+    [17, 17, 17],
+    [11, 207, 172],
+    [8, 155, 129],
+    [4, 75, 62],
+    [21, 21, 21],
+    [22, 22, 22],
+    [23, 23, 23],
+    [24, 24, 24],
+    [25, 25, 25],
+    [26, 26, 26],
+    [169, 93, 255],
+    [150, 0, 255],
+    [88, 0, 138],
+    [252, 0, 255],
+];
+
+/// The trees.bmp index of a pixel without trees
+const NO_TREES_INDEX: u8 = 0;
+/// The size of trees.bmp relative to provinces.bmp, which is 1650x675 against 5632x2304 in the
+/// base game
+const TREES_SCALE: (u32, u32) = (75, 256);
+
+/// How many trees grow on a pixel of trees.bmp
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Density {
+    None,
+    Sparse,
+    Medium,
+    Dense,
+}
+
+/// The kind of tree mesh drawn on a pixel of trees.bmp
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TreeKind {
+    Pine,
+    Deciduous,
+    Wetland,
+    Tropical,
+}
+
+impl TreeKind {
+    /// The index of the sparsest tree mesh of the kind, with denser meshes following it
+    const fn first_index(self) -> u8 {
+        match self {
+            Self::Pine => 2,
+            Self::Deciduous => 5,
+            Self::Wetland => 8,
+            Self::Tropical => 27,
+        }
+    }
+
+    /// The trees.bmp index of the kind of tree at the given density
+    #[allow(clippy::integer_arithmetic)]
+    const fn index(self, density: Density) -> u8 {
+        match density {
+            Density::None => NO_TREES_INDEX,
+            Density::Sparse => self.first_index(),
+            Density::Medium => self.first_index() + 1,
+            Density::Dense => self.first_index() + 2,
+        }
+    }
+}
+
+/// An indexed trees image using the `TREE_PALETTE`, along with the indices that count as trees.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TreeImage {
+    /// The palette index of each pixel
+    pub indices: GrayImage,
+    /// The indices of the palette dense enough to count as trees when the game assigns terrain,
+    /// for the `tree` entry of default.map
+    pub trees: Vec<usize>,
+}
+
+impl TreeImage {
+    /// Converts the indexes to the colors of the palette
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    pub fn to_rgb(&self) -> RgbImage {
+        RgbImage::from_fn(self.indices.width(), self.indices.height(), |x, y| {
+            let index = self.indices.get_pixel(x, y).0[0];
+            Rgb(TREE_PALETTE
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default())
+        })
+    }
+
+    /// The `tree` entry of default.map, such as `tree = { 3 4 7 10 }`
+    #[inline]
+    #[must_use]
+    pub fn default_map_entry(&self) -> String {
+        let indices = self
+            .trees
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        format!("tree = {{ {} }}", indices.join(" "))
+    }
+
+    /// Saves the image as trees.bmp, with the kind of tree of each pixel as its palette index
+    /// # Errors
+    /// * If the file could not be written
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        save_indexed_bmp(path, &self.indices, &TREE_PALETTE)
+    }
+}
+
+/// Draws trees.bmp from the terrain of the provinces and the moisture of the map.
+///
+/// The image is smaller than the provinces image by the same ratio as in the base game.  Forests
+/// and jungles are always wooded, while plains, hills and marshes only grow trees where the
+/// moisture is high enough.  Pines grow towards the poles, palms in jungles and wetland trees in
+/// marshes.  The moisture noise matches that of a `TerrainClassifier` with the same seed and
+/// layering, so that the trees follow the forests it assigns.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TreeGenerator {
+    /// The seed for the moisture noise
+    pub seed: u64,
+    /// The layering of the moisture noise
+    pub moisture: Fractal,
+    /// The distance from the equator beyond which trees are pines, from 0.0 at the equator to
+    /// 1.0 at the poles
+    pub pine_latitude: f64,
+    /// The moisture above which open land grows sparse trees, from 0.0 to 1.0
+    pub sparse_moisture: f64,
+    /// The moisture above which trees grow a step denser, from 0.0 to 1.0
+    pub dense_moisture: f64,
+}
+
+impl Default for TreeGenerator {
+    #[inline]
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl TreeGenerator {
+    /// Creates a new tree generator with the default settings
+    #[inline]
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            moisture: Fractal::new(4, 1.0 / 1024.0, 2.0, 0.5),
+            pine_latitude: 0.6,
+            sparse_moisture: 0.5,
+            dense_moisture: 0.65,
+        }
+    }
+
+    /// Draws the trees for the provinces from the terrain in their definitions
+    /// # Errors
+    /// * If the provinces image is too small to scale down to a trees image
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn generate(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+    ) -> Result<TreeImage, MapError> {
+        let (width, height) = provinces.dimensions();
+        let (numerator, denominator) = TREES_SCALE;
+        let trees_width = width * numerator / denominator;
+        let trees_height = height * numerator / denominator;
+        if trees_width == 0 || trees_height == 0 {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "A {width}x{height} map is too small for trees"
+            )));
+        }
+        let terrains = definitions
+            .definitions
+            .values()
+            .filter(|d| d.id.0 != 0_i32)
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.terrain.0.as_str()))
+            .collect::<HashMap<_, _>>();
+        let noise = Noise::new(NoiseKind::default(), self.seed);
+
+        let mut trees = BTreeSet::new();
+        let indices = GrayImage::from_fn(trees_width, trees_height, |x, y| {
+            // Sample the middle of the block of provinces pixels under the trees pixel
+            let px = ((2 * x + 1) * denominator / (2 * numerator)).min(width - 1);
+            let py = ((2 * y + 1) * denominator / (2 * numerator)).min(height - 1);
+            let terrain = match terrains.get(provinces.get_pixel(px, py)) {
+                Some(terrain) => *terrain,
+                None => return Luma([NO_TREES_INDEX]),
+            };
+            let (x, y) = (f64::from(px), f64::from(py));
+            let moisture = noise.fbm(x, y, &self.moisture).mul_add(0.5, 0.5);
+            let latitude = ((y / f64::from(height)) - 0.5_f64).abs() * 2.0_f64;
+            let (kind, density) = self.trees(terrain, moisture, latitude);
+            if density >= Density::Medium {
+                trees.insert(usize::from(kind.index(density)));
+            }
+            Luma([kind.index(density)])
+        });
+        Ok(TreeImage {
+            indices,
+            trees: trees.into_iter().collect(),
+        })
+    }
+
+    /// Picks the kind and density of trees for a pixel with the given terrain
+    fn trees(&self, terrain: &str, moisture: f64, latitude: f64) -> (TreeKind, Density) {
+        let wet = moisture > self.dense_moisture;
+        let damp = moisture > self.sparse_moisture;
+        let density = match terrain {
+            "forest" | "jungle" if wet => Density::Dense,
+            "forest" | "jungle" => Density::Medium,
+            "plains" | "hills" | "marsh" if wet => Density::Medium,
+            "plains" | "hills" | "marsh" if damp => Density::Sparse,
+            "mountain" if wet => Density::Sparse,
+            _ => Density::None,
+        };
+        let kind = if terrain == "jungle" {
+            TreeKind::Tropical
+        } else if latitude > self.pine_latitude || terrain == "mountain" {
+            TreeKind::Pine
+        } else if terrain == "marsh" {
+            TreeKind::Wetland
+        } else {
+            TreeKind::Deciduous
+        };
+        (kind, density)
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashSet;
+
+    /// A map split into a forest on the left and a desert on the right
+    fn map() -> (RgbImage, Definitions) {
+        let provinces = RgbImage::from_fn(512, 256, |x, _y| {
+            if x < 256 {
+                Rgb([1, 0, 0])
+            } else {
+                Rgb([2, 0, 0])
+            }
+        });
+        let definition = |id: i32, terrain: &str| {
+            DefinitionBuilder::new(id)
+                .color(Rgb([id as u8, 0, 0]))
+                .terrain(terrain)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, "forest")),
+                (ProvinceId(2), definition(2, "desert")),
+            ]),
+            terrain: HashSet::new(),
+        };
+        (provinces, definitions)
+    }
+
+    #[test]
+    fn it_draws_trees_on_forests() {
+        let (provinces, definitions) = map();
+        let trees = TreeGenerator::new(3)
+            .generate(&provinces, &definitions)
+            .expect("Failed to generate trees");
+        assert_eq!(trees.indices.dimensions(), (150, 75));
+        let forest = trees.indices.get_pixel(10, 37).0[0];
+        assert_ne!(forest, NO_TREES_INDEX);
+        assert_eq!(trees.indices.get_pixel(140, 37).0[0], NO_TREES_INDEX);
+        assert!(trees.trees.contains(&usize::from(forest)));
+        assert!(trees.default_map_entry().starts_with("tree = { "));
+    }
+
+    #[test]
+    fn it_rejects_maps_too_small_for_trees() {
+        let (_provinces, definitions) = map();
+        let result = TreeGenerator::new(3).generate(&RgbImage::new(2, 2), &definitions);
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+
+    #[test]
+    fn it_saves_an_indexed_trees_image() {
+        let (provinces, definitions) = map();
+        let trees = TreeGenerator::new(3)
+            .generate(&provinces, &definitions)
+            .expect("Failed to generate trees");
+        let path = std::env::temp_dir().join("world_gen_trees_test.bmp");
+        trees.save(&path).expect("Failed to save trees");
+        let loaded = image::open(&path).expect("Failed to open trees");
+        std::fs::remove_file(&path).expect("Failed to remove trees");
+        assert_eq!(loaded.to_rgb8(), trees.to_rgb());
+    }
+}
@@ -0,0 +1,648 @@
+use crate::components::airport::Airports;
+use crate::components::continent::Continents;
+use crate::components::localisation::Localisation;
+use crate::components::prelude::*;
+use crate::components::rocket_site::RocketSites;
+use crate::components::state::States;
+use crate::components::weather_position::WeatherPositions;
+use crate::generation::cities::{CityGenerator, GeneratedCities};
+use crate::generation::constraints::{ConstraintReport, ConstraintSolver};
+use crate::generation::continents::ContinentGenerator;
+use crate::generation::heightmap::HeightmapGenerator;
+use crate::generation::naval_terrain::NavalTerrainClassifier;
+use crate::generation::provinces::{GeneratedProvinces, ProvinceGenerator};
+use crate::generation::rivers::RiverGenerator;
+use crate::generation::states::StateGenerator;
+use crate::generation::strategic_regions::StrategicRegionGenerator;
+use crate::generation::supply::{GeneratedSupply, SupplyGenerator};
+use crate::generation::terrain::{TerrainClassifier, TerrainImage};
+use crate::generation::trees::{TreeGenerator, TreeImage};
+use crate::topology::Wrapping;
+use crate::{ErrorContext, MapError};
+use image::{Rgb, RgbImage};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How steep the slopes of the heightmap are drawn in world_normal.bmp
+const NORMAL_STRENGTH: f64 = 0.05;
+
+/// The files of a generated world that come from the base game rather than the generators, along
+/// with the smallest contents the map loads with when no base game is given.
+const BASE_GAME_FILES: [(&str, &str); 5] = [
+    ("common/terrain/00_terrain.txt", TERRAIN_CATEGORIES),
+    ("common/buildings/00_buildings.txt", BUILDING_TYPES),
+    ("map/adjacency_rules.txt", ""),
+    ("map/colors.txt", ""),
+    ("map/seasons.txt", SEASONS),
+];
+
+/// The terrain categories the generators assign
+const TERRAIN_CATEGORIES: &str = "categories = {
+\tunknown = { }
+\tocean = { is_water = yes }
+\tlakes = { is_water = yes }
+\tforest = { }
+\thills = { }
+\tmountain = { }
+\tplains = { }
+\turban = { }
+\tjungle = { }
+\tmarsh = { }
+\tdesert = { }
+\twater_shallow_sea = { is_water = yes }
+\twater_deep_ocean = { is_water = yes }
+}
+";
+
+/// The building types of the base game
+const BUILDING_TYPES: &str = "buildings = {
+\tinfrastructure = { }
+\tarms_factory = { }
+\tindustrial_complex = { }
+\tair_base = { }
+\tsupply_node = { }
+\trail_way = { }
+\tnaval_base = { }
+\tbunker = { }
+\tcoastal_bunker = { }
+\tdockyard = { }
+\tanti_air_building = { }
+\tsynthetic_refinery = { }
+\tfuel_silo = { }
+\tradar_station = { }
+\trocket_site = { }
+\tnuclear_reactor = { }
+}
+";
+
+/// The seasons of the base game
+const SEASONS: &str = "winter = {
+\tstart_date = 00.12.01
+\tend_date = 00.02.28
+\thsv_north = { 0.0 0.4 0.7 }
+\tcolorbalance_north = { 0.8 0.8 1.1 }
+\thsv_center = { 0.0 0.85 1.0 }
+\tcolorbalance_center = { 1.1 1.0 1.0 }
+\thsv_south = { 0.0 0.85 1.0 }
+\tcolorbalance_south = { 1.1 1.0 1.0 }
+}
+spring = {
+\tstart_date = 00.04.01
+\tend_date = 00.05.1
+\thsv_north = { 0.0 0.85 0.75 }
+\tcolorbalance_north = { 1.1 1.0 1.2 }
+\thsv_center = { 0.0 0.85 1.0 }
+\tcolorbalance_center = { 1.1 1.1 1.0 }
+\thsv_south = { 0.0 0.85 1.0 }
+\tcolorbalance_south = { 1.1 1.0 1.0 }
+}
+summer = {
+\tstart_date = 00.06.01
+\tend_date = 00.09.10
+\thsv_north = { 0.0 0.9 0.9 }
+\tcolorbalance_north = { 1.3 1.15 0.9 }
+\thsv_center = { 0.0 0.85 1.0 }
+\tcolorbalance_center = { 1.0 1.0 1.0 }
+\thsv_south = { 0.0 0.85 1.0 }
+\tcolorbalance_south = { 1.0 1.0 1.0 }
+}
+autumn = {
+\tstart_date = 00.10.10
+\tend_date = 00.10.31
+\thsv_north = { 0.0 0.75 0.8 }
+\tcolorbalance_north = { 1.3 1.1 0.8 }
+\thsv_center = { 0.0 0.85 1.0 }
+\tcolorbalance_center = { 1.1 1.0 1.0 }
+\thsv_south = { 0.0 0.85 1.0 }
+\tcolorbalance_south = { 1.1 1.0 1.0 }
+}
+tree_winter = { start_date = 00.11.15 end_date = 00.12.01 }
+tree_winter2 = { start_date = 00.12.20 end_date = 00.01.20 }
+tree_spring = { start_date = 00.02.20 end_date = 00.03.01 }
+tree_spring2 = { start_date = 00.03.20 end_date = 00.04.20 }
+tree_summer = { start_date = 00.05.20 end_date = 00.06.01 }
+tree_summer2 = { start_date = 00.06.20 end_date = 00.09.10 }
+tree_autumn = { start_date = 00.10.01 end_date = 00.10.10 }
+tree_autumn2 = { start_date = 00.10.25 end_date = 00.11.01 }
+";
+
+/// The climate of a generated world, from the poles to the equator.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ClimateBands {
+    /// The temperature below which land is cold, from 0.0 at the poles to 1.0 at the equator
+    pub cold_temperature: f64,
+    /// The temperature above which land is warm enough for deserts and jungles
+    pub warm_temperature: f64,
+    /// The latitude in degrees at the top of the map, which sets the weather of the regions
+    pub north_latitude: f64,
+    /// The latitude in degrees at the bottom of the map
+    pub south_latitude: f64,
+}
+
+impl Default for ClimateBands {
+    #[inline]
+    fn default() -> Self {
+        let terrain = TerrainClassifier::default();
+        let regions = StrategicRegionGenerator::default();
+        Self {
+            cold_temperature: terrain.cold_temperature,
+            warm_temperature: terrain.warm_temperature,
+            north_latitude: regions.north_latitude,
+            south_latitude: regions.south_latitude,
+        }
+    }
+}
+
+/// The settings of a generated world, usually read from a TOML file such as
+///
+/// ```toml
+/// seed = 42
+/// width = 2048
+/// height = 1024
+/// land_fraction = 0.35
+/// province_count = 3000
+///
+/// [climate]
+/// cold_temperature = 0.25
+/// ```
+///
+/// Any setting left out takes its default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct WorldConfig {
+    /// The seed for every generator
+    pub seed: u64,
+    /// The width of the map in pixels, a multiple of 256
+    pub width: u32,
+    /// The height of the map in pixels, a multiple of 256
+    pub height: u32,
+    /// The fraction of the map above sea level, from 0.0 to 1.0
+    pub land_fraction: f64,
+    /// The number of land and sea provinces to aim for
+    pub province_count: u32,
+    /// The number of provinces to aim for in each state
+    pub provinces_per_state: u32,
+    /// The number of states to aim for in each land strategic region
+    pub states_per_region: u32,
+    /// Whether the left and right edges of the map are connected
+    pub wrap_horizontally: bool,
+    /// The Hearts of Iron IV directory to copy the terrain, buildings, adjacency rules, colors
+    /// and seasons from.  Without it, the smallest files the map loads with are written instead.
+    pub base_game: Option<PathBuf>,
+    /// The climate from the poles to the equator, last as TOML writes tables after values
+    pub climate: ClimateBands,
+}
+
+impl Default for WorldConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            width: 2048,
+            height: 1024,
+            land_fraction: 0.4,
+            province_count: 3000,
+            provinces_per_state: StateGenerator::default().provinces_per_state,
+            states_per_region: StrategicRegionGenerator::default().states_per_region,
+            wrap_horizontally: false,
+            base_game: None,
+            climate: ClimateBands::default(),
+        }
+    }
+}
+
+impl WorldConfig {
+    /// Reads the config from a TOML file
+    /// # Errors
+    /// * If the file cannot be read or is not a valid config
+    #[inline]
+    pub fn from_file(path: &Path) -> Result<Self, MapError> {
+        let data = fs::read_to_string(path).in_file(path)?;
+        toml::from_str(&data).in_file(path)
+    }
+
+    /// Writes the config to a TOML file
+    /// # Errors
+    /// * If the file cannot be written
+    #[inline]
+    pub fn to_file(&self, path: &Path) -> Result<(), MapError> {
+        fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// How the edges of the generated map connect to each other
+    const fn wrapping(&self) -> Wrapping {
+        if self.wrap_horizontally {
+            Wrapping::Horizontal
+        } else {
+            Wrapping::None
+        }
+    }
+}
+
+/// Every file of a generated world, before it is written to a mod directory.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct GeneratedWorld {
+    /// The heightmap
+    pub heightmap: RgbImage,
+    /// The provinces image
+    pub provinces: RgbImage,
+    /// The definitions of the provinces
+    pub definitions: Definitions,
+    /// The graphical terrain
+    pub terrain: TerrainImage,
+    /// The rivers image
+    pub rivers: RgbImage,
+    /// The trees image
+    pub trees: TreeImage,
+    /// The normal map, at half the size of the heightmap
+    pub normal_map: RgbImage,
+    /// The cities image and cities.txt
+    pub cities: GeneratedCities,
+    /// The continents
+    pub continents: Continents,
+    /// The states
+    pub states: States,
+    /// The strategic regions
+    pub strategic_regions: StrategicRegions,
+    /// The supply nodes and railways
+    pub supply: GeneratedSupply,
+    /// A weather position in each strategic region
+    pub weather_positions: WeatherPositions,
+    /// An airport in each state
+    pub airports: Airports,
+    /// A rocket site in each state
+    pub rocket_sites: RocketSites,
+    /// The conditions that crash the game fixed after the provinces were generated, and those
+    /// left on the map
+    pub constraints: ConstraintReport,
+}
+
+impl GeneratedWorld {
+    /// Writes the world to the given root directory, laid out as a Hearts of Iron IV mod that
+    /// the map loads from.  The files that do not come from the generators are copied from the
+    /// base game directory if one is given.
+    /// # Errors
+    /// * If any of the files could not be written, or copied from the base game
+    #[inline]
+    pub fn save(&self, root_path: &Path, base_game: Option<&Path>) -> Result<(), MapError> {
+        let map = root_path.join("map");
+        let strategic_regions = map.join("strategicregions");
+        let states = root_path.join("history/states");
+        fs::create_dir_all(&strategic_regions)?;
+        fs::create_dir_all(&states)?;
+        for (file, fallback) in BASE_GAME_FILES {
+            let path = root_path.join(file);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            match base_game {
+                Some(base) => {
+                    fs::copy(base.join(file), &path)?;
+                }
+                None => fs::write(&path, fallback)?,
+            }
+        }
+
+        fs::write(map.join("default.map"), self.default_map())?;
+        self.provinces.save(map.join("provinces.bmp"))?;
+        self.heightmap.save(map.join("heightmap.bmp"))?;
+        self.terrain.save(&map.join("terrain.bmp"))?;
+        self.rivers.save(map.join("rivers.bmp"))?;
+        self.trees.save(&map.join("trees.bmp"))?;
+        self.normal_map.save(map.join("world_normal.bmp"))?;
+        self.cities.save(&map.join("cities.bmp"))?;
+        self.cities.cities.save(&map.join("cities.txt"))?;
+        self.definitions.to_file(&map.join("definition.csv"))?;
+        self.continents.to_file(&map.join("continent.txt"))?;
+        // The world starts without any adjacencies, only the row ending the file
+        let adjacencies = Adjacencies {
+            adjacencies: vec![Adjacency::terminator()],
+            lines: Vec::new(),
+        };
+        adjacencies.to_file(&map.join("adjacencies.csv"))?;
+        self.supply
+            .supply_nodes
+            .to_file(&map.join("supply_nodes.txt"))?;
+        self.supply.railways.to_file(&map.join("railways.txt"))?;
+        self.weather_positions
+            .to_file(map.join("weatherpositions.txt"))?;
+        self.airports.to_file(&map.join("airports.txt"))?;
+        self.rocket_sites.to_file(&map.join("rocketsites.txt"))?;
+        fs::write(map.join("buildings.txt"), "")?;
+        fs::write(map.join("unitstacks.txt"), "")?;
+        fs::write(map.join("positions.txt"), "")?;
+        fs::write(map.join("ambient_object.txt"), "")?;
+        self.strategic_regions.to_dir(&strategic_regions)?;
+        self.states.to_dir(&states)?;
+        // The states are named after their ids until they are renamed in the editor
+        let mut localisation = Localisation::default();
+        for state in self.states.states.values() {
+            localisation.set_text(state.name.0.clone(), format!("State {}", state.id.0));
+        }
+        localisation.save(root_path)?;
+        Ok(())
+    }
+
+    /// The contents of default.map, pointing at the generated files
+    fn default_map(&self) -> String {
+        format!(
+            "definitions = \"definition.csv\"
+provinces = \"provinces.bmp\"
+positions = \"positions.txt\"
+terrain = \"terrain.bmp\"
+rivers = \"rivers.bmp\"
+heightmap = \"heightmap.bmp\"
+tree_definition = \"trees.bmp\"
+continent = \"continent.txt\"
+adjacency_rules = \"adjacency_rules.txt\"
+adjacencies = \"adjacencies.csv\"
+ambient_object = \"ambient_object.txt\"
+seasons = \"seasons.txt\"
+
+{}
+",
+            self.trees.default_map_entry()
+        )
+    }
+}
+
+/// Generates a whole world from a `WorldConfig` by running every generator in turn.
+///
+/// The heightmap is partitioned into provinces, which are given terrain, naval terrain and
+/// continents.  The conditions that crash the game are fixed with a `ConstraintSolver` before the
+/// provinces are grouped into states and strategic regions.  The states are connected by
+/// supply nodes and railways, each given an airport and rocket site at its supply node, and
+/// drawn with cities.  The rivers, trees and normal map are drawn from the heightmap and the
+/// terrain.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct WorldGenerator {
+    /// The settings of the world
+    pub config: WorldConfig,
+}
+
+impl WorldGenerator {
+    /// Creates a new world generator
+    #[inline]
+    #[must_use]
+    pub const fn new(config: WorldConfig) -> Self {
+        Self { config }
+    }
+
+    /// Generates the world and writes it to the given root directory as a mod the map loads
+    /// from
+    /// # Errors
+    /// * If any of the generators fail with the settings of the config
+    /// * If any of the files could not be written
+    #[inline]
+    pub fn generate(&self, root_path: &Path) -> Result<GeneratedWorld, MapError> {
+        let world = self.build()?;
+        world.save(root_path, self.config.base_game.as_deref())?;
+        Ok(world)
+    }
+
+    /// Generates the world without writing it
+    /// # Errors
+    /// * If the land fraction is not between 0 and 1
+    /// * If any of the generators fail with the settings of the config
+    #[inline]
+    pub fn build(&self) -> Result<GeneratedWorld, MapError> {
+        let config = &self.config;
+        if !(0.0_f64..=1.0_f64).contains(&config.land_fraction) {
+            return Err(MapError::InvalidGeneratorSettings(format!(
+                "The land fraction must be between 0 and 1, not {}",
+                config.land_fraction
+            )));
+        }
+        let wrapping = config.wrapping();
+
+        let mut heightmap_generator = HeightmapGenerator::new(config.seed);
+        heightmap_generator.ocean_level = 1.0_f64 - config.land_fraction;
+        let heightmap = heightmap_generator.generate(config.width, config.height)?;
+
+        let mut province_generator = ProvinceGenerator::new(config.seed, config.province_count);
+        province_generator.wrapping = wrapping;
+        let mut generated = province_generator.generate(&heightmap)?;
+
+        let mut terrain_classifier = TerrainClassifier::new(config.seed);
+        terrain_classifier.cold_temperature = config.climate.cold_temperature;
+        terrain_classifier.warm_temperature = config.climate.warm_temperature;
+        let terrain = terrain_classifier.classify(&heightmap, &mut generated)?;
+        let GeneratedProvinces {
+            mut provinces,
+            mut definitions,
+        } = generated;
+
+        let mut naval_terrain_classifier = NavalTerrainClassifier::default();
+        naval_terrain_classifier.wrapping = wrapping;
+        naval_terrain_classifier.classify(&heightmap, &provinces, &mut definitions)?;
+
+        let mut continent_generator = ContinentGenerator::default();
+        continent_generator.wrapping = wrapping;
+        let continents = continent_generator.generate(&provinces, &mut definitions);
+
+        let mut constraint_solver = ConstraintSolver::default();
+        constraint_solver.wrapping = wrapping;
+        let constraints = constraint_solver.solve(&mut provinces, &mut definitions, &continents);
+        for unsolved in &constraints.unsolved {
+            warn!("Generated map has a problem that could not be fixed: {unsolved:?}");
+        }
+
+        let mut state_generator = StateGenerator::new(config.provinces_per_state);
+        state_generator.wrapping = wrapping;
+        let states = state_generator.generate(&provinces, &definitions)?;
+
+        let mut region_generator = StrategicRegionGenerator::default();
+        region_generator.states_per_region = config.states_per_region;
+        region_generator.north_latitude = config.climate.north_latitude;
+        region_generator.south_latitude = config.climate.south_latitude;
+        region_generator.wrapping = wrapping;
+        let strategic_regions = region_generator.generate(&provinces, &definitions, &states)?;
+
+        let mut supply_generator = SupplyGenerator::default();
+        supply_generator.wrapping = wrapping;
+        let supply = supply_generator.generate(&provinces, &definitions, &states)?;
+        let weather_positions =
+            WeatherPositions::generate(&strategic_regions, &provinces, &heightmap, &definitions)?;
+        let sites = supply_node_sites(&states, &supply);
+
+        let mut river_generator = RiverGenerator::default();
+        river_generator.wrapping = wrapping;
+        let rivers = river_generator.generate(&heightmap)?;
+        let cities = CityGenerator::default().generate(&provinces, &definitions, &states);
+        let trees = TreeGenerator::new(config.seed).generate(&provinces, &definitions)?;
+        let normal_map = normal_map(&heightmap);
+
+        Ok(GeneratedWorld {
+            heightmap,
+            provinces,
+            definitions,
+            terrain,
+            rivers,
+            trees,
+            normal_map,
+            cities,
+            continents,
+            states,
+            strategic_regions,
+            supply,
+            weather_positions,
+            airports: Airports {
+                airports: sites.clone(),
+            },
+            rocket_sites: RocketSites {
+                rocket_sites: sites,
+            },
+            constraints,
+        })
+    }
+}
+
+/// The supply node of each state, where its airport and rocket site are placed
+fn supply_node_sites(
+    states: &States,
+    supply: &GeneratedSupply,
+) -> HashMap<StateId, Vec<ProvinceId>> {
+    states
+        .states
+        .values()
+        .filter_map(|state| {
+            let node = state
+                .provinces
+                .iter()
+                .find(|p| supply.supply_nodes.nodes.contains(p))?;
+            Some((state.id, vec![*node]))
+        })
+        .collect()
+}
+
+/// Draws world_normal.bmp at half the size of the heightmap from its slopes, with flat ground
+/// facing straight up
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn normal_map(heightmap: &RgbImage) -> RgbImage {
+    let (width, height) = heightmap.dimensions();
+    let height_at = |x: u32, y: u32| {
+        f64::from(
+            heightmap
+                .get_pixel(
+                    x.min(width.saturating_sub(1)),
+                    y.min(height.saturating_sub(1)),
+                )
+                .0[0],
+        )
+    };
+    RgbImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let (hx, hy) = (x * 2, y * 2);
+        let dx = height_at(hx + 2, hy) - height_at(hx.saturating_sub(2), hy);
+        let dy = height_at(hx, hy + 2) - height_at(hx, hy.saturating_sub(2));
+        let (nx, ny, nz) = (-dx * NORMAL_STRENGTH, -dy * NORMAL_STRENGTH, 1.0_f64);
+        let length = nx.hypot(ny).hypot(nz);
+        let channel = |v: f64| (v / length).mul_add(127.5, 127.5).round().clamp(0.0, 255.0) as u8;
+        Rgb([channel(nx), channel(ny), channel(nz)])
+    })
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::unwrap_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Map, MapLoadOptions};
+    use indicatif::InMemoryTerm;
+
+    fn small_config() -> WorldConfig {
+        WorldConfig {
+            seed: 7,
+            width: 256,
+            height: 256,
+            land_fraction: 0.5,
+            province_count: 80,
+            provinces_per_state: 4,
+            states_per_region: 3,
+            ..WorldConfig::default()
+        }
+    }
+
+    #[test]
+    fn it_reads_a_partial_config_with_defaults() {
+        let text = "seed = 42\nprovince_count = 500\n\n[climate]\ncold_temperature = 0.3\n";
+        let config: WorldConfig = toml::from_str(text).expect("Failed to read config");
+        assert_eq!(config.seed, 42);
+        assert_eq!(config.province_count, 500);
+        assert_eq!(config.width, WorldConfig::default().width);
+        assert!((config.climate.cold_temperature - 0.3).abs() < f64::EPSILON);
+        assert!(
+            (config.climate.warm_temperature - ClimateBands::default().warm_temperature).abs()
+                < f64::EPSILON
+        );
+    }
+
+    #[test]
+    fn it_writes_a_config_that_can_be_read_back() {
+        let config = small_config();
+        let path = std::env::temp_dir().join("world_gen_world_config_test.toml");
+        config.to_file(&path).expect("Failed to write config");
+        let read = WorldConfig::from_file(&path).expect("Failed to read config");
+        fs::remove_file(&path).expect("Failed to remove config");
+        assert_eq!(read, config);
+    }
+
+    #[test]
+    fn it_rejects_a_land_fraction_above_one() {
+        let mut config = small_config();
+        config.land_fraction = 1.5;
+        let result = WorldGenerator::new(config).build();
+        assert!(matches!(result, Err(MapError::InvalidGeneratorSettings(_))));
+    }
+
+    #[test]
+    fn it_generates_a_world_the_map_loads() {
+        let dir = std::env::temp_dir().join("world_gen_world_test");
+        let _result = fs::remove_dir_all(&dir);
+        let world = WorldGenerator::new(small_config())
+            .generate(&dir)
+            .expect("Failed to generate world");
+        assert!(!world.states.states.is_empty());
+        assert_eq!(
+            world.airports.airports.len(),
+            world.supply.supply_nodes.nodes.len()
+        );
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let root = dir.clone();
+        let handle = rt
+            .spawn_blocking(move || Map::new::<InMemoryTerm>(&root, &None, &MapLoadOptions::new()));
+        let map = rt.block_on(handle).unwrap().expect("Failed to load world");
+        let default_map = fs::read_to_string(dir.join("map/default.map")).expect("No default.map");
+        for file in default_map.lines().filter_map(|l| l.split('"').nth(1)) {
+            assert!(
+                dir.join("map").join(file).exists(),
+                "{file} was not written"
+            );
+        }
+        fs::remove_dir_all(&dir).expect("Failed to remove world");
+        assert_eq!(map.states.len(), world.states.states.len());
+        assert_eq!(map.localisation.text("STATE_1"), Some("State 1"));
+        assert_eq!(
+            map.definitions.definitions.len(),
+            world.definitions.definitions.len()
+        );
+        let report = map.validate();
+        assert!(!report.has_errors(), "{report}");
+    }
+}
@@ -0,0 +1,423 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::province_index::ProvinceIndex;
+use crate::MapError;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A closed outline traced along the corners of the pixels, ending on the corner it started from
+pub type Ring = Vec<(u32, u32)>;
+
+/// An outer ring followed by the rings of the holes within it
+pub type Polygon = Vec<Ring>;
+
+/// Traces the outlines of the regions the provinces belong to, giving the polygons of each region.
+///
+/// This is marching squares over the corners of the pixels: a corner is on an outline where the
+/// pixels around it are not all of the same region.  The outer rings run clockwise on the image
+/// and the holes counterclockwise, so that the region is always on the right of an outline.  Where
+/// two pixels of a region only touch at a corner they are kept apart, as the game does not count
+/// provinces touching at a corner as neighbors.  Pixels without a province or region are left out.
+#[inline]
+#[must_use]
+pub fn trace_regions<K, F>(
+    province_index: &ProvinceIndex,
+    region_of: F,
+) -> BTreeMap<K, Vec<Polygon>>
+where
+    K: Copy + Ord + Hash,
+    F: Fn(ProvinceId) -> Option<K>,
+{
+    boundary_edges(province_index, region_of)
+        .into_iter()
+        .map(|(region, edges)| (region, polygons(trace_rings(&edges))))
+        .collect()
+}
+
+/// The edges between the pixels of each region and the pixels around them, from the corner each
+/// edge starts at to the corners it runs to
+#[allow(clippy::integer_arithmetic)]
+fn boundary_edges<K, F>(
+    province_index: &ProvinceIndex,
+    region_of: F,
+) -> HashMap<K, BTreeMap<(u32, u32), Vec<(u32, u32)>>>
+where
+    K: Copy + Ord + Hash,
+    F: Fn(ProvinceId) -> Option<K>,
+{
+    let (width, height) = province_index.dimensions();
+    let region_at = |x: Option<u32>, y: Option<u32>| match (x, y) {
+        (Some(x), Some(y)) if x < width && y < height => {
+            province_index.province_at(x, y).and_then(&region_of)
+        }
+        _ => None,
+    };
+    let mut edges: HashMap<K, BTreeMap<(u32, u32), Vec<(u32, u32)>>> = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let region = match region_at(Some(x), Some(y)) {
+                Some(r) => r,
+                None => continue,
+            };
+            // The sides of the pixel clockwise from the top, each with the pixel beyond it
+            let sides = [
+                ((x, y), (x + 1, y), region_at(Some(x), y.checked_sub(1))),
+                ((x + 1, y), (x + 1, y + 1), region_at(Some(x + 1), Some(y))),
+                ((x + 1, y + 1), (x, y + 1), region_at(Some(x), Some(y + 1))),
+                ((x, y + 1), (x, y), region_at(x.checked_sub(1), Some(y))),
+            ];
+            for (from, to, beyond) in sides {
+                if beyond != Some(region) {
+                    let region_edges = edges.entry(region).or_default();
+                    region_edges.entry(from).or_default().push(to);
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Joins the edges of a region into rings, leaving out the corners where an outline runs straight
+fn trace_rings(edges: &BTreeMap<(u32, u32), Vec<(u32, u32)>>) -> Vec<Ring> {
+    let mut visited = HashSet::new();
+    let mut rings = Vec::new();
+    for (&start_from, ends) in edges {
+        for &start_to in ends {
+            if visited.contains(&(start_from, start_to)) {
+                continue;
+            }
+            let mut ring = vec![start_from];
+            let (mut from, mut to) = (start_from, start_to);
+            loop {
+                visited.insert((from, to));
+                ring.push(to);
+                let next = match next_edge(edges, from, to) {
+                    Some(n) => n,
+                    None => break,
+                };
+                if (to, next) == (start_from, start_to) {
+                    break;
+                }
+                (from, to) = (to, next);
+            }
+            rings.push(simplify(&ring));
+        }
+    }
+    rings
+}
+
+/// The corner an outline runs to after the edge between two corners.  Where two pixels of the
+/// region touch at the corner, the outline turns right to stay around the pixel it was following.
+fn next_edge(
+    edges: &BTreeMap<(u32, u32), Vec<(u32, u32)>>,
+    from: (u32, u32),
+    to: (u32, u32),
+) -> Option<(u32, u32)> {
+    let ends = edges.get(&to)?;
+    if let [end] = ends.as_slice() {
+        return Some(*end);
+    }
+    let (dx, dy) = direction(from, to);
+    ends.iter()
+        .copied()
+        .find(|end| direction(to, *end) == (-dy, dx))
+        .or_else(|| ends.first().copied())
+}
+
+/// The direction from one corner to the next along an outline
+fn direction(from: (u32, u32), to: (u32, u32)) -> (i64, i64) {
+    (
+        i64::from(to.0).saturating_sub(i64::from(from.0)).signum(),
+        i64::from(to.1).saturating_sub(i64::from(from.1)).signum(),
+    )
+}
+
+/// Leaves out the corners of a closed ring where the outline runs straight on
+fn simplify(ring: &[(u32, u32)]) -> Ring {
+    let corners = ring.get(..ring.len().saturating_sub(1)).unwrap_or_default();
+    let before = corners.iter().cycle().skip(corners.len().saturating_sub(1));
+    let after = corners.iter().cycle().skip(1);
+    let mut simplified = corners
+        .iter()
+        .zip(before.zip(after))
+        .filter(|(corner, (previous, next))| {
+            direction(**previous, **corner) != direction(**corner, **next)
+        })
+        .map(|(corner, _)| *corner)
+        .collect::<Vec<_>>();
+    if let Some(first) = simplified.first().copied() {
+        simplified.push(first);
+    }
+    simplified
+}
+
+/// Twice the signed area of a ring, which is positive for a ring running clockwise on the image
+#[allow(clippy::integer_arithmetic)]
+fn doubled_area(ring: &[(u32, u32)]) -> i64 {
+    ring.windows(2)
+        .map(|pair| match pair {
+            [(x0, y0), (x1, y1)] => {
+                i64::from(*x0) * i64::from(*y1) - i64::from(*x1) * i64::from(*y0)
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Whether a point lies within a ring, by counting the edges a ray to its right crosses
+#[allow(clippy::integer_arithmetic)]
+fn contains(ring: &[(u32, u32)], (x, y): (f64, f64)) -> bool {
+    ring.windows(2)
+        .filter(|pair| match pair {
+            [(x0, y0), (x1, y1)] => {
+                let (x0, y0, x1, y1) = (
+                    f64::from(*x0),
+                    f64::from(*y0),
+                    f64::from(*x1),
+                    f64::from(*y1),
+                );
+                (y0 > y) != (y1 > y) && x < (x1 - x0).mul_add((y - y0) / (y1 - y0), x0)
+            }
+            _ => false,
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// Groups the rings of a region into polygons, putting each hole in the smallest outer ring
+/// around it
+fn polygons(rings: Vec<Ring>) -> Vec<Polygon> {
+    let (outer, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|r| doubled_area(r) > 0);
+    let mut polygons = outer.into_iter().map(|r| vec![r]).collect::<Vec<_>>();
+    for hole in holes {
+        // The center of the pixel of the region to the right of the first edge of the hole, which
+        // lies within the outer ring of the hole and on no outline
+        let point = match hole.as_slice() {
+            [from, to, ..] => {
+                let (dx, dy) = direction(*from, *to);
+                let mid = |a: u32, b: u32| (f64::from(a) + f64::from(b)) / 2.0;
+                #[allow(clippy::as_conversions)]
+                #[allow(clippy::cast_precision_loss)]
+                let offset = |d: i64| d as f64 / 2.0;
+                (
+                    mid(from.0, to.0) - offset(dy),
+                    mid(from.1, to.1) + offset(dx),
+                )
+            }
+            _ => continue,
+        };
+        let around = polygons
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| {
+                let outer = p.first()?;
+                contains(outer, point).then(|| (doubled_area(outer), i))
+            })
+            .min_by_key(|(area, _)| *area)
+            .and_then(|(_, i)| polygons.get_mut(i));
+        if let Some(polygon) = around {
+            polygon.push(hole);
+        }
+    }
+    polygons
+}
+
+/// Converts a ring from the corners of the pixels to longitude and latitude, stretching the map
+/// over the whole globe.  Flipping the map upright turns the outer rings counterclockwise, as
+/// GeoJSON expects.
+fn coordinates(ring: &[(u32, u32)], (width, height): (u32, u32)) -> Vec<[f64; 2]> {
+    let (width, height) = (f64::from(width.max(1)), f64::from(height.max(1)));
+    let round = |v: f64| (v * 1e6).round() / 1e6;
+    ring.iter()
+        .map(|(x, y)| {
+            let longitude = (f64::from(*x) / width).mul_add(360.0, -180.0);
+            let latitude = (f64::from(*y) / height).mul_add(-180.0, 90.0);
+            [round(longitude), round(latitude)]
+        })
+        .collect()
+}
+
+/// Builds a `FeatureCollection` with a `MultiPolygon` feature for each region
+fn feature_collection<K, F, P>(province_index: &ProvinceIndex, region_of: F, properties: P) -> Value
+where
+    K: Copy + Ord + Hash,
+    F: Fn(ProvinceId) -> Option<K>,
+    P: Fn(K) -> Value,
+{
+    let size = province_index.dimensions();
+    let features = trace_regions(province_index, region_of)
+        .into_iter()
+        .map(|(region, polygons)| {
+            let polygons = polygons
+                .iter()
+                .map(|p| p.iter().map(|r| coordinates(r, size)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+                "properties": properties(region),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+/// Builds the `FeatureCollection` of the provinces, with their definitions and the state and
+/// strategic region each belongs to
+#[inline]
+#[must_use]
+pub fn provinces_geojson(
+    province_index: &ProvinceIndex,
+    definitions: &Definitions,
+    states_by_province: &HashMap<ProvinceId, StateId>,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+) -> Value {
+    feature_collection(province_index, Some, |id| {
+        let definition = definitions.definitions.get(&id);
+        json!({
+            "id": id,
+            "type": definition.map(|d| d.province_type),
+            "coastal": definition.map(|d| d.coastal),
+            "terrain": definition.map(|d| &d.terrain),
+            "continent": definition.map(|d| d.continent),
+            "state": states_by_province.get(&id),
+            "strategic_region": strategic_regions_by_province.get(&id),
+        })
+    })
+}
+
+/// Builds the `FeatureCollection` of the states, with their history and provinces
+#[inline]
+#[must_use]
+pub fn states_geojson(
+    province_index: &ProvinceIndex,
+    states: &HashMap<StateId, State>,
+    states_by_province: &HashMap<ProvinceId, StateId>,
+) -> Value {
+    let region_of = |id| states_by_province.get(&id).copied();
+    feature_collection(province_index, region_of, |id| {
+        let state = states.get(&id);
+        let history = state.and_then(|s| s.history.as_ref());
+        json!({
+            "id": id,
+            "name": state.map(|s| &s.name),
+            "manpower": state.and_then(State::current_manpower),
+            "category": state.and_then(State::current_category),
+            "owner": history.map(|h| &h.owner),
+            "controller": history.and_then(|h| h.controller.as_ref()),
+            "provinces": state.map(|s| s.provinces.iter().collect::<BTreeSet<_>>()),
+        })
+    })
+}
+
+/// Builds the `FeatureCollection` of the strategic regions, with their names and provinces
+#[inline]
+#[must_use]
+pub fn strategic_regions_geojson(
+    province_index: &ProvinceIndex,
+    strategic_regions: &StrategicRegions,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+) -> Value {
+    let region_of = |id| strategic_regions_by_province.get(&id).copied();
+    feature_collection(province_index, region_of, |id| {
+        let region = strategic_regions.strategic_regions.get(&id);
+        json!({
+            "id": id,
+            "name": region.map(|r| &r.name),
+            "provinces": region.map(|r| r.provinces.iter().collect::<BTreeSet<_>>()),
+        })
+    })
+}
+
+/// Writes a GeoJSON value to a file
+/// # Errors
+/// If the file cannot be written
+#[inline]
+pub fn write_geojson(path: &Path, geojson: &Value) -> Result<(), MapError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, geojson)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn index(ids: &[&[u8]]) -> ProvinceIndex {
+        let provinces = RgbImage::from_fn(ids[0].len() as u32, ids.len() as u32, |x, y| {
+            Rgb([ids[y as usize][x as usize], 0, 0])
+        });
+        let provinces_by_color = (1..=3)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index")
+    }
+
+    #[test]
+    fn it_traces_the_outlines_of_provinces_with_holes() {
+        // Province 2 is a lake within province 1
+        let index = index(&[&[1, 1, 1], &[1, 2, 1], &[1, 1, 1]]);
+        let outlines = trace_regions(&index, Some);
+
+        let outer = &outlines[&ProvinceId(1)];
+        assert_eq!(outer.len(), 1);
+        assert_eq!(outer[0].len(), 2);
+        assert_eq!(outer[0][0], vec![(0, 0), (3, 0), (3, 3), (0, 3), (0, 0)]);
+        assert_eq!(outer[0][1], vec![(1, 1), (1, 2), (2, 2), (2, 1), (1, 1)]);
+
+        let inner = &outlines[&ProvinceId(2)];
+        assert_eq!(
+            inner,
+            &vec![vec![vec![(1, 1), (2, 1), (2, 2), (1, 2), (1, 1)]]]
+        );
+    }
+
+    #[test]
+    fn it_keeps_pixels_touching_at_a_corner_apart() {
+        let index = index(&[&[1, 2], &[2, 1]]);
+        let outlines = trace_regions(&index, Some);
+        assert_eq!(outlines[&ProvinceId(1)].len(), 2);
+        assert_eq!(outlines[&ProvinceId(2)].len(), 2);
+        assert!(outlines
+            .values()
+            .flatten()
+            .all(|p| p.len() == 1 && p[0].len() == 5));
+    }
+
+    #[test]
+    fn it_merges_provinces_into_regions() {
+        let index = index(&[&[1, 2, 3]]);
+        let region_of = |id: ProvinceId| (id.0 < 3).then_some(1);
+        let outlines = trace_regions(&index, region_of);
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(
+            outlines[&1],
+            vec![vec![vec![(0, 0), (2, 0), (2, 1), (0, 1), (0, 0)]]]
+        );
+
+        let geojson = feature_collection(&index, region_of, |id| json!({ "id": id }));
+        let feature = &geojson["features"][0];
+        assert_eq!(feature["properties"]["id"], 1);
+        assert_eq!(
+            feature["geometry"]["coordinates"][0][0][0],
+            json!([-180.0, 90.0])
+        );
+        assert_eq!(
+            feature["geometry"]["coordinates"][0][0][2],
+            json!([60.0, -90.0])
+        );
+    }
+}
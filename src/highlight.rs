@@ -0,0 +1,78 @@
+use crate::components::prelude::*;
+use crate::province_index::ProvinceIndex;
+use image::{Rgba, RgbaImage};
+use std::collections::HashSet;
+
+/// The color the selected provinces are tinted with
+const SELECTION_TINT: Rgba<u8> = Rgba([255, 255, 255, 72]);
+/// The color of the outline of the selected provinces
+const SELECTION_OUTLINE: Rgba<u8> = Rgba([255, 255, 0, 255]);
+
+/// Draws a layer highlighting the selected provinces, to be drawn over the texture of the display
+/// mode so that the selected province, state or strategic region stands out from the rest of the
+/// map.  The selected provinces are tinted and the edge of the selection is outlined, so the
+/// borders between the provinces of a selected state or strategic region are not drawn.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn highlight_provinces(
+    province_index: &ProvinceIndex,
+    selected: &HashSet<ProvinceId>,
+) -> RgbaImage {
+    let (width, height) = province_index.dimensions();
+    let is_selected = |x: u32, y: u32| {
+        province_index
+            .province_at(x, y)
+            .map_or(false, |p| selected.contains(&p))
+    };
+    RgbaImage::from_fn(width, height, |x, y| {
+        if !is_selected(x, y) {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let on_edge = x == 0
+            || y == 0
+            || x + 1 == width
+            || y + 1 == height
+            || !is_selected(x - 1, y)
+            || !is_selected(x + 1, y)
+            || !is_selected(x, y - 1)
+            || !is_selected(x, y + 1);
+        if on_edge {
+            SELECTION_OUTLINE
+        } else {
+            SELECTION_TINT
+        }
+    })
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_outlines_and_tints_the_selected_provinces() {
+        // Provinces 1, 2 and 3 are the left, middle and right thirds of the map
+        let provinces = RgbImage::from_fn(9, 5, |x, _| Rgb([(x / 3 + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=3)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+
+        let layer = highlight_provinces(&index, &HashSet::from([ProvinceId(1), ProvinceId(2)]));
+        assert_eq!(*layer.get_pixel(0, 2), SELECTION_OUTLINE);
+        assert_eq!(*layer.get_pixel(1, 2), SELECTION_TINT);
+        // The border between the two selected provinces is not outlined
+        assert_eq!(*layer.get_pixel(2, 2), SELECTION_TINT);
+        assert_eq!(*layer.get_pixel(3, 2), SELECTION_TINT);
+        assert_eq!(*layer.get_pixel(5, 2), SELECTION_OUTLINE);
+        assert_eq!(*layer.get_pixel(6, 2), Rgba([0, 0, 0, 0]));
+        assert_eq!(*layer.get_pixel(4, 0), SELECTION_OUTLINE);
+    }
+}
@@ -0,0 +1,703 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::{MapDisplayMode, MapError};
+use image::{Rgb, RgbImage};
+use log::{debug, warn};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::PathBuf;
+
+/// The memory the undo history may use before older edits are spilled to disk, in bytes
+pub const DEFAULT_MEMORY_CAP: usize = 256 * 1024 * 1024;
+
+/// The bytes a run takes besides its pixels
+const RUN_HEADER_SIZE: usize = 3 * size_of::<u32>();
+
+/// The bytes a pixel takes in a run, once before and once after the edit
+const RUN_PIXEL_SIZE: usize = 6;
+
+/// A run of pixels along a row of an image that an edit changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PixelRun {
+    x: u32,
+    y: u32,
+    before: Vec<[u8; 3]>,
+    after: Vec<[u8; 3]>,
+}
+
+/// The pixels of an image changed by an edit, kept as runs along the rows of the image so that
+/// even a large stroke over a large image takes little memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ImageDelta {
+    runs: Vec<PixelRun>,
+}
+
+impl ImageDelta {
+    /// Finds the pixels that differ between an image before and after an edit.  Returns `None` if
+    /// the edit changed the size of the image.
+    #[inline]
+    #[must_use]
+    pub fn between(before: &RgbImage, after: &RgbImage) -> Option<Self> {
+        if before.dimensions() != after.dimensions() {
+            return None;
+        }
+        let mut runs: Vec<PixelRun> = Vec::new();
+        for ((x, y, old), new) in before.enumerate_pixels().zip(after.pixels()) {
+            if old == new {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run)
+                    if run.y == y
+                        && x.checked_sub(run.x) == u32::try_from(run.before.len()).ok() =>
+                {
+                    run.before.push(old.0);
+                    run.after.push(new.0);
+                }
+                _ => runs.push(PixelRun {
+                    x,
+                    y,
+                    before: vec![old.0],
+                    after: vec![new.0],
+                }),
+            }
+        }
+        Some(Self { runs })
+    }
+
+    /// Whether the edit changed no pixels
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// The number of pixels the edit changed
+    #[inline]
+    #[must_use]
+    pub fn pixel_count(&self) -> usize {
+        self.runs.iter().map(|r| r.before.len()).sum()
+    }
+
+    /// The memory the delta takes, in bytes
+    #[inline]
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|r| RUN_HEADER_SIZE.saturating_add(r.before.len().saturating_mul(RUN_PIXEL_SIZE)))
+            .sum()
+    }
+
+    /// Restores the pixels of the image to how they were before the edit
+    #[inline]
+    pub fn revert(&self, image: &mut RgbImage) {
+        self.paint(image, |run| &run.before);
+    }
+
+    /// Changes the pixels of the image to how they were after the edit
+    #[inline]
+    pub fn reapply(&self, image: &mut RgbImage) {
+        self.paint(image, |run| &run.after);
+    }
+
+    fn paint(&self, image: &mut RgbImage, pixels: impl Fn(&PixelRun) -> &Vec<[u8; 3]>) {
+        for run in &self.runs {
+            for (x, color) in (run.x..).zip(pixels(run)) {
+                if let Some(pixel) = image.get_pixel_mut_checked(x, run.y) {
+                    *pixel = Rgb(*color);
+                }
+            }
+        }
+    }
+
+    /// Writes the runs as their position and length followed by the pixels before and after
+    fn write(&self, writer: &mut impl Write) -> Result<(), MapError> {
+        for run in &self.runs {
+            let length = u32::try_from(run.before.len()).unwrap_or(u32::MAX);
+            for value in [run.x, run.y, length] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            for color in run.before.iter().chain(&run.after) {
+                writer.write_all(color)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the runs written by `write`
+    fn read(reader: &mut impl Read) -> Result<Self, MapError> {
+        let mut runs = Vec::new();
+        let mut header = [0; RUN_HEADER_SIZE];
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let mut values = header
+                .chunks_exact(size_of::<u32>())
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap_or_default()));
+            let (x, y, length) = match (values.next(), values.next(), values.next()) {
+                (Some(x), Some(y), Some(length)) => (x, y, length),
+                _ => break,
+            };
+            let mut read_colors = || -> Result<Vec<[u8; 3]>, MapError> {
+                (0..length)
+                    .map(|_| {
+                        let mut color = [0; 3];
+                        reader.read_exact(&mut color)?;
+                        Ok(color)
+                    })
+                    .collect()
+            };
+            let before = read_colors()?;
+            let after = read_colors()?;
+            runs.push(PixelRun {
+                x,
+                y,
+                before,
+                after,
+            });
+        }
+        Ok(Self { runs })
+    }
+}
+
+/// The changed pixels of an image, either in memory or spilled to a file
+#[derive(Debug)]
+enum Pixels {
+    Loaded(ImageDelta),
+    Spilled { path: PathBuf },
+}
+
+impl Pixels {
+    fn size(&self) -> usize {
+        match self {
+            Self::Loaded(delta) => delta.size(),
+            Self::Spilled { .. } => 0,
+        }
+    }
+
+    fn load(&self) -> Result<Cow<'_, ImageDelta>, MapError> {
+        match self {
+            Self::Loaded(delta) => Ok(Cow::Borrowed(delta)),
+            Self::Spilled { path } => {
+                let mut reader = BufReader::new(File::open(path)?);
+                Ok(Cow::Owned(ImageDelta::read(&mut reader)?))
+            }
+        }
+    }
+}
+
+/// A change an edit made to one component of the map
+#[derive(Debug)]
+enum Delta {
+    Image {
+        mode: MapDisplayMode,
+        pixels: Pixels,
+    },
+    State {
+        id: StateId,
+        before: Box<Option<State>>,
+        after: Box<Option<State>>,
+    },
+}
+
+impl Delta {
+    fn size(&self) -> usize {
+        match self {
+            Self::Image { pixels, .. } => pixels.size(),
+            Self::State { .. } => size_of::<State>().saturating_mul(2),
+        }
+    }
+
+    /// Changes the target to how it was before the edit for `undo`, or after it otherwise,
+    /// returning the display mode of the image changed if any
+    fn apply(
+        &self,
+        target: &mut impl Editable,
+        undo: bool,
+    ) -> Result<Option<MapDisplayMode>, MapError> {
+        match self {
+            Self::Image { mode, pixels } => {
+                let delta = pixels.load()?;
+                if let Some(image) = target.image_mut(*mode) {
+                    if undo {
+                        delta.revert(image);
+                    } else {
+                        delta.reapply(image);
+                    }
+                }
+                Ok(Some(*mode))
+            }
+            Self::State { id, before, after } => {
+                let state = if undo { before } else { after };
+                target.replace_state(*id, (**state).clone());
+                Ok(None)
+            }
+        }
+    }
+
+    fn remove_spilled_file(&self) {
+        if let Self::Image {
+            pixels: Pixels::Spilled { path },
+            ..
+        } = self
+        {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// An edit of the map that can be undone, as the changes it made to each component
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Edit {
+    /// What the edit did, such as "Paint terrain"
+    pub name: String,
+    deltas: Vec<Delta>,
+}
+
+impl Edit {
+    /// Creates a new edit that has not changed anything yet
+    #[inline]
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            deltas: Vec::new(),
+        }
+    }
+
+    /// Adds the pixels the edit changed on an image, unless it changed none
+    #[inline]
+    #[must_use]
+    pub fn with_image(mut self, mode: MapDisplayMode, delta: ImageDelta) -> Self {
+        if !delta.is_empty() {
+            self.deltas.push(Delta::Image {
+                mode,
+                pixels: Pixels::Loaded(delta),
+            });
+        }
+        self
+    }
+
+    /// Adds a state as it was before and after the edit, where `None` is a state that did not
+    /// exist
+    #[inline]
+    #[must_use]
+    pub fn with_state(mut self, id: StateId, before: Option<State>, after: Option<State>) -> Self {
+        self.deltas.push(Delta::State {
+            id,
+            before: Box::new(before),
+            after: Box::new(after),
+        });
+        self
+    }
+
+    /// Whether the edit changed nothing
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// The memory the edit takes, in bytes
+    #[inline]
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.deltas.iter().map(Delta::size).sum()
+    }
+
+    fn apply(
+        &self,
+        target: &mut impl Editable,
+        undo: bool,
+    ) -> Result<Vec<MapDisplayMode>, MapError> {
+        let mut modes = Vec::new();
+        // The deltas are undone in the reverse of the order they were made in
+        let deltas: Box<dyn Iterator<Item = &Delta>> = if undo {
+            Box::new(self.deltas.iter().rev())
+        } else {
+            Box::new(self.deltas.iter())
+        };
+        for delta in deltas {
+            if let Some(mode) = delta.apply(target, undo)? {
+                if !modes.contains(&mode) {
+                    modes.push(mode);
+                }
+            }
+        }
+        Ok(modes)
+    }
+
+    fn remove_spilled_files(&self) {
+        for delta in &self.deltas {
+            delta.remove_spilled_file();
+        }
+    }
+}
+
+/// The parts of the map that edits can change
+pub trait Editable {
+    /// The image shown in a display mode, if it has been loaded or generated
+    fn image_mut(&mut self, mode: MapDisplayMode) -> Option<&mut RgbImage>;
+    /// Replaces a state, removing it for `None`
+    fn replace_state(&mut self, id: StateId, state: Option<State>);
+}
+
+/// The edits that can be undone and redone.
+///
+/// Edits keep only what they changed rather than copies of whole components.  Once the edits take
+/// more memory than the cap, the changed pixels of the oldest edits are spilled to files in the
+/// spill directory, or the oldest edits are forgotten if there is none.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct EditHistory {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    /// The memory the edits may take before they are spilled or forgotten, in bytes
+    pub memory_cap: usize,
+    /// The directory spilled edits are written to, if any
+    pub spill_dir: Option<PathBuf>,
+    spilled_files: usize,
+}
+
+impl Default for EditHistory {
+    #[inline]
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CAP, None)
+    }
+}
+
+impl EditHistory {
+    /// Creates a new empty history
+    #[inline]
+    #[must_use]
+    pub const fn new(memory_cap: usize, spill_dir: Option<PathBuf>) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            memory_cap,
+            spill_dir,
+            spilled_files: 0,
+        }
+    }
+
+    /// Records an edit, which can no longer be redone past
+    /// # Errors
+    /// * If an older edit could not be spilled to disk
+    #[inline]
+    pub fn push(&mut self, edit: Edit) -> Result<(), MapError> {
+        if edit.is_empty() {
+            return Ok(());
+        }
+        debug!("Recording edit {:?} of {} bytes", edit.name, edit.size());
+        for redo in self.redo.drain(..) {
+            redo.remove_spilled_files();
+        }
+        self.undo.push_back(edit);
+        self.enforce_memory_cap()
+    }
+
+    /// Forgets every edit, for changes to the map that are not recorded as edits and that the
+    /// recorded edits could not be replayed over, such as recoloring the provinces
+    #[inline]
+    pub fn clear(&mut self) {
+        for edit in self.undo.drain(..).chain(self.redo.drain(..)) {
+            edit.remove_spilled_files();
+        }
+    }
+
+    /// The name of the edit that would be undone next
+    #[inline]
+    #[must_use]
+    pub fn next_undo(&self) -> Option<&str> {
+        self.undo.back().map(|e| e.name.as_str())
+    }
+
+    /// The name of the edit that would be redone next
+    #[inline]
+    #[must_use]
+    pub fn next_redo(&self) -> Option<&str> {
+        self.redo.last().map(|e| e.name.as_str())
+    }
+
+    /// The memory the edits in memory take, in bytes
+    #[inline]
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.undo.iter().chain(&self.redo).map(Edit::size).sum()
+    }
+
+    /// Undoes the last edit, returning the display modes of the images it changed, or `None` if
+    /// there is nothing to undo.  An edit that cannot be undone stays the next one to undo.
+    /// # Errors
+    /// * If the edit was spilled to disk and cannot be read back
+    #[inline]
+    pub fn undo(
+        &mut self,
+        target: &mut impl Editable,
+    ) -> Result<Option<Vec<MapDisplayMode>>, MapError> {
+        let edit = match self.undo.pop_back() {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        match edit.apply(target, true) {
+            Ok(modes) => {
+                self.redo.push(edit);
+                Ok(Some(modes))
+            }
+            Err(e) => {
+                self.undo.push_back(edit);
+                Err(e)
+            }
+        }
+    }
+
+    /// Redoes the last undone edit, returning the display modes of the images it changed, or
+    /// `None` if there is nothing to redo.  An edit that cannot be redone stays the next one to
+    /// redo.
+    /// # Errors
+    /// * If the edit was spilled to disk and cannot be read back
+    #[inline]
+    pub fn redo(
+        &mut self,
+        target: &mut impl Editable,
+    ) -> Result<Option<Vec<MapDisplayMode>>, MapError> {
+        let edit = match self.redo.pop() {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        match edit.apply(target, false) {
+            Ok(modes) => {
+                self.undo.push_back(edit);
+                Ok(Some(modes))
+            }
+            Err(e) => {
+                self.redo.push(edit);
+                Err(e)
+            }
+        }
+    }
+
+    /// Spills or forgets the oldest edits until the edits in memory fit within the cap.  The
+    /// latest edit is always kept in memory.
+    fn enforce_memory_cap(&mut self) -> Result<(), MapError> {
+        let mut usage = self.memory_usage();
+        let kept = self.undo.len().saturating_sub(1);
+        let mut index = 0;
+        while usage > self.memory_cap && index < kept {
+            let spill_dir = match &self.spill_dir {
+                Some(d) => d.clone(),
+                None => break,
+            };
+            if let Some(edit) = self.undo.get_mut(index) {
+                for delta in &mut edit.deltas {
+                    if let Delta::Image { pixels, .. } = delta {
+                        if let Pixels::Loaded(image_delta) = pixels {
+                            let path = spill_dir.join(format!(
+                                "edit_{}_{}.bin",
+                                std::process::id(),
+                                self.spilled_files
+                            ));
+                            fs::create_dir_all(&spill_dir)?;
+                            let mut writer = BufWriter::new(File::create(&path)?);
+                            image_delta.write(&mut writer)?;
+                            writer.flush()?;
+                            self.spilled_files = self.spilled_files.saturating_add(1);
+                            usage = usage.saturating_sub(image_delta.size());
+                            *pixels = Pixels::Spilled { path };
+                        }
+                    }
+                }
+            }
+            index = index.saturating_add(1);
+        }
+        while usage > self.memory_cap && self.undo.len() > 1 {
+            if let Some(edit) = self.undo.pop_front() {
+                debug!("Forgetting edit {:?}", edit.name);
+                usage = usage.saturating_sub(edit.size());
+                edit.remove_spilled_files();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for EditHistory {
+    #[inline]
+    fn drop(&mut self) {
+        for edit in self.undo.iter().chain(&self.redo) {
+            edit.remove_spilled_files();
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Target {
+        image: RgbImage,
+        states: HashMap<StateId, State>,
+    }
+
+    impl Editable for Target {
+        fn image_mut(&mut self, mode: MapDisplayMode) -> Option<&mut RgbImage> {
+            (mode == MapDisplayMode::Terrain).then_some(&mut self.image)
+        }
+
+        fn replace_state(&mut self, id: StateId, state: Option<State>) {
+            match state {
+                Some(s) => self.states.insert(id, s),
+                None => self.states.remove(&id),
+            };
+        }
+    }
+
+    /// Paints a horizontal line onto the target, recording it as an edit
+    fn paint_line(target: &mut Target, y: u32, color: u8) -> Edit {
+        let before = target.image.clone();
+        for x in 2..8 {
+            target.image.put_pixel(x, y, Rgb([color, 0, 0]));
+        }
+        let delta = ImageDelta::between(&before, &target.image).expect("Failed to compare");
+        Edit::new("Paint").with_image(MapDisplayMode::Terrain, delta)
+    }
+
+    #[test]
+    fn it_keeps_only_the_changed_pixels_as_runs() {
+        let before = RgbImage::new(16, 4);
+        let mut after = before.clone();
+        for x in 3..9 {
+            after.put_pixel(x, 1, Rgb([1, 2, 3]));
+        }
+        after.put_pixel(0, 2, Rgb([4, 5, 6]));
+        let delta = ImageDelta::between(&before, &after).expect("Failed to compare");
+        assert_eq!(delta.runs.len(), 2);
+        assert_eq!(delta.pixel_count(), 7);
+        assert_eq!(delta.size(), 2 * RUN_HEADER_SIZE + 7 * RUN_PIXEL_SIZE);
+
+        let mut bytes = Vec::new();
+        delta.write(&mut bytes).expect("Failed to write delta");
+        let read = ImageDelta::read(&mut bytes.as_slice()).expect("Failed to read delta");
+        assert_eq!(read, delta);
+
+        let mut image = after.clone();
+        delta.revert(&mut image);
+        assert_eq!(image, before);
+        delta.reapply(&mut image);
+        assert_eq!(image, after);
+        assert!(ImageDelta::between(&before, &RgbImage::new(4, 4)).is_none());
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_edits() {
+        let mut target = Target {
+            image: RgbImage::new(10, 10),
+            ..Target::default()
+        };
+        let mut history = EditHistory::default();
+        let original = target.image.clone();
+        let edit = paint_line(&mut target, 1, 10);
+        history.push(edit).expect("Failed to record edit");
+        let painted = target.image.clone();
+        let edit = paint_line(&mut target, 1, 20);
+        history.push(edit).expect("Failed to record edit");
+
+        let modes = history.undo(&mut target).expect("Failed to undo");
+        assert_eq!(modes, Some(vec![MapDisplayMode::Terrain]));
+        assert_eq!(target.image, painted);
+        history.undo(&mut target).expect("Failed to undo");
+        assert_eq!(target.image, original);
+        assert_eq!(history.undo(&mut target).expect("Failed to undo"), None);
+
+        history.redo(&mut target).expect("Failed to redo");
+        assert_eq!(target.image, painted);
+        // A new edit cannot be redone past
+        let edit = paint_line(&mut target, 5, 30);
+        history.push(edit).expect("Failed to record edit");
+        assert_eq!(history.next_redo(), None);
+        assert_eq!(history.next_undo(), Some("Paint"));
+    }
+
+    #[test]
+    fn it_spills_old_edits_to_disk_past_the_memory_cap() {
+        let dir = std::env::temp_dir().join("world_gen_history_test");
+        let _result = fs::remove_dir_all(&dir);
+        let mut target = Target {
+            image: RgbImage::new(10, 10),
+            ..Target::default()
+        };
+        let line_size = RUN_HEADER_SIZE + 6 * RUN_PIXEL_SIZE;
+        let mut history = EditHistory::new(line_size, Some(dir.clone()));
+        let original = target.image.clone();
+        for y in 0..3 {
+            let edit = paint_line(&mut target, y, 10);
+            history.push(edit).expect("Failed to record edit");
+        }
+        assert_eq!(history.memory_usage(), line_size);
+        let spilled = fs::read_dir(&dir).expect("Failed to read spill directory");
+        assert_eq!(spilled.count(), 2);
+
+        for _ in 0..3 {
+            history.undo(&mut target).expect("Failed to undo");
+        }
+        assert_eq!(target.image, original);
+        drop(history);
+        let spilled = fs::read_dir(&dir).expect("Failed to read spill directory");
+        assert_eq!(spilled.count(), 0);
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+
+        // Without a spill directory the oldest edits are forgotten
+        let mut history = EditHistory::new(line_size, None);
+        for y in 0..3 {
+            let edit = paint_line(&mut target, y, 20);
+            history.push(edit).expect("Failed to record edit");
+        }
+        assert!(history.undo(&mut target).expect("Failed to undo").is_some());
+        assert!(history.undo(&mut target).expect("Failed to undo").is_none());
+
+        history.clear();
+        assert!(history.next_undo().is_none());
+        assert!(history.next_redo().is_none());
+    }
+
+    #[test]
+    fn it_keeps_an_edit_that_cannot_be_undone_in_place() {
+        let dir = std::env::temp_dir().join("world_gen_history_failure_test");
+        let _result = fs::remove_dir_all(&dir);
+        let mut target = Target {
+            image: RgbImage::new(10, 10),
+            ..Target::default()
+        };
+        let line_size = RUN_HEADER_SIZE + 6 * RUN_PIXEL_SIZE;
+        let mut history = EditHistory::new(line_size, Some(dir.clone()));
+        for y in 0..2 {
+            let edit = paint_line(&mut target, y, 10);
+            history.push(edit).expect("Failed to record edit");
+        }
+        // The spilled first edit can no longer be read back
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+
+        assert!(history.undo(&mut target).expect("Failed to undo").is_some());
+        assert!(history.undo(&mut target).is_err());
+        assert_eq!(history.next_undo(), Some("Paint"));
+        assert!(history.redo(&mut target).expect("Failed to redo").is_some());
+        assert_eq!(*target.image.get_pixel(2, 1), Rgb([10, 0, 0]));
+        assert!(history.next_redo().is_none());
+    }
+}
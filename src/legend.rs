@@ -0,0 +1,541 @@
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The size in pixels of each pixel of the legend font
+const SCALE: u32 = 2;
+/// The width of a character of the legend font, including the space after it
+const CHAR_WIDTH: u32 = 4 * SCALE;
+/// The height of a row of the legend, including the space below it
+const ROW_HEIGHT: u32 = 7 * SCALE;
+/// The space around the edges of the legend
+const MARGIN: u32 = 2 * SCALE;
+/// The color of the background of the legend
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+/// The color of the text of the legend
+const TEXT: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// The ways a legend can be saved
+#[allow(clippy::exhaustive_enums)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LegendFormat {
+    /// A semicolon separated table of ids, names and colors
+    #[default]
+    Csv,
+    /// An image with a swatch of each color next to its id and name
+    Image,
+}
+
+impl LegendFormat {
+    /// The extension of a legend file in this format
+    #[inline]
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Image => "png",
+        }
+    }
+}
+
+/// The lowest value of each channel of a region color, so that no region is mistaken for the
+/// black of the pixels without a region
+const MIN_CHANNEL: u8 = 48;
+
+/// The smallest distance between the colors of neighboring regions, on the scale of
+/// `color_distance`
+const MIN_NEIGHBOR_DISTANCE: f64 = 150.0;
+/// How many colors are tried for a region before taking the one furthest from its neighbors
+const MAX_TRIES: usize = 64;
+/// The fraction of the hue circle between consecutive colors of the distinct scheme, which never
+/// lands close to a hue used shortly before
+const GOLDEN_RATIO_STEP: f64 = 0.618_033_988_749_895;
+
+/// The ways the regions of the generated region maps can be colored
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionColorScheme {
+    /// Hues stepped around the color wheel by the golden ratio, with neighboring regions given
+    /// colors far apart
+    #[default]
+    Distinct,
+    /// A color mixed from the id of each region alone
+    Hashed,
+}
+
+/// How the regions of the generated region maps are colored.  A region is always given the same
+/// color, so that screenshots and diffs of the region maps can be compared between runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct RegionColors {
+    /// The way colors are chosen when there is no palette
+    pub scheme: RegionColorScheme,
+    /// Mixed into the color of every region, to shuffle the colors when neighbors look alike
+    pub seed: u64,
+    /// The colors to use instead of generated ones, given to the regions in order of their ids
+    /// and repeating once every color is used
+    pub palette: Vec<[u8; 3]>,
+    /// Whether the colors of the provinces are left out, so that a region is never drawn in the
+    /// color of a province
+    pub avoid_province_colors: bool,
+}
+
+impl RegionColors {
+    /// The color of the region with an id, from the palette or else mixed from the id and the
+    /// seed, with each channel kept away from black.  Neighbors are not taken into account.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn color(&self, id: i32) -> Rgb<u8> {
+        if !self.palette.is_empty() {
+            let index = i64::from(id).rem_euclid(self.palette.len() as i64) as usize;
+            if let Some(color) = self.palette.get(index) {
+                return Rgb(*color);
+            }
+        }
+        mixed_color(self.seed, id, 0)
+    }
+
+    /// Colors each of the regions, visiting them in order of id.  Regions are given colors no
+    /// other region has, and never one of the colors to avoid.  With the distinct scheme the
+    /// colors of neighboring regions, given as pairs of ids, are kept far apart where a close
+    /// color can be passed over.  A palette is used as it is.
+    #[inline]
+    #[must_use]
+    pub fn assign(
+        &self,
+        ids: &[i32],
+        neighbors: &HashSet<(i32, i32)>,
+        avoid: &HashSet<Rgb<u8>>,
+    ) -> HashMap<i32, Rgb<u8>> {
+        let mut ids = ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+        if !self.palette.is_empty() {
+            return ids.into_iter().map(|id| (id, self.color(id))).collect();
+        }
+        let mut adjacent: HashMap<i32, Vec<i32>> = HashMap::new();
+        for (a, b) in neighbors {
+            adjacent.entry(*a).or_default().push(*b);
+            adjacent.entry(*b).or_default().push(*a);
+        }
+        let mut colors = HashMap::new();
+        let mut used = HashSet::new();
+        // The steps of the distinct scheme passed over so far, and the next step not yet tried
+        let mut skipped = BTreeSet::new();
+        let mut next = 0_usize;
+        for id in ids {
+            let neighbor_colors = adjacent
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .filter_map(|n| colors.get(n))
+                .copied()
+                .collect::<Vec<Rgb<u8>>>();
+            let free = |c: &Rgb<u8>| !used.contains(c) && !avoid.contains(c);
+            let color = match self.scheme {
+                RegionColorScheme::Hashed => (0..)
+                    .map(|attempt| mixed_color(self.seed, id, attempt))
+                    .find(free),
+                RegionColorScheme::Distinct => {
+                    let candidates = skipped
+                        .iter()
+                        .copied()
+                        .chain(next..)
+                        .take(MAX_TRIES)
+                        .collect::<Vec<_>>();
+                    let mut best: Option<(f64, usize, Rgb<u8>)> = None;
+                    for step in &candidates {
+                        let color = distinct_color(self.seed, *step);
+                        if !free(&color) {
+                            continue;
+                        }
+                        let distance = neighbor_colors
+                            .iter()
+                            .map(|n| color_distance(color, *n))
+                            .fold(f64::INFINITY, f64::min);
+                        if best.map_or(true, |(d, _, _)| distance > d) {
+                            best = Some((distance, *step, color));
+                        }
+                        if distance >= MIN_NEIGHBOR_DISTANCE {
+                            break;
+                        }
+                    }
+                    // The fresh steps tried are kept for later regions, except the one taken
+                    let fresh = candidates.iter().copied().filter(|s| *s >= next);
+                    skipped.extend(fresh);
+                    next = candidates
+                        .iter()
+                        .max()
+                        .map_or(next, |s| next.max(s.saturating_add(1)));
+                    best.map(|(_, step, color)| {
+                        skipped.remove(&step);
+                        color
+                    })
+                }
+            };
+            if let Some(color) = color {
+                used.insert(color);
+                colors.insert(id, color);
+            }
+        }
+        colors
+    }
+}
+
+/// A color mixed from a name, such as the tag of a country, so that a name is given the same
+/// color on every run
+#[inline]
+#[must_use]
+pub fn name_color(name: &str) -> Rgb<u8> {
+    // FNV-1a, as the hashes of the standard library change between runs
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    RegionColors::default().color(i32::from_ne_bytes(hash.to_ne_bytes()))
+}
+
+/// A color mixed from the seed, the id of a region and the number of colors already tried for
+/// it, with each channel kept away from black
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::integer_arithmetic)]
+fn mixed_color(seed: u64, id: i32, attempt: u32) -> Rgb<u8> {
+    // The finalizer of splitmix64, which spreads neighboring ids over the whole range
+    let mut hash = seed ^ u64::from(id as u32) ^ (u64::from(attempt) << 32);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    let [r, g, b, ..] = hash.to_le_bytes();
+    let spread =
+        |c: u8| MIN_CHANNEL + (u16::from(c) * u16::from(u8::MAX - MIN_CHANNEL) / 255) as u8;
+    Rgb([spread(r), spread(g), spread(b)])
+}
+
+/// The color of a step of the distinct scheme.  The hue turns by the golden ratio each step, and
+/// the saturation and brightness change every few steps so that the colors do not repeat.
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::integer_arithmetic)]
+fn distinct_color(seed: u64, step: usize) -> Rgb<u8> {
+    const SATURATIONS: [f64; 3] = [0.75, 0.5, 0.95];
+    const VALUES: [f64; 2] = [0.95, 0.7];
+    let offset = (seed % 1000) as f64 / 1000.0;
+    let hue = (offset + step as f64 * GOLDEN_RATIO_STEP).fract();
+    let saturation = SATURATIONS
+        .get(step / 7 % SATURATIONS.len())
+        .copied()
+        .unwrap_or(0.75);
+    let value = VALUES
+        .get(step / 21 % VALUES.len())
+        .copied()
+        .unwrap_or(0.95);
+    hsv_to_rgb(hue, saturation, value)
+}
+
+/// Converts a hue, saturation and value, each from 0 to 1, to a color
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::many_single_char_names)]
+pub(crate) fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Rgb<u8> {
+    let sector = hue * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = value - c;
+    let channel = |v: f64| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Rgb([channel(r), channel(g), channel(b)])
+}
+
+/// How different two colors look, weighting the channels by how sensitive the eye is to them
+/// at the mean red of the colors.  Runs from 0 for the same color to about 765.
+fn color_distance(a: Rgb<u8>, b: Rgb<u8>) -> f64 {
+    let [r1, g1, b1] = a.0.map(f64::from);
+    let [r2, g2, b2] = b.0.map(f64::from);
+    let mean_red = (r1 + r2) / 2.0;
+    let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+    ((2.0 + mean_red / 256.0) * dr * dr
+        + 4.0 * dg * dg
+        + (2.0 + (255.0 - mean_red) / 256.0) * db * db)
+        .sqrt()
+}
+
+/// A region on a generated region map and the color it was drawn with
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LegendEntry {
+    /// The id of the region
+    pub id: i32,
+    /// The name of the region
+    pub name: String,
+    /// The color of the region on the map
+    pub color: Rgb<u8>,
+}
+
+impl LegendEntry {
+    /// Creates a new legend entry
+    #[inline]
+    #[must_use]
+    pub const fn new(id: i32, name: String, color: Rgb<u8>) -> Self {
+        Self { id, name, color }
+    }
+}
+
+/// Maps the colors of a generated region map to the regions they stand for, so that an exported
+/// map can be read outside of the editor.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Legend {
+    /// The regions of the map, ordered by id
+    pub entries: Vec<LegendEntry>,
+}
+
+impl Legend {
+    /// Creates a new legend, ordering the entries by id
+    #[inline]
+    #[must_use]
+    pub fn new(mut entries: Vec<LegendEntry>) -> Self {
+        entries.sort_by_key(|e| e.id);
+        Self { entries }
+    }
+
+    /// Writes the legend as a semicolon separated table with the columns `id;name;r;g;b`
+    #[inline]
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = "id;name;r;g;b\n".to_owned();
+        for entry in &self.entries {
+            let Rgb([r, g, b]) = entry.color;
+            // Writing to a `String` cannot fail
+            let _result = writeln!(csv, "{};{};{r};{g};{b}", entry.id, entry.name);
+        }
+        csv
+    }
+
+    /// Draws the legend as an image, with one row for each region holding a swatch of its color
+    /// followed by its id and name.  Names are drawn in capitals, and characters other than
+    /// letters, digits, `_`, `-` and `.` are drawn as spaces.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn to_image(&self) -> RgbImage {
+        let labels = self
+            .entries
+            .iter()
+            .map(|e| format!("{} {}", e.id, e.name))
+            .collect::<Vec<_>>();
+        let longest = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+        let text_x = MARGIN + ROW_HEIGHT;
+        let width = text_x + longest * CHAR_WIDTH + MARGIN;
+        let height = MARGIN * 2 + ROW_HEIGHT * self.entries.len() as u32;
+        let mut image = RgbImage::from_pixel(width, height, BACKGROUND);
+        for (row, (entry, label)) in self.entries.iter().zip(&labels).enumerate() {
+            let top = MARGIN + ROW_HEIGHT * row as u32;
+            fill(&mut image, MARGIN, top, 5 * SCALE, 5 * SCALE, entry.color);
+            for (column, c) in label.chars().enumerate() {
+                let left = text_x + CHAR_WIDTH * column as u32;
+                for (y, bits) in glyph(c).iter().enumerate() {
+                    for x in 0..3 {
+                        if bits & (0b100 >> x) != 0 {
+                            let px = left + x * SCALE;
+                            let py = top + y as u32 * SCALE;
+                            fill(&mut image, px, py, SCALE, SCALE, TEXT);
+                        }
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// Saves the legend to the given path in the given format
+    /// # Errors
+    /// * If the legend could not be written
+    #[inline]
+    pub fn save(&self, path: &Path, format: LegendFormat) -> Result<(), MapError> {
+        match format {
+            LegendFormat::Csv => fs::write(path, self.to_csv())?,
+            LegendFormat::Image => self.to_image().save(path)?,
+        }
+        Ok(())
+    }
+}
+
+/// The path of the legend for a map image, next to the image with `_legend` after its name
+#[inline]
+#[must_use]
+pub fn legend_path(image_path: &Path, format: LegendFormat) -> PathBuf {
+    let stem = image_path
+        .file_stem()
+        .map_or_else(|| "map".to_owned(), |s| s.to_string_lossy().into_owned());
+    image_path.with_file_name(format!("{stem}_legend.{}", format.extension()))
+}
+
+/// Fills a rectangle of the image with a color, clipping it to the image
+#[allow(clippy::integer_arithmetic)]
+fn fill(image: &mut RgbImage, left: u32, top: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for y in top..(top + height).min(image.height()) {
+        for x in left..(left + width).min(image.width()) {
+            image.put_pixel(x, y, color);
+        }
+    }
+}
+
+/// The rows of a character of the legend font, three pixels wide with the leftmost pixel in the
+/// highest bit
+const fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_the_colors_of_neighbors_apart() {
+        // A row of regions, each bordering the next, and a color that must not be used
+        let ids = (1..=40).collect::<Vec<_>>();
+        let neighbors = (1..40).map(|id| (id, id + 1)).collect::<HashSet<_>>();
+        let avoid = HashSet::from([distinct_color(0, 0)]);
+        let colors = RegionColors::default();
+        let assigned = colors.assign(&ids, &neighbors, &avoid);
+        assert_eq!(assigned.len(), 40);
+        assert_eq!(assigned, colors.assign(&ids, &neighbors, &avoid));
+        let unique = assigned.values().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), 40);
+        assert!(!unique.contains(&distinct_color(0, 0)));
+        for (a, b) in &neighbors {
+            assert!(color_distance(assigned[a], assigned[b]) >= MIN_NEIGHBOR_DISTANCE);
+        }
+
+        let hashed = RegionColors {
+            scheme: RegionColorScheme::Hashed,
+            ..RegionColors::default()
+        };
+        let assigned = hashed.assign(&ids, &neighbors, &HashSet::new());
+        assert_eq!(assigned[&7], hashed.color(7));
+    }
+
+    #[test]
+    fn it_gives_each_region_the_same_color_every_time() {
+        let colors = RegionColors::default();
+        assert_eq!(colors.color(42), RegionColors::default().color(42));
+        assert_ne!(colors.color(42), colors.color(43));
+        assert!((-5..500).all(|id| colors.color(id).0.iter().all(|c| *c >= MIN_CHANNEL)));
+
+        let seeded = RegionColors {
+            seed: 7,
+            ..RegionColors::default()
+        };
+        assert_ne!(seeded.color(42), colors.color(42));
+
+        let palette = RegionColors {
+            palette: vec![[255, 0, 0], [0, 0, 255]],
+            ..RegionColors::default()
+        };
+        assert_eq!(palette.color(3), Rgb([0, 0, 255]));
+        assert_eq!(palette.color(-2), Rgb([255, 0, 0]));
+    }
+
+    fn legend() -> Legend {
+        Legend::new(vec![
+            LegendEntry::new(2, "STATE_2".to_owned(), Rgb([0, 0, 255])),
+            LegendEntry::new(1, "STATE_1".to_owned(), Rgb([255, 0, 0])),
+        ])
+    }
+
+    #[test]
+    fn it_writes_a_legend_as_csv() {
+        assert_eq!(
+            legend().to_csv(),
+            "id;name;r;g;b\n1;STATE_1;255;0;0\n2;STATE_2;0;0;255\n"
+        );
+    }
+
+    #[test]
+    fn it_draws_a_swatch_for_each_region() {
+        let image = legend().to_image();
+        assert_eq!(image.height(), MARGIN * 2 + ROW_HEIGHT * 2);
+        assert_eq!(image.width(), MARGIN * 2 + ROW_HEIGHT + CHAR_WIDTH * 9);
+        assert_eq!(*image.get_pixel(MARGIN, MARGIN), Rgb([255, 0, 0]));
+        assert_eq!(
+            *image.get_pixel(MARGIN, MARGIN + ROW_HEIGHT),
+            Rgb([0, 0, 255])
+        );
+        assert!(image.pixels().any(|p| *p == TEXT));
+    }
+
+    #[test]
+    fn it_places_the_legend_next_to_the_map() {
+        assert_eq!(
+            legend_path(Path::new("/maps/states.png"), LegendFormat::Csv),
+            PathBuf::from("/maps/states_legend.csv")
+        );
+        assert_eq!(
+            legend_path(Path::new("/maps/states.png"), LegendFormat::Image),
+            PathBuf::from("/maps/states_legend.png")
+        );
+    }
+}
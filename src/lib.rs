@@ -23,14 +23,15 @@
 #![allow(clippy::pub_use)]
 
 use crate::components::prelude::*;
+use crate::encoding::{read_text, TextEncoding};
 use derive_more::Display;
 use image::ImageError;
 use indicatif::style::TemplateError;
 use jomini::{ScalarError, TextDeserializer, TextTape};
-use log::error;
-use serde::Deserialize;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::fmt::{Debug, Display};
+use std::fmt::{Debug, Display, Write};
 use std::fs;
 use std::hash::Hash;
 use std::path::{Path, PathBuf};
@@ -38,14 +39,77 @@ use std::str::FromStr;
 use thiserror::Error;
 use tokio::task::JoinError;
 
+/// Holds the loading of the DDS and TGA graphics the game draws the map with
+pub mod assets;
+/// Holds the brushes used to edit the map images
+pub mod brush;
+/// Holds the heat map of the buildings on the map
+pub mod building_density;
+/// Holds the cache of parsed map components
+pub mod cache;
 /// Holds the components of the map
 pub mod components;
+/// Holds the detection of the encodings of text files
+pub mod encoding;
+/// Holds the C interface for loading and checking maps from other languages
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Holds the synthetic maps used to test the editor at scale
+pub mod fixtures;
+/// Holds the formatting of the files users maintain by hand
+pub mod format;
+/// Holds the procedural generation of the map
+pub mod generation;
+/// Holds the export of the provinces, states and strategic regions as GeoJSON
+pub mod geojson;
+/// Holds the highlighting of the selected provinces, states and strategic regions on the map
+pub mod highlight;
+/// Holds the undo history of edits to the map
+pub mod history;
+/// Holds the legends explaining the colors of exported region maps
+pub mod legend;
+/// Holds the map of the manpower of the states
+pub mod manpower_map;
 /// Holds the components together into one struct
 pub mod map;
+/// Holds the traits for extending the map editor with custom layers and tools
+pub mod plugin;
+/// Holds the map of the countries owning the states
+pub mod political_map;
+/// Holds the posters of the map exported for sharing
+pub mod poster;
+/// Holds the previews of edits that have not been applied yet
+pub mod preview;
+/// Holds the progress of loading a map
+pub mod progress;
+/// Holds the editor's settings for a map, such as its bookmarks
+pub mod project;
+/// Holds the files and lines the entities of the map were loaded from
+pub mod provenance;
+/// Holds the index of the province of each pixel of the provinces image
+pub mod province_index;
+/// Holds the recoloring of provinces to structured color schemes
+pub mod recolor;
+/// Holds the search for provinces, states and strategic regions by id or name
+pub mod search;
+/// Holds the map of the categories of the states
+pub mod state_category_map;
+/// Holds the creation of support bundles for reporting issues
+pub mod support;
+/// Holds the conversion of map images into textures
+pub mod texture;
+/// Holds how the edges of the map connect to each other
+pub mod topology;
+/// Holds the checks for the consistency of a map
+pub mod validation;
+/// Holds the watching of the root directory for files changed outside of the editor
+pub mod watch;
+/// Holds the map of the weather of the strategic regions on a day of the year
+pub mod weather_map;
 
 /// The map display mode
 #[allow(clippy::exhaustive_enums)]
-#[derive(Default, Display, Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Default, Display, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum MapDisplayMode {
     #[default]
     HeightMap,
@@ -54,6 +118,29 @@ pub enum MapDisplayMode {
     Rivers,
     StrategicRegions,
     States,
+    Continents,
+    DefinitionTerrain,
+    Elevation,
+    Trees,
+    NormalMap,
+    Cities,
+    BuildingDensity,
+    Weather,
+    Manpower,
+    Political,
+    StateCategories,
+}
+
+/// A map of regions generated from the provinces, which can be exported
+#[allow(clippy::exhaustive_enums)]
+#[derive(Display, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegionMap {
+    /// The map of states
+    #[display(fmt = "state")]
+    States,
+    /// The map of strategic regions
+    #[display(fmt = "strategic region")]
+    StrategicRegions,
 }
 
 /// Errors that may occur when loading/verifying/creating a map.
@@ -106,7 +193,7 @@ pub enum MapError {
     #[error("{0}")]
     InvalidKeyFile(String),
     /// Duplicate terrain type
-    #[error("0")]
+    #[error("{0}")]
     DuplicateKeyType(String),
     /// Invalid image file
     #[error("{0}")]
@@ -120,15 +207,6 @@ pub enum MapError {
     /// Image size mismatch
     #[error("{0}")]
     ImageSizeMismatch(String),
-    /// Invalid province color
-    #[error("{0:?}")]
-    InvalidProvinceColor((Red, Green, Blue)),
-    /// Incomplete province definitions
-    #[error("{0:?}")]
-    IncompleteProvinceDefinitions(Vec<(Red, Green, Blue)>),
-    /// Invalid province terrain
-    #[error("{0:?}")]
-    InvalidProvinceTerrain(Definition),
     /// A join error
     #[error("{0}")]
     JoinError(#[from] JoinError),
@@ -169,6 +247,167 @@ pub enum MapError {
     RegionNotFoundForProvince(ProvinceId),
     #[error("Invalid Period")]
     InvalidPeriod,
+    /// A map size that is not a positive multiple of 256
+    #[error("{0}x{1} is not a positive multiple of 256")]
+    InvalidMapSize(u32, u32),
+    /// Settings for a generator that it cannot generate from
+    #[error("{0}")]
+    InvalidGeneratorSettings(String),
+    /// A support bundle that could not be created
+    #[error("{0}")]
+    InvalidSupportBundle(String),
+    /// A region map that has not been generated yet
+    #[error("The {0} map has not been generated yet")]
+    RegionMapNotGenerated(RegionMap),
+    /// A color scheme without a free color for every province
+    #[error("{0}")]
+    ColorSchemeExhausted(String),
+    /// A world config that could not be read
+    #[error("{0}")]
+    InvalidWorldConfig(#[from] toml::de::Error),
+    /// A world config that could not be written
+    #[error("{0}")]
+    WorldConfigNotWritten(#[from] toml::ser::Error),
+    /// A province that does not belong to the state it was edited in
+    #[error("Province {0} does not belong to state {1}")]
+    ProvinceNotInState(ProvinceId, StateId),
+    /// A province that does not belong to any state
+    #[error("Province {0} does not belong to a state")]
+    StateNotFoundForProvince(ProvinceId),
+    /// A state id that is not on the map
+    #[error("State {0} not found")]
+    StateNotFound(StateId),
+    /// A state without a history to edit
+    #[error("State {0} has no history")]
+    StateHistoryNotFound(StateId),
+    /// An error in a file, with the line of the file it is on where it is known
+    #[error("{}: {source}", location(.path, .line))]
+    Contextual {
+        /// The file the error is in
+        path: Option<PathBuf>,
+        /// The line of the file the error is on, counting from 1
+        line: Option<usize>,
+        /// The error itself
+        source: Box<MapError>,
+    },
+    /// A row of a CSV file that could not be read, with the line it is on
+    #[error("Line {1} of {}: {2}", .0.display())]
+    InvalidCsvRow(PathBuf, u64, String),
+    /// A project file that could not be read
+    #[error("{0}")]
+    InvalidProjectFile(toml::de::Error),
+    /// A project file that could not be written
+    #[error("{0}")]
+    ProjectFileNotWritten(toml::ser::Error),
+    /// A map with more provinces than the province index can hold
+    #[error(
+        "{0} provinces are more than the {} the province index can hold",
+        u16::MAX
+    )]
+    TooManyProvinces(usize),
+    /// A layer of the map that was neither loaded with the map nor put off
+    #[error("the {0:?} layer of the map is not loaded")]
+    LayerNotLoaded(map::Layer),
+    /// The root directory could not be watched for changes
+    #[error("{0}")]
+    WatchError(#[from] notify::Error),
+    /// The loading of the map was canceled
+    #[error("the loading of the map was canceled")]
+    LoadCanceled,
+    /// A component of the map that could not be written to or read from the cache
+    #[error("{0}")]
+    CacheError(#[from] bincode::Error),
+    /// A JSON or GeoJSON file that could not be read or written
+    #[error("{0}")]
+    JsonError(#[from] serde_json::Error),
+    /// GeoJSON without the features or geometries it should hold
+    #[error("Invalid GeoJSON: {0}")]
+    InvalidGeoJson(String),
+    /// The image of a display mode that has not been drawn yet, such as a region map
+    #[error("The {0} image has not been drawn yet")]
+    ImageNotDrawn(MapDisplayMode),
+    /// A plugin library that could not be loaded
+    #[cfg(feature = "dylib-plugins")]
+    #[error("{0}")]
+    PluginError(#[from] libloading::Error),
+}
+
+/// Describes where in a file an error is, in the `path:line` form editors understand
+#[allow(clippy::ref_option_ref)]
+fn location(path: &Option<PathBuf>, line: &Option<usize>) -> String {
+    match (path, line) {
+        (Some(path), Some(line)) => format!("{}:{line}", path.display()),
+        (Some(path), None) => path.display().to_string(),
+        (None, Some(line)) => format!("line {line}"),
+        (None, None) => "unknown location".to_owned(),
+    }
+}
+
+/// Adds where in a file an error happened to it, so that an error from a long file can be found
+pub trait ErrorContext<T> {
+    /// Adds the file the error happened in.  The line of an error that already has one is kept,
+    /// as is the file of an error that already has one.
+    /// # Errors
+    /// Returns the error with its file
+    fn in_file(self, path: &Path) -> Result<T, MapError>;
+
+    /// Adds the line, counting from 1, the error happened on.  The file is added by `in_file`.
+    /// # Errors
+    /// Returns the error with its line
+    fn at_line(self, line: usize) -> Result<T, MapError>;
+}
+
+impl<T, E: Into<MapError>> ErrorContext<T> for Result<T, E> {
+    #[inline]
+    fn in_file(self, path: &Path) -> Result<T, MapError> {
+        self.map_err(|e| match e.into() {
+            MapError::Contextual {
+                path: None,
+                line,
+                source,
+            } => MapError::Contextual {
+                path: Some(path.to_path_buf()),
+                line,
+                source,
+            },
+            e @ MapError::Contextual { .. } => e,
+            e => MapError::Contextual {
+                path: Some(path.to_path_buf()),
+                line: None,
+                source: Box::new(e),
+            },
+        })
+    }
+
+    #[inline]
+    fn at_line(self, line: usize) -> Result<T, MapError> {
+        self.map_err(|e| match e.into() {
+            e @ MapError::Contextual { .. } => e,
+            e => MapError::Contextual {
+                path: None,
+                line: Some(line),
+                source: Box::new(e),
+            },
+        })
+    }
+}
+
+/// Adds the file, and the line the parser stopped at where it reports one, to an error from
+/// parsing the text of a file
+pub(crate) fn parse_error(error: jomini::Error, path: &Path, data: &[u8]) -> MapError {
+    let line = error.offset().map(|offset| {
+        let before = data.get(..offset).unwrap_or(data);
+        before
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count()
+            .saturating_add(1)
+    });
+    MapError::Contextual {
+        path: Some(path.to_path_buf()),
+        line,
+        source: Box::new(error.into()),
+    }
 }
 
 /// Appends a directory to the front of a given path.
@@ -186,71 +425,190 @@ pub fn append_dir(p: &Path, d: &str) -> Result<PathBuf, MapError> {
     ))
 }
 
+/// How the rows of a CSV file that cannot be read are handled
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum CsvParsing {
+    /// Fail at the first row that cannot be read, with the line it is on
+    #[default]
+    Strict,
+    /// Skip the rows that cannot be read, logging a warning for each of them
+    Lenient,
+}
+
 /// Returns a vector of rows from a CSV file.
 pub trait LoadCsv
 where
     Self: Sized,
 {
-    /// Returns a vector of rows from a CSV file.
+    /// Returns a vector of rows from a CSV file, read in `encoding` as `read_text` reads it.
     /// # Errors
-    /// Returns an error if the file cannot be read.
-    fn load_csv<P: AsRef<Path>>(path: P, has_headers: bool) -> Result<Vec<Self>, MapError>;
+    /// Returns an error if the file cannot be read, or in strict parsing if one of its rows
+    /// cannot be read.
+    fn load_csv<P: AsRef<Path>>(
+        path: P,
+        has_headers: bool,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Vec<Self>, MapError>;
+
+    /// Returns a vector of rows from a CSV file, each with the line of the file it starts on,
+    /// counting from 1.
+    /// # Errors
+    /// Returns an error if the file cannot be read, or in strict parsing if one of its rows
+    /// cannot be read.
+    fn load_csv_lines<P: AsRef<Path>>(
+        path: P,
+        has_headers: bool,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Vec<(usize, Self)>, MapError>;
 }
 
 impl<T: Sized + for<'de> Deserialize<'de>> LoadCsv for T {
     #[inline]
-    fn load_csv<P: AsRef<Path>>(path: P, has_headers: bool) -> Result<Vec<Self>, MapError> {
-        let data = fs::read_to_string(path)?;
+    fn load_csv<P: AsRef<Path>>(
+        path: P,
+        has_headers: bool,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Vec<Self>, MapError> {
+        let rows = Self::load_csv_lines(path, has_headers, parsing, encoding)?;
+        Ok(rows.into_iter().map(|(_, row)| row).collect())
+    }
+
+    #[inline]
+    fn load_csv_lines<P: AsRef<Path>>(
+        path: P,
+        has_headers: bool,
+        parsing: CsvParsing,
+        encoding: Option<TextEncoding>,
+    ) -> Result<Vec<(usize, Self)>, MapError> {
+        let path = path.as_ref();
+        let data = read_text(path, encoding).in_file(path)?;
+        // The game does not quote fields, so a stray quote must not swallow the rows after it
         let mut rdr = csv::ReaderBuilder::new()
             .has_headers(has_headers)
             .delimiter(b';')
+            .quoting(false)
             .from_reader(data.as_bytes());
-        let rows = rdr.deserialize().flatten().collect();
+        let headers = if has_headers {
+            rdr.headers().ok().cloned()
+        } else {
+            None
+        };
+        let mut rows = Vec::new();
+        for record in rdr.records() {
+            let row = record.and_then(|record| {
+                let line = record.position().map_or(0, csv::Position::line);
+                Ok((line, record.deserialize(headers.as_ref())?))
+            });
+            match (row, parsing) {
+                (Ok((line, row)), _) => rows.push((usize::try_from(line).unwrap_or_default(), row)),
+                (Err(e), CsvParsing::Strict) => {
+                    return Err(MapError::InvalidCsvRow(
+                        path.to_path_buf(),
+                        csv_error_line(&e),
+                        e.to_string(),
+                    ))
+                }
+                (Err(e), CsvParsing::Lenient) => warn!(
+                    "Skipping line {} of {}: {e}",
+                    csv_error_line(&e),
+                    path.display()
+                ),
+            }
+        }
         Ok(rows)
     }
 }
 
+/// The line of a CSV file an error is on, or 0 if the error has no position
+fn csv_error_line(error: &csv::Error) -> u64 {
+    error.position().map_or(0, csv::Position::line)
+}
+
 /// Returns a set of all the keys in the first object of the file.
 pub trait LoadKeys
 where
     Self: Sized,
 {
-    /// Returns a set of all the keys in the given object of the file.
+    /// Returns a set of all the keys in the given object of the file, or of every `.txt` file of
+    /// a directory as `text_files` lists them.  Files of a directory without the object are
+    /// skipped, and a key defined again in a later file is the same key.  The files are read in
+    /// `encoding` as `read_text` reads them.
     /// # Errors
-    /// If the file is not found or if the file is empty.
-    fn load_keys(path: &Path, object_name: &str) -> Result<HashSet<Self>, MapError>;
+    /// If the file is not found, if no file has the object, or if a file defines a key twice.
+    fn load_keys(
+        path: &Path,
+        object_name: &str,
+        encoding: Option<TextEncoding>,
+    ) -> Result<HashSet<Self>, MapError>;
 }
 
 impl<T: Sized + From<String> + Eq + Hash> LoadKeys for T {
     #[inline]
-    fn load_keys(path: &Path, object_name: &str) -> Result<HashSet<T>, MapError> {
-        let data = fs::read_to_string(&path)?;
-        let tape = TextTape::from_slice(data.as_bytes())?;
-        let reader = tape.windows1252_reader();
-        let fields = reader
-            .fields()
-            .filter(|f| {
-                let (raw_key, _op, _value) = f;
-                raw_key.read_str() == object_name
-            })
-            .collect::<Vec<_>>();
-        let (_key, _op, value) = fields
-            .get(0)
-            .ok_or_else(|| MapError::InvalidKeyFile(path.to_string_lossy().to_string()))?;
-        let types_container = value.read_object()?;
-        let types_objects = types_container.fields().collect::<Vec<_>>();
+    fn load_keys(
+        path: &Path,
+        object_name: &str,
+        encoding: Option<TextEncoding>,
+    ) -> Result<HashSet<T>, MapError> {
         let mut types = HashSet::new();
-        for (key, _op, _value) in types_objects {
-            let terrain_type = key.read_string().into();
-            if types.contains(&terrain_type) {
-                return Err(MapError::DuplicateKeyType(key.read_string()));
+        let mut found = false;
+        for file in text_files(path)? {
+            let data = read_text(&file, encoding).in_file(&file)?;
+            let tape = TextTape::from_slice(data.as_bytes())
+                .map_err(|e| parse_error(e, &file, data.as_bytes()))?;
+            // The text is decoded from the encoding of the file, so it is read as UTF-8
+            let reader = tape.utf8_reader();
+            let value = match reader
+                .fields()
+                .find(|(raw_key, _op, _value)| raw_key.read_str() == object_name)
+            {
+                Some((_key, _op, value)) => value,
+                None => continue,
+            };
+            found = true;
+            let types_container = value.read_object().in_file(&file)?;
+            let mut file_types = HashSet::new();
+            for (key, _op, _value) in types_container.fields() {
+                let terrain_type = key.read_string().into();
+                if file_types.contains(&terrain_type) {
+                    return Err(MapError::DuplicateKeyType(key.read_string())).in_file(&file);
+                }
+                file_types.insert(terrain_type);
             }
-            types.insert(terrain_type);
+            types.extend(file_types);
+        }
+        if !found {
+            return Err(MapError::InvalidKeyFile(path.to_string_lossy().to_string()));
         }
         Ok(types)
     }
 }
 
+/// The files definitions are read from at a path.  A directory is read the way the game reads
+/// it, every `.txt` file in order of name so that later files override the definitions of
+/// earlier ones, while a file is read on its own.
+/// # Errors
+/// If the directory cannot be read
+#[inline]
+pub fn text_files(path: &Path) -> Result<Vec<PathBuf>, MapError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).in_file(path)? {
+        let file = entry?.path();
+        if file.extension().map_or(false, |e| e == "txt") {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
 /// A trait for when a structure can easily be converted from a string directly via `jomini`'s
 /// `TextDeserializer`..
 pub trait LoadObject
@@ -258,25 +616,25 @@ where
     Self: Sized,
 {
     /// Deserializes a string into a structure.  Only works if the string requires no modification
-    /// prior to deserialization.
+    /// prior to deserialization.  The file is read in `encoding` as `read_text` reads it.
     /// # Errors
     /// Returns an error if the file cannot be read.
-    fn load_object(path: &Path) -> Result<Self, MapError>;
+    fn load_object(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError>;
 }
 
 impl<T: Sized + for<'de> Deserialize<'de>> LoadObject for T {
     #[inline]
-    fn load_object(path: &Path) -> Result<Self, MapError> {
-        let data = fs::read_to_string(path)?;
-        let object_result = TextDeserializer::from_windows1252_slice(data.as_bytes());
-        if object_result.is_err() {
+    fn load_object(path: &Path, encoding: Option<TextEncoding>) -> Result<Self, MapError> {
+        let data = read_text(path, encoding).in_file(path)?;
+        TextDeserializer::from_utf8_slice(data.as_bytes()).map_err(|e| {
             error!("Error deserializing from {:?}", path.display());
-        }
-        Ok(object_result?)
+            parse_error(e, path, data.as_bytes())
+        })
     }
 }
 
-/// Loads a map where the keys and values are deserializable from strings.
+/// Loads a map where the keys and values are deserializable from strings, reading the file in
+/// `encoding` as `read_text` reads it.
 /// # Errors
 /// Returns an error if the file cannot be read.
 #[inline]
@@ -288,34 +646,65 @@ pub fn load_map<
     E2: Display,
 >(
     path: P,
+    encoding: Option<TextEncoding>,
 ) -> Result<HashMap<K, Vec<V>>, MapError> {
-    let data = fs::read_to_string(path)?;
+    let path = path.as_ref();
+    let data = read_text(path, encoding).in_file(path)?;
     let mut map = HashMap::new();
+    for (index, line) in data.lines().enumerate() {
+        load_map_line(line, &mut map)
+            .at_line(index.saturating_add(1))
+            .in_file(path)?;
+    }
+    Ok(map)
+}
 
-    for line in data.lines() {
-        let tape = TextTape::from_slice(line.as_bytes())?;
-        let reader = tape.windows1252_reader();
-        for (key, _op, value) in reader.fields() {
-            let id = match key.read_str().parse::<K>() {
-                Ok(i) => i,
-                Err(e) => return Err(MapError::InvalidKey(e.to_string())),
-            };
-            let values = {
-                let array = value.read_array()?;
-                let mut ids = Vec::new();
-                for val in array.values() {
-                    let v_string = val.read_string()?;
-                    let v = match v_string.parse::<V>() {
-                        Ok(v) => v,
-                        Err(e) => return Err(MapError::InvalidValue(e.to_string())),
-                    };
-                    ids.push(v);
-                }
-                ids
-            };
-            map.insert(id, values);
-        }
+/// Adds the keys and values of a line of a file read by `load_map` to the map
+fn load_map_line<K: Eq + Hash + FromStr<Err = E>, E: Display, V: FromStr<Err = E2>, E2: Display>(
+    line: &str,
+    map: &mut HashMap<K, Vec<V>>,
+) -> Result<(), MapError> {
+    let tape = TextTape::from_slice(line.as_bytes())?;
+    let reader = tape.utf8_reader();
+    for (key, _op, value) in reader.fields() {
+        let id = match key.read_str().parse::<K>() {
+            Ok(i) => i,
+            Err(e) => return Err(MapError::InvalidKey(e.to_string())),
+        };
+        let values = {
+            let array = value.read_array()?;
+            let mut ids = Vec::new();
+            for val in array.values() {
+                let v_string = val.read_string()?;
+                let v = match v_string.parse::<V>() {
+                    Ok(v) => v,
+                    Err(e) => return Err(MapError::InvalidValue(e.to_string())),
+                };
+                ids.push(v);
+            }
+            ids
+        };
+        map.insert(id, values);
     }
+    Ok(())
+}
 
-    Ok(map)
+/// Saves a map in the format read by `load_map`, with one key per line in order of key, such as
+/// `12={ 345 }`.
+/// # Errors
+/// Returns an error if the file cannot be written.
+#[inline]
+pub fn save_map<P: AsRef<Path>, K: Ord + Display, V: Display>(
+    path: P,
+    map: &HashMap<K, Vec<V>>,
+) -> Result<(), MapError> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_unstable_by(|(a, _a_values), (b, _b_values)| a.cmp(b));
+    let mut data = String::new();
+    for (key, values) in entries {
+        let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let _result = writeln!(data, "{key}={{ {} }}", values.join(" "));
+    }
+    fs::write(path, data)?;
+    Ok(())
 }
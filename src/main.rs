@@ -25,17 +25,25 @@
 #![allow(clippy::missing_docs_in_private_items)]
 #![allow(clippy::expect_used)]
 
+mod cli;
 mod ui;
 
+use crate::ui::active_tool::ActiveTool;
+use crate::ui::busy::Busy;
 use crate::ui::central_panel_renderer::CentralPanelRenderer;
 use crate::ui::control_panel_renderer::ControlPanelRenderer;
 use crate::ui::map_loader::MapLoader;
 use crate::ui::map_mode::MapMode;
 use crate::ui::map_textures::MapTextures;
+use crate::ui::metrics::{Metrics, RecordTiming};
+use crate::ui::plugins::Plugins;
 use crate::ui::right_panel_renderer::RightPanelRenderer;
 use crate::ui::root_path::RootPath;
+use crate::ui::search_renderer::SearchRenderer;
 use crate::ui::selection::Selection;
+use crate::ui::session::Session;
 use crate::ui::top_menu_renderer::TopMenuRenderer;
+use crate::ui::validation_renderer::ValidationRenderer;
 use crate::ui::viewport::Viewport;
 use crate::ui::{root_path::SetRootPath, UiRenderer};
 use actix::{Actor, System};
@@ -43,8 +51,11 @@ use eframe::App;
 use egui::{Context, Vec2};
 use indicatif::InMemoryTerm;
 use log::{debug, error, info, trace};
+use std::process::ExitCode;
+use std::time::Instant;
 use tokio::runtime::Runtime;
 use tokio::task::JoinHandle;
+use world_gen::plugin::PluginRegistry;
 use world_gen::MapError;
 
 struct WorldGenApp {
@@ -83,20 +94,43 @@ impl WorldGenApp {
             let system = System::new();
 
             system.block_on(async {
+                trace!("Starting metrics");
+                let metrics = Metrics::default().start();
+                trace!("Starting busy state");
+                let busy = Busy::default().start();
                 trace!("Starting root path");
-                let root_path = RootPath::default().start();
-                let top_menu_renderer = TopMenuRenderer::new(root_path.clone());
+                let root_path = RootPath::new(busy.clone()).start();
                 trace!("Starting map textures");
-                let map_textures = MapTextures::default().start();
+                let map_textures = MapTextures::new(metrics.clone()).start();
                 trace!("Starting map loader");
-                let map_loader = MapLoader::default().start();
+                let map_loader = MapLoader::new(busy.clone()).start();
                 trace!("Starting map mode");
                 let map_mode = MapMode::default().start();
+                trace!("Starting viewport");
+                let viewport = Viewport::default().start();
+                trace!("Starting plugins");
+                let plugins = Plugins::new(plugin_registry()).start();
+                let top_menu_renderer = TopMenuRenderer::new(
+                    root_path.clone(),
+                    map_loader.clone(),
+                    map_mode.clone(),
+                    viewport.clone(),
+                    plugins.clone(),
+                    busy.clone(),
+                    metrics.clone(),
+                    terminal.clone(),
+                );
+                trace!("Starting active tool");
+                let active_tool = ActiveTool::default().start();
                 let control_panel_renderer = ControlPanelRenderer::new(
-                    root_path,
+                    root_path.clone(),
                     map_loader.clone(),
                     map_mode.clone(),
                     map_textures.clone(),
+                    active_tool.clone(),
+                    plugins.clone(),
+                    viewport.clone(),
+                    busy.clone(),
                     terminal.clone(),
                 );
                 trace!("Starting selection");
@@ -105,16 +139,38 @@ impl WorldGenApp {
                     map_mode.clone(),
                     selection.clone(),
                     map_loader.clone(),
-                    terminal,
+                    root_path,
+                    terminal.clone(),
                 );
-                trace!("Starting viewport");
-                let viewport = Viewport::default().start();
                 let central_panel_renderer = CentralPanelRenderer::new(
-                    map_loader,
+                    map_loader.clone(),
+                    map_mode.clone(),
+                    map_textures.clone(),
+                    selection.clone(),
+                    viewport.clone(),
+                    active_tool,
+                    plugins.clone(),
+                    metrics.clone(),
+                );
+                let validation_renderer = ValidationRenderer::new(
+                    map_loader.clone(),
+                    selection.clone(),
+                    viewport.clone(),
+                    busy.clone(),
+                );
+                let search_renderer = SearchRenderer::new(
+                    map_loader.clone(),
                     map_mode.clone(),
+                    selection.clone(),
+                    viewport.clone(),
+                );
+                let session = Session::new(
+                    map_loader,
                     map_textures,
                     selection,
-                    viewport.clone(),
+                    plugins,
+                    busy.clone(),
+                    terminal,
                 );
 
                 let ui_renderer = UiRenderer::new(
@@ -122,8 +178,13 @@ impl WorldGenApp {
                     control_panel_renderer,
                     right_panel_renderer,
                     central_panel_renderer,
+                    validation_renderer,
+                    search_renderer,
                     map_mode,
                     viewport,
+                    busy,
+                    metrics,
+                    session,
                 );
                 trace!("Sending Ui Renderer");
                 tx.send(ui_renderer).unwrap();
@@ -149,39 +210,32 @@ impl WorldGenApp {
         if let Some(ui_renderer) = &mut self.ui_renderer {
             if let Some(rt) = &self.runtime {
                 trace!("Render Loop start");
+                let start = Instant::now();
                 trace!("Block on TopMenu");
                 rt.block_on(ui_renderer.top_menu_renderer.render_top_menu_bar(ctx))?;
+                trace!("Block on SearchBar");
+                rt.block_on(ui_renderer.search_renderer.render_search_bar(ctx))?;
                 trace!("Block on ControlPanel");
                 rt.block_on(ui_renderer.control_panel_renderer.render_control_panel(ctx))?;
                 trace!("Block on RightPanel");
                 rt.block_on(ui_renderer.right_panel_renderer.render_right_panel(ctx))?;
+                trace!("Block on ValidationPanel");
+                rt.block_on(ui_renderer.validation_renderer.render_validation_panel(ctx))?;
                 trace!("Block on CentralPanel");
                 rt.block_on(ui_renderer.central_panel_renderer.render_central_panel(ctx))?;
+                trace!("Block on BusyOverlay");
+                rt.block_on(ui_renderer.render_busy_overlay(ctx))?;
+                trace!("Block on SwitchRoot");
+                rt.block_on(ui_renderer.switch_root_if_changed())?;
+                ui_renderer
+                    .metrics
+                    .do_send(RecordTiming::new("frame", start.elapsed()));
                 trace!("Render Loop End");
             }
-            if ui_renderer.top_menu_renderer.root_path_changed {
-                let root_path = ui_renderer.top_menu_renderer.new_root_path.clone();
-                self.clear_map()?;
-                if let Some(mut ui_renderer) = self.ui_renderer.as_mut() {
-                    ui_renderer.top_menu_renderer.new_root_path = root_path;
-                }
-            }
         }
 
         Ok(())
     }
-
-    fn clear_map(&mut self) -> Result<(), MapError> {
-        self.terminal = InMemoryTerm::new(16, 240);
-        self.ui_renderer = None;
-        if let Some(s) = &self.system {
-            s.stop();
-        }
-        self.runtime = None;
-        self.system_thread = None;
-        self.initialize_renderer()?;
-        Ok(())
-    }
 }
 
 impl App for WorldGenApp {
@@ -204,6 +258,27 @@ impl App for WorldGenApp {
     }
 }
 
+/// The built in layers and tools, along with those of the plugin libraries in the `plugins`
+/// folder next to the editor
+fn plugin_registry() -> PluginRegistry {
+    #[allow(unused_mut)]
+    let mut registry = PluginRegistry::with_builtins();
+    #[cfg(feature = "dylib-plugins")]
+    {
+        let dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|parent| parent.join("plugins")));
+        if let Some(dir) = dir {
+            // SAFETY: the user put the libraries in the plugins folder to have the editor load them
+            match unsafe { registry.load_dir(&dir) } {
+                Ok(loaded) => debug!("Loaded {loaded} plugins from {}", dir.display()),
+                Err(e) => error!("Failed to read the plugins in {}: {e}", dir.display()),
+            }
+        }
+    }
+    registry
+}
+
 /// Truncates a floating point number to the specified number of decimal places.
 #[must_use]
 #[inline]
@@ -217,8 +292,17 @@ pub fn truncate_to_decimal_places(num: f32, places: i32) -> f32 {
     (num * ten).floor() / ten
 }
 
-fn main() {
+#[allow(clippy::print_stderr)]
+fn main() -> ExitCode {
     env_logger::init();
+    match cli::parse_command(std::env::args().skip(1)) {
+        Ok(Some(command)) => return cli::run(command),
+        Ok(None) => {}
+        Err(usage) => {
+            eprintln!("{usage}");
+            return ExitCode::from(2);
+        }
+    }
     let options = eframe::NativeOptions {
         initial_window_size: Some(Vec2::new(800.0, 600.0)),
         ..Default::default()
@@ -231,4 +315,5 @@ fn main() {
         options,
         Box::new(|_cc| Box::new(app)),
     );
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,149 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::legend::{Legend, LegendEntry};
+use crate::province_index::ProvinceIndex;
+use image::{Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// The color of the provinces of states without manpower, and of provinces without a state
+const NO_MANPOWER: Rgb<u8> = Rgb([48, 48, 48]);
+
+/// The colors of the choropleth from the least manpower to the most, spread evenly between them
+const MANPOWER_RAMP: [Rgb<u8>; 4] = [
+    Rgb([255, 244, 196]),
+    Rgb([248, 160, 64]),
+    Rgb([200, 40, 40]),
+    Rgb([80, 16, 96]),
+];
+
+/// Draws each state in the color of its manpower on a log scale, along with a legend of the least
+/// and most manpower of a state.  As the manpower of a state on most maps ranges from a few
+/// thousand to several million, a linear scale would draw all but the largest states in the
+/// palest color.  States without manpower are dark grey, and pixels without a province are black.
+#[inline]
+#[must_use]
+pub fn render_manpower(
+    states: &HashMap<StateId, State>,
+    province_index: &ProvinceIndex,
+) -> (RgbImage, Legend) {
+    let manpower = states
+        .values()
+        .filter_map(|state| Some((state, state.current_manpower()?)))
+        .collect::<Vec<_>>();
+    let least = manpower.iter().min_by_key(|(state, m)| (*m, state.id));
+    let most = manpower.iter().max_by_key(|(state, m)| (*m, state.id));
+    let (low, high) = match (least, most) {
+        (Some((_, least)), Some((_, most))) => (log_manpower(*least), log_manpower(*most)),
+        _ => (0.0, 0.0),
+    };
+    let colors = manpower
+        .iter()
+        .flat_map(|(state, m)| {
+            let fraction = if high > low {
+                (log_manpower(*m) - low) / (high - low)
+            } else {
+                1.0
+            };
+            let color = manpower_color(fraction);
+            state
+                .provinces
+                .iter()
+                .map(move |province| (*province, color))
+        })
+        .collect::<HashMap<_, _>>();
+    let map = province_index.paint(|id| Some(colors.get(&id).copied().unwrap_or(NO_MANPOWER)));
+    let legend = [(0, "Least", least, 0.0), (1, "Most", most, 1.0)]
+        .into_iter()
+        .filter_map(|(id, label, extreme, fraction)| {
+            let (state, m) = extreme?;
+            let name = format!("{label}: {m} in state {}", state.id);
+            Some(LegendEntry::new(id, name, manpower_color(fraction)))
+        })
+        .collect();
+    (map, Legend::new(legend))
+}
+
+/// The manpower of a state on the log scale of the choropleth, counting no manpower as one so
+/// that empty states are drawn in the palest color
+fn log_manpower(manpower: Manpower) -> f64 {
+    f64::from(manpower.0.max(1)).ln()
+}
+
+/// The color of the choropleth at a fraction from 0.0 to 1.0 of the way from the least manpower
+/// to the most
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+fn manpower_color(fraction: f64) -> Rgb<u8> {
+    let position = fraction.clamp(0.0, 1.0) * (MANPOWER_RAMP.len().saturating_sub(1)) as f64;
+    let lower = position.floor() as usize;
+    let weight = position - position.floor();
+    match (
+        MANPOWER_RAMP.get(lower),
+        MANPOWER_RAMP.get(lower.saturating_add(1)),
+    ) {
+        (Some(from), Some(to)) => {
+            let mut color = *from;
+            for (channel, (a, b)) in color.0.iter_mut().zip(from.0.iter().zip(to.0)) {
+                let value = (f64::from(b) - f64::from(*a)).mul_add(weight, f64::from(*a));
+                *channel = value.round().clamp(0.0, 255.0) as u8;
+            }
+            color
+        }
+        (Some(color), None) => *color,
+        _ => NO_MANPOWER,
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn state(id: i32, province: i32, manpower: &[u32]) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: manpower.iter().map(|m| Manpower(*m)).collect(),
+            state_category: Vec::new(),
+            history: None,
+            provinces: HashSet::from([ProvinceId(province)]),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    #[test]
+    fn it_colors_the_states_by_their_manpower_on_a_log_scale() {
+        // Provinces 1 to 4 are the columns of the map
+        let provinces = RgbImage::from_fn(4, 1, |x, _| Rgb([(x + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=4)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let states = HashMap::from([
+            (StateId(1), state(1, 1, &[100])),
+            (StateId(2), state(2, 2, &[10_000])),
+            (StateId(3), state(3, 3, &[5, 1_000_000])),
+            (StateId(4), state(4, 4, &[])),
+        ]);
+
+        let (map, legend) = render_manpower(&states, &index);
+        assert_eq!(*map.get_pixel(0, 0), MANPOWER_RAMP[0]);
+        // 10,000 is halfway from 100 to 1,000,000 on a log scale
+        assert_eq!(*map.get_pixel(1, 0), manpower_color(0.5));
+        assert_eq!(*map.get_pixel(2, 0), MANPOWER_RAMP[3]);
+        assert_eq!(*map.get_pixel(3, 0), NO_MANPOWER);
+        assert_eq!(legend.entries.len(), 2);
+        assert_eq!(legend.entries[0].name, "Least: 100 in state 1");
+        assert_eq!(legend.entries[1].name, "Most: 1000000 in state 3");
+    }
+}
@@ -1,59 +1,207 @@
+use crate::brush::{HeightBrush, RiverBrush, TerrainBrush};
+use crate::building_density::BuildingDensity;
+use crate::cache::{cached, ComponentCache};
+use crate::components::ambient_object::AmbientObjects;
+use crate::components::country::CountryColors;
+use crate::components::country_history::CountryHistories;
+use crate::components::localisation::{Localisation, DEFAULT_LANGUAGE};
 use crate::components::prelude::*;
-use crate::components::state::{State, States};
-use crate::{LoadObject, MapDisplayMode, MapError};
-use actix::{Actor, AsyncContext, Context, Handler, Message};
+use crate::components::state::{State, StateEdit, States};
+use crate::components::state_category::StateCategories;
+use crate::components::supply_area::SupplyAreas;
+use crate::components::weather::WeatherStates;
+use crate::encoding::TextEncoding;
+use crate::format::SourceFile;
+use crate::generation::heightmap::hypsometric_tint;
+use crate::generation::lakes::LakeClassifier;
+use crate::generation::terrain::{definition_terrain_map, sync_terrain};
+use crate::geojson::{provinces_geojson, states_geojson, strategic_regions_geojson, write_geojson};
+use crate::highlight::highlight_provinces;
+use crate::history::{Edit, EditHistory, Editable, ImageDelta};
+use crate::legend::{legend_path, Legend, LegendEntry, LegendFormat, RegionColors};
+use crate::manpower_map::render_manpower;
+use crate::plugin::{MapLayer, MapTool};
+use crate::political_map::render_political;
+use crate::poster::{composite, save_poster, state_labels, PosterFormat, PosterOptions};
+use crate::preview::EditPreview;
+use crate::progress::{CancelToken, LoadProgress, LoadSteps};
+use crate::project::{Project, ValidationSettings};
+use crate::provenance::Provenance;
+use crate::province_index::ProvinceIndex;
+use crate::recolor::{recolor_provinces, ColorScheme};
+use crate::search::{search, SearchResult, SearchTarget};
+use crate::state_category_map::render_state_categories;
+use crate::support::{diagnostics_report, map_manifest, SupportBundle};
+use crate::texture::{texture_patch, DirtyRegions, TexturePatch};
+use crate::topology::Wrapping;
+use crate::validation::adjacencies::verify_adjacencies;
+use crate::validation::buildings::verify_buildings;
+use crate::validation::coastal::verify_coastal_flags;
+use crate::validation::countries::verify_countries;
+use crate::validation::fixes::AutoFix;
+use crate::validation::province_bounds::verify_province_bounds;
+use crate::validation::province_contiguity::verify_province_contiguity;
+use crate::validation::province_heights::{
+    verify_province_heights, ProvinceHeightIssue, SEA_LEVEL,
+};
+use crate::validation::province_sizes::verify_province_sizes;
+use crate::validation::province_types::verify_province_types;
+use crate::validation::railways::{province_neighbors, verify_railways};
+use crate::validation::report::{Finding, FindingKind, Severity, ValidationReport};
+use crate::validation::rivers::{reverify_rivers_in, verify_rivers, RiverIssue};
+use crate::validation::states::{
+    verify_state_categories, verify_state_files, verify_states, verify_strategic_region_coverage,
+};
+use crate::validation::straits::{StraitCandidate, StraitFinder};
+use crate::validation::supply::{verify_supply, SupplyGraph};
+use crate::validation::weather_periods::verify_weather_periods;
+use crate::validation::weather_positions::verify_weather_positions;
+use crate::validation::x_crossings::{find_x_crossings, fix_x_crossings};
+use crate::watch::{FileWatcher, WatchedFile};
+use crate::weather_map::WeatherView;
+use crate::{
+    text_files, CsvParsing, ErrorContext, LoadObject, MapDisplayMode, MapError, RegionMap,
+};
+use actix::{Actor, AsyncContext, Context, Handler, Message, MessageResult};
 use egui::Pos2;
-use image::{open, DynamicImage, Pixel, Rgb, RgbImage};
-use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
+use image::math::Rect;
+use image::{open, DynamicImage, Pixel, Rgb, RgbImage, RgbaImage};
+use indicatif::{MultiProgress, ProgressDrawTarget, ProgressStyle, TermLike};
 use log::{debug, error, info, trace, warn};
-use rand::{thread_rng, Rng};
-use std::collections::{HashMap, HashSet};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
 use std::hash::Hash;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::task::JoinHandle;
 use tokio::try_join;
 
-/// All the components needed to represent a map.
+/// Every display mode, in the order their images are loaded
+const ALL_MAP_MODES: [MapDisplayMode; 17] = [
+    MapDisplayMode::HeightMap,
+    MapDisplayMode::Terrain,
+    MapDisplayMode::Provinces,
+    MapDisplayMode::Rivers,
+    MapDisplayMode::StrategicRegions,
+    MapDisplayMode::States,
+    MapDisplayMode::Continents,
+    MapDisplayMode::DefinitionTerrain,
+    MapDisplayMode::Elevation,
+    MapDisplayMode::Trees,
+    MapDisplayMode::NormalMap,
+    MapDisplayMode::Cities,
+    MapDisplayMode::BuildingDensity,
+    MapDisplayMode::Weather,
+    MapDisplayMode::Manpower,
+    MapDisplayMode::Political,
+    MapDisplayMode::StateCategories,
+];
+
+/// All the components needed to represent a map.  The images shown in the editor are shared with
+/// the textures loading them, and copied only when they are edited while still shared.
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct Map {
     /// The provinces.bmp image.
-    pub provinces: RgbImage,
+    pub provinces: Arc<RgbImage>,
     /// The terrain.bmp image
-    pub terrain: RgbImage,
+    pub terrain: Arc<RgbImage>,
     /// The rivers.bmp image
-    pub rivers: RgbImage,
+    pub rivers: Arc<RgbImage>,
     /// The heightmap.bmp image
-    pub heightmap: RgbImage,
-    /// The trees.bmp image
-    pub trees: RgbImage,
-    /// The world_normal.bmp image
+    pub heightmap: Arc<RgbImage>,
+    /// The trees.bmp image, unless its loading was put off
+    pub trees: Option<Arc<RgbImage>>,
+    /// The world_normal.bmp image, unless its loading was put off
     /// Remember to invert the Y axis.
-    pub normal_map: RgbImage,
-    /// The cities.bmp image
-    pub cities_map: RgbImage,
+    pub normal_map: Option<Arc<RgbImage>>,
+    /// The cities.bmp image, unless its loading was put off
+    pub cities_map: Option<Arc<RgbImage>>,
+    /// The root directory and map file of the layers whose loading was put off, loaded by `layer`
+    /// when first needed
+    deferred_layers: HashMap<Layer, (PathBuf, PathBuf)>,
+    /// The root directory of the components whose loading was put off, which are empty until
+    /// `load_component` loads them
+    deferred_components: HashMap<MapComponent, PathBuf>,
+    /// How the rows of the CSV files that cannot be read were handled when the map was loaded,
+    /// which the components loaded or reloaded later are read with too
+    csv_parsing: CsvParsing,
+    /// The encoding the text files were forced to be read in when the map was loaded, if any,
+    /// which the components loaded or reloaded later are read in too
+    encoding: Option<TextEncoding>,
     /// The map of strategic regions
-    pub strategic_region_map: Option<RgbImage>,
+    pub strategic_region_map: Option<Arc<RgbImage>>,
+    /// The colors of the strategic regions on the map of strategic regions
+    pub strategic_region_legend: Legend,
     /// The map of states
-    pub state_map: Option<RgbImage>,
+    pub state_map: Option<Arc<RgbImage>>,
+    /// The colors of the states on the map of states
+    pub state_legend: Legend,
+    /// The map of the continents of the provinces
+    pub continent_map: Option<Arc<RgbImage>>,
+    /// The colors of the continents on the map of continents, with the provinces without a
+    /// continent as continent 0
+    pub continent_legend: Legend,
+    /// The map of the terrain of the provinces in the definitions, drawn in the colors of
+    /// terrain.bmp
+    pub definition_terrain_map: Option<Arc<RgbImage>>,
+    /// The height below which the elevation map shades the heightmap as sea
+    pub elevation_sea_level: u8,
+    /// Which buildings the heat map of the buildings counts and what it counts them by
+    pub building_density: BuildingDensity,
+    /// The day and measure the map of the weather of the strategic regions is drawn for
+    pub weather_view: WeatherView,
+    /// The colors of the map of the weather as it was last drawn
+    pub weather_legend: Legend,
+    /// The least and most manpower of a state as the map of manpower was last drawn
+    pub manpower_legend: Legend,
+    /// The countries owning the states as the political map was last drawn
+    pub political_legend: Legend,
+    /// The categories of the states as the map of state categories was last drawn
+    pub state_category_legend: Legend,
     /// The province definitions
     pub definitions: Definitions,
+    /// The path of the definitions file, relative to the root directory
+    pub definitions_path: PathBuf,
+    /// The terrain types of the `common/terrain` files that are water
+    pub water_terrain: HashSet<Terrain>,
+    /// The colors of the countries of `common/countries/colors.txt`, empty if it is missing
+    pub country_colors: CountryColors,
+    /// The histories of the countries of `history/countries`, empty if it is missing
+    pub country_histories: CountryHistories,
+    /// The state categories of `common/state_category`, empty if it is missing
+    pub state_categories: StateCategories,
+    /// The English localisation of `localisation`, holding the names of the states and victory
+    /// points, empty if it is missing
+    pub localisation: Localisation,
+    /// The weather states of `common/weather.txt`, those of the base game if it is missing
+    pub weather_states: WeatherStates,
     /// The continent definitions
     pub continents: Continents,
     /// The adjacency rules definitions
     pub adjacency_rules: AdjacencyRules,
     /// The adjacencies between provinces
     pub adjacencies: Adjacencies,
+    /// The path of the adjacencies file, relative to the root directory
+    pub adjacencies_path: PathBuf,
     /// The seasons definitions
     pub seasons: Seasons,
     /// The tree indices
     pub tree_indices: Vec<usize>,
     /// The strategic regions definitions
     pub strategic_regions: StrategicRegions,
+    /// The version of the game the map is made for, which decides how supply is given
+    pub version: MapVersion,
     /// The supply nodes on the map
     pub supply_nodes: SupplyNodes,
     /// The railways on the map
     pub railways: Railways,
+    /// The supply areas of `map/supplyareas`, empty unless the map is from before 1.11
+    pub supply_areas: SupplyAreas,
     /// The buildings on the map
     pub buildings: Buildings,
     /// The graphical information for cities on the map
@@ -64,26 +212,237 @@ pub struct Map {
     pub rocket_sites: RocketSites,
     /// The unit stacks on the map
     pub unit_stacks: UnitStacks,
+    /// The objects of `map/ambient_object.txt`, such as the frame around the map, empty if it is
+    /// missing
+    pub ambient_objects: AmbientObjects,
     /// The weather positions on the map
     pub weather_positions: WeatherPositions,
     /// The airports definitions
     pub airports: Airports,
     /// The map of colors to province ids
     pub provinces_by_color: HashMap<Rgb<u8>, ProvinceId>,
+    /// The province of each pixel of the provinces image
+    pub province_index: ProvinceIndex,
     /// The map of province ids to strategic regions
     pub strategic_regions_by_province: HashMap<ProvinceId, StrategicRegionId>,
     /// The map of state ids to States
     pub states: HashMap<StateId, State>,
     /// The map of province ids to states
     pub states_by_province: HashMap<ProvinceId, StateId>,
+    /// The files the states were loaded from
+    pub state_sources: HashMap<StateId, SourceFile>,
+    /// The state files that were not loaded because an earlier file defined the same state id
+    pub duplicate_state_files: Vec<(StateId, String)>,
+    /// The states edited since they were last saved
+    pub dirty_states: HashSet<StateId>,
+    /// The issues found on the rivers image since it was last verified
+    pub river_issues: Vec<RiverIssue>,
+    /// The straits found since they were last searched for that have not been reviewed yet
+    pub strait_candidates: Vec<StraitCandidate>,
+    /// The images as they were before the edits that have not been applied yet
+    pub edit_preview: EditPreview,
+    /// The areas of the images painted since their textures were last patched
+    pub dirty_regions: DirtyRegions,
+    /// How the edges of the map connect to each other
+    pub wrapping: Wrapping,
+    /// The settings of the checks run by `validate`, from the project file of the root directory
+    pub validation_settings: ValidationSettings,
+    /// How the regions of the region maps are colored, from the project file of the root
+    /// directory
+    pub region_colors: RegionColors,
+    /// The edits that can be undone and redone
+    pub history: EditHistory,
     strategic_region_map_handle: Option<JoinHandle<()>>,
     state_map_handle: Option<JoinHandle<()>>,
+    continent_map_handle: Option<JoinHandle<()>>,
+    definition_terrain_map_handle: Option<JoinHandle<()>>,
+    /// The watcher reloading the files of the root directory changed outside of the editor
+    watcher: Option<FileWatcher>,
+}
+
+/// The components parsed from the files of a map, as `Map::export_json` writes them
+#[derive(Debug, Serialize)]
+struct MapExport<'map> {
+    definitions: &'map Definitions,
+    continents: &'map Continents,
+    adjacency_rules: &'map AdjacencyRules,
+    adjacencies: &'map Adjacencies,
+    seasons: &'map Seasons,
+    strategic_regions: &'map StrategicRegions,
+    states: &'map HashMap<StateId, State>,
+    supply_nodes: &'map SupplyNodes,
+    railways: &'map Railways,
+    supply_areas: &'map SupplyAreas,
+    buildings: &'map Buildings,
+    cities: &'map Cities,
+    rocket_sites: &'map RocketSites,
+    unit_stacks: &'map UnitStacks,
+    ambient_objects: &'map AmbientObjects,
+    weather_positions: &'map WeatherPositions,
+    airports: &'map Airports,
+}
+
+/// The large images of the map that the editor does not show, which can be left unloaded until
+/// they are needed
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Layer {
+    /// The trees.bmp image
+    Trees,
+    /// The world_normal.bmp image
+    NormalMap,
+    /// The cities.bmp image
+    Cities,
+}
+
+/// The components of the map with many entries that few uses of the map read, which can be left
+/// unloaded until they are needed
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapComponent {
+    /// The buildings of `map/buildings.txt`
+    Buildings,
+    /// The unit stacks of `map/unitstacks.txt`
+    UnitStacks,
+}
+
+/// The version of the game a map is made for, as far as it changes the files of the map
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MapVersion {
+    /// A map from before 1.11, which gives supply through the supply areas of `map/supplyareas`
+    Legacy,
+    /// A map from 1.11 on, which gives supply through supply nodes and railways
+    #[default]
+    Current,
+}
+
+impl MapVersion {
+    /// Detects the version of the map in a root directory.  A map with a `map/supplyareas`
+    /// directory and no `map/supply_nodes.txt` is from before 1.11, and any other is current.
+    #[inline]
+    #[must_use]
+    pub fn detect(root_path: &Path) -> Self {
+        let supply_areas = map_file(root_path, Path::new("supplyareas"));
+        let supply_nodes = map_file(root_path, Path::new("supply_nodes.txt"));
+        if supply_areas.is_dir() && !supply_nodes.is_file() {
+            Self::Legacy
+        } else {
+            Self::Current
+        }
+    }
+}
+
+/// How a map is loaded.  Everything is loaded with the map unless it is skipped.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct MapLoadOptions {
+    /// The layers that are not loaded with the map.  Each is loaded the first time it is asked
+    /// for, and its size is not checked against the other images until then.
+    pub skip_layers: HashSet<Layer>,
+    /// The components that are not loaded with the map.  Each is empty until it is loaded with
+    /// `load_component`.
+    pub skip_components: HashSet<MapComponent>,
+    /// The channel sent an event as each component starts and stops loading
+    pub progress: Option<UnboundedSender<LoadProgress>>,
+    /// The token that stops the load before the next component starts once it is canceled
+    pub cancel: CancelToken,
+    /// The cache the definitions, adjacencies, buildings, unit stacks and weather positions are
+    /// read from when their files are unchanged.  The other components are parsed every time.
+    pub cache: Option<ComponentCache>,
+    /// How the rows of the definitions, adjacencies, buildings, unit stacks and weather positions
+    /// that cannot be read are handled.  A strict load fails at the first such row.
+    pub csv_parsing: CsvParsing,
+    /// The encoding the text files of the map are read in.  Without one, the encoding set by
+    /// `WORLD_GEN_ENCODING` is used, and without that the encoding of each file is detected.
+    pub encoding: Option<TextEncoding>,
+}
+
+impl MapLoadOptions {
+    /// Loads everything with the map
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts off loading everything the editor does not show until it is needed, which saves the
+    /// time and memory of the largest images and files
+    #[inline]
+    #[must_use]
+    pub fn deferred() -> Self {
+        Self::new()
+            .skip_layer(Layer::Trees)
+            .skip_layer(Layer::NormalMap)
+            .skip_layer(Layer::Cities)
+            .skip_component(MapComponent::UnitStacks)
+    }
+
+    /// Skips loading a layer with the map
+    #[inline]
+    #[must_use]
+    pub fn skip_layer(mut self, layer: Layer) -> Self {
+        self.skip_layers.insert(layer);
+        self
+    }
+
+    /// Skips loading a component with the map
+    #[inline]
+    #[must_use]
+    pub fn skip_component(mut self, component: MapComponent) -> Self {
+        self.skip_components.insert(component);
+        self
+    }
+
+    /// Sends the progress of loading each component to a channel.  Without a terminal, the
+    /// progress is then not printed.
+    #[inline]
+    #[must_use]
+    pub fn with_progress(mut self, progress: UnboundedSender<LoadProgress>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Stops the load with `MapError::LoadCanceled` once the token is canceled
+    #[inline]
+    #[must_use]
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Reads the components that can be cached from a cache, and caches them when they are parsed
+    #[inline]
+    #[must_use]
+    pub fn with_cache(mut self, cache: ComponentCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Handles the rows of the CSV files that cannot be read as `parsing` says, such as skipping
+    /// them with `CsvParsing::Lenient` rather than failing the load
+    #[inline]
+    #[must_use]
+    pub const fn with_csv_parsing(mut self, parsing: CsvParsing) -> Self {
+        self.csv_parsing = parsing;
+        self
+    }
+
+    /// Reads the text files of the map in `encoding` rather than detecting the encoding of each
+    #[inline]
+    #[must_use]
+    pub const fn with_encoding(mut self, encoding: TextEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
 }
 
 impl Map {
     /// Loads a map
     /// # Arguments
     /// * `root_path` - the path to the root Hearts of Iron IV directory
+    /// * `options` - the layers and components whose loading is put off, and where the progress
+    ///   is sent
     /// # Errors
     /// * If any of the required files could not be read
     /// * If any of the images are not formatted correctly
@@ -93,69 +452,65 @@ impl Map {
     pub fn new<T: TermLike + Clone + 'static>(
         root_path: &Path,
         term: &Option<T>,
+        options: &MapLoadOptions,
     ) -> Result<Self, MapError> {
         let progress = {
-            let dt = draw_target(term);
+            // Progress sent to a channel is not also drawn, unless a terminal is given
+            let dt = if term.is_none() && options.progress.is_some() {
+                ProgressDrawTarget::hidden()
+            } else {
+                draw_target(term)
+            };
             let p = MultiProgress::new();
             p.set_draw_target(dt);
             p
         };
         let progress_style = ProgressStyle::with_template("{wide_msg}")?;
+        let steps = LoadSteps::new(
+            progress,
+            progress_style,
+            options.progress.clone(),
+            options.cancel.clone(),
+        );
         let default_path = {
             let mut root_path_buf = root_path.to_path_buf();
             root_path_buf.push("map/default.map");
             root_path_buf
         };
-        let default_map = DefaultMap::load_object(&default_path)?;
-
-        let provinces_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            &default_map.provinces,
-        );
-
-        let terrain_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            &default_map.terrain,
-        );
+        let encoding = options.encoding;
+        let default_map = DefaultMap::load_object(&default_path, encoding)?;
 
-        let rivers_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            &default_map.rivers,
-        );
+        let provinces_handle =
+            Self::spawn_image_loading_thread(root_path, &steps, &default_map.provinces);
 
-        let heightmap_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            &default_map.heightmap,
-        );
+        let terrain_handle =
+            Self::spawn_image_loading_thread(root_path, &steps, &default_map.terrain);
 
-        let trees_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            &default_map.tree_definition,
-        );
+        let rivers_handle =
+            Self::spawn_image_loading_thread(root_path, &steps, &default_map.rivers);
 
-        let normal_map_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            Path::new("world_normal.bmp"),
-        );
+        let heightmap_handle =
+            Self::spawn_image_loading_thread(root_path, &steps, &default_map.heightmap);
 
-        let cities_map_handle = Self::spawn_image_loading_thread(
-            root_path,
-            &progress,
-            &progress_style,
-            Path::new("cities.bmp"),
-        );
+        let layer_paths = [
+            (Layer::Trees, default_map.tree_definition.clone()),
+            (Layer::NormalMap, PathBuf::from("world_normal.bmp")),
+            (Layer::Cities, PathBuf::from("cities.bmp")),
+        ];
+        let [trees_handle, normal_map_handle, cities_map_handle] =
+            layer_paths.clone().map(|(layer, path)| {
+                Self::spawn_layer_loading_thread(
+                    root_path,
+                    &steps,
+                    &path,
+                    options.skip_layers.contains(&layer),
+                )
+            });
+        let deferred_layers = layer_paths
+            .into_iter()
+            .filter(|(layer, _)| options.skip_layers.contains(layer))
+            .map(|(layer, path)| (layer, (root_path.to_path_buf(), path)))
+            .collect::<HashMap<_, _>>();
 
         let rt = tokio::runtime::Handle::current();
         let (
@@ -184,6 +539,7 @@ impl Map {
         let trees = trees_result?;
         let normal_map = normal_map_result?;
         let cities_map = cities_map_result?;
+        options.cancel.check()?;
 
         let verify_images_handle = {
             let provinces_clone = provinces.clone();
@@ -193,37 +549,44 @@ impl Map {
             let trees_clone = trees.clone();
             let normal_map_clone = normal_map.clone();
             let cities_map_clone = cities_map.clone();
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("image verification");
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Verifying images...\n");
+                pb.start("Verifying images...\n")?;
                 let result = verify_images(
                     &provinces_clone,
                     &terrain_clone,
                     &rivers_clone,
                     &heightmap_clone,
-                    &trees_clone,
-                    &normal_map_clone,
-                    &cities_map_clone,
+                    trees_clone.as_ref(),
+                    normal_map_clone.as_ref(),
+                    cities_map_clone.as_ref(),
                 );
                 if result.is_err() {
                     error!("Error verifying images");
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let definitions_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
-            let terrain_path = {
-                let mut root_path_buf = root_path.to_path_buf();
-                root_path_buf.push("common/terrain/00_terrain.txt");
-                root_path_buf
-            };
+            let pb = steps.step("definitions");
+            let terrain_path = root_path.join("common/terrain");
             let definitions_path = map_file(root_path, &default_map.definitions);
+            let cache = options.cache.clone();
+            let parsing = options.csv_parsing;
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading definitions and terrain...\n");
-                let result = Definitions::from_files(&definitions_path, &terrain_path);
+                pb.start("Loading definitions and terrain...\n")?;
+                let mut sources = vec![definitions_path.clone()];
+                sources.extend(text_files(&terrain_path)?);
+                let result = cached(
+                    cache.as_ref(),
+                    "definitions",
+                    &sources,
+                    parsing,
+                    encoding,
+                    || Definitions::from_files(&definitions_path, &terrain_path, parsing, encoding),
+                );
                 if result.is_err() {
                     error!(
                         "Error loading definitions and terrain from {} and {}",
@@ -231,32 +594,32 @@ impl Map {
                         terrain_path.display()
                     );
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let continents_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("continents");
             let continent_path = map_file(root_path, &default_map.continent);
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading continents...\n");
-                let result = Continents::load_object(&continent_path);
+                pb.start("Loading continents...\n")?;
+                let result = Continents::load_object(&continent_path, encoding);
                 if result.is_err() {
                     error!("Error loading continents from {}", continent_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let adjacency_rules_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("adjacency rules");
             let adjacency_rules_path = map_file(root_path, &default_map.adjacency_rules);
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading adjacency rules...\n");
-                let result = AdjacencyRules::from_file(&adjacency_rules_path);
-                pb.finish();
+                pb.start("Loading adjacency rules...\n")?;
+                let result = AdjacencyRules::from_file(&adjacency_rules_path, encoding);
+                pb.finish(&result);
                 match result {
                     Ok(rules) => Ok(rules),
                     Err(e) => {
@@ -272,32 +635,46 @@ impl Map {
         };
 
         let adjacencies_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("adjacencies");
             let adjacencies_path = map_file(root_path, &default_map.adjacencies);
+            let cache = options.cache.clone();
+            let parsing = options.csv_parsing;
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading adjacencies...\n");
-                let result = Adjacencies::from_file(&adjacencies_path);
+                pb.start("Loading adjacencies...\n")?;
+                // The lines are not serialized with the adjacencies, so they are cached alongside
+                let result = cached(
+                    cache.as_ref(),
+                    "adjacencies",
+                    &[adjacencies_path.clone()],
+                    parsing,
+                    encoding,
+                    || {
+                        Adjacencies::from_file(&adjacencies_path, parsing, encoding)
+                            .map(|loaded| (loaded.adjacencies, loaded.lines))
+                    },
+                )
+                .map(|(adjacencies, lines)| Adjacencies { adjacencies, lines });
                 if result.is_err() {
                     error!(
                         "Error loading adjacencies from {}",
                         adjacencies_path.display()
                     );
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let seasons_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("seasons");
             let seasons_path = map_file(root_path, &default_map.seasons);
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading seasons...\n");
-                let result = Seasons::load_object(&seasons_path);
+                pb.start("Loading seasons...\n")?;
+                let result = Seasons::load_object(&seasons_path, encoding);
                 if result.is_err() {
                     error!("Error loading seasons from {}", seasons_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
@@ -305,12 +682,12 @@ impl Map {
         let tree_indices = default_map.tree;
 
         let strategic_regions_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("strategic regions");
             let strategic_regions_path = map_file(root_path, Path::new("strategicregions"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading strategic regions...\n");
-                let result = StrategicRegions::from_dir(&strategic_regions_path);
-                pb.finish();
+                pb.start("Loading strategic regions...\n")?;
+                let result = StrategicRegions::from_dir(&strategic_regions_path, encoding);
+                pb.finish(&result);
                 match result {
                     Ok(regions) => Ok(regions),
                     Err(e) => {
@@ -325,167 +702,199 @@ impl Map {
             })
         };
 
+        let version = MapVersion::detect(root_path);
+        if version == MapVersion::Legacy {
+            info!("Loading a map from before 1.11, with supply areas in place of supply nodes");
+        }
+
         let supply_nodes_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("supply nodes");
             let supply_nodes_path = map_file(root_path, Path::new("supply_nodes.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading supply nodes...\n");
-                let result = SupplyNodes::from_file(&supply_nodes_path);
+                if version == MapVersion::Legacy && !supply_nodes_path.is_file() {
+                    pb.skip();
+                    return Ok(SupplyNodes::default());
+                }
+                pb.start("Loading supply nodes...\n")?;
+                let result = SupplyNodes::from_file(&supply_nodes_path, encoding);
                 if result.is_err() {
                     error!(
                         "Error loading supply nodes from {}",
                         supply_nodes_path.display()
                     );
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let railways_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("railways");
             let railways_path = map_file(root_path, Path::new("railways.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading railways...\n");
-                let result = Railways::from_file(&railways_path);
+                if version == MapVersion::Legacy && !railways_path.is_file() {
+                    pb.skip();
+                    return Ok(Railways::default());
+                }
+                pb.start("Loading railways...\n")?;
+                let result = Railways::from_file(&railways_path, encoding);
                 if result.is_err() {
                     error!("Error loading railways from {}", railways_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let buildings_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
-            let types_path = {
-                let mut root_path_buf = root_path.to_path_buf();
-                root_path_buf.push("common/buildings/00_buildings.txt");
-                root_path_buf
-            };
-            let buildings_path = map_file(root_path, Path::new("buildings.txt"));
+            let pb = steps.step("buildings");
+            let path = root_path.to_path_buf();
+            let skip = options.skip_components.contains(&MapComponent::Buildings);
+            let cache = options.cache.clone();
+            let parsing = options.csv_parsing;
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading buildings and building types...\n");
-                let result = Buildings::from_files(&types_path, &buildings_path);
-                if result.is_err() {
-                    error!(
-                        "Error loading buildings from {} and {}",
-                        buildings_path.display(),
-                        types_path.display()
-                    );
+                if skip {
+                    pb.skip();
+                    return Ok(Buildings::default());
                 }
-                pb.finish();
+                pb.start("Loading buildings and building types...\n")?;
+                let result = cached(
+                    cache.as_ref(),
+                    "buildings",
+                    &buildings_sources(&path),
+                    parsing,
+                    encoding,
+                    || load_buildings(&path, parsing, encoding),
+                );
+                pb.finish(&result);
                 result
             })
         };
 
         let cities_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("cities");
             let cities_path = map_file(root_path, Path::new("cities.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading cities...\n");
-                let result = Cities::load_object(&cities_path);
+                pb.start("Loading cities...\n")?;
+                let result = Cities::load_object(&cities_path, encoding);
                 if result.is_err() {
                     error!("Error loading cities from {}", cities_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let colors_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("colors");
             let colors_path = map_file(root_path, Path::new("colors.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading colors...\n");
-                let result = Colors::load_object(&colors_path);
+                pb.start("Loading colors...\n")?;
+                let result = Colors::load_object(&colors_path, encoding);
                 if result.is_err() {
                     error!("Error loading colors from {}", colors_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let rocket_sites_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("rocket sites");
             let rocket_sites_path = map_file(root_path, Path::new("rocketsites.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading rocket sites...\n");
-                let result = RocketSites::from_file(&rocket_sites_path);
+                pb.start("Loading rocket sites...\n")?;
+                let result = RocketSites::from_file(&rocket_sites_path, encoding);
                 if result.is_err() {
                     error!(
                         "Error loading rocket sites from {}",
                         rocket_sites_path.display()
                     );
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let unit_stacks_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
-            let unit_stacks_path = map_file(root_path, Path::new("unitstacks.txt"));
+            let pb = steps.step("unit stacks");
+            let path = root_path.to_path_buf();
+            let skip = options.skip_components.contains(&MapComponent::UnitStacks);
+            let cache = options.cache.clone();
+            let parsing = options.csv_parsing;
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading unit stacks...\n");
-                let result = UnitStacks::from_file(&unit_stacks_path);
-                if result.is_err() {
-                    error!(
-                        "Error loading unit stacks from {}",
-                        unit_stacks_path.display()
-                    );
+                if skip {
+                    pb.skip();
+                    return Ok(UnitStacks::default());
                 }
-                pb.finish();
+                pb.start("Loading unit stacks...\n")?;
+                let result = cached(
+                    cache.as_ref(),
+                    "unit_stacks",
+                    &[map_file(&path, Path::new("unitstacks.txt"))],
+                    parsing,
+                    encoding,
+                    || load_unit_stacks(&path, parsing, encoding),
+                );
+                pb.finish(&result);
                 result
             })
         };
 
         let weather_positions_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("weather positions");
             let weather_positions_path = map_file(root_path, Path::new("weatherpositions.txt"));
+            let cache = options.cache.clone();
+            let parsing = options.csv_parsing;
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading weather positions...\n");
-                let result = WeatherPositions::from_file(&weather_positions_path);
+                pb.start("Loading weather positions...\n")?;
+                let result = cached(
+                    cache.as_ref(),
+                    "weather_positions",
+                    &[weather_positions_path.clone()],
+                    parsing,
+                    encoding,
+                    || WeatherPositions::from_file(&weather_positions_path, parsing, encoding),
+                );
                 if result.is_err() {
                     error!(
                         "Failed to load weather positions from {}",
                         weather_positions_path.display()
                     );
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let airports_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("airports");
             let airports_path = map_file(root_path, Path::new("airports.txt"));
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading airports...\n");
-                let result = Airports::from_file(&airports_path);
+                pb.start("Loading airports...\n")?;
+                let result = Airports::from_file(&airports_path, encoding);
                 if result.is_err() {
                     error!("Failed to load airports from {}", airports_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
 
         let states_handle = {
-            let pb = Self::create_map_progress_indicator(&progress, &progress_style);
+            let pb = steps.step("states");
             let states_path = {
                 let mut states = root_path.to_path_buf();
                 states.push("history/states");
                 states
             };
             tokio::task::spawn_blocking(move || {
-                pb.set_message("Loading states...\n");
-                let result = States::from_dir(&states_path);
+                pb.start("Loading states...\n")?;
+                let result = States::from_dir(&states_path, encoding);
                 if result.is_err() {
                     error!("Failed to load states from {}", states_path.display());
                 }
-                pb.finish();
+                pb.finish(&result);
                 result
             })
         };
@@ -530,8 +939,17 @@ impl Map {
             )
         })?;
 
+        options.cancel.check()?;
         verify_result?;
         let definitions = definitions_result?;
+        let water_terrain = load_water_terrain(&root_path.join("common/terrain"), encoding)?;
+        let country_colors = load_country_colors(root_path, encoding);
+        let country_histories = load_country_histories(root_path, encoding);
+        let state_categories = load_state_categories(root_path, encoding);
+        let ambient_objects = load_ambient_objects(root_path, encoding);
+        let supply_areas = load_supply_areas(root_path, version, encoding);
+        let localisation = load_localisation(root_path, encoding);
+        let weather_states = load_weather_states(root_path, encoding);
         let continents = continents_result?;
         let adjacency_rules = adjacency_rules_result?;
         let adjacencies = adjacencies_result?;
@@ -546,7 +964,11 @@ impl Map {
         let unit_stacks = unit_stacks_result?;
         let weather_positions = weather_positions_result?;
         let airports = airports_result?;
-        let states = states_result?.states;
+        let States {
+            states,
+            sources: state_sources,
+            duplicates: duplicate_state_files,
+        } = states_result?;
 
         let provinces_by_color = definitions
             .definitions
@@ -558,6 +980,7 @@ impl Map {
                 )
             })
             .collect();
+        let province_index = ProvinceIndex::new(&provinces, &provinces_by_color)?;
 
         let strategic_regions_by_province = strategic_regions
             .strategic_regions
@@ -570,378 +993,2924 @@ impl Map {
             .flat_map(|(id, sr)| sr.provinces.iter().map(|p| (*p, *id)).collect::<Vec<_>>())
             .collect();
 
-        progress.println("Loading map complete")?;
-        progress.clear()?;
+        let project = Project::load(root_path).unwrap_or_else(|e| {
+            warn!("Using the default project settings: {e}");
+            Project::default()
+        });
+        let spill_dir = project
+            .history
+            .spill_to_disk
+            .then(|| std::env::temp_dir().join("world_gen_history"));
+        let history = EditHistory::new(project.history.memory_cap, spill_dir);
+
+        let deferred_components = options
+            .skip_components
+            .iter()
+            .map(|component| (*component, root_path.to_path_buf()))
+            .collect();
+
+        let definitions_path = Path::new("map").join(&default_map.definitions);
+        let adjacencies_path = Path::new("map").join(&default_map.adjacencies);
+
+        steps.progress().println("Loading map complete")?;
+        steps.progress().clear()?;
 
         Ok(Self {
-            provinces,
-            terrain,
-            rivers,
-            heightmap,
-            trees,
-            normal_map,
-            cities_map,
+            provinces: Arc::new(provinces),
+            terrain: Arc::new(terrain),
+            rivers: Arc::new(rivers),
+            heightmap: Arc::new(heightmap),
+            trees: trees.map(Arc::new),
+            normal_map: normal_map.map(Arc::new),
+            cities_map: cities_map.map(Arc::new),
+            deferred_layers,
+            deferred_components,
+            csv_parsing: options.csv_parsing,
+            encoding,
             definitions,
+            definitions_path,
+            water_terrain,
+            country_colors,
+            country_histories,
+            state_categories,
+            localisation,
+            weather_states,
             continents,
             adjacency_rules,
             adjacencies,
+            adjacencies_path,
             seasons,
             tree_indices,
             strategic_regions,
             strategic_region_map: None,
+            strategic_region_legend: Legend::default(),
+            version,
             supply_nodes,
             railways,
+            supply_areas,
             buildings,
             cities,
             colors,
             rocket_sites,
             unit_stacks,
+            ambient_objects,
             weather_positions,
             airports,
             provinces_by_color,
+            province_index,
             strategic_regions_by_province,
             strategic_region_map_handle: None,
             states,
             state_map_handle: None,
             state_map: None,
+            state_legend: Legend::default(),
+            continent_map_handle: None,
+            continent_map: None,
+            continent_legend: Legend::default(),
+            definition_terrain_map_handle: None,
+            definition_terrain_map: None,
+            elevation_sea_level: SEA_LEVEL,
+            building_density: BuildingDensity::default(),
+            weather_view: WeatherView::default(),
+            weather_legend: Legend::default(),
+            manpower_legend: Legend::default(),
+            political_legend: Legend::default(),
+            state_category_legend: Legend::default(),
             states_by_province,
+            state_sources,
+            duplicate_state_files,
+            dirty_states: HashSet::new(),
+            river_issues: Vec::new(),
+            strait_candidates: Vec::new(),
+            edit_preview: EditPreview::default(),
+            dirty_regions: DirtyRegions::default(),
+            wrapping: Wrapping::None,
+            validation_settings: project.validation,
+            region_colors: project.region_colors,
+            history,
+            watcher: None,
         })
     }
 
+    /// Saves the states to the `history/states/` directory of the given root directory.  States
+    /// loaded with the map keep the formatting of the files they were loaded from.
+    /// # Errors
+    /// * If the states directory does not exist
+    /// * If any of the state files fail to be written
+    #[inline]
+    pub fn save_states(&self, root_path: &Path) -> Result<(), MapError> {
+        let states_path = root_path.join("history/states");
+        for (id, state) in &self.states {
+            state.save(&states_path, self.state_sources.get(id))?;
+        }
+        Ok(())
+    }
+
+    /// Saves the strategic regions to the `map/strategicregions/` directory of the given root
+    /// directory.  Strategic regions loaded with the map keep the formatting of the files they were
+    /// loaded from, along with any keys that are not read.
+    /// # Errors
+    /// * If the strategic regions directory does not exist
+    /// * If any of the strategic region files fail to be written
+    #[inline]
+    pub fn save_strategic_regions(&self, root_path: &Path) -> Result<(), MapError> {
+        self.strategic_regions
+            .to_dir(&map_file(root_path, Path::new("strategicregions")))
+    }
+
+    /// Writes the components parsed from the files of the map to a single JSON document for
+    /// external tools, loading any components whose loading was put off first.  The document has
+    /// a key for each component, such as `definitions`, `states` and `railways`, holding the
+    /// component as it was parsed.
+    /// # Errors
+    /// * If a component whose loading was put off cannot be loaded
+    /// * If the file cannot be written
+    #[inline]
+    pub fn export_json(&mut self, path: &Path) -> Result<(), MapError> {
+        self.load_component(MapComponent::Buildings)?;
+        self.load_component(MapComponent::UnitStacks)?;
+        let export = MapExport {
+            definitions: &self.definitions,
+            continents: &self.continents,
+            adjacency_rules: &self.adjacency_rules,
+            adjacencies: &self.adjacencies,
+            seasons: &self.seasons,
+            strategic_regions: &self.strategic_regions,
+            states: &self.states,
+            supply_nodes: &self.supply_nodes,
+            supply_areas: &self.supply_areas,
+            railways: &self.railways,
+            buildings: &self.buildings,
+            cities: &self.cities,
+            rocket_sites: &self.rocket_sites,
+            unit_stacks: &self.unit_stacks,
+            ambient_objects: &self.ambient_objects,
+            weather_positions: &self.weather_positions,
+            airports: &self.airports,
+        };
+        let mut writer = BufWriter::new(File::create(path).in_file(path)?);
+        serde_json::to_writer(&mut writer, &export).in_file(path)?;
+        writer.flush().in_file(path)?;
+        info!("Exported the map to {}", path.display());
+        Ok(())
+    }
+
+    /// Applies fixes of the findings of `validate`, writing the definitions, adjacencies and
+    /// states they change to the given root directory.  Fixes that no longer change anything are
+    /// skipped.  Returns the number of fixes applied.
+    /// # Errors
+    /// If any of the changed files fail to be written
+    #[inline]
+    pub fn apply_fixes(&mut self, root_path: &Path, fixes: &[AutoFix]) -> Result<usize, MapError> {
+        let mut applied = 0_usize;
+        let mut definitions_changed = false;
+        let mut adjacencies_changed = false;
+        let mut states_changed = BTreeSet::new();
+        for fix in fixes {
+            let changed = match fix {
+                AutoFix::SetCoastal(id, coastal) => {
+                    match self.definitions.definitions.get_mut(id) {
+                        Some(d) if d.coastal.0 != *coastal => {
+                            d.coastal = Coastal(*coastal);
+                            definitions_changed = true;
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+                AutoFix::SetContinent(id, continent) => {
+                    match self.definitions.definitions.get_mut(id) {
+                        Some(d) if d.continent != *continent => {
+                            d.continent = *continent;
+                            definitions_changed = true;
+                            self.continent_map = None;
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+                AutoFix::TerminateAdjacencies => {
+                    let changed = !self.adjacencies.is_terminated();
+                    if changed {
+                        self.adjacencies.push(Adjacency::terminator());
+                    }
+                    adjacencies_changed |= changed;
+                    changed
+                }
+                AutoFix::DeduplicateManpower(id) => {
+                    let edit = self
+                        .states
+                        .get(id)
+                        .filter(|s| s.manpower.len() > 1)
+                        .and_then(State::current_manpower);
+                    match (self.states.get_mut(id), edit) {
+                        (Some(state), Some(manpower)) => {
+                            let before = state.clone();
+                            state.apply(StateEdit::Manpower(manpower));
+                            let after = state.clone();
+                            self.record(Edit::new("Apply fix").with_state(
+                                *id,
+                                Some(before),
+                                Some(after),
+                            ));
+                            states_changed.insert(*id);
+                            true
+                        }
+                        _ => false,
+                    }
+                }
+            };
+            if changed {
+                debug!("Applied fix: {fix}");
+                applied = applied.saturating_add(1);
+            }
+        }
+
+        if definitions_changed {
+            self.definitions
+                .to_file(&root_path.join(&self.definitions_path))?;
+        }
+        if adjacencies_changed {
+            self.adjacencies
+                .to_file(&root_path.join(&self.adjacencies_path))?;
+        }
+        let states_path = root_path.join("history/states");
+        for id in states_changed {
+            if let Some(state) = self.states.get(&id) {
+                state.save(&states_path, self.state_sources.get(&id))?;
+                self.dirty_states.remove(&id);
+            }
+        }
+        Ok(applied)
+    }
+
     /// Spawns a thread to load an image
     fn spawn_image_loading_thread(
         root_path: &Path,
-        progress: &MultiProgress,
-        progress_style: &ProgressStyle,
+        steps: &LoadSteps,
         image_path: &Path,
     ) -> JoinHandle<Result<RgbImage, MapError>> {
         let path = root_path.to_path_buf();
-        let pb = Self::create_map_progress_indicator(progress, progress_style);
+        let pb = steps.step(image_path.display().to_string());
         let ip = image_path.to_path_buf();
         tokio::task::spawn_blocking(move || {
-            pb.set_message(format!("Loading {} \n", ip.display()));
+            pb.start(format!("Loading {} \n", ip.display()))?;
             let image_result = load_image(&path, &ip);
             if image_result.is_err() {
                 error!("Error loading {}", ip.display());
             }
-            pb.finish();
+            pb.finish(&image_result);
             image_result
         })
     }
 
-    /// Creates a map progress indicator
-    fn create_map_progress_indicator(
-        progress: &MultiProgress,
-        progress_style: &ProgressStyle,
-    ) -> ProgressBar {
-        progress
-            .add(ProgressBar::new(1))
-            .with_style(progress_style.clone())
+    /// Spawns a thread to load the image of a layer, unless its loading is put off
+    fn spawn_layer_loading_thread(
+        root_path: &Path,
+        steps: &LoadSteps,
+        image_path: &Path,
+        skip: bool,
+    ) -> JoinHandle<Result<Option<RgbImage>, MapError>> {
+        if skip {
+            debug!("Putting off loading {}", image_path.display());
+            steps.step(image_path.display().to_string()).skip();
+            return tokio::task::spawn_blocking(|| Ok(None));
+        }
+        let handle = Self::spawn_image_loading_thread(root_path, steps, image_path);
+        tokio::task::spawn(async move { Ok(Some(handle.await??)) })
     }
 
-    /// Verifies the province colors against the provinces image
+    /// The image of a layer, loading it first if its loading was put off.  A layer loaded late is
+    /// checked against the size of the heightmap as it would have been with the map.
     /// # Errors
-    /// * If the province definitions are not valid
-    #[inline]
-    pub fn verify_province_colors(&self) -> Result<(), MapError> {
-        let mut color_set = HashSet::new();
-        color_set.insert((Red(0), Green(0), Blue(0)));
-        for pixel in self.provinces.pixels() {
-            if let [r, g, b] = pixel.channels() {
-                let red = Red(*r);
-                let green = Green(*g);
-                let blue = Blue(*b);
-                color_set.insert((red, green, blue));
-            }
-        }
-        trace!("{} colors found", color_set.len());
-        for definition in self.definitions.definitions.values() {
-            let color = (definition.r, definition.g, definition.b);
-            if !color_set.contains(&color) {
-                return Err(MapError::InvalidProvinceColor(color));
-            }
-            color_set.remove(&color);
-        }
-        if !color_set.is_empty() {
-            return Err(MapError::IncompleteProvinceDefinitions(
-                color_set.into_iter().collect(),
-            ));
+    /// * If the image cannot be loaded
+    /// * If the size of the image does not match the heightmap
+    #[inline]
+    pub fn layer(&mut self, layer: Layer) -> Result<&RgbImage, MapError> {
+        if let Some((root_path, path)) = self.deferred_layers.get(&layer) {
+            let image = load_image(root_path, path)?;
+            let (trees, normal_map, cities) = match layer {
+                Layer::Trees => (Some(&image), None, None),
+                Layer::NormalMap => (None, Some(&image), None),
+                Layer::Cities => (None, None, Some(&image)),
+            };
+            verify_images(
+                &self.provinces,
+                &self.terrain,
+                &self.rivers,
+                &self.heightmap,
+                trees,
+                normal_map,
+                cities,
+            )?;
+            self.deferred_layers.remove(&layer);
+            *self.layer_image(layer) = Some(Arc::new(image));
         }
+        self.layer_image(layer)
+            .as_deref()
+            .ok_or(MapError::LayerNotLoaded(layer))
+    }
 
-        Ok(())
+    /// Draws the heat map of the buildings with the current settings, loading the buildings first
+    /// if their loading was put off
+    fn building_density_map(&mut self) -> RgbImage {
+        if let Err(e) = self.load_component(MapComponent::Buildings) {
+            error!("Failed to load the buildings: {e}");
+        }
+        self.building_density
+            .render(&self.buildings, &self.states, &self.province_index)
     }
 
-    /// Gets the province id from a given point.
-    fn province_id_from_point(&self, point: Pos2) -> Option<ProvinceId> {
-        let color = self.provinces.get_pixel(point.x as u32, point.y as u32);
-        self.provinces_by_color.get(color).copied()
+    /// Draws the map of the weather with the current day and measure, keeping its legend
+    fn weather_map(&mut self) -> RgbImage {
+        let (map, legend) = self.weather_view.render(
+            &self.strategic_regions,
+            &self.strategic_regions_by_province,
+            &self.province_index,
+        );
+        self.weather_legend = legend;
+        map
     }
-}
 
-impl Actor for Map {
-    type Context = Context<Self>;
-}
+    /// Draws the map of the manpower of the states, keeping its legend
+    fn manpower_map(&mut self) -> RgbImage {
+        let (map, legend) = render_manpower(&self.states, &self.province_index);
+        self.manpower_legend = legend;
+        map
+    }
 
-/// A request to get a `ProvinceId` from a supplied texture uv coordinate
-#[derive(Message, Debug)]
-#[rtype(result = "Option<ProvinceId>")]
-#[non_exhaustive]
-pub struct GetProvinceIdFromPoint(pub Pos2);
+    /// Draws the political map of the owners of the states, keeping its legend
+    fn political_map(&mut self) -> RgbImage {
+        let (map, legend) = render_political(
+            &self.states,
+            &self.country_colors,
+            &self.country_histories,
+            &self.province_index,
+        );
+        self.political_legend = legend;
+        map
+    }
 
-impl GetProvinceIdFromPoint {
-    /// Creates a new request for a province id
-    #[inline]
-    #[must_use]
-    pub const fn new(pos: Pos2) -> Self {
-        Self(pos)
+    /// Draws the map of the categories of the states, keeping its legend
+    fn state_category_map(&mut self) -> RgbImage {
+        let (map, legend) =
+            render_state_categories(&self.states, &self.state_categories, &self.province_index);
+        self.state_category_legend = legend;
+        map
     }
-}
 
-/// A request to get a `StrategicRegionId` from a supplied texture uv coordinate
-#[derive(Message, Debug)]
-#[rtype(result = "Option<StrategicRegionId>")]
-#[non_exhaustive]
-pub struct GetStrategicRegionIdFromPoint(pub Pos2);
+    /// The image of a layer shared with a texture, loading it first if its loading was put off.
+    /// A layer that fails to load is logged and left without an image.
+    fn shared_layer(&mut self, layer: Layer) -> Option<Arc<RgbImage>> {
+        if let Err(e) = self.layer(layer) {
+            error!("Failed to load the {layer:?} layer: {e}");
+        }
+        self.layer_image(layer).clone()
+    }
 
-impl GetStrategicRegionIdFromPoint {
-    /// Creates a new request for a strategic region id
+    /// Whether a component has been loaded, either with the map or by `load_component`
     #[inline]
     #[must_use]
-    pub const fn new(pos: Pos2) -> Self {
-        Self(pos)
+    pub fn is_loaded(&self, component: MapComponent) -> bool {
+        !self.deferred_components.contains_key(&component)
     }
-}
-
-/// A request to get a `StrategicRegionId` from a supplied texture uv coordinate
-#[derive(Message, Debug)]
-#[rtype(result = "Option<StateId>")]
-#[non_exhaustive]
-pub struct GetStateIdFromPoint(pub Pos2);
 
-impl GetStateIdFromPoint {
-    /// Creates a new request for a state id
+    /// Loads a component whose loading was put off.  Returns whether it was loaded, which is
+    /// `false` if it already was.
+    /// # Errors
+    /// * If the files of the component cannot be loaded
     #[inline]
-    #[must_use]
-    pub const fn new(pos: Pos2) -> Self {
-        Self(pos)
+    pub fn load_component(&mut self, component: MapComponent) -> Result<bool, MapError> {
+        let root_path = match self.deferred_components.get(&component) {
+            Some(root_path) => root_path,
+            None => return Ok(false),
+        };
+        match component {
+            MapComponent::Buildings => {
+                self.buildings = load_buildings(root_path, self.csv_parsing, self.encoding)?;
+            }
+            MapComponent::UnitStacks => {
+                self.unit_stacks = load_unit_stacks(root_path, self.csv_parsing, self.encoding)?;
+            }
+        }
+        self.deferred_components.remove(&component);
+        info!("Loaded {component:?}");
+        Ok(true)
     }
-}
 
-/// A request to get a `Definition` from a supplied `ProvinceId`
-#[derive(Message, Debug)]
-#[rtype(result = "Option<Definition>")]
-#[non_exhaustive]
-pub struct GetProvinceDefinitionFromId(pub ProvinceId);
-
-impl GetProvinceDefinitionFromId {
-    /// Creates a new request for a province id
+    /// Reloads a file of the root directory that changed on disk, replacing only the component
+    /// it holds.  A state edited in the editor since it was saved is kept rather than replaced,
+    /// and a component whose loading was put off is left to be loaded when needed.  The region
+    /// maps are cleared when the provinces of a state or strategic region changed.
+    /// # Errors
+    /// * If the file cannot be loaded
     #[inline]
-    #[must_use]
-    pub const fn new(id: ProvinceId) -> Self {
-        Self(id)
+    pub fn reload_file(&mut self, root_path: &Path, file: WatchedFile) -> Result<(), MapError> {
+        debug!("Reloading {file:?}");
+        match file {
+            WatchedFile::State(path) => self.reload_state(&path)?,
+            WatchedFile::StrategicRegions => {
+                let strategic_regions = StrategicRegions::from_dir(
+                    &map_file(root_path, Path::new("strategicregions")),
+                    self.encoding,
+                )?;
+                let strategic_regions_by_province = strategic_regions
+                    .strategic_regions
+                    .iter()
+                    .flat_map(|(id, sr)| sr.provinces.iter().map(|p| (*p, *id)))
+                    .collect::<HashMap<_, _>>();
+                if strategic_regions_by_province != self.strategic_regions_by_province {
+                    self.strategic_region_map = None;
+                }
+                self.strategic_regions = strategic_regions;
+                self.strategic_regions_by_province = strategic_regions_by_province;
+            }
+            WatchedFile::Adjacencies => {
+                self.adjacencies = Adjacencies::from_file(
+                    root_path.join(&self.adjacencies_path),
+                    self.csv_parsing,
+                    self.encoding,
+                )?;
+            }
+            WatchedFile::SupplyNodes => {
+                self.supply_nodes = SupplyNodes::from_file(
+                    &map_file(root_path, Path::new("supply_nodes.txt")),
+                    self.encoding,
+                )?;
+            }
+            WatchedFile::Railways => {
+                self.railways = Railways::from_file(
+                    &map_file(root_path, Path::new("railways.txt")),
+                    self.encoding,
+                )?;
+            }
+            WatchedFile::Buildings => {
+                if self.is_loaded(MapComponent::Buildings) {
+                    self.buildings = load_buildings(root_path, self.csv_parsing, self.encoding)?;
+                }
+            }
+            WatchedFile::UnitStacks => {
+                if self.is_loaded(MapComponent::UnitStacks) {
+                    self.unit_stacks =
+                        load_unit_stacks(root_path, self.csv_parsing, self.encoding)?;
+                }
+            }
+            WatchedFile::WeatherPositions => {
+                self.weather_positions = WeatherPositions::from_file(
+                    map_file(root_path, Path::new("weatherpositions.txt")),
+                    self.csv_parsing,
+                    self.encoding,
+                )?;
+            }
+            WatchedFile::Airports => {
+                self.airports = Airports::from_file(
+                    &map_file(root_path, Path::new("airports.txt")),
+                    self.encoding,
+                )?;
+            }
+            WatchedFile::RocketSites => {
+                self.rocket_sites = RocketSites::from_file(
+                    &map_file(root_path, Path::new("rocketsites.txt")),
+                    self.encoding,
+                )?;
+            }
+        }
+        Ok(())
     }
-}
 
-/// A request to get a `StrategicRegion` from a given `StrategicRegionId`
-#[derive(Message, Debug)]
-#[rtype(result = "Option<StrategicRegion>")]
-#[non_exhaustive]
-pub struct GetStrategicRegionFromId(pub StrategicRegionId);
+    /// Reloads the state of a single state file, unless it was edited in the editor or another
+    /// file defines the same state
+    fn reload_state(&mut self, path: &Path) -> Result<(), MapError> {
+        let (state, source) = States::load_file(path, self.encoding)?;
+        let id = state.id;
+        if self.dirty_states.contains(&id) {
+            warn!(
+                "Keeping the edits of state {id} over the changes to {}",
+                path.display()
+            );
+            return Ok(());
+        }
+        if let Some(loaded) = self.state_sources.get(&id) {
+            if loaded.file_name != source.file_name {
+                warn!(
+                    "Not reloading {}, as state {id} is defined by {}",
+                    path.display(),
+                    loaded.file_name
+                );
+                return Ok(());
+            }
+        }
+        let old_provinces = self.states.get(&id).map(|s| s.provinces.clone());
+        if old_provinces.as_ref() != Some(&state.provinces) {
+            self.states_by_province
+                .retain(|_province, state_id| *state_id != id);
+            self.states_by_province
+                .extend(state.provinces.iter().map(|province| (*province, id)));
+            self.state_map = None;
+        }
+        self.state_sources.insert(id, source);
+        self.states.insert(id, state);
+        info!("Reloaded state {id} from {}", path.display());
+        Ok(())
+    }
 
-impl GetStrategicRegionFromId {
-    /// Creates a new request for a strategic region id
-    #[inline]
-    #[must_use]
-    pub const fn new(id: StrategicRegionId) -> Self {
-        Self(id)
+    /// The field holding the image of a layer
+    fn layer_image(&mut self, layer: Layer) -> &mut Option<Arc<RgbImage>> {
+        match layer {
+            Layer::Trees => &mut self.trees,
+            Layer::NormalMap => &mut self.normal_map,
+            Layer::Cities => &mut self.cities_map,
+        }
     }
-}
 
-/// A request to get a `State` from a given `StateId`.
+    /// Verifies the province colors against the provinces image, returning a finding for each
+    /// definition whose color is not on the image and each color on the image without a definition
+    #[inline]
+    #[must_use]
+    pub fn verify_province_colors(&self) -> Vec<Finding> {
+        let mut first_pixels = first_pixels(&self.provinces);
+        trace!("{} colors found", first_pixels.len());
+        let mut definitions = self.definitions.definitions.values().collect::<Vec<_>>();
+        definitions.sort_unstable_by_key(|d| d.id);
+        let mut findings = Vec::new();
+        for definition in definitions {
+            let color = Rgb([definition.r.0, definition.g.0, definition.b.0]);
+            // The black province 0 is not required to be on the image
+            if first_pixels.remove(&color).is_none() && color != Rgb([0, 0, 0]) {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::MissingProvinceColor,
+                        format!("Province color {:?} is not on the provinces image", color.0),
+                    )
+                    .in_province(definition.id)
+                    .with_fix("Paint the province on provinces.bmp or remove its definition"),
+                );
+            }
+        }
+        let mut undefined = first_pixels.into_iter().collect::<Vec<_>>();
+        undefined.sort_unstable_by_key(|(_color, (x, y))| (*y, *x));
+        for (color, (x, y)) in undefined {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::UndefinedProvinceColor,
+                    format!(
+                        "Color {:?} on the provinces image has no definition",
+                        color.0
+                    ),
+                )
+                .at_pixel(x, y)
+                .with_fix("Add a definition for the color to definition.csv"),
+            );
+        }
+        findings
+    }
+
+    /// Runs every check of the map, reporting all of the problems found rather than stopping at
+    /// the first.  The severities are those of the project's validation profile, and the findings
+    /// are sorted with the most serious first.
+    #[inline]
+    #[must_use]
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        report.extend(self.verify_province_colors());
+        report.extend(self.definitions.verify_unique_colors());
+        report.extend(self.definitions.verify_province_terrain());
+        report.extend(verify_province_types(
+            &self.definitions,
+            self.continents.continents.len(),
+            &self.water_terrain,
+        ));
+        report.extend(verify_province_sizes(
+            &self.provinces,
+            &self.provinces_by_color,
+            self.validation_settings.minimum_province_size,
+        ));
+        report.extend(verify_province_bounds(
+            &self.provinces,
+            &self.provinces_by_color,
+            self.wrapping,
+        ));
+        report.extend(verify_coastal_flags(
+            &self.definitions,
+            &self.province_index,
+            self.wrapping,
+        ));
+        report.extend(verify_province_contiguity(
+            &self.province_index,
+            self.wrapping,
+        ));
+        report.extend(
+            self.verify_province_heights()
+                .into_iter()
+                .map(Finding::from),
+        );
+        report.extend(
+            verify_rivers(&self.rivers, self.wrapping)
+                .into_iter()
+                .map(Finding::from),
+        );
+        report.extend(
+            find_x_crossings(&self.provinces, self.wrapping)
+                .into_iter()
+                .map(Finding::from),
+        );
+        report.extend(verify_states(
+            &self.states,
+            &self.definitions,
+            &self.strategic_regions_by_province,
+        ));
+        report.extend(verify_state_categories(
+            &self.states,
+            &self.state_categories,
+        ));
+        report.extend(verify_countries(&self.states, &self.country_histories));
+        report.extend(verify_state_files(
+            &self.states,
+            &self.state_sources,
+            &self.duplicate_state_files,
+        ));
+        report.extend(verify_strategic_region_coverage(
+            &self.definitions,
+            &self.strategic_regions_by_province,
+        ));
+        report.extend(verify_adjacencies(
+            &self.adjacencies,
+            &self.adjacency_rules,
+            &self.definitions,
+        ));
+        report.extend(verify_weather_periods(
+            &self.strategic_regions,
+            &self.weather_states,
+        ));
+        report.extend(verify_weather_positions(
+            &self.weather_positions,
+            &self.strategic_regions,
+            &self.strategic_regions_by_province,
+            &self.province_index,
+        ));
+        report.extend(verify_railways(
+            &self.railways,
+            &self.adjacencies,
+            &self.definitions,
+            &self.province_index,
+            self.wrapping,
+        ));
+        if self.is_loaded(MapComponent::Buildings) {
+            report.extend(verify_buildings(
+                &self.buildings,
+                &self.states,
+                &self.definitions,
+                &self.province_index,
+                &self.heightmap,
+                self.wrapping,
+            ));
+            // Maps from before 1.11 give supply through their supply areas, not supply nodes
+            if self.version == MapVersion::Current {
+                let supply_graph = SupplyGraph::new(
+                    &self.definitions,
+                    &self.province_index,
+                    self.wrapping,
+                    &self.adjacencies,
+                    &self.railways,
+                    &self.supply_nodes,
+                    &self.buildings,
+                );
+                report.extend(verify_supply(
+                    &supply_graph,
+                    &self.definitions,
+                    &self.states,
+                ));
+            }
+        } else {
+            debug!("Skipping the checks of the buildings and supply, as buildings are not loaded");
+        }
+        report.apply_profile(
+            self.validation_settings.profile,
+            &self.validation_settings.rules,
+        );
+        report.sort();
+        report
+    }
+
+    /// The pixel of the provinces image to show a finding at: its own pixel if it has one,
+    /// otherwise the pixel of its province, or of the lowest province of its state, closest to
+    /// the middle of that province.  Returns `None` if the finding has no location on the map.
+    #[inline]
+    #[must_use]
+    pub fn locate_finding(&self, finding: &Finding) -> Option<(u32, u32)> {
+        if let Some(pixel) = finding.pixel {
+            return Some(pixel);
+        }
+        let province = finding.province.or_else(|| {
+            finding
+                .state
+                .and_then(|s| self.states.get(&s))
+                .and_then(|s| s.provinces.iter().min().copied())
+        })?;
+        let definition = self.definitions.definitions.get(&province)?;
+        let color = Rgb::from([definition.r.0, definition.g.0, definition.b.0]);
+        province_pixel(&self.provinces, color)
+    }
+
+    /// Verifies the province types against the heightmap, returning the inconsistent provinces
+    /// with the worst offenders first.
+    #[inline]
+    #[must_use]
+    pub fn verify_province_heights(&self) -> Vec<ProvinceHeightIssue> {
+        verify_province_heights(
+            &self.province_index,
+            &self.heightmap,
+            &self.definitions,
+            self.wrapping,
+        )
+    }
+
+    /// The colors the regions of the region maps must not be drawn in
+    fn colors_to_avoid(&self) -> HashSet<Rgb<u8>> {
+        if self.region_colors.avoid_province_colors {
+            self.provinces_by_color.keys().copied().collect()
+        } else {
+            HashSet::new()
+        }
+    }
+
+    /// Gets the province id from a given point.
+    fn province_id_from_point(&self, point: Pos2) -> Option<ProvinceId> {
+        self.province_index
+            .province_at(point.x as u32, point.y as u32)
+    }
+
+    /// Gets the image shown in a display mode, if it has been loaded or generated
+    fn image(&self, mode: MapDisplayMode) -> Option<&RgbImage> {
+        match mode {
+            MapDisplayMode::HeightMap => Some(self.heightmap.as_ref()),
+            MapDisplayMode::Terrain => Some(self.terrain.as_ref()),
+            MapDisplayMode::Provinces => Some(self.provinces.as_ref()),
+            MapDisplayMode::Rivers => Some(self.rivers.as_ref()),
+            MapDisplayMode::StrategicRegions => self.strategic_region_map.as_deref(),
+            MapDisplayMode::States => self.state_map.as_deref(),
+            MapDisplayMode::Continents => self.continent_map.as_deref(),
+            MapDisplayMode::DefinitionTerrain => self.definition_terrain_map.as_deref(),
+            // The elevation map is tinted from the heightmap whenever it is asked for
+            MapDisplayMode::Elevation => None,
+            MapDisplayMode::Trees => self.trees.as_deref(),
+            MapDisplayMode::NormalMap => self.normal_map.as_deref(),
+            MapDisplayMode::Cities => self.cities_map.as_deref(),
+            MapDisplayMode::BuildingDensity
+            | MapDisplayMode::Weather
+            | MapDisplayMode::Manpower
+            | MapDisplayMode::Political
+            | MapDisplayMode::StateCategories => None,
+        }
+    }
+
+    /// Gets the image shown in a display mode to edit, if it has been loaded or generated.  An
+    /// image still shared with a texture that is loading is copied first.
+    fn image_mut(&mut self, mode: MapDisplayMode) -> Option<&mut RgbImage> {
+        match mode {
+            MapDisplayMode::HeightMap => Some(Arc::make_mut(&mut self.heightmap)),
+            MapDisplayMode::Terrain => Some(Arc::make_mut(&mut self.terrain)),
+            MapDisplayMode::Provinces => Some(Arc::make_mut(&mut self.provinces)),
+            MapDisplayMode::Rivers => Some(Arc::make_mut(&mut self.rivers)),
+            MapDisplayMode::StrategicRegions => {
+                self.strategic_region_map.as_mut().map(Arc::make_mut)
+            }
+            MapDisplayMode::States => self.state_map.as_mut().map(Arc::make_mut),
+            MapDisplayMode::Continents => self.continent_map.as_mut().map(Arc::make_mut),
+            MapDisplayMode::DefinitionTerrain => {
+                self.definition_terrain_map.as_mut().map(Arc::make_mut)
+            }
+            MapDisplayMode::Elevation => None,
+            MapDisplayMode::Trees => self.trees.as_mut().map(Arc::make_mut),
+            MapDisplayMode::NormalMap => self.normal_map.as_mut().map(Arc::make_mut),
+            MapDisplayMode::Cities => self.cities_map.as_mut().map(Arc::make_mut),
+            MapDisplayMode::BuildingDensity
+            | MapDisplayMode::Weather
+            | MapDisplayMode::Manpower
+            | MapDisplayMode::Political
+            | MapDisplayMode::StateCategories => None,
+        }
+    }
+
+    /// Records an edit in the undo history, logging rather than failing the edit if it cannot be
+    /// recorded
+    fn record(&mut self, edit: Edit) {
+        if let Err(e) = self.history.push(edit) {
+            warn!("Failed to record the edit for undoing: {e}");
+        }
+    }
+
+    /// Checks the images changed by undoing or redoing an edit again, returning their display
+    /// modes
+    fn after_history_change(&mut self, modes: Option<Vec<MapDisplayMode>>) -> Vec<MapDisplayMode> {
+        let modes = modes.unwrap_or_default();
+        if modes.contains(&MapDisplayMode::Rivers) {
+            self.river_issues = verify_rivers(&self.rivers, self.wrapping);
+        }
+        if modes.contains(&MapDisplayMode::Provinces) {
+            self.province_index.refresh(&self.provinces);
+        }
+        modes
+    }
+}
+
+impl Editable for Map {
+    #[inline]
+    fn image_mut(&mut self, mode: MapDisplayMode) -> Option<&mut RgbImage> {
+        Map::image_mut(self, mode)
+    }
+
+    #[inline]
+    fn replace_state(&mut self, id: StateId, state: Option<State>) {
+        match state {
+            Some(s) => {
+                self.states.insert(id, s);
+            }
+            None => {
+                self.states.remove(&id);
+            }
+        }
+        self.dirty_states.insert(id);
+    }
+}
+
+impl Actor for Map {
+    type Context = Context<Self>;
+}
+
+/// A request to get a `ProvinceId` from a supplied texture uv coordinate
 #[derive(Message, Debug)]
-#[rtype(result = "Option<State>")]
+#[rtype(result = "Option<ProvinceId>")]
 #[non_exhaustive]
-pub struct GetStateFromId(pub StateId);
+pub struct GetProvinceIdFromPoint(pub Pos2);
 
-impl GetStateFromId {
-    /// Creates a new request for a state id
+impl GetProvinceIdFromPoint {
+    /// Creates a new request for a province id
     #[inline]
     #[must_use]
-    pub const fn new(id: StateId) -> Self {
-        Self(id)
+    pub const fn new(pos: Pos2) -> Self {
+        Self(pos)
     }
 }
 
-/// A request to get a `Continent` from a supplied `ContinentIndex`
+/// A request to get a `StrategicRegionId` from a supplied texture uv coordinate
 #[derive(Message, Debug)]
-#[rtype(result = "Option<Continent>")]
+#[rtype(result = "Option<StrategicRegionId>")]
 #[non_exhaustive]
-pub struct GetContinentFromIndex(pub ContinentIndex);
+pub struct GetStrategicRegionIdFromPoint(pub Pos2);
 
-impl GetContinentFromIndex {
-    /// Creates a new request for a province id
+impl GetStrategicRegionIdFromPoint {
+    /// Creates a new request for a strategic region id
     #[inline]
     #[must_use]
-    pub const fn new(index: ContinentIndex) -> Self {
-        Self(index)
+    pub const fn new(pos: Pos2) -> Self {
+        Self(pos)
     }
 }
 
-/// A request to generate a strategic region map
+/// A request to get a `StrategicRegionId` from a supplied texture uv coordinate
 #[derive(Message, Debug)]
-#[rtype(result = "()")]
-pub struct GenerateStrategicRegionMap;
+#[rtype(result = "Option<StateId>")]
+#[non_exhaustive]
+pub struct GetStateIdFromPoint(pub Pos2);
 
-/// A request to generate a state map
+impl GetStateIdFromPoint {
+    /// Creates a new request for a state id
+    #[inline]
+    #[must_use]
+    pub const fn new(pos: Pos2) -> Self {
+        Self(pos)
+    }
+}
+
+/// A request to get a `Definition` from a supplied `ProvinceId`
 #[derive(Message, Debug)]
-#[rtype(result = "()")]
-pub struct GenerateStateMap;
+#[rtype(result = "Option<Definition>")]
+#[non_exhaustive]
+pub struct GetProvinceDefinitionFromId(pub ProvinceId);
 
-/// A request to update the strategic region map
-#[derive(Message)]
-#[rtype(result = "()")]
+impl GetProvinceDefinitionFromId {
+    /// Creates a new request for a province id
+    #[inline]
+    #[must_use]
+    pub const fn new(id: ProvinceId) -> Self {
+        Self(id)
+    }
+}
+
+/// A request to get a `StrategicRegion` from a given `StrategicRegionId`
+#[derive(Message, Debug)]
+#[rtype(result = "Option<StrategicRegion>")]
 #[non_exhaustive]
-struct UpdateStrategicRegionMap(RgbImage);
+pub struct GetStrategicRegionFromId(pub StrategicRegionId);
 
-/// A request to update the state map
-#[derive(Message)]
-#[rtype(result = "()")]
+impl GetStrategicRegionFromId {
+    /// Creates a new request for a strategic region id
+    #[inline]
+    #[must_use]
+    pub const fn new(id: StrategicRegionId) -> Self {
+        Self(id)
+    }
+}
+
+/// A request to get a `State` from a given `StateId`.
+#[derive(Message, Debug)]
+#[rtype(result = "Option<State>")]
 #[non_exhaustive]
-struct UpdateStateMap(RgbImage);
+pub struct GetStateFromId(pub StateId);
 
-/// A request to get an `RgbImage` from a supplied `MapDisplayMode`
-#[allow(clippy::exhaustive_enums)]
+impl GetStateFromId {
+    /// Creates a new request for a state id
+    #[inline]
+    #[must_use]
+    pub const fn new(id: StateId) -> Self {
+        Self(id)
+    }
+}
+
+/// A request to find the provinces, states and strategic regions named by a query, such as an id
+/// from an error log of the game or part of a name
 #[derive(Message, Debug)]
-#[rtype(result = "Option<RgbImage>")]
-pub enum GetMapImage {
-    HeightMap,
-    Terrain,
-    Provinces,
-    Rivers,
-    StrategicRegions,
-    States,
+#[rtype(result = "Vec<SearchResult>")]
+#[non_exhaustive]
+pub struct Search(pub String);
+
+impl Search {
+    /// Creates a new request to search for a query
+    #[inline]
+    #[must_use]
+    pub const fn new(query: String) -> Self {
+        Self(query)
+    }
 }
 
-impl From<MapDisplayMode> for GetMapImage {
+/// A request to get the pixel at the center of a province, state or strategic region, if it has
+/// any pixels on the map
+#[derive(Message, Debug)]
+#[rtype(result = "Option<(u32, u32)>")]
+#[non_exhaustive]
+pub struct LocateSearchTarget(pub SearchTarget);
+
+impl LocateSearchTarget {
+    /// Creates a new request for the center of a search target
     #[inline]
-    fn from(mode: MapDisplayMode) -> Self {
-        match mode {
-            MapDisplayMode::HeightMap => Self::HeightMap,
-            MapDisplayMode::Terrain => Self::Terrain,
-            MapDisplayMode::Provinces => Self::Provinces,
-            MapDisplayMode::Rivers => Self::Rivers,
-            MapDisplayMode::StrategicRegions => Self::StrategicRegions,
-            MapDisplayMode::States => Self::States,
-        }
+    #[must_use]
+    pub const fn new(target: SearchTarget) -> Self {
+        Self(target)
     }
 }
 
-impl Handler<GetMapImage> for Map {
-    type Result = Option<RgbImage>;
+/// A request to get the file a state was loaded from, or `None` for a state created since.
+#[derive(Message, Debug)]
+#[rtype(result = "Option<Provenance>")]
+#[non_exhaustive]
+pub struct GetStateProvenance(pub StateId);
 
+impl GetStateProvenance {
+    /// Creates a new request for the file of a state
     #[inline]
-    fn handle(&mut self, msg: GetMapImage, _ctx: &mut Context<Self>) -> Self::Result {
-        match msg {
-            GetMapImage::HeightMap => Some(self.heightmap.clone()),
-            GetMapImage::Terrain => Some(self.terrain.clone()),
-            GetMapImage::Provinces => Some(self.provinces.clone()),
-            GetMapImage::Rivers => Some(self.rivers.clone()),
-            GetMapImage::StrategicRegions => self.strategic_region_map.clone(),
-            GetMapImage::States => self.state_map.clone(),
-        }
+    #[must_use]
+    pub const fn new(id: StateId) -> Self {
+        Self(id)
     }
 }
 
-impl Handler<GetProvinceIdFromPoint> for Map {
-    type Result = Option<ProvinceId>;
+/// A request to get the file a strategic region was loaded from, or `None` for a strategic
+/// region created since.
+#[derive(Message, Debug)]
+#[rtype(result = "Option<Provenance>")]
+#[non_exhaustive]
+pub struct GetStrategicRegionProvenance(pub StrategicRegionId);
 
+impl GetStrategicRegionProvenance {
+    /// Creates a new request for the file of a strategic region
     #[inline]
-    fn handle(&mut self, msg: GetProvinceIdFromPoint, _ctx: &mut Context<Self>) -> Self::Result {
-        let point = msg.0;
-        self.province_id_from_point(point)
+    #[must_use]
+    pub const fn new(id: StrategicRegionId) -> Self {
+        Self(id)
     }
 }
 
-impl Handler<GetStrategicRegionIdFromPoint> for Map {
-    type Result = Option<StrategicRegionId>;
+/// A request to get the adjacencies from or to a province, each with the line of the adjacencies
+/// file it was loaded from, or `None` for an adjacency added since.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<(Adjacency, Option<Provenance>)>")]
+#[non_exhaustive]
+pub struct GetProvinceAdjacencies(pub ProvinceId);
+
+impl GetProvinceAdjacencies {
+    /// Creates a new request for the adjacencies of a province
     #[inline]
-    fn handle(
-        &mut self,
-        msg: GetStrategicRegionIdFromPoint,
-        _ctx: &mut Context<Self>,
-    ) -> Self::Result {
-        let point = msg.0;
-        if self.strategic_region_map.is_some() {
-            let color = self.provinces.get_pixel(point.x as u32, point.y as u32);
-            let province_id = self.provinces_by_color.get(color).copied();
-            if let Some(id) = province_id {
-                return self.strategic_regions_by_province.get(&id).copied();
-            }
-        }
+    #[must_use]
+    pub const fn new(id: ProvinceId) -> Self {
+        Self(id)
+    }
+}
 
-        None
+/// A request to get the buildings of a state, each with the line of `buildings.txt` it was loaded
+/// from.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<(StateBuilding, Option<Provenance>)>")]
+#[non_exhaustive]
+pub struct GetStateBuildings(pub StateId);
+
+impl GetStateBuildings {
+    /// Creates a new request for the buildings of a state
+    #[inline]
+    #[must_use]
+    pub const fn new(id: StateId) -> Self {
+        Self(id)
     }
 }
 
-impl Handler<GetStateIdFromPoint> for Map {
-    type Result = Option<StateId>;
+/// A request to set the victory points of a province in the history of the state it belongs to,
+/// or to remove them for `None`.  Returns the edited state.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<State, MapError>")]
+#[non_exhaustive]
+pub struct SetVictoryPoints {
+    /// The province to give the victory points
+    pub province: ProvinceId,
+    /// The victory points to give the province
+    pub points: Option<VictoryPoints>,
+}
 
+impl SetVictoryPoints {
+    /// Creates a new request to set the victory points of a province
     #[inline]
-    fn handle(&mut self, msg: GetStateIdFromPoint, _ctx: &mut Self::Context) -> Self::Result {
+    #[must_use]
+    pub const fn new(province: ProvinceId, points: Option<VictoryPoints>) -> Self {
+        Self { province, points }
+    }
+}
+
+/// A request to edit a value of a state, marking it to be saved.  Returns the edited state.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<State, MapError>")]
+#[non_exhaustive]
+pub struct EditState {
+    /// The state to edit
+    pub id: StateId,
+    /// The edit to apply
+    pub edit: StateEdit,
+}
+
+impl EditState {
+    /// Creates a new request to edit a state
+    #[inline]
+    #[must_use]
+    pub const fn new(id: StateId, edit: StateEdit) -> Self {
+        Self { id, edit }
+    }
+}
+
+/// A request to get the localised text of keys such as the names of states and victory points.
+/// Returns the text of each key in turn, `None` for a key without text.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<Option<String>>")]
+#[non_exhaustive]
+pub struct GetLocalisedTexts(pub Vec<String>);
+
+impl GetLocalisedTexts {
+    /// Creates a new request to get the localised text of keys
+    #[inline]
+    #[must_use]
+    pub const fn new(keys: Vec<String>) -> Self {
+        Self(keys)
+    }
+}
+
+/// A request to set the localised text of a key, adding the key if it is new.  The edit is
+/// written with the edited states.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetLocalisedText {
+    /// The localisation key, such as `STATE_1`
+    pub key: String,
+    /// The text of the key
+    pub text: String,
+}
+
+impl SetLocalisedText {
+    /// Creates a new request to set the localised text of a key
+    #[inline]
+    #[must_use]
+    pub const fn new(key: String, text: String) -> Self {
+        Self { key, text }
+    }
+}
+
+/// A request to get the categories defined in `common/state_category` and those of the loaded
+/// states, sorted by name
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<StateCategoryName>")]
+pub struct GetStateCategories;
+
+/// A request to get a `Continent` from a supplied `ContinentIndex`
+#[derive(Message, Debug)]
+#[rtype(result = "Option<Continent>")]
+#[non_exhaustive]
+pub struct GetContinentFromIndex(pub ContinentIndex);
+
+impl GetContinentFromIndex {
+    /// Creates a new request for a province id
+    #[inline]
+    #[must_use]
+    pub const fn new(index: ContinentIndex) -> Self {
+        Self(index)
+    }
+}
+
+/// A request to get the adjacency rules of the map
+#[derive(Message, Debug)]
+#[rtype(result = "AdjacencyRules")]
+pub struct GetAdjacencyRules;
+
+/// A request to apply a `HeightBrush` to the heightmap at a supplied texture uv coordinate
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct PaintHeightMap {
+    /// The point at the center of the brush
+    pub point: Pos2,
+    /// The brush to apply
+    pub brush: HeightBrush,
+}
+
+impl PaintHeightMap {
+    /// Creates a new request to paint the heightmap
+    #[inline]
+    #[must_use]
+    pub const fn new(point: Pos2, brush: HeightBrush) -> Self {
+        Self { point, brush }
+    }
+}
+
+/// A request to apply a `RiverBrush` to the rivers image along a stroke between two texture uv
+/// coordinates
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct PaintRivers {
+    /// The previous point of the stroke, if the stroke has just started this is `None`
+    pub from: Option<Pos2>,
+    /// The current point of the stroke
+    pub to: Pos2,
+    /// The brush to apply
+    pub brush: RiverBrush,
+}
+
+impl PaintRivers {
+    /// Creates a new request to paint the rivers
+    #[inline]
+    #[must_use]
+    pub const fn new(from: Option<Pos2>, to: Pos2, brush: RiverBrush) -> Self {
+        Self { from, to, brush }
+    }
+}
+
+/// A request to apply a `TerrainBrush` to terrain.bmp at a supplied texture uv coordinate
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct PaintTerrain {
+    /// The point at the center of the brush
+    pub point: Pos2,
+    /// The brush to apply
+    pub brush: TerrainBrush,
+}
+
+impl PaintTerrain {
+    /// Creates a new request to paint the terrain
+    #[inline]
+    #[must_use]
+    pub const fn new(point: Pos2, brush: TerrainBrush) -> Self {
+        Self { point, brush }
+    }
+}
+
+/// A request to verify the whole rivers image
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct VerifyRivers;
+
+/// A request to get the issues found on the rivers image
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<RiverIssue>")]
+pub struct GetRiverIssues;
+
+/// A request to render a plugin overlay layer
+#[derive(Message, Debug)]
+#[rtype(result = "RgbaImage")]
+#[non_exhaustive]
+pub struct RenderLayer(pub Arc<dyn MapLayer>);
+
+impl RenderLayer {
+    /// Creates a new request to render a layer
+    #[inline]
+    #[must_use]
+    pub const fn new(layer: Arc<dyn MapLayer>) -> Self {
+        Self(layer)
+    }
+}
+
+/// A request to apply a plugin tool at a supplied texture uv coordinate
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<MapDisplayMode>")]
+#[non_exhaustive]
+pub struct ApplyTool {
+    /// The tool to apply
+    pub tool: Arc<dyn MapTool>,
+    /// The point the tool is applied at
+    pub point: Pos2,
+}
+
+impl ApplyTool {
+    /// Creates a new request to apply a tool
+    #[inline]
+    #[must_use]
+    pub const fn new(tool: Arc<dyn MapTool>, point: Pos2) -> Self {
+        Self { tool, point }
+    }
+}
+
+/// A request to draw a layer highlighting the pixels changed by the edits that have not been
+/// applied yet to the image of a display mode
+#[derive(Message, Debug)]
+#[rtype(result = "Option<RgbaImage>")]
+#[non_exhaustive]
+pub struct GetEditPreview(pub MapDisplayMode);
+
+impl GetEditPreview {
+    /// Creates a new request to draw the preview of the pending edits
+    #[inline]
+    #[must_use]
+    pub const fn new(mode: MapDisplayMode) -> Self {
+        Self(mode)
+    }
+}
+
+/// A request to draw a layer highlighting the selected provinces, such as the provinces of the
+/// selected state
+#[derive(Message, Debug)]
+#[rtype(result = "RgbaImage")]
+#[non_exhaustive]
+pub struct GetSelectionHighlight(pub HashSet<ProvinceId>);
+
+impl GetSelectionHighlight {
+    /// Creates a new request to highlight the given provinces
+    #[inline]
+    #[must_use]
+    pub const fn new(provinces: HashSet<ProvinceId>) -> Self {
+        Self(provinces)
+    }
+}
+
+/// A request to take the patch of the texture of a display mode covering the areas painted since
+/// the last patch, shrunk to fit the maximum side of a texture.  Returns `None` if nothing was
+/// painted.
+#[derive(Message, Debug)]
+#[rtype(result = "Option<TexturePatch>")]
+#[non_exhaustive]
+pub struct TakeTexturePatch {
+    /// The display mode of the texture
+    pub mode: MapDisplayMode,
+    /// The longest side of a texture
+    pub max_side: usize,
+}
+
+impl TakeTexturePatch {
+    /// Creates a new request to take the patch of a texture
+    #[inline]
+    #[must_use]
+    pub const fn new(mode: MapDisplayMode, max_side: usize) -> Self {
+        Self { mode, max_side }
+    }
+}
+
+/// A request to keep the edits that have not been applied yet.  Returns the display modes of the
+/// edited images.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<MapDisplayMode>")]
+pub struct ApplyEdits;
+
+/// A request to undo the edits that have not been applied yet.  Returns the display modes of the
+/// restored images.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<MapDisplayMode>")]
+pub struct CancelEdits;
+
+/// A request to undo the last applied edit.  Returns the display modes of the images it changed,
+/// which is empty if there was nothing to undo or edits are still pending.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<Vec<MapDisplayMode>, MapError>")]
+#[non_exhaustive]
+pub struct Undo;
+
+/// A request to redo the last undone edit.  Returns the display modes of the images it changed,
+/// which is empty if there was nothing to redo or edits are still pending.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<Vec<MapDisplayMode>, MapError>")]
+#[non_exhaustive]
+pub struct Redo;
+
+/// A request to set each water province to a lake or sea depending on whether it is enclosed
+/// by land.  Returns the ids of the provinces whose type changed.
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<ProvinceId>")]
+#[non_exhaustive]
+pub struct ClassifyLakes(pub LakeClassifier);
+
+impl ClassifyLakes {
+    /// Creates a new request to classify lakes
+    #[inline]
+    #[must_use]
+    pub const fn new(classifier: LakeClassifier) -> Self {
+        Self(classifier)
+    }
+}
+
+/// A request to watch a root directory, reloading the files of the map changed outside of the
+/// editor until the map is dropped or another root directory is watched
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct WatchRootPath(pub PathBuf);
+
+impl WatchRootPath {
+    /// Creates a new request to watch a root directory
+    #[inline]
+    #[must_use]
+    pub const fn new(root_path: PathBuf) -> Self {
+        Self(root_path)
+    }
+}
+
+/// A request to reload a file of the watched root directory that changed on disk.  Failures are
+/// logged, as the request is sent by the watcher.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ReloadWatchedFile(pub WatchedFile);
+
+impl ReloadWatchedFile {
+    /// Creates a new request to reload a watched file
+    #[inline]
+    #[must_use]
+    pub const fn new(file: WatchedFile) -> Self {
+        Self(file)
+    }
+}
+
+/// A request to load a component whose loading was put off when the map was loaded.  Returns
+/// whether it was loaded, which is `false` if it already was.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<bool, MapError>")]
+#[non_exhaustive]
+pub struct LoadComponent(pub MapComponent);
+
+impl LoadComponent {
+    /// Creates a new request to load a component
+    #[inline]
+    #[must_use]
+    pub const fn new(component: MapComponent) -> Self {
+        Self(component)
+    }
+}
+
+/// A request to recolor the provinces to a structured color scheme, keeping their ids
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct RecolorProvinces(pub ColorScheme);
+
+impl RecolorProvinces {
+    /// Creates a new request to recolor the provinces
+    #[inline]
+    #[must_use]
+    pub const fn new(scheme: ColorScheme) -> Self {
+        Self(scheme)
+    }
+}
+
+/// A request to repaint terrain.bmp within each province to match the terrain of its definition,
+/// dithering the edges of the provinces if set.  Returns the number of pixels changed.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<usize, MapError>")]
+#[non_exhaustive]
+pub struct SyncTerrain {
+    /// Whether to dither the terrain along the edges of the provinces
+    pub dither: bool,
+}
+
+impl SyncTerrain {
+    /// Creates a new request to sync the terrain
+    #[inline]
+    #[must_use]
+    pub const fn new(dither: bool) -> Self {
+        Self { dither }
+    }
+}
+
+/// A request to fix every corner of provinces.bmp where four provinces meet by giving one of its
+/// pixels to a neighboring province.  Returns the number of corners fixed.
+#[derive(Message, Debug)]
+#[rtype(result = "usize")]
+#[non_exhaustive]
+pub struct FixXCrossings;
+
+/// A request to search for straits between land provinces that are not connected yet.  Returns
+/// the number of straits found, which replace any that have not been reviewed yet.
+#[derive(Message, Debug)]
+#[rtype(result = "usize")]
+#[non_exhaustive]
+pub struct FindStraits(pub StraitFinder);
+
+impl FindStraits {
+    /// Creates a new request to find straits
+    #[inline]
+    #[must_use]
+    pub const fn new(finder: StraitFinder) -> Self {
+        Self(finder)
+    }
+}
+
+/// A request to get the straits that have not been reviewed yet
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<StraitCandidate>")]
+pub struct GetStraitCandidates;
+
+/// A request to accept or reject the suggested strait between two provinces.  An accepted strait
+/// is added to the adjacencies of the map.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ReviewStrait {
+    /// The province the strait starts from
+    pub from: ProvinceId,
+    /// The province the strait ends at
+    pub to: ProvinceId,
+    /// Whether to add the strait to the adjacencies
+    pub accept: bool,
+}
+
+impl ReviewStrait {
+    /// Creates a new request to review a strait
+    #[inline]
+    #[must_use]
+    pub const fn new(from: ProvinceId, to: ProvinceId, accept: bool) -> Self {
+        Self { from, to, accept }
+    }
+}
+
+/// A request to get how the edges of the map connect to each other
+#[derive(Message, Debug)]
+#[rtype(result = "Wrapping")]
+pub struct GetWrapping;
+
+/// A request to set how the edges of the map connect to each other.  The rivers are verified
+/// again, as rivers may now continue across an edge.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetWrapping(pub Wrapping);
+
+impl SetWrapping {
+    /// Creates a new request to set the wrapping of the map
+    #[inline]
+    #[must_use]
+    pub const fn new(wrapping: Wrapping) -> Self {
+        Self(wrapping)
+    }
+}
+
+/// A request to save a support bundle with the diagnostics of the map
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct CreateSupportBundle {
+    /// The path to save the bundle to
+    pub path: PathBuf,
+    /// The path to the root Hearts of Iron IV directory the map was loaded from
+    pub root_path: PathBuf,
+    /// The log of the application
+    pub log: String,
+    /// The settings of the application
+    pub settings: String,
+}
+
+impl CreateSupportBundle {
+    /// Creates a new request to save a support bundle
+    #[inline]
+    #[must_use]
+    pub const fn new(path: PathBuf, root_path: PathBuf, log: String, settings: String) -> Self {
+        Self {
+            path,
+            root_path,
+            log,
+            settings,
+        }
+    }
+}
+
+/// A request to save a generated region map as an image, optionally with a legend next to it
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct ExportRegionMap {
+    /// The region map to save
+    pub region_map: RegionMap,
+    /// The path to save the image to
+    pub path: PathBuf,
+    /// The format of the legend to save next to the image, if any
+    pub legend: Option<LegendFormat>,
+}
+
+impl ExportRegionMap {
+    /// Creates a new request to save a region map
+    #[inline]
+    #[must_use]
+    pub const fn new(region_map: RegionMap, path: PathBuf, legend: Option<LegendFormat>) -> Self {
+        Self {
+            region_map,
+            path,
+            legend,
+        }
+    }
+}
+
+/// A request to save a poster of a display mode with layers drawn over it, independent of the
+/// part of the map shown in the editor
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct ExportPoster {
+    /// The display mode the poster is drawn from
+    pub mode: MapDisplayMode,
+    /// The layers drawn over the display mode in order, each with its opacity
+    pub layers: Vec<(Arc<dyn MapLayer>, f32)>,
+    /// How the poster is drawn
+    pub options: PosterOptions,
+    /// The path to save the poster to
+    pub path: PathBuf,
+}
+
+impl ExportPoster {
+    /// Creates a new request to save a poster
+    #[inline]
+    #[must_use]
+    pub fn new(
+        mode: MapDisplayMode,
+        layers: Vec<(Arc<dyn MapLayer>, f32)>,
+        options: PosterOptions,
+        path: PathBuf,
+    ) -> Self {
+        Self {
+            mode,
+            layers,
+            options,
+            path,
+        }
+    }
+}
+
+/// A request to save the outlines of the provinces, states and strategic regions as GeoJSON files
+/// in a directory, named `provinces.geojson`, `states.geojson` and `strategic_regions.geojson`
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct ExportGeoJson(pub PathBuf);
+
+impl ExportGeoJson {
+    /// Creates a new request to save the GeoJSON files to a directory
+    #[inline]
+    #[must_use]
+    pub const fn new(directory: PathBuf) -> Self {
+        Self(directory)
+    }
+}
+
+/// A request to save the states edited since they were last saved to the `history/states/`
+/// directory of a root directory, along with the edited localisation of their names
+#[derive(Message, Debug)]
+#[rtype(result = "Result<(), MapError>")]
+#[non_exhaustive]
+pub struct SaveStates(pub PathBuf);
+
+impl SaveStates {
+    /// Creates a new request to save the edited states
+    #[inline]
+    #[must_use]
+    pub const fn new(root_path: PathBuf) -> Self {
+        Self(root_path)
+    }
+}
+
+/// A request to run every check of the map
+#[derive(Message, Debug)]
+#[rtype(result = "ValidationReport")]
+#[non_exhaustive]
+pub struct Validate;
+
+/// A request to get the pixel of the provinces image a finding of a validation is at
+#[derive(Message, Debug)]
+#[rtype(result = "Option<(u32, u32)>")]
+#[non_exhaustive]
+pub struct LocateFinding(pub Finding);
+
+impl LocateFinding {
+    /// Creates a new request to locate a finding
+    #[inline]
+    #[must_use]
+    pub const fn new(finding: Finding) -> Self {
+        Self(finding)
+    }
+}
+
+/// A request to get the width and height of the map in pixels
+#[derive(Message, Debug)]
+#[rtype(result = "(u32, u32)")]
+#[non_exhaustive]
+pub struct GetMapSize;
+
+/// A request to apply fixes of the findings of a validation and write the files they change to a
+/// root directory.  Returns the number of fixes applied.
+#[derive(Message, Debug)]
+#[rtype(result = "Result<usize, MapError>")]
+#[non_exhaustive]
+pub struct ApplyFixes {
+    /// The root directory to write the changed files to
+    pub root_path: PathBuf,
+    /// The fixes to apply
+    pub fixes: Vec<AutoFix>,
+}
+
+impl ApplyFixes {
+    /// Creates a new request to apply fixes
+    #[inline]
+    #[must_use]
+    pub const fn new(root_path: PathBuf, fixes: Vec<AutoFix>) -> Self {
+        Self { root_path, fixes }
+    }
+}
+
+/// A request to generate a strategic region map
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct GenerateStrategicRegionMap;
+
+/// A request to generate a state map
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct GenerateStateMap;
+
+/// A request to generate a map of the continents of the provinces
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct GenerateContinentMap;
+
+/// A request to generate a map of the terrain of the provinces in the definitions
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct GenerateDefinitionTerrainMap;
+
+/// A request to set the height below which the elevation map shades the heightmap as sea
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetElevationSeaLevel(pub u8);
+
+impl SetElevationSeaLevel {
+    /// Creates a new request to set the sea level of the elevation map
+    #[inline]
+    #[must_use]
+    pub const fn new(sea_level: u8) -> Self {
+        Self(sea_level)
+    }
+}
+
+/// A request to get the height below which the elevation map shades the heightmap as sea
+#[derive(Message, Debug)]
+#[rtype(result = "u8")]
+pub struct GetElevationSeaLevel;
+
+/// A request to set which buildings the heat map of the buildings counts and what it counts them
+/// by
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetBuildingDensity(pub BuildingDensity);
+
+impl SetBuildingDensity {
+    /// Creates a new request to set the settings of the heat map of the buildings
+    #[inline]
+    #[must_use]
+    pub const fn new(density: BuildingDensity) -> Self {
+        Self(density)
+    }
+}
+
+/// A request to get which buildings the heat map of the buildings counts and what it counts them
+/// by
+#[derive(Message, Debug)]
+#[rtype(result = "BuildingDensity")]
+pub struct GetBuildingDensity;
+
+/// A request to get the building types of the map, sorted by name
+#[derive(Message, Debug)]
+#[rtype(result = "Vec<BuildingId>")]
+pub struct GetBuildingTypes;
+
+/// A request to set the day and measure the map of the weather is drawn for
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetWeatherView(pub WeatherView);
+
+impl SetWeatherView {
+    /// Creates a new request to set the day and measure of the map of the weather
+    #[inline]
+    #[must_use]
+    pub const fn new(view: WeatherView) -> Self {
+        Self(view)
+    }
+}
+
+/// A request to get the day and measure the map of the weather is drawn for
+#[derive(Message, Debug)]
+#[rtype(result = "WeatherView")]
+pub struct GetWeatherView;
+
+/// A request to get the colors of the map of the weather as it was last drawn
+#[derive(Message, Debug)]
+#[rtype(result = "Legend")]
+pub struct GetWeatherLegend;
+
+/// A request to get the least and most manpower of a state as the map of manpower was last
+/// drawn
+#[derive(Message, Debug)]
+#[rtype(result = "Legend")]
+pub struct GetManpowerLegend;
+
+/// A request to get the countries owning the states as the political map was last drawn
+#[derive(Message, Debug)]
+#[rtype(result = "Legend")]
+pub struct GetPoliticalLegend;
+
+/// A request to get the categories of the states as the map of state categories was last drawn
+#[derive(Message, Debug)]
+#[rtype(result = "Legend")]
+pub struct GetStateCategoryLegend;
+
+/// A request to get the colors of the continents on the map of continents
+#[derive(Message, Debug)]
+#[rtype(result = "Legend")]
+pub struct GetContinentLegend;
+
+/// A request to update the strategic region map
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+struct UpdateStrategicRegionMap(RgbImage, Legend);
+
+/// A request to update the state map
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+struct UpdateStateMap(RgbImage, Legend);
+
+/// A request to update the map of continents
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+struct UpdateContinentMap(RgbImage, Legend);
+
+/// A request to update the map of the terrain of the definitions
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+struct UpdateDefinitionTerrainMap(RgbImage);
+
+/// A request to get an `RgbImage` from a supplied `MapDisplayMode`.  The image is shared rather
+/// than copied, as the largest images are tens of megabytes.  A layer whose loading was put off
+/// is loaded when it is first asked for.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Message, Debug)]
+#[rtype(result = "Option<Arc<RgbImage>>")]
+pub enum GetMapImage {
+    HeightMap,
+    Terrain,
+    Provinces,
+    Rivers,
+    StrategicRegions,
+    States,
+    Continents,
+    DefinitionTerrain,
+    Elevation,
+    Trees,
+    NormalMap,
+    Cities,
+    BuildingDensity,
+    Weather,
+    Manpower,
+    Political,
+    StateCategories,
+}
+
+impl From<MapDisplayMode> for GetMapImage {
+    #[inline]
+    fn from(mode: MapDisplayMode) -> Self {
+        match mode {
+            MapDisplayMode::HeightMap => Self::HeightMap,
+            MapDisplayMode::Terrain => Self::Terrain,
+            MapDisplayMode::Provinces => Self::Provinces,
+            MapDisplayMode::Rivers => Self::Rivers,
+            MapDisplayMode::StrategicRegions => Self::StrategicRegions,
+            MapDisplayMode::States => Self::States,
+            MapDisplayMode::Continents => Self::Continents,
+            MapDisplayMode::DefinitionTerrain => Self::DefinitionTerrain,
+            MapDisplayMode::Elevation => Self::Elevation,
+            MapDisplayMode::Trees => Self::Trees,
+            MapDisplayMode::NormalMap => Self::NormalMap,
+            MapDisplayMode::Cities => Self::Cities,
+            MapDisplayMode::BuildingDensity => Self::BuildingDensity,
+            MapDisplayMode::Weather => Self::Weather,
+            MapDisplayMode::Manpower => Self::Manpower,
+            MapDisplayMode::Political => Self::Political,
+            MapDisplayMode::StateCategories => Self::StateCategories,
+        }
+    }
+}
+
+impl Handler<GetMapImage> for Map {
+    type Result = Option<Arc<RgbImage>>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetMapImage, _ctx: &mut Context<Self>) -> Self::Result {
+        self.map_image(msg)
+    }
+}
+
+impl Map {
+    /// The image of a display mode, drawing the images of the modes that are drawn on demand
+    fn map_image(&mut self, image: GetMapImage) -> Option<Arc<RgbImage>> {
+        match image {
+            GetMapImage::HeightMap => Some(Arc::clone(&self.heightmap)),
+            GetMapImage::Terrain => Some(Arc::clone(&self.terrain)),
+            GetMapImage::Provinces => Some(Arc::clone(&self.provinces)),
+            GetMapImage::Rivers => Some(Arc::clone(&self.rivers)),
+            GetMapImage::StrategicRegions => self.strategic_region_map.clone(),
+            GetMapImage::States => self.state_map.clone(),
+            GetMapImage::Continents => self.continent_map.clone(),
+            GetMapImage::DefinitionTerrain => self.definition_terrain_map.clone(),
+            // Tinting is quick next to loading the texture, and a fresh tint always matches the
+            // heightmap as edited
+            GetMapImage::Elevation => Some(Arc::new(hypsometric_tint(
+                &self.heightmap,
+                self.elevation_sea_level,
+            ))),
+            GetMapImage::Trees => self.shared_layer(Layer::Trees),
+            GetMapImage::NormalMap => self.shared_layer(Layer::NormalMap),
+            GetMapImage::Cities => self.shared_layer(Layer::Cities),
+            GetMapImage::BuildingDensity => Some(Arc::new(self.building_density_map())),
+            GetMapImage::Weather => Some(Arc::new(self.weather_map())),
+            GetMapImage::Manpower => Some(Arc::new(self.manpower_map())),
+            GetMapImage::Political => Some(Arc::new(self.political_map())),
+            GetMapImage::StateCategories => Some(Arc::new(self.state_category_map())),
+        }
+    }
+}
+
+impl Handler<SetElevationSeaLevel> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: SetElevationSeaLevel, _ctx: &mut Context<Self>) -> Self::Result {
+        self.elevation_sea_level = msg.0;
+    }
+}
+
+impl Handler<SetBuildingDensity> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: SetBuildingDensity, _ctx: &mut Context<Self>) -> Self::Result {
+        self.building_density = msg.0;
+    }
+}
+
+impl Handler<GetBuildingDensity> for Map {
+    type Result = MessageResult<GetBuildingDensity>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetBuildingDensity, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.building_density.clone())
+    }
+}
+
+impl Handler<GetBuildingTypes> for Map {
+    type Result = MessageResult<GetBuildingTypes>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetBuildingTypes, _ctx: &mut Context<Self>) -> Self::Result {
+        let mut types = self.buildings.types.iter().cloned().collect::<Vec<_>>();
+        types.sort();
+        MessageResult(types)
+    }
+}
+
+impl Handler<SetWeatherView> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: SetWeatherView, _ctx: &mut Context<Self>) -> Self::Result {
+        self.weather_view = msg.0;
+    }
+}
+
+impl Handler<GetWeatherView> for Map {
+    type Result = WeatherView;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetWeatherView, _ctx: &mut Context<Self>) -> Self::Result {
+        self.weather_view
+    }
+}
+
+impl Handler<GetWeatherLegend> for Map {
+    type Result = MessageResult<GetWeatherLegend>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetWeatherLegend, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.weather_legend.clone())
+    }
+}
+
+impl Handler<GetManpowerLegend> for Map {
+    type Result = MessageResult<GetManpowerLegend>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetManpowerLegend, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.manpower_legend.clone())
+    }
+}
+
+impl Handler<GetPoliticalLegend> for Map {
+    type Result = MessageResult<GetPoliticalLegend>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetPoliticalLegend, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.political_legend.clone())
+    }
+}
+
+impl Handler<GetStateCategoryLegend> for Map {
+    type Result = MessageResult<GetStateCategoryLegend>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetStateCategoryLegend, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.state_category_legend.clone())
+    }
+}
+
+impl Handler<GetElevationSeaLevel> for Map {
+    type Result = u8;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetElevationSeaLevel, _ctx: &mut Context<Self>) -> Self::Result {
+        self.elevation_sea_level
+    }
+}
+
+impl Handler<GetProvinceIdFromPoint> for Map {
+    type Result = Option<ProvinceId>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetProvinceIdFromPoint, _ctx: &mut Context<Self>) -> Self::Result {
+        let point = msg.0;
+        self.province_id_from_point(point)
+    }
+}
+
+impl Handler<GetStrategicRegionIdFromPoint> for Map {
+    type Result = Option<StrategicRegionId>;
+    #[inline]
+    fn handle(
+        &mut self,
+        msg: GetStrategicRegionIdFromPoint,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        let point = msg.0;
+        if self.strategic_region_map.is_some() {
+            let province_id = self
+                .province_index
+                .province_at(point.x as u32, point.y as u32);
+            if let Some(id) = province_id {
+                return self.strategic_regions_by_province.get(&id).copied();
+            }
+        }
+
+        None
+    }
+}
+
+impl Handler<GetStateIdFromPoint> for Map {
+    type Result = Option<StateId>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetStateIdFromPoint, _ctx: &mut Self::Context) -> Self::Result {
         let point = msg.0;
         if self.state_map.is_some() {
-            let color = self.provinces.get_pixel(point.x as u32, point.y as u32);
-            let province_id = self.provinces_by_color.get(color).copied();
+            let province_id = self
+                .province_index
+                .province_at(point.x as u32, point.y as u32);
             if let Some(id) = province_id {
                 return self.states_by_province.get(&id).copied();
             }
         }
-        None
+        None
+    }
+}
+
+impl Handler<GetStrategicRegionFromId> for Map {
+    type Result = Option<StrategicRegion>;
+    #[inline]
+    fn handle(&mut self, msg: GetStrategicRegionFromId, _ctx: &mut Context<Self>) -> Self::Result {
+        self.strategic_regions
+            .strategic_regions
+            .get(&msg.0)
+            .cloned()
+    }
+}
+
+impl Handler<GetStateFromId> for Map {
+    type Result = Option<State>;
+    #[inline]
+    fn handle(&mut self, msg: GetStateFromId, _ctx: &mut Context<Self>) -> Self::Result {
+        self.states.get(&msg.0).cloned()
+    }
+}
+
+impl Handler<Search> for Map {
+    type Result = MessageResult<Search>;
+
+    #[inline]
+    fn handle(&mut self, msg: Search, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(search(
+            &msg.0,
+            &self.definitions,
+            &self.states,
+            &self.strategic_regions,
+            &self.localisation,
+        ))
+    }
+}
+
+impl Handler<LocateSearchTarget> for Map {
+    type Result = Option<(u32, u32)>;
+
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::integer_arithmetic)]
+    #[inline]
+    fn handle(&mut self, msg: LocateSearchTarget, _ctx: &mut Self::Context) -> Self::Result {
+        let provinces = match msg.0 {
+            SearchTarget::Province(id) => HashSet::from([id]),
+            SearchTarget::State(id) => self.states.get(&id)?.provinces.clone(),
+            SearchTarget::StrategicRegion(id) => self
+                .strategic_regions
+                .strategic_regions
+                .get(&id)?
+                .provinces
+                .clone(),
+        };
+        let (width, _height) = self.province_index.dimensions();
+        let width = u64::from(width.max(1));
+        let (mut sum_x, mut sum_y, mut count) = (0_u64, 0_u64, 0_u64);
+        for (position, province) in (0_u64..).zip(self.province_index.provinces()) {
+            if province.map_or(false, |id| provinces.contains(&id)) {
+                sum_x = sum_x.saturating_add(position % width);
+                sum_y = sum_y.saturating_add(position / width);
+                count = count.saturating_add(1);
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        let count = count as f64;
+        Some((
+            (sum_x as f64 / count).round() as u32,
+            (sum_y as f64 / count).round() as u32,
+        ))
+    }
+}
+
+impl Handler<GetStateProvenance> for Map {
+    type Result = Option<Provenance>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetStateProvenance, _ctx: &mut Self::Context) -> Self::Result {
+        self.state_sources
+            .get(&msg.0)
+            .map(|s| Provenance::file(Path::new("history/states").join(&s.file_name)))
+    }
+}
+
+impl Handler<GetStrategicRegionProvenance> for Map {
+    type Result = Option<Provenance>;
+
+    #[inline]
+    fn handle(
+        &mut self,
+        msg: GetStrategicRegionProvenance,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.strategic_regions
+            .sources
+            .get(&msg.0)
+            .map(|s| Provenance::file(Path::new("map/strategicregions").join(&s.file_name)))
+    }
+}
+
+impl Handler<GetProvinceAdjacencies> for Map {
+    type Result = Vec<(Adjacency, Option<Provenance>)>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetProvinceAdjacencies, _ctx: &mut Self::Context) -> Self::Result {
+        let lines = &self.adjacencies.lines;
+        self.adjacencies
+            .adjacencies
+            .iter()
+            .enumerate()
+            .filter(|(_i, a)| a.from == msg.0 || a.to == msg.0)
+            .map(|(i, a)| {
+                let provenance = lines
+                    .get(i)
+                    .map(|line| Provenance::line(self.adjacencies_path.clone(), *line));
+                (a.clone(), provenance)
+            })
+            .collect()
+    }
+}
+
+impl Handler<GetStateBuildings> for Map {
+    type Result = Vec<(StateBuilding, Option<Provenance>)>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetStateBuildings, _ctx: &mut Self::Context) -> Self::Result {
+        let lines = &self.buildings.lines;
+        self.buildings
+            .buildings
+            .iter()
+            .enumerate()
+            .filter(|(_i, b)| b.state_id == msg.0)
+            .map(|(i, b)| {
+                let provenance = lines
+                    .get(i)
+                    .map(|line| Provenance::line(PathBuf::from("map/buildings.txt"), *line));
+                (b.clone(), provenance)
+            })
+            .collect()
+    }
+}
+
+impl Handler<SetVictoryPoints> for Map {
+    type Result = Result<State, MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: SetVictoryPoints, _ctx: &mut Self::Context) -> Self::Result {
+        let province = msg.province;
+        let state_id = self
+            .states_by_province
+            .get(&province)
+            .ok_or(MapError::StateNotFoundForProvince(province))?;
+        let state = self
+            .states
+            .get_mut(state_id)
+            .ok_or(MapError::StateNotFoundForProvince(province))?;
+        let before = state.clone();
+        state.set_victory_points(province, msg.points)?;
+        self.dirty_states.insert(state.id);
+        debug!(
+            "Set the victory points of province {province} in state {} to {:?}",
+            state.id, msg.points
+        );
+        let after = state.clone();
+        self.record(Edit::new("Set victory points").with_state(
+            after.id,
+            Some(before),
+            Some(after.clone()),
+        ));
+        Ok(after)
+    }
+}
+
+impl Handler<EditState> for Map {
+    type Result = Result<State, MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: EditState, _ctx: &mut Self::Context) -> Self::Result {
+        let state = self
+            .states
+            .get_mut(&msg.id)
+            .ok_or(MapError::StateNotFound(msg.id))?;
+        debug!("Editing state {}: {:?}", msg.id, msg.edit);
+        let before = state.clone();
+        state.apply(msg.edit);
+        let after = state.clone();
+        self.dirty_states.insert(msg.id);
+        self.record(Edit::new("Edit state").with_state(msg.id, Some(before), Some(after.clone())));
+        Ok(after)
+    }
+}
+
+impl Handler<GetLocalisedTexts> for Map {
+    type Result = Vec<Option<String>>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetLocalisedTexts, _ctx: &mut Self::Context) -> Self::Result {
+        msg.0
+            .iter()
+            .map(|key| self.localisation.text(key).map(str::to_owned))
+            .collect()
+    }
+}
+
+impl Handler<SetLocalisedText> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: SetLocalisedText, _ctx: &mut Self::Context) -> Self::Result {
+        self.localisation.set_text(msg.key, msg.text);
+    }
+}
+
+impl Handler<GetStateCategories> for Map {
+    type Result = Vec<StateCategoryName>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetStateCategories, _ctx: &mut Self::Context) -> Self::Result {
+        let categories = self
+            .states
+            .values()
+            .filter_map(State::current_category)
+            .chain(self.state_categories.categories.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        categories.into_iter().collect()
+    }
+}
+
+impl Handler<GetProvinceDefinitionFromId> for Map {
+    type Result = Option<Definition>;
+
+    #[inline]
+    fn handle(
+        &mut self,
+        msg: GetProvinceDefinitionFromId,
+        _ctx: &mut Context<Self>,
+    ) -> Self::Result {
+        self.definitions.definitions.get(&msg.0).cloned()
+    }
+}
+
+impl Handler<GetContinentFromIndex> for Map {
+    type Result = Option<Continent>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetContinentFromIndex, _ctx: &mut Context<Self>) -> Self::Result {
+        let index = msg.0;
+        if index.0 < 1 {
+            return None;
+        }
+        self.continents.continents.get(index.0 - 1).cloned()
+    }
+}
+
+impl Handler<GetAdjacencyRules> for Map {
+    type Result = MessageResult<GetAdjacencyRules>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetAdjacencyRules, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.adjacency_rules.clone())
+    }
+}
+
+impl Handler<PaintHeightMap> for Map {
+    type Result = ();
+
+    #[inline]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn handle(&mut self, msg: PaintHeightMap, _ctx: &mut Self::Context) -> Self::Result {
+        let point = msg.point;
+        if point.x < 0.0 || point.y < 0.0 {
+            return;
+        }
+        self.edit_preview
+            .record(MapDisplayMode::HeightMap, &self.heightmap);
+        self.dirty_regions.mark(
+            MapDisplayMode::HeightMap,
+            brush_area(&self.heightmap, point, msg.brush.radius),
+        );
+        msg.brush.apply(
+            Arc::make_mut(&mut self.heightmap),
+            point.x as u32,
+            point.y as u32,
+        );
+    }
+}
+
+impl Handler<PaintRivers> for Map {
+    type Result = ();
+
+    #[inline]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::integer_arithmetic)]
+    fn handle(&mut self, msg: PaintRivers, _ctx: &mut Self::Context) -> Self::Result {
+        let to = msg.to;
+        if to.x < 0.0 || to.y < 0.0 {
+            return;
+        }
+        let from = msg
+            .from
+            .filter(|f| f.x >= 0.0 && f.y >= 0.0)
+            .map(|f| (f.x as u32, f.y as u32));
+        self.edit_preview
+            .record(MapDisplayMode::Rivers, &self.rivers);
+        let touched = msg.brush.apply(
+            Arc::make_mut(&mut self.rivers),
+            from,
+            (to.x as u32, to.y as u32),
+        );
+        if let Some(area) = touched {
+            self.dirty_regions.mark(MapDisplayMode::Rivers, area);
+            // Neighboring pixels may have become thick or detached, so check around the stroke too.
+            let x = area.x.saturating_sub(1);
+            let y = area.y.saturating_sub(1);
+            let area = Rect {
+                x,
+                y,
+                width: area.x + area.width + 1 - x,
+                height: area.y + area.height + 1 - y,
+            };
+            // The stroke may join or cut the rivers it touches, so their markers are checked again
+            reverify_rivers_in(&self.rivers, &mut self.river_issues, &area, self.wrapping);
+        }
+    }
+}
+
+impl Handler<PaintTerrain> for Map {
+    type Result = ();
+
+    #[inline]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn handle(&mut self, msg: PaintTerrain, _ctx: &mut Self::Context) -> Self::Result {
+        let point = msg.point;
+        if point.x < 0.0 || point.y < 0.0 {
+            return;
+        }
+        self.edit_preview
+            .record(MapDisplayMode::Terrain, &self.terrain);
+        self.dirty_regions.mark(
+            MapDisplayMode::Terrain,
+            brush_area(&self.terrain, point, msg.brush.radius),
+        );
+        msg.brush.apply(
+            Arc::make_mut(&mut self.terrain),
+            point.x as u32,
+            point.y as u32,
+        );
+    }
+}
+
+impl Handler<VerifyRivers> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, _msg: VerifyRivers, _ctx: &mut Self::Context) -> Self::Result {
+        self.river_issues = verify_rivers(&self.rivers, self.wrapping);
+    }
+}
+
+impl Handler<GetRiverIssues> for Map {
+    type Result = Vec<RiverIssue>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetRiverIssues, _ctx: &mut Self::Context) -> Self::Result {
+        self.river_issues.clone()
+    }
+}
+
+impl Handler<RenderLayer> for Map {
+    type Result = MessageResult<RenderLayer>;
+
+    #[inline]
+    fn handle(&mut self, msg: RenderLayer, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(msg.0.render(self))
+    }
+}
+
+impl Handler<ApplyTool> for Map {
+    type Result = Vec<MapDisplayMode>;
+
+    #[inline]
+    fn handle(&mut self, msg: ApplyTool, _ctx: &mut Self::Context) -> Self::Result {
+        // Which images a tool edits is only known once it has been applied, so keep a copy of
+        // each image it could edit that has no pending edits yet.
+        let originals = ALL_MAP_MODES
+            .into_iter()
+            .filter(|m| msg.tool.applies_to(*m) && !self.edit_preview.is_edited(*m))
+            .filter_map(|m| self.image(m).map(|image| (m, image.clone())))
+            .collect::<Vec<_>>();
+        let edited = msg.tool.apply(self, msg.point);
+        for (mode, original) in originals {
+            if edited.contains(&mode) {
+                self.edit_preview.record(mode, &original);
+            }
+        }
+        if edited.contains(&MapDisplayMode::Provinces) {
+            self.province_index.refresh(&self.provinces);
+        }
+        edited
+    }
+}
+
+impl Handler<TakeTexturePatch> for Map {
+    type Result = Option<TexturePatch>;
+
+    #[inline]
+    fn handle(&mut self, msg: TakeTexturePatch, _ctx: &mut Self::Context) -> Self::Result {
+        let area = self.dirty_regions.take(msg.mode)?;
+        texture_patch(self.image(msg.mode)?, area, msg.max_side)
+    }
+}
+
+impl Handler<GetEditPreview> for Map {
+    type Result = Option<RgbaImage>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetEditPreview, _ctx: &mut Self::Context) -> Self::Result {
+        let image = self.image(msg.0)?;
+        self.edit_preview.layer(msg.0, image)
+    }
+}
+
+impl Handler<GetSelectionHighlight> for Map {
+    type Result = MessageResult<GetSelectionHighlight>;
+
+    #[inline]
+    fn handle(&mut self, msg: GetSelectionHighlight, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(highlight_provinces(&self.province_index, &msg.0))
+    }
+}
+
+impl Handler<ApplyEdits> for Map {
+    type Result = Vec<MapDisplayMode>;
+
+    #[inline]
+    fn handle(&mut self, _msg: ApplyEdits, _ctx: &mut Self::Context) -> Self::Result {
+        let mut edit = Edit::new("Paint");
+        let mut modes = Vec::new();
+        for (mode, original) in self.edit_preview.finish() {
+            let delta = self
+                .image(mode)
+                .and_then(|image| ImageDelta::between(&original, image));
+            if let Some(d) = delta {
+                edit = edit.with_image(mode, d);
+            }
+            modes.push(mode);
+        }
+        self.record(edit);
+        modes
+    }
+}
+
+impl Handler<Undo> for Map {
+    type Result = Result<Vec<MapDisplayMode>, MapError>;
+
+    #[inline]
+    fn handle(&mut self, _msg: Undo, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.edit_preview.is_empty() {
+            debug!("Not undoing while edits are pending");
+            return Ok(Vec::new());
+        }
+        let mut history = std::mem::take(&mut self.history);
+        let modes = history.undo(self);
+        self.history = history;
+        Ok(self.after_history_change(modes?))
+    }
+}
+
+impl Handler<Redo> for Map {
+    type Result = Result<Vec<MapDisplayMode>, MapError>;
+
+    #[inline]
+    fn handle(&mut self, _msg: Redo, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.edit_preview.is_empty() {
+            debug!("Not redoing while edits are pending");
+            return Ok(Vec::new());
+        }
+        let mut history = std::mem::take(&mut self.history);
+        let modes = history.redo(self);
+        self.history = history;
+        Ok(self.after_history_change(modes?))
+    }
+}
+
+impl Handler<CancelEdits> for Map {
+    type Result = Vec<MapDisplayMode>;
+
+    #[inline]
+    fn handle(&mut self, _msg: CancelEdits, _ctx: &mut Self::Context) -> Self::Result {
+        let mut restored = Vec::new();
+        for (mode, original) in self.edit_preview.cancel() {
+            if let Some(image) = self.image_mut(mode) {
+                *image = original;
+                restored.push(mode);
+            }
+        }
+        if restored.contains(&MapDisplayMode::Rivers) {
+            self.river_issues = verify_rivers(&self.rivers, self.wrapping);
+        }
+        if restored.contains(&MapDisplayMode::Provinces) {
+            self.province_index.refresh(&self.provinces);
+        }
+        restored
+    }
+}
+
+impl Handler<ClassifyLakes> for Map {
+    type Result = MessageResult<ClassifyLakes>;
+
+    #[inline]
+    fn handle(&mut self, msg: ClassifyLakes, ctx: &mut Self::Context) -> Self::Result {
+        let mut classifier = msg.0;
+        classifier.wrapping = self.wrapping;
+        let changed = classifier.classify(&self.provinces, &mut self.definitions);
+        info!("Changed the type of {} water provinces", changed.len());
+        if !changed.is_empty() {
+            // The lakes and the former lakes are given the terrain of their new type
+            self.definition_terrain_map = None;
+            ctx.address().do_send(GenerateDefinitionTerrainMap);
+        }
+        MessageResult(changed)
+    }
+}
+
+impl Handler<WatchRootPath> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: WatchRootPath, ctx: &mut Self::Context) -> Self::Result {
+        // The map keeps the watcher, so the watcher only keeps a weak address to the map, which
+        // lets the map stop once it is unloaded
+        let self_addr = ctx.address().downgrade();
+        let watcher = FileWatcher::new(&msg.0, &self.adjacencies_path, move |file| {
+            if let Some(addr) = self_addr.upgrade() {
+                addr.do_send(ReloadWatchedFile::new(file));
+            }
+        })
+        .map_err(|e| {
+            error!("Failed to watch {}: {e}", msg.0.display());
+            e
+        })?;
+        info!("Watching {} for changes", watcher.root_path().display());
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+}
+
+impl Handler<ReloadWatchedFile> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: ReloadWatchedFile, ctx: &mut Self::Context) -> Self::Result {
+        let root_path = match &self.watcher {
+            Some(watcher) => watcher.root_path().to_path_buf(),
+            None => return,
+        };
+        let reloads_regions =
+            matches!(msg.0, WatchedFile::State(_) | WatchedFile::StrategicRegions);
+        if let Err(e) = self.reload_file(&root_path, msg.0) {
+            error!("Failed to reload a changed file: {e}");
+            return;
+        }
+        if reloads_regions {
+            // The region maps are only generated again if the reload cleared them
+            ctx.address().do_send(GenerateStateMap);
+            ctx.address().do_send(GenerateStrategicRegionMap);
+        }
+    }
+}
+
+impl Handler<LoadComponent> for Map {
+    type Result = Result<bool, MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: LoadComponent, _ctx: &mut Self::Context) -> Self::Result {
+        self.load_component(msg.0)
+    }
+}
+
+impl Handler<RecolorProvinces> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: RecolorProvinces, _ctx: &mut Self::Context) -> Self::Result {
+        let colors = recolor_provinces(
+            Arc::make_mut(&mut self.provinces),
+            &mut self.definitions,
+            msg.0,
+        )?;
+        self.provinces_by_color = self
+            .definitions
+            .definitions
+            .values()
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.id))
+            .collect();
+        // The index knows the provinces by their old colors, so it is built again
+        self.province_index = ProvinceIndex::new(&self.provinces, &self.provinces_by_color)?;
+        // The recorded edits hold the old colors, which no longer have a definition
+        self.history.clear();
+        info!("Recolored {} provinces", colors.len());
+        Ok(())
+    }
+}
+
+impl Handler<SyncTerrain> for Map {
+    type Result = Result<usize, MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: SyncTerrain, _ctx: &mut Self::Context) -> Self::Result {
+        let changed = sync_terrain(
+            Arc::make_mut(&mut self.terrain),
+            &self.provinces,
+            &self.definitions,
+            msg.dither,
+        )?;
+        // The recorded edits would write back the pixels the sync replaced
+        if changed > 0 {
+            self.history.clear();
+        }
+        info!("Repainted {changed} terrain pixels");
+        Ok(changed)
+    }
+}
+
+impl Handler<FixXCrossings> for Map {
+    type Result = usize;
+
+    #[inline]
+    fn handle(&mut self, _msg: FixXCrossings, _ctx: &mut Self::Context) -> Self::Result {
+        let province_type = |color: Rgb<u8>| {
+            self.provinces_by_color
+                .get(&color)
+                .and_then(|id| self.definitions.definitions.get(id))
+                .map(|d| d.province_type)
+        };
+        let same_type = |a, b| province_type(a) == province_type(b);
+        let fixed = fix_x_crossings(Arc::make_mut(&mut self.provinces), self.wrapping, same_type);
+        if fixed > 0 {
+            self.province_index.refresh(&self.provinces);
+            // The recorded edits would write back the pixels the fixes replaced
+            self.history.clear();
+        }
+        info!("Fixed {fixed} X crossings");
+        fixed
+    }
+}
+
+impl Handler<FindStraits> for Map {
+    type Result = usize;
+
+    #[inline]
+    fn handle(&mut self, msg: FindStraits, _ctx: &mut Self::Context) -> Self::Result {
+        let mut finder = msg.0;
+        finder.wrapping = self.wrapping;
+        self.strait_candidates = finder.find(&self.provinces, &self.definitions, &self.adjacencies);
+        info!("Found {} straits", self.strait_candidates.len());
+        self.strait_candidates.len()
+    }
+}
+
+impl Handler<GetStraitCandidates> for Map {
+    type Result = Vec<StraitCandidate>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetStraitCandidates, _ctx: &mut Self::Context) -> Self::Result {
+        self.strait_candidates.clone()
+    }
+}
+
+impl Handler<ReviewStrait> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: ReviewStrait, _ctx: &mut Self::Context) -> Self::Result {
+        let index = self
+            .strait_candidates
+            .iter()
+            .position(|s| s.adjacency.from == msg.from && s.adjacency.to == msg.to);
+        if let Some(strait) = index.map(|i| self.strait_candidates.remove(i)) {
+            if msg.accept {
+                debug!("Adding a sea adjacency from {} to {}", msg.from, msg.to);
+                self.adjacencies.push(strait.adjacency);
+            }
+        }
+    }
+}
+
+impl Handler<GetWrapping> for Map {
+    type Result = MessageResult<GetWrapping>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetWrapping, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.wrapping)
+    }
+}
+
+impl Handler<SetWrapping> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: SetWrapping, _ctx: &mut Self::Context) -> Self::Result {
+        self.wrapping = msg.0;
+        self.river_issues = verify_rivers(&self.rivers, self.wrapping);
+    }
+}
+
+impl Handler<CreateSupportBundle> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: CreateSupportBundle, _ctx: &mut Self::Context) -> Self::Result {
+        let manifest = map_manifest(&msg.root_path)
+            .unwrap_or_else(|e| format!("Failed to list map files: {e}"));
+        let mut bundle = SupportBundle::new();
+        bundle.add("diagnostics.txt", diagnostics_report(self));
+        bundle.add("log.txt", msg.log);
+        bundle.add("settings.txt", msg.settings);
+        bundle.add("manifest.csv", manifest);
+        bundle.save(&msg.path)?;
+        info!("Saved support bundle to {}", msg.path.display());
+        Ok(())
+    }
+}
+
+impl Handler<ExportRegionMap> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: ExportRegionMap, _ctx: &mut Self::Context) -> Self::Result {
+        let (image, legend) = match msg.region_map {
+            RegionMap::States => (&self.state_map, &self.state_legend),
+            RegionMap::StrategicRegions => {
+                (&self.strategic_region_map, &self.strategic_region_legend)
+            }
+        };
+        let image = image
+            .as_ref()
+            .ok_or(MapError::RegionMapNotGenerated(msg.region_map))?;
+        image.save(&msg.path)?;
+        info!("Saved {} map to {}", msg.region_map, msg.path.display());
+        if let Some(format) = msg.legend {
+            let path = legend_path(&msg.path, format);
+            legend.save(&path, format)?;
+            info!("Saved {} map legend to {}", msg.region_map, path.display());
+        }
+        Ok(())
+    }
+}
+
+impl Handler<ExportPoster> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: ExportPoster, _ctx: &mut Self::Context) -> Self::Result {
+        let image = self
+            .map_image(GetMapImage::from(msg.mode))
+            .ok_or(MapError::ImageNotDrawn(msg.mode))?;
+        let layers = msg
+            .layers
+            .iter()
+            .map(|(layer, opacity)| (layer.render(self), *opacity))
+            .collect::<Vec<_>>();
+        let labels = if msg.options.labels && msg.options.format == PosterFormat::Svg {
+            state_labels(&self.states, &self.states_by_province, &self.province_index)
+        } else {
+            Vec::new()
+        };
+        save_poster(&composite(&image, &layers), msg.options, &labels, &msg.path)?;
+        info!("Saved {} poster to {}", msg.mode, msg.path.display());
+        Ok(())
+    }
+}
+
+impl Handler<ExportGeoJson> for Map {
+    type Result = Result<(), MapError>;
+
+    #[inline]
+    fn handle(&mut self, msg: ExportGeoJson, _ctx: &mut Self::Context) -> Self::Result {
+        let index = &self.province_index;
+        let provinces = provinces_geojson(
+            index,
+            &self.definitions,
+            &self.states_by_province,
+            &self.strategic_regions_by_province,
+        );
+        write_geojson(&msg.0.join("provinces.geojson"), &provinces)?;
+        let states = states_geojson(index, &self.states, &self.states_by_province);
+        write_geojson(&msg.0.join("states.geojson"), &states)?;
+        let strategic_regions = strategic_regions_geojson(
+            index,
+            &self.strategic_regions,
+            &self.strategic_regions_by_province,
+        );
+        write_geojson(&msg.0.join("strategic_regions.geojson"), &strategic_regions)?;
+        info!("Saved GeoJSON files to {}", msg.0.display());
+        Ok(())
+    }
+}
+
+impl Handler<Validate> for Map {
+    type Result = MessageResult<Validate>;
+
+    #[inline]
+    fn handle(&mut self, _msg: Validate, _ctx: &mut Self::Context) -> Self::Result {
+        if let Err(e) = self.load_component(MapComponent::Buildings) {
+            error!("Failed to load the buildings to validate them: {e}");
+        }
+        MessageResult(self.validate())
     }
 }
 
-impl Handler<GetStrategicRegionFromId> for Map {
-    type Result = Option<StrategicRegion>;
+impl Handler<LocateFinding> for Map {
+    type Result = MessageResult<LocateFinding>;
+
     #[inline]
-    fn handle(&mut self, msg: GetStrategicRegionFromId, _ctx: &mut Context<Self>) -> Self::Result {
-        self.strategic_regions
-            .strategic_regions
-            .get(&msg.0)
-            .cloned()
+    fn handle(&mut self, msg: LocateFinding, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.locate_finding(&msg.0))
     }
 }
 
-impl Handler<GetStateFromId> for Map {
-    type Result = Option<State>;
+impl Handler<GetMapSize> for Map {
+    type Result = MessageResult<GetMapSize>;
+
     #[inline]
-    fn handle(&mut self, msg: GetStateFromId, _ctx: &mut Context<Self>) -> Self::Result {
-        self.states.get(&msg.0).cloned()
+    fn handle(&mut self, _msg: GetMapSize, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.provinces.dimensions())
     }
 }
 
-impl Handler<GetProvinceDefinitionFromId> for Map {
-    type Result = Option<Definition>;
+impl Handler<ApplyFixes> for Map {
+    type Result = Result<usize, MapError>;
 
     #[inline]
-    fn handle(
-        &mut self,
-        msg: GetProvinceDefinitionFromId,
-        _ctx: &mut Context<Self>,
-    ) -> Self::Result {
-        self.definitions.definitions.get(&msg.0).cloned()
+    fn handle(&mut self, msg: ApplyFixes, ctx: &mut Self::Context) -> Self::Result {
+        let applied = self.apply_fixes(&msg.root_path, &msg.fixes)?;
+        // The map of continents is only generated again if a fix cleared it
+        ctx.address().do_send(GenerateContinentMap);
+        info!(
+            "Applied {applied} of {} fixes to {}",
+            msg.fixes.len(),
+            msg.root_path.display()
+        );
+        Ok(applied)
     }
 }
 
-impl Handler<GetContinentFromIndex> for Map {
-    type Result = Option<Continent>;
+impl Handler<SaveStates> for Map {
+    type Result = Result<(), MapError>;
 
     #[inline]
-    fn handle(&mut self, msg: GetContinentFromIndex, _ctx: &mut Context<Self>) -> Self::Result {
-        let index = msg.0;
-        if index.0 < 1 {
-            return None;
+    fn handle(&mut self, msg: SaveStates, _ctx: &mut Self::Context) -> Self::Result {
+        let states_path = msg.0.join("history/states");
+        let mut dirty_states = self.dirty_states.iter().collect::<Vec<_>>();
+        dirty_states.sort_unstable();
+        for id in dirty_states {
+            if let Some(state) = self.states.get(id) {
+                state.save(&states_path, self.state_sources.get(id))?;
+            }
         }
-        self.continents.continents.get(index.0 - 1).cloned()
+        info!(
+            "Saved {} edited states to {}",
+            self.dirty_states.len(),
+            states_path.display()
+        );
+        self.dirty_states.clear();
+        self.localisation.save(&msg.0)?;
+        Ok(())
     }
 }
 
@@ -958,27 +3927,24 @@ impl Handler<GenerateStrategicRegionMap> for Map {
             return;
         }
         let strategic_regions = self.strategic_regions.strategic_regions.clone();
-        let provinces = self.provinces.clone();
-        let provinces_by_color = self.provinces_by_color.clone();
-        let definitions = self.definitions.definitions.clone();
+        let province_index = self.province_index.clone();
+        let region_colors = self.region_colors.clone();
+        let avoid = self.colors_to_avoid();
+        let wrapping = self.wrapping;
         let strategic_regions_by_province = self.strategic_regions_by_province.clone();
         let self_addr = ctx.address();
         let strategic_region_map_handle = tokio::task::spawn_blocking(move || {
-            match generate_region_map(
+            let (m, legend) = generate_region_map(
                 &strategic_regions,
-                &provinces,
-                &provinces_by_color,
-                &definitions,
+                &province_index,
                 &strategic_regions_by_province,
-            ) {
-                Ok(m) => {
-                    if let Err(e) = self_addr.try_send(UpdateStrategicRegionMap(m)) {
-                        error!("Failed to send strategic region map update: {}", e);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to generate strategic region map: {:?}", e);
-                }
+                wrapping,
+                &region_colors,
+                &avoid,
+                |r| (r.id.0, r.name.0.clone()),
+            );
+            if let Err(e) = self_addr.try_send(UpdateStrategicRegionMap(m, legend)) {
+                error!("Failed to send strategic region map update: {}", e);
             }
         });
 
@@ -991,7 +3957,8 @@ impl Handler<UpdateStrategicRegionMap> for Map {
 
     #[inline]
     fn handle(&mut self, msg: UpdateStrategicRegionMap, _ctx: &mut Self::Context) -> Self::Result {
-        self.strategic_region_map = Some(msg.0);
+        self.strategic_region_map = Some(Arc::new(msg.0));
+        self.strategic_region_legend = msg.1;
         self.strategic_region_map_handle.take();
     }
 }
@@ -1005,27 +3972,24 @@ impl Handler<GenerateStateMap> for Map {
             return;
         }
         let states = self.states.clone();
-        let provinces = self.provinces.clone();
-        let provinces_by_color = self.provinces_by_color.clone();
-        let definitions = self.definitions.definitions.clone();
+        let province_index = self.province_index.clone();
+        let region_colors = self.region_colors.clone();
+        let avoid = self.colors_to_avoid();
+        let wrapping = self.wrapping;
         let states_by_province = self.states_by_province.clone();
         let self_addr = ctx.address();
         let state_map_handle = tokio::task::spawn_blocking(move || {
-            match generate_region_map(
+            let (m, legend) = generate_region_map(
                 &states,
-                &provinces,
-                &provinces_by_color,
-                &definitions,
+                &province_index,
                 &states_by_province,
-            ) {
-                Ok(m) => {
-                    if let Err(e) = self_addr.try_send(UpdateStateMap(m)) {
-                        error!("Failed to send state map update: {}", e);
-                    }
-                }
-                Err(e) => {
-                    error!("Failed to generate state map: {:?}", e);
-                }
+                wrapping,
+                &region_colors,
+                &avoid,
+                |s| (s.id.0, s.name.0.clone()),
+            );
+            if let Err(e) = self_addr.try_send(UpdateStateMap(m, legend)) {
+                error!("Failed to send state map update: {}", e);
             }
         });
 
@@ -1038,53 +4002,264 @@ impl Handler<UpdateStateMap> for Map {
 
     #[inline]
     fn handle(&mut self, msg: UpdateStateMap, _ctx: &mut Self::Context) -> Self::Result {
-        self.state_map = Some(msg.0);
+        self.state_map = Some(Arc::new(msg.0));
+        self.state_legend = msg.1;
         self.state_map_handle.take();
     }
 }
 
-/// Generates an `RgbImage` from the regions
-/// # Errors
-/// * If the regions are not valid
+impl Handler<GenerateContinentMap> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, _msg: GenerateContinentMap, ctx: &mut Self::Context) -> Self::Result {
+        if self.continent_map.is_some() || self.continent_map_handle.is_some() {
+            return;
+        }
+        let continents = continent_names(&self.continents);
+        let continents_by_province = self
+            .definitions
+            .definitions
+            .values()
+            .map(|d| (d.id, d.continent.0))
+            .collect::<HashMap<_, _>>();
+        let province_index = self.province_index.clone();
+        let region_colors = self.region_colors.clone();
+        let avoid = self.colors_to_avoid();
+        let wrapping = self.wrapping;
+        let self_addr = ctx.address();
+        let continent_map_handle = tokio::task::spawn_blocking(move || {
+            let (m, legend) = generate_region_map(
+                &continents,
+                &province_index,
+                &continents_by_province,
+                wrapping,
+                &region_colors,
+                &avoid,
+                Clone::clone,
+            );
+            if let Err(e) = self_addr.try_send(UpdateContinentMap(m, legend)) {
+                error!("Failed to send continent map update: {}", e);
+            }
+        });
+
+        self.continent_map_handle = Some(continent_map_handle);
+    }
+}
+
+impl Handler<UpdateContinentMap> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(&mut self, msg: UpdateContinentMap, _ctx: &mut Self::Context) -> Self::Result {
+        self.continent_map = Some(Arc::new(msg.0));
+        self.continent_legend = msg.1;
+        self.continent_map_handle.take();
+    }
+}
+
+impl Handler<GenerateDefinitionTerrainMap> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(
+        &mut self,
+        _msg: GenerateDefinitionTerrainMap,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if self.definition_terrain_map.is_some() || self.definition_terrain_map_handle.is_some() {
+            return;
+        }
+        let definitions = self.definitions.clone();
+        let province_index = self.province_index.clone();
+        let self_addr = ctx.address();
+        let handle = tokio::task::spawn_blocking(move || {
+            let m = definition_terrain_map(&province_index, &definitions);
+            if let Err(e) = self_addr.try_send(UpdateDefinitionTerrainMap(m)) {
+                error!("Failed to send definition terrain map update: {}", e);
+            }
+        });
+
+        self.definition_terrain_map_handle = Some(handle);
+    }
+}
+
+impl Handler<UpdateDefinitionTerrainMap> for Map {
+    type Result = ();
+
+    #[inline]
+    fn handle(
+        &mut self,
+        msg: UpdateDefinitionTerrainMap,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        self.definition_terrain_map = Some(Arc::new(msg.0));
+        self.definition_terrain_map_handle.take();
+    }
+}
+
+impl Handler<GetContinentLegend> for Map {
+    type Result = MessageResult<GetContinentLegend>;
+
+    #[inline]
+    fn handle(&mut self, _msg: GetContinentLegend, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.continent_legend.clone())
+    }
+}
+
+/// The id and name of each continent by its index, starting with the provinces without a
+/// continent at index 0, so that they are colored on the map of continents as well
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_possible_wrap)]
+fn continent_names(continents: &Continents) -> HashMap<usize, (i32, String)> {
+    let named = continents
+        .continents
+        .iter()
+        .zip(1_usize..)
+        .map(|(continent, index)| (index, (index as i32, continent.0.clone())));
+    std::iter::once((0, (0, "No continent".to_owned())))
+        .chain(named)
+        .collect()
+}
+
+/// Generates an `RgbImage` from the regions, along with a legend of the color of each region.  The
+/// label gives the id and name of a region for the legend, and the colors of the regions follow
+/// from their ids and the regions they border, never using one of the colors to avoid.  Pixels
+/// without a province or region are black.
 #[inline]
-fn generate_region_map<RegionId: Copy + Eq + Hash, Region>(
+#[allow(clippy::too_many_arguments)]
+fn generate_region_map<RegionId: Copy + Eq + Hash, Region, F: Fn(&Region) -> (i32, String)>(
     regions: &HashMap<RegionId, Region>,
-    provinces: &RgbImage,
-    provinces_by_color: &HashMap<Rgb<u8>, ProvinceId>,
-    definitions: &HashMap<ProvinceId, Definition>,
+    province_index: &ProvinceIndex,
     regions_by_province: &HashMap<ProvinceId, RegionId>,
-) -> Result<RgbImage, MapError> {
-    let region_colors = {
-        let mut rng = thread_rng();
-        regions
-            .keys()
+    wrapping: Wrapping,
+    colors: &RegionColors,
+    avoid: &HashSet<Rgb<u8>>,
+    label: F,
+) -> (RgbImage, Legend) {
+    let ids = regions
+        .iter()
+        .map(|(id, region)| (*id, label(region).0))
+        .collect::<HashMap<_, _>>();
+    let region_of = |province| {
+        regions_by_province
+            .get(&province)
+            .and_then(|region| ids.get(region))
             .copied()
-            .map(|id| {
-                let r = rng.gen();
-                let g = rng.gen();
-                let b = rng.gen();
-                let color = Rgb::<u8>::from([r, g, b]);
-                (id, color)
+    };
+    let neighbors = province_neighbors(province_index, wrapping)
+        .into_iter()
+        .filter_map(|(a, b)| region_of(a).zip(region_of(b)))
+        .filter(|(a, b)| a != b)
+        .collect::<HashSet<_>>();
+    let id_list = ids.values().copied().collect::<Vec<_>>();
+    let colors_by_id = colors.assign(&id_list, &neighbors, avoid);
+    let region_colors = ids
+        .iter()
+        .filter_map(|(region, id)| Some((*region, *colors_by_id.get(id)?)))
+        .collect::<HashMap<_, _>>();
+    let region_map = province_index.paint(|id| {
+        let region = regions_by_province.get(&id)?;
+        region_colors.get(region).copied()
+    });
+    let legend = Legend::new(
+        regions
+            .iter()
+            .filter_map(|(id, region)| {
+                let (region_id, name) = label(region);
+                let color = *region_colors.get(id)?;
+                Some(LegendEntry::new(region_id, name, color))
             })
-            .collect::<HashMap<_, _>>()
+            .collect(),
+    );
+    (region_map, legend)
+}
+
+/// The area of an image a round brush of a radius covers around a point, cut off at the edges of
+/// the image
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn brush_area(image: &RgbImage, point: Pos2, radius: u32) -> Rect {
+    let radius = radius.max(1);
+    let (x, y) = (point.x as u32, point.y as u32);
+    let (min_x, min_y) = (x.saturating_sub(radius), y.saturating_sub(radius));
+    let max_x = x
+        .saturating_add(radius)
+        .saturating_add(1)
+        .min(image.width());
+    let max_y = y
+        .saturating_add(radius)
+        .saturating_add(1)
+        .min(image.height());
+    Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x.saturating_sub(min_x),
+        height: max_y.saturating_sub(min_y),
+    }
+}
+
+/// The number of subpixels in a row of an image, and at least 1 so that the subpixels can be
+/// split into rows even for an empty image
+#[allow(clippy::as_conversions)]
+#[allow(clippy::integer_arithmetic)]
+fn row_length(image: &RgbImage) -> usize {
+    (image.width() as usize * 3).max(1)
+}
+
+/// The first pixel of each color of an image, reading the rows from the top.  The rows are read
+/// in parallel, keeping the topmost and then leftmost pixel of each color.
+fn first_pixels(image: &RgbImage) -> HashMap<Rgb<u8>, (u32, u32)> {
+    let keep_first = |first: &mut (u32, u32), (x, y): (u32, u32)| {
+        if (y, x) < (first.1, first.0) {
+            *first = (x, y);
+        }
     };
-    let mut region_map = RgbImage::new(provinces.width(), provinces.height());
-    for (x, y, pixel) in provinces.enumerate_pixels() {
-        let province_id = provinces_by_color.get(pixel).ok_or_else(|| {
-            MapError::InvalidProvinceColor((Red(pixel.0[0]), Green(pixel.0[1]), Blue(pixel.0[2])))
-        })?;
-        let province = definitions
-            .get(province_id)
-            .ok_or(MapError::DefinitionNotFound(*province_id))?;
-        let region_id = regions_by_province.get(&province.id);
-        let color = region_id.map_or(Rgb::<u8>::from([0, 0, 0]), |rid| {
-            *region_colors
-                .get(rid)
-                .expect("Regions are inconsistent with assigned colors")
+    image
+        .par_chunks(row_length(image))
+        .zip(0_u32..image.height())
+        .fold(HashMap::new, |mut colors, (row, y)| {
+            for (x, pixel) in (0_u32..).zip(row.chunks_exact(3).map(Rgb::<u8>::from_slice)) {
+                colors.entry(*pixel).or_insert((x, y));
+            }
+            colors
+        })
+        .reduce(HashMap::new, |mut colors, other| {
+            for (color, pixel) in other {
+                colors
+                    .entry(color)
+                    .and_modify(|first| keep_first(first, pixel))
+                    .or_insert(pixel);
+            }
+            colors
+        })
+}
+
+/// The pixel of a color in the provinces image closest to the middle of all of its pixels, so
+/// that a province curving around another is not located outside of itself
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::integer_division)]
+fn province_pixel(provinces: &RgbImage, color: Rgb<u8>) -> Option<(u32, u32)> {
+    let pixels = provinces
+        .enumerate_pixels()
+        .filter(|(_, _, p)| **p == color)
+        .map(|(x, y, _)| (u64::from(x), u64::from(y)));
+    let (count, sum_x, sum_y) = pixels
+        .clone()
+        .fold((0_u64, 0_u64, 0_u64), |(n, sx, sy), (x, y)| {
+            (n + 1, sx + x, sy + y)
         });
-        region_map.put_pixel(x, y, color);
+    if count == 0 {
+        return None;
     }
-    Ok(region_map)
+    let (middle_x, middle_y) = (sum_x / count, sum_y / count);
+    let (x, y) = pixels.min_by_key(|(x, y)| {
+        let (dx, dy) = (x.abs_diff(middle_x), y.abs_diff(middle_y));
+        dx * dx + dy * dy
+    })?;
+    Some((u32::try_from(x).ok()?, u32::try_from(y).ok()?))
 }
 
 /// Checks the image sizes and aspect ratios
@@ -1093,9 +4268,9 @@ fn verify_images(
     terrain: &RgbImage,
     rivers: &RgbImage,
     heightmap: &RgbImage,
-    trees: &RgbImage,
-    normal_map: &RgbImage,
-    cities: &RgbImage,
+    trees: Option<&RgbImage>,
+    normal_map: Option<&RgbImage>,
+    cities: Option<&RgbImage>,
 ) -> Result<(), MapError> {
     if provinces.width() != heightmap.width() || provinces.height() != heightmap.height() {
         return Err(MapError::ImageSizeMismatch(
@@ -1112,24 +4287,30 @@ fn verify_images(
             "rivers map does not match heightmap".to_owned(),
         ));
     }
-    if cities.width() != heightmap.width() || cities.height() != heightmap.height() {
-        return Err(MapError::ImageSizeMismatch(
-            "cities map does not match heightmap".to_owned(),
-        ));
+    if let Some(cities) = cities {
+        if cities.width() != heightmap.width() || cities.height() != heightmap.height() {
+            return Err(MapError::ImageSizeMismatch(
+                "cities map does not match heightmap".to_owned(),
+            ));
+        }
     }
 
     let heightmap_aspect_ratio = f64::from(heightmap.width()) / f64::from(heightmap.height());
-    let trees_aspect_ratio = f64::from(trees.width()) / f64::from(trees.height());
-    if (heightmap_aspect_ratio - trees_aspect_ratio).abs() > 0.01_f64 {
-        return Err(MapError::ImageSizeMismatch(
-            "heightmap aspect ratio does not match trees aspect ratio".to_owned(),
-        ));
+    if let Some(trees) = trees {
+        let trees_aspect_ratio = f64::from(trees.width()) / f64::from(trees.height());
+        if (heightmap_aspect_ratio - trees_aspect_ratio).abs() > 0.01_f64 {
+            return Err(MapError::ImageSizeMismatch(
+                "heightmap aspect ratio does not match trees aspect ratio".to_owned(),
+            ));
+        }
     }
-    let normal_aspect_ratio = f64::from(normal_map.width()) / f64::from(normal_map.height());
-    if (heightmap_aspect_ratio - normal_aspect_ratio).abs() > 0.01_f64 {
-        return Err(MapError::ImageSizeMismatch(
-            "heightmap aspect ratio does not match normal aspect ratio".to_owned(),
-        ));
+    if let Some(normal_map) = normal_map {
+        let normal_aspect_ratio = f64::from(normal_map.width()) / f64::from(normal_map.height());
+        if (heightmap_aspect_ratio - normal_aspect_ratio).abs() > 0.01_f64 {
+            return Err(MapError::ImageSizeMismatch(
+                "heightmap aspect ratio does not match normal aspect ratio".to_owned(),
+            ));
+        }
     }
 
     Ok(())
@@ -1139,7 +4320,7 @@ fn verify_images(
 fn load_image(root_path: &Path, image_path: &Path) -> Result<RgbImage, MapError> {
     let image_bmp_path = map_file(root_path, image_path);
     info!("Loading {}", image_bmp_path.display());
-    let provinces_bmp: DynamicImage = open(&image_bmp_path)?;
+    let provinces_bmp: DynamicImage = open(&image_bmp_path).in_file(&image_bmp_path)?;
     if let DynamicImage::ImageRgb8(image) = provinces_bmp {
         let is_trees = image_path.display().to_string().contains("trees");
         let is_normal = image_path.display().to_string().contains("world_normal");
@@ -1164,6 +4345,160 @@ fn map_path(root_path: &Path) -> PathBuf {
     root_path_buf
 }
 
+/// Loads the English localisation of the root directory.  The names of the states and victory
+/// points fall back to their keys, so a localisation that cannot be read is not an error.
+fn load_localisation(root_path: &Path, encoding: Option<TextEncoding>) -> Localisation {
+    Localisation::load(root_path, DEFAULT_LANGUAGE, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the localisation: {e}");
+        Localisation::default()
+    })
+}
+
+/// Loads the weather states of `common/weather.txt`.  The periods of the strategic regions are
+/// read whatever their weather states, so a missing or invalid file falls back to the weather
+/// states of the base game.
+fn load_weather_states(root_path: &Path, encoding: Option<TextEncoding>) -> WeatherStates {
+    let path = root_path.join("common/weather.txt");
+    if !path.is_file() {
+        info!("No weather states at {}", path.display());
+        return WeatherStates::default();
+    }
+    match WeatherStates::from_file(&path, encoding) {
+        Ok(states) if !states.states.is_empty() => states,
+        Ok(_states) => {
+            warn!("No weather states in {}", path.display());
+            WeatherStates::default()
+        }
+        Err(e) => {
+            warn!("Failed to load the weather states: {e}");
+            WeatherStates::default()
+        }
+    }
+}
+
+/// Loads the state categories of `common/state_category`.  The map of state categories falls back
+/// to colors mixed from the names of the categories, so a missing or invalid directory is not an
+/// error.
+fn load_state_categories(root_path: &Path, encoding: Option<TextEncoding>) -> StateCategories {
+    let path = root_path.join("common/state_category");
+    if !path.is_dir() {
+        info!("No state categories at {}", path.display());
+        return StateCategories::default();
+    }
+    StateCategories::from_dir(&path, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the state categories: {e}");
+        StateCategories::default()
+    })
+}
+
+/// Loads the colors of the countries of `common/countries/colors.txt`.  The political map falls
+/// back to colors mixed from the tags of the countries, so a missing or invalid file is not an
+/// error.
+fn load_country_colors(root_path: &Path, encoding: Option<TextEncoding>) -> CountryColors {
+    let path = root_path.join("common/countries/colors.txt");
+    if !path.is_file() {
+        info!("No country colors at {}", path.display());
+        return CountryColors::default();
+    }
+    CountryColors::from_file(&path, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the country colors: {e}");
+        CountryColors::default()
+    })
+}
+
+/// Loads the histories of the countries of `history/countries`.  Only the capitals and the checks
+/// of the owners of the states use them, so a missing or invalid directory is not an error.
+fn load_country_histories(root_path: &Path, encoding: Option<TextEncoding>) -> CountryHistories {
+    let path = root_path.join("history/countries");
+    if !path.is_dir() {
+        info!("No country histories at {}", path.display());
+        return CountryHistories::default();
+    }
+    CountryHistories::from_dir(&path, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the country histories: {e}");
+        CountryHistories::default()
+    })
+}
+
+/// The files the buildings are loaded from: the building types of the `common/buildings` files and
+/// `map/buildings.txt`
+fn buildings_sources(root_path: &Path) -> Vec<PathBuf> {
+    let types_path = root_path.join("common/buildings");
+    // A directory that cannot be read fails the load itself, so it is kept as the source
+    let mut sources = text_files(&types_path).unwrap_or_else(|_e| vec![types_path]);
+    sources.push(map_file(root_path, Path::new("buildings.txt")));
+    sources
+}
+
+/// Loads the buildings of `map/buildings.txt` and the building types of `common/buildings` they
+/// may have
+fn load_buildings(
+    root_path: &Path,
+    parsing: CsvParsing,
+    encoding: Option<TextEncoding>,
+) -> Result<Buildings, MapError> {
+    let types_path = root_path.join("common/buildings");
+    let buildings_path = map_file(root_path, Path::new("buildings.txt"));
+    let result = Buildings::from_files(&types_path, &buildings_path, parsing, encoding);
+    if result.is_err() {
+        error!(
+            "Error loading buildings from {} and {}",
+            buildings_path.display(),
+            types_path.display()
+        );
+    }
+    result
+}
+
+/// Loads the supply areas of `map/supplyareas` for a map from before 1.11.  Current maps give
+/// supply through supply nodes and railways, and an older map without supply areas can still be
+/// edited, so a missing or invalid directory is not an error.
+fn load_supply_areas(
+    root_path: &Path,
+    version: MapVersion,
+    encoding: Option<TextEncoding>,
+) -> SupplyAreas {
+    if version != MapVersion::Legacy {
+        return SupplyAreas::default();
+    }
+    let path = map_file(root_path, Path::new("supplyareas"));
+    SupplyAreas::from_dir(&path, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the supply areas: {e}");
+        SupplyAreas::default()
+    })
+}
+
+/// Loads the objects of `map/ambient_object.txt`.  They are only shown in an overlay, so a missing
+/// or invalid file is not an error.
+fn load_ambient_objects(root_path: &Path, encoding: Option<TextEncoding>) -> AmbientObjects {
+    let path = map_file(root_path, Path::new("ambient_object.txt"));
+    if !path.is_file() {
+        info!("No ambient objects at {}", path.display());
+        return AmbientObjects::default();
+    }
+    AmbientObjects::from_file(&path, encoding).unwrap_or_else(|e| {
+        warn!("Failed to load the ambient objects: {e}");
+        AmbientObjects::default()
+    })
+}
+
+/// Loads the unit stacks of `map/unitstacks.txt`
+fn load_unit_stacks(
+    root_path: &Path,
+    parsing: CsvParsing,
+    encoding: Option<TextEncoding>,
+) -> Result<UnitStacks, MapError> {
+    let unit_stacks_path = map_file(root_path, Path::new("unitstacks.txt"));
+    let result = UnitStacks::from_file(&unit_stacks_path, parsing, encoding);
+    if result.is_err() {
+        error!(
+            "Error loading unit stacks from {}",
+            unit_stacks_path.display()
+        );
+    }
+    result
+}
+
 /// Generates a path to a file in the root/map/ directory
 fn map_file(root_path: &Path, file_path: &Path) -> PathBuf {
     let mut map_path = map_path(root_path);
@@ -1181,12 +4516,125 @@ fn draw_target<T: TermLike + Clone + Sized + 'static>(term: &Option<T>) -> Progr
 }
 
 #[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
 #[allow(clippy::panic)]
 #[allow(clippy::unwrap_used)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::decimal_literal_representation)]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixtures::SyntheticMap;
+    use crate::texture::texture_image;
+    use crate::{CsvParsing, LoadCsv};
     use indicatif::InMemoryTerm;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    /// Runs a step of a scalability test, failing if it takes longer than its budget
+    fn within_budget<T>(step: &str, budget: Duration, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed <= budget,
+            "{step} took {elapsed:?}, over its budget of {budget:?}"
+        );
+        result
+    }
+
+    /// The most memory the process has used at once in bytes, where the platform reports it.  It
+    /// covers the whole test process, so the tests that ran before or alongside the caller count
+    /// towards it, and a budget checked against it only holds for a test run on its own.
+    fn peak_memory() -> Option<u64> {
+        let status = fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+        let kilobytes = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+        Some(kilobytes * 1024)
+    }
+
+    /// Saves a synthetic map, loads it back and draws its state map and texture, checking each
+    /// step against its time budget
+    fn exercise_synthetic_map(width: u32, height: u32, province_count: u32, budget: Duration) {
+        let map = within_budget("Building the map", budget, || {
+            SyntheticMap::new(width, height, province_count)
+        })
+        .expect("Failed to build map");
+        let root = std::env::temp_dir().join(format!("world_gen_synthetic_{width}x{height}"));
+        within_budget("Saving the map", budget, || map.save(&root.join("map")))
+            .expect("Failed to save map");
+
+        let provinces = within_budget("Loading the provinces", budget, || {
+            load_image(&root, Path::new("provinces.bmp"))
+        })
+        .expect("Failed to load provinces");
+        assert_eq!(provinces, map.provinces);
+        let definitions = within_budget("Loading the definitions", budget, || {
+            Definition::load_csv(
+                root.join("map/definition.csv"),
+                false,
+                CsvParsing::Strict,
+                None,
+            )
+        })
+        .expect("Failed to load definitions");
+        assert_eq!(definitions.len(), province_count as usize + 1);
+        fs::remove_dir_all(&root).expect("Failed to remove map");
+
+        let province_index = within_budget("Indexing the provinces", budget, || {
+            ProvinceIndex::new(&provinces, &map.provinces_by_color)
+        })
+        .expect("Failed to index provinces");
+        let (state_map, legend) = within_budget("Generating the state map", budget, || {
+            generate_region_map(
+                &map.states,
+                &province_index,
+                &map.states_by_province,
+                Wrapping::None,
+                &RegionColors::default(),
+                &HashSet::new(),
+                |s| (s.id.0, s.name.0.clone()),
+            )
+        });
+        assert_eq!(state_map.dimensions(), (width, height));
+        assert_eq!(legend.entries.len(), map.states.len());
+        let (same_map, _) = generate_region_map(
+            &map.states,
+            &province_index,
+            &map.states_by_province,
+            Wrapping::None,
+            &RegionColors::default(),
+            &HashSet::new(),
+            |s| (s.id.0, s.name.0.clone()),
+        );
+        assert_eq!(same_map, state_map);
+
+        let texture = within_budget("Building the texture", budget, || {
+            texture_image(&state_map, 8192)
+        });
+        assert!(texture.size.iter().all(|side| *side <= 8192));
+    }
+
+    #[test]
+    fn it_handles_a_synthetic_map() {
+        exercise_synthetic_map(1024, 512, 2_000, Duration::from_secs(30));
+    }
+
+    #[test]
+    #[ignore = "builds a 16384x8192 map, run on its own with \
+                `cargo test --release -- --ignored it_handles_a_map_beyond_vanilla_size`"]
+    fn it_handles_a_map_beyond_vanilla_size() {
+        exercise_synthetic_map(16384, 8192, 60_000, Duration::from_mins(1));
+        if let Some(peak) = peak_memory() {
+            assert!(
+                peak <= 4_u64 << 30,
+                "Used {} MiB at most, over the budget of 4 GiB",
+                peak >> 20_u64
+            );
+        }
+    }
 
     #[test]
     fn it_loads_a_map() {
@@ -1194,20 +4642,225 @@ mod tests {
             .enable_all()
             .build()
             .unwrap();
-        let handle = rt.spawn_blocking(|| Map::new::<InMemoryTerm>(Path::new("./test"), &None));
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::new())
+        });
         let map = rt.block_on(handle).unwrap();
         assert!(map.is_ok());
     }
 
+    #[test]
+    fn it_loads_deferred_layers_when_asked_for() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::deferred())
+        });
+        let mut map = rt.block_on(handle).unwrap().expect("Failed to load map");
+        assert!(map.trees.is_none());
+        assert!(map.cities_map.is_none());
+        let width = map
+            .layer(Layer::Cities)
+            .expect("Failed to load cities")
+            .width();
+        assert_eq!(width, map.heightmap.width());
+        assert!(map.cities_map.is_some());
+        assert!(map.trees.is_none());
+
+        assert!(!map.is_loaded(MapComponent::UnitStacks));
+        assert!(map.unit_stacks.stacks.is_empty());
+        assert!(map
+            .load_component(MapComponent::UnitStacks)
+            .expect("Failed to load unit stacks"));
+        assert!(map.is_loaded(MapComponent::UnitStacks));
+        assert!(!map.unit_stacks.stacks.is_empty());
+        assert!(!map
+            .load_component(MapComponent::UnitStacks)
+            .expect("Failed to load unit stacks again"));
+    }
+
+    #[test]
+    fn it_stops_loading_once_canceled() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let options = MapLoadOptions::new().with_cancel(cancel);
+        let handle = rt
+            .spawn_blocking(move || Map::new::<InMemoryTerm>(Path::new("./test"), &None, &options));
+        let map = rt.block_on(handle).unwrap();
+        assert!(matches!(map, Err(MapError::LoadCanceled)));
+    }
+
+    #[test]
+    fn it_reloads_a_changed_state_file_unless_it_was_edited() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::new())
+        });
+        let mut map = rt.block_on(handle).unwrap().expect("Failed to load map");
+        let root = Path::new("./test");
+        let path = root.join("history/states/1-State.txt");
+        let (state, _source) = States::load_file(&path, None).expect("Failed to load state");
+        let id = state.id;
+
+        map.states.get_mut(&id).expect("Missing state").name = StateName("Edited".to_owned());
+        map.reload_file(root, WatchedFile::State(path.clone()))
+            .expect("Failed to reload state");
+        assert_eq!(map.states.get(&id).map(|s| &s.name.0), Some(&state.name.0));
+        assert!(map
+            .states_by_province
+            .iter()
+            .all(|(province, state_id)| { *state_id != id || state.provinces.contains(province) }));
+
+        map.states.get_mut(&id).expect("Missing state").name = StateName("Edited".to_owned());
+        map.dirty_states.insert(id);
+        map.reload_file(root, WatchedFile::State(path))
+            .expect("Failed to reload state");
+        assert_eq!(
+            map.states.get(&id).map(|s| s.name.0.as_str()),
+            Some("Edited")
+        );
+
+        map.reload_file(root, WatchedFile::Adjacencies)
+            .expect("Failed to reload adjacencies");
+    }
+
     #[test]
     fn it_verifies_province_colors() {
         let rt = tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()
             .unwrap();
-        let handle = rt.spawn_blocking(|| Map::new::<InMemoryTerm>(Path::new("./test"), &None));
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::new())
+        });
         let map = rt.block_on(handle).unwrap().expect("Failed to load map");
-        map.verify_province_colors()
-            .expect("Failed to verify provinces");
+        assert_eq!(map.verify_province_colors(), Vec::new());
+    }
+
+    #[test]
+    fn it_finds_the_first_pixel_of_each_color_in_parallel() {
+        let image = RgbImage::from_fn(300, 200, |x, y| {
+            Rgb([
+                u8::try_from((x * 7 + y * 3) % 11).unwrap(),
+                0,
+                u8::try_from(y / 50).unwrap(),
+            ])
+        });
+        let mut expected = HashMap::new();
+        for (x, y, color) in image.enumerate_pixels() {
+            expected.entry(*color).or_insert((x, y));
+        }
+        assert_eq!(first_pixels(&image), expected);
+        assert!(first_pixels(&RgbImage::new(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn it_applies_fixes_and_writes_the_changed_files() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::new())
+        });
+        let mut map = rt.block_on(handle).unwrap().expect("Failed to load map");
+        let fixes = map.validate().auto_fixes();
+        assert!(fixes.contains(&AutoFix::TerminateAdjacencies));
+
+        let root = std::env::temp_dir().join("world_gen_apply_fixes");
+        fs::create_dir_all(root.join("map")).expect("Failed to create map directory");
+        let fixes = [AutoFix::TerminateAdjacencies, AutoFix::TerminateAdjacencies];
+        let applied = map
+            .apply_fixes(&root, &fixes)
+            .expect("Failed to apply fixes");
+        let adjacencies =
+            Adjacencies::from_file(root.join(&map.adjacencies_path), CsvParsing::Strict, None);
+        fs::remove_dir_all(&root).expect("Failed to remove root directory");
+        assert_eq!(applied, 1);
+        assert!(adjacencies
+            .expect("Failed to read adjacencies")
+            .is_terminated());
+        let report = map.validate();
+        assert_eq!(
+            report.of_kind(FindingKind::UnterminatedAdjacencies).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn it_exports_the_parsed_components_as_json() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::deferred())
+        });
+        let mut map = rt.block_on(handle).unwrap().expect("Failed to load map");
+        let path = std::env::temp_dir().join("world_gen_export.json");
+        map.export_json(&path).expect("Failed to export the map");
+        let data = fs::read_to_string(&path).expect("Failed to read the export");
+        fs::remove_file(&path).expect("Failed to remove the export");
+        let export: serde_json::Value = serde_json::from_str(&data).expect("Invalid JSON");
+
+        assert!(map.is_loaded(MapComponent::UnitStacks));
+        let states = export["states"].as_object().expect("No states");
+        assert_eq!(states.len(), map.states.len());
+        let railways = export["railways"]["railways"]
+            .as_array()
+            .expect("No railways");
+        assert_eq!(railways.len(), map.railways.railways.len());
+        assert!(export["strategic_regions"].get("sources").is_none());
+    }
+
+    #[test]
+    fn it_reports_every_problem_at_once() {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let handle = rt.spawn_blocking(|| {
+            Map::new::<InMemoryTerm>(Path::new("./test"), &None, &MapLoadOptions::new())
+        });
+        let mut map = rt.block_on(handle).unwrap().expect("Failed to load map");
+        let corner = map.provinces_by_color[map.provinces.get_pixel(0, 0)];
+        map.definitions.definitions.remove(&corner);
+        let terrain_findings = map.definitions.verify_province_terrain().len();
+
+        let report = map.validate();
+        assert!(report.has_errors());
+        assert_eq!(
+            report.of_kind(FindingKind::UndefinedTerrain).count(),
+            terrain_findings
+        );
+        let undefined = report
+            .of_kind(FindingKind::UndefinedProvinceColor)
+            .collect::<Vec<_>>();
+        assert_eq!(undefined.len(), 1);
+        assert_eq!(undefined[0].pixel, Some((0, 0)));
+        assert_eq!(report.findings[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn it_names_the_continents_after_the_provinces_without_one() {
+        let continents = Continents {
+            continents: vec![
+                Continent("europe".to_owned()),
+                Continent("north_america".to_owned()),
+            ],
+        };
+        let names = continent_names(&continents);
+        assert_eq!(names.len(), 3);
+        assert_eq!(names[&0], (0, "No continent".to_owned()));
+        assert_eq!(names[&2], (2, "north_america".to_owned()));
     }
 }
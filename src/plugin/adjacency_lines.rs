@@ -0,0 +1,45 @@
+use crate::components::prelude::*;
+use crate::map::Map;
+use crate::plugin::drawing::{draw_line, province_centers};
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the sea crossings and the adjacencies without a type
+const CROSSING: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// The color of the adjacencies that block movement between provinces
+const IMPASSABLE: Rgba<u8> = Rgba([224, 32, 32, 255]);
+/// The color of the adjacencies across rivers
+const RIVER: Rgba<u8> = Rgba([32, 128, 255, 255]);
+
+/// An overlay drawing each adjacency of `map/adjacencies.csv` as a line between the centers of
+/// its provinces, colored by its type.  Adjacencies to a province that is not on the map are left
+/// out.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct AdjacencyLines;
+
+impl MapLayer for AdjacencyLines {
+    #[inline]
+    fn name(&self) -> &str {
+        "Adjacencies"
+    }
+
+    #[inline]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let centers = province_centers(&map.province_index);
+        for adjacency in &map.adjacencies.adjacencies {
+            let color = match adjacency.adjacency_type {
+                Some(AdjacencyType::Impassable) => IMPASSABLE,
+                Some(AdjacencyType::River | AdjacencyType::LargeRiver) => RIVER,
+                Some(AdjacencyType::Sea) | None => CROSSING,
+            };
+            let ends = (centers.get(&adjacency.from), centers.get(&adjacency.to));
+            if let (Some(from), Some(to)) = ends {
+                draw_line(&mut image, *from, *to, 1, color);
+            }
+        }
+        image
+    }
+}
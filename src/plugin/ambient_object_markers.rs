@@ -0,0 +1,44 @@
+use crate::map::Map;
+use crate::plugin::drawing::draw_disc;
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the outline of the ambient object markers
+const OUTLINE: Rgba<u8> = Rgba([0, 0, 0, 255]);
+/// The color of the ambient object markers
+const AMBIENT_OBJECT: Rgba<u8> = Rgba([224, 64, 224, 255]);
+
+/// An overlay marking each position of the objects of `map/ambient_object.txt` with an outlined
+/// dot, so that the frame of the map and the objects placed on it can be told apart before any of
+/// them are removed
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct AmbientObjectMarkers;
+
+impl MapLayer for AmbientObjectMarkers {
+    #[inline]
+    fn name(&self) -> &str {
+        "Ambient Objects"
+    }
+
+    #[inline]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_precision_loss)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let positions = map
+            .ambient_objects
+            .objects
+            .iter()
+            .flat_map(|object| &object.positions);
+        for [x, _y, z] in positions {
+            // The z coordinate runs up from the bottom of the map, unlike the rows of the image
+            let center = (x.round() as i64, (height as f32 - z).round() as i64);
+            draw_disc(&mut image, center, 4, OUTLINE);
+            draw_disc(&mut image, center, 3, AMBIENT_OBJECT);
+        }
+        image
+    }
+}
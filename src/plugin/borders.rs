@@ -0,0 +1,48 @@
+use crate::map::Map;
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the borders between provinces of the same state
+const PROVINCE_BORDER: Rgba<u8> = Rgba([64, 64, 64, 128]);
+/// The color of the borders between states, and between a state and the sea
+const STATE_BORDER: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// An overlay outlining the provinces, with the borders between states drawn darker than those
+/// within a state.  A pixel is on a border when the pixel to its right or below it belongs to
+/// another province.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct Borders;
+
+impl MapLayer for Borders {
+    #[inline]
+    fn name(&self) -> &str {
+        "Borders"
+    }
+
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let index = &map.province_index;
+        let (width, height) = index.dimensions();
+        RgbaImage::from_fn(width, height, |x, y| {
+            let province = index.province_at(x, y);
+            let neighbors = [
+                (x + 1 < width).then(|| index.province_at(x + 1, y)),
+                (y + 1 < height).then(|| index.province_at(x, y + 1)),
+            ];
+            let state = |p| map.states_by_province.get(&p);
+            let mut color = Rgba([0, 0, 0, 0]);
+            for neighbor in neighbors.into_iter().flatten() {
+                if neighbor == province {
+                    continue;
+                }
+                if province.and_then(state) != neighbor.and_then(state) {
+                    return STATE_BORDER;
+                }
+                color = PROVINCE_BORDER;
+            }
+            color
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use crate::map::Map;
+use crate::plugin::drawing::{draw_disc, province_centers};
+use crate::plugin::MapLayer;
+use crate::political_map::country_color;
+use image::{Rgba, RgbaImage};
+
+/// The color of the outline of the capital markers
+const OUTLINE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// An overlay marking the capital of each country of `history/countries` with a dot in the color
+/// of the country.  The dot is at the province of the capital state with the most victory points,
+/// where the game places the capital, or else at its province with the lowest id.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct CapitalMarkers;
+
+impl MapLayer for CapitalMarkers {
+    #[inline]
+    fn name(&self) -> &str {
+        "Capitals"
+    }
+
+    #[inline]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let centers = province_centers(&map.province_index);
+        for country in map.country_histories.countries.values() {
+            let state = match country.capital.and_then(|id| map.states.get(&id)) {
+                Some(state) => state,
+                None => continue,
+            };
+            let victory_point = state
+                .history
+                .iter()
+                .flat_map(|history| &history.victory_points)
+                .filter(|(province, _points)| state.provinces.contains(province))
+                .max_by(|(_a, a), (_b, b)| a.0.total_cmp(&b.0))
+                .map(|(province, _points)| *province);
+            let province = victory_point.or_else(|| state.provinces.iter().min().copied());
+            if let Some((x, y)) = province.and_then(|p| centers.get(&p)) {
+                let [red, green, blue] = country_color(&country.tag, &map.country_colors).0;
+                let center = (i64::from(*x), i64::from(*y));
+                draw_disc(&mut image, center, 6, OUTLINE);
+                draw_disc(&mut image, center, 4, Rgba([red, green, blue, 255]));
+            }
+        }
+        image
+    }
+}
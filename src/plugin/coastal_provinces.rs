@@ -0,0 +1,34 @@
+use crate::map::Map;
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color coastal provinces are highlighted with
+const HIGHLIGHT: Rgba<u8> = Rgba([255, 64, 0, 160]);
+
+/// An overlay highlighting the provinces marked as coastal in the definitions.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct CoastalProvinces;
+
+impl MapLayer for CoastalProvinces {
+    #[inline]
+    fn name(&self) -> &str {
+        "Coastal Provinces"
+    }
+
+    #[inline]
+    fn render(&self, map: &Map) -> RgbaImage {
+        RgbaImage::from_fn(map.provinces.width(), map.provinces.height(), |x, y| {
+            let is_coastal = map
+                .provinces_by_color
+                .get(map.provinces.get_pixel(x, y))
+                .and_then(|id| map.definitions.definitions.get(id))
+                .map_or(false, |definition| definition.coastal.0);
+            if is_coastal {
+                HIGHLIGHT
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+}
@@ -0,0 +1,127 @@
+use crate::components::prelude::*;
+use crate::province_index::ProvinceIndex;
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// The mean position of the pixels of each province, where the overlays place the markers and
+/// lines of a province.  The center of a crescent shaped province may lie outside of it.
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::integer_arithmetic)]
+pub(crate) fn province_centers(province_index: &ProvinceIndex) -> HashMap<ProvinceId, (u32, u32)> {
+    let (width, _height) = province_index.dimensions();
+    let width = u64::from(width.max(1));
+    let mut sums: HashMap<ProvinceId, (u64, u64, u64)> = HashMap::new();
+    for (position, province) in (0_u64..).zip(province_index.provinces()) {
+        if let Some(id) = province {
+            let (x, y) = (position % width, position / width);
+            let (sum_x, sum_y, count) = sums.entry(id).or_default();
+            *sum_x = sum_x.saturating_add(x);
+            *sum_y = sum_y.saturating_add(y);
+            *count = count.saturating_add(1);
+        }
+    }
+    sums.into_iter()
+        .map(|(id, (sum_x, sum_y, count))| {
+            let count = count.max(1) as f64;
+            let center = (
+                (sum_x as f64 / count).round() as u32,
+                (sum_y as f64 / count).round() as u32,
+            );
+            (id, center)
+        })
+        .collect()
+}
+
+/// Fills the pixels of the image within a radius of a point, leaving out those past its edges
+#[allow(clippy::integer_arithmetic)]
+pub(crate) fn draw_disc(image: &mut RgbaImage, center: (i64, i64), radius: i64, color: Rgba<u8>) {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let (x, y) = (u32::try_from(center.0 + dx), u32::try_from(center.1 + dy));
+            if let (Ok(x), Ok(y)) = (x, y) {
+                if x < image.width() && y < image.height() {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Draws a straight line between two points, with discs of a radius stamped along it so that a
+/// radius of 0 is a line one pixel wide
+#[allow(clippy::integer_arithmetic)]
+pub(crate) fn draw_line(
+    image: &mut RgbaImage,
+    from: (u32, u32),
+    to: (u32, u32),
+    radius: i64,
+    color: Rgba<u8>,
+) {
+    // Bresenham's line algorithm, stepping one pixel at a time along the longer axis
+    let (mut x, mut y) = (i64::from(from.0), i64::from(from.1));
+    let (end_x, end_y) = (i64::from(to.0), i64::from(to.1));
+    let (dx, dy) = ((end_x - x).abs(), -(end_y - y).abs());
+    let (step_x, step_y) = ((end_x - x).signum(), (end_y - y).signum());
+    let mut error = dx + dy;
+    loop {
+        draw_disc(image, (x, y), radius, color);
+        if x == end_x && y == end_y {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn it_finds_the_centers_of_the_provinces() {
+        // Province 1 is the left 4 columns and province 2 the right 2 columns
+        let provinces = RgbImage::from_fn(6, 3, |x, _| Rgb([if x < 4 { 1 } else { 2 }, 0, 0]));
+        let provinces_by_color = (1..=2)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let centers = province_centers(&index);
+        assert_eq!(centers[&ProvinceId(1)], (2, 1));
+        assert_eq!(centers[&ProvinceId(2)], (5, 1));
+    }
+
+    #[test]
+    fn it_draws_lines_between_points() {
+        let color = Rgba([255, 0, 0, 255]);
+        let mut image = RgbaImage::new(8, 8);
+        draw_line(&mut image, (0, 0), (7, 7), 0, color);
+        assert!((0..8).all(|i| *image.get_pixel(i, i) == color));
+        assert_eq!(image.pixels().filter(|p| **p == color).count(), 8);
+
+        let mut image = RgbaImage::new(8, 8);
+        draw_line(&mut image, (1, 4), (6, 4), 1, color);
+        assert_eq!(*image.get_pixel(3, 3), color);
+        assert_eq!(*image.get_pixel(3, 5), color);
+        assert_eq!(*image.get_pixel(3, 6), Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,100 @@
+use crate::plugin::PluginRegistry;
+use crate::MapError;
+use libloading::Library;
+use log::{info, warn};
+use std::env::consts::DLL_EXTENSION;
+use std::fs;
+use std::path::Path;
+
+/// The name of the function a plugin library exports to register its layers and tools
+pub const REGISTER_SYMBOL: &str = "world_gen_register";
+
+/// The function a plugin library exports as `world_gen_register`, declared in the plugin as
+/// `#[no_mangle] pub fn world_gen_register(registry: &mut PluginRegistry)`
+pub type RegisterFn = fn(&mut PluginRegistry);
+
+impl PluginRegistry {
+    /// Loads a plugin library and registers its layers and tools.  The library is never
+    /// unloaded, as its layers and tools may be used until the editor exits.
+    /// # Errors
+    /// If the library cannot be loaded or does not export `world_gen_register`
+    /// # Safety
+    /// Loading a library runs its initialization code, and calling `world_gen_register` passes
+    /// Rust types across the library boundary, so the library must be trusted and built with the
+    /// same compiler and the same version of this crate as the editor.
+    #[inline]
+    pub unsafe fn load_library(&mut self, path: &Path) -> Result<(), MapError> {
+        // SAFETY: the caller guarantees that the library is trusted
+        let library: &'static Library = Box::leak(Box::new(unsafe { Library::new(path) }?));
+        // SAFETY: the caller guarantees that the library was built against this crate, so its
+        // `world_gen_register` has the signature of `RegisterFn`
+        let register = unsafe { library.get::<RegisterFn>(REGISTER_SYMBOL.as_bytes()) }?;
+        register(self);
+        info!("Loaded the plugin {}", path.display());
+        Ok(())
+    }
+
+    /// Loads every plugin library in a directory, in order of their file names, returning how
+    /// many were loaded.  A missing directory has no plugins, and a library that fails to load
+    /// is logged and skipped so that one broken plugin does not keep the editor from starting.
+    /// # Errors
+    /// If the directory exists but cannot be read
+    /// # Safety
+    /// Every library in the directory must be safe to load with `load_library`.
+    #[inline]
+    pub unsafe fn load_dir(&mut self, dir: &Path) -> Result<usize, MapError> {
+        if !dir.is_dir() {
+            return Ok(0);
+        }
+        let mut libraries = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |e| e == DLL_EXTENSION) {
+                libraries.push(path);
+            }
+        }
+        libraries.sort();
+        let mut loaded = 0_usize;
+        for path in libraries {
+            // SAFETY: the caller guarantees that every library in the directory is safe to load
+            match unsafe { self.load_library(&path) } {
+                Ok(()) => loaded = loaded.saturating_add(1),
+                Err(e) => warn!("Failed to load the plugin {}: {e}", path.display()),
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_loads_nothing_from_a_missing_directory() {
+        let dir = std::env::temp_dir().join("world_gen_no_plugins");
+        let _result = fs::remove_dir_all(&dir);
+        let mut registry = PluginRegistry::default();
+        // SAFETY: the directory does not exist, so no library is loaded
+        let loaded = unsafe { registry.load_dir(&dir) }.expect("Failed to read plugins");
+        assert_eq!(loaded, 0);
+        assert!(registry.layers().is_empty());
+    }
+
+    #[test]
+    fn it_skips_a_library_that_fails_to_load() {
+        let dir = std::env::temp_dir().join("world_gen_broken_plugins");
+        let _result = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("Failed to create dir");
+        let library = dir.join(format!("broken.{DLL_EXTENSION}"));
+        fs::write(&library, "not a library").expect("Failed to write library");
+        fs::write(dir.join("readme.txt"), "not a plugin").expect("Failed to write file");
+        let mut registry = PluginRegistry::default();
+        // SAFETY: the file is not a library, so loading it fails before any code runs
+        assert!(unsafe { registry.load_library(&library) }.is_err());
+        // SAFETY: as above
+        let loaded = unsafe { registry.load_dir(&dir) }.expect("Failed to read plugins");
+        assert_eq!(loaded, 0);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::components::prelude::*;
+use crate::map::Map;
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// The color of the stripes hatching the provinces of impassable states
+const IMPASSABLE: Rgba<u8> = Rgba([32, 32, 32, 200]);
+/// The color of the stripes hatching lake provinces
+const LAKE: Rgba<u8> = Rgba([0, 220, 255, 200]);
+/// The color sea provinces are tinted with
+const SEA: Rgba<u8> = Rgba([20, 40, 160, 96]);
+/// The number of pixels from one stripe of a hatching to the next
+const HATCH_SPACING: u32 = 8;
+/// The width of the stripes of a hatching in pixels
+const HATCH_WIDTH: u32 = 3;
+
+/// How a province is marked on the overlay
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Marking {
+    /// Hatched with stripes rising to the right
+    Impassable,
+    /// Hatched with stripes falling to the right
+    Lake,
+    /// Tinted without stripes
+    Sea,
+}
+
+/// An overlay hatching the provinces of impassable states and lakes, and tinting the sea, so that
+/// they can be told apart in any display mode.  A province of an impassable state is hatched as
+/// impassable whatever its type.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct ImpassableProvinces;
+
+impl MapLayer for ImpassableProvinces {
+    #[inline]
+    fn name(&self) -> &str {
+        "Impassable, Lakes and Seas"
+    }
+
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let markings = map
+            .definitions
+            .definitions
+            .values()
+            .filter_map(|definition| {
+                let impassable = map
+                    .states_by_province
+                    .get(&definition.id)
+                    .and_then(|state| map.states.get(state))
+                    .and_then(|state| state.impassable)
+                    .unwrap_or(false);
+                let marking = match definition.province_type {
+                    _ if impassable => Marking::Impassable,
+                    ProvinceType::Lake => Marking::Lake,
+                    ProvinceType::Sea => Marking::Sea,
+                    ProvinceType::Land => return None,
+                };
+                Some((definition.id, marking))
+            })
+            .collect::<HashMap<_, _>>();
+        let (width, height) = map.province_index.dimensions();
+        RgbaImage::from_fn(width, height, |x, y| {
+            let marking = map
+                .province_index
+                .province_at(x, y)
+                .and_then(|id| markings.get(&id));
+            match marking {
+                Some(Marking::Impassable) if (x + y) % HATCH_SPACING < HATCH_WIDTH => IMPASSABLE,
+                Some(Marking::Lake)
+                    if (x + HATCH_SPACING - y % HATCH_SPACING) % HATCH_SPACING < HATCH_WIDTH =>
+                {
+                    LAKE
+                }
+                Some(Marking::Sea) => SEA,
+                _ => Rgba([0, 0, 0, 0]),
+            }
+        })
+    }
+}
@@ -0,0 +1,185 @@
+/// Holds the built in overlay of the adjacencies between provinces
+pub mod adjacency_lines;
+/// Holds the built in overlay of the ambient objects
+pub mod ambient_object_markers;
+/// Holds the built in overlay of the borders of provinces and states
+pub mod borders;
+/// Holds the built in overlay of the capitals of the countries
+pub mod capital_markers;
+/// Holds the built in overlay of coastal provinces
+pub mod coastal_provinces;
+/// Holds the drawing of the lines and markers of the built in overlays
+pub(crate) mod drawing;
+/// Holds the loading of plugins from shared libraries
+#[cfg(feature = "dylib-plugins")]
+pub mod dylib;
+/// Holds the built in overlay hatching impassable provinces and lakes
+pub mod impassable_provinces;
+/// Holds the built in overlay of the railways
+pub mod railway_lines;
+/// Holds the built in overlay of the borders crossed by rivers
+pub mod river_crossings;
+/// Holds the built in overlay of the supply nodes
+pub mod supply_node_markers;
+/// Holds the built in overlay of the victory points
+pub mod victory_point_markers;
+/// Holds the built in overlay of lakes and seas
+pub mod water_bodies;
+
+use crate::map::Map;
+use crate::MapDisplayMode;
+use egui::Pos2;
+use image::RgbaImage;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// An overlay drawn on top of the current map display mode.
+pub trait MapLayer: Debug + Send + Sync {
+    /// The name of the layer shown in the UI
+    fn name(&self) -> &str;
+
+    /// Renders the layer for the map.  The image should be the same size as the provinces image,
+    /// and fully transparent wherever the map underneath should show through.
+    fn render(&self, map: &Map) -> RgbaImage;
+}
+
+/// A tool that edits the map when it is clicked or dragged over.
+pub trait MapTool: Debug + Send + Sync {
+    /// The name of the tool shown in the UI
+    fn name(&self) -> &str;
+
+    /// Whether the tool can be used while the map is shown in the given display mode
+    #[inline]
+    fn applies_to(&self, _mode: MapDisplayMode) -> bool {
+        true
+    }
+
+    /// Applies the tool to the map at the given texture uv coordinate.
+    /// Returns the display modes whose images were changed by the tool.
+    fn apply(&self, map: &mut Map, point: Pos2) -> Vec<MapDisplayMode>;
+}
+
+/// A collection of layers and tools that can be registered together.
+pub trait Plugin {
+    /// Registers the layers and tools of the plugin
+    fn register(&self, registry: &mut PluginRegistry);
+}
+
+/// The layers and tools available to the UI, registered at startup.
+#[derive(Default, Clone)]
+#[non_exhaustive]
+pub struct PluginRegistry {
+    layers: Vec<Arc<dyn MapLayer>>,
+    tools: Vec<Arc<dyn MapTool>>,
+}
+
+impl PluginRegistry {
+    /// Creates a registry with the built in layers and tools
+    #[inline]
+    #[must_use]
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        registry.register_layer(coastal_provinces::CoastalProvinces);
+        registry.register_layer(water_bodies::WaterBodies);
+        registry.register_layer(impassable_provinces::ImpassableProvinces);
+        registry.register_layer(borders::Borders);
+        registry.register_layer(railway_lines::RailwayLines);
+        registry.register_layer(supply_node_markers::SupplyNodeMarkers);
+        registry.register_layer(adjacency_lines::AdjacencyLines);
+        registry.register_layer(victory_point_markers::VictoryPointMarkers);
+        registry.register_layer(river_crossings::RiverCrossings);
+        registry.register_layer(ambient_object_markers::AmbientObjectMarkers);
+        registry.register_layer(capital_markers::CapitalMarkers);
+        registry
+    }
+
+    /// Registers an overlay layer
+    #[inline]
+    pub fn register_layer<L: MapLayer + 'static>(&mut self, layer: L) {
+        self.layers.push(Arc::new(layer));
+    }
+
+    /// Registers a tool
+    #[inline]
+    pub fn register_tool<T: MapTool + 'static>(&mut self, tool: T) {
+        self.tools.push(Arc::new(tool));
+    }
+
+    /// Registers all the layers and tools of a plugin
+    #[inline]
+    pub fn register_plugin<P: Plugin + ?Sized>(&mut self, plugin: &P) {
+        plugin.register(self);
+    }
+
+    /// The registered layers, in the order they were registered
+    #[inline]
+    #[must_use]
+    pub fn layers(&self) -> &[Arc<dyn MapLayer>] {
+        &self.layers
+    }
+
+    /// The registered tools, in the order they were registered
+    #[inline]
+    #[must_use]
+    pub fn tools(&self) -> &[Arc<dyn MapTool>] {
+        &self.tools
+    }
+}
+
+impl Debug for PluginRegistry {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginRegistry")
+            .field(
+                "layers",
+                &self.layers.iter().map(|l| l.name()).collect::<Vec<_>>(),
+            )
+            .field(
+                "tools",
+                &self.tools.iter().map(|t| t.name()).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct NoopTool;
+
+    impl MapTool for NoopTool {
+        fn name(&self) -> &str {
+            "Noop"
+        }
+
+        fn applies_to(&self, mode: MapDisplayMode) -> bool {
+            mode == MapDisplayMode::Provinces
+        }
+
+        fn apply(&self, _map: &mut Map, _point: Pos2) -> Vec<MapDisplayMode> {
+            Vec::new()
+        }
+    }
+
+    struct NoopPlugin;
+
+    impl Plugin for NoopPlugin {
+        fn register(&self, registry: &mut PluginRegistry) {
+            registry.register_tool(NoopTool);
+        }
+    }
+
+    #[test]
+    fn it_registers_plugins() {
+        let mut registry = PluginRegistry::with_builtins();
+        registry.register_plugin(&NoopPlugin);
+        assert_eq!(registry.layers().len(), 11);
+        assert_eq!(registry.tools().len(), 1);
+        assert_eq!(registry.tools()[0].name(), "Noop");
+        assert!(registry.tools()[0].applies_to(MapDisplayMode::Provinces));
+        assert!(!registry.tools()[0].applies_to(MapDisplayMode::Rivers));
+    }
+}
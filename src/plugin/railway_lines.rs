@@ -0,0 +1,40 @@
+use crate::map::Map;
+use crate::plugin::drawing::{draw_line, province_centers};
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the railways
+const RAILWAY: Rgba<u8> = Rgba([96, 48, 16, 255]);
+
+/// An overlay drawing each railway of `map/railways.txt` as lines between the centers of the
+/// provinces along it, with higher levels drawn thicker
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct RailwayLines;
+
+impl MapLayer for RailwayLines {
+    #[inline]
+    fn name(&self) -> &str {
+        "Railways"
+    }
+
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let centers = province_centers(&map.province_index);
+        for railway in &map.railways.railways {
+            // Levels run from 1 to 5, drawn from 1 to 3 pixels wide
+            let radius = i64::from(railway.level.0.clamp(1, 5).saturating_sub(1) / 2);
+            for pair in railway.provinces.windows(2) {
+                if let [from, to] = pair {
+                    if let (Some(from), Some(to)) = (centers.get(from), centers.get(to)) {
+                        draw_line(&mut image, *from, *to, radius, RAILWAY);
+                    }
+                }
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,53 @@
+use crate::map::Map;
+use crate::plugin::MapLayer;
+use crate::validation::river_crossings::{find_river_crossings, RiverCrossing};
+use image::{Rgba, RgbaImage};
+
+/// The color of the borders crossed only by small rivers
+const SMALL_CROSSING: Rgba<u8> = Rgba([255, 208, 0, 255]);
+/// The color of the borders crossed by a large river
+const LARGE_CROSSING: Rgba<u8> = Rgba([255, 32, 32, 255]);
+
+/// An overlay highlighting the borders between provinces that a river crosses, as the game sees
+/// them when it applies the penalties for attacking across a river.  The whole border of a pair of
+/// provinces is drawn in the color of the widest river crossing it.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct RiverCrossings;
+
+impl MapLayer for RiverCrossings {
+    #[inline]
+    fn name(&self) -> &str {
+        "River Crossings"
+    }
+
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let index = &map.province_index;
+        let crossings = find_river_crossings(index, &map.rivers);
+        let (width, height) = index.dimensions();
+        RgbaImage::from_fn(width, height, |x, y| {
+            let province = index.province_at(x, y);
+            let neighbors = [
+                (x > 0).then(|| index.province_at(x - 1, y)),
+                (x + 1 < width).then(|| index.province_at(x + 1, y)),
+                (y > 0).then(|| index.province_at(x, y - 1)),
+                (y + 1 < height).then(|| index.province_at(x, y + 1)),
+            ];
+            let crossing = neighbors
+                .into_iter()
+                .flatten()
+                .filter_map(|neighbor| {
+                    let (a, b) = (province?, neighbor?);
+                    crossings.get(&(a.min(b), a.max(b))).copied()
+                })
+                .max();
+            match crossing {
+                Some(RiverCrossing::Large) => LARGE_CROSSING,
+                Some(RiverCrossing::Small) => SMALL_CROSSING,
+                None => Rgba([0, 0, 0, 0]),
+            }
+        })
+    }
+}
@@ -0,0 +1,37 @@
+use crate::map::Map;
+use crate::plugin::drawing::{draw_disc, province_centers};
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the outline of the supply node markers
+const OUTLINE: Rgba<u8> = Rgba([0, 0, 0, 255]);
+/// The color of the supply node markers
+const SUPPLY_NODE: Rgba<u8> = Rgba([255, 176, 0, 255]);
+
+/// An overlay marking the provinces with a supply node of `map/supply_nodes.txt` with an outlined
+/// dot at their center
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct SupplyNodeMarkers;
+
+impl MapLayer for SupplyNodeMarkers {
+    #[inline]
+    fn name(&self) -> &str {
+        "Supply Nodes"
+    }
+
+    #[inline]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let centers = province_centers(&map.province_index);
+        for node in &map.supply_nodes.nodes {
+            if let Some((x, y)) = centers.get(node) {
+                let center = (i64::from(*x), i64::from(*y));
+                draw_disc(&mut image, center, 4, OUTLINE);
+                draw_disc(&mut image, center, 3, SUPPLY_NODE);
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,48 @@
+use crate::map::Map;
+use crate::plugin::drawing::{draw_disc, province_centers};
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color of the outline of the victory point markers
+const OUTLINE: Rgba<u8> = Rgba([0, 0, 0, 255]);
+/// The color of the victory point markers
+const VICTORY_POINT: Rgba<u8> = Rgba([255, 255, 255, 255]);
+
+/// An overlay marking the provinces given victory points in the state histories with a dot at
+/// their center, larger for provinces worth more points
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct VictoryPointMarkers;
+
+impl MapLayer for VictoryPointMarkers {
+    #[inline]
+    fn name(&self) -> &str {
+        "Victory Points"
+    }
+
+    #[inline]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    fn render(&self, map: &Map) -> RgbaImage {
+        let (width, height) = map.province_index.dimensions();
+        let mut image = RgbaImage::new(width, height);
+        let centers = province_centers(&map.province_index);
+        let victory_points = map
+            .states
+            .values()
+            .filter_map(|state| state.history.as_ref())
+            .flat_map(|history| &history.victory_points);
+        for (province, points) in victory_points {
+            if let Some((x, y)) = centers.get(province) {
+                // From 2 pixels for a single point to 6 for the 50 points of a capital
+                let radius = (points.0.max(1.0).sqrt().round() as i64)
+                    .saturating_add(1)
+                    .clamp(2, 6);
+                let center = (i64::from(*x), i64::from(*y));
+                draw_disc(&mut image, center, radius.saturating_add(1), OUTLINE);
+                draw_disc(&mut image, center, radius, VICTORY_POINT);
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,37 @@
+use crate::components::prelude::*;
+use crate::map::Map;
+use crate::plugin::MapLayer;
+use image::{Rgba, RgbaImage};
+
+/// The color lake provinces are highlighted with
+const LAKE: Rgba<u8> = Rgba([0, 220, 255, 160]);
+/// The color sea provinces are highlighted with
+const SEA: Rgba<u8> = Rgba([20, 40, 160, 160]);
+
+/// An overlay showing which water provinces are lakes and which are sea.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct WaterBodies;
+
+impl MapLayer for WaterBodies {
+    #[inline]
+    fn name(&self) -> &str {
+        "Lakes and Seas"
+    }
+
+    #[inline]
+    fn render(&self, map: &Map) -> RgbaImage {
+        RgbaImage::from_fn(map.provinces.width(), map.provinces.height(), |x, y| {
+            let province_type = map
+                .provinces_by_color
+                .get(map.provinces.get_pixel(x, y))
+                .and_then(|id| map.definitions.definitions.get(id))
+                .map(|definition| definition.province_type);
+            match province_type {
+                Some(ProvinceType::Lake) => LAKE,
+                Some(ProvinceType::Sea) => SEA,
+                _ => Rgba([0, 0, 0, 0]),
+            }
+        })
+    }
+}
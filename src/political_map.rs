@@ -0,0 +1,142 @@
+use crate::components::country::CountryColors;
+use crate::components::country_history::CountryHistories;
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::legend::{name_color, Legend, LegendEntry};
+use crate::province_index::ProvinceIndex;
+use image::{Rgb, RgbImage};
+use std::collections::{BTreeMap, HashMap};
+
+/// The color of the provinces of states without an owner, and of provinces without a state
+const UNOWNED: Rgb<u8> = Rgb([48, 48, 48]);
+
+/// Draws each state in the color of the country that owns it at the start of the game, along with
+/// a legend of the countries and how many states each owns.  Countries without a color in
+/// `common/countries/colors.txt` are given a color mixed from their tag, so that they keep the
+/// same color every time the map is drawn.  Countries with a history in `history/countries` give
+/// the state of their capital in the legend.  States without an owner are dark grey, and pixels
+/// without a province are black.
+#[inline]
+#[must_use]
+pub fn render_political(
+    states: &HashMap<StateId, State>,
+    country_colors: &CountryColors,
+    country_histories: &CountryHistories,
+    province_index: &ProvinceIndex,
+) -> (RgbImage, Legend) {
+    let mut owned: BTreeMap<&CountryTag, usize> = BTreeMap::new();
+    let mut colors = HashMap::new();
+    for state in states.values() {
+        if let Some(history) = &state.history {
+            let color = country_color(&history.owner, country_colors);
+            colors.extend(state.provinces.iter().map(|p| (*p, color)));
+            let count = owned.entry(&history.owner).or_default();
+            *count = count.saturating_add(1);
+        }
+    }
+    let map = province_index.paint(|id| Some(colors.get(&id).copied().unwrap_or(UNOWNED)));
+    let legend = owned
+        .into_iter()
+        .zip(1..)
+        .map(|((tag, count), id)| {
+            let mut name = match count {
+                1 => format!("{tag}: 1 state"),
+                _ => format!("{tag}: {count} states"),
+            };
+            let capital = country_histories
+                .countries
+                .get(tag)
+                .and_then(|history| history.capital);
+            if let Some(capital) = capital {
+                name.push_str(&format!(" (capital {capital})"));
+            }
+            LegendEntry::new(id, name, country_color(tag, country_colors))
+        })
+        .collect();
+    (map, Legend::new(legend))
+}
+
+/// The color of a country, from `common/countries/colors.txt` or else mixed from its tag
+#[inline]
+#[must_use]
+pub fn country_color(tag: &CountryTag, country_colors: &CountryColors) -> Rgb<u8> {
+    country_colors
+        .colors
+        .get(tag)
+        .copied()
+        .unwrap_or_else(|| name_color(&tag.0))
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::country_history::CountryHistory;
+    use crate::components::state::StateHistory;
+    use std::collections::HashSet;
+
+    fn state(id: i32, province: i32, owner: Option<&str>) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: owner.map(|owner| StateHistory {
+                owner: CountryTag(owner.to_owned()),
+                controller: None,
+                victory_points: Vec::new(),
+            }),
+            provinces: HashSet::from([ProvinceId(province)]),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    #[test]
+    fn it_colors_the_states_by_their_owner() {
+        // Provinces 1 to 4 are the columns of the map
+        let provinces = RgbImage::from_fn(4, 1, |x, _| Rgb([(x + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=4)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let states = HashMap::from([
+            (StateId(1), state(1, 1, Some("GER"))),
+            (StateId(2), state(2, 2, Some("GER"))),
+            (StateId(3), state(3, 3, Some("FRA"))),
+            (StateId(4), state(4, 4, None)),
+        ]);
+        let country_colors = CountryColors {
+            colors: HashMap::from([(CountryTag("GER".to_owned()), Rgb([85, 92, 74]))]),
+        };
+
+        let histories = CountryHistories {
+            countries: HashMap::from([(
+                CountryTag("GER".to_owned()),
+                CountryHistory {
+                    tag: CountryTag("GER".to_owned()),
+                    capital: Some(StateId(1)),
+                },
+            )]),
+        };
+
+        let (map, legend) = render_political(&states, &country_colors, &histories, &index);
+        assert_eq!(*map.get_pixel(0, 0), Rgb([85, 92, 74]));
+        assert_eq!(*map.get_pixel(1, 0), Rgb([85, 92, 74]));
+        let france = country_color(&CountryTag("FRA".to_owned()), &country_colors);
+        assert_eq!(*map.get_pixel(2, 0), france);
+        assert_eq!(*map.get_pixel(3, 0), UNOWNED);
+        let names = legend
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["FRA: 1 state", "GER: 2 states (capital 1)"]);
+    }
+}
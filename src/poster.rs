@@ -0,0 +1,319 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::province_index::ProvinceIndex;
+use crate::MapError;
+use derive_more::Display;
+use image::imageops::{resize, FilterType};
+use image::{ImageOutputFormat, Rgb, RgbImage, Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// The longest side of a poster in pixels, past which the scale of a poster is lowered.  A poster
+/// of this size takes a gigabyte of memory to draw.
+pub const MAX_POSTER_SIDE: u32 = 16_384;
+
+/// The namespace of the elements of an SVG
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+/// How the labels of a poster are drawn: centered on their position in white, outlined in black
+const LABEL_STYLE: &str = "font-family:sans-serif; text-anchor:middle; \
+                           dominant-baseline:central; fill:white; stroke:black; \
+                           paint-order:stroke";
+
+/// The file formats a poster can be saved in
+#[allow(clippy::exhaustive_enums)]
+#[derive(Display, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PosterFormat {
+    /// An image enlarged by the scale of the poster
+    #[display(fmt = "PNG")]
+    Png,
+    /// The image of the map at its own size, drawn across the size of the poster with the names
+    /// of the states written over it
+    #[display(fmt = "SVG")]
+    Svg,
+}
+
+/// How a poster of the map is drawn
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PosterOptions {
+    /// The file format of the poster
+    pub format: PosterFormat,
+    /// How many pixels of the poster each pixel of the map is drawn across
+    pub scale: u32,
+    /// Whether the names of the states are written over the map, which only SVG posters can hold
+    pub labels: bool,
+}
+
+impl Default for PosterOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            format: PosterFormat::Png,
+            scale: 2,
+            labels: true,
+        }
+    }
+}
+
+impl PosterOptions {
+    /// Creates the options of a poster
+    #[inline]
+    #[must_use]
+    pub const fn new(format: PosterFormat, scale: u32, labels: bool) -> Self {
+        Self {
+            format,
+            scale,
+            labels,
+        }
+    }
+
+    /// The scale of a poster of a map of the given size, lowered so that neither side of the
+    /// poster is longer than `MAX_POSTER_SIDE`
+    #[inline]
+    #[must_use]
+    pub fn scale_for(&self, (width, height): (u32, u32)) -> u32 {
+        let longest = width.max(height).max(1);
+        self.scale
+            .min(MAX_POSTER_SIDE.checked_div(longest).unwrap_or_default())
+            .max(1)
+    }
+}
+
+/// Draws the layers over the image of a display mode, blending each by its opacity in the order
+/// they are given
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub fn composite(base: &RgbImage, layers: &[(RgbaImage, f32)]) -> RgbImage {
+    let mut poster = base.clone();
+    for (layer, opacity) in layers {
+        if layer.dimensions() != poster.dimensions() {
+            continue;
+        }
+        let opacity = opacity.clamp(0.0, 1.0);
+        for (pixel, Rgba([r, g, b, a])) in poster.pixels_mut().zip(layer.pixels()) {
+            let alpha = (f32::from(*a) * opacity).round() as u16;
+            for (channel, over) in pixel.0.iter_mut().zip([r, g, b]) {
+                let blended = u16::from(*over) * alpha + u16::from(*channel) * (255 - alpha);
+                *channel = ((blended + 127) / 255) as u8;
+            }
+        }
+    }
+    poster
+}
+
+/// Saves a poster of an image of the map, drawn from the image with the layers over it by
+/// `composite`.  A PNG poster is enlarged by its scale, each pixel of the map becoming a square
+/// so that the borders stay sharp.  An SVG poster holds the image at its own size, drawn across
+/// the size of the poster without smoothing, with the labels written over it as text.  A PNG
+/// poster has no labels, as there is no font to draw them with.
+/// # Errors
+/// If the image cannot be encoded or the file cannot be written
+#[inline]
+pub fn save_poster(
+    image: &RgbImage,
+    options: PosterOptions,
+    labels: &[Label],
+    path: &Path,
+) -> Result<(), MapError> {
+    let scale = options.scale_for(image.dimensions());
+    let (width, height) = (
+        image.width().saturating_mul(scale),
+        image.height().saturating_mul(scale),
+    );
+    match options.format {
+        PosterFormat::Png => resize(image, width, height, FilterType::Nearest).save(path)?,
+        PosterFormat::Svg => fs::write(path, svg_poster(image, (width, height), labels)?)?,
+    }
+    Ok(())
+}
+
+/// A name written over the map on a poster
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Label {
+    /// The text of the label
+    pub text: String,
+    /// The point of the map the label is centered on, in pixels of the map
+    pub position: (f64, f64),
+    /// The height of the text, in pixels of the map
+    pub size: f64,
+}
+
+/// Labels each state with its name, centered on its pixels and sized to fit them
+#[inline]
+#[must_use]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+pub fn state_labels(
+    states: &HashMap<StateId, State>,
+    states_by_province: &HashMap<ProvinceId, StateId>,
+    province_index: &ProvinceIndex,
+) -> Vec<Label> {
+    let (width, _height) = province_index.dimensions();
+    let width = u64::from(width.max(1));
+    let mut sums: HashMap<StateId, (u64, u64, u64)> = HashMap::new();
+    for (position, province) in (0_u64..).zip(province_index.provinces()) {
+        if let Some(state) = province.and_then(|p| states_by_province.get(&p)) {
+            let (sum_x, sum_y, count) = sums.entry(*state).or_default();
+            *sum_x = sum_x.saturating_add(position.checked_rem(width).unwrap_or_default());
+            *sum_y = sum_y.saturating_add(position.checked_div(width).unwrap_or_default());
+            *count = count.saturating_add(1);
+        }
+    }
+    let mut labels = sums
+        .into_iter()
+        .filter_map(|(id, (sum_x, sum_y, count))| {
+            let name = &states.get(&id)?.name;
+            let count = count.max(1) as f64;
+            // The center of a pixel is half a pixel from its corner
+            let position = (sum_x as f64 / count + 0.5, sum_y as f64 / count + 0.5);
+            let characters = name.0.chars().count().max(1) as f64;
+            let size = (count.sqrt() * 1.6 / characters).clamp(2.0, 24.0);
+            Some(Label {
+                text: name.0.clone(),
+                position,
+                size,
+            })
+        })
+        .collect::<Vec<_>>();
+    labels.sort_by(|a, b| a.text.cmp(&b.text));
+    labels
+}
+
+/// Writes an SVG holding the image at its own size, drawn across the size of the poster, with the
+/// labels over it
+fn svg_poster(
+    image: &RgbImage,
+    (width, height): (u32, u32),
+    labels: &[Label],
+) -> Result<String, MapError> {
+    let mut png = Cursor::new(Vec::new());
+    image.write_to(&mut png, ImageOutputFormat::Png)?;
+    let (map_width, map_height) = image.dimensions();
+    let mut svg = String::new();
+    let _result = writeln!(
+        svg,
+        r#"<svg xmlns="{SVG_NAMESPACE}" width="{width}" height="{height}" "#,
+    );
+    let _result = writeln!(svg, r#"viewBox="0 0 {map_width} {map_height}">"#);
+    let _result = writeln!(
+        svg,
+        r#"<image width="{map_width}" height="{map_height}" style="image-rendering:pixelated" "#,
+    );
+    let _result = writeln!(
+        svg,
+        r#"href="data:image/png;base64,{}"/>"#,
+        base64(png.get_ref())
+    );
+    let _result = writeln!(svg, r#"<g style="{LABEL_STYLE}">"#);
+    for label in labels {
+        let (x, y) = label.position;
+        let _result = writeln!(
+            svg,
+            r#"<text x="{x:.1}" y="{y:.1}" font-size="{:.1}" stroke-width="{:.2}">{}</text>"#,
+            label.size,
+            label.size / 8.0,
+            escape_xml(&label.text)
+        );
+    }
+    svg.push_str("</g>\n</svg>\n");
+    Ok(svg)
+}
+
+/// Escapes the characters of text that have a meaning in XML
+fn escape_xml(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+/// Encodes bytes in base64, to embed an image in an SVG
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::as_conversions)]
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().saturating_add(2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        // Each sextet is less than 64, so it is always within the alphabet
+        let sextet = |shift: u32| char::from(ALPHABET[((n >> shift) & 0x3f) as usize]);
+        encoded.push(sextet(18));
+        encoded.push(sextet(12));
+        encoded.push(if chunk.len() > 1 { sextet(6) } else { '=' });
+        encoded.push(if chunk.len() > 2 { sextet(0) } else { '=' });
+    }
+    encoded
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_blends_the_layers_over_the_map() {
+        let base = RgbImage::from_pixel(2, 1, Rgb([0, 0, 200]));
+        let mut layer = RgbaImage::new(2, 1);
+        layer.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let poster = composite(&base, &[(layer.clone(), 1.0)]);
+        assert_eq!(*poster.get_pixel(0, 0), Rgb([255, 0, 0]));
+        assert_eq!(*poster.get_pixel(1, 0), Rgb([0, 0, 200]));
+
+        let poster = composite(&base, &[(layer, 0.5)]);
+        assert_eq!(*poster.get_pixel(0, 0), Rgb([128, 0, 100]));
+    }
+
+    #[test]
+    fn it_keeps_posters_within_the_longest_side() {
+        let options = PosterOptions::new(PosterFormat::Png, 8, false);
+        assert_eq!(options.scale_for((1024, 512)), 8);
+        assert_eq!(options.scale_for((5632, 2048)), 2);
+        assert_eq!(options.scale_for((MAX_POSTER_SIDE * 2, 16)), 1);
+    }
+
+    #[test]
+    fn it_writes_labels_into_svg_posters() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+
+        let image = RgbImage::new(4, 2);
+        let labels = [Label {
+            text: "Alsace & Lorraine".to_owned(),
+            position: (2.0, 1.0),
+            size: 1.5,
+        }];
+        let svg = svg_poster(&image, (16, 8), &labels).expect("Failed to draw the poster");
+        assert!(svg.contains(r#"width="16" height="8" "#));
+        assert!(svg.contains(r#"viewBox="0 0 4 2">"#));
+        assert!(svg.contains("data:image/png;base64,iVBORw0KGgo"));
+        assert!(svg.contains(r#"<text x="2.0" y="1.0" font-size="1.5""#));
+        assert!(svg.contains(">Alsace &amp; Lorraine</text>"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::MapDisplayMode;
+use image::{RgbImage, Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// The color changed pixels are highlighted with in the preview layer
+const CHANGE_HIGHLIGHT: Rgba<u8> = Rgba([255, 0, 255, 128]);
+
+/// The images of the map as they were before the edits that have not been applied yet.
+///
+/// Edits are made to the map images straight away, so that the edited images can be shown as they
+/// would be saved.  The first edit of an image keeps a copy of it, which the edits are compared
+/// against to draw the preview layer and which the image is restored from if the edits are
+/// cancelled.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct EditPreview {
+    /// The images before the first pending edit, by the display mode they are shown in
+    originals: HashMap<MapDisplayMode, RgbImage>,
+}
+
+impl EditPreview {
+    /// Keeps a copy of the image before it is edited, unless it already has pending edits
+    #[inline]
+    pub fn record(&mut self, mode: MapDisplayMode, image: &RgbImage) {
+        self.originals.entry(mode).or_insert_with(|| image.clone());
+    }
+
+    /// Whether any image has pending edits
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.originals.is_empty()
+    }
+
+    /// Whether the image shown in the given display mode has pending edits
+    #[inline]
+    #[must_use]
+    pub fn is_edited(&self, mode: MapDisplayMode) -> bool {
+        self.originals.contains_key(&mode)
+    }
+
+    /// Draws a layer highlighting the pixels of the image that differ from before its pending
+    /// edits.  Returns `None` if the image has no pending edits or its size has changed.
+    #[inline]
+    #[must_use]
+    pub fn layer(&self, mode: MapDisplayMode, current: &RgbImage) -> Option<RgbaImage> {
+        let original = self.originals.get(&mode)?;
+        if original.dimensions() != current.dimensions() {
+            return None;
+        }
+        Some(RgbaImage::from_fn(
+            current.width(),
+            current.height(),
+            |x, y| {
+                if original.get_pixel(x, y) == current.get_pixel(x, y) {
+                    Rgba([0, 0, 0, 0])
+                } else {
+                    CHANGE_HIGHLIGHT
+                }
+            },
+        ))
+    }
+
+    /// Keeps the pending edits, returning the display modes of the edited images
+    #[inline]
+    pub fn apply(&mut self) -> Vec<MapDisplayMode> {
+        self.originals
+            .drain()
+            .map(|(mode, _original)| mode)
+            .collect()
+    }
+
+    /// Keeps the pending edits, returning the images as they were before them so that the edits
+    /// can be undone later
+    #[inline]
+    pub fn finish(&mut self) -> Vec<(MapDisplayMode, RgbImage)> {
+        self.originals.drain().collect()
+    }
+
+    /// Discards the pending edits, returning the images as they were before them
+    #[inline]
+    pub fn cancel(&mut self) -> Vec<(MapDisplayMode, RgbImage)> {
+        self.originals.drain().collect()
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn it_highlights_pending_changes() {
+        let mut preview = EditPreview::default();
+        let mut image = RgbImage::new(4, 4);
+        preview.record(MapDisplayMode::HeightMap, &image);
+        image.put_pixel(1, 2, Rgb([10, 10, 10]));
+        // A second edit keeps the image from before the first
+        preview.record(MapDisplayMode::HeightMap, &image);
+        image.put_pixel(3, 3, Rgb([20, 20, 20]));
+
+        let layer = preview
+            .layer(MapDisplayMode::HeightMap, &image)
+            .expect("Failed to draw the preview layer");
+        assert_eq!(*layer.get_pixel(1, 2), CHANGE_HIGHLIGHT);
+        assert_eq!(*layer.get_pixel(3, 3), CHANGE_HIGHLIGHT);
+        assert_eq!(layer.get_pixel(0, 0).0[3], 0);
+        assert!(preview.layer(MapDisplayMode::Rivers, &image).is_none());
+    }
+
+    #[test]
+    fn it_applies_or_cancels_pending_edits() {
+        let mut preview = EditPreview::default();
+        let image = RgbImage::new(2, 2);
+        preview.record(MapDisplayMode::Rivers, &image);
+        assert!(preview.is_edited(MapDisplayMode::Rivers));
+        assert_eq!(preview.apply(), vec![MapDisplayMode::Rivers]);
+        assert!(preview.is_empty());
+
+        preview.record(MapDisplayMode::Rivers, &image);
+        let restored = preview.cancel();
+        assert_eq!(restored, vec![(MapDisplayMode::Rivers, image)]);
+        assert!(preview.is_empty());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::MapError;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// How far the loading of a component of the map has got
+#[allow(clippy::exhaustive_enums)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    /// The component is being loaded
+    Started,
+    /// The component was loaded
+    Finished,
+    /// The component was not loaded, as its loading was put off
+    Skipped,
+    /// The component failed to load, with the error it failed with
+    Failed(String),
+    /// The component was not loaded, as the loading of the map was canceled first
+    Canceled,
+}
+
+/// An event of the loading of a map, sent when a component starts and stops loading
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LoadProgress {
+    /// The name of the component, such as `states` or the file name of an image
+    pub component: String,
+    /// How far the loading of the component has got
+    pub state: LoadState,
+}
+
+impl LoadProgress {
+    /// Creates a new event of the loading of a component
+    #[inline]
+    #[must_use]
+    pub fn new(component: impl Into<String>, state: LoadState) -> Self {
+        Self {
+            component: component.into(),
+            state,
+        }
+    }
+}
+
+/// A token for canceling the loading of a map from another thread.  Loading stops before the
+/// next component starts once the token is canceled, as a component being read cannot be stopped.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Creates a new token that has not been canceled
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the loading of the maps given the token
+    #[inline]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token was canceled
+    #[inline]
+    #[must_use]
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fails with `MapError::LoadCanceled` if the token was canceled
+    /// # Errors
+    /// If the token was canceled
+    #[inline]
+    pub fn check(&self) -> Result<(), MapError> {
+        if self.is_canceled() {
+            return Err(MapError::LoadCanceled);
+        }
+        Ok(())
+    }
+}
+
+/// The steps of loading a map, which share the terminal, the progress channel and the
+/// cancellation of the load
+#[derive(Clone, Debug)]
+pub(crate) struct LoadSteps {
+    /// The terminal the bars of the steps are drawn on
+    progress: MultiProgress,
+    /// The style of the bars
+    progress_style: ProgressStyle,
+    /// The channel the events of the steps are sent to
+    events: Option<UnboundedSender<LoadProgress>>,
+    /// The token canceling the load
+    cancel: CancelToken,
+}
+
+impl LoadSteps {
+    /// Creates the steps of loading a map
+    pub(crate) const fn new(
+        progress: MultiProgress,
+        progress_style: ProgressStyle,
+        events: Option<UnboundedSender<LoadProgress>>,
+        cancel: CancelToken,
+    ) -> Self {
+        Self {
+            progress,
+            progress_style,
+            events,
+            cancel,
+        }
+    }
+
+    /// Adds the bar of a component to the terminal
+    pub(crate) fn step(&self, component: impl Into<String>) -> LoadStep {
+        LoadStep {
+            bar: self
+                .progress
+                .add(ProgressBar::new(1))
+                .with_style(self.progress_style.clone()),
+            component: component.into(),
+            events: self.events.clone(),
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    /// The terminal the bars of the steps are drawn on
+    pub(crate) const fn progress(&self) -> &MultiProgress {
+        &self.progress
+    }
+}
+
+/// The loading of a single component of the map, shown as a bar of the terminal and sent to the
+/// progress channel, if there is one
+#[derive(Debug)]
+pub(crate) struct LoadStep {
+    /// The bar of the terminal showing the component
+    bar: ProgressBar,
+    /// The name of the component
+    component: String,
+    /// The channel the events of the component are sent to
+    events: Option<UnboundedSender<LoadProgress>>,
+    /// The token canceling the load
+    cancel: CancelToken,
+}
+
+impl LoadStep {
+    /// Marks the component as being loaded, showing a message on its bar
+    /// # Errors
+    /// If the load was canceled, in which case the component should not be loaded
+    pub(crate) fn start(&self, message: impl Into<Cow<'static, str>>) -> Result<(), MapError> {
+        if self.cancel.is_canceled() {
+            self.bar.finish_and_clear();
+            self.send(LoadState::Canceled);
+            return Err(MapError::LoadCanceled);
+        }
+        self.bar.set_message(message);
+        self.send(LoadState::Started);
+        Ok(())
+    }
+
+    /// Marks the component as loaded or failed, depending on the result of loading it
+    pub(crate) fn finish<T>(&self, result: &Result<T, MapError>) {
+        self.bar.finish();
+        match result {
+            Ok(_) => self.send(LoadState::Finished),
+            Err(e) => self.send(LoadState::Failed(e.to_string())),
+        }
+    }
+
+    /// Marks the component as skipped, removing its bar
+    pub(crate) fn skip(&self) {
+        self.bar.finish_and_clear();
+        self.send(LoadState::Skipped);
+    }
+
+    /// Sends an event of the component to the progress channel.  A channel whose receiver was
+    /// dropped is no longer listened to, so the event is dropped with it.
+    fn send(&self, state: LoadState) {
+        if let Some(events) = &self.events {
+            let _result = events.send(LoadProgress::new(self.component.clone(), state));
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressDrawTarget;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn it_sends_the_progress_of_each_step() {
+        let progress = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let style = ProgressStyle::with_template("{wide_msg}").expect("Failed to create style");
+        let (sender, mut receiver) = unbounded_channel();
+        let cancel = CancelToken::new();
+        let steps = LoadSteps::new(progress, style, Some(sender), cancel.clone());
+
+        let states = steps.step("states");
+        states.start("Loading states...").expect("Failed to start");
+        states.finish(&Ok::<(), MapError>(()));
+        steps.step("trees.bmp").skip();
+        let railways = steps.step("railways");
+        railways.finish(&Err::<(), _>(MapError::InvalidRailway("1 2".to_owned())));
+        cancel.cancel();
+        assert!(matches!(
+            steps.step("airports").start("Loading airports..."),
+            Err(MapError::LoadCanceled)
+        ));
+
+        let received = std::iter::from_fn(|| receiver.try_recv().ok()).collect::<Vec<_>>();
+        assert_eq!(
+            received,
+            vec![
+                LoadProgress::new("states", LoadState::Started),
+                LoadProgress::new("states", LoadState::Finished),
+                LoadProgress::new("trees.bmp", LoadState::Skipped),
+                LoadProgress::new("railways", LoadState::Failed("1 2".to_owned())),
+                LoadProgress::new("airports", LoadState::Canceled),
+            ]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::encoding::TextEncoding;
+use crate::history::DEFAULT_MEMORY_CAP;
+use crate::legend::RegionColors;
+use crate::validation::profile::{RuleSeverity, ValidationProfile};
+use crate::validation::province_sizes::MINIMUM_PROVINCE_SIZE_IN_PIXELS;
+use crate::{CsvParsing, ErrorContext, MapError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// The name of the file in the root directory that holds the editor's settings for the map
+pub const PROJECT_FILE: &str = "world_gen.toml";
+
+/// A named position of the viewport over the map, so that an area can be returned to quickly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Bookmark {
+    /// The name shown for the bookmark
+    pub name: String,
+    /// The left edge of the viewport, from 0.0 to 1.0 across the map
+    pub min_x: f32,
+    /// The top edge of the viewport, from 0.0 to 1.0 down the map
+    pub min_y: f32,
+    /// The right edge of the viewport, which may run past 1.0 on a wrapping map
+    pub max_x: f32,
+    /// The bottom edge of the viewport
+    pub max_y: f32,
+    /// The zoom level of the viewport, if it has been zoomed
+    pub zoom: Option<f32>,
+}
+
+impl Bookmark {
+    /// Creates a new bookmark of a viewport given by its edges
+    #[inline]
+    #[must_use]
+    pub const fn new(name: String, min: (f32, f32), max: (f32, f32), zoom: Option<f32>) -> Self {
+        Self {
+            name,
+            min_x: min.0,
+            min_y: min.1,
+            max_x: max.0,
+            max_y: max.1,
+            zoom,
+        }
+    }
+}
+
+/// The settings of the checks run when validating the map.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct ValidationSettings {
+    /// The number of pixels below which a province is reported as too small
+    pub minimum_province_size: u32,
+    /// The severities the rules are reported with
+    pub profile: ValidationProfile,
+    /// The severities of rules that differ from the profile, by the name of the rule
+    pub rules: BTreeMap<String, RuleSeverity>,
+}
+
+impl Default for ValidationSettings {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            minimum_province_size: MINIMUM_PROVINCE_SIZE_IN_PIXELS,
+            profile: ValidationProfile::default(),
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
+/// The settings of the undo history.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct HistorySettings {
+    /// The memory the undo history may use, in bytes
+    pub memory_cap: usize,
+    /// Whether edits past the memory cap are written to the temporary directory rather than
+    /// forgotten
+    pub spill_to_disk: bool,
+}
+
+impl Default for HistorySettings {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            memory_cap: DEFAULT_MEMORY_CAP,
+            spill_to_disk: true,
+        }
+    }
+}
+
+/// The settings of how the files of the map are read.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct LoadSettings {
+    /// How the rows of the CSV files that cannot be read are handled, failing the load at the
+    /// first with `strict` or skipping them with `lenient`
+    pub csv_parsing: CsvParsing,
+    /// The encoding the text files are read in, such as `windows-1252`, rather than detecting the
+    /// encoding of each file
+    pub encoding: Option<TextEncoding>,
+}
+
+/// The editor's settings for a map, kept in the `world_gen.toml` file of its root directory.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Project {
+    /// The bookmarked viewport positions, in the order they were added
+    pub bookmarks: Vec<Bookmark>,
+    /// The settings of the checks run when validating the map
+    pub validation: ValidationSettings,
+    /// The settings of the undo history
+    pub history: HistorySettings,
+    /// How the regions of the state and strategic region maps are colored
+    pub region_colors: RegionColors,
+    /// How the files of the map are read
+    pub loading: LoadSettings,
+}
+
+impl Project {
+    /// Loads the project file of a root directory.  A root directory without a project file has
+    /// an empty project.
+    /// # Errors
+    /// * If the project file cannot be read
+    /// * If the project file is not valid
+    #[inline]
+    pub fn load(root_path: &Path) -> Result<Self, MapError> {
+        let path = root_path.join(PROJECT_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read_to_string(&path).in_file(&path)?;
+        toml::from_str(&data)
+            .map_err(MapError::InvalidProjectFile)
+            .in_file(&path)
+    }
+
+    /// Saves the project file to a root directory
+    /// # Errors
+    /// * If the project file cannot be written
+    #[inline]
+    pub fn save(&self, root_path: &Path) -> Result<(), MapError> {
+        let data = toml::to_string(self).map_err(MapError::ProjectFileNotWritten)?;
+        fs::write(root_path.join(PROJECT_FILE), data)?;
+        Ok(())
+    }
+
+    /// Adds a bookmark, replacing any bookmark with the same name
+    #[inline]
+    pub fn add_bookmark(&mut self, bookmark: Bookmark) {
+        match self.bookmarks.iter_mut().find(|b| b.name == bookmark.name) {
+            Some(b) => *b = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+    }
+
+    /// Removes the bookmark with the given name
+    #[inline]
+    pub fn remove_bookmark(&mut self, name: &str) {
+        self.bookmarks.retain(|b| b.name != name);
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_bookmarks_in_the_project_file() {
+        let dir = std::env::temp_dir().join("world_gen_project_test");
+        fs::create_dir_all(&dir).expect("Failed to create directory");
+        let _result = fs::remove_file(dir.join(PROJECT_FILE));
+        let mut project = Project::load(&dir).expect("Failed to load missing project");
+        assert!(project.bookmarks.is_empty());
+
+        project.add_bookmark(Bookmark::new(
+            "Alps".to_owned(),
+            (0.5, 0.25),
+            (0.75, 0.5),
+            Some(0.5),
+        ));
+        project.add_bookmark(Bookmark::new(
+            "Pacific".to_owned(),
+            (0.9, 0.0),
+            (1.1, 1.0),
+            None,
+        ));
+        project.add_bookmark(Bookmark::new(
+            "Alps".to_owned(),
+            (0.5, 0.25),
+            (0.625, 0.375),
+            Some(0.75),
+        ));
+        project.save(&dir).expect("Failed to save project");
+        let loaded = Project::load(&dir).expect("Failed to load project");
+        fs::remove_dir_all(&dir).expect("Failed to remove directory");
+
+        assert_eq!(loaded, project);
+        assert_eq!(loaded.bookmarks.len(), 2);
+        assert_eq!(loaded.bookmarks[0].zoom, Some(0.75));
+        assert_eq!(loaded.bookmarks[1].zoom, None);
+    }
+
+    #[test]
+    fn it_fills_in_missing_settings() {
+        let project: Project = toml::from_str("[validation]\nminimum_province_size = 4\n")
+            .expect("Failed to parse project");
+        assert!(project.bookmarks.is_empty());
+        assert_eq!(project.validation.minimum_province_size, 4);
+        let project: Project = toml::from_str("").expect("Failed to parse empty project");
+        assert_eq!(project.validation, ValidationSettings::default());
+        assert_eq!(project.loading.csv_parsing, CsvParsing::Strict);
+        assert_eq!(project.loading.encoding, None);
+        let project: Project =
+            toml::from_str("[loading]\ncsv_parsing = \"lenient\"\nencoding = \"windows-1252\"\n")
+                .expect("Failed to parse loading settings");
+        assert_eq!(project.loading.csv_parsing, CsvParsing::Lenient);
+        assert_eq!(project.loading.encoding, Some(TextEncoding::Windows1252));
+    }
+
+    #[test]
+    fn it_reads_the_region_colors() {
+        let project: Project =
+            toml::from_str("[region_colors]\nseed = 3\npalette = [[255, 0, 0], [0, 0, 255]]\n")
+                .expect("Failed to parse project");
+        assert_eq!(project.region_colors.seed, 3);
+        assert_eq!(
+            project.region_colors.palette,
+            vec![[255, 0, 0], [0, 0, 255]]
+        );
+    }
+
+    #[test]
+    fn it_reads_the_validation_profile_and_rules() {
+        let project: Project = toml::from_str(
+            "[validation]\nprofile = \"early-development\"\n\n[validation.rules]\n\
+             x_crossing = \"error\"\nsmall_province = \"ignore\"\n",
+        )
+        .expect("Failed to parse project");
+        assert_eq!(
+            project.validation.profile,
+            ValidationProfile::EarlyDevelopment
+        );
+        assert_eq!(
+            project.validation.rules.get("x_crossing"),
+            Some(&RuleSeverity::Error)
+        );
+        assert_eq!(
+            project.validation.rules.get("small_province"),
+            Some(&RuleSeverity::Ignore)
+        );
+    }
+}
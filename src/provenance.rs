@@ -0,0 +1,101 @@
+use crate::MapError;
+use std::env;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The file an entity of the map was loaded from, and the line of the file for files with an
+/// entity on every line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// The path of the file, relative to the root directory of the map
+    pub path: PathBuf,
+    /// The line of the file the entity is on, counting from 1
+    pub line: Option<usize>,
+}
+
+impl Provenance {
+    /// Creates a new provenance for a whole file
+    #[inline]
+    #[must_use]
+    pub const fn file(path: PathBuf) -> Self {
+        Self { path, line: None }
+    }
+
+    /// Creates a new provenance for a line of a file
+    #[inline]
+    #[must_use]
+    pub const fn line(path: PathBuf, line: usize) -> Self {
+        Self {
+            path,
+            line: Some(line),
+        }
+    }
+
+    /// Opens the file in the editor named by the `VISUAL` or `EDITOR` environment variables,
+    /// passing the line as `+<line>` as most editors accept.  Without either variable, the file
+    /// is opened with the program the system opens it with, which cannot be sent to the line.
+    /// # Errors
+    /// If the editor cannot be started.
+    #[inline]
+    pub fn open_in_editor(&self, root_path: &Path) -> Result<(), MapError> {
+        let path = root_path.join(&self.path);
+        let mut command = match env::var("VISUAL").or_else(|_| env::var("EDITOR")) {
+            Ok(editor) if !editor.trim().is_empty() => {
+                let mut parts = editor.split_whitespace();
+                let mut command = Command::new(parts.next().unwrap_or_default());
+                command.args(parts);
+                if let Some(line) = self.line {
+                    command.arg(format!("+{line}"));
+                }
+                command.arg(&path);
+                command
+            }
+            _ => system_opener(&path),
+        };
+        command.spawn()?;
+        Ok(())
+    }
+}
+
+impl Display for Provenance {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.path.display()),
+            None => write!(f, "{}", self.path.display()),
+        }
+    }
+}
+
+/// The command opening a file with the program the system opens it with
+fn system_opener(path: &Path) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]).arg(path);
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg(path);
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(path);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shows_the_line_after_the_path() {
+        let state = Provenance::file(PathBuf::from("history/states/1-France.txt"));
+        assert_eq!(state.to_string(), "history/states/1-France.txt");
+        let adjacency = Provenance::line(PathBuf::from("adjacencies.csv"), 12);
+        assert_eq!(adjacency.to_string(), "adjacencies.csv:12");
+    }
+}
@@ -0,0 +1,247 @@
+use crate::components::wrappers::ProvinceId;
+use crate::MapError;
+use image::{ImageBuffer, Luma, Pixel, Rgb, RgbImage};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// The index of pixels without a province, such as pixels of a color without a definition
+const NO_PROVINCE: u16 = 0;
+
+/// The province of every pixel of the provinces image.  The index is built once when the map
+/// loads, so that looking up the province of a pixel does not hash its color each time.  Each
+/// pixel holds an index into the provinces of the map, with 0 for pixels without a province.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ProvinceIndex {
+    /// The index into `provinces` of the province of each pixel, plus one
+    pixels: ImageBuffer<Luma<u16>, Vec<u16>>,
+    /// The provinces of the map, with the color they are drawn with
+    provinces: Vec<(ProvinceId, Rgb<u8>)>,
+    /// The index of each color of a province, plus one
+    indices: HashMap<Rgb<u8>, u16>,
+}
+
+impl ProvinceIndex {
+    /// Indexes the provinces of the provinces image.  The rows are indexed in parallel.
+    /// # Errors
+    /// If there are more provinces than the index can hold
+    #[inline]
+    pub fn new(
+        provinces: &RgbImage,
+        provinces_by_color: &HashMap<Rgb<u8>, ProvinceId>,
+    ) -> Result<Self, MapError> {
+        let mut by_id = provinces_by_color
+            .iter()
+            .map(|(color, id)| (*id, *color))
+            .collect::<Vec<_>>();
+        by_id.sort_unstable_by_key(|(id, color)| (*id, color.0));
+        let indices = (1..=u16::MAX)
+            .zip(&by_id)
+            .map(|(index, (_id, color))| (*color, index))
+            .collect::<HashMap<_, _>>();
+        if indices.len() < by_id.len() {
+            return Err(MapError::TooManyProvinces(by_id.len()));
+        }
+        let mut index = Self {
+            pixels: ImageBuffer::new(provinces.width(), provinces.height()),
+            provinces: by_id,
+            indices,
+        };
+        index.index_rows(provinces, |_, _| true);
+        Ok(index)
+    }
+
+    /// Indexes the pixels of the provinces image again after it was edited.  Only the pixels
+    /// whose color is no longer the color of their province are looked up again, so refreshing
+    /// is much faster than indexing the image from the start.  The colors of the provinces must
+    /// be the same as when the index was built, so an index must be built again after
+    /// recoloring.
+    #[inline]
+    pub fn refresh(&mut self, provinces: &RgbImage) {
+        if self.pixels.dimensions() != provinces.dimensions() {
+            self.pixels = ImageBuffer::new(provinces.width(), provinces.height());
+            self.index_rows(provinces, |_, _| true);
+            return;
+        }
+        let colors = &self.provinces;
+        let changed = |index: u16, color: &Rgb<u8>| {
+            usize::from(index)
+                .checked_sub(1)
+                .and_then(|i| colors.get(i))
+                .map_or(true, |(_id, c)| c != color)
+        };
+        index_rows(&mut self.pixels, provinces, &self.indices, changed);
+    }
+
+    /// The province of a pixel, or `None` if the pixel is off the image or its color has no
+    /// province
+    #[inline]
+    #[must_use]
+    pub fn province_at(&self, x: u32, y: u32) -> Option<ProvinceId> {
+        let Luma([index]) = *self.pixels.get_pixel_checked(x, y)?;
+        self.province(index)
+    }
+
+    /// The width and height of the indexed image
+    #[inline]
+    #[must_use]
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.pixels.dimensions()
+    }
+
+    /// The provinces of the pixels of each row, from the top row down.  The rows can be read in
+    /// parallel.
+    #[inline]
+    pub fn par_rows(
+        &self,
+    ) -> impl IndexedParallelIterator<Item = impl Iterator<Item = Option<ProvinceId>> + '_> + '_
+    {
+        let width = usize::try_from(self.pixels.width())
+            .unwrap_or_default()
+            .max(1);
+        self.pixels
+            .par_chunks(width)
+            .map(move |row| row.iter().map(move |index| self.province(*index)))
+    }
+
+    /// Draws an image the size of the provinces image with each pixel in the color `color`
+    /// gives its province, and in black if it has no province or `color` gives `None`
+    #[inline]
+    #[must_use]
+    pub fn paint<F>(&self, color: F) -> RgbImage
+    where
+        F: Fn(ProvinceId) -> Option<Rgb<u8>> + Sync,
+    {
+        let (width, height) = self.dimensions();
+        let mut image = RgbImage::new(width, height);
+        let row_length = usize::try_from(width)
+            .unwrap_or_default()
+            .saturating_mul(3)
+            .max(1);
+        // The rows are colored in parallel, as the largest maps have tens of millions of pixels
+        image
+            .par_chunks_mut(row_length)
+            .zip(self.par_rows())
+            .for_each(|(row, provinces)| {
+                for (pixel, province) in row.chunks_exact_mut(3).zip(provinces) {
+                    let painted = province.and_then(&color).unwrap_or(Rgb([0, 0, 0]));
+                    pixel.copy_from_slice(&painted.0);
+                }
+            });
+        image
+    }
+
+    /// The province of each pixel in order, reading the rows from the top
+    #[inline]
+    pub fn provinces(&self) -> impl Iterator<Item = Option<ProvinceId>> + '_ {
+        self.pixels.iter().map(|index| self.province(*index))
+    }
+
+    /// The province an index of a pixel stands for
+    fn province(&self, index: u16) -> Option<ProvinceId> {
+        if index == NO_PROVINCE {
+            return None;
+        }
+        let i = usize::from(index).checked_sub(1)?;
+        self.provinces.get(i).map(|(id, _color)| *id)
+    }
+
+    /// Indexes the pixels of the provinces image that `needs_index` selects
+    fn index_rows<F>(&mut self, provinces: &RgbImage, needs_index: F)
+    where
+        F: Fn(u16, &Rgb<u8>) -> bool + Sync,
+    {
+        index_rows(&mut self.pixels, provinces, &self.indices, needs_index);
+    }
+}
+
+/// Looks up the index of each pixel of the provinces image that `needs_index` selects from its
+/// current index and color, one row at a time in parallel
+fn index_rows<F>(
+    pixels: &mut ImageBuffer<Luma<u16>, Vec<u16>>,
+    provinces: &RgbImage,
+    indices: &HashMap<Rgb<u8>, u16>,
+    needs_index: F,
+) where
+    F: Fn(u16, &Rgb<u8>) -> bool + Sync,
+{
+    let width = usize::try_from(pixels.width()).unwrap_or_default().max(1);
+    let row_length = width.saturating_mul(3);
+    pixels
+        .par_chunks_mut(width)
+        .zip(provinces.par_chunks(row_length))
+        .for_each(|(index_row, province_row)| {
+            for (index, color) in index_row
+                .iter_mut()
+                .zip(province_row.chunks_exact(3).map(Rgb::<u8>::from_slice))
+            {
+                if needs_index(*index, color) {
+                    *index = indices.get(color).copied().unwrap_or(NO_PROVINCE);
+                }
+            }
+        });
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_indexes_and_refreshes_the_provinces_of_the_pixels() {
+        // Province 1 on the left, province 2 on the right, and a color without a definition
+        let mut provinces = RgbImage::from_fn(8, 4, |x, y| match (x, y) {
+            (0, 0) => Rgb([9, 9, 9]),
+            (0..=3, _) => Rgb([0, 0, 1]),
+            _ => Rgb([0, 0, 2]),
+        });
+        let provinces_by_color = HashMap::from([
+            (Rgb([0, 0, 1]), ProvinceId(1)),
+            (Rgb([0, 0, 2]), ProvinceId(2)),
+        ]);
+        let mut index =
+            ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        assert_eq!(index.dimensions(), (8, 4));
+        assert_eq!(index.province_at(0, 0), None);
+        assert_eq!(index.province_at(1, 0), Some(ProvinceId(1)));
+        assert_eq!(index.province_at(7, 3), Some(ProvinceId(2)));
+        assert_eq!(index.province_at(8, 0), None);
+
+        provinces.put_pixel(0, 0, Rgb([0, 0, 2]));
+        provinces.put_pixel(5, 1, Rgb([0, 0, 1]));
+        index.refresh(&provinces);
+        assert_eq!(index.province_at(0, 0), Some(ProvinceId(2)));
+        assert_eq!(index.province_at(5, 1), Some(ProvinceId(1)));
+        let rows = index
+            .par_rows()
+            .map(|row| row.flatten().count())
+            .collect::<Vec<_>>();
+        assert_eq!(rows, vec![8; 4]);
+        assert_eq!(
+            index
+                .provinces()
+                .filter(|p| *p == Some(ProvinceId(1)))
+                .count(),
+            16
+        );
+    }
+
+    #[test]
+    fn it_paints_the_provinces_and_leaves_other_pixels_black() {
+        let provinces = RgbImage::from_fn(4, 2, |x, _y| match x {
+            0 => Rgb([9, 9, 9]),
+            1 => Rgb([0, 0, 1]),
+            _ => Rgb([0, 0, 2]),
+        });
+        let provinces_by_color = HashMap::from([
+            (Rgb([0, 0, 1]), ProvinceId(1)),
+            (Rgb([0, 0, 2]), ProvinceId(2)),
+        ]);
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let painted = index.paint(|id| (id == ProvinceId(1)).then_some(Rgb([200, 0, 0])));
+        assert_eq!(painted.dimensions(), (4, 2));
+        assert_eq!(*painted.get_pixel(0, 1), Rgb([0, 0, 0]));
+        assert_eq!(*painted.get_pixel(1, 1), Rgb([200, 0, 0]));
+        assert_eq!(*painted.get_pixel(3, 0), Rgb([0, 0, 0]));
+    }
+}
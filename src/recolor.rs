@@ -0,0 +1,198 @@
+use crate::components::prelude::*;
+use crate::MapError;
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, HashSet};
+
+/// A structured way of coloring the provinces, so that a province can be told apart by its color
+/// alone while debugging.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorScheme {
+    /// Red is the continent of the province, with sea and lake provinces in continent 0.  Green
+    /// and blue count the provinces of each continent from 1, in order of id, with green as the
+    /// high byte.
+    #[default]
+    Continent,
+    /// The color is the id of the province as a 24 bit number, with red as the high byte.
+    ProvinceId,
+}
+
+/// Recolors every province except the province 0 to the given scheme.
+///
+/// The provinces image and the definitions are updated together, keeping the ids of the
+/// provinces.  Pixels whose color is not defined are left as they are.  Returns the new color of
+/// each recolored province.
+/// # Errors
+/// * If the scheme does not have enough colors for the provinces
+#[inline]
+pub fn recolor_provinces(
+    provinces: &mut RgbImage,
+    definitions: &mut Definitions,
+    scheme: ColorScheme,
+) -> Result<HashMap<ProvinceId, Rgb<u8>>, MapError> {
+    let defined = definitions
+        .definitions
+        .values()
+        .map(|d| Rgb([d.r.0, d.g.0, d.b.0]))
+        .collect::<HashSet<_>>();
+    // Colors that are kept, which no province may be recolored to
+    let mut reserved = provinces
+        .pixels()
+        .filter(|p| !defined.contains(*p))
+        .copied()
+        .collect::<HashSet<_>>();
+    if let Some(d) = definitions.definitions.get(&ProvinceId(0)) {
+        reserved.insert(Rgb([d.r.0, d.g.0, d.b.0]));
+    }
+
+    let mut ids = definitions
+        .definitions
+        .keys()
+        .copied()
+        .filter(|id| id.0 != 0_i32)
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    let mut counts: HashMap<u8, u32> = HashMap::new();
+    let mut colors = HashMap::new();
+    for id in ids {
+        let color = match scheme {
+            ColorScheme::Continent => {
+                let definition = definitions
+                    .definitions
+                    .get(&id)
+                    .ok_or(MapError::DefinitionNotFound(id))?;
+                let continent = u8::try_from(definition.continent.0)?;
+                let count = counts.entry(continent).or_default();
+                next_continent_color(continent, count, &reserved)?
+            }
+            ColorScheme::ProvinceId => id_color(id)?,
+        };
+        if reserved.contains(&color) {
+            return Err(MapError::ColorSchemeExhausted(format!(
+                "Province {id} cannot be recolored to a color already on the map"
+            )));
+        }
+        colors.insert(id, color);
+    }
+
+    let mut old_to_new = HashMap::new();
+    for definition in definitions.definitions.values_mut() {
+        if let Some(color) = colors.get(&definition.id) {
+            old_to_new.insert(
+                Rgb([definition.r.0, definition.g.0, definition.b.0]),
+                *color,
+            );
+            let Rgb([r, g, b]) = *color;
+            definition.r = Red(r);
+            definition.g = Green(g);
+            definition.b = Blue(b);
+        }
+    }
+    for pixel in provinces.pixels_mut() {
+        if let Some(color) = old_to_new.get(pixel) {
+            *pixel = *color;
+        }
+    }
+    Ok(colors)
+}
+
+/// The next color of the continent scheme for the given continent, skipping reserved colors
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::big_endian_bytes)]
+fn next_continent_color(
+    continent: u8,
+    count: &mut u32,
+    reserved: &HashSet<Rgb<u8>>,
+) -> Result<Rgb<u8>, MapError> {
+    loop {
+        *count += 1;
+        let [_, _, g, b] = count.to_be_bytes();
+        if *count > u32::from(u16::MAX) {
+            return Err(MapError::ColorSchemeExhausted(format!(
+                "Continent {continent} has more provinces than there are colors"
+            )));
+        }
+        let color = Rgb([continent, g, b]);
+        if !reserved.contains(&color) {
+            return Ok(color);
+        }
+    }
+}
+
+/// The color of the province id scheme for the given province
+#[allow(clippy::big_endian_bytes)]
+fn id_color(id: ProvinceId) -> Result<Rgb<u8>, MapError> {
+    match u32::try_from(id.0)?.to_be_bytes() {
+        [0, r, g, b] => Ok(Rgb([r, g, b])),
+        _ => Err(MapError::ColorSchemeExhausted(format!(
+            "Province {id} is too large to be a color"
+        ))),
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+
+    /// Three provinces side by side, two on continent 2 and a sea province
+    fn map() -> (RgbImage, Definitions) {
+        let provinces = RgbImage::from_fn(6, 2, |x, _y| Rgb([10 * (x / 2 + 1) as u8, 0, 0]));
+        let definition = |id: i32, province_type: ProvinceType, continent: usize| {
+            DefinitionBuilder::new(id)
+                .color(Rgb([10 * id as u8, 0, 0]))
+                .province_type(province_type)
+                .continent(continent)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, ProvinceType::Land, 2)),
+                (ProvinceId(2), definition(2, ProvinceType::Sea, 0)),
+                (ProvinceId(3), definition(3, ProvinceType::Land, 2)),
+            ]),
+            terrain: HashSet::new(),
+        };
+        (provinces, definitions)
+    }
+
+    #[test]
+    fn it_recolors_provinces_by_continent() {
+        let (mut provinces, mut definitions) = map();
+        let colors = recolor_provinces(&mut provinces, &mut definitions, ColorScheme::Continent)
+            .expect("Failed to recolor provinces");
+        assert_eq!(colors[&ProvinceId(1)], Rgb([2, 0, 1]));
+        assert_eq!(colors[&ProvinceId(2)], Rgb([0, 0, 1]));
+        assert_eq!(colors[&ProvinceId(3)], Rgb([2, 0, 2]));
+        assert_eq!(*provinces.get_pixel(0, 0), Rgb([2, 0, 1]));
+        assert_eq!(*provinces.get_pixel(5, 1), Rgb([2, 0, 2]));
+        let third = &definitions.definitions[&ProvinceId(3)];
+        assert_eq!((third.r, third.g, third.b), (Red(2), Green(0), Blue(2)));
+    }
+
+    #[test]
+    fn it_recolors_provinces_by_id() {
+        let (mut provinces, mut definitions) = map();
+        recolor_provinces(&mut provinces, &mut definitions, ColorScheme::ProvinceId)
+            .expect("Failed to recolor provinces");
+        assert_eq!(*provinces.get_pixel(2, 0), Rgb([0, 0, 2]));
+        assert_eq!(definitions.definitions[&ProvinceId(2)].b, Blue(2));
+    }
+
+    #[test]
+    fn it_keeps_the_map_when_a_color_is_taken() {
+        let (mut provinces, mut definitions) = map();
+        provinces.put_pixel(0, 0, Rgb([0, 0, 3]));
+        let original = provinces.clone();
+        let result = recolor_provinces(&mut provinces, &mut definitions, ColorScheme::ProvinceId);
+        assert!(matches!(result, Err(MapError::ColorSchemeExhausted(_))));
+        assert_eq!(provinces, original);
+    }
+}
@@ -0,0 +1,251 @@
+use crate::components::localisation::{victory_points_key, Localisation};
+use crate::components::prelude::*;
+use crate::components::state::State;
+use derive_more::Display;
+use std::collections::HashMap;
+
+/// The most results a search returns
+pub const MAX_SEARCH_RESULTS: usize = 20;
+
+/// A province, state or strategic region found by a search
+#[allow(clippy::exhaustive_enums)]
+#[derive(Display, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SearchTarget {
+    /// A province
+    #[display(fmt = "province {_0}")]
+    Province(ProvinceId),
+    /// A state
+    #[display(fmt = "state {_0}")]
+    State(StateId),
+    /// A strategic region
+    #[display(fmt = "strategic region {_0}")]
+    StrategicRegion(StrategicRegionId),
+}
+
+/// A match of a search, with the text it is listed with
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SearchResult {
+    /// What was found
+    pub target: SearchTarget,
+    /// The text the result is listed with, such as `state 64: Brandenburg`
+    pub label: String,
+}
+
+/// Finds the provinces, states and strategic regions a query names.  A number finds the
+/// province, state and strategic region with that id, or only one of them when the number
+/// follows `province`, `state` or `region`, as in the error log of the game.  Any other query
+/// finds the states and strategic regions whose name, or localised name, contains it, and the
+/// provinces whose victory points are named with it, ignoring case.  Exact matches are listed
+/// before the others, and at most `MAX_SEARCH_RESULTS` are returned.
+#[inline]
+#[must_use]
+pub fn search(
+    query: &str,
+    definitions: &Definitions,
+    states: &HashMap<StateId, State>,
+    strategic_regions: &StrategicRegions,
+    localisation: &Localisation,
+) -> Vec<SearchResult> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let state_label = |state: &State| match localisation.text(&state.name.0) {
+        Some(name) => format!("state {}: {name}", state.id),
+        None => format!("state {}: {}", state.id, state.name),
+    };
+    let region_label = |region: &StrategicRegion| match localisation.text(&region.name.0) {
+        Some(name) => format!("strategic region {}: {name}", region.id),
+        None => format!("strategic region {}: {}", region.id, region.name),
+    };
+    let province_label = |id: ProvinceId| match localisation.text(&victory_points_key(id)) {
+        Some(name) => format!("province {id}: {name}"),
+        None => format!("province {id}"),
+    };
+
+    let (kind, id) = match query.split_once(' ') {
+        Some((kind, id)) => (kind, id.trim()),
+        None => ("", query.as_str()),
+    };
+    let numbered = matches!(
+        kind,
+        "" | "province" | "state" | "region" | "strategic_region"
+    );
+    if let (true, Ok(id)) = (numbered, id.parse::<i32>()) {
+        let mut results = Vec::new();
+        let province = ProvinceId(id);
+        if matches!(kind, "" | "province") && definitions.definitions.contains_key(&province) {
+            results.push(SearchResult {
+                target: SearchTarget::Province(province),
+                label: province_label(province),
+            });
+        }
+        let state = states
+            .get(&StateId(id))
+            .filter(|_| matches!(kind, "" | "state"));
+        if let Some(state) = state {
+            results.push(SearchResult {
+                target: SearchTarget::State(state.id),
+                label: state_label(state),
+            });
+        }
+        let region = strategic_regions
+            .strategic_regions
+            .get(&StrategicRegionId(id))
+            .filter(|_| matches!(kind, "" | "region" | "strategic_region"));
+        if let Some(region) = region {
+            results.push(SearchResult {
+                target: SearchTarget::StrategicRegion(region.id),
+                label: region_label(region),
+            });
+        }
+        return results;
+    }
+
+    // Each match is ranked by whether a name is the query, and then by its label
+    let mut matches = Vec::new();
+    let rank = |names: &[Option<&str>]| {
+        let names = names.iter().flatten().map(|n| n.to_lowercase());
+        names.fold(None, |best: Option<bool>, name| {
+            let exact = name == query;
+            match (best, exact || name.contains(&query)) {
+                (_, false) => best,
+                (None, true) => Some(exact),
+                (Some(best), true) => Some(best || exact),
+            }
+        })
+    };
+    for state in states.values() {
+        let names = [
+            Some(state.name.0.as_str()),
+            localisation.text(&state.name.0),
+        ];
+        if let Some(exact) = rank(&names) {
+            matches.push((!exact, SearchTarget::State(state.id), state_label(state)));
+        }
+    }
+    for region in strategic_regions.strategic_regions.values() {
+        let names = [
+            Some(region.name.0.as_str()),
+            localisation.text(&region.name.0),
+        ];
+        if let Some(exact) = rank(&names) {
+            let target = SearchTarget::StrategicRegion(region.id);
+            matches.push((!exact, target, region_label(region)));
+        }
+    }
+    let victory_points = states
+        .values()
+        .filter_map(|state| state.history.as_ref())
+        .flat_map(|history| &history.victory_points);
+    for (province, _points) in victory_points {
+        let names = [localisation.text(&victory_points_key(*province))];
+        if let Some(exact) = rank(&names) {
+            let target = SearchTarget::Province(*province);
+            matches.push((!exact, target, province_label(*province)));
+        }
+    }
+    matches.sort_by(|(a_inexact, _a, a_label), (b_inexact, _b, b_label)| {
+        a_inexact.cmp(b_inexact).then_with(|| a_label.cmp(b_label))
+    });
+    matches.dedup_by_key(|(_inexact, target, _label)| *target);
+    matches
+        .into_iter()
+        .take(MAX_SEARCH_RESULTS)
+        .map(|(_inexact, target, label)| SearchResult { target, label })
+        .collect()
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::state::StateHistory;
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashSet;
+
+    fn state(id: i32, name: &str, victory_point: i32) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(name.to_owned()),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: Some(StateHistory {
+                owner: CountryTag("GER".to_owned()),
+                controller: None,
+                victory_points: vec![(ProvinceId(victory_point), VictoryPoints(10.0))],
+            }),
+            provinces: HashSet::from([ProvinceId(victory_point)]),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    fn labels(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|r| r.label.as_str()).collect()
+    }
+
+    #[test]
+    fn it_finds_regions_by_id_and_name() {
+        let definition = DefinitionBuilder::new(1).build();
+        let definitions = Definitions {
+            definitions: HashMap::from([(ProvinceId(1), definition)]),
+            terrain: HashSet::new(),
+        };
+        let region = StrategicRegion {
+            id: StrategicRegionId(1),
+            name: StrategicRegionName("REGION_1".to_owned()),
+            provinces: HashSet::from([ProvinceId(1)]),
+            weather: Weather { period: Vec::new() },
+        };
+        let regions = StrategicRegions {
+            strategic_regions: HashMap::from([(StrategicRegionId(1), region)]),
+            sources: HashMap::new(),
+        };
+        let states = HashMap::from([
+            (StateId(64), state(64, "STATE_64", 6521)),
+            (StateId(65), state(65, "STATE_65", 9)),
+        ]);
+        let mut localisation = Localisation::default();
+        localisation
+            .entries
+            .insert("STATE_64".to_owned(), "Brandenburg".to_owned());
+        localisation
+            .entries
+            .insert("STATE_65".to_owned(), "Berlin".to_owned());
+        localisation
+            .entries
+            .insert("VICTORY_POINTS_6521".to_owned(), "Berlin".to_owned());
+        let find = |query: &str| search(query, &definitions, &states, &regions, &localisation);
+
+        assert_eq!(labels(&find("64")), ["state 64: Brandenburg"]);
+        assert_eq!(labels(&find("State 65")), ["state 65: Berlin"]);
+        assert!(find("province 64").is_empty());
+        assert_eq!(
+            labels(&find("1")),
+            ["province 1", "strategic region 1: REGION_1"]
+        );
+        assert_eq!(labels(&find("region 1")), ["strategic region 1: REGION_1"]);
+        assert_eq!(
+            labels(&find("berlin")),
+            ["province 6521: Berlin", "state 65: Berlin"]
+        );
+        assert_eq!(
+            labels(&find("b")),
+            [
+                "province 6521: Berlin",
+                "state 64: Brandenburg",
+                "state 65: Berlin"
+            ]
+        );
+        assert_eq!(find("state_64")[0].target, SearchTarget::State(StateId(64)));
+        assert_eq!(
+            find("Region_1")[0].target,
+            SearchTarget::StrategicRegion(StrategicRegionId(1))
+        );
+        assert!(find("  ").is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::components::state_category::StateCategories;
+use crate::legend::{name_color, Legend, LegendEntry};
+use crate::province_index::ProvinceIndex;
+use image::{Rgb, RgbImage};
+use std::collections::{BTreeMap, HashMap};
+
+/// The color of the provinces of states without a category, and of provinces without a state
+const UNCATEGORIZED: Rgb<u8> = Rgb([48, 48, 48]);
+
+/// Draws each state in the color of its category, along with a legend of the categories and how
+/// many states have each.  The legend runs from the category with the fewest building slots to the
+/// one with the most.  Categories without a color in `common/state_category` are given one mixed
+/// from their name, and categories it does not define are marked as such in the legend.  States
+/// without a category are dark grey, and pixels without a province are black.
+#[inline]
+#[must_use]
+pub fn render_state_categories(
+    states: &HashMap<StateId, State>,
+    categories: &StateCategories,
+    province_index: &ProvinceIndex,
+) -> (RgbImage, Legend) {
+    let mut counts: BTreeMap<&StateCategoryName, usize> = BTreeMap::new();
+    let mut colors = HashMap::new();
+    for state in states.values() {
+        if let Some(category) = state.current_category() {
+            let color = category_color(category, categories);
+            colors.extend(state.provinces.iter().map(|p| (*p, color)));
+            let count = counts.entry(category).or_default();
+            *count = count.saturating_add(1);
+        }
+    }
+    let map = province_index.paint(|id| Some(colors.get(&id).copied().unwrap_or(UNCATEGORIZED)));
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    // Sorting is stable, so categories with the same building slots stay in order of name
+    counts.sort_by_key(|(name, _count)| {
+        categories.get(name).map_or((true, 0), |c| {
+            (c.building_slots.is_none(), c.building_slots.unwrap_or(0))
+        })
+    });
+    let legend = counts
+        .into_iter()
+        .zip(1..)
+        .map(|((name, count), id)| {
+            let states = match count {
+                1 => "1 state".to_owned(),
+                _ => format!("{count} states"),
+            };
+            let text = match categories.get(name) {
+                Some(category) => match category.building_slots {
+                    Some(slots) => format!("{name} ({slots} slots): {states}"),
+                    None => format!("{name}: {states}"),
+                },
+                None => format!("{name} (undefined): {states}"),
+            };
+            LegendEntry::new(id, text, category_color(name, categories))
+        })
+        .collect();
+    (map, Legend::new(legend))
+}
+
+/// The color of a state category, from `common/state_category` or else mixed from its name
+#[inline]
+#[must_use]
+pub fn category_color(name: &StateCategoryName, categories: &StateCategories) -> Rgb<u8> {
+    categories
+        .get(name)
+        .and_then(|c| c.color)
+        .unwrap_or_else(|| name_color(&name.0))
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::state_category::StateCategory;
+    use std::collections::HashSet;
+
+    fn state(id: i32, province: i32, category: Option<&str>) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: Vec::new(),
+            state_category: category
+                .map(|c| StateCategoryName(c.to_owned()))
+                .into_iter()
+                .collect(),
+            history: None,
+            provinces: HashSet::from([ProvinceId(province)]),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    fn category(name: &str, building_slots: u32, color: Option<Rgb<u8>>) -> StateCategory {
+        StateCategory {
+            name: StateCategoryName(name.to_owned()),
+            building_slots: Some(building_slots),
+            color,
+        }
+    }
+
+    #[test]
+    fn it_colors_the_states_by_their_category() {
+        // Provinces 1 to 4 are the columns of the map
+        let provinces = RgbImage::from_fn(4, 1, |x, _| Rgb([(x + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=4)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let states = HashMap::from([
+            (StateId(1), state(1, 1, Some("city"))),
+            (StateId(2), state(2, 2, Some("rural"))),
+            (StateId(3), state(3, 3, Some("village"))),
+            (StateId(4), state(4, 4, None)),
+        ]);
+        let categories = StateCategories {
+            categories: [
+                category("city", 8, Some(Rgb([200, 40, 40]))),
+                category("rural", 4, None),
+            ]
+            .into_iter()
+            .map(|c| (c.name.clone(), c))
+            .collect(),
+        };
+
+        let (map, legend) = render_state_categories(&states, &categories, &index);
+        assert_eq!(*map.get_pixel(0, 0), Rgb([200, 40, 40]));
+        assert_eq!(*map.get_pixel(1, 0), name_color("rural"));
+        assert_eq!(*map.get_pixel(2, 0), name_color("village"));
+        assert_eq!(*map.get_pixel(3, 0), UNCATEGORIZED);
+        let names = legend
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "rural (4 slots): 1 state",
+                "city (8 slots): 1 state",
+                "village (undefined): 1 state"
+            ]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::components::prelude::*;
+use crate::map::Map;
+use crate::validation::report::Severity;
+use crate::{LoadObject, MapError};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The number of validation findings listed in the diagnostics report
+const MAX_REPORTED_ISSUES: usize = 50;
+
+/// The DOS date of the entries in the bundle, 1980-01-01.  The bundle does not record when it was
+/// created so that it only contains what the user chose to share.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// A zip archive of diagnostic information that users can attach to issues.  Only text generated
+/// by the editor is added, never the game's own files.
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct SupportBundle {
+    /// The files in the bundle, by name
+    pub entries: Vec<(String, Vec<u8>)>,
+}
+
+impl SupportBundle {
+    /// Creates an empty support bundle
+    #[inline]
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a file to the bundle
+    #[inline]
+    pub fn add<S: Into<String>, C: Into<Vec<u8>>>(&mut self, name: S, contents: C) {
+        self.entries.push((name.into(), contents.into()));
+    }
+
+    /// Writes the bundle to a file as a zip archive
+    /// # Errors
+    /// * If the file could not be written
+    /// * If the bundle is too large for a zip archive
+    #[inline]
+    pub fn save(&self, path: &Path) -> Result<(), MapError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_zip(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes the bundle as an uncompressed zip archive
+    /// # Errors
+    /// * If the writer fails
+    /// * If the bundle is too large for a zip archive
+    #[inline]
+    pub fn write_zip<W: Write>(&self, writer: &mut W) -> Result<(), MapError> {
+        let mut offset = 0_u32;
+        let mut central_directory = Vec::new();
+        for (name, contents) in &self.entries {
+            let name_length = u16::try_from(name.len())?;
+            let size = u32::try_from(contents.len())?;
+            let crc = crc32(contents);
+
+            let mut local_header = Vec::with_capacity(30);
+            local_header.extend(0x0403_4b50_u32.to_le_bytes());
+            write_entry_fields(&mut local_header, crc, size, name_length);
+            local_header.extend(0_u16.to_le_bytes());
+            writer.write_all(&local_header)?;
+            writer.write_all(name.as_bytes())?;
+            writer.write_all(contents)?;
+
+            central_directory.extend(0x0201_4b50_u32.to_le_bytes());
+            central_directory.extend(20_u16.to_le_bytes());
+            write_entry_fields(&mut central_directory, crc, size, name_length);
+            // Extra field, comment, disk number, internal and external attributes
+            central_directory.extend([0_u8; 12]);
+            central_directory.extend(offset.to_le_bytes());
+            central_directory.extend(name.as_bytes());
+
+            let entry_length = u32::try_from(local_header.len())?
+                .checked_add(u32::from(name_length))
+                .and_then(|l| l.checked_add(size))
+                .ok_or_else(|| {
+                    MapError::InvalidSupportBundle("The bundle is too large".to_owned())
+                })?;
+            offset = offset.checked_add(entry_length).ok_or_else(|| {
+                MapError::InvalidSupportBundle("The bundle is too large".to_owned())
+            })?;
+        }
+        let entry_count = u16::try_from(self.entries.len())?;
+        writer.write_all(&central_directory)?;
+        let mut end = Vec::with_capacity(22);
+        end.extend(0x0605_4b50_u32.to_le_bytes());
+        end.extend([0_u8; 4]);
+        end.extend(entry_count.to_le_bytes());
+        end.extend(entry_count.to_le_bytes());
+        end.extend(u32::try_from(central_directory.len())?.to_le_bytes());
+        end.extend(offset.to_le_bytes());
+        end.extend(0_u16.to_le_bytes());
+        writer.write_all(&end)?;
+        Ok(())
+    }
+}
+
+/// Writes the fields shared by the local and central headers of a stored zip entry
+fn write_entry_fields(buffer: &mut Vec<u8>, crc: u32, size: u32, name_length: u16) {
+    // Version needed, flags, and the compression method of stored
+    buffer.extend(20_u16.to_le_bytes());
+    buffer.extend(0_u16.to_le_bytes());
+    buffer.extend(0_u16.to_le_bytes());
+    buffer.extend(0_u16.to_le_bytes());
+    buffer.extend(DOS_DATE.to_le_bytes());
+    buffer.extend(crc.to_le_bytes());
+    buffer.extend(size.to_le_bytes());
+    buffer.extend(size.to_le_bytes());
+    buffer.extend(name_length.to_le_bytes());
+}
+
+/// Computes the CRC-32 checksum used by zip archives
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Lists the files the map is made of with their sizes, and the dimensions of the images, without
+/// any of their contents.
+/// # Errors
+/// * If the `map/default.map` file could not be read
+#[inline]
+pub fn map_manifest(root_path: &Path) -> Result<String, MapError> {
+    let map_path = root_path.join("map");
+    let default_map = DefaultMap::load_object(&map_path.join("default.map"), None)?;
+    let files = [
+        &default_map.definitions,
+        &default_map.provinces,
+        &default_map.positions,
+        &default_map.terrain,
+        &default_map.rivers,
+        &default_map.heightmap,
+        &default_map.tree_definition,
+        &default_map.continent,
+        &default_map.adjacency_rules,
+        &default_map.adjacencies,
+        &default_map.ambient_object,
+        &default_map.seasons,
+    ];
+    let mut manifest = String::from("file;bytes;dimensions\n");
+    for file in files {
+        let path = map_path.join(file);
+        let bytes =
+            fs::metadata(&path).map_or_else(|_| "missing".to_owned(), |m| m.len().to_string());
+        let dimensions = image::image_dimensions(&path)
+            .map_or_else(|_| String::new(), |(w, h)| format!("{w}x{h}"));
+        let _result = writeln!(manifest, "{};{bytes};{dimensions}", file.display());
+    }
+    Ok(manifest)
+}
+
+/// Summarizes the results of verifying the map.
+#[inline]
+#[must_use]
+pub fn diagnostics_report(map: &Map) -> String {
+    let mut report = format!("world_gen {}\n\n", env!("CARGO_PKG_VERSION"));
+    let _result = writeln!(
+        report,
+        "Provinces image: {}x{}",
+        map.provinces.width(),
+        map.provinces.height()
+    );
+    let _result = writeln!(report, "Provinces: {}", map.definitions.definitions.len());
+    let _result = writeln!(report, "States: {}", map.states.len());
+    let _result = writeln!(
+        report,
+        "Strategic regions: {}",
+        map.strategic_regions.strategic_regions.len()
+    );
+
+    let validation = map.validate();
+    let _result = writeln!(
+        report,
+        "\nValidation: {} errors, {} warnings",
+        validation.count(Severity::Error),
+        validation.count(Severity::Warning)
+    );
+    for finding in validation.findings.iter().take(MAX_REPORTED_ISSUES) {
+        let _result = writeln!(report, "  {finding}");
+    }
+    let _result = writeln!(
+        report,
+        "River issues found while editing: {}",
+        map.river_issues.len()
+    );
+    report
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_computes_the_zip_checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn it_writes_a_zip_archive() {
+        let mut bundle = SupportBundle::new();
+        bundle.add("log.txt", "hello");
+        bundle.add("settings.txt", "");
+        let mut zip = Vec::new();
+        bundle.write_zip(&mut zip).expect("Failed to write zip");
+        // Two local headers, two central headers and the end of the central directory
+        assert_eq!(
+            zip.len(),
+            (30 + 7 + 5) + (30 + 12) + (46 + 7) + (46 + 12) + 22
+        );
+        assert_eq!(zip[0..4], [0x50, 0x4b, 0x03, 0x04]);
+        assert_eq!(zip[30..37], *b"log.txt");
+        assert_eq!(zip[37..42], *b"hello");
+        let end = zip.len() - 22;
+        assert_eq!(zip[end..end + 4], [0x50, 0x4b, 0x05, 0x06]);
+        assert_eq!(zip[end + 10..end + 12], [2, 0]);
+    }
+
+    #[test]
+    fn it_lists_the_map_files() {
+        let manifest = map_manifest(Path::new("./test")).expect("Failed to create manifest");
+        assert!(manifest.contains("definition.csv"));
+        assert!(manifest.contains("provinces.bmp"));
+    }
+}
@@ -0,0 +1,318 @@
+use crate::MapDisplayMode;
+use egui::{Color32, ColorImage};
+use image::math::Rect;
+use image::{ImageBuffer, Pixel};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Converts a map image into an image that can be loaded as a texture.
+///
+/// Graphics cards limit the size of a texture, commonly to 8192 or 16384 pixels on a side, which
+/// modded maps can go beyond.  Images with a side longer than `max_side` are shrunk by the
+/// smallest whole factor that fits them, taking the top left pixel of each block so that the
+/// colors of the map are kept as they are.  As the map is drawn from coordinates relative to its
+/// size, a shrunk texture lines up with the full size map.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+pub fn texture_image<P: Pixel<Subpixel = u8>>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    max_side: usize,
+) -> ColorImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let factor = texture_factor(width, height, max_side);
+    let size = [
+        (width + factor - 1) / factor,
+        (height + factor - 1) / factor,
+    ];
+    texels(image, factor, 0..size[0], 0..size[1])
+}
+
+/// A part of a texture to replace after an area of its image was edited
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TexturePatch {
+    /// The texel of the texture the top left corner of the patch goes on
+    pub position: [usize; 2],
+    /// The texels of the patch
+    pub image: ColorImage,
+}
+
+/// Converts an area of a map image into the patch of its texture that covers the area, shrunk by
+/// the same factor as `texture_image` shrinks the whole image.  Returns `None` if the area is
+/// empty or off the image.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+pub fn texture_patch<P: Pixel<Subpixel = u8>>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    area: Rect,
+    max_side: usize,
+) -> Option<TexturePatch> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let factor = texture_factor(width, height, max_side);
+    // The texels whose top left pixel is in the area
+    let texels_in = |start: u32, length: u32, side: usize| {
+        let start = start as usize;
+        let end = start.saturating_add(length as usize).min(side);
+        (start + factor - 1) / factor..(end + factor - 1) / factor
+    };
+    let columns = texels_in(area.x, area.width, width);
+    let rows = texels_in(area.y, area.height, height);
+    if columns.is_empty() || rows.is_empty() {
+        return None;
+    }
+    Some(TexturePatch {
+        position: [columns.start, rows.start],
+        image: texels(image, factor, columns, rows),
+    })
+}
+
+/// The texels of the columns and rows of a texture shrunk by `factor`, each the top left pixel
+/// of its block of the image
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn texels<P: Pixel<Subpixel = u8>>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    factor: usize,
+    columns: Range<usize>,
+    rows: Range<usize>,
+) -> ColorImage {
+    let size = [columns.len(), rows.len()];
+    // Build the pixels directly instead of through an RGBA copy of the image, as the copy alone
+    // takes gigabytes on the largest maps
+    let mut pixels = Vec::with_capacity(size[0] * size[1]);
+    for y in rows {
+        for x in columns.clone() {
+            let [r, g, b, a] = image
+                .get_pixel((x * factor) as u32, (y * factor) as u32)
+                .to_rgba()
+                .0;
+            pixels.push(Color32::from_rgba_unmultiplied(r, g, b, a));
+        }
+    }
+    ColorImage { size, pixels }
+}
+
+/// The number of levels of a texture pyramid, from the full size texture down to an eighth of it
+pub const PYRAMID_LEVELS: usize = 4;
+
+/// Shrinks a texture to a level of its pyramid, where each level is half the size of the one
+/// before.  Like `texture_image`, each texel is the top left texel of its block, so that the
+/// colors of the map are kept as they are.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn pyramid_level(texture: &ColorImage, level: usize) -> ColorImage {
+    let step = 1_usize << level;
+    let columns = 0..(texture.size[0] + step - 1) / step;
+    let rows = 0..(texture.size[1] + step - 1) / step;
+    sample(texture, [0, 0], step, columns, rows)
+}
+
+/// Shrinks a patch of the full size texture to the patch of a level of its pyramid that covers
+/// the same area.  Returns `None` if no texel of the level takes its color from the patch.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn pyramid_patch(patch: &TexturePatch, level: usize) -> Option<TexturePatch> {
+    let step = 1_usize << level;
+    // The texels of the level whose top left texel of the full texture is in the patch
+    let texels_in =
+        |start: usize, length: usize| (start + step - 1) / step..(start + length + step - 1) / step;
+    let columns = texels_in(patch.position[0], patch.image.size[0]);
+    let rows = texels_in(patch.position[1], patch.image.size[1]);
+    if columns.is_empty() || rows.is_empty() {
+        return None;
+    }
+    Some(TexturePatch {
+        position: [columns.start, rows.start],
+        image: sample(&patch.image, patch.position, step, columns, rows),
+    })
+}
+
+/// The level of a texture pyramid to draw a texture with when each pixel of the screen covers
+/// `texels_per_pixel` texels of the full size texture.  The level is the smallest one that still
+/// has a texel for each pixel, so that zooming out draws fewer texels without losing detail.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn pyramid_level_for(texels_per_pixel: f32, levels: usize) -> usize {
+    let mut level = 0;
+    let mut per_pixel = texels_per_pixel;
+    while per_pixel >= 2.0 && level + 1 < levels {
+        per_pixel /= 2.0;
+        level += 1;
+    }
+    level
+}
+
+/// The texels of the columns and rows of a texture shrunk by `step`, each the top left texel of
+/// its block.  `origin` is the texel of the full texture the first texel of `texture` is at.
+#[allow(clippy::integer_arithmetic)]
+fn sample(
+    texture: &ColorImage,
+    origin: [usize; 2],
+    step: usize,
+    columns: Range<usize>,
+    rows: Range<usize>,
+) -> ColorImage {
+    let size = [columns.len(), rows.len()];
+    let mut pixels = Vec::with_capacity(size[0] * size[1]);
+    for y in rows {
+        let row = (y * step - origin[1]) * texture.size[0];
+        for x in columns.clone() {
+            let texel = texture.pixels.get(row + x * step - origin[0]);
+            pixels.push(texel.copied().unwrap_or_default());
+        }
+    }
+    ColorImage { size, pixels }
+}
+
+/// The areas of the map images edited since their textures were last updated, so that only the
+/// edited parts of a texture need to be uploaded again
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct DirtyRegions {
+    /// The smallest rectangle around the edits of each image
+    areas: HashMap<MapDisplayMode, Rect>,
+}
+
+impl DirtyRegions {
+    /// Marks an area of an image as edited, growing the edited area of the image to cover it
+    #[inline]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn mark(&mut self, mode: MapDisplayMode, area: Rect) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+        self.areas
+            .entry(mode)
+            .and_modify(|dirty| {
+                let x = dirty.x.min(area.x);
+                let y = dirty.y.min(area.y);
+                let right = (dirty.x + dirty.width).max(area.x + area.width);
+                let bottom = (dirty.y + dirty.height).max(area.y + area.height);
+                *dirty = Rect {
+                    x,
+                    y,
+                    width: right - x,
+                    height: bottom - y,
+                };
+            })
+            .or_insert(area);
+    }
+
+    /// Takes the edited area of an image, leaving it clean
+    #[inline]
+    pub fn take(&mut self, mode: MapDisplayMode) -> Option<Rect> {
+        self.areas.remove(&mode)
+    }
+}
+
+/// The whole factor an image must be shrunk by so that no side is longer than `max_side`
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn texture_factor(width: usize, height: usize, max_side: usize) -> usize {
+    let longest = width.max(height);
+    let max_side = max_side.max(1);
+    ((longest + max_side - 1) / max_side).max(1)
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn it_keeps_small_images_at_full_size() {
+        let image = RgbImage::from_fn(4, 2, |x, y| Rgb([x as u8, y as u8, 7]));
+        let texture = texture_image(&image, 16);
+        assert_eq!(texture.size, [4, 2]);
+        assert_eq!(texture.pixels[5], Color32::from_rgb(1, 1, 7));
+    }
+
+    #[test]
+    fn it_shrinks_images_larger_than_the_maximum_side() {
+        let image = RgbImage::from_fn(10, 4, |x, _y| Rgb([x as u8, 0, 0]));
+        let texture = texture_image(&image, 4);
+        assert_eq!(texture_factor(10, 4, 4), 3);
+        assert_eq!(texture.size, [4, 2]);
+        assert!(texture.size.iter().all(|side| *side <= 4));
+        assert_eq!(texture.pixels[1], Color32::from_rgb(3, 0, 0));
+        assert_eq!(texture.pixels[3], Color32::from_rgb(9, 0, 0));
+    }
+
+    #[test]
+    fn it_patches_the_texels_of_an_edited_area() {
+        let image = RgbImage::from_fn(10, 4, |x, y| Rgb([x as u8, y as u8, 0]));
+        let patch = texture_patch(&image, rect(2, 1, 3, 2), 16).expect("Failed to patch");
+        assert_eq!(patch.position, [2, 1]);
+        assert_eq!(patch.image.size, [3, 2]);
+        assert_eq!(patch.image.pixels[4], Color32::from_rgb(3, 2, 0));
+
+        // Shrunk by 3, only the texels at columns 3 and 6 and row 0 take pixels from the area
+        let patch = texture_patch(&image, rect(2, 0, 5, 2), 4).expect("Failed to patch");
+        assert_eq!(patch.position, [1, 0]);
+        assert_eq!(patch.image.size, [2, 1]);
+        assert_eq!(patch.image.pixels[1], Color32::from_rgb(6, 0, 0));
+        assert!(texture_patch(&image, rect(4, 1, 1, 1), 4).is_none());
+        assert!(texture_patch(&image, rect(12, 0, 2, 2), 16).is_none());
+    }
+
+    #[test]
+    fn it_shrinks_textures_and_their_patches_into_a_pyramid() {
+        let image = RgbImage::from_fn(10, 4, |x, y| Rgb([x as u8, y as u8, 0]));
+        let texture = texture_image(&image, 16);
+        let half = pyramid_level(&texture, 1);
+        assert_eq!(half.size, [5, 2]);
+        assert_eq!(half.pixels[6], Color32::from_rgb(2, 2, 0));
+        assert_eq!(pyramid_level(&texture, 2).size, [3, 1]);
+
+        let patch = texture_patch(&image, rect(3, 1, 4, 3), 16).expect("Failed to patch");
+        let half_patch = pyramid_patch(&patch, 1).expect("Failed to shrink patch");
+        assert_eq!(half_patch.position, [2, 1]);
+        assert_eq!(half_patch.image.size, [2, 1]);
+        assert_eq!(half_patch.image.pixels[1], Color32::from_rgb(6, 2, 0));
+        assert!(pyramid_patch(&patch, 3).is_none());
+    }
+
+    #[test]
+    fn it_picks_the_smallest_level_with_a_texel_for_each_pixel() {
+        assert_eq!(pyramid_level_for(0.5, PYRAMID_LEVELS), 0);
+        assert_eq!(pyramid_level_for(1.9, PYRAMID_LEVELS), 0);
+        assert_eq!(pyramid_level_for(2.0, PYRAMID_LEVELS), 1);
+        assert_eq!(pyramid_level_for(5.0, PYRAMID_LEVELS), 2);
+        assert_eq!(pyramid_level_for(100.0, PYRAMID_LEVELS), 3);
+        assert_eq!(pyramid_level_for(100.0, 1), 0);
+    }
+
+    #[test]
+    fn it_grows_the_dirty_region_to_cover_every_edit() {
+        let mut dirty = DirtyRegions::default();
+        dirty.mark(MapDisplayMode::Terrain, rect(4, 4, 2, 2));
+        dirty.mark(MapDisplayMode::Terrain, rect(1, 5, 2, 4));
+        dirty.mark(MapDisplayMode::Rivers, rect(0, 0, 0, 3));
+        assert_eq!(dirty.take(MapDisplayMode::Terrain), Some(rect(1, 4, 5, 5)));
+        assert_eq!(dirty.take(MapDisplayMode::Terrain), None);
+        assert_eq!(dirty.take(MapDisplayMode::Rivers), None);
+    }
+}
@@ -0,0 +1,162 @@
+/// How the edges of the map connect to each other.
+///
+/// On a horizontally wrapping map the leftmost and rightmost columns of pixels are next to each
+/// other, so the map forms a cylinder as the world map of the base game does.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Wrapping {
+    /// The edges of the map are not connected
+    #[default]
+    None,
+    /// The left and right edges of the map are connected
+    Horizontal,
+}
+
+impl Wrapping {
+    /// Whether the left and right edges of the map are connected
+    #[inline]
+    #[must_use]
+    pub const fn wraps_horizontally(self) -> bool {
+        matches!(self, Self::Horizontal)
+    }
+
+    /// Gets the indices of the pixels sharing an edge with the pixel at the given index, in the
+    /// order left, right, up and down.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    pub fn neighbors(self, index: usize, width: u32, height: u32) -> [Option<usize>; 4] {
+        let width = width as usize;
+        let height = height as usize;
+        let x = index % width;
+        let y = index / width;
+        let wraps = self.wraps_horizontally() && width > 1;
+        [
+            if x > 0 {
+                Some(index - 1)
+            } else {
+                wraps.then(|| index + width - 1)
+            },
+            if x + 1 < width {
+                Some(index + 1)
+            } else {
+                wraps.then(|| index + 1 - width)
+            },
+            (y > 0).then(|| index - width),
+            (y + 1 < height).then(|| index + width),
+        ]
+    }
+
+    /// Gets the pixels sharing an edge with the given pixel, in the order left, right, up and down.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    pub fn neighbor_points(
+        self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> [Option<(u32, u32)>; 4] {
+        let wraps = self.wraps_horizontally() && width > 1;
+        [
+            if x > 0 {
+                Some((x - 1, y))
+            } else {
+                wraps.then(|| (width - 1, y))
+            },
+            if x + 1 < width {
+                Some((x + 1, y))
+            } else {
+                wraps.then_some((0, y))
+            },
+            y.checked_sub(1).map(|ny| (x, ny)),
+            (y + 1 < height).then_some((x, y + 1)),
+        ]
+    }
+
+    /// Whether the pixel lies on an edge of the map that does not connect to another edge
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    pub const fn is_on_edge(self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let on_side = x == 0 || x + 1 >= width;
+        y == 0 || y + 1 >= height || (on_side && !self.wraps_horizontally())
+    }
+
+    /// The horizontal distance from one x coordinate to another, taking the shorter way around
+    /// the map if it wraps.  The result is negative if the shorter way is to the left.
+    #[inline]
+    #[must_use]
+    pub fn delta_x(self, from: f64, to: f64, width: f64) -> f64 {
+        let delta = to - from;
+        if !self.wraps_horizontally() || width <= 0.0 {
+            return delta;
+        }
+        let wrapped = delta.rem_euclid(width);
+        if wrapped > width / 2.0 {
+            wrapped - width
+        } else {
+            wrapped
+        }
+    }
+
+    /// The straight line distance between two points, taking the shorter way around the map if
+    /// it wraps.
+    #[inline]
+    #[must_use]
+    pub fn distance(self, from: (f64, f64), to: (f64, f64), width: f64) -> f64 {
+        self.delta_x(from.0, to.0, width).hypot(to.1 - from.1)
+    }
+
+    /// Brings an x coordinate back onto the map if it wraps, otherwise clamps it to the map.
+    #[inline]
+    #[must_use]
+    pub fn wrap_x(self, x: f64, width: f64) -> f64 {
+        if self.wraps_horizontally() && width > 0.0 {
+            x.rem_euclid(width)
+        } else {
+            x.clamp(0.0, width)
+        }
+    }
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::float_cmp)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_connects_the_sides_of_a_wrapping_map() {
+        assert_eq!(
+            Wrapping::None.neighbors(4, 5, 2),
+            [Some(3), None, None, Some(9)]
+        );
+        assert_eq!(
+            Wrapping::Horizontal.neighbors(4, 5, 2),
+            [Some(3), Some(0), None, Some(9)]
+        );
+        assert_eq!(
+            Wrapping::Horizontal.neighbor_points(0, 1, 5, 2),
+            [Some((4, 1)), Some((1, 1)), Some((0, 0)), None]
+        );
+        assert!(Wrapping::None.is_on_edge(0, 1, 5, 3));
+        assert!(!Wrapping::Horizontal.is_on_edge(0, 1, 5, 3));
+        assert!(Wrapping::Horizontal.is_on_edge(2, 0, 5, 3));
+    }
+
+    #[test]
+    fn it_measures_the_shorter_way_around() {
+        assert_eq!(Wrapping::None.delta_x(1.0, 99.0, 100.0), 98.0);
+        assert_eq!(Wrapping::Horizontal.delta_x(1.0, 99.0, 100.0), -2.0);
+        assert_eq!(Wrapping::Horizontal.delta_x(99.0, 1.0, 100.0), 2.0);
+        assert_eq!(
+            Wrapping::Horizontal.distance((1.0, 0.0), (97.0, 3.0), 100.0),
+            5.0
+        );
+        assert_eq!(Wrapping::Horizontal.wrap_x(-1.0, 100.0), 99.0);
+        assert_eq!(Wrapping::None.wrap_x(-1.0, 100.0), 0.0);
+    }
+}
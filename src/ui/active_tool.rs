@@ -0,0 +1,58 @@
+use actix::{Actor, Context, Handler, Message};
+use world_gen::brush::{HeightBrush, RiverBrush, TerrainBrush};
+use world_gen::components::wrappers::VictoryPoints;
+
+/// An editing tool that can be applied to the map
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tool {
+    HeightBrush(HeightBrush),
+    RiverBrush(RiverBrush),
+    TerrainBrush(TerrainBrush),
+    /// Sets the victory points of the clicked province, removing them for zero
+    VictoryPoints(VictoryPoints),
+    /// A tool registered by a plugin, by its index in the `PluginRegistry`
+    Plugin(usize),
+}
+
+/// A request to get the active tool
+#[derive(Message)]
+#[rtype(result = "Option<Tool>")]
+#[non_exhaustive]
+pub struct GetActiveTool;
+
+/// A request to set the active tool
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetActiveTool(pub Option<Tool>);
+
+impl SetActiveTool {
+    pub const fn new(tool: Option<Tool>) -> Self {
+        Self(tool)
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct ActiveTool {
+    tool: Option<Tool>,
+}
+
+impl Actor for ActiveTool {
+    type Context = Context<Self>;
+}
+
+impl Handler<GetActiveTool> for ActiveTool {
+    type Result = Option<Tool>;
+
+    fn handle(&mut self, _msg: GetActiveTool, _ctx: &mut Self::Context) -> Self::Result {
+        self.tool
+    }
+}
+
+impl Handler<SetActiveTool> for ActiveTool {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetActiveTool, _ctx: &mut Self::Context) -> Self::Result {
+        self.tool = msg.0;
+    }
+}
@@ -0,0 +1,86 @@
+use egui::{Color32, ComboBox, Context, Grid, Ui, Window};
+use std::collections::HashMap;
+use world_gen::components::prelude::{
+    AdjacencyLogic, AdjacencyRule, AdjacencyRuleName, AdjacencyRules, Controller, ProvinceId,
+};
+
+/// A window showing what can pass through an adjacency rule when its required provinces are
+/// controlled by friends, enemies or neutrals, so that rules can be checked without the game.
+#[derive(Debug, Default)]
+pub struct AdjacencyRuleWindow {
+    open: bool,
+    rule: Option<AdjacencyRuleName>,
+    controllers: HashMap<ProvinceId, Controller>,
+}
+
+impl AdjacencyRuleWindow {
+    pub const fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn render(&mut self, ctx: &Context, rules: &AdjacencyRules) {
+        let Self {
+            open,
+            rule,
+            controllers,
+        } = self;
+        let mut names = rules.adjacency_rules.keys().collect::<Vec<_>>();
+        names.sort_unstable();
+        Window::new("Adjacency rules").open(open).show(ctx, |ui| {
+            ComboBox::from_label("Rule")
+                .selected_text(rule.as_ref().map_or("", |r| r.0.as_str()))
+                .show_ui(ui, |ui| {
+                    for name in names {
+                        ui.selectable_value(rule, Some(name.clone()), &name.0);
+                    }
+                });
+            if let Some(selected) = rule.as_ref().and_then(|r| rules.adjacency_rules.get(r)) {
+                render_controllers(selected, controllers, ui);
+                ui.separator();
+                render_logic(selected.logic(controllers), ui);
+                if let Some(disabled) = &selected.is_disabled {
+                    ui.label(format!(
+                        "The rule may also be disabled ({}), which is not simulated",
+                        disabled.tooltip
+                    ));
+                }
+            }
+        });
+    }
+}
+
+fn render_controllers(
+    rule: &AdjacencyRule,
+    controllers: &mut HashMap<ProvinceId, Controller>,
+    ui: &mut Ui,
+) {
+    Grid::new("adjacency_rule_controllers").show(ui, |ui| {
+        for province in &rule.required_provinces {
+            ui.label(format!("Province {province}"));
+            let controller = controllers.entry(*province).or_default();
+            for option in [Controller::Friend, Controller::Enemy, Controller::Neutral] {
+                ui.radio_value(controller, option, option.to_string());
+            }
+            ui.end_row();
+        }
+    });
+}
+
+fn render_logic(logic: AdjacencyLogic, ui: &mut Ui) {
+    for (label, can_pass) in [
+        ("Army", logic.army),
+        ("Navy", logic.navy),
+        ("Submarine", logic.submarine),
+        ("Trade", logic.trade),
+    ] {
+        if can_pass {
+            ui.colored_label(Color32::GREEN, format!("{label} can pass"));
+        } else {
+            ui.colored_label(Color32::RED, format!("{label} is blocked"));
+        }
+    }
+}
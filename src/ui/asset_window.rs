@@ -0,0 +1,142 @@
+use egui::{
+    Color32, ColorImage, ComboBox, Context, ScrollArea, TextureFilter, TextureHandle, Vec2, Window,
+};
+use log::error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use world_gen::assets::{find_assets, load_asset};
+use world_gen::texture::texture_image;
+use world_gen::MapError;
+
+/// A loaded graphic as a texture, along with the size of the file
+type LoadedAsset = (ColorImage, (u32, u32));
+
+/// A window showing the DDS and TGA graphics of the map, such as the terrain textures and
+/// colormaps, so that the whole of what the game draws can be inspected.  A graphic is loaded in
+/// the background once it is picked.
+#[derive(Default)]
+pub struct AssetWindow {
+    open: bool,
+    root_path: Option<PathBuf>,
+    assets: Vec<PathBuf>,
+    selected: Option<PathBuf>,
+    loading: Option<Receiver<Result<LoadedAsset, MapError>>>,
+    texture: Option<(TextureHandle, (u32, u32))>,
+    error: Option<String>,
+}
+
+impl AssetWindow {
+    pub const fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Renders the window, finding the graphics of the map again when the root directory changes
+    pub fn render(&mut self, ctx: &Context, root_path: &Path) {
+        if self.root_path.as_deref() != Some(root_path) {
+            self.find_assets(root_path);
+        }
+        self.receive_texture(ctx);
+        let mut picked = None;
+        let mut open = self.open;
+        Window::new("Map graphics").open(&mut open).show(ctx, |ui| {
+            if self.assets.is_empty() {
+                ui.label("There are no DDS or TGA files in the map folder");
+                return;
+            }
+            let selected_text = self
+                .selected
+                .as_ref()
+                .map_or_else(String::new, |p| p.display().to_string());
+            ComboBox::from_label("File")
+                .selected_text(selected_text)
+                .width(320.0)
+                .show_ui(ui, |ui| {
+                    for asset in &self.assets {
+                        let is_selected = self.selected.as_ref() == Some(asset);
+                        if ui
+                            .selectable_label(is_selected, asset.display().to_string())
+                            .clicked()
+                            && !is_selected
+                        {
+                            picked = Some(asset.clone());
+                        }
+                    }
+                });
+            ui.separator();
+            if self.loading.is_some() {
+                ui.spinner();
+            } else if let Some(e) = &self.error {
+                ui.colored_label(Color32::RED, e);
+            } else if let Some((texture, (width, height))) = &self.texture {
+                ui.label(format!("{width}x{height}"));
+                let size = texture.size_vec2();
+                let scale = (ui.available_width() / size.x).min(1.0);
+                ScrollArea::vertical().show(ui, |ui| {
+                    ui.image(texture.id(), Vec2::new(size.x * scale, size.y * scale));
+                });
+            }
+        });
+        self.open = open;
+        if let Some(asset) = picked {
+            self.load(ctx, root_path, asset);
+        }
+    }
+
+    /// Finds the graphics of the map in a new root directory, forgetting the one shown
+    fn find_assets(&mut self, root_path: &Path) {
+        self.root_path = Some(root_path.to_path_buf());
+        self.assets = find_assets(root_path).unwrap_or_else(|e| {
+            error!("Failed to find the graphics of the map: {e}");
+            Vec::new()
+        });
+        self.selected = None;
+        self.loading = None;
+        self.texture = None;
+        self.error = None;
+    }
+
+    /// Loads a graphic on the blocking threads, shrinking it to fit in a texture
+    fn load(&mut self, ctx: &Context, root_path: &Path, asset: PathBuf) {
+        let (sender, receiver) = channel();
+        let path = root_path.join(&asset);
+        let max_side = ctx.input().max_texture_side;
+        let context = ctx.clone();
+        tokio::task::spawn_blocking(move || {
+            let loaded = load_asset(&path).map(|image| {
+                let size = image.dimensions();
+                (texture_image(&image, max_side), size)
+            });
+            let _result = sender.send(loaded);
+            context.request_repaint();
+        });
+        self.selected = Some(asset);
+        self.loading = Some(receiver);
+        self.error = None;
+    }
+
+    /// Turns the graphic into a texture once it has loaded
+    fn receive_texture(&mut self, ctx: &Context) {
+        let received = match &self.loading {
+            Some(receiver) => receiver.try_recv(),
+            None => return,
+        };
+        match received {
+            Ok(Ok((image, size))) => {
+                let texture = ctx.load_texture("map_graphic", image, TextureFilter::Linear);
+                self.texture = Some((texture, size));
+            }
+            Ok(Err(e)) => {
+                error!("Failed to load the graphic: {e}");
+                self.texture = None;
+                self.error = Some(e.to_string());
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {}
+        }
+        self.loading = None;
+    }
+}
@@ -0,0 +1,126 @@
+use egui::{Context, Key, Pos2, Rect, Ui};
+use log::{debug, error};
+use std::path::{Path, PathBuf};
+use world_gen::project::{Bookmark, Project};
+
+/// The keys that jump to the first nine bookmarks
+const HOTKEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+/// The bookmarked viewport positions of the open root folder, kept in its project file
+#[derive(Debug, Default)]
+pub struct Bookmarks {
+    root_path: Option<PathBuf>,
+    project: Project,
+    new_name: String,
+}
+
+impl Bookmarks {
+    /// Loads the project of the root folder, unless it is already loaded
+    pub fn load(&mut self, root_path: &Option<PathBuf>) {
+        if &self.root_path == root_path {
+            return;
+        }
+        self.root_path = root_path.clone();
+        self.project = match root_path.as_deref().map(Project::load) {
+            Some(Ok(project)) => project,
+            Some(Err(e)) => {
+                error!("Failed to load the project file: {e}");
+                Project::default()
+            }
+            None => Project::default(),
+        };
+        debug!("Loaded {} bookmarks", self.project.bookmarks.len());
+    }
+
+    /// Renders the bookmarks menu, returning the bookmark to jump to if one was picked
+    pub fn render_menu(
+        &mut self,
+        ui: &mut Ui,
+        viewport: Option<Rect>,
+        zoom: Option<f32>,
+    ) -> Option<Bookmark> {
+        let mut jump = None;
+        let mut remove = None;
+        let root_path = match self.root_path.clone() {
+            Some(p) => p,
+            None => {
+                ui.label("Open a root folder to bookmark it");
+                return None;
+            }
+        };
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_name);
+            let can_add = viewport.is_some() && !self.new_name.is_empty();
+            if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                if let Some(rect) = viewport {
+                    let name = std::mem::take(&mut self.new_name);
+                    self.project.add_bookmark(Bookmark::new(
+                        name,
+                        (rect.min.x, rect.min.y),
+                        (rect.max.x, rect.max.y),
+                        zoom,
+                    ));
+                    self.save(&root_path);
+                }
+            }
+        });
+        ui.separator();
+        for (index, bookmark) in self.project.bookmarks.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let label = match HOTKEYS.get(index) {
+                    Some(_) => format!("{}. {}", index.saturating_add(1), bookmark.name),
+                    None => bookmark.name.clone(),
+                };
+                if ui.button(label).clicked() {
+                    jump = Some(bookmark.clone());
+                    ui.close_menu();
+                }
+                if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                    remove = Some(bookmark.name.clone());
+                }
+            });
+        }
+        if let Some(name) = remove {
+            self.project.remove_bookmark(&name);
+            self.save(&root_path);
+        }
+        jump
+    }
+
+    /// The bookmark whose number key was pressed, unless a text field has the keyboard
+    pub fn hotkey(&self, ctx: &Context) -> Option<Bookmark> {
+        if ctx.wants_keyboard_input() {
+            return None;
+        }
+        let input = ctx.input();
+        HOTKEYS
+            .iter()
+            .zip(&self.project.bookmarks)
+            .find(|(key, _bookmark)| input.key_pressed(**key))
+            .map(|(_key, bookmark)| bookmark.clone())
+    }
+
+    fn save(&self, root_path: &Path) {
+        if let Err(e) = self.project.save(root_path) {
+            error!("Failed to save the project file: {e}");
+        }
+    }
+}
+
+/// The viewport area a bookmark was taken of
+pub fn bookmark_area(bookmark: &Bookmark) -> Rect {
+    Rect::from_min_max(
+        Pos2::new(bookmark.min_x, bookmark.min_y),
+        Pos2::new(bookmark.max_x, bookmark.max_y),
+    )
+}
@@ -0,0 +1,185 @@
+use actix::{Actor, Context, Handler, Message, MessageResult};
+use egui::{Align2, Area, Color32, Context as EguiContext, Order, Pos2, Sense, Vec2, Window};
+use log::{trace, warn};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// A long running operation that other operations must wait for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    ChoosingRootFolder,
+    LoadingMap,
+    GeneratingMap,
+    SavingStates,
+    ExportingRegionMap,
+    ExportingGeoJson,
+    ExportingPoster,
+    CreatingSupportBundle,
+    ApplyingFixes,
+    Validating,
+}
+
+impl Operation {
+    /// Whether the rest of the UI is blocked while the operation runs.  Loading the map is not,
+    /// so that its progress stays visible.
+    pub const fn is_modal(self) -> bool {
+        !matches!(self, Self::LoadingMap)
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::ChoosingRootFolder => "choosing a root folder",
+            Self::LoadingMap => "loading the map",
+            Self::GeneratingMap => "generating a map",
+            Self::SavingStates => "saving the states",
+            Self::ExportingRegionMap => "exporting a region map",
+            Self::ExportingGeoJson => "exporting GeoJSON",
+            Self::ExportingPoster => "exporting a poster",
+            Self::CreatingSupportBundle => "creating a support bundle",
+            Self::ApplyingFixes => "applying automatic fixes",
+            Self::Validating => "validating the map",
+        };
+        write!(f, "{description}")
+    }
+}
+
+/// A request to start an operation.  Returns the operation in progress if there is one, in which
+/// case the new operation must not be started.
+#[derive(Message)]
+#[rtype(result = "Result<(), Operation>")]
+#[non_exhaustive]
+pub struct Begin(pub Operation);
+
+impl Begin {
+    pub const fn new(operation: Operation) -> Self {
+        Self(operation)
+    }
+}
+
+/// A request to mark an operation as finished
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct End(pub Operation);
+
+impl End {
+    pub const fn new(operation: Operation) -> Self {
+        Self(operation)
+    }
+}
+
+/// A request to get the operation in progress and the last operation that was rejected
+#[derive(Message)]
+#[rtype(result = "BusyState")]
+#[non_exhaustive]
+pub struct GetBusyState;
+
+/// A request to stop showing the last rejected operation
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct DismissRejection;
+
+/// The operation in progress and the last operation that was rejected because of it
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BusyState {
+    pub operation: Option<Operation>,
+    pub rejected: Option<(Operation, Operation)>,
+}
+
+/// Tracks the operation in progress, so that a second one is rejected rather than interleaved
+#[derive(Debug, Default)]
+pub struct Busy {
+    state: BusyState,
+}
+
+impl Actor for Busy {
+    type Context = Context<Self>;
+}
+
+impl Handler<Begin> for Busy {
+    type Result = Result<(), Operation>;
+
+    fn handle(&mut self, msg: Begin, _ctx: &mut Self::Context) -> Self::Result {
+        trace!("Begin {}", msg.0);
+        match self.state.operation {
+            Some(current) => {
+                warn!("Cannot start {} while {current}", msg.0);
+                self.state.rejected = Some((msg.0, current));
+                Err(current)
+            }
+            None => {
+                self.state.operation = Some(msg.0);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Handler<End> for Busy {
+    type Result = ();
+
+    fn handle(&mut self, msg: End, _ctx: &mut Self::Context) -> Self::Result {
+        trace!("End {}", msg.0);
+        if self.state.operation == Some(msg.0) {
+            self.state.operation = None;
+        }
+    }
+}
+
+impl Handler<GetBusyState> for Busy {
+    type Result = MessageResult<GetBusyState>;
+
+    fn handle(&mut self, _msg: GetBusyState, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.state)
+    }
+}
+
+impl Handler<DismissRejection> for Busy {
+    type Result = ();
+
+    fn handle(&mut self, _msg: DismissRejection, _ctx: &mut Self::Context) -> Self::Result {
+        self.state.rejected = None;
+    }
+}
+
+/// Covers the window while a modal operation runs, so that nothing else can be started, and
+/// explains why an operation was rejected.  Returns whether the rejection was dismissed.
+pub fn render_busy_overlay(ctx: &EguiContext, state: BusyState) -> bool {
+    if let Some(operation) = state.operation.filter(|o| o.is_modal()) {
+        let screen = ctx.input().screen_rect();
+        Area::new("busy_overlay")
+            .order(Order::Foreground)
+            .fixed_pos(Pos2::ZERO)
+            .show(ctx, |ui| {
+                // Taking the clicks on the whole window keeps them from the panels underneath
+                let (rect, _response) = ui.allocate_exact_size(screen.size(), Sense::click());
+                ui.painter()
+                    .rect_filled(rect, 0.0, Color32::from_black_alpha(160));
+            });
+        Area::new("busy_message")
+            .order(Order::Tooltip)
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.heading(format!("Please wait while {operation}…"));
+                });
+            });
+    }
+    let mut dismissed = false;
+    if let Some((requested, current)) = state.rejected {
+        Window::new("Busy")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_TOP, Vec2::new(0.0, 40.0))
+            .show(ctx, |ui| {
+                ui.label(format!("Cannot start {requested} while {current}."));
+                ui.label("Try again once it has finished.");
+                dismissed = ui.button("OK").clicked();
+            });
+    }
+    dismissed
+}
@@ -1,18 +1,40 @@
+use crate::ui::active_tool::{ActiveTool, GetActiveTool, Tool};
 use crate::ui::map_loader::GetMap;
 use crate::ui::map_mode::GetMapMode;
-use crate::ui::map_textures::GetTexture;
-use crate::ui::selection::SetSelectedPoint;
-use crate::ui::viewport::{GetViewportArea, GetZoomLevel, Scroll, SetViewportArea};
+use crate::ui::map_textures::{
+    GetTexture, IsTextureLoading, LoadImage, PatchTexture, TexturePyramid,
+};
+use crate::ui::metrics::{send_timed, Metrics, RecordCacheLookup};
+use crate::ui::plugins::{GetLayerTextures, GetPluginRegistry, LayerTexture, Plugins};
+use crate::ui::selection::{
+    GetSelectedProvince, GetSelectedState, GetSelectedStrategicRegion, SetSelectedPoint,
+    SetSelectedState,
+};
+use crate::ui::viewport::{
+    GetViewportArea, GetViewportWrapping, GetZoomLevel, Scroll, SetViewportArea,
+};
 use crate::{MapError, MapLoader, MapMode, MapTextures, Selection, Viewport};
 use actix::Addr;
 use egui::{
-    CentralPanel, Context, ImageButton, Pos2, Rect, Response, Sense, Spinner, TextureHandle, Ui,
-    Vec2,
+    CentralPanel, Color32, Context, ImageButton, Key, Pos2, Rect, Response, Sense, Shape, Spinner,
+    TextureFilter, TextureHandle, TextureId, Ui, Vec2,
 };
-use world_gen::map::Map;
+use log::warn;
+use std::collections::HashSet;
+use std::sync::Arc;
+use world_gen::components::prelude::{Definition, ProvinceId, StrategicRegion};
+use world_gen::components::state::State;
+use world_gen::components::wrappers::VictoryPoints;
+use world_gen::map::{
+    ApplyEdits, ApplyTool, CancelEdits, GetEditPreview, GetMapImage, GetProvinceIdFromPoint,
+    GetSelectionHighlight, Map, PaintHeightMap, PaintRivers, PaintTerrain, Redo, SetVictoryPoints,
+    TakeTexturePatch, Undo,
+};
+use world_gen::plugin::PluginRegistry;
+use world_gen::texture::{pyramid_level_for, texture_image};
+use world_gen::topology::Wrapping;
 use world_gen::MapDisplayMode;
 
-#[derive(Debug)]
 pub struct CentralPanelRenderer {
     map_loader: Addr<MapLoader>,
     map_mode: Addr<MapMode>,
@@ -20,6 +42,19 @@ pub struct CentralPanelRenderer {
     selection: Addr<Selection>,
     map: Option<Addr<Map>>,
     viewport: Addr<Viewport>,
+    active_tool: Addr<ActiveTool>,
+    plugins: Addr<Plugins>,
+    metrics: Addr<Metrics>,
+    last_painted_point: Option<Pos2>,
+    edited_map_modes: Vec<MapDisplayMode>,
+    /// The display mode the preview of the pending edits was last drawn for
+    preview_mode: Option<MapDisplayMode>,
+    /// The layer highlighting the pixels changed by the pending edits
+    preview_texture: Option<TextureHandle>,
+    /// The provinces the selection highlight was last drawn for
+    highlighted: HashSet<ProvinceId>,
+    /// The layer highlighting the selected province, state or strategic region
+    highlight_texture: Option<TextureHandle>,
 }
 
 impl CentralPanelRenderer {
@@ -30,6 +65,9 @@ impl CentralPanelRenderer {
         map_textures: Addr<MapTextures>,
         selection: Addr<Selection>,
         viewport: Addr<Viewport>,
+        active_tool: Addr<ActiveTool>,
+        plugins: Addr<Plugins>,
+        metrics: Addr<Metrics>,
     ) -> Self {
         Self {
             map_loader,
@@ -38,55 +76,120 @@ impl CentralPanelRenderer {
             selection,
             map: None,
             viewport,
+            active_tool,
+            plugins,
+            metrics,
+            last_painted_point: None,
+            edited_map_modes: Vec::new(),
+            preview_mode: None,
+            preview_texture: None,
+            highlighted: HashSet::new(),
+            highlight_texture: None,
         }
     }
 
     #[allow(clippy::else_if_without_else)]
+    #[allow(clippy::too_many_lines)]
     #[allow(clippy::cast_possible_truncation)]
     #[allow(clippy::as_conversions)]
     pub async fn render_central_panel(&mut self, ctx: &Context) -> Result<(), MapError> {
-        let map_mode: MapDisplayMode = self.map_mode.send(GetMapMode).await?;
-        let texture: Option<TextureHandle> =
-            self.map_textures.send(GetTexture::from(map_mode)).await?;
-        if self.map.is_none() {
-            let addr = self.map_loader.send(GetMap).await?;
-            if let Some(m) = addr {
-                self.map = Some(m);
-            }
+        let metrics = &self.metrics;
+        let map_mode: MapDisplayMode =
+            send_timed(metrics, "mailbox/map_mode", &self.map_mode, GetMapMode).await?;
+        let texture: Option<TexturePyramid> = send_timed(
+            metrics,
+            "mailbox/map_textures",
+            &self.map_textures,
+            GetTexture::from(map_mode),
+        )
+        .await?;
+        // The map is asked for every frame, as it is swapped out when the root folder changes
+        self.map = send_timed(metrics, "mailbox/map_loader", &self.map_loader, GetMap).await?;
+        if self.map.is_some() {
+            metrics.do_send(RecordCacheLookup::new("textures", texture.is_some()));
         }
-        let viewport_rect: Rect = self.viewport.send(GetViewportArea).await?.map_or(
-            Rect::from([Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0)]),
-            |r| r,
-        );
+        let viewport_rect: Rect =
+            send_timed(metrics, "mailbox/viewport", &self.viewport, GetViewportArea)
+                .await?
+                .map_or(
+                    Rect::from([Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0)]),
+                    |r| r,
+                );
         let zoom_level = self.viewport.send(GetZoomLevel).await?;
+        let wrapping: Wrapping = self.viewport.send(GetViewportWrapping).await?;
+        let active_tool: Option<Tool> = self.active_tool.send(GetActiveTool).await?;
+        let registry: Arc<PluginRegistry> = self.plugins.send(GetPluginRegistry).await?;
+        let layer_textures: Vec<LayerTexture> = self.plugins.send(GetLayerTextures).await?;
+        let painting_tool = active_tool.filter(|t| tool_applies_to(*t, map_mode, &registry));
 
+        let preview_texture = self.preview_texture.clone();
+        let highlight_texture = self.highlight_texture.clone();
         let mut selected_point = None;
+        let mut painted_point = None;
+        let mut finish_edits = None;
         CentralPanel::default().show(ctx, |ui| {
-            if let Some(tex) = &texture {
-                let tex_size = tex.size_vec2();
+            if let Some(pyramid) = &texture {
+                let tex_size = pyramid.full().size_vec2();
                 let size = ui.ctx().available_rect().size() * 0.9;
                 let x_scale = size.x / tex_size.x;
                 let y_scale = size.y / tex_size.y;
                 let min_scale = x_scale.min(y_scale);
-                let image_button = ImageButton::new(tex, tex_size * min_scale)
-                    .frame(false)
-                    .uv(viewport_rect)
-                    .sense(Sense::click_and_drag());
-                let map = ui.add(image_button);
+                // The viewport's share of the texels of the full size texture are drawn across
+                // the map's share of the physical pixels
+                let texels_per_pixel =
+                    viewport_rect.width() / (min_scale * ui.ctx().pixels_per_point());
+                let tex = pyramid.level(pyramid_level_for(texels_per_pixel, pyramid.levels()));
+                let map = if viewport_rect.max.x > 1.0 {
+                    // The viewport runs past the right edge of a wrapping map, which a single
+                    // image cannot show.
+                    let (_rect, response) =
+                        ui.allocate_exact_size(tex_size * min_scale, Sense::click_and_drag());
+                    paint_wrapped_image(ui, tex.id(), response.rect, viewport_rect, Color32::WHITE);
+                    response
+                } else {
+                    let image_button = ImageButton::new(tex, tex_size * min_scale)
+                        .frame(false)
+                        .uv(viewport_rect)
+                        .sense(Sense::click_and_drag());
+                    ui.add(image_button)
+                };
                 let map_rect = map.rect;
+                paint_overlays(ui, &layer_textures, map_rect, viewport_rect);
+                if let Some(highlight) = &highlight_texture {
+                    paint_wrapped_image(
+                        ui,
+                        highlight.id(),
+                        map_rect,
+                        viewport_rect,
+                        Color32::WHITE,
+                    );
+                }
+                if let Some(preview) = &preview_texture {
+                    finish_edits = render_edit_preview(ui, preview, map_rect, viewport_rect);
+                }
                 let mouse_pos = ui.ctx().pointer_latest_pos();
                 if let Some(pos) = mouse_pos {
                     if map_rect.contains(pos) {
                         let scroll = handle_scroll(ui, &self.viewport);
-                        handle_zoom(&self.viewport, zoom_level, viewport_rect, scroll);
-                        handle_drag(&self.viewport, zoom_level, viewport_rect, &map);
+                        handle_zoom(&self.viewport, zoom_level, viewport_rect, wrapping, scroll);
                         let tex_uv = project_to_texture(&viewport_rect, tex_size, pos, &map_rect);
                         ui.label(format!(
                             "Map Coordinate: ({:?}, {:?})",
                             tex_uv.x as i32, tex_uv.y as i32
                         ));
-                        if map.clicked() {
-                            selected_point = Some(tex_uv);
+                        if let Some(Tool::VictoryPoints(_)) = painting_tool {
+                            if map.clicked() {
+                                painted_point = Some(tex_uv);
+                            }
+                        } else if painting_tool.is_some() {
+                            if map.dragged() || map.clicked() {
+                                painted_point = Some(tex_uv);
+                            }
+                        } else {
+                            handle_drag(&self.viewport, zoom_level, viewport_rect, wrapping, &map);
+                            if map.clicked() {
+                                selected_point = Some(tex_uv);
+                            }
                         }
                     }
                 }
@@ -99,10 +202,298 @@ impl CentralPanelRenderer {
         if let Some(point) = selected_point {
             self.selection.send(SetSelectedPoint::new(point)).await?;
         }
+        if let (Some(point), Some(tool), Some(map)) = (painted_point, painting_tool, &self.map) {
+            let edited_modes = match tool {
+                Tool::HeightBrush(brush) => {
+                    map.send(PaintHeightMap::new(point, brush)).await?;
+                    self.patch_texture(ctx, map, MapDisplayMode::HeightMap)
+                        .await?
+                }
+                Tool::RiverBrush(brush) => {
+                    map.send(PaintRivers::new(self.last_painted_point, point, brush))
+                        .await?;
+                    self.patch_texture(ctx, map, MapDisplayMode::Rivers).await?
+                }
+                Tool::TerrainBrush(brush) => {
+                    map.send(PaintTerrain::new(point, brush)).await?;
+                    self.patch_texture(ctx, map, MapDisplayMode::Terrain)
+                        .await?
+                }
+                Tool::VictoryPoints(points) => {
+                    self.set_victory_points(map, point, points).await?;
+                    Vec::new()
+                }
+                Tool::Plugin(index) => match registry.tools().get(index) {
+                    Some(t) => map.send(ApplyTool::new(Arc::clone(t), point)).await?,
+                    None => Vec::new(),
+                },
+            };
+            self.preview_mode = None;
+            for mode in edited_modes {
+                if !self.edited_map_modes.contains(&mode) {
+                    self.edited_map_modes.push(mode);
+                }
+            }
+        }
+        self.last_painted_point = painted_point;
+        self.finish_edits(finish_edits).await?;
+        self.handle_history_keys(ctx).await?;
+        self.refresh_preview(ctx, map_mode).await?;
+        self.refresh_highlight(ctx, map_mode).await?;
+        self.refresh_edited_textures(ctx).await?;
+        Ok(())
+    }
+
+    /// Uploads the painted area of the image of a display mode to its texture.  Returns the mode
+    /// if its whole texture must be loaded again instead, as when the texture is still loading.
+    async fn patch_texture(
+        &self,
+        ctx: &Context,
+        map: &Addr<Map>,
+        mode: MapDisplayMode,
+    ) -> Result<Vec<MapDisplayMode>, MapError> {
+        let max_side = ctx.input().max_texture_side;
+        let patch = match map.send(TakeTexturePatch::new(mode, max_side)).await? {
+            Some(p) => p,
+            None => return Ok(Vec::new()),
+        };
+        if self
+            .map_textures
+            .send(PatchTexture::new(mode, patch))
+            .await?
+        {
+            Ok(Vec::new())
+        } else {
+            Ok(vec![mode])
+        }
+    }
+
+    /// Sets the victory points of the province at the point, removing them for zero, and selects
+    /// the edited state to show them
+    async fn set_victory_points(
+        &self,
+        map: &Addr<Map>,
+        point: Pos2,
+        points: VictoryPoints,
+    ) -> Result<(), MapError> {
+        let province = match map.send(GetProvinceIdFromPoint::new(point)).await? {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let points = (points.0 > 0.0).then_some(points);
+        match map.send(SetVictoryPoints::new(province, points)).await? {
+            Ok(state) => {
+                self.selection.send(SetSelectedPoint::new(point)).await?;
+                self.selection.send(SetSelectedState::new(state)).await?;
+            }
+            Err(e) => warn!("Failed to set the victory points of province {province}: {e}"),
+        }
+        Ok(())
+    }
+
+    /// Applies the pending edits for `Some(true)` or cancels them for `Some(false)`, reloading the
+    /// textures of the images restored by cancelling them
+    async fn finish_edits(&mut self, finish: Option<bool>) -> Result<(), MapError> {
+        let (map, apply) = match (&self.map, finish) {
+            (Some(m), Some(apply)) => (m, apply),
+            _ => return Ok(()),
+        };
+        if apply {
+            map.send(ApplyEdits).await?;
+        } else {
+            for mode in map.send(CancelEdits).await? {
+                if !self.edited_map_modes.contains(&mode) {
+                    self.edited_map_modes.push(mode);
+                }
+            }
+        }
+        self.preview_mode = None;
+        Ok(())
+    }
+
+    /// Undoes the last edit on Ctrl+Z, and redoes it on Ctrl+Y or Ctrl+Shift+Z
+    async fn handle_history_keys(&mut self, ctx: &Context) -> Result<(), MapError> {
+        let map = match &self.map {
+            Some(m) if !ctx.wants_keyboard_input() => m,
+            _ => return Ok(()),
+        };
+        let (undo, redo) = {
+            let input = ctx.input();
+            let command = input.modifiers.command;
+            let z = command && input.key_pressed(Key::Z);
+            (
+                z && !input.modifiers.shift,
+                (z && input.modifiers.shift) || (command && input.key_pressed(Key::Y)),
+            )
+        };
+        let result = if undo {
+            map.send(Undo).await?
+        } else if redo {
+            map.send(Redo).await?
+        } else {
+            return Ok(());
+        };
+        match result {
+            Ok(modes) => {
+                for mode in modes {
+                    if !self.edited_map_modes.contains(&mode) {
+                        self.edited_map_modes.push(mode);
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to undo or redo the last edit: {e}"),
+        }
+        Ok(())
+    }
+
+    /// Draws the layer highlighting the pixels changed by the pending edits in the given mode,
+    /// unless it is already drawn.  The preview is only drawn again once a stroke has finished,
+    /// as comparing the whole image on every step of a stroke would slow painting down.
+    async fn refresh_preview(
+        &mut self,
+        ctx: &Context,
+        map_mode: MapDisplayMode,
+    ) -> Result<(), MapError> {
+        if self.preview_mode == Some(map_mode) || self.last_painted_point.is_some() {
+            return Ok(());
+        }
+        let map = match &self.map {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let layer = map.send(GetEditPreview::new(map_mode)).await?;
+        self.preview_texture = layer.map(|image| {
+            let color_image = texture_image(&image, ctx.input().max_texture_side);
+            ctx.load_texture("edit_preview", color_image, TextureFilter::Nearest)
+        });
+        self.preview_mode = Some(map_mode);
+        Ok(())
+    }
+
+    /// Draws the layer highlighting the region selected in the given mode, unless the selected
+    /// provinces are the same as when it was last drawn
+    async fn refresh_highlight(
+        &mut self,
+        ctx: &Context,
+        map_mode: MapDisplayMode,
+    ) -> Result<(), MapError> {
+        let selected = self.selected_provinces(map_mode).await?;
+        if selected == self.highlighted {
+            return Ok(());
+        }
+        self.highlight_texture = match &self.map {
+            Some(map) if !selected.is_empty() => {
+                let image = map
+                    .send(GetSelectionHighlight::new(selected.clone()))
+                    .await?;
+                let color_image = texture_image(&image, ctx.input().max_texture_side);
+                Some(ctx.load_texture("selection_highlight", color_image, TextureFilter::Nearest))
+            }
+            _ => None,
+        };
+        self.highlighted = selected;
+        Ok(())
+    }
+
+    /// The provinces of the region selected in the given mode, which is a province, a state or a
+    /// strategic region depending on the regions the mode shows
+    async fn selected_provinces(
+        &self,
+        map_mode: MapDisplayMode,
+    ) -> Result<HashSet<ProvinceId>, MapError> {
+        let provinces = match map_mode {
+            MapDisplayMode::HeightMap
+            | MapDisplayMode::Elevation
+            | MapDisplayMode::Terrain
+            | MapDisplayMode::Rivers
+            | MapDisplayMode::Trees
+            | MapDisplayMode::NormalMap
+            | MapDisplayMode::Cities => None,
+            MapDisplayMode::Provinces
+            | MapDisplayMode::Continents
+            | MapDisplayMode::DefinitionTerrain => {
+                let province: Option<Definition> = self.selection.send(GetSelectedProvince).await?;
+                province.map(|d| HashSet::from([d.id]))
+            }
+            MapDisplayMode::StrategicRegions | MapDisplayMode::Weather => {
+                let region: Option<StrategicRegion> =
+                    self.selection.send(GetSelectedStrategicRegion).await?;
+                region.map(|r| r.provinces)
+            }
+            MapDisplayMode::States
+            | MapDisplayMode::BuildingDensity
+            | MapDisplayMode::Manpower
+            | MapDisplayMode::Political
+            | MapDisplayMode::StateCategories => {
+                let state: Option<State> = self.selection.send(GetSelectedState).await?;
+                state.map(|s| s.provinces)
+            }
+        };
+        Ok(provinces.unwrap_or_default())
+    }
+
+    /// Reloads the textures of any images that have been edited, once the previous load of the
+    /// texture has completed.
+    async fn refresh_edited_textures(&mut self, ctx: &Context) -> Result<(), MapError> {
+        let map = match &self.map {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let mut still_edited = Vec::new();
+        for mode in self.edited_map_modes.drain(..) {
+            if self.map_textures.send(IsTextureLoading(mode)).await? {
+                still_edited.push(mode);
+                continue;
+            }
+            if let Some(image) = map.send(GetMapImage::from(mode)).await? {
+                self.map_textures
+                    .do_send(LoadImage::from_display_mode(mode, image, ctx.clone()));
+            }
+        }
+        self.edited_map_modes = still_edited;
         Ok(())
     }
 }
 
+/// Paints the preview of the pending edits over the map.  Returns `Some(true)` if the edits
+/// should be applied and `Some(false)` if they should be cancelled.
+fn render_edit_preview(
+    ui: &mut Ui,
+    preview: &TextureHandle,
+    map_rect: Rect,
+    viewport: Rect,
+) -> Option<bool> {
+    paint_wrapped_image(ui, preview.id(), map_rect, viewport, Color32::WHITE);
+    ui.label("Pending edits: press Enter to apply or Escape to cancel");
+    if ui.ctx().wants_keyboard_input() {
+        return None;
+    }
+    let input = ui.input();
+    if input.key_pressed(Key::Enter) {
+        Some(true)
+    } else if input.key_pressed(Key::Escape) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Whether a tool can be used in the given map mode
+fn tool_applies_to(tool: Tool, map_mode: MapDisplayMode, registry: &PluginRegistry) -> bool {
+    match tool {
+        Tool::HeightBrush(_) => matches!(map_mode, MapDisplayMode::HeightMap),
+        Tool::RiverBrush(_) => matches!(map_mode, MapDisplayMode::Rivers),
+        Tool::TerrainBrush(_) => matches!(map_mode, MapDisplayMode::Terrain),
+        Tool::VictoryPoints(_) => {
+            matches!(map_mode, MapDisplayMode::Provinces | MapDisplayMode::States)
+        }
+        Tool::Plugin(index) => registry
+            .tools()
+            .get(index)
+            .map_or(false, |t| t.applies_to(map_mode)),
+    }
+}
+
 fn handle_scroll(ui: &mut Ui, viewport: &Addr<Viewport>) -> f32 {
     let scroll = ui.input().scroll_delta.y;
     viewport.do_send(Scroll(scroll));
@@ -113,6 +504,7 @@ fn handle_zoom(
     viewport: &Addr<Viewport>,
     zoom_level: Option<f32>,
     mut viewport_rect: Rect,
+    wrapping: Wrapping,
     scroll: f32,
 ) {
     let mut zoomed_viewport = Rect::from_min_max(
@@ -132,10 +524,9 @@ fn handle_zoom(
     let translate = viewport_center - zoomed_viewport_center;
 
     if translate.length() > 0.00001 {
-        zoomed_viewport.max =
-            (zoomed_viewport.max + translate).clamp(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0));
-        zoomed_viewport.min =
-            (zoomed_viewport.min + translate).clamp(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0));
+        let (min, max) = viewport_bounds(wrapping);
+        zoomed_viewport.max = (zoomed_viewport.max + translate).clamp(min, max);
+        zoomed_viewport.min = (zoomed_viewport.min + translate).clamp(min, max);
     }
     if scroll != 0.0 {
         viewport_rect = zoomed_viewport;
@@ -147,6 +538,7 @@ fn handle_drag(
     viewport: &Addr<Viewport>,
     zoom_level: Option<f32>,
     mut viewport_rect: Rect,
+    wrapping: Wrapping,
     map: &Response,
 ) {
     let map_rect = map.rect;
@@ -154,11 +546,10 @@ fn handle_drag(
     map_drag.x = map_drag.x / map_rect.width() * zoom_level.map_or(1.0, |z| 1.0 - z);
     map_drag.y = map_drag.y / map_rect.height() * zoom_level.map_or(1.0, |z| 1.0 - z);
     if map_drag.x != 0.0 || map_drag.y != 0.0 {
-        let new_min =
-            (viewport_rect.min - map_drag).clamp(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0));
+        let (min, max) = viewport_bounds(wrapping);
+        let new_min = (viewport_rect.min - map_drag).clamp(min, max);
 
-        let new_max =
-            (viewport_rect.max - map_drag).clamp(Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0));
+        let new_max = (viewport_rect.max - map_drag).clamp(min, max);
 
         let new_rect = Rect::from_min_max(new_min, new_max);
 
@@ -171,6 +562,55 @@ fn handle_drag(
     }
 }
 
+/// The bounds of the viewport while panning and zooming.  The viewport can move freely across a
+/// horizontally wrapping map, as the viewport actor moves it back onto the map.
+fn viewport_bounds(wrapping: Wrapping) -> (Pos2, Pos2) {
+    if wrapping.wraps_horizontally() {
+        (
+            Pos2::new(f32::NEG_INFINITY, 0.0),
+            Pos2::new(f32::INFINITY, 1.0),
+        )
+    } else {
+        (Pos2::new(0.0, 0.0), Pos2::new(1.0, 1.0))
+    }
+}
+
+/// Paints the part of a texture within the viewport, multiplied by a tint, splitting it in two
+/// where the viewport runs past the right edge of a wrapping map.
+fn paint_wrapped_image(
+    ui: &Ui,
+    texture_id: TextureId,
+    map_rect: Rect,
+    viewport: Rect,
+    tint: Color32,
+) {
+    if viewport.max.x <= 1.0 {
+        ui.painter()
+            .add(Shape::image(texture_id, map_rect, viewport, tint));
+        return;
+    }
+    let split = (1.0 - viewport.min.x) / viewport.width();
+    let split_x = map_rect.width().mul_add(split, map_rect.min.x);
+    let right_uv = Rect::from_x_y_ranges(viewport.min.x..=1.0, viewport.y_range());
+    let left_uv = Rect::from_x_y_ranges(0.0..=viewport.max.x - 1.0, viewport.y_range());
+    let right = Rect::from_x_y_ranges(map_rect.min.x..=split_x, map_rect.y_range());
+    let left = Rect::from_x_y_ranges(split_x..=map_rect.max.x, map_rect.y_range());
+    ui.painter()
+        .add(Shape::image(texture_id, right, right_uv, tint));
+    ui.painter()
+        .add(Shape::image(texture_id, left, left_uv, tint));
+}
+
+/// Composites the enabled overlays over the map in the order they were enabled, fading each by
+/// its opacity.  Fully transparent layers are skipped rather than drawn.
+fn paint_overlays(ui: &Ui, layers: &[LayerTexture], map_rect: Rect, viewport: Rect) {
+    for layer in layers.iter().filter(|l| l.opacity > 0.0) {
+        // The textures are premultiplied, so fading every channel fades the layer evenly
+        let tint = Color32::WHITE.linear_multiply(layer.opacity);
+        paint_wrapped_image(ui, layer.texture.id(), map_rect, viewport, tint);
+    }
+}
+
 /// Projects a position from the UI space to the texture space.
 #[allow(clippy::similar_names)]
 fn project_to_texture(viewport: &Rect, tex_size: Vec2, pos: Pos2, map_rect: &Rect) -> Pos2 {
@@ -189,7 +629,13 @@ fn project_to_texture(viewport: &Rect, tex_size: Vec2, pos: Pos2, map_rect: &Rec
     let viewport_v = viewport_map_v_scale * map_rect_uv.y;
 
     // Project viewport uv to texture uv
-    let tex_u = viewport.min.x.mul_add(tex_size.x, viewport_u).round();
+    // A viewport running past the right edge of a wrapping map continues on the left side
+    let tex_u = viewport
+        .min
+        .x
+        .mul_add(tex_size.x, viewport_u)
+        .round()
+        .rem_euclid(tex_size.x.max(1.0));
     let tex_v = viewport.min.y.mul_add(tex_size.y, viewport_v).round();
     Pos2::new(tex_u, tex_v)
 }
@@ -1,16 +1,50 @@
-use crate::ui::map_loader::{GetMap, IsMapLoading, LoadMap, MapLoader};
+use crate::ui::active_tool::{ActiveTool, GetActiveTool, SetActiveTool, Tool};
+use crate::ui::busy::{Begin, Busy, End, Operation};
+use crate::ui::map_loader::{
+    CancelLoad, GetLoadProgress, GetMap, IsMapLoading, LoadMap, MapLoader,
+};
 use crate::ui::map_mode::{GetMapMode, SetMapMode};
-use crate::ui::map_textures::{GetTexture, LoadImage};
+use crate::ui::map_textures::{GetTexture, IsTextureLoading, LoadImage, TexturePyramid};
+use crate::ui::plugins::{
+    DisableLayer, EnableLayer, GetEnabledLayers, GetLayerOpacities, GetPluginRegistry, Plugins,
+    RefreshLayers, SetLayerOpacity,
+};
 use crate::ui::root_path::GetRootPath;
-use crate::{MapError, MapMode, MapTextures, RootPath};
+use crate::ui::viewport::SetViewportWrapping;
+use crate::{MapError, MapMode, MapTextures, RootPath, Viewport};
 use actix::Addr;
-use eframe::epaint::TextureHandle;
-use egui::{Context, TopBottomPanel, Ui};
+use egui::{
+    Color32, ComboBox, Context, DragValue, ProgressBar, RichText, ScrollArea, Slider,
+    TopBottomPanel, Ui,
+};
+use image::Rgb;
 use indicatif::InMemoryTerm;
 use log::{debug, error, trace};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::try_join;
-use world_gen::map::{GetMapImage, Map};
+use world_gen::brush::{HeightBrushMode, RiverBrushMode, TerrainBrush};
+use world_gen::building_density::{BuildingDensity, DensityScope};
+use world_gen::components::day_month::DAYS_IN_YEAR;
+use world_gen::components::river::RIVER_WIDTHS;
+use world_gen::components::wrappers::{BuildingId, VictoryPoints};
+use world_gen::generation::lakes::LakeClassifier;
+use world_gen::generation::terrain::TERRAIN_PALETTE;
+use world_gen::map::{
+    ClassifyLakes, FindStraits, FixXCrossings, GetBuildingDensity, GetBuildingTypes,
+    GetElevationSeaLevel, GetMapImage, GetRiverIssues, GetStraitCandidates, GetWeatherView,
+    GetWrapping, Map, RecolorProvinces, ReviewStrait, SetBuildingDensity, SetElevationSeaLevel,
+    SetWeatherView, SetWrapping, SyncTerrain, VerifyRivers,
+};
+use world_gen::plugin::PluginRegistry;
+use world_gen::progress::{LoadProgress, LoadState};
+use world_gen::recolor::ColorScheme;
+use world_gen::topology::Wrapping;
+use world_gen::validation::province_heights::SEA_LEVEL;
+use world_gen::validation::rivers::RiverIssue;
+use world_gen::validation::straits::{StraitCandidate, StraitFinder};
+use world_gen::weather_map::{WeatherMeasure, WeatherView};
 use world_gen::MapDisplayMode;
 
 pub struct ControlPanelRenderer {
@@ -18,22 +52,54 @@ pub struct ControlPanelRenderer {
     map_loader: Addr<MapLoader>,
     map_mode: Addr<MapMode>,
     map_textures: Addr<MapTextures>,
+    active_tool: Addr<ActiveTool>,
+    plugins: Addr<Plugins>,
+    viewport: Addr<Viewport>,
+    busy: Addr<Busy>,
     terminal: InMemoryTerm,
 }
 
 struct TextureHandles {
-    heightmap: Option<TextureHandle>,
-    terrain: Option<TextureHandle>,
-    rivers: Option<TextureHandle>,
-    provinces: Option<TextureHandle>,
-    states: Option<TextureHandle>,
-    strategic_regions: Option<TextureHandle>,
+    heightmap: Option<TexturePyramid>,
+    terrain: Option<TexturePyramid>,
+    rivers: Option<TexturePyramid>,
+    provinces: Option<TexturePyramid>,
+    states: Option<TexturePyramid>,
+    strategic_regions: Option<TexturePyramid>,
+    continents: Option<TexturePyramid>,
+    definition_terrain: Option<TexturePyramid>,
+    elevation: Option<TexturePyramid>,
+    trees: Option<TexturePyramid>,
+    normal_map: Option<TexturePyramid>,
+    cities: Option<TexturePyramid>,
+    building_density: Option<TexturePyramid>,
+    weather: Option<TexturePyramid>,
+    manpower: Option<TexturePyramid>,
+    political: Option<TexturePyramid>,
+    state_categories: Option<TexturePyramid>,
+}
+
+/// What was requested from the root directory controls this frame
+enum RootDirectoryAction {
+    /// Loading the map in the root directory
+    Load(PathBuf),
+    /// Canceling the map being loaded
+    CancelLoad,
+}
+
+/// The actions requested from the province controls this frame
+#[derive(Default)]
+struct ProvinceActions {
+    classify_lakes: bool,
+    find_straits: bool,
+    fix_x_crossings: bool,
+    recolor: Option<ColorScheme>,
 }
 
 impl TextureHandles {
     #[allow(clippy::integer_arithmetic)]
     pub async fn new(map_textures: &Addr<MapTextures>) -> Result<Self, MapError> {
-        // The type for these are Option<TextureHandle>
+        // The type for these are Option<TexturePyramid>
         let (
             heightmap_texture,
             terrain_texture,
@@ -41,13 +107,35 @@ impl TextureHandles {
             provinces_texture,
             states_texture,
             strategic_regions_texture,
+            continents_texture,
+            definition_terrain_texture,
+            elevation_texture,
+            trees_texture,
+            normal_map_texture,
+            cities_texture,
+            building_density_texture,
+            weather_texture,
+            manpower_texture,
+            political_texture,
+            state_categories_texture,
         ) = try_join!(
             map_textures.send(GetTexture::HeightMap),
             map_textures.send(GetTexture::Terrain),
             map_textures.send(GetTexture::Rivers),
             map_textures.send(GetTexture::Provinces),
             map_textures.send(GetTexture::States),
-            map_textures.send(GetTexture::StrategicRegions)
+            map_textures.send(GetTexture::StrategicRegions),
+            map_textures.send(GetTexture::Continents),
+            map_textures.send(GetTexture::DefinitionTerrain),
+            map_textures.send(GetTexture::Elevation),
+            map_textures.send(GetTexture::Trees),
+            map_textures.send(GetTexture::NormalMap),
+            map_textures.send(GetTexture::Cities),
+            map_textures.send(GetTexture::BuildingDensity),
+            map_textures.send(GetTexture::Weather),
+            map_textures.send(GetTexture::Manpower),
+            map_textures.send(GetTexture::Political),
+            map_textures.send(GetTexture::StateCategories)
         )?;
 
         Ok(Self {
@@ -57,17 +145,48 @@ impl TextureHandles {
             provinces: provinces_texture,
             states: states_texture,
             strategic_regions: strategic_regions_texture,
+            continents: continents_texture,
+            definition_terrain: definition_terrain_texture,
+            elevation: elevation_texture,
+            trees: trees_texture,
+            normal_map: normal_map_texture,
+            cities: cities_texture,
+            building_density: building_density_texture,
+            weather: weather_texture,
+            manpower: manpower_texture,
+            political: political_texture,
+            state_categories: state_categories_texture,
         })
     }
+
+    /// The texture of a display mode whose image is only loaded once it is first shown
+    const fn deferred(&self, mode: MapDisplayMode) -> Option<&TexturePyramid> {
+        match mode {
+            MapDisplayMode::Trees => self.trees.as_ref(),
+            MapDisplayMode::NormalMap => self.normal_map.as_ref(),
+            MapDisplayMode::Cities => self.cities.as_ref(),
+            MapDisplayMode::BuildingDensity => self.building_density.as_ref(),
+            MapDisplayMode::Weather => self.weather.as_ref(),
+            MapDisplayMode::Manpower => self.manpower.as_ref(),
+            MapDisplayMode::Political => self.political.as_ref(),
+            MapDisplayMode::StateCategories => self.state_categories.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 impl ControlPanelRenderer {
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub const fn new(
         root_path: Addr<RootPath>,
         map_loader: Addr<MapLoader>,
         map_mode: Addr<MapMode>,
         map_textures: Addr<MapTextures>,
+        active_tool: Addr<ActiveTool>,
+        plugins: Addr<Plugins>,
+        viewport: Addr<Viewport>,
+        busy: Addr<Busy>,
         terminal: InMemoryTerm,
     ) -> Self {
         Self {
@@ -75,6 +194,10 @@ impl ControlPanelRenderer {
             map_loader,
             map_mode,
             map_textures,
+            active_tool,
+            plugins,
+            viewport,
+            busy,
             terminal,
         }
     }
@@ -85,13 +208,61 @@ impl ControlPanelRenderer {
         let root_path: Option<PathBuf> = self.root_path.send(GetRootPath).await?;
         let map: Option<Addr<Map>> = self.map_loader.send(GetMap).await?;
         let map_mode: MapDisplayMode = self.map_mode.send(GetMapMode).await?;
+        let active_tool: Option<Tool> = self.active_tool.send(GetActiveTool).await?;
+        let registry: Arc<PluginRegistry> = self.plugins.send(GetPluginRegistry).await?;
+        let enabled_layers: Vec<usize> = self.plugins.send(GetEnabledLayers).await?;
+        let layer_opacities: HashMap<usize, f32> = self.plugins.send(GetLayerOpacities).await?;
 
         let texture_handles = TextureHandles::new(&self.map_textures).await?;
         let is_map_loading = self.map_loader.send(IsMapLoading).await?;
+        let load_progress: Vec<LoadProgress> = if is_map_loading {
+            self.map_loader.send(GetLoadProgress).await?
+        } else {
+            Vec::new()
+        };
+        let river_issues: Vec<RiverIssue> = match (&map, map_mode) {
+            (Some(m), MapDisplayMode::Rivers) => m.send(GetRiverIssues).await?,
+            _ => Vec::new(),
+        };
+        let straits: Vec<StraitCandidate> = match (&map, map_mode) {
+            (Some(m), MapDisplayMode::Provinces) => m.send(GetStraitCandidates).await?,
+            _ => Vec::new(),
+        };
+        let wrapping: Wrapping = match &map {
+            Some(m) => m.send(GetWrapping).await?,
+            None => Wrapping::None,
+        };
+        let (density, building_types): (BuildingDensity, Vec<BuildingId>) = match (&map, map_mode) {
+            (Some(m), MapDisplayMode::BuildingDensity) => (
+                m.send(GetBuildingDensity).await?,
+                m.send(GetBuildingTypes).await?,
+            ),
+            _ => (BuildingDensity::default(), Vec::new()),
+        };
+        let weather_view: WeatherView = match (&map, map_mode) {
+            (Some(m), MapDisplayMode::Weather) => m.send(GetWeatherView).await?,
+            _ => WeatherView::default(),
+        };
+        let sea_level: u8 = match (&map, map_mode) {
+            (Some(m), MapDisplayMode::Elevation) => m.send(GetElevationSeaLevel).await?,
+            _ => SEA_LEVEL,
+        };
         self.load_textures(ctx, &map, &texture_handles, is_map_loading)
             .await?;
+        let mut province_actions = ProvinceActions::default();
+        let mut terrain_sync = None;
+        let mut reviews = Vec::new();
+        let mut new_wrapping = wrapping;
+        let mut root_action = None;
+        let mut new_sea_level = None;
+        let mut reload_elevation = false;
+        let mut new_density = None;
+        let mut new_weather_view = None;
+        let mut reload_weather = false;
+        let mut reload_state_map = None;
         TopBottomPanel::top("control_panel").show(ctx, |ui| {
-            self.render_root_directory(root_path, &map, is_map_loading, ui);
+            root_action =
+                Self::render_root_directory(root_path, &map, is_map_loading, &load_progress, ui);
             if map.is_some() {
                 ui.horizontal(|ui| {
                     self.render_map_button(
@@ -101,6 +272,14 @@ impl ControlPanelRenderer {
                         &texture_handles.heightmap,
                         ui,
                     );
+                    // The heightmap may have been sculpted since the elevation map was tinted
+                    reload_elevation = self.render_map_button(
+                        map_mode,
+                        MapDisplayMode::Elevation,
+                        "Elevation",
+                        &texture_handles.elevation,
+                        ui,
+                    ) && map_mode != MapDisplayMode::Elevation;
                     self.render_map_button(
                         map_mode,
                         MapDisplayMode::Terrain,
@@ -108,6 +287,13 @@ impl ControlPanelRenderer {
                         &texture_handles.terrain,
                         ui,
                     );
+                    self.render_map_button(
+                        map_mode,
+                        MapDisplayMode::DefinitionTerrain,
+                        "Province Terrain",
+                        &texture_handles.definition_terrain,
+                        ui,
+                    );
                     self.render_map_button(
                         map_mode,
                         MapDisplayMode::Rivers,
@@ -136,17 +322,265 @@ impl ControlPanelRenderer {
                         &texture_handles.strategic_regions,
                         ui,
                     );
+                    self.render_map_button(
+                        map_mode,
+                        MapDisplayMode::Continents,
+                        "Continents",
+                        &texture_handles.continents,
+                        ui,
+                    );
+                    ui.separator();
+                    for (mode, text) in [
+                        (MapDisplayMode::Trees, "Trees"),
+                        (MapDisplayMode::NormalMap, "Normal Map"),
+                        (MapDisplayMode::Cities, "Cities"),
+                        (MapDisplayMode::BuildingDensity, "Buildings"),
+                        (MapDisplayMode::Weather, "Weather"),
+                        (MapDisplayMode::Manpower, "Manpower"),
+                        (MapDisplayMode::Political, "Political"),
+                        (MapDisplayMode::StateCategories, "Categories"),
+                    ] {
+                        let clicked = self.render_deferred_map_button(map_mode, mode, text, ui);
+                        // The states may have been edited or reloaded since the map was drawn
+                        let drawn_from_states = matches!(
+                            mode,
+                            MapDisplayMode::Manpower
+                                | MapDisplayMode::Political
+                                | MapDisplayMode::StateCategories
+                        );
+                        if clicked
+                            && drawn_from_states
+                            && map_mode != mode
+                            && texture_handles.deferred(mode).is_some()
+                        {
+                            reload_state_map = Some(mode);
+                        }
+                    }
+                    ui.separator();
+                    let mut wraps = wrapping.wraps_horizontally();
+                    ui.checkbox(&mut wraps, "Wrap horizontally")
+                        .on_hover_text("Connect the left and right edges of the map");
+                    new_wrapping = if wraps {
+                        Wrapping::Horizontal
+                    } else {
+                        Wrapping::None
+                    };
                 });
                 ui.horizontal(|ui| match map_mode {
-                    MapDisplayMode::HeightMap => {}
-                    MapDisplayMode::Terrain => {}
-                    MapDisplayMode::Provinces => if ui.button("Edit").clicked() {},
-                    MapDisplayMode::Rivers => {}
-                    MapDisplayMode::StrategicRegions => {}
-                    MapDisplayMode::States => {}
+                    MapDisplayMode::HeightMap => {
+                        self.render_height_brush_controls(active_tool, ui);
+                    }
+                    MapDisplayMode::Terrain => {
+                        self.render_terrain_brush_controls(active_tool, ui);
+                        terrain_sync = Self::render_terrain_sync(ui);
+                    }
+                    MapDisplayMode::Provinces => {
+                        province_actions = Self::render_province_controls(ui);
+                        ui.separator();
+                        self.render_victory_point_controls(active_tool, ui);
+                    }
+                    MapDisplayMode::Rivers => {
+                        self.render_river_brush_controls(active_tool, &map, &river_issues, ui);
+                    }
+                    MapDisplayMode::StrategicRegions
+                    | MapDisplayMode::Continents
+                    | MapDisplayMode::DefinitionTerrain
+                    | MapDisplayMode::Trees
+                    | MapDisplayMode::NormalMap
+                    | MapDisplayMode::Cities
+                    | MapDisplayMode::Manpower
+                    | MapDisplayMode::Political
+                    | MapDisplayMode::StateCategories => {}
+                    MapDisplayMode::BuildingDensity => {
+                        new_density =
+                            Self::render_building_density_controls(&density, &building_types, ui);
+                    }
+                    MapDisplayMode::Weather => {
+                        (new_weather_view, reload_weather) =
+                            Self::render_weather_controls(weather_view, ui);
+                    }
+                    MapDisplayMode::Elevation => {
+                        (new_sea_level, reload_elevation) =
+                            Self::render_sea_level_controls(sea_level, ui);
+                    }
+                    MapDisplayMode::States => {
+                        self.render_victory_point_controls(active_tool, ui);
+                    }
                 });
+                if !straits.is_empty() {
+                    reviews = Self::render_strait_candidates(&straits, ui);
+                }
+                if let Some(m) = &map {
+                    self.render_plugins(
+                        ctx,
+                        m,
+                        &registry,
+                        &enabled_layers,
+                        &layer_opacities,
+                        active_tool,
+                        ui,
+                    );
+                }
             }
         });
+        match root_action {
+            Some(RootDirectoryAction::Load(p)) => self.load_map(p).await?,
+            Some(RootDirectoryAction::CancelLoad) => {
+                self.map_loader.send(CancelLoad).await?;
+            }
+            None => {}
+        }
+        if let (true, Some(m)) = (new_wrapping != wrapping, &map) {
+            m.send(SetWrapping::new(new_wrapping)).await?;
+        }
+        self.viewport.do_send(SetViewportWrapping(new_wrapping));
+        if let Some(m) = &map {
+            if let Some(level) = new_sea_level {
+                m.send(SetElevationSeaLevel::new(level)).await?;
+            }
+            if let (
+                MapDisplayMode::Trees
+                | MapDisplayMode::NormalMap
+                | MapDisplayMode::Cities
+                | MapDisplayMode::BuildingDensity
+                | MapDisplayMode::Weather
+                | MapDisplayMode::Manpower
+                | MapDisplayMode::Political
+                | MapDisplayMode::StateCategories,
+                None,
+            ) = (map_mode, texture_handles.deferred(map_mode))
+            {
+                self.load_deferred_texture(ctx, m, map_mode).await?;
+            }
+            if let Some(density) = new_density {
+                m.send(SetBuildingDensity::new(density)).await?;
+                if let Some(image) = m.send(GetMapImage::BuildingDensity).await? {
+                    self.map_textures.do_send(LoadImage::BuildingDensity {
+                        image,
+                        context: ctx.clone(),
+                    });
+                }
+            }
+            if let Some(view) = new_weather_view {
+                m.send(SetWeatherView::new(view)).await?;
+            }
+            if reload_weather {
+                if let Some(image) = m.send(GetMapImage::Weather).await? {
+                    self.map_textures.do_send(LoadImage::Weather {
+                        image,
+                        context: ctx.clone(),
+                    });
+                }
+            }
+            if let Some(mode) = reload_state_map {
+                if let Some(image) = m.send(GetMapImage::from(mode)).await? {
+                    self.map_textures.do_send(LoadImage::from_display_mode(
+                        mode,
+                        image,
+                        ctx.clone(),
+                    ));
+                }
+            }
+            if reload_elevation {
+                if let Some(image) = m.send(GetMapImage::Elevation).await? {
+                    self.map_textures.do_send(LoadImage::Elevation {
+                        image,
+                        context: ctx.clone(),
+                    });
+                }
+            }
+            self.apply_province_actions(ctx, m, province_actions, reviews)
+                .await?;
+            if let Some(dither) = terrain_sync {
+                self.sync_terrain(ctx, m, dither).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn render_province_controls(ui: &mut Ui) -> ProvinceActions {
+        if ui.button("Edit").clicked() {}
+        let classify_lakes = ui
+            .button("Detect Lakes")
+            .on_hover_text("Set enclosed water provinces to lakes, and the rest to sea")
+            .clicked();
+        let find_straits = ui
+            .button("Find Straits")
+            .on_hover_text(
+                "Suggest sea adjacencies between land provinces separated by a narrow stretch of \
+                 sea",
+            )
+            .clicked();
+        let fix_x_crossings = ui
+            .button("Fix X Crossings")
+            .on_hover_text(
+                "Give a pixel of every corner where four provinces meet to a neighboring province",
+            )
+            .clicked();
+        let mut recolor = None;
+        ui.menu_button("Recolor", |ui| {
+            for (scheme, label) in [
+                (ColorScheme::Continent, "By continent"),
+                (ColorScheme::ProvinceId, "By province id"),
+            ] {
+                if ui.button(label).clicked() {
+                    recolor = Some(scheme);
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Give every province a color that encodes where it belongs");
+        ProvinceActions {
+            classify_lakes,
+            find_straits,
+            fix_x_crossings,
+            recolor,
+        }
+    }
+
+    /// Carries out the actions requested from the province controls and the strait reviews
+    async fn apply_province_actions(
+        &self,
+        ctx: &Context,
+        map: &Addr<Map>,
+        actions: ProvinceActions,
+        reviews: Vec<ReviewStrait>,
+    ) -> Result<(), MapError> {
+        let mut refresh_layers = actions.classify_lakes;
+        if actions.classify_lakes {
+            map.send(ClassifyLakes::new(LakeClassifier::default()))
+                .await?;
+        }
+        let mut reload_provinces = false;
+        if actions.fix_x_crossings {
+            reload_provinces = map.send(FixXCrossings).await? > 0;
+        }
+        if let Some(scheme) = actions.recolor {
+            match map.send(RecolorProvinces::new(scheme)).await? {
+                Ok(()) => reload_provinces = true,
+                Err(e) => error!("Failed to recolor provinces: {e}"),
+            }
+        }
+        if reload_provinces {
+            if let Some(image) = map.send(GetMapImage::Provinces).await? {
+                self.map_textures.do_send(LoadImage::Provinces {
+                    image,
+                    context: ctx.clone(),
+                });
+            }
+            refresh_layers = true;
+        }
+        if refresh_layers {
+            self.plugins
+                .do_send(RefreshLayers::new(map.clone(), ctx.clone()));
+        }
+        if actions.find_straits {
+            map.send(FindStraits::new(StraitFinder::default())).await?;
+        }
+        for review in reviews {
+            map.send(review).await?;
+        }
         Ok(())
     }
 
@@ -155,47 +589,441 @@ impl ControlPanelRenderer {
         current_map_mode: MapDisplayMode,
         button_map_mode: MapDisplayMode,
         button_text: &str,
-        texture_handle: &Option<TextureHandle>,
+        texture_handle: &Option<TexturePyramid>,
         ui: &mut Ui,
-    ) {
+    ) -> bool {
         if texture_handle.is_some() {
-            if ui
+            let clicked = ui
                 .selectable_label(current_map_mode == button_map_mode, button_text)
-                .clicked()
-            {
+                .clicked();
+            if clicked {
                 self.map_mode.do_send(SetMapMode::new(button_map_mode));
             }
+            clicked
         } else {
             ui.spinner();
+            false
         }
     }
 
-    fn render_root_directory(
+    /// Renders the button of a display mode whose image is only loaded once it is first shown,
+    /// returning whether it was clicked.  Unlike the other buttons it can be clicked before the
+    /// texture exists, as showing the mode is what loads the image.
+    fn render_deferred_map_button(
+        &self,
+        current_map_mode: MapDisplayMode,
+        button_map_mode: MapDisplayMode,
+        button_text: &str,
+        ui: &mut Ui,
+    ) -> bool {
+        let clicked = ui
+            .selectable_label(current_map_mode == button_map_mode, button_text)
+            .clicked();
+        if clicked {
+            self.map_mode.do_send(SetMapMode::new(button_map_mode));
+        }
+        clicked
+    }
+
+    /// Loads the texture of a display mode whose image is loaded when first asked for, unless it
+    /// is already loading
+    async fn load_deferred_texture(
+        &self,
+        ctx: &Context,
+        map: &Addr<Map>,
+        mode: MapDisplayMode,
+    ) -> Result<(), MapError> {
+        if self.map_textures.send(IsTextureLoading(mode)).await? {
+            return Ok(());
+        }
+        if let Some(image) = map.send(GetMapImage::from(mode)).await? {
+            self.map_textures
+                .do_send(LoadImage::from_display_mode(mode, image, ctx.clone()));
+        }
+        Ok(())
+    }
+
+    /// Renders the choice of the buildings the heat map counts and what it counts them by,
+    /// returning the new settings if they were changed
+    fn render_building_density_controls(
+        density: &BuildingDensity,
+        building_types: &[BuildingId],
+        ui: &mut Ui,
+    ) -> Option<BuildingDensity> {
+        let mut changed = density.clone();
+        for scope in [DensityScope::State, DensityScope::Province] {
+            ui.selectable_value(&mut changed.scope, scope, scope.to_string());
+        }
+        ComboBox::from_label("Building")
+            .selected_text(
+                changed
+                    .building
+                    .as_ref()
+                    .map_or_else(|| "All buildings".to_owned(), ToString::to_string),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut changed.building, None, "All buildings");
+                for building in building_types {
+                    ui.selectable_value(
+                        &mut changed.building,
+                        Some(building.clone()),
+                        building.to_string(),
+                    );
+                }
+            });
+        (changed != *density).then_some(changed)
+    }
+
+    /// Renders the day and measure of the weather map, returning the new view if it was changed
+    /// and whether to draw the map again.  Like the sea level, the map is only drawn again once
+    /// the day slider is let go.
+    fn render_weather_controls(view: WeatherView, ui: &mut Ui) -> (Option<WeatherView>, bool) {
+        let mut changed = view;
+        for measure in [WeatherMeasure::Temperature, WeatherMeasure::DominantWeather] {
+            ui.selectable_value(&mut changed.measure, measure, measure.to_string());
+        }
+        let date = view.date().to_string();
+        let response = ui
+            .add(
+                Slider::new(&mut changed.day, 0..=DAYS_IN_YEAR.saturating_sub(1))
+                    .show_value(false)
+                    .text(date),
+            )
+            .on_hover_text("Scrub through the days of the year");
+        let finished = changed.measure != view.measure
+            || response.drag_released()
+            || (response.changed() && !response.dragged());
+        ((changed != view).then_some(changed), finished)
+    }
+
+    /// Renders the slider for the sea level of the elevation map, returning the new sea level if
+    /// it was moved and whether to tint the map again.  The map is only tinted again once the
+    /// slider is let go, as tinting a large map on every step of a drag would stall the editor.
+    fn render_sea_level_controls(sea_level: u8, ui: &mut Ui) -> (Option<u8>, bool) {
+        let mut level = sea_level;
+        let response = ui
+            .add(Slider::new(&mut level, 1..=u8::MAX).text("Sea level"))
+            .on_hover_text("Shade the heights below this level as sea");
+        let moved = (level != sea_level).then_some(level);
+        let finished = response.drag_released() || (response.changed() && !response.dragged());
+        (moved, finished)
+    }
+
+    fn render_height_brush_controls(&self, active_tool: Option<Tool>, ui: &mut Ui) {
+        let current_brush = if let Some(Tool::HeightBrush(brush)) = active_tool {
+            Some(brush)
+        } else {
+            None
+        };
+        let mut sculpting = current_brush.is_some();
+        let mut brush = current_brush.unwrap_or_default();
+        ui.checkbox(&mut sculpting, "Sculpt");
+        if sculpting {
+            ui.selectable_value(&mut brush.mode, HeightBrushMode::Raise, "Raise");
+            ui.selectable_value(&mut brush.mode, HeightBrushMode::Lower, "Lower");
+            ui.selectable_value(&mut brush.mode, HeightBrushMode::Smooth, "Smooth");
+            ui.add(Slider::new(&mut brush.radius, 1..=128).text("Radius"));
+            ui.add(Slider::new(&mut brush.strength, 0.0..=1.0).text("Strength"));
+        }
+        let new_tool = sculpting.then_some(Tool::HeightBrush(brush));
+        if new_tool != active_tool {
+            self.active_tool.do_send(SetActiveTool::new(new_tool));
+        }
+    }
+
+    fn render_terrain_brush_controls(&self, active_tool: Option<Tool>, ui: &mut Ui) {
+        let current_brush = if let Some(Tool::TerrainBrush(brush)) = active_tool {
+            Some(brush)
+        } else {
+            None
+        };
+        let mut painting = current_brush.is_some();
+        let mut brush = current_brush.unwrap_or_default();
+        ui.checkbox(&mut painting, "Paint");
+        if painting {
+            ComboBox::from_label("Terrain")
+                .selected_text(terrain_swatch(brush))
+                .show_ui(ui, |ui| {
+                    for index in (0_u8..).take(TERRAIN_PALETTE.len()) {
+                        let option = TerrainBrush::new(index, brush.radius);
+                        ui.selectable_value(&mut brush.index, index, terrain_swatch(option));
+                    }
+                });
+            ui.add(Slider::new(&mut brush.radius, 1..=64).text("Radius"));
+        }
+        let new_tool = painting.then_some(Tool::TerrainBrush(brush));
+        if new_tool != active_tool {
+            self.active_tool.do_send(SetActiveTool::new(new_tool));
+        }
+    }
+
+    fn render_victory_point_controls(&self, active_tool: Option<Tool>, ui: &mut Ui) {
+        let current_points = if let Some(Tool::VictoryPoints(points)) = active_tool {
+            Some(points)
+        } else {
+            None
+        };
+        let mut placing = current_points.is_some();
+        let mut points = current_points.unwrap_or_else(|| VictoryPoints::from(1.0));
+        ui.checkbox(&mut placing, "Victory Points")
+            .on_hover_text("Click a province to set its victory points, or remove them with 0");
+        if placing {
+            ui.add(
+                DragValue::new(&mut points.0)
+                    .speed(1.0)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+        }
+        let new_tool = placing.then_some(Tool::VictoryPoints(points));
+        if new_tool != active_tool {
+            self.active_tool.do_send(SetActiveTool::new(new_tool));
+        }
+    }
+
+    /// Renders the menu to sync the terrain with the definitions, returning whether to dither the
+    /// edges of the provinces if it was clicked
+    fn render_terrain_sync(ui: &mut Ui) -> Option<bool> {
+        let mut sync = None;
+        ui.menu_button("Sync", |ui| {
+            for (dither, label) in [(false, "Sharp edges"), (true, "Dithered edges")] {
+                if ui.button(label).clicked() {
+                    sync = Some(dither);
+                    ui.close_menu();
+                }
+            }
+        })
+        .response
+        .on_hover_text("Repaint each province with the terrain of its definition");
+        sync
+    }
+
+    /// Repaints the terrain to match the definitions and reloads its texture
+    async fn sync_terrain(
+        &self,
+        ctx: &Context,
+        map: &Addr<Map>,
+        dither: bool,
+    ) -> Result<(), MapError> {
+        match map.send(SyncTerrain::new(dither)).await? {
+            Ok(_changed) => {
+                if let Some(image) = map.send(GetMapImage::Terrain).await? {
+                    self.map_textures.do_send(LoadImage::Terrain {
+                        image,
+                        context: ctx.clone(),
+                    });
+                }
+            }
+            Err(e) => error!("Failed to sync terrain: {e}"),
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::integer_arithmetic)]
+    fn render_river_brush_controls(
+        &self,
+        active_tool: Option<Tool>,
+        map: &Option<Addr<Map>>,
+        river_issues: &[RiverIssue],
+        ui: &mut Ui,
+    ) {
+        let current_brush = if let Some(Tool::RiverBrush(brush)) = active_tool {
+            Some(brush)
+        } else {
+            None
+        };
+        let mut drawing = current_brush.is_some();
+        let mut brush = current_brush.unwrap_or_default();
+        ui.checkbox(&mut drawing, "Draw");
+        if drawing {
+            ui.selectable_value(&mut brush.mode, RiverBrushMode::Draw, "River");
+            ui.selectable_value(&mut brush.mode, RiverBrushMode::Source, "Source");
+            ui.selectable_value(&mut brush.mode, RiverBrushMode::FlowIn, "Flow-in");
+            ui.selectable_value(&mut brush.mode, RiverBrushMode::FlowOut, "Flow-out");
+            ui.selectable_value(&mut brush.mode, RiverBrushMode::Erase, "Erase");
+            ui.add(Slider::new(&mut brush.width, 0..=RIVER_WIDTHS - 1).text("Width"));
+        }
+        let new_tool = drawing.then_some(Tool::RiverBrush(brush));
+        if new_tool != active_tool {
+            self.active_tool.do_send(SetActiveTool::new(new_tool));
+        }
+        if let Some(m) = map {
+            if ui.button("Verify").clicked() {
+                m.do_send(VerifyRivers);
+            }
+        }
+        if !river_issues.is_empty() {
+            let details = river_issues
+                .iter()
+                .take(20)
+                .map(|issue| format!("({}, {}): {:?}", issue.x, issue.y, issue.kind))
+                .collect::<Vec<_>>()
+                .join("\n");
+            ui.colored_label(
+                Color32::YELLOW,
+                format!("{} river issues", river_issues.len()),
+            )
+            .on_hover_text(details);
+        }
+    }
+
+    /// Lists the suggested straits, returning the ones accepted or rejected this frame
+    fn render_strait_candidates(straits: &[StraitCandidate], ui: &mut Ui) -> Vec<ReviewStrait> {
+        let mut reviews = Vec::new();
+        ui.collapsing(format!("{} suggested straits", straits.len()), |ui| {
+            ScrollArea::vertical().max_height(150.0).show(ui, |ui| {
+                for strait in straits {
+                    let adjacency = &strait.adjacency;
+                    let through = adjacency
+                        .through_province()
+                        .map_or_else(String::new, |p| format!(" through {p}"));
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "{} to {}{through}, {} pixels wide at ({}, {})",
+                            adjacency.from, adjacency.to, strait.width, strait.x, strait.y
+                        ));
+                        if ui.button("Accept").clicked() {
+                            reviews.push(ReviewStrait::new(adjacency.from, adjacency.to, true));
+                        }
+                        if ui.button("Reject").clicked() {
+                            reviews.push(ReviewStrait::new(adjacency.from, adjacency.to, false));
+                        }
+                    });
+                }
+            });
+        });
+        reviews
+    }
+
+    /// Renders the overlays drawn over the map, each with a slider for its opacity once it is
+    /// enabled, and the tools of the plugins
+    #[allow(clippy::too_many_arguments)]
+    fn render_plugins(
         &self,
+        ctx: &Context,
+        map: &Addr<Map>,
+        registry: &PluginRegistry,
+        enabled_layers: &[usize],
+        layer_opacities: &HashMap<usize, f32>,
+        active_tool: Option<Tool>,
+        ui: &mut Ui,
+    ) {
+        if !registry.layers().is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Overlays:");
+                ui.spacing_mut().slider_width = 60.0;
+                for (index, layer) in registry.layers().iter().enumerate() {
+                    let was_enabled = enabled_layers.contains(&index);
+                    let mut enabled = was_enabled;
+                    ui.checkbox(&mut enabled, layer.name());
+                    if enabled && !was_enabled {
+                        self.plugins
+                            .do_send(EnableLayer::new(index, map.clone(), ctx.clone()));
+                    } else if !enabled && was_enabled {
+                        self.plugins.do_send(DisableLayer(index));
+                    }
+                    if let (true, Some(opacity)) = (was_enabled, layer_opacities.get(&index)) {
+                        let mut new_opacity = *opacity;
+                        ui.add(Slider::new(&mut new_opacity, 0.0..=1.0).show_value(false))
+                            .on_hover_text("Opacity");
+                        if (new_opacity - opacity).abs() > f32::EPSILON {
+                            self.plugins
+                                .do_send(SetLayerOpacity::new(index, new_opacity));
+                        }
+                    }
+                    ui.separator();
+                }
+            });
+        }
+        if !registry.tools().is_empty() {
+            ui.horizontal(|ui| {
+                ui.label("Tools:");
+                for (index, tool) in registry.tools().iter().enumerate() {
+                    let selected = active_tool == Some(Tool::Plugin(index));
+                    if ui.selectable_label(selected, tool.name()).clicked() {
+                        let new_tool = (!selected).then_some(Tool::Plugin(index));
+                        self.active_tool.do_send(SetActiveTool::new(new_tool));
+                    }
+                }
+            });
+        }
+    }
+
+    /// Renders the root directory, returning whether the map in it should be loaded or the map
+    /// being loaded canceled
+    fn render_root_directory(
         root_path: Option<PathBuf>,
         map: &Option<Addr<Map>>,
         is_map_loading: bool,
+        load_progress: &[LoadProgress],
         ui: &mut Ui,
-    ) {
+    ) -> Option<RootDirectoryAction> {
+        let mut root_action = None;
         if let Some(pathbuf) = root_path {
             ui.horizontal(|ui| {
                 ui.label("Root Directory: ");
                 ui.label(pathbuf.display().to_string());
-                if map.is_none() && ui.button("Load Map").clicked() {
-                    if let Err(e) = self
-                        .map_loader
-                        .try_send(LoadMap::new(pathbuf, self.terminal.clone()))
-                    {
-                        error!("{e}");
-                    }
+                let can_load = map.is_none() && !is_map_loading;
+                if can_load && ui.button("Load Map").clicked() {
+                    root_action = Some(RootDirectoryAction::Load(pathbuf));
+                }
+                if is_map_loading && ui.button("Cancel").clicked() {
+                    root_action = Some(RootDirectoryAction::CancelLoad);
                 }
             });
             if is_map_loading {
-                ui.spinner();
+                Self::render_load_progress(load_progress, ui);
             }
         } else {
             ui.heading("Please select a root folder");
         }
+        root_action
+    }
+
+    /// Renders how many of the components of the map that started loading are done, along with
+    /// the components still loading and those that failed
+    fn render_load_progress(load_progress: &[LoadProgress], ui: &mut Ui) {
+        let done = load_progress
+            .iter()
+            .filter(|p| p.state != LoadState::Started)
+            .count();
+        #[allow(clippy::as_conversions)]
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = done as f32 / load_progress.len().max(1) as f32;
+        ui.add(ProgressBar::new(fraction).text(format!(
+            "Loaded {done} of {} components",
+            load_progress.len()
+        )));
+        ui.horizontal_wrapped(|ui| {
+            for progress in load_progress {
+                match &progress.state {
+                    LoadState::Started => {
+                        ui.spinner();
+                        ui.label(&progress.component);
+                    }
+                    LoadState::Failed(e) => {
+                        ui.colored_label(Color32::RED, format!("{}: {e}", progress.component));
+                    }
+                    LoadState::Finished | LoadState::Skipped | LoadState::Canceled => {}
+                }
+            }
+        });
+    }
+
+    /// Starts loading the map in the root directory, unless another operation is in progress
+    async fn load_map(&self, root_path: PathBuf) -> Result<(), MapError> {
+        if self
+            .busy
+            .send(Begin::new(Operation::LoadingMap))
+            .await?
+            .is_err()
+        {
+            return Ok(());
+        }
+        let load = LoadMap::new(root_path, self.terminal.clone());
+        if !self.map_loader.send(load).await? {
+            self.busy.do_send(End::new(Operation::LoadingMap));
+        }
+        Ok(())
     }
 
     async fn load_textures(
@@ -268,9 +1096,64 @@ impl ControlPanelRenderer {
                             .await?;
                     }
                 }
+
+                if texture_handles.continents.is_none() {
+                    if let Some(image) = m.send(GetMapImage::Continents).await? {
+                        self.map_textures
+                            .send(LoadImage::Continents {
+                                image,
+                                context: ctx.clone(),
+                            })
+                            .await?;
+                    }
+                }
+
+                if texture_handles.definition_terrain.is_none() {
+                    if let Some(image) = m.send(GetMapImage::DefinitionTerrain).await? {
+                        self.map_textures
+                            .send(LoadImage::DefinitionTerrain {
+                                image,
+                                context: ctx.clone(),
+                            })
+                            .await?;
+                    }
+                }
+
+                // The elevation map is tinted when it is asked for, so it is only asked for once
+                // no texture of it is loading
+                if texture_handles.elevation.is_none()
+                    && !self
+                        .map_textures
+                        .send(IsTextureLoading(MapDisplayMode::Elevation))
+                        .await?
+                {
+                    if let Some(image) = m.send(GetMapImage::Elevation).await? {
+                        self.map_textures
+                            .send(LoadImage::Elevation {
+                                image,
+                                context: ctx.clone(),
+                            })
+                            .await?;
+                    }
+                }
             }
         }
 
         Ok(())
     }
 }
+
+/// A label showing the palette index of a terrain brush on its color
+#[allow(clippy::integer_arithmetic)]
+fn terrain_swatch(brush: TerrainBrush) -> RichText {
+    let Rgb([r, g, b]) = brush.color();
+    let text = if u32::from(r) + u32::from(g) + u32::from(b) > 384 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    };
+    RichText::new(format!("{:>2}", brush.index))
+        .monospace()
+        .color(text)
+        .background_color(Color32::from_rgb(r, g, b))
+}
@@ -1,14 +1,22 @@
+use crate::ui::busy::{Busy, End, Operation};
 use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
 use indicatif::InMemoryTerm;
 use log::{debug, error, trace};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::unbounded_channel;
 use tokio::task::JoinHandle;
-use world_gen::map::{GenerateStateMap, GenerateStrategicRegionMap, Map};
+use world_gen::cache::ComponentCache;
+use world_gen::map::{
+    GenerateContinentMap, GenerateDefinitionTerrainMap, GenerateStateMap,
+    GenerateStrategicRegionMap, Map, MapLoadOptions, WatchRootPath,
+};
+use world_gen::progress::{CancelToken, LoadProgress};
+use world_gen::project::Project;
 use world_gen::MapError;
 
-/// A request to load the map
+/// A request to load the map.  Returns false if the map is already being loaded.
 #[derive(Message)]
-#[rtype(result = "()")]
+#[rtype(result = "bool")]
 #[non_exhaustive]
 pub struct LoadMap {
     root_path: PathBuf,
@@ -24,6 +32,26 @@ impl LoadMap {
     }
 }
 
+/// A request to unload the map, so that another one can be loaded.  Returns false if the map is
+/// being loaded, which has to finish or be stopped with `CancelLoad` first.
+#[derive(Message)]
+#[rtype(result = "bool")]
+#[non_exhaustive]
+pub struct UnloadMap;
+
+/// A request to unload the map and load the one in another root directory in its place.  Returns
+/// false if the map is being loaded.
+#[derive(Message)]
+#[rtype(result = "bool")]
+#[non_exhaustive]
+pub struct ReloadWithNewRoot(pub LoadMap);
+
+impl ReloadWithNewRoot {
+    pub const fn new(root_path: PathBuf, terminal: InMemoryTerm) -> Self {
+        Self(LoadMap::new(root_path, terminal))
+    }
+}
+
 /// A request to get the map
 #[derive(Message)]
 #[rtype(result = "Option<Addr<Map>>")]
@@ -36,15 +64,18 @@ pub struct GetMap;
 #[non_exhaustive]
 pub struct IsMapLoading;
 
-/// A request to update the map
+/// A request to update the map loaded from a root directory
 #[derive(Message)]
 #[rtype(result = "()")]
 #[non_exhaustive]
-pub struct UpdateMap(Result<Map, MapError>);
+pub struct UpdateMap {
+    map: Result<Map, MapError>,
+    root_path: PathBuf,
+}
 
 impl UpdateMap {
-    pub const fn new(map: Result<Map, MapError>) -> Self {
-        Self(map)
+    pub const fn new(map: Result<Map, MapError>, root_path: PathBuf) -> Self {
+        Self { map, root_path }
     }
 }
 
@@ -54,10 +85,48 @@ impl UpdateMap {
 #[non_exhaustive]
 pub struct IsMapLoaded;
 
-#[derive(Debug, Default)]
+/// A request to cancel the loading of the map.  The components being read are finished, but no
+/// other component is started and the map is dropped once they are.  Returns false if the map is
+/// not being loaded.
+#[derive(Message)]
+#[rtype(result = "bool")]
+#[non_exhaustive]
+pub struct CancelLoad;
+
+/// A request to record an event of the loading of the map
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct RecordLoadProgress(LoadProgress);
+
+/// A request to get the latest state of each component of the map that has started loading, in
+/// the order they started
+#[derive(Message)]
+#[rtype(result = "Vec<LoadProgress>")]
+#[non_exhaustive]
+pub struct GetLoadProgress;
+
+#[derive(Debug)]
 pub struct MapLoader {
     map: Option<Addr<Map>>,
     map_handle: Option<JoinHandle<()>>,
+    busy: Addr<Busy>,
+    /// The latest state of each component of the map being loaded
+    progress: Vec<LoadProgress>,
+    /// The token canceling the map being loaded
+    cancel: Option<CancelToken>,
+}
+
+impl MapLoader {
+    pub const fn new(busy: Addr<Busy>) -> Self {
+        Self {
+            map: None,
+            map_handle: None,
+            busy,
+            progress: Vec::new(),
+            cancel: None,
+        }
+    }
 }
 
 impl Actor for MapLoader {
@@ -78,33 +147,132 @@ impl Handler<UpdateMap> for MapLoader {
 
     fn handle(&mut self, msg: UpdateMap, _ctx: &mut Self::Context) -> Self::Result {
         trace!("UpdateMap");
-        match msg.0 {
+        let canceled = self.cancel.take().map_or(false, |c| c.is_canceled());
+        match msg.map {
+            Ok(_) if canceled => debug!("Dropping the map, as its loading was canceled"),
+            Err(MapError::LoadCanceled) => debug!("The loading of the map was canceled"),
             Ok(m) => {
                 let map_addr = m.start();
                 map_addr.do_send(GenerateStrategicRegionMap);
                 map_addr.do_send(GenerateStateMap);
+                map_addr.do_send(GenerateContinentMap);
+                map_addr.do_send(GenerateDefinitionTerrainMap);
+                // Files edited outside of the editor are reloaded into the map as they change
+                map_addr.do_send(WatchRootPath::new(msg.root_path));
                 self.map = Some(map_addr);
-                self.map_handle.take();
             }
             Err(e) => error!("{e:?}"),
         }
+        // A map that failed to load may be loaded again
+        self.map_handle.take();
+        self.progress.clear();
+        self.busy.do_send(End::new(Operation::LoadingMap));
     }
 }
 
 impl Handler<LoadMap> for MapLoader {
-    type Result = ();
+    type Result = bool;
 
     fn handle(&mut self, msg: LoadMap, ctx: &mut Self::Context) -> Self::Result {
         trace!("LoadMap");
         if self.map_handle.is_some() {
-            return;
+            debug!("The map is already being loaded");
+            return false;
         }
         let self_addr = ctx.address();
+        self.progress.clear();
+        let cancel = CancelToken::new();
+        self.cancel = Some(cancel.clone());
+        let (progress, mut events) = unbounded_channel();
+        let progress_addr = self_addr.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                progress_addr.do_send(RecordLoadProgress(event));
+            }
+        });
         let map_loading_handle = tokio::task::spawn_blocking(move || {
-            let map = Map::new(&msg.root_path, &Some(msg.terminal));
-            self_addr.do_send(UpdateMap::new(map));
+            // The editor does not show the trees, normal map, cities or unit stacks, so they are
+            // loaded when something first needs them.  An invalid project file is reported by the
+            // load itself.
+            let loading = Project::load(&msg.root_path)
+                .map(|project| project.loading)
+                .unwrap_or_default();
+            let mut options = MapLoadOptions::deferred()
+                .with_progress(progress)
+                .with_cancel(cancel)
+                .with_cache(component_cache(&msg.root_path))
+                .with_csv_parsing(loading.csv_parsing);
+            if let Some(encoding) = loading.encoding {
+                options = options.with_encoding(encoding);
+            }
+            let map = Map::new(&msg.root_path, &Some(msg.terminal), &options);
+            self_addr.do_send(UpdateMap::new(map, msg.root_path));
         });
         self.map_handle = Some(map_loading_handle);
+        true
+    }
+}
+
+impl Handler<UnloadMap> for MapLoader {
+    type Result = bool;
+
+    fn handle(&mut self, _msg: UnloadMap, _ctx: &mut Self::Context) -> Self::Result {
+        trace!("UnloadMap");
+        if self.map_handle.is_some() {
+            debug!("The map cannot be unloaded while it is loading");
+            return false;
+        }
+        // The map actor stops once the last address to it is dropped
+        self.map.take();
+        true
+    }
+}
+
+impl Handler<ReloadWithNewRoot> for MapLoader {
+    type Result = bool;
+
+    fn handle(&mut self, msg: ReloadWithNewRoot, ctx: &mut Self::Context) -> Self::Result {
+        trace!("ReloadWithNewRoot");
+        <Self as Handler<UnloadMap>>::handle(self, UnloadMap, ctx)
+            && <Self as Handler<LoadMap>>::handle(self, msg.0, ctx)
+    }
+}
+
+impl Handler<CancelLoad> for MapLoader {
+    type Result = bool;
+
+    fn handle(&mut self, _msg: CancelLoad, _ctx: &mut Self::Context) -> Self::Result {
+        trace!("CancelLoad");
+        match &self.cancel {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Handler<RecordLoadProgress> for MapLoader {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordLoadProgress, _ctx: &mut Self::Context) -> Self::Result {
+        match self
+            .progress
+            .iter_mut()
+            .find(|p| p.component == msg.0.component)
+        {
+            Some(progress) => *progress = msg.0,
+            None => self.progress.push(msg.0),
+        }
+    }
+}
+
+impl Handler<GetLoadProgress> for MapLoader {
+    type Result = Vec<LoadProgress>;
+
+    fn handle(&mut self, _msg: GetLoadProgress, _ctx: &mut Self::Context) -> Self::Result {
+        self.progress.clone()
     }
 }
 
@@ -116,3 +284,13 @@ impl Handler<IsMapLoading> for MapLoader {
         self.map_handle.is_some()
     }
 }
+
+/// The cache of the parsed components of a root directory, kept in the temporary directory under
+/// the name of the root directory so that switching between two mods keeps both cached
+fn component_cache(root_path: &Path) -> ComponentCache {
+    let name = root_path.file_name().map_or_else(
+        || "root".to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    );
+    ComponentCache::new(std::env::temp_dir().join("world_gen_cache").join(name))
+}
@@ -1,25 +1,94 @@
-use actix::{Actor, AsyncContext, Context as ActixContext, Handler, Message};
-use egui::{ColorImage, Context, TextureFilter, TextureHandle};
-use image::{DynamicImage, RgbImage};
+use crate::ui::metrics::{Metrics, RecordTiming};
+use actix::{Actor, Addr, AsyncContext, Context as ActixContext, Handler, Message};
+use egui::{Context, TextureFilter, TextureHandle};
+use image::RgbImage;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::task::JoinHandle;
+use world_gen::texture::{
+    pyramid_level, pyramid_patch, texture_image, TexturePatch, PYRAMID_LEVELS,
+};
 use world_gen::MapDisplayMode;
 
-/// A request to load an image
+/// A request to load an image.  The image is shared with the map, so loading it copies nothing
+/// but the texture.
 #[derive(Message)]
 #[rtype(result = "()")]
 pub enum LoadImage {
-    HeightMap { image: RgbImage, context: Context },
-    Terrain { image: RgbImage, context: Context },
-    Provinces { image: RgbImage, context: Context },
-    Rivers { image: RgbImage, context: Context },
-    StrategicRegions { image: RgbImage, context: Context },
-    States { image: RgbImage, context: Context },
+    HeightMap {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Terrain {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Provinces {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Rivers {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    StrategicRegions {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    States {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Continents {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    DefinitionTerrain {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Elevation {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Trees {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    NormalMap {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Cities {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    BuildingDensity {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Weather {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Manpower {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    Political {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
+    StateCategories {
+        image: Arc<RgbImage>,
+        context: Context,
+    },
 }
 
 impl LoadImage {
     pub const fn from_display_mode(
         mode: MapDisplayMode,
-        image: RgbImage,
+        image: Arc<RgbImage>,
         context: Context,
     ) -> Self {
         match mode {
@@ -29,25 +98,56 @@ impl LoadImage {
             MapDisplayMode::Rivers => Self::Rivers { image, context },
             MapDisplayMode::StrategicRegions => Self::StrategicRegions { image, context },
             MapDisplayMode::States => Self::States { image, context },
+            MapDisplayMode::Continents => Self::Continents { image, context },
+            MapDisplayMode::DefinitionTerrain => Self::DefinitionTerrain { image, context },
+            MapDisplayMode::Elevation => Self::Elevation { image, context },
+            MapDisplayMode::Trees => Self::Trees { image, context },
+            MapDisplayMode::NormalMap => Self::NormalMap { image, context },
+            MapDisplayMode::Cities => Self::Cities { image, context },
+            MapDisplayMode::BuildingDensity => Self::BuildingDensity { image, context },
+            MapDisplayMode::Weather => Self::Weather { image, context },
+            MapDisplayMode::Manpower => Self::Manpower { image, context },
+            MapDisplayMode::Political => Self::Political { image, context },
+            MapDisplayMode::StateCategories => Self::StateCategories { image, context },
         }
     }
 }
 
-/// A request to update a texture
-#[derive(Message)]
-#[rtype(result = "()")]
+/// A texture that has been loaded
 enum UpdateTexture {
-    HeightMap(TextureHandle),
-    Terrain(TextureHandle),
-    Provinces(TextureHandle),
-    Rivers(TextureHandle),
-    StrategicRegions(TextureHandle),
-    States(TextureHandle),
+    HeightMap(TexturePyramid),
+    Terrain(TexturePyramid),
+    Provinces(TexturePyramid),
+    Rivers(TexturePyramid),
+    StrategicRegions(TexturePyramid),
+    States(TexturePyramid),
+    Continents(TexturePyramid),
+    DefinitionTerrain(TexturePyramid),
+    Elevation(TexturePyramid),
+    Trees(TexturePyramid),
+    NormalMap(TexturePyramid),
+    Cities(TexturePyramid),
+    BuildingDensity(TexturePyramid),
+    Weather(TexturePyramid),
+    Manpower(TexturePyramid),
+    Political(TexturePyramid),
+    StateCategories(TexturePyramid),
 }
 
+/// A request to update a texture, unless the textures were cleared since it started loading
+#[derive(Message)]
+#[rtype(result = "()")]
+struct TextureLoaded(u64, UpdateTexture);
+
+/// A request to drop every texture, such as when the map is unloaded
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ClearTextures;
+
 /// A request to get a texture
 #[derive(Message)]
-#[rtype(result = "Option<TextureHandle>")]
+#[rtype(result = "Option<TexturePyramid>")]
 #[non_exhaustive]
 pub enum GetTexture {
     HeightMap,
@@ -56,8 +156,42 @@ pub enum GetTexture {
     Rivers,
     StrategicRegions,
     States,
+    Continents,
+    DefinitionTerrain,
+    Elevation,
+    Trees,
+    NormalMap,
+    Cities,
+    BuildingDensity,
+    Weather,
+    Manpower,
+    Political,
+    StateCategories,
+}
+
+/// A request to replace part of a loaded texture after a small edit, which is much faster than
+/// loading the whole texture again on large maps.  Returns `false` if the texture is not loaded
+/// or is loading, in which case it must be loaded again instead.
+#[derive(Message)]
+#[rtype(result = "bool")]
+#[non_exhaustive]
+pub struct PatchTexture {
+    pub mode: MapDisplayMode,
+    pub patch: TexturePatch,
+}
+
+impl PatchTexture {
+    pub const fn new(mode: MapDisplayMode, patch: TexturePatch) -> Self {
+        Self { mode, patch }
+    }
 }
 
+/// A request to check if a texture is currently being loaded
+#[derive(Message)]
+#[rtype(result = "bool")]
+#[non_exhaustive]
+pub struct IsTextureLoading(pub MapDisplayMode);
+
 impl From<MapDisplayMode> for GetTexture {
     fn from(m: MapDisplayMode) -> Self {
         match m {
@@ -67,24 +201,103 @@ impl From<MapDisplayMode> for GetTexture {
             MapDisplayMode::Rivers => Self::Rivers,
             MapDisplayMode::StrategicRegions => Self::StrategicRegions,
             MapDisplayMode::States => Self::States,
+            MapDisplayMode::Continents => Self::Continents,
+            MapDisplayMode::DefinitionTerrain => Self::DefinitionTerrain,
+            MapDisplayMode::Elevation => Self::Elevation,
+            MapDisplayMode::Trees => Self::Trees,
+            MapDisplayMode::NormalMap => Self::NormalMap,
+            MapDisplayMode::Cities => Self::Cities,
+            MapDisplayMode::BuildingDensity => Self::BuildingDensity,
+            MapDisplayMode::Weather => Self::Weather,
+            MapDisplayMode::Manpower => Self::Manpower,
+            MapDisplayMode::Political => Self::Political,
+            MapDisplayMode::StateCategories => Self::StateCategories,
         }
     }
 }
 
-#[derive(Default)]
 pub struct MapTextures {
-    heightmap_texture: Option<TextureHandle>,
-    terrain_texture: Option<TextureHandle>,
-    provinces_texture: Option<TextureHandle>,
-    rivers_texture: Option<TextureHandle>,
-    strategic_regions_texture: Option<TextureHandle>,
-    states_texture: Option<TextureHandle>,
+    heightmap_texture: Option<TexturePyramid>,
+    terrain_texture: Option<TexturePyramid>,
+    provinces_texture: Option<TexturePyramid>,
+    rivers_texture: Option<TexturePyramid>,
+    strategic_regions_texture: Option<TexturePyramid>,
+    states_texture: Option<TexturePyramid>,
+    continents_texture: Option<TexturePyramid>,
+    definition_terrain_texture: Option<TexturePyramid>,
+    elevation_texture: Option<TexturePyramid>,
+    trees_texture: Option<TexturePyramid>,
+    normal_map_texture: Option<TexturePyramid>,
+    cities_texture: Option<TexturePyramid>,
+    building_density_texture: Option<TexturePyramid>,
+    weather_texture: Option<TexturePyramid>,
+    manpower_texture: Option<TexturePyramid>,
+    political_texture: Option<TexturePyramid>,
+    state_categories_texture: Option<TexturePyramid>,
     heightmap_handle: Option<JoinHandle<()>>,
     terrain_handle: Option<JoinHandle<()>>,
     provinces_handle: Option<JoinHandle<()>>,
     rivers_handle: Option<JoinHandle<()>>,
     strategic_regions_handle: Option<JoinHandle<()>>,
     states_handle: Option<JoinHandle<()>>,
+    continents_handle: Option<JoinHandle<()>>,
+    definition_terrain_handle: Option<JoinHandle<()>>,
+    elevation_handle: Option<JoinHandle<()>>,
+    trees_handle: Option<JoinHandle<()>>,
+    normal_map_handle: Option<JoinHandle<()>>,
+    cities_handle: Option<JoinHandle<()>>,
+    building_density_handle: Option<JoinHandle<()>>,
+    weather_handle: Option<JoinHandle<()>>,
+    manpower_handle: Option<JoinHandle<()>>,
+    political_handle: Option<JoinHandle<()>>,
+    state_categories_handle: Option<JoinHandle<()>>,
+    /// The number of times the textures have been cleared, so that textures of an unloaded map
+    /// that finish loading afterwards are dropped
+    generation: u64,
+    metrics: Addr<Metrics>,
+}
+
+impl MapTextures {
+    pub const fn new(metrics: Addr<Metrics>) -> Self {
+        Self {
+            heightmap_texture: None,
+            terrain_texture: None,
+            provinces_texture: None,
+            rivers_texture: None,
+            strategic_regions_texture: None,
+            states_texture: None,
+            continents_texture: None,
+            definition_terrain_texture: None,
+            elevation_texture: None,
+            trees_texture: None,
+            normal_map_texture: None,
+            cities_texture: None,
+            building_density_texture: None,
+            weather_texture: None,
+            manpower_texture: None,
+            political_texture: None,
+            state_categories_texture: None,
+            heightmap_handle: None,
+            terrain_handle: None,
+            provinces_handle: None,
+            rivers_handle: None,
+            strategic_regions_handle: None,
+            states_handle: None,
+            continents_handle: None,
+            definition_terrain_handle: None,
+            elevation_handle: None,
+            trees_handle: None,
+            normal_map_handle: None,
+            cities_handle: None,
+            building_density_handle: None,
+            weather_handle: None,
+            manpower_handle: None,
+            political_handle: None,
+            state_categories_handle: None,
+            generation: 0,
+            metrics,
+        }
+    }
 }
 
 impl Actor for MapTextures {
@@ -96,14 +309,16 @@ impl Handler<LoadImage> for MapTextures {
 
     fn handle(&mut self, msg: LoadImage, ctx: &mut Self::Context) -> Self::Result {
         let self_addr = ctx.address();
+        let generation = self.generation;
+        let metrics = self.metrics.clone();
         match msg {
             LoadImage::HeightMap { image, context } => {
                 if self.heightmap_handle.is_some() {
                     return;
                 }
                 self.heightmap_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::HeightMap(tex));
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/heightmap");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::HeightMap(tex)));
                 }));
             }
             LoadImage::Terrain { image, context } => {
@@ -111,8 +326,8 @@ impl Handler<LoadImage> for MapTextures {
                     return;
                 }
                 self.terrain_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::Terrain(tex));
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/terrain");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Terrain(tex)));
                 }));
             }
             LoadImage::Provinces { image, context } => {
@@ -120,8 +335,8 @@ impl Handler<LoadImage> for MapTextures {
                     return;
                 }
                 self.provinces_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::Provinces(tex));
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/provinces");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Provinces(tex)));
                 }));
             }
             LoadImage::Rivers { image, context } => {
@@ -129,8 +344,8 @@ impl Handler<LoadImage> for MapTextures {
                     return;
                 }
                 self.rivers_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::Rivers(tex));
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/rivers");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Rivers(tex)));
                 }));
             }
             LoadImage::StrategicRegions { image, context } => {
@@ -138,8 +353,16 @@ impl Handler<LoadImage> for MapTextures {
                     return;
                 }
                 self.strategic_regions_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::StrategicRegions(tex));
+                    let tex = load_pyramid(
+                        image,
+                        &context,
+                        &metrics,
+                        "texture_upload/strategic_regions",
+                    );
+                    self_addr.do_send(TextureLoaded(
+                        generation,
+                        UpdateTexture::StrategicRegions(tex),
+                    ));
                 }));
             }
             LoadImage::States { image, context } => {
@@ -147,24 +370,201 @@ impl Handler<LoadImage> for MapTextures {
                     return;
                 }
                 self.states_handle = Some(tokio::task::spawn_blocking(move || {
-                    let tex = load_texture(image, &context);
-                    self_addr.do_send(UpdateTexture::States(tex));
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/states");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::States(tex)));
+                }));
+            }
+            LoadImage::Continents { image, context } => {
+                if self.continents_handle.is_some() {
+                    return;
+                }
+                self.continents_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/continents");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Continents(tex)));
+                }));
+            }
+            LoadImage::DefinitionTerrain { image, context } => {
+                if self.definition_terrain_handle.is_some() {
+                    return;
+                }
+                self.definition_terrain_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(
+                        image,
+                        &context,
+                        &metrics,
+                        "texture_upload/definition_terrain",
+                    );
+                    self_addr.do_send(TextureLoaded(
+                        generation,
+                        UpdateTexture::DefinitionTerrain(tex),
+                    ));
+                }));
+            }
+            LoadImage::Elevation { image, context } => {
+                if self.elevation_handle.is_some() {
+                    return;
+                }
+                self.elevation_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/elevation");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Elevation(tex)));
+                }));
+            }
+            LoadImage::Trees { image, context } => {
+                if self.trees_handle.is_some() {
+                    return;
+                }
+                self.trees_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/trees");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Trees(tex)));
+                }));
+            }
+            LoadImage::NormalMap { image, context } => {
+                if self.normal_map_handle.is_some() {
+                    return;
+                }
+                self.normal_map_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/normal_map");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::NormalMap(tex)));
+                }));
+            }
+            LoadImage::Cities { image, context } => {
+                if self.cities_handle.is_some() {
+                    return;
+                }
+                self.cities_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/cities");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Cities(tex)));
+                }));
+            }
+            LoadImage::BuildingDensity { image, context } => {
+                if self.building_density_handle.is_some() {
+                    return;
+                }
+                self.building_density_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex =
+                        load_pyramid(image, &context, &metrics, "texture_upload/building_density");
+                    self_addr.do_send(TextureLoaded(
+                        generation,
+                        UpdateTexture::BuildingDensity(tex),
+                    ));
+                }));
+            }
+            LoadImage::Weather { image, context } => {
+                if self.weather_handle.is_some() {
+                    return;
+                }
+                self.weather_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/weather");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Weather(tex)));
+                }));
+            }
+            LoadImage::Manpower { image, context } => {
+                if self.manpower_handle.is_some() {
+                    return;
+                }
+                self.manpower_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/manpower");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Manpower(tex)));
+                }));
+            }
+            LoadImage::Political { image, context } => {
+                if self.political_handle.is_some() {
+                    return;
+                }
+                self.political_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex = load_pyramid(image, &context, &metrics, "texture_upload/political");
+                    self_addr.do_send(TextureLoaded(generation, UpdateTexture::Political(tex)));
+                }));
+            }
+            LoadImage::StateCategories { image, context } => {
+                if self.state_categories_handle.is_some() {
+                    return;
+                }
+                self.state_categories_handle = Some(tokio::task::spawn_blocking(move || {
+                    let tex =
+                        load_pyramid(image, &context, &metrics, "texture_upload/state_categories");
+                    self_addr.do_send(TextureLoaded(
+                        generation,
+                        UpdateTexture::StateCategories(tex),
+                    ));
                 }));
             }
         };
     }
 }
 
-fn load_texture(rgb_image: RgbImage, context: &Context) -> TextureHandle {
-    let size = [rgb_image.width() as usize, rgb_image.height() as usize];
-    let image_buffer = DynamicImage::ImageRgb8(rgb_image).into_rgba8();
-    let pixels = image_buffer.as_flat_samples();
-    let color_image = ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-    context.load_texture("map", color_image, TextureFilter::Nearest)
+/// The textures of a map image at each level of its pyramid.  The viewport draws the smallest
+/// level that still has a texel for each pixel of the screen, so that a zoomed out map does not
+/// draw every texel of the full size texture.
+#[derive(Clone)]
+pub struct TexturePyramid {
+    /// The full size texture, which the coordinates of the map are measured on
+    full: TextureHandle,
+    /// The textures of the levels after the first, each half the size of the one before
+    smaller: Vec<TextureHandle>,
+}
+
+impl TexturePyramid {
+    /// The full size texture, which the coordinates of the map are measured on
+    pub const fn full(&self) -> &TextureHandle {
+        &self.full
+    }
+
+    /// The texture of a level, or the smallest texture if there are fewer levels
+    pub fn level(&self, level: usize) -> &TextureHandle {
+        match level.checked_sub(1) {
+            Some(smaller) => self
+                .smaller
+                .get(smaller)
+                .or_else(|| self.smaller.last())
+                .unwrap_or(&self.full),
+            None => &self.full,
+        }
+    }
+
+    /// The number of levels of the pyramid
+    pub fn levels(&self) -> usize {
+        self.smaller.len().saturating_add(1)
+    }
+
+    /// Replaces part of every level after a small edit of the full size texture
+    fn patch(&mut self, patch: TexturePatch) {
+        for (level, texture) in (1..).zip(self.smaller.iter_mut()) {
+            if let Some(p) = pyramid_patch(&patch, level) {
+                texture.set_partial(p.position, p.image, TextureFilter::Nearest);
+            }
+        }
+        self.full
+            .set_partial(patch.position, patch.image, TextureFilter::Nearest);
+    }
+}
+
+/// Uploads the image as a pyramid of textures, recording how long it took under the given name.
+/// The image is released as soon as its full size texture is built, so that editing it
+/// afterwards does not copy it.
+fn load_pyramid(
+    rgb_image: Arc<RgbImage>,
+    context: &Context,
+    metrics: &Addr<Metrics>,
+    name: &'static str,
+) -> TexturePyramid {
+    let start = Instant::now();
+    let max_side = context.input().max_texture_side;
+    let color_image = texture_image(&rgb_image, max_side);
+    drop(rgb_image);
+    let smaller = (1..PYRAMID_LEVELS)
+        .map(|level| {
+            let image = pyramid_level(&color_image, level);
+            context.load_texture(format!("map_{level}"), image, TextureFilter::Nearest)
+        })
+        .collect();
+    let full = context.load_texture("map", color_image, TextureFilter::Nearest);
+    metrics.do_send(RecordTiming::new(name, start.elapsed()));
+    TexturePyramid { full, smaller }
 }
 
 impl Handler<GetTexture> for MapTextures {
-    type Result = Option<TextureHandle>;
+    type Result = Option<TexturePyramid>;
 
     fn handle(&mut self, msg: GetTexture, _ctx: &mut Self::Context) -> Self::Result {
         match msg {
@@ -174,15 +574,105 @@ impl Handler<GetTexture> for MapTextures {
             GetTexture::Rivers => self.rivers_texture.clone(),
             GetTexture::StrategicRegions => self.strategic_regions_texture.clone(),
             GetTexture::States => self.states_texture.clone(),
+            GetTexture::Continents => self.continents_texture.clone(),
+            GetTexture::DefinitionTerrain => self.definition_terrain_texture.clone(),
+            GetTexture::Elevation => self.elevation_texture.clone(),
+            GetTexture::Trees => self.trees_texture.clone(),
+            GetTexture::NormalMap => self.normal_map_texture.clone(),
+            GetTexture::Cities => self.cities_texture.clone(),
+            GetTexture::BuildingDensity => self.building_density_texture.clone(),
+            GetTexture::Weather => self.weather_texture.clone(),
+            GetTexture::Manpower => self.manpower_texture.clone(),
+            GetTexture::Political => self.political_texture.clone(),
+            GetTexture::StateCategories => self.state_categories_texture.clone(),
+        }
+    }
+}
+
+impl Handler<PatchTexture> for MapTextures {
+    type Result = bool;
+
+    fn handle(&mut self, msg: PatchTexture, _ctx: &mut Self::Context) -> Self::Result {
+        let (texture, handle) = match msg.mode {
+            MapDisplayMode::HeightMap => (&mut self.heightmap_texture, &self.heightmap_handle),
+            MapDisplayMode::Terrain => (&mut self.terrain_texture, &self.terrain_handle),
+            MapDisplayMode::Provinces => (&mut self.provinces_texture, &self.provinces_handle),
+            MapDisplayMode::Rivers => (&mut self.rivers_texture, &self.rivers_handle),
+            MapDisplayMode::StrategicRegions => (
+                &mut self.strategic_regions_texture,
+                &self.strategic_regions_handle,
+            ),
+            MapDisplayMode::States => (&mut self.states_texture, &self.states_handle),
+            MapDisplayMode::Continents => (&mut self.continents_texture, &self.continents_handle),
+            MapDisplayMode::DefinitionTerrain => (
+                &mut self.definition_terrain_texture,
+                &self.definition_terrain_handle,
+            ),
+            MapDisplayMode::Elevation => (&mut self.elevation_texture, &self.elevation_handle),
+            MapDisplayMode::Trees => (&mut self.trees_texture, &self.trees_handle),
+            MapDisplayMode::NormalMap => (&mut self.normal_map_texture, &self.normal_map_handle),
+            MapDisplayMode::Cities => (&mut self.cities_texture, &self.cities_handle),
+            MapDisplayMode::BuildingDensity => (
+                &mut self.building_density_texture,
+                &self.building_density_handle,
+            ),
+            MapDisplayMode::Weather => (&mut self.weather_texture, &self.weather_handle),
+            MapDisplayMode::Manpower => (&mut self.manpower_texture, &self.manpower_handle),
+            MapDisplayMode::Political => (&mut self.political_texture, &self.political_handle),
+            MapDisplayMode::StateCategories => (
+                &mut self.state_categories_texture,
+                &self.state_categories_handle,
+            ),
+        };
+        // A texture that is loading would replace the patch with the image from before the edit
+        match (texture, handle) {
+            (Some(texture), None) => {
+                let start = Instant::now();
+                texture.patch(msg.patch);
+                self.metrics
+                    .do_send(RecordTiming::new("texture_patch", start.elapsed()));
+                true
+            }
+            _ => false,
         }
     }
 }
 
-impl Handler<UpdateTexture> for MapTextures {
+impl Handler<IsTextureLoading> for MapTextures {
+    type Result = bool;
+
+    fn handle(&mut self, msg: IsTextureLoading, _ctx: &mut Self::Context) -> Self::Result {
+        match msg.0 {
+            MapDisplayMode::HeightMap => self.heightmap_handle.is_some(),
+            MapDisplayMode::Terrain => self.terrain_handle.is_some(),
+            MapDisplayMode::Provinces => self.provinces_handle.is_some(),
+            MapDisplayMode::Rivers => self.rivers_handle.is_some(),
+            MapDisplayMode::StrategicRegions => self.strategic_regions_handle.is_some(),
+            MapDisplayMode::States => self.states_handle.is_some(),
+            MapDisplayMode::Continents => self.continents_handle.is_some(),
+            MapDisplayMode::DefinitionTerrain => self.definition_terrain_handle.is_some(),
+            MapDisplayMode::Elevation => self.elevation_handle.is_some(),
+            MapDisplayMode::Trees => self.trees_handle.is_some(),
+            MapDisplayMode::NormalMap => self.normal_map_handle.is_some(),
+            MapDisplayMode::Cities => self.cities_handle.is_some(),
+            MapDisplayMode::BuildingDensity => self.building_density_handle.is_some(),
+            MapDisplayMode::Weather => self.weather_handle.is_some(),
+            MapDisplayMode::Manpower => self.manpower_handle.is_some(),
+            MapDisplayMode::Political => self.political_handle.is_some(),
+            MapDisplayMode::StateCategories => self.state_categories_handle.is_some(),
+        }
+    }
+}
+
+impl Handler<TextureLoaded> for MapTextures {
     type Result = ();
 
-    fn handle(&mut self, msg: UpdateTexture, _ctx: &mut Self::Context) -> Self::Result {
-        match msg {
+    fn handle(&mut self, msg: TextureLoaded, _ctx: &mut Self::Context) -> Self::Result {
+        let TextureLoaded(generation, update) = msg;
+        if generation != self.generation {
+            return;
+        }
+        match update {
             UpdateTexture::HeightMap(t) => {
                 self.heightmap_texture = Some(t);
                 self.heightmap_handle.take();
@@ -207,6 +697,61 @@ impl Handler<UpdateTexture> for MapTextures {
                 self.states_texture = Some(t);
                 self.states_handle.take();
             }
+            UpdateTexture::Continents(t) => {
+                self.continents_texture = Some(t);
+                self.continents_handle.take();
+            }
+            UpdateTexture::DefinitionTerrain(t) => {
+                self.definition_terrain_texture = Some(t);
+                self.definition_terrain_handle.take();
+            }
+            UpdateTexture::Elevation(t) => {
+                self.elevation_texture = Some(t);
+                self.elevation_handle.take();
+            }
+            UpdateTexture::Trees(t) => {
+                self.trees_texture = Some(t);
+                self.trees_handle.take();
+            }
+            UpdateTexture::NormalMap(t) => {
+                self.normal_map_texture = Some(t);
+                self.normal_map_handle.take();
+            }
+            UpdateTexture::Cities(t) => {
+                self.cities_texture = Some(t);
+                self.cities_handle.take();
+            }
+            UpdateTexture::BuildingDensity(t) => {
+                self.building_density_texture = Some(t);
+                self.building_density_handle.take();
+            }
+            UpdateTexture::Weather(t) => {
+                self.weather_texture = Some(t);
+                self.weather_handle.take();
+            }
+            UpdateTexture::Manpower(t) => {
+                self.manpower_texture = Some(t);
+                self.manpower_handle.take();
+            }
+            UpdateTexture::Political(t) => {
+                self.political_texture = Some(t);
+                self.political_handle.take();
+            }
+            UpdateTexture::StateCategories(t) => {
+                self.state_categories_texture = Some(t);
+                self.state_categories_handle.take();
+            }
         }
     }
 }
+
+impl Handler<ClearTextures> for MapTextures {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ClearTextures, _ctx: &mut Self::Context) -> Self::Result {
+        *self = Self {
+            generation: self.generation.wrapping_add(1),
+            ..Self::new(self.metrics.clone())
+        };
+    }
+}
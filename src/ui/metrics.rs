@@ -0,0 +1,292 @@
+use actix::dev::ToEnvelope;
+use actix::{Actor, Addr, Context, Handler, MailboxError, Message, MessageResult};
+use egui::{Context as EguiContext, Grid, Window};
+use log::{error, info};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The number of recent samples of a timing its statistics are computed over
+const RECENT_SAMPLES: usize = 120;
+
+/// A request to record how long something took
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct RecordTiming {
+    pub name: &'static str,
+    pub duration: Duration,
+}
+
+impl RecordTiming {
+    pub const fn new(name: &'static str, duration: Duration) -> Self {
+        Self { name, duration }
+    }
+}
+
+/// A request to record whether a lookup found what it was looking for in a cache
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct RecordCacheLookup {
+    pub name: &'static str,
+    pub hit: bool,
+}
+
+impl RecordCacheLookup {
+    pub const fn new(name: &'static str, hit: bool) -> Self {
+        Self { name, hit }
+    }
+}
+
+/// A request to get the statistics of everything recorded so far
+#[derive(Message)]
+#[rtype(result = "MetricsSnapshot")]
+#[non_exhaustive]
+pub struct GetMetrics;
+
+/// A request to forget everything recorded so far
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ResetMetrics;
+
+/// The statistics of a timing, in milliseconds.  The mean and maximum are over the most recent
+/// samples, so that they follow what the editor is doing now.
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct TimingStats {
+    pub count: u64,
+    pub last_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+/// The number of lookups in a cache that found what they were looking for, and that did not
+#[derive(Copy, Clone, Debug, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+/// The statistics of every timing and cache, by name
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MetricsSnapshot {
+    pub timings: BTreeMap<&'static str, TimingStats>,
+    pub caches: BTreeMap<&'static str, CacheStats>,
+}
+
+#[derive(Debug, Default)]
+struct Timing {
+    count: u64,
+    recent: VecDeque<Duration>,
+}
+
+impl Timing {
+    fn record(&mut self, duration: Duration) {
+        self.count = self.count.saturating_add(1);
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(duration);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::as_conversions)]
+    fn stats(&self) -> TimingStats {
+        let to_ms = |d: &Duration| d.as_secs_f64() * 1000.0;
+        let total = self.recent.iter().map(to_ms).sum::<f64>();
+        TimingStats {
+            count: self.count,
+            last_ms: self.recent.back().map_or(0.0, to_ms),
+            mean_ms: total / self.recent.len().max(1) as f64,
+            max_ms: self.recent.iter().map(to_ms).fold(0.0, f64::max),
+        }
+    }
+}
+
+/// Collects how long frames, actor replies and texture uploads take, and how often caches are
+/// hit, so that slow spots on large maps can be found
+#[derive(Debug, Default)]
+pub struct Metrics {
+    timings: BTreeMap<&'static str, Timing>,
+    caches: BTreeMap<&'static str, (u64, u64)>,
+}
+
+impl Actor for Metrics {
+    type Context = Context<Self>;
+}
+
+impl Handler<RecordTiming> for Metrics {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordTiming, _ctx: &mut Self::Context) -> Self::Result {
+        self.timings
+            .entry(msg.name)
+            .or_default()
+            .record(msg.duration);
+    }
+}
+
+impl Handler<RecordCacheLookup> for Metrics {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordCacheLookup, _ctx: &mut Self::Context) -> Self::Result {
+        let (hits, misses) = self.caches.entry(msg.name).or_default();
+        if msg.hit {
+            *hits = hits.saturating_add(1);
+        } else {
+            *misses = misses.saturating_add(1);
+        }
+    }
+}
+
+impl Handler<GetMetrics> for Metrics {
+    type Result = MessageResult<GetMetrics>;
+
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::as_conversions)]
+    fn handle(&mut self, _msg: GetMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        let timings = self
+            .timings
+            .iter()
+            .map(|(name, timing)| (*name, timing.stats()))
+            .collect();
+        let caches = self
+            .caches
+            .iter()
+            .map(|(name, (hits, misses))| {
+                let lookups = hits.saturating_add(*misses).max(1);
+                let stats = CacheStats {
+                    hits: *hits,
+                    misses: *misses,
+                    hit_rate: *hits as f64 / lookups as f64,
+                };
+                (*name, stats)
+            })
+            .collect();
+        MessageResult(MetricsSnapshot { timings, caches })
+    }
+}
+
+impl Handler<ResetMetrics> for Metrics {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ResetMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        *self = Self::default();
+    }
+}
+
+/// Sends a message to an actor, recording the time until it replies under the given name
+pub async fn send_timed<A, M>(
+    metrics: &Addr<Metrics>,
+    name: &'static str,
+    addr: &Addr<A>,
+    msg: M,
+) -> Result<M::Result, MailboxError>
+where
+    A: Actor + Handler<M>,
+    A::Context: ToEnvelope<A, M>,
+    M: Message + Send + 'static,
+    M::Result: Send,
+{
+    let start = Instant::now();
+    let result = addr.send(msg).await;
+    metrics.do_send(RecordTiming::new(name, start.elapsed()));
+    result
+}
+
+/// What was requested from the metrics window this frame
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetricsAction {
+    Export,
+    Reset,
+}
+
+/// A debug window showing the recorded metrics
+#[derive(Debug, Default)]
+pub struct MetricsWindow {
+    open: bool,
+}
+
+impl MetricsWindow {
+    pub const fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn render(
+        &mut self,
+        ctx: &EguiContext,
+        snapshot: &MetricsSnapshot,
+    ) -> Option<MetricsAction> {
+        let mut action = None;
+        Window::new("Metrics").open(&mut self.open).show(ctx, |ui| {
+            ui.heading("Timings (ms)");
+            Grid::new("metrics_timings").striped(true).show(ui, |ui| {
+                for header in ["Name", "Count", "Last", "Mean", "Max"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for (name, stats) in &snapshot.timings {
+                    ui.label(*name);
+                    ui.label(stats.count.to_string());
+                    ui.label(format!("{:.2}", stats.last_ms));
+                    ui.label(format!("{:.2}", stats.mean_ms));
+                    ui.label(format!("{:.2}", stats.max_ms));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.heading("Caches");
+            Grid::new("metrics_caches").striped(true).show(ui, |ui| {
+                for header in ["Name", "Hits", "Misses", "Hit rate"] {
+                    ui.strong(header);
+                }
+                ui.end_row();
+                for (name, stats) in &snapshot.caches {
+                    ui.label(*name);
+                    ui.label(stats.hits.to_string());
+                    ui.label(stats.misses.to_string());
+                    ui.label(format!("{:.1}%", stats.hit_rate * 100.0));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Export JSON…").clicked() {
+                    action = Some(MetricsAction::Export);
+                }
+                if ui.button("Reset").clicked() {
+                    action = Some(MetricsAction::Reset);
+                }
+            });
+        });
+        action
+    }
+}
+
+/// Asks the user where to save the metrics, then writes them there as JSON
+pub fn export_metrics(snapshot: MetricsSnapshot) {
+    tokio::spawn(async move {
+        let path = tokio::task::spawn_blocking(|| {
+            rfd::FileDialog::new()
+                .add_filter("JSON", &["json"])
+                .set_file_name("metrics.json")
+                .save_file()
+        })
+        .await;
+        if let Ok(Some(p)) = path {
+            let written = serde_json::to_string_pretty(&snapshot)
+                .map_err(|e| e.to_string())
+                .and_then(|json| std::fs::write(&p, json).map_err(|e| e.to_string()));
+            match written {
+                Ok(()) => info!("Exported metrics to {}", p.display()),
+                Err(e) => error!("Failed to export metrics: {e}"),
+            }
+        }
+    });
+}
@@ -1,48 +1,101 @@
+pub mod active_tool;
+pub mod adjacency_rule_window;
+pub mod asset_window;
+pub mod bookmarks;
+pub mod busy;
 pub mod central_panel_renderer;
 pub mod control_panel_renderer;
 pub mod map_loader;
 pub mod map_mode;
 pub mod map_textures;
+pub mod metrics;
+pub mod new_map_window;
+pub mod plugins;
 pub mod right_panel_renderer;
 pub mod root_path;
+pub mod search_renderer;
 pub mod selection;
+pub mod session;
 pub mod top_menu_renderer;
+pub mod validation_renderer;
 pub mod viewport;
 
+use crate::ui::busy::{render_busy_overlay, Busy, DismissRejection, GetBusyState};
 use crate::ui::central_panel_renderer::CentralPanelRenderer;
 use crate::ui::control_panel_renderer::ControlPanelRenderer;
 use crate::ui::map_mode::MapMode;
+use crate::ui::metrics::Metrics;
 use crate::ui::right_panel_renderer::RightPanelRenderer;
+use crate::ui::search_renderer::SearchRenderer;
+use crate::ui::session::Session;
 use crate::ui::top_menu_renderer::TopMenuRenderer;
+use crate::ui::validation_renderer::ValidationRenderer;
 use crate::ui::viewport::Viewport;
 use actix::Addr;
+use egui::Context;
+use world_gen::MapError;
 
 pub struct UiRenderer {
     pub top_menu_renderer: TopMenuRenderer,
     pub control_panel_renderer: ControlPanelRenderer,
     pub right_panel_renderer: RightPanelRenderer,
     pub central_panel_renderer: CentralPanelRenderer,
+    pub validation_renderer: ValidationRenderer,
+    pub search_renderer: SearchRenderer,
     pub map_mode: Addr<MapMode>,
     pub viewport: Addr<Viewport>,
+    pub busy: Addr<Busy>,
+    pub metrics: Addr<Metrics>,
+    pub session: Session,
 }
 
 impl UiRenderer {
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub const fn new(
         top_menu_renderer: TopMenuRenderer,
         control_panel_renderer: ControlPanelRenderer,
         right_panel_renderer: RightPanelRenderer,
         central_panel_renderer: CentralPanelRenderer,
+        validation_renderer: ValidationRenderer,
+        search_renderer: SearchRenderer,
         map_mode: Addr<MapMode>,
         viewport: Addr<Viewport>,
+        busy: Addr<Busy>,
+        metrics: Addr<Metrics>,
+        session: Session,
     ) -> Self {
         Self {
             top_menu_renderer,
             control_panel_renderer,
             right_panel_renderer,
             central_panel_renderer,
+            validation_renderer,
+            search_renderer,
             map_mode,
             viewport,
+            busy,
+            metrics,
+            session,
         }
     }
+
+    /// Swaps the open map for the one in the new root directory once the root directory changes
+    pub async fn switch_root_if_changed(&mut self) -> Result<(), MapError> {
+        if !self.top_menu_renderer.root_path_changed {
+            return Ok(());
+        }
+        self.top_menu_renderer.root_path_changed = false;
+        let root_path = self.top_menu_renderer.new_root_path.clone();
+        self.session.switch_root(root_path).await
+    }
+
+    /// Renders the busy overlay over the panels while an operation is in progress
+    pub async fn render_busy_overlay(&self, ctx: &Context) -> Result<(), MapError> {
+        let state = self.busy.send(GetBusyState).await?;
+        if render_busy_overlay(ctx, state) {
+            self.busy.do_send(DismissRejection);
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,101 @@
+use egui::{Context, DragValue, Grid, Slider, Ui, Window};
+use std::path::PathBuf;
+use world_gen::generation::world::WorldConfig;
+
+/// The size of the map is set in steps of this many pixels, as the game requires
+const MAP_SIZE_STEP: u32 = 256;
+
+/// A wizard for the settings of a new world, which is generated into a folder chosen once the
+/// settings are confirmed.
+#[derive(Debug, Default)]
+pub struct NewMapWindow {
+    open: bool,
+    config: WorldConfig,
+    base_game: String,
+}
+
+impl NewMapWindow {
+    pub const fn open(&mut self) {
+        self.open = true;
+    }
+
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Renders the window, returning the config to generate once the user confirms it
+    pub fn render(&mut self, ctx: &Context) -> Option<WorldConfig> {
+        let Self {
+            open,
+            config,
+            base_game,
+        } = self;
+        let mut generate = false;
+        Window::new("New map").open(open).show(ctx, |ui| {
+            render_settings(config, ui);
+            ui.separator();
+            ui.label("Base game folder, leave empty to write minimal game files");
+            ui.text_edit_singleline(base_game);
+            ui.separator();
+            generate = ui.button("Generate…").clicked();
+        });
+        if !generate {
+            return None;
+        }
+        self.open = false;
+        let mut config = self.config.clone();
+        config.base_game = (!self.base_game.is_empty()).then(|| PathBuf::from(&self.base_game));
+        Some(config)
+    }
+}
+
+fn render_settings(config: &mut WorldConfig, ui: &mut Ui) {
+    Grid::new("new_map_settings").show(ui, |ui| {
+        ui.label("Seed");
+        ui.add(DragValue::new(&mut config.seed));
+        ui.end_row();
+        for (label, size) in [("Width", &mut config.width), ("Height", &mut config.height)] {
+            ui.label(label);
+            ui.add(
+                DragValue::new(size)
+                    .speed(MAP_SIZE_STEP)
+                    .clamp_range(MAP_SIZE_STEP..=MAP_SIZE_STEP * 64),
+            );
+            *size = (*size / MAP_SIZE_STEP).max(1) * MAP_SIZE_STEP;
+            ui.end_row();
+        }
+        ui.label("Land fraction");
+        ui.add(Slider::new(&mut config.land_fraction, 0.0..=1.0));
+        ui.end_row();
+        ui.label("Provinces");
+        ui.add(DragValue::new(&mut config.province_count).clamp_range(1..=100_000));
+        ui.end_row();
+        ui.label("Provinces per state");
+        ui.add(Slider::new(&mut config.provinces_per_state, 1..=32));
+        ui.end_row();
+        ui.label("States per region");
+        ui.add(Slider::new(&mut config.states_per_region, 1..=16));
+        ui.end_row();
+        ui.label("Cold temperature");
+        ui.add(Slider::new(&mut config.climate.cold_temperature, 0.0..=1.0));
+        ui.end_row();
+        ui.label("Warm temperature");
+        ui.add(Slider::new(&mut config.climate.warm_temperature, 0.0..=1.0));
+        ui.end_row();
+        ui.label("North latitude");
+        ui.add(Slider::new(
+            &mut config.climate.north_latitude,
+            -90.0..=90.0,
+        ));
+        ui.end_row();
+        ui.label("South latitude");
+        ui.add(Slider::new(
+            &mut config.climate.south_latitude,
+            -90.0..=90.0,
+        ));
+        ui.end_row();
+        ui.label("Wrap horizontally");
+        ui.checkbox(&mut config.wrap_horizontally, "");
+        ui.end_row();
+    });
+}
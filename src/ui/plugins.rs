@@ -0,0 +1,294 @@
+use actix::{Actor, Addr, AsyncContext, Context as ActixContext, Handler, Message, MessageResult};
+use egui::{Context, TextureFilter, TextureHandle};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use world_gen::map::{Map, RenderLayer};
+use world_gen::plugin::PluginRegistry;
+use world_gen::texture::texture_image;
+
+/// A request to get the registered plugins
+#[derive(Message)]
+#[rtype(result = "Arc<PluginRegistry>")]
+#[non_exhaustive]
+pub struct GetPluginRegistry;
+
+/// A request to get the indices of the enabled layers
+#[derive(Message)]
+#[rtype(result = "Vec<usize>")]
+#[non_exhaustive]
+pub struct GetEnabledLayers;
+
+/// A request to enable a layer, rendering it if it has not been rendered yet
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct EnableLayer {
+    index: usize,
+    map: Addr<Map>,
+    context: Context,
+}
+
+impl EnableLayer {
+    pub const fn new(index: usize, map: Addr<Map>, context: Context) -> Self {
+        Self {
+            index,
+            map,
+            context,
+        }
+    }
+}
+
+/// A request to disable a layer
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct DisableLayer(pub usize);
+
+/// A request to render the enabled layers again after the map has changed
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct RefreshLayers {
+    map: Addr<Map>,
+    context: Context,
+}
+
+impl RefreshLayers {
+    pub const fn new(map: Addr<Map>, context: Context) -> Self {
+        Self { map, context }
+    }
+}
+
+/// The opacity of a layer that has not been given one
+pub const DEFAULT_OPACITY: f32 = 1.0;
+
+/// The texture of an enabled layer and how opaque it is drawn over the map
+#[derive(Clone)]
+#[non_exhaustive]
+pub struct LayerTexture {
+    /// The layer as it was rendered
+    pub texture: TextureHandle,
+    /// From 0.0 for an invisible layer to 1.0 for the layer as it was rendered
+    pub opacity: f32,
+}
+
+/// A request to get the textures of the enabled layers that have finished rendering, in the
+/// order they are drawn over the map
+#[derive(Message)]
+#[rtype(result = "Vec<LayerTexture>")]
+#[non_exhaustive]
+pub struct GetLayerTextures;
+
+/// A request to set how opaque a layer is drawn over the map
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct SetLayerOpacity {
+    index: usize,
+    opacity: f32,
+}
+
+impl SetLayerOpacity {
+    pub const fn new(index: usize, opacity: f32) -> Self {
+        Self { index, opacity }
+    }
+}
+
+/// A request to get the opacity of each enabled layer by its index
+#[derive(Message)]
+#[rtype(result = "HashMap<usize, f32>")]
+#[non_exhaustive]
+pub struct GetLayerOpacities;
+
+/// A request to disable every layer and drop their textures, such as when the map is unloaded
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ClearLayers;
+
+/// A request to update the texture of a layer
+#[derive(Message)]
+#[rtype(result = "()")]
+struct UpdateLayerTexture(usize, Option<TextureHandle>);
+
+pub struct Plugins {
+    registry: Arc<PluginRegistry>,
+    enabled_layers: Vec<usize>,
+    layer_textures: HashMap<usize, TextureHandle>,
+    layer_handles: HashMap<usize, JoinHandle<()>>,
+    /// The opacities the layers were given, which are kept while a layer is disabled
+    layer_opacities: HashMap<usize, f32>,
+}
+
+impl Plugins {
+    pub fn new(registry: PluginRegistry) -> Self {
+        Self {
+            registry: Arc::new(registry),
+            enabled_layers: Vec::new(),
+            layer_textures: HashMap::new(),
+            layer_handles: HashMap::new(),
+            layer_opacities: HashMap::new(),
+        }
+    }
+
+    /// The opacity a layer is drawn with
+    fn opacity(&self, index: usize) -> f32 {
+        self.layer_opacities
+            .get(&index)
+            .copied()
+            .unwrap_or(DEFAULT_OPACITY)
+    }
+
+    /// Renders a layer in the background, replacing its texture once it is done
+    fn render_layer(
+        &mut self,
+        index: usize,
+        map: Addr<Map>,
+        context: Context,
+        ctx: &mut ActixContext<Self>,
+    ) {
+        let layer = match self.registry.layers().get(index) {
+            Some(l) => Arc::clone(l),
+            None => return,
+        };
+        let self_addr = ctx.address();
+        let handle = tokio::spawn(async move {
+            let texture = map.send(RenderLayer::new(layer)).await.ok().map(|image| {
+                let color_image = texture_image(&image, context.input().max_texture_side);
+                context.load_texture("layer", color_image, TextureFilter::Nearest)
+            });
+            self_addr.do_send(UpdateLayerTexture(index, texture));
+        });
+        self.layer_handles.insert(index, handle);
+    }
+}
+
+impl Actor for Plugins {
+    type Context = ActixContext<Self>;
+}
+
+impl Handler<GetPluginRegistry> for Plugins {
+    type Result = Arc<PluginRegistry>;
+
+    fn handle(&mut self, _msg: GetPluginRegistry, _ctx: &mut Self::Context) -> Self::Result {
+        Arc::clone(&self.registry)
+    }
+}
+
+impl Handler<GetEnabledLayers> for Plugins {
+    type Result = MessageResult<GetEnabledLayers>;
+
+    fn handle(&mut self, _msg: GetEnabledLayers, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.enabled_layers.clone())
+    }
+}
+
+impl Handler<EnableLayer> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, msg: EnableLayer, ctx: &mut Self::Context) -> Self::Result {
+        if self.registry.layers().get(msg.index).is_none() {
+            return;
+        }
+        if !self.enabled_layers.contains(&msg.index) {
+            self.enabled_layers.push(msg.index);
+        }
+        if self.layer_textures.contains_key(&msg.index)
+            || self.layer_handles.contains_key(&msg.index)
+        {
+            return;
+        }
+        self.render_layer(msg.index, msg.map, msg.context, ctx);
+    }
+}
+
+impl Handler<RefreshLayers> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, msg: RefreshLayers, ctx: &mut Self::Context) -> Self::Result {
+        // Disabled layers are rendered again when they are next enabled
+        self.layer_textures.clear();
+        for (_index, handle) in self.layer_handles.drain() {
+            handle.abort();
+        }
+        for index in self.enabled_layers.clone() {
+            self.render_layer(index, msg.map.clone(), msg.context.clone(), ctx);
+        }
+    }
+}
+
+impl Handler<ClearLayers> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ClearLayers, _ctx: &mut Self::Context) -> Self::Result {
+        self.enabled_layers.clear();
+        self.layer_textures.clear();
+        for (_index, handle) in self.layer_handles.drain() {
+            handle.abort();
+        }
+    }
+}
+
+impl Handler<DisableLayer> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, msg: DisableLayer, _ctx: &mut Self::Context) -> Self::Result {
+        self.enabled_layers.retain(|index| *index != msg.0);
+    }
+}
+
+impl Handler<GetLayerTextures> for Plugins {
+    type Result = MessageResult<GetLayerTextures>;
+
+    fn handle(&mut self, _msg: GetLayerTextures, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.enabled_layers
+                .iter()
+                .filter_map(|index| {
+                    Some(LayerTexture {
+                        texture: self.layer_textures.get(index)?.clone(),
+                        opacity: self.opacity(*index),
+                    })
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Handler<SetLayerOpacity> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetLayerOpacity, _ctx: &mut Self::Context) -> Self::Result {
+        self.layer_opacities
+            .insert(msg.index, msg.opacity.clamp(0.0, 1.0));
+    }
+}
+
+impl Handler<GetLayerOpacities> for Plugins {
+    type Result = MessageResult<GetLayerOpacities>;
+
+    fn handle(&mut self, _msg: GetLayerOpacities, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.enabled_layers
+                .iter()
+                .map(|index| (*index, self.opacity(*index)))
+                .collect(),
+        )
+    }
+}
+
+impl Handler<UpdateLayerTexture> for Plugins {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateLayerTexture, _ctx: &mut Self::Context) -> Self::Result {
+        let UpdateLayerTexture(index, texture) = msg;
+        self.layer_handles.remove(&index);
+        match texture {
+            Some(t) => {
+                self.layer_textures.insert(index, t);
+            }
+            None => self.enabled_layers.retain(|i| *i != index),
+        }
+    }
+}
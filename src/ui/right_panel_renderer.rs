@@ -1,23 +1,36 @@
 use crate::ui::map_loader::GetMap;
 use crate::ui::map_mode::GetMapMode;
+use crate::ui::root_path::{GetRootPath, RootPath};
 use crate::ui::selection::{
     GetSelectedPoint, GetSelectedProvince, GetSelectedState, GetSelectedStrategicRegion, Selection,
     SetSelectedProvince, SetSelectedState, SetSelectedStrategicRegion,
 };
 use crate::{MapError, MapLoader, MapMode};
 use actix::Addr;
-use egui::{Context, Pos2, SidePanel, TopBottomPanel, Ui};
+use egui::{
+    Color32, ComboBox, Context, DragValue, Pos2, RichText, SidePanel, TextEdit, TopBottomPanel, Ui,
+    WidgetText,
+};
+use image::Rgb;
 use indicatif::InMemoryTerm;
-use log::{debug, trace};
+use log::{debug, error, trace};
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::hash::Hash;
-use world_gen::components::prelude::{Definition, StrategicRegion};
-use world_gen::components::state::State;
-use world_gen::components::wrappers::Continent;
+use world_gen::components::localisation::victory_points_key;
+use world_gen::components::prelude::{Adjacency, Definition, StateBuilding, StrategicRegion};
+use world_gen::components::state::{State, StateEdit};
+use world_gen::components::wrappers::{Continent, Manpower, StateCategoryName, StateId};
+use world_gen::legend::Legend;
 use world_gen::map::{
-    GetContinentFromIndex, GetProvinceDefinitionFromId, GetProvinceIdFromPoint, GetStateFromId,
-    GetStateIdFromPoint, GetStrategicRegionFromId, GetStrategicRegionIdFromPoint, Map,
+    EditState, GetContinentFromIndex, GetContinentLegend, GetLocalisedTexts, GetManpowerLegend,
+    GetPoliticalLegend, GetProvinceAdjacencies, GetProvinceDefinitionFromId,
+    GetProvinceIdFromPoint, GetStateBuildings, GetStateCategories, GetStateCategoryLegend,
+    GetStateFromId, GetStateIdFromPoint, GetStateProvenance, GetStrategicRegionFromId,
+    GetStrategicRegionIdFromPoint, GetStrategicRegionProvenance, GetWeatherLegend, Map,
+    SetLocalisedText,
 };
+use world_gen::provenance::Provenance;
 use world_gen::MapDisplayMode;
 
 struct SelectedRegions {
@@ -27,10 +40,32 @@ struct SelectedRegions {
     selected_point: Option<Pos2>,
 }
 
+/// Where the selected region and the entities listed with it were loaded from
+#[derive(Default)]
+struct SelectedProvenance {
+    /// The file of the selected state or strategic region
+    file: Option<Provenance>,
+    /// The adjacencies of the selected province
+    adjacencies: Vec<(Adjacency, Option<Provenance>)>,
+    /// The buildings of the selected state
+    buildings: Vec<(StateBuilding, Option<Provenance>)>,
+}
+
+/// What was requested from the info panel this frame
+enum InfoPanelAction {
+    /// An edit to the selected state
+    EditState(StateEdit),
+    /// Opening the file an entity was loaded from in an external editor
+    OpenFile(Provenance),
+    /// An edit to the localised text of a key, such as the name of the selected state
+    EditLocalisation(String, String),
+}
+
 pub struct RightPanelRenderer {
     map_mode: Addr<MapMode>,
     selection: Addr<Selection>,
     map_loader: Addr<MapLoader>,
+    root_path: Addr<RootPath>,
     terminal: InMemoryTerm,
 }
 
@@ -40,12 +75,14 @@ impl RightPanelRenderer {
         map_mode: Addr<MapMode>,
         selection: Addr<Selection>,
         map_loader: Addr<MapLoader>,
+        root_path: Addr<RootPath>,
         terminal: InMemoryTerm,
     ) -> Self {
         Self {
             map_mode,
             selection,
             map_loader,
+            root_path,
             terminal,
         }
     }
@@ -67,13 +104,80 @@ impl RightPanelRenderer {
             } else {
                 None
             };
+        let categories: Vec<StateCategoryName> = match (&map_addr, map_mode) {
+            (
+                Some(m),
+                MapDisplayMode::States
+                | MapDisplayMode::Manpower
+                | MapDisplayMode::Political
+                | MapDisplayMode::StateCategories,
+            ) => m.send(GetStateCategories).await?,
+            _ => Vec::new(),
+        };
+        let legend = match (&map_addr, map_mode) {
+            (Some(m), MapDisplayMode::Continents) => m.send(GetContinentLegend).await?,
+            (Some(m), MapDisplayMode::Weather) => m.send(GetWeatherLegend).await?,
+            (Some(m), MapDisplayMode::Manpower) => m.send(GetManpowerLegend).await?,
+            (Some(m), MapDisplayMode::Political) => m.send(GetPoliticalLegend).await?,
+            (Some(m), MapDisplayMode::StateCategories) => m.send(GetStateCategoryLegend).await?,
+            _ => Legend::default(),
+        };
+        let provenance = match &map_addr {
+            Some(m) => get_selected_provenance(m, map_mode, &selected_regions).await?,
+            None => SelectedProvenance::default(),
+        };
+        let names = match (&map_addr, &selected_regions.selected_state) {
+            (Some(m), Some(state)) => get_localised_names(m, state).await?,
+            _ => HashMap::new(),
+        };
+        let mut action = None;
         SidePanel::right("right_panel")
             .resizable(true)
             .min_width(200.0)
             .show(ctx, |ui| {
-                render_info_panel(map_mode, &map_addr, &selected_regions, continent, ui);
+                action = render_info_panel(
+                    map_mode,
+                    &map_addr,
+                    &selected_regions,
+                    &provenance,
+                    &names,
+                    continent,
+                    &categories,
+                    &legend,
+                    ui,
+                );
                 self.render_log_panel(ui);
             });
+        match (action, &map_addr, &selected_regions.selected_state) {
+            (Some(InfoPanelAction::EditState(edit)), Some(map), Some(state)) => {
+                self.edit_state(map, state.id, edit).await?;
+            }
+            (Some(InfoPanelAction::OpenFile(provenance)), _, _) => {
+                if let Some(root_path) = self.root_path.send(GetRootPath).await? {
+                    if let Err(e) = provenance.open_in_editor(&root_path) {
+                        error!("Failed to open {provenance}: {e}");
+                    }
+                }
+            }
+            (Some(InfoPanelAction::EditLocalisation(key, text)), Some(map), _) => {
+                map.send(SetLocalisedText::new(key, text)).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Applies an edit to a state on the map and selects the edited state
+    async fn edit_state(
+        &self,
+        map: &Addr<Map>,
+        id: StateId,
+        edit: StateEdit,
+    ) -> Result<(), MapError> {
+        match map.send(EditState::new(id, edit)).await? {
+            Ok(state) => self.selection.send(SetSelectedState::new(state)).await?,
+            Err(e) => error!("Failed to edit state {id}: {e}"),
+        }
         Ok(())
     }
 
@@ -85,8 +189,16 @@ impl RightPanelRenderer {
     ) -> Result<(), MapError> {
         if let (Some(map), Some(point)) = (map_addr.clone(), selected_regions.selected_point) {
             match map_mode {
-                MapDisplayMode::HeightMap | MapDisplayMode::Terrain | MapDisplayMode::Rivers => {}
-                MapDisplayMode::Provinces => {
+                MapDisplayMode::HeightMap
+                | MapDisplayMode::Elevation
+                | MapDisplayMode::Terrain
+                | MapDisplayMode::Rivers
+                | MapDisplayMode::Trees
+                | MapDisplayMode::NormalMap
+                | MapDisplayMode::Cities => {}
+                MapDisplayMode::Provinces
+                | MapDisplayMode::Continents
+                | MapDisplayMode::DefinitionTerrain => {
                     if selected_regions.selected_province.is_none() {
                         if let Some(province_id) =
                             map.send(GetProvinceIdFromPoint::new(point)).await?
@@ -100,7 +212,7 @@ impl RightPanelRenderer {
                         }
                     }
                 }
-                MapDisplayMode::StrategicRegions => {
+                MapDisplayMode::StrategicRegions | MapDisplayMode::Weather => {
                     if selected_regions.selected_strategic_region.is_none() {
                         if let Some(sr_id) =
                             map.send(GetStrategicRegionIdFromPoint::new(point)).await?
@@ -114,7 +226,11 @@ impl RightPanelRenderer {
                         }
                     }
                 }
-                MapDisplayMode::States => {
+                MapDisplayMode::States
+                | MapDisplayMode::BuildingDensity
+                | MapDisplayMode::Manpower
+                | MapDisplayMode::Political
+                | MapDisplayMode::StateCategories => {
                     if selected_regions.selected_state.is_none() {
                         if let Some(s_id) = map.send(GetStateIdFromPoint::new(point)).await? {
                             if let Some(s) = map.send(GetStateFromId::new(s_id)).await? {
@@ -161,13 +277,78 @@ impl RightPanelRenderer {
     }
 }
 
+/// Gets where the selected region of the map mode and the entities listed with it were loaded
+/// from
+async fn get_selected_provenance(
+    map: &Addr<Map>,
+    map_mode: MapDisplayMode,
+    selected_regions: &SelectedRegions,
+) -> Result<SelectedProvenance, MapError> {
+    let mut provenance = SelectedProvenance::default();
+    match map_mode {
+        MapDisplayMode::Provinces | MapDisplayMode::DefinitionTerrain => {
+            if let Some(definition) = &selected_regions.selected_province {
+                provenance.adjacencies =
+                    map.send(GetProvinceAdjacencies::new(definition.id)).await?;
+            }
+        }
+        MapDisplayMode::States
+        | MapDisplayMode::BuildingDensity
+        | MapDisplayMode::Manpower
+        | MapDisplayMode::Political
+        | MapDisplayMode::StateCategories => {
+            if let Some(state) = &selected_regions.selected_state {
+                provenance.file = map.send(GetStateProvenance::new(state.id)).await?;
+                provenance.buildings = map.send(GetStateBuildings::new(state.id)).await?;
+            }
+        }
+        MapDisplayMode::StrategicRegions | MapDisplayMode::Weather => {
+            if let Some(sr) = &selected_regions.selected_strategic_region {
+                provenance.file = map.send(GetStrategicRegionProvenance::new(sr.id)).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(provenance)
+}
+
+/// Gets the localised names of the selected state and its victory points, by their keys
+async fn get_localised_names(
+    map: &Addr<Map>,
+    state: &State,
+) -> Result<HashMap<String, String>, MapError> {
+    let mut keys = vec![state.name.0.clone()];
+    if let Some(history) = &state.history {
+        keys.extend(
+            history
+                .victory_points
+                .iter()
+                .map(|(id, _)| victory_points_key(*id)),
+        );
+    }
+    let texts = map.send(GetLocalisedTexts::new(keys.clone())).await?;
+    Ok(keys
+        .into_iter()
+        .zip(texts)
+        .filter_map(|(key, text)| Some((key, text?)))
+        .collect())
+}
+
+/// Renders the information about the selected region, returning the edit made to the selected
+/// state or the file to open if there is one
+#[allow(clippy::too_many_arguments)]
 fn render_info_panel(
     map_mode: MapDisplayMode,
     map_addr: &Option<Addr<Map>>,
     selected_regions: &SelectedRegions,
+    provenance: &SelectedProvenance,
+    names: &HashMap<String, String>,
     continent: Option<Continent>,
+    categories: &[StateCategoryName],
+    legend: &Legend,
     ui: &mut Ui,
-) {
+) -> Option<InfoPanelAction> {
+    let mut action = None;
     TopBottomPanel::top("info_panel")
         .min_height(200.0)
         .max_height(600.0)
@@ -176,32 +357,92 @@ fn render_info_panel(
             egui::ScrollArea::vertical()
                 .auto_shrink([true, false])
                 .show(ui, |ui| match map_mode {
-                    MapDisplayMode::Provinces => {
-                        render_province_info(map_addr, selected_regions, continent, ui);
+                    MapDisplayMode::Provinces | MapDisplayMode::DefinitionTerrain => {
+                        action = render_province_info(
+                            map_addr,
+                            selected_regions,
+                            provenance,
+                            continent,
+                            ui,
+                        );
                     }
-                    MapDisplayMode::States => {
-                        render_state_info(map_addr, selected_regions, ui);
+                    MapDisplayMode::States | MapDisplayMode::BuildingDensity => {
+                        action = render_state_info(
+                            map_addr,
+                            selected_regions,
+                            provenance,
+                            names,
+                            categories,
+                            ui,
+                        );
                     }
                     MapDisplayMode::StrategicRegions => {
-                        render_strategic_region_info(map_addr, selected_regions, ui);
+                        action = render_strategic_region_info(
+                            map_addr,
+                            selected_regions,
+                            provenance,
+                            ui,
+                        );
+                    }
+                    MapDisplayMode::Manpower
+                    | MapDisplayMode::Political
+                    | MapDisplayMode::StateCategories => {
+                        for entry in &legend.entries {
+                            render_legend_entry(entry.color, entry.name.as_str(), ui);
+                        }
+                        ui.separator();
+                        action = render_state_info(
+                            map_addr,
+                            selected_regions,
+                            provenance,
+                            names,
+                            categories,
+                            ui,
+                        );
+                    }
+                    MapDisplayMode::Weather => {
+                        action =
+                            render_weather_info(map_addr, selected_regions, provenance, legend, ui);
+                    }
+                    MapDisplayMode::Continents => {
+                        render_continent_info(selected_regions, legend, ui);
                     }
                     MapDisplayMode::HeightMap
+                    | MapDisplayMode::Elevation
                     | MapDisplayMode::Terrain
-                    | MapDisplayMode::Rivers => {}
+                    | MapDisplayMode::Rivers
+                    | MapDisplayMode::Trees
+                    | MapDisplayMode::NormalMap
+                    | MapDisplayMode::Cities => {}
                     m => {
                         ui.label(format!("Unknown map mode: {m}"));
                     }
                 });
         });
+    action
+}
+
+/// Renders the file an entity was loaded from with a button to open it, returning the file if
+/// the button was clicked
+fn render_provenance(ui: &mut Ui, provenance: &Provenance) -> Option<InfoPanelAction> {
+    ui.horizontal(|ui| {
+        ui.label(format!("Source: {provenance}"));
+        ui.button("Open in external editor")
+            .clicked()
+            .then(|| InfoPanelAction::OpenFile(provenance.clone()))
+    })
+    .inner
 }
 
 fn render_strategic_region_info(
     map_addr: &Option<Addr<Map>>,
     selected_regions: &SelectedRegions,
+    provenance: &SelectedProvenance,
     ui: &mut Ui,
-) {
+) -> Option<InfoPanelAction> {
     ui.heading("Strategic Region Information");
     ui.separator();
+    let mut action = None;
     if let (Some(_), Some(_), Some(sr)) = (
         map_addr,
         selected_regions.selected_point,
@@ -209,6 +450,9 @@ fn render_strategic_region_info(
     ) {
         ui.label(format!("Id: {:?}", sr.id.0));
         ui.label(format!("Name: {:?}", sr.name.0));
+        if let Some(file) = &provenance.file {
+            action = render_provenance(ui, file);
+        }
         let mut provinces = sr.provinces.iter().collect::<Vec<_>>();
         provinces.sort();
         list_items(
@@ -254,26 +498,41 @@ fn render_strategic_region_info(
                 });
         });
     }
+    action
 }
 
+/// Renders the information about the selected state, with the manpower and category editable,
+/// along with the localised names of the state and its victory points.  Returns the edit made to
+/// the state or its names, or the file to open if there is one.
 fn render_state_info(
     map_addr: &Option<Addr<Map>>,
     selected_regions: &SelectedRegions,
+    provenance: &SelectedProvenance,
+    names: &HashMap<String, String>,
+    categories: &[StateCategoryName],
     ui: &mut Ui,
-) {
+) -> Option<InfoPanelAction> {
     ui.heading("State Information");
     ui.separator();
+    let mut edit = None;
+    let mut action = None;
     if let (Some(_), Some(_), Some(state)) = (
         map_addr,
         selected_regions.selected_point,
         &selected_regions.selected_state,
     ) {
         ui.label(format!("Id: {:?}", state.id.0));
-        ui.label(format!("Name: {:?}", state.name.0));
-        ui.label(format!(
-            "Manpower: {:?}",
-            state.manpower[state.manpower.len() - 1].0
-        ));
+        action = render_localised_name(ui, "Name:", &state.name.0, names);
+        if let Some(file) = &provenance.file {
+            action = render_provenance(ui, file).or(action.take());
+        }
+        ui.horizontal(|ui| {
+            ui.label("Manpower:");
+            let mut manpower = state.current_manpower().map_or(0, |m| m.0);
+            if ui.add(DragValue::new(&mut manpower).speed(100.0)).changed() {
+                edit = Some(StateEdit::Manpower(Manpower::from(manpower)));
+            }
+        });
         if let Some(supplies) = state.local_supplies {
             ui.label(format!("Local Supplies: {:?}", supplies.0));
         }
@@ -286,10 +545,18 @@ fn render_state_info(
         if let Some(impassable) = state.impassable {
             ui.label(format!("Impassable: {:?}", impassable));
         }
-        ui.label(format!(
-            "Category: {:?}",
-            state.state_category[state.state_category.len() - 1].0
-        ));
+        let current_category = state.current_category();
+        let mut category = current_category.cloned();
+        ComboBox::from_label("Category")
+            .selected_text(current_category.map_or("", |c| c.0.as_str()))
+            .show_ui(ui, |ui| {
+                for option in categories {
+                    ui.selectable_value(&mut category, Some(option.clone()), &option.0);
+                }
+            });
+        if category.as_ref() != current_category {
+            edit = category.map(StateEdit::Category);
+        }
         if let Some(history) = &state.history {
             ui.collapsing("History", |ui| {
                 ui.label(format!("Owner: {:?}", history.owner.0));
@@ -301,7 +568,10 @@ fn render_state_info(
                         .auto_shrink([true, true])
                         .show(ui, |ui| {
                             for (id, vp) in &history.victory_points {
-                                ui.label(format!("{:?}: {:?}", id.0, vp.0));
+                                let label = format!("{:?}: {:?}", id.0, vp.0);
+                                let key = victory_points_key(*id);
+                                action = render_localised_name(ui, &label, &key, names)
+                                    .or(action.take());
                             }
                         });
                 });
@@ -310,7 +580,45 @@ fn render_state_info(
         let mut provinces = state.provinces.iter().collect::<Vec<_>>();
         provinces.sort();
         list_items(ui, &provinces, "Provinces", "state_provinces_list");
+        ui.collapsing("Buildings", |ui| {
+            egui::ScrollArea::vertical()
+                .auto_shrink([true, true])
+                .id_source("state_buildings_list")
+                .show(ui, |ui| {
+                    for (building, source) in &provenance.buildings {
+                        ui.label(format!(
+                            "{} at ({:.2}, {:.2})",
+                            building.building_id.0, building.x, building.z
+                        ));
+                        if let Some(source) = source {
+                            action = render_provenance(ui, source).or(action.take());
+                        }
+                    }
+                });
+        });
     }
+    edit.map(InfoPanelAction::EditState).or(action)
+}
+
+/// Renders the localised name of a key as editable text, hinting at the key while it has no
+/// name.  Returns the edit made to the name if there is one.
+fn render_localised_name(
+    ui: &mut Ui,
+    label: &str,
+    key: &str,
+    names: &HashMap<String, String>,
+) -> Option<InfoPanelAction> {
+    let mut name = names.get(key).cloned().unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let response = ui
+            .add(TextEdit::singleline(&mut name).hint_text(key))
+            .on_hover_text(key);
+        response
+            .changed()
+            .then(|| InfoPanelAction::EditLocalisation(key.to_owned(), name.clone()))
+    })
+    .inner
 }
 
 fn list_items<T: Display>(ui: &mut Ui, list: &[T], heading: &str, id: impl Hash) {
@@ -326,14 +634,60 @@ fn list_items<T: Display>(ui: &mut Ui, list: &[T], heading: &str, id: impl Hash)
     });
 }
 
+/// Renders the colors of the continents on the map of continents, and the continent of the
+/// selected province, so that land without a continent and sea with one stand out
+fn render_continent_info(selected_regions: &SelectedRegions, legend: &Legend, ui: &mut Ui) {
+    ui.heading("Continents");
+    ui.separator();
+    for entry in &legend.entries {
+        render_legend_entry(entry.color, format!("{}: {}", entry.id, entry.name), ui);
+    }
+    if let Some(definition) = &selected_regions.selected_province {
+        ui.separator();
+        ui.label(format!(
+            "Province {}: {:?} on continent {}",
+            definition.id, definition.province_type, definition.continent.0
+        ));
+    }
+}
+
+/// Renders the colors of the weather map above the information about the selected strategic
+/// region, whose weather periods give the colors
+fn render_weather_info(
+    map_addr: &Option<Addr<Map>>,
+    selected_regions: &SelectedRegions,
+    provenance: &SelectedProvenance,
+    legend: &Legend,
+    ui: &mut Ui,
+) -> Option<InfoPanelAction> {
+    ui.heading("Weather");
+    ui.separator();
+    for entry in &legend.entries {
+        render_legend_entry(entry.color, entry.name.as_str(), ui);
+    }
+    ui.separator();
+    render_strategic_region_info(map_addr, selected_regions, provenance, ui)
+}
+
+/// Renders a swatch of a color of a map beside what the color stands for
+fn render_legend_entry(color: Rgb<u8>, text: impl Into<WidgetText>, ui: &mut Ui) {
+    let Rgb([r, g, b]) = color;
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("■").color(Color32::from_rgb(r, g, b)));
+        ui.label(text);
+    });
+}
+
 fn render_province_info(
     map_addr: &Option<Addr<Map>>,
     selected_regions: &SelectedRegions,
+    provenance: &SelectedProvenance,
     continent: Option<Continent>,
     ui: &mut Ui,
-) {
+) -> Option<InfoPanelAction> {
     ui.heading("Province Information");
     ui.separator();
+    let mut action = None;
     if let (Some(_), Some(_), Some(definition)) = (
         map_addr,
         selected_regions.selected_point,
@@ -348,5 +702,20 @@ fn render_province_info(
         ui.label(format!("Coastal: {:?}", definition.coastal.0));
         ui.label(format!("Terrain: {:?}", definition.terrain.0));
         continent.map(|c| ui.label(format!("Continent: {:?}", c.0)));
+        ui.collapsing("Adjacencies", |ui| {
+            for (adjacency, source) in &provenance.adjacencies {
+                ui.label(match adjacency.comment.as_ref().filter(|c| !c.is_empty()) {
+                    Some(comment) => format!("{} to {} ({comment})", adjacency.from, adjacency.to),
+                    None => format!("{} to {}", adjacency.from, adjacency.to),
+                });
+                match source {
+                    Some(source) => action = render_provenance(ui, source).or(action.take()),
+                    None => {
+                        ui.label("Source: added since the map was loaded");
+                    }
+                }
+            }
+        });
     }
+    action
 }
@@ -1,11 +1,13 @@
-use actix::{Actor, AsyncContext, Context, Handler, Message};
+use crate::ui::busy::{Busy, End, Operation};
+use actix::{Actor, Addr, AsyncContext, Context, Handler, Message};
 use log::{debug, trace};
 use std::path::PathBuf;
 use tokio::task::JoinHandle;
 
-/// A request to set the root path
+/// A request to set the root path by asking the user for a folder.  Returns false if a folder is
+/// already being asked for.
 #[derive(Message)]
-#[rtype(result = "()")]
+#[rtype(result = "bool")]
 #[non_exhaustive]
 pub struct SetRootPath;
 
@@ -27,10 +29,20 @@ impl UpdateRootPath {
     }
 }
 
-#[derive(Default)]
 pub struct RootPath {
     root_path: Option<PathBuf>,
     root_path_handle: Option<JoinHandle<()>>,
+    busy: Addr<Busy>,
+}
+
+impl RootPath {
+    pub const fn new(busy: Addr<Busy>) -> Self {
+        Self {
+            root_path: None,
+            root_path_handle: None,
+            busy,
+        }
+    }
 }
 
 impl Actor for RootPath {
@@ -38,12 +50,13 @@ impl Actor for RootPath {
 }
 
 impl Handler<SetRootPath> for RootPath {
-    type Result = ();
+    type Result = bool;
 
     fn handle(&mut self, _msg: SetRootPath, ctx: &mut Self::Context) -> Self::Result {
         trace!("SetRootPath");
         if self.root_path_handle.is_some() {
-            return;
+            debug!("A root folder is already being chosen");
+            return false;
         }
         let self_addr = ctx.address();
         let handle = tokio::task::spawn_blocking(move || {
@@ -51,6 +64,7 @@ impl Handler<SetRootPath> for RootPath {
             self_addr.do_send(UpdateRootPath::new(path));
         });
         self.root_path_handle = Some(handle);
+        true
     }
 }
 
@@ -69,6 +83,8 @@ impl Handler<UpdateRootPath> for RootPath {
     fn handle(&mut self, msg: UpdateRootPath, _ctx: &mut Self::Context) -> Self::Result {
         trace!("UpdateRootPath");
         self.root_path = msg.0;
-        self.root_path_handle.take();
+        if self.root_path_handle.take().is_some() {
+            self.busy.do_send(End::new(Operation::ChoosingRootFolder));
+        }
     }
 }
@@ -0,0 +1,188 @@
+use crate::ui::map_loader::GetMap;
+use crate::ui::map_mode::{GetMapMode, MapMode, SetMapMode};
+use crate::ui::selection::{
+    Selection, SetSelectedPoint, SetSelectedProvince, SetSelectedState, SetSelectedStrategicRegion,
+};
+use crate::ui::validation_renderer::focus_area;
+use crate::ui::viewport::{GetZoomLevel, SetViewportArea, SetZoomLevel, Viewport};
+use crate::{MapError, MapLoader};
+use actix::Addr;
+use egui::{Context, Key, Pos2, TextEdit, TopBottomPanel, Vec2};
+use log::debug;
+use world_gen::map::{
+    GetMapSize, GetProvinceDefinitionFromId, GetStateFromId, GetStrategicRegionFromId,
+    LocateSearchTarget, Map, Search,
+};
+use world_gen::search::{SearchResult, SearchTarget};
+use world_gen::MapDisplayMode;
+
+/// How far the viewport zooms in on a search result, unless it is zoomed in further already
+const SEARCH_ZOOM: f32 = 0.8;
+
+/// The bar below the top menu for finding a province, state or strategic region by its id or
+/// name, such as one named in an error log of the game.  Choosing a result, or pressing enter for
+/// the first one, moves the viewport to it and selects it.
+pub struct SearchRenderer {
+    map_loader: Addr<MapLoader>,
+    map_mode: Addr<MapMode>,
+    selection: Addr<Selection>,
+    viewport: Addr<Viewport>,
+    query: String,
+    /// The results of the query, and the map they were found in
+    results: Option<(Addr<Map>, Vec<SearchResult>)>,
+}
+
+impl SearchRenderer {
+    #[inline]
+    pub const fn new(
+        map_loader: Addr<MapLoader>,
+        map_mode: Addr<MapMode>,
+        selection: Addr<Selection>,
+        viewport: Addr<Viewport>,
+    ) -> Self {
+        Self {
+            map_loader,
+            map_mode,
+            selection,
+            viewport,
+            query: String::new(),
+            results: None,
+        }
+    }
+
+    pub async fn render_search_bar(&mut self, ctx: &Context) -> Result<(), MapError> {
+        let map: Option<Addr<Map>> = self.map_loader.send(GetMap).await?;
+        let map = match map {
+            Some(map) => map,
+            None => {
+                self.results = None;
+                return Ok(());
+            }
+        };
+
+        let mut changed = false;
+        let mut chosen = None;
+        TopBottomPanel::top("search_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Search");
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.query)
+                        .hint_text("Province, state or region id or name"),
+                );
+                changed = response.changed();
+                let submitted = response.lost_focus() && ui.input().key_pressed(Key::Enter);
+                let results = match &self.results {
+                    Some((_, results)) => results,
+                    None => return,
+                };
+                if submitted {
+                    chosen = results.first().map(|r| r.target);
+                }
+                if results.is_empty() && !self.query.trim().is_empty() {
+                    ui.label("No matches");
+                }
+            });
+            if let Some((_, results)) = self.results.as_ref().filter(|(_, r)| !r.is_empty()) {
+                ui.horizontal_wrapped(|ui| {
+                    for result in results {
+                        let response = ui
+                            .selectable_label(false, &result.label)
+                            .on_hover_text("Click to move to it and select it");
+                        if response.clicked() {
+                            chosen = Some(result.target);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Results found in a map that has since been replaced no longer apply
+        if changed || self.results.as_ref().map_or(false, |(m, _)| *m != map) {
+            let results = map.send(Search::new(self.query.clone())).await?;
+            self.results = Some((map.clone(), results));
+        }
+        if let Some(target) = chosen {
+            self.focus_target(&map, target).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the viewport to a search result, selects it and shows a display mode with it in
+    async fn focus_target(&self, map: &Addr<Map>, target: SearchTarget) -> Result<(), MapError> {
+        if let Some((x, y)) = map.send(LocateSearchTarget::new(target)).await? {
+            debug!("Moving to {target} at ({x}, {y})");
+            let (width, height) = map.send(GetMapSize).await?;
+            let zoom = self
+                .viewport
+                .send(GetZoomLevel)
+                .await?
+                .map_or(SEARCH_ZOOM, |z| z.max(SEARCH_ZOOM));
+            #[allow(clippy::as_conversions)]
+            #[allow(clippy::cast_precision_loss)]
+            let (point, size) = (
+                Pos2::new(x as f32, y as f32),
+                Vec2::new(width as f32, height as f32),
+            );
+            self.viewport.do_send(SetZoomLevel::new(zoom));
+            self.viewport
+                .do_send(SetViewportArea(focus_area(point, size, zoom)));
+            self.selection.send(SetSelectedPoint::new(point)).await?;
+        }
+        let mode = self.map_mode.send(GetMapMode).await?;
+        if !shows(mode, target) {
+            let mode = match target {
+                SearchTarget::Province(_) => MapDisplayMode::Provinces,
+                SearchTarget::State(_) => MapDisplayMode::States,
+                SearchTarget::StrategicRegion(_) => MapDisplayMode::StrategicRegions,
+            };
+            self.map_mode.do_send(SetMapMode::new(mode));
+        }
+        match target {
+            SearchTarget::Province(id) => {
+                if let Some(definition) = map.send(GetProvinceDefinitionFromId::new(id)).await? {
+                    self.selection
+                        .send(SetSelectedProvince::new(definition))
+                        .await?;
+                }
+            }
+            SearchTarget::State(id) => {
+                if let Some(state) = map.send(GetStateFromId::new(id)).await? {
+                    self.selection.send(SetSelectedState::new(state)).await?;
+                }
+            }
+            SearchTarget::StrategicRegion(id) => {
+                if let Some(region) = map.send(GetStrategicRegionFromId::new(id)).await? {
+                    self.selection
+                        .send(SetSelectedStrategicRegion::new(region))
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a display mode shows the kind of region of a search result, so that the right panel
+/// describes it once it is selected
+const fn shows(mode: MapDisplayMode, target: SearchTarget) -> bool {
+    match target {
+        SearchTarget::Province(_) => matches!(
+            mode,
+            MapDisplayMode::Provinces
+                | MapDisplayMode::Continents
+                | MapDisplayMode::DefinitionTerrain
+        ),
+        SearchTarget::State(_) => matches!(
+            mode,
+            MapDisplayMode::States
+                | MapDisplayMode::BuildingDensity
+                | MapDisplayMode::Manpower
+                | MapDisplayMode::Political
+                | MapDisplayMode::StateCategories
+        ),
+        SearchTarget::StrategicRegion(_) => matches!(
+            mode,
+            MapDisplayMode::StrategicRegions | MapDisplayMode::Weather
+        ),
+    }
+}
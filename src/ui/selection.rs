@@ -51,6 +51,12 @@ pub struct GetSelectedStrategicRegion;
 #[non_exhaustive]
 pub struct SetSelectedStrategicRegion(pub StrategicRegion);
 
+/// A request to clear the selection, such as when the map is unloaded
+#[derive(Message)]
+#[rtype(result = "()")]
+#[non_exhaustive]
+pub struct ClearSelection;
+
 impl SetSelectedProvince {
     #[inline]
     pub const fn new(definition: Definition) -> Self {
@@ -161,3 +167,11 @@ impl Handler<SetSelectedStrategicRegion> for Selection {
         self.selected_strategic_region = Some(msg.0);
     }
 }
+
+impl Handler<ClearSelection> for Selection {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ClearSelection, _ctx: &mut Self::Context) -> Self::Result {
+        *self = Self::default();
+    }
+}
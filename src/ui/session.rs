@@ -0,0 +1,78 @@
+use crate::ui::busy::{Begin, Busy, End, Operation};
+use crate::ui::map_loader::{GetMap, MapLoader, ReloadWithNewRoot, UnloadMap};
+use crate::ui::map_textures::{ClearTextures, MapTextures};
+use crate::ui::plugins::{ClearLayers, Plugins};
+use crate::ui::selection::{ClearSelection, Selection};
+use actix::Addr;
+use indicatif::InMemoryTerm;
+use log::{debug, warn};
+use std::path::PathBuf;
+use world_gen::MapError;
+
+/// The actors holding the state of the open map, so that it can be swapped for another map
+/// without restarting the editor
+pub struct Session {
+    map_loader: Addr<MapLoader>,
+    map_textures: Addr<MapTextures>,
+    selection: Addr<Selection>,
+    plugins: Addr<Plugins>,
+    busy: Addr<Busy>,
+    terminal: InMemoryTerm,
+}
+
+impl Session {
+    #[inline]
+    pub const fn new(
+        map_loader: Addr<MapLoader>,
+        map_textures: Addr<MapTextures>,
+        selection: Addr<Selection>,
+        plugins: Addr<Plugins>,
+        busy: Addr<Busy>,
+        terminal: InMemoryTerm,
+    ) -> Self {
+        Self {
+            map_loader,
+            map_textures,
+            selection,
+            plugins,
+            busy,
+            terminal,
+        }
+    }
+
+    /// Unloads the open map and everything shown of it.  If a map was open, the map in the new
+    /// root directory is loaded in its place.
+    pub async fn switch_root(&self, root_path: Option<PathBuf>) -> Result<(), MapError> {
+        let was_loaded = self.map_loader.send(GetMap).await?.is_some();
+        let unloaded = match root_path {
+            Some(p) if was_loaded => {
+                if self
+                    .busy
+                    .send(Begin::new(Operation::LoadingMap))
+                    .await?
+                    .is_err()
+                {
+                    return Ok(());
+                }
+                let reloaded = self
+                    .map_loader
+                    .send(ReloadWithNewRoot::new(p, self.terminal.clone()))
+                    .await?;
+                if !reloaded {
+                    self.busy.do_send(End::new(Operation::LoadingMap));
+                }
+                reloaded
+            }
+            _ => self.map_loader.send(UnloadMap).await?,
+        };
+        if !unloaded {
+            warn!("The map could not be unloaded while it was loading");
+            return Ok(());
+        }
+        debug!("Clearing the open map");
+        self.map_textures.do_send(ClearTextures);
+        self.selection.do_send(ClearSelection);
+        self.plugins.do_send(ClearLayers);
+        Ok(())
+    }
+}
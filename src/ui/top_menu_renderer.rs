@@ -1,25 +1,88 @@
+use crate::ui::adjacency_rule_window::AdjacencyRuleWindow;
+use crate::ui::asset_window::AssetWindow;
+use crate::ui::bookmarks::{bookmark_area, Bookmarks};
+use crate::ui::busy::{Begin, Busy, End, Operation};
+use crate::ui::map_loader::{GetMap, MapLoader};
+use crate::ui::map_mode::{GetMapMode, MapMode};
+use crate::ui::metrics::{
+    export_metrics, GetMetrics, Metrics, MetricsAction, MetricsWindow, ResetMetrics,
+};
+use crate::ui::new_map_window::NewMapWindow;
+use crate::ui::plugins::{
+    GetEnabledLayers, GetLayerOpacities, GetPluginRegistry, Plugins, DEFAULT_OPACITY,
+};
 use crate::ui::root_path::{GetRootPath, UpdateRootPath};
-use crate::{RootPath, SetRootPath};
+use crate::ui::viewport::{GetViewportArea, GetZoomLevel, SetViewportArea, SetZoomLevel};
+use crate::{RootPath, SetRootPath, Viewport};
 use actix::{Addr, Handler, Message, ResponseFuture};
 use egui::menu::bar;
-use egui::{Context, TopBottomPanel};
-use log::{debug, error, trace};
+use egui::{Checkbox, Context, Slider, TopBottomPanel};
+use indicatif::InMemoryTerm;
+use log::{debug, error, info, trace};
+use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
-use world_gen::MapError;
+use std::sync::Arc;
+use world_gen::generation::world::{WorldConfig, WorldGenerator};
+use world_gen::legend::LegendFormat;
+use world_gen::map::{
+    ApplyFixes, CreateSupportBundle, ExportGeoJson, ExportPoster, ExportRegionMap,
+    GetAdjacencyRules, Map, SaveStates, Validate,
+};
+use world_gen::plugin::{MapLayer, PluginRegistry};
+use world_gen::poster::{PosterFormat, PosterOptions};
+use world_gen::{MapDisplayMode, MapError, RegionMap};
 
 pub struct TopMenuRenderer {
     root_path: Addr<RootPath>,
+    map_loader: Addr<MapLoader>,
+    map_mode: Addr<MapMode>,
+    viewport: Addr<Viewport>,
+    plugins: Addr<Plugins>,
+    busy: Addr<Busy>,
+    metrics: Addr<Metrics>,
+    terminal: InMemoryTerm,
     pub new_root_path: Option<PathBuf>,
     pub root_path_changed: bool,
+    pub export_legend: Option<LegendFormat>,
+    pub poster_options: PosterOptions,
+    pub adjacency_rule_window: AdjacencyRuleWindow,
+    pub asset_window: AssetWindow,
+    pub new_map_window: NewMapWindow,
+    pub metrics_window: MetricsWindow,
+    pub bookmarks: Bookmarks,
 }
 
 impl TopMenuRenderer {
     #[inline]
-    pub const fn new(root_path: Addr<RootPath>) -> Self {
+    pub fn new(
+        root_path: Addr<RootPath>,
+        map_loader: Addr<MapLoader>,
+        map_mode: Addr<MapMode>,
+        viewport: Addr<Viewport>,
+        plugins: Addr<Plugins>,
+        busy: Addr<Busy>,
+        metrics: Addr<Metrics>,
+        terminal: InMemoryTerm,
+    ) -> Self {
         Self {
             root_path,
+            map_loader,
+            map_mode,
+            viewport,
+            plugins,
+            busy,
+            metrics,
+            terminal,
             new_root_path: None,
             root_path_changed: false,
+            export_legend: None,
+            poster_options: PosterOptions::default(),
+            adjacency_rule_window: AdjacencyRuleWindow::default(),
+            asset_window: AssetWindow::default(),
+            new_map_window: NewMapWindow::default(),
+            metrics_window: MetricsWindow::default(),
+            bookmarks: Bookmarks::default(),
         }
     }
 
@@ -40,23 +103,421 @@ impl TopMenuRenderer {
             self.new_root_path = root_path.clone();
         }
 
-        let mut new_root_path = None;
+        let map: Option<Addr<Map>> = self.map_loader.send(GetMap).await?;
+        self.bookmarks.load(&root_path);
+        let viewport_area = self.viewport.send(GetViewportArea).await?;
+        let zoom_level = self.viewport.send(GetZoomLevel).await?;
+
+        let mut open_root_folder = false;
+        let mut bookmark = self.bookmarks.hotkey(ctx);
+        let mut create_support_bundle = false;
+        let mut save_states = false;
+        let mut apply_fixes = false;
+        let mut export_region_map = None;
+        let mut export_geojson = false;
+        let mut export_poster = false;
         TopBottomPanel::top("top_panel").show(ctx, |ui| {
             bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
+                    if ui.button("New map…").clicked() {
+                        self.new_map_window.open();
+                        ui.close_menu();
+                    }
                     if ui.button("Open root folder").clicked() {
-                        new_root_path = Some(self.root_path.send(SetRootPath));
+                        open_root_folder = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(map.is_some(), egui::Button::new("Save states"))
+                        .on_hover_text(
+                            "Write the edited states back to history/states and their names to \
+                             localisation",
+                        )
+                        .clicked()
+                    {
+                        save_states = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(map.is_some(), egui::Button::new("Create support bundle"))
+                        .clicked()
+                    {
+                        create_support_bundle = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    for (region_map, label) in [
+                        (RegionMap::States, "Export state map"),
+                        (RegionMap::StrategicRegions, "Export strategic region map"),
+                    ] {
+                        if ui
+                            .add_enabled(map.is_some(), egui::Button::new(label))
+                            .clicked()
+                        {
+                            export_region_map = Some(region_map);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.label("Export legend");
+                    ui.radio_value(&mut self.export_legend, None, "None");
+                    ui.radio_value(&mut self.export_legend, Some(LegendFormat::Csv), "CSV");
+                    ui.radio_value(&mut self.export_legend, Some(LegendFormat::Image), "Image");
+                    ui.separator();
+                    if ui
+                        .add_enabled(map.is_some(), egui::Button::new("Export GeoJSON"))
+                        .on_hover_text(
+                            "Save the provinces, states and strategic regions for GIS tools",
+                        )
+                        .clicked()
+                    {
+                        export_geojson = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(map.is_some(), egui::Button::new("Export poster"))
+                        .on_hover_text("Save the current display mode and overlays for sharing")
+                        .clicked()
+                    {
+                        export_poster = true;
+                        ui.close_menu();
+                    }
+                    let options = &mut self.poster_options;
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut options.format, PosterFormat::Png, "PNG");
+                        ui.radio_value(&mut options.format, PosterFormat::Svg, "SVG");
+                    });
+                    ui.add(Slider::new(&mut options.scale, 1..=8).text("Scale"));
+                    ui.add_enabled(
+                        options.format == PosterFormat::Svg,
+                        Checkbox::new(&mut options.labels, "State names"),
+                    );
+                });
+                ui.menu_button("Bookmarks", |ui| {
+                    if let Some(b) = self.bookmarks.render_menu(ui, viewport_area, zoom_level) {
+                        bookmark = Some(b);
+                    }
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui
+                        .add_enabled(map.is_some(), egui::Button::new("Simulate adjacency rules"))
+                        .clicked()
+                    {
+                        self.adjacency_rule_window.open();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(root_path.is_some(), egui::Button::new("Map graphics"))
+                        .on_hover_text("The DDS and TGA textures and colormaps of the map")
+                        .clicked()
+                    {
+                        self.asset_window.open();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            map.is_some() && root_path.is_some(),
+                            egui::Button::new("Apply automatic fixes"),
+                        )
+                        .on_hover_text(
+                            "Fixes every problem found by the validation that has a single fix, \
+                             writing the changed files",
+                        )
+                        .clicked()
+                    {
+                        apply_fixes = true;
                         ui.close_menu();
                     }
-                })
+                    if ui
+                        .button("Metrics")
+                        .on_hover_text("Timings and cache hit rates, for finding slow spots")
+                        .clicked()
+                    {
+                        self.metrics_window.open();
+                        ui.close_menu();
+                    }
+                });
             });
         });
 
-        if let Some(p) = new_root_path {
+        if let Some(b) = bookmark {
+            debug!("Jumping to bookmark {}", b.name);
+            if let Some(zoom) = b.zoom {
+                self.viewport.do_send(SetZoomLevel::new(zoom));
+            }
+            self.viewport.do_send(SetViewportArea(bookmark_area(&b)));
+        }
+
+        if open_root_folder {
             debug!("New root path requested");
-            p.await?;
+            self.open_root_folder().await?;
+        }
+
+        if let (true, Some(m)) = (self.adjacency_rule_window.is_open(), &map) {
+            let rules = m.send(GetAdjacencyRules).await?;
+            self.adjacency_rule_window.render(ctx, &rules);
+        }
+
+        if let (true, Some(root)) = (self.asset_window.is_open(), &root_path) {
+            self.asset_window.render(ctx, root);
+        }
+
+        if self.metrics_window.is_open() {
+            let snapshot = self.metrics.send(GetMetrics).await?;
+            match self.metrics_window.render(ctx, &snapshot) {
+                Some(MetricsAction::Export) => export_metrics(snapshot),
+                Some(MetricsAction::Reset) => self.metrics.do_send(ResetMetrics),
+                None => {}
+            }
+        }
+
+        if self.new_map_window.is_open() {
+            if let Some(config) = self.new_map_window.render(ctx) {
+                if self.begin(Operation::GeneratingMap).await? {
+                    self.generate_map(config);
+                }
+            }
+        }
+
+        if let (Some(region_map), Some(m)) = (export_region_map, map.clone()) {
+            if self.begin(Operation::ExportingRegionMap).await? {
+                self.export_region_map(m, region_map);
+            }
+        }
+
+        if let (true, Some(m)) = (export_geojson, map.clone()) {
+            if self.begin(Operation::ExportingGeoJson).await? {
+                self.export_geojson(m);
+            }
+        }
+
+        if let (true, Some(m)) = (export_poster, map.clone()) {
+            if self.begin(Operation::ExportingPoster).await? {
+                let mode: MapDisplayMode = self.map_mode.send(GetMapMode).await?;
+                let layers = self.enabled_layers().await?;
+                self.export_poster(m, mode, layers);
+            }
         }
 
+        if let (true, Some(m), Some(root)) = (save_states, &map, &root_path) {
+            if self.begin(Operation::SavingStates).await? {
+                let saved = m.send(SaveStates::new(root.clone())).await;
+                self.busy.do_send(End::new(Operation::SavingStates));
+                if let Err(e) = saved? {
+                    error!("Failed to save states: {e}");
+                }
+            }
+        }
+
+        if let (true, Some(m), Some(root)) = (apply_fixes, &map, &root_path) {
+            if self.begin(Operation::ApplyingFixes).await? {
+                let applied = self.apply_fixes(m, root.clone()).await;
+                self.busy.do_send(End::new(Operation::ApplyingFixes));
+                applied?;
+            }
+        }
+
+        if let (true, Some(m), Some(root)) = (create_support_bundle, map, root_path) {
+            if self.begin(Operation::CreatingSupportBundle).await? {
+                self.create_support_bundle(m, root).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts an operation, returning false if another operation is in progress
+    async fn begin(&self, operation: Operation) -> Result<bool, MapError> {
+        Ok(self.busy.send(Begin::new(operation)).await?.is_ok())
+    }
+
+    /// Validates the map, then applies the fixes of every finding that can be fixed automatically
+    async fn apply_fixes(&self, map: &Addr<Map>, root_path: PathBuf) -> Result<(), MapError> {
+        let fixes = map.send(Validate).await?.auto_fixes();
+        if fixes.is_empty() {
+            info!("The validation found nothing to fix automatically");
+            return Ok(());
+        }
+        if let Err(e) = map.send(ApplyFixes::new(root_path, fixes)).await? {
+            error!("Failed to apply fixes: {e}");
+        }
+        Ok(())
+    }
+
+    /// Asks the user for a new root folder, unless another operation is in progress
+    async fn open_root_folder(&self) -> Result<(), MapError> {
+        if self.begin(Operation::ChoosingRootFolder).await?
+            && !self.root_path.send(SetRootPath).await?
+        {
+            self.busy.do_send(End::new(Operation::ChoosingRootFolder));
+        }
+        Ok(())
+    }
+
+    /// Asks the user for an empty folder, then generates a world into it and opens it as the new
+    /// root folder.  A folder that is not empty is only written to once the user confirms that
+    /// its map files may be overwritten.
+    fn generate_map(&self, config: WorldConfig) {
+        let root_path = self.root_path.clone();
+        let busy = self.busy.clone();
+        tokio::spawn(async move {
+            let path = tokio::task::spawn_blocking(|| {
+                let path = rfd::FileDialog::new().pick_folder()?;
+                let empty =
+                    fs::read_dir(&path).map_or(true, |mut entries| entries.next().is_none());
+                let confirmed = empty
+                    || rfd::MessageDialog::new()
+                        .set_level(rfd::MessageLevel::Warning)
+                        .set_title("Folder is not empty")
+                        .set_description(&format!(
+                            "{} is not empty.  Generating a map overwrites its map, history and \
+                             localisation files.  Generate anyway?",
+                            path.display()
+                        ))
+                        .set_buttons(rfd::MessageButtons::YesNo)
+                        .show();
+                confirmed.then_some(path)
+            })
+            .await;
+            if let Ok(Some(p)) = path {
+                info!("Generating map in {}", p.display());
+                let generator = WorldGenerator::new(config);
+                let dir = p.clone();
+                match tokio::task::spawn_blocking(move || generator.generate(&dir)).await {
+                    Ok(Ok(_world)) => {
+                        info!("Generated map in {}", p.display());
+                        root_path.do_send(UpdateRootPath::new(Some(p)));
+                    }
+                    Ok(Err(e)) => error!("Failed to generate map: {e}"),
+                    Err(e) => error!("Failed to generate map: {e}"),
+                }
+            }
+            busy.do_send(End::new(Operation::GeneratingMap));
+        });
+    }
+
+    /// Asks the user where to save a region map, then has the map save it there along with the
+    /// chosen legend.
+    fn export_region_map(&self, map: Addr<Map>, region_map: RegionMap) {
+        let legend = self.export_legend;
+        let file_name = match region_map {
+            RegionMap::States => "states.png",
+            RegionMap::StrategicRegions => "strategic_regions.png",
+        };
+        let busy = self.busy.clone();
+        tokio::spawn(async move {
+            let path = tokio::task::spawn_blocking(move || {
+                rfd::FileDialog::new()
+                    .add_filter("PNG", &["png"])
+                    .set_file_name(file_name)
+                    .save_file()
+            })
+            .await;
+            if let Ok(Some(p)) = path {
+                match map.send(ExportRegionMap::new(region_map, p, legend)).await {
+                    Ok(Ok(())) => info!("Exported {region_map} map"),
+                    Ok(Err(e)) => error!("Failed to export {region_map} map: {e}"),
+                    Err(e) => error!("Failed to export {region_map} map: {e}"),
+                }
+            }
+            busy.do_send(End::new(Operation::ExportingRegionMap));
+        });
+    }
+
+    /// The enabled layers in the order they are drawn over the map, each with its opacity
+    async fn enabled_layers(&self) -> Result<Vec<(Arc<dyn MapLayer>, f32)>, MapError> {
+        let registry: Arc<PluginRegistry> = self.plugins.send(GetPluginRegistry).await?;
+        let enabled: Vec<usize> = self.plugins.send(GetEnabledLayers).await?;
+        let opacities: HashMap<usize, f32> = self.plugins.send(GetLayerOpacities).await?;
+        Ok(enabled
+            .into_iter()
+            .filter_map(|index| {
+                let layer = Arc::clone(registry.layers().get(index)?);
+                let opacity = opacities.get(&index).copied().unwrap_or(DEFAULT_OPACITY);
+                Some((layer, opacity))
+            })
+            .collect())
+    }
+
+    /// Asks the user where to save a poster of a display mode, then has the map draw the poster
+    /// with the layers over it and save it there.
+    fn export_poster(
+        &self,
+        map: Addr<Map>,
+        mode: MapDisplayMode,
+        layers: Vec<(Arc<dyn MapLayer>, f32)>,
+    ) {
+        let options = self.poster_options;
+        let (filter, extension) = match options.format {
+            PosterFormat::Png => ("PNG", "png"),
+            PosterFormat::Svg => ("SVG", "svg"),
+        };
+        let busy = self.busy.clone();
+        tokio::spawn(async move {
+            let path = tokio::task::spawn_blocking(move || {
+                rfd::FileDialog::new()
+                    .add_filter(filter, &[extension])
+                    .set_file_name(&format!("poster.{extension}"))
+                    .save_file()
+            })
+            .await;
+            if let Ok(Some(p)) = path {
+                match map.send(ExportPoster::new(mode, layers, options, p)).await {
+                    Ok(Ok(())) => info!("Exported {mode} poster"),
+                    Ok(Err(e)) => error!("Failed to export {mode} poster: {e}"),
+                    Err(e) => error!("Failed to export {mode} poster: {e}"),
+                }
+            }
+            busy.do_send(End::new(Operation::ExportingPoster));
+        });
+    }
+
+    /// Asks the user for a directory, then has the map save the GeoJSON files in it.
+    fn export_geojson(&self, map: Addr<Map>) {
+        let busy = self.busy.clone();
+        tokio::spawn(async move {
+            let path = tokio::task::spawn_blocking(|| rfd::FileDialog::new().pick_folder()).await;
+            if let Ok(Some(p)) = path {
+                match map.send(ExportGeoJson::new(p)).await {
+                    Ok(Ok(())) => info!("Exported GeoJSON"),
+                    Ok(Err(e)) => error!("Failed to export GeoJSON: {e}"),
+                    Err(e) => error!("Failed to export GeoJSON: {e}"),
+                }
+            }
+            busy.do_send(End::new(Operation::ExportingGeoJson));
+        });
+    }
+
+    /// Asks the user where to save a support bundle, then has the map save it there.
+    async fn create_support_bundle(
+        &self,
+        map: Addr<Map>,
+        root_path: PathBuf,
+    ) -> Result<(), MapError> {
+        let map_mode = self.map_mode.send(GetMapMode).await?;
+        let settings = format!("root_path={}\nmap_mode={map_mode}\n", root_path.display());
+        let log = self.terminal.contents();
+        let busy = self.busy.clone();
+        tokio::spawn(async move {
+            let path = tokio::task::spawn_blocking(|| {
+                rfd::FileDialog::new()
+                    .add_filter("Zip", &["zip"])
+                    .set_file_name("support_bundle.zip")
+                    .save_file()
+            })
+            .await;
+            if let Ok(Some(p)) = path {
+                match map
+                    .send(CreateSupportBundle::new(p, root_path, log, settings))
+                    .await
+                {
+                    Ok(Ok(())) => info!("Created support bundle"),
+                    Ok(Err(e)) => error!("Failed to create support bundle: {e}"),
+                    Err(e) => error!("Failed to create support bundle: {e}"),
+                }
+            }
+            busy.do_send(End::new(Operation::CreatingSupportBundle));
+        });
         Ok(())
     }
 }
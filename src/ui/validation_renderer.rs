@@ -0,0 +1,200 @@
+use crate::ui::busy::{Begin, Busy, End, Operation};
+use crate::ui::map_loader::GetMap;
+use crate::ui::selection::{Selection, SetSelectedPoint, SetSelectedProvince, SetSelectedState};
+use crate::ui::viewport::{GetZoomLevel, SetViewportArea, SetZoomLevel, Viewport};
+use crate::{MapError, MapLoader};
+use actix::Addr;
+use egui::{Button, Color32, Context, Pos2, Rect, RichText, ScrollArea, TopBottomPanel, Vec2};
+use log::{debug, info};
+use world_gen::map::{
+    GetMapSize, GetProvinceDefinitionFromId, GetStateFromId, LocateFinding, Map, Validate,
+};
+use world_gen::validation::report::{Finding, Severity, ValidationReport};
+
+/// How far the viewport zooms in on a finding, unless it is zoomed in further already
+const FINDING_ZOOM: f32 = 0.9;
+
+/// The panel listing the findings of the last validation of the map.  Clicking a finding moves
+/// the viewport to it and selects its province and state.
+pub struct ValidationRenderer {
+    map_loader: Addr<MapLoader>,
+    selection: Addr<Selection>,
+    viewport: Addr<Viewport>,
+    busy: Addr<Busy>,
+    /// The findings of the last validation, and the map they were found in
+    report: Option<(Addr<Map>, ValidationReport)>,
+    show_warnings: bool,
+    show_info: bool,
+}
+
+impl ValidationRenderer {
+    #[inline]
+    pub const fn new(
+        map_loader: Addr<MapLoader>,
+        selection: Addr<Selection>,
+        viewport: Addr<Viewport>,
+        busy: Addr<Busy>,
+    ) -> Self {
+        Self {
+            map_loader,
+            selection,
+            viewport,
+            busy,
+            report: None,
+            show_warnings: true,
+            show_info: false,
+        }
+    }
+
+    pub async fn render_validation_panel(&mut self, ctx: &Context) -> Result<(), MapError> {
+        let map: Option<Addr<Map>> = self.map_loader.send(GetMap).await?;
+        let map = match map {
+            Some(map) => map,
+            None => {
+                self.report = None;
+                return Ok(());
+            }
+        };
+        // A report of a map that has since been replaced no longer applies
+        if self.report.as_ref().map_or(false, |(m, _)| *m != map) {
+            self.report = None;
+        }
+
+        let mut validate = false;
+        let mut clicked = None;
+        TopBottomPanel::bottom("validation_panel")
+            .resizable(true)
+            .default_height(150.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Validation");
+                    validate = ui
+                        .add(Button::new("Validate"))
+                        .on_hover_text("Runs every check of the map")
+                        .clicked();
+                    if let Some((_, report)) = &self.report {
+                        ui.label(format!(
+                            "{} errors, {} warnings, {} info",
+                            report.count(Severity::Error),
+                            report.count(Severity::Warning),
+                            report.count(Severity::Info)
+                        ));
+                    }
+                    ui.checkbox(&mut self.show_warnings, "Warnings");
+                    ui.checkbox(&mut self.show_info, "Info");
+                });
+                ui.separator();
+                let report = match &self.report {
+                    Some((_, report)) => report,
+                    None => {
+                        ui.label("Validate the map to list its problems here");
+                        return;
+                    }
+                };
+                ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let shown = report.findings.iter().filter(|f| match f.severity {
+                            Severity::Error => true,
+                            Severity::Warning => self.show_warnings,
+                            Severity::Info => self.show_info,
+                        });
+                        for finding in shown {
+                            let response = ui
+                                .selectable_label(false, finding_text(finding))
+                                .on_hover_text(finding.suggested_fix.as_deref().unwrap_or(
+                                    "Click to move to the problem, if it is on the map",
+                                ));
+                            if response.clicked() {
+                                clicked = Some(finding.clone());
+                            }
+                        }
+                    });
+            });
+
+        if validate
+            && self
+                .busy
+                .send(Begin::new(Operation::Validating))
+                .await?
+                .is_ok()
+        {
+            let report = map.send(Validate).await;
+            self.busy.do_send(End::new(Operation::Validating));
+            let report = report?;
+            info!("Validation found {} problems", report.findings.len());
+            self.report = Some((map.clone(), report));
+        }
+
+        if let Some(finding) = clicked {
+            self.focus_finding(&map, finding).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the viewport to a finding and selects the province and state it is in
+    async fn focus_finding(&self, map: &Addr<Map>, finding: Finding) -> Result<(), MapError> {
+        let (province, state) = (finding.province, finding.state);
+        if let Some((x, y)) = map.send(LocateFinding::new(finding)).await? {
+            debug!("Moving to finding at ({x}, {y})");
+            let (width, height) = map.send(GetMapSize).await?;
+            let zoom = self
+                .viewport
+                .send(GetZoomLevel)
+                .await?
+                .map_or(FINDING_ZOOM, |z| z.max(FINDING_ZOOM));
+            #[allow(clippy::as_conversions)]
+            #[allow(clippy::cast_precision_loss)]
+            let (point, size) = (
+                Pos2::new(x as f32, y as f32),
+                Vec2::new(width as f32, height as f32),
+            );
+            self.viewport.do_send(SetZoomLevel::new(zoom));
+            self.viewport
+                .do_send(SetViewportArea(focus_area(point, size, zoom)));
+            self.selection.send(SetSelectedPoint::new(point)).await?;
+        }
+        if let Some(province) = province {
+            if let Some(definition) = map.send(GetProvinceDefinitionFromId::new(province)).await? {
+                self.selection
+                    .send(SetSelectedProvince::new(definition))
+                    .await?;
+            }
+        }
+        if let Some(state) = state {
+            if let Some(state) = map.send(GetStateFromId::new(state)).await? {
+                self.selection.send(SetSelectedState::new(state)).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The line listing a finding, colored by its severity
+fn finding_text(finding: &Finding) -> RichText {
+    let color = match finding.severity {
+        Severity::Error => Color32::LIGHT_RED,
+        Severity::Warning => Color32::YELLOW,
+        Severity::Info => Color32::LIGHT_BLUE,
+    };
+    let mut text = format!("[{}] {}", finding.kind.rule(), finding.message);
+    if let Some(province) = finding.province {
+        text.push_str(&format!(" (province {province})"));
+    }
+    if let Some(state) = finding.state {
+        text.push_str(&format!(" (state {state})"));
+    }
+    RichText::new(text).color(color)
+}
+
+/// The viewport area of the zoom level centered on a point of the map, moved back onto the map
+/// where the point is close to an edge
+pub fn focus_area(point: Pos2, map_size: Vec2, zoom: f32) -> Rect {
+    let size = (1.0 - zoom).clamp(0.0, 1.0);
+    let center = Pos2::new(point.x / map_size.x, point.y / map_size.y);
+    let min = Pos2::new(
+        (center.x - size / 2.0).clamp(0.0, 1.0 - size),
+        (center.y - size / 2.0).clamp(0.0, 1.0 - size),
+    );
+    Rect::from_min_size(min, Vec2::splat(size))
+}
@@ -1,7 +1,8 @@
 use crate::truncate_to_decimal_places;
-use actix::{Actor, Context, Handler, Message};
+use actix::{Actor, Context, Handler, Message, MessageResult};
 use egui::Rect;
 use std::mem::swap;
+use world_gen::topology::Wrapping;
 
 /// A request to get the zoom level
 #[derive(Message)]
@@ -13,7 +14,13 @@ pub struct GetZoomLevel;
 #[derive(Message)]
 #[rtype(result = "()")]
 #[non_exhaustive]
-pub struct SetZoomLevel(f32);
+pub struct SetZoomLevel(pub f32);
+
+impl SetZoomLevel {
+    pub const fn new(zoom_level: f32) -> Self {
+        Self(zoom_level)
+    }
+}
 
 /// A request to set the zoom level
 #[derive(Message)]
@@ -31,10 +38,22 @@ pub struct GetViewportArea;
 #[rtype(result = "()")]
 pub struct SetViewportArea(pub Rect);
 
+/// A request to get how the edges of the viewed map connect
+#[derive(Message)]
+#[rtype(result = "Wrapping")]
+#[non_exhaustive]
+pub struct GetViewportWrapping;
+
+/// A request to set how the edges of the viewed map connect
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetViewportWrapping(pub Wrapping);
+
 #[derive(Default, Debug)]
 pub struct Viewport {
     zoom_level: Option<f32>,
     viewport_area: Option<Rect>,
+    wrapping: Wrapping,
 }
 
 impl Actor for Viewport {
@@ -70,11 +89,30 @@ impl Handler<SetViewportArea> for Viewport {
 
     fn handle(&mut self, msg: SetViewportArea, _ctx: &mut Self::Context) -> Self::Result {
         let mut rect = msg.0;
-        clamp_viewport(&mut rect);
+        clamp_viewport(&mut rect, self.wrapping);
         self.viewport_area = Some(rect);
     }
 }
 
+impl Handler<GetViewportWrapping> for Viewport {
+    type Result = MessageResult<GetViewportWrapping>;
+
+    fn handle(&mut self, _msg: GetViewportWrapping, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.wrapping)
+    }
+}
+
+impl Handler<SetViewportWrapping> for Viewport {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetViewportWrapping, _ctx: &mut Self::Context) -> Self::Result {
+        self.wrapping = msg.0;
+        if let Some(rect) = &mut self.viewport_area {
+            clamp_viewport(rect, self.wrapping);
+        }
+    }
+}
+
 impl Handler<Scroll> for Viewport {
     type Result = ();
 
@@ -101,14 +139,22 @@ impl Handler<Scroll> for Viewport {
     }
 }
 
-fn clamp_viewport(mut viewport: &mut Rect) {
-    viewport.min.x = viewport.min.x.clamp(0.0, 1.0);
-    viewport.min.y = viewport.min.y.clamp(0.0, 1.0);
-    viewport.max.x = viewport.max.x.clamp(0.0, 1.0);
-    viewport.max.y = viewport.max.y.clamp(0.0, 1.0);
+/// Keeps the viewport on the map.  On a horizontally wrapping map the viewport is instead moved
+/// so that it starts on the map, and may run past the right edge onto the left side of the map.
+fn clamp_viewport(mut viewport: &mut Rect, wrapping: Wrapping) {
     if viewport.min.x > viewport.max.x {
         swap(&mut viewport.min.x, &mut viewport.max.x);
     }
+    if wrapping.wraps_horizontally() {
+        let width = viewport.width().min(1.0);
+        viewport.min.x = viewport.min.x.rem_euclid(1.0);
+        viewport.max.x = viewport.min.x + width;
+    } else {
+        viewport.min.x = viewport.min.x.clamp(0.0, 1.0);
+        viewport.max.x = viewport.max.x.clamp(0.0, 1.0);
+    }
+    viewport.min.y = viewport.min.y.clamp(0.0, 1.0);
+    viewport.max.y = viewport.max.y.clamp(0.0, 1.0);
     if viewport.min.y > viewport.max.y {
         swap(&mut viewport.min.y, &mut viewport.max.y);
     }
@@ -0,0 +1,179 @@
+use crate::components::prelude::*;
+use crate::validation::fixes::AutoFix;
+use crate::validation::report::{Finding, FindingKind, Severity};
+
+/// Checks the adjacencies against the province definitions and the adjacency rules.  Every sea
+/// adjacency must pass through a province, every province an adjacency names must be defined, and
+/// every adjacency rule it references must exist.  Rows that do not start at a province, such as
+/// the `-1` row ending the file, are skipped, but the file must end with that row.
+#[inline]
+#[must_use]
+pub fn verify_adjacencies(
+    adjacencies: &Adjacencies,
+    adjacency_rules: &AdjacencyRules,
+    definitions: &Definitions,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for adjacency in adjacencies
+        .adjacencies
+        .iter()
+        .filter(|a| a.from.is_province())
+    {
+        let row = describe(adjacency);
+        if adjacency.adjacency_type == Some(AdjacencyType::Sea)
+            && adjacency.through_province().is_none()
+        {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::SeaAdjacencyWithoutThrough,
+                    format!("Sea {row} does not pass through a province"),
+                )
+                .in_province(adjacency.from)
+                .with_fix("Set the Through column to the sea province the adjacency crosses"),
+            );
+        }
+        let provinces = [
+            ("From", Some(adjacency.from)),
+            ("To", Some(adjacency.to)),
+            ("Through", adjacency.through_province()),
+        ];
+        let undefined = provinces.into_iter().filter_map(|(column, province)| {
+            province
+                .filter(|p| !definitions.definitions.contains_key(p))
+                .map(|p| (column, p))
+        });
+        for (column, province) in undefined {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::UndefinedAdjacencyProvince,
+                    format!("The {column} province {province} of {row} has no definition"),
+                )
+                .in_province(province)
+                .with_fix("Point the adjacency at a defined province or remove it"),
+            );
+        }
+        let missing_rule = adjacency.adjacency_rule_name.as_ref().filter(|name| {
+            !name.0.is_empty() && !adjacency_rules.adjacency_rules.contains_key(*name)
+        });
+        if let Some(name) = missing_rule {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::UndefinedAdjacencyRule,
+                    format!("The adjacency rule \"{name}\" of {row} does not exist"),
+                )
+                .in_province(adjacency.from)
+                .with_fix("Add the rule to the adjacency rules or fix its name"),
+            );
+        }
+    }
+    if !adjacencies.is_terminated() {
+        findings.push(
+            Finding::new(
+                Severity::Warning,
+                FindingKind::UnterminatedAdjacencies,
+                "The adjacencies do not end with the -1 row the game stops reading at",
+            )
+            .with_fix("Add a row of -1 to the end of adjacencies.csv")
+            .with_auto_fix(AutoFix::TerminateAdjacencies),
+        );
+    }
+    findings
+}
+
+/// Describes the row of an adjacency by its provinces and its comment, as the rows are not
+/// numbered in the file
+fn describe(adjacency: &Adjacency) -> String {
+    match adjacency.comment.as_deref().filter(|c| !c.is_empty()) {
+        Some(comment) => format!(
+            "adjacency {};{} (\"{comment}\")",
+            adjacency.from, adjacency.to
+        ),
+        None => format!("adjacency {};{}", adjacency.from, adjacency.to),
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::{HashMap, HashSet};
+
+    fn definitions() -> Definitions {
+        let definition = |id: i32, province_type: ProvinceType| {
+            DefinitionBuilder::new(id)
+                .province_type(province_type)
+                .coastal(true)
+                .build()
+        };
+        Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, ProvinceType::Land)),
+                (ProvinceId(2), definition(2, ProvinceType::Land)),
+                (ProvinceId(3), definition(3, ProvinceType::Sea)),
+            ]),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        }
+    }
+
+    #[test]
+    fn it_reports_broken_adjacencies_with_their_row() {
+        let definitions = definitions();
+        let adjacency_rules = AdjacencyRules {
+            adjacency_rules: HashMap::new(),
+        };
+        let mut unblocked = Adjacency::sea(ProvinceId(1), ProvinceId(2), ProvinceId(-1));
+        unblocked.comment = Some("Open Strait".to_owned());
+        let mut canal = Adjacency::sea(ProvinceId(1), ProvinceId(2), ProvinceId(3));
+        canal.adjacency_rule_name = Some(AdjacencyRuleName("Missing Canal".to_owned()));
+        let adjacencies = Adjacencies {
+            adjacencies: vec![
+                Adjacency::sea(ProvinceId(1), ProvinceId(2), ProvinceId(3)),
+                unblocked,
+                Adjacency::sea(ProvinceId(1), ProvinceId(999_999), ProvinceId(3)),
+                canal,
+                Adjacency::sea(ProvinceId(-1), ProvinceId(-1), ProvinceId(-1)),
+            ],
+            lines: Vec::new(),
+        };
+
+        let findings = verify_adjacencies(&adjacencies, &adjacency_rules, &definitions);
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::SeaAdjacencyWithoutThrough,
+                FindingKind::UndefinedAdjacencyProvince,
+                FindingKind::UndefinedAdjacencyRule,
+            ]
+        );
+        assert!(findings[0]
+            .message
+            .contains("adjacency 1;2 (\"Open Strait\")"));
+        assert_eq!(findings[1].province, Some(ProvinceId(999_999)));
+        assert!(findings[1].message.contains("To province 999999"));
+        assert!(findings[2].message.contains("Missing Canal"));
+    }
+
+    #[test]
+    fn it_reports_adjacencies_without_the_ending_row() {
+        let adjacency_rules = AdjacencyRules {
+            adjacency_rules: HashMap::new(),
+        };
+        let mut adjacencies = Adjacencies {
+            adjacencies: vec![Adjacency::sea(ProvinceId(1), ProvinceId(2), ProvinceId(3))],
+            lines: Vec::new(),
+        };
+        let findings = verify_adjacencies(&adjacencies, &adjacency_rules, &definitions());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::UnterminatedAdjacencies);
+        assert_eq!(findings[0].auto_fix, Some(AutoFix::TerminateAdjacencies));
+
+        adjacencies.push(Adjacency::terminator());
+        assert!(verify_adjacencies(&adjacencies, &adjacency_rules, &definitions()).is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::railways::province_neighbors;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// How far the height of a building model may be from the heightmap under it, as the nudger
+/// places models between pixels and the heights are written with two decimals
+const HEIGHT_TOLERANCE: f32 = 0.5;
+
+/// The building types that ships reach from a sea province, which must be given in their row
+const PORT_BUILDINGS: [&str; 2] = ["naval_base", "floating_harbor"];
+
+/// Checks every row of `buildings.txt` against the states, provinces and heightmap, reporting
+/// each problem of each building on its own.  A building must belong to an existing state and be
+/// placed on one of its provinces, and its height should match the heightmap under it.  A building
+/// with a sea province must name a sea province bordering the province it is on, and naval bases
+/// and floating harbors must name one.  Floating harbors are placed on their sea province, so the
+/// sea province must border one of the provinces of their state instead.
+#[inline]
+#[must_use]
+#[allow(clippy::too_many_lines)]
+pub fn verify_buildings(
+    buildings: &Buildings,
+    states: &HashMap<StateId, State>,
+    definitions: &Definitions,
+    province_index: &ProvinceIndex,
+    heightmap: &RgbImage,
+    wrapping: Wrapping,
+) -> Vec<Finding> {
+    if buildings.buildings.is_empty() {
+        return Vec::new();
+    }
+    let neighbors = province_neighbors(province_index, wrapping);
+    let borders = |a: ProvinceId, b: ProvinceId| neighbors.contains(&(a.min(b), a.max(b)));
+    let mut findings = Vec::new();
+    for (index, building) in buildings.buildings.iter().enumerate() {
+        let row = describe(building, buildings.lines.get(index).copied());
+        let pixel = building_pixel(building, province_index.dimensions());
+        let province = pixel.and_then(|(x, y)| province_index.province_at(x, y));
+        let sea = building.sea_province();
+        let on_sea = province.is_some() && province == sea;
+        let mut building_findings = Vec::new();
+
+        let state = states.get(&building.state_id);
+        match (state, province) {
+            (None, _) => building_findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::BuildingInUndefinedState,
+                    format!("The {row} belongs to a state that does not exist"),
+                )
+                .with_fix("Remove the row or change it to the state the building is in"),
+            ),
+            (Some(_), None) => building_findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::MisplacedBuilding,
+                    format!("The {row} is not on a province"),
+                )
+                .with_fix("Move the building onto a province of its state"),
+            ),
+            (Some(state), Some(province)) if !on_sea && !state.provinces.contains(&province) => {
+                building_findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::MisplacedBuilding,
+                        format!("The {row} is on the province {province} outside of its state"),
+                    )
+                    .with_fix("Move the building onto a province of its state"),
+                );
+            }
+            (Some(_), Some(_)) => {}
+        }
+
+        let ground = pixel
+            .and_then(|(x, y)| heightmap.get_pixel_checked(x, y))
+            .map(|p| f32::from(p.0[0]) / 10.0);
+        if let Some(ground) = ground {
+            if !on_sea && (building.y - ground).abs() > HEIGHT_TOLERANCE {
+                building_findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::BuildingHeightMismatch,
+                        format!(
+                            "The {row} is at the height {:.2}, but the heightmap is at {ground:.2}",
+                            building.y
+                        ),
+                    )
+                    .with_fix(format!("Set the height of the building to {ground:.2}")),
+                );
+            }
+        }
+
+        let sea_issue = match sea {
+            None if PORT_BUILDINGS.contains(&building.building_id.0.as_str()) => {
+                Some("does not name the sea province ships reach it from".to_owned())
+            }
+            None => None,
+            Some(sea) => match definitions.definitions.get(&sea) {
+                None => Some(format!(
+                    "names the sea province {sea}, which is not defined"
+                )),
+                Some(d) if d.province_type != ProvinceType::Sea => Some(format!(
+                    "names the province {sea}, which is not a sea province"
+                )),
+                Some(_) => {
+                    let coast = match (on_sea, state, province) {
+                        (true, Some(state), _) => state.provinces.iter().any(|p| borders(*p, sea)),
+                        (false, _, Some(province)) => borders(province, sea),
+                        _ => true,
+                    };
+                    (!coast)
+                        .then(|| format!("names the sea province {sea}, which it does not border"))
+                }
+            },
+        };
+        if let Some(issue) = sea_issue {
+            building_findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::InvalidBuildingSea,
+                    format!("The {row} {issue}"),
+                )
+                .with_fix("Set the sea province to the sea province the building faces"),
+            );
+        }
+
+        findings.extend(building_findings.into_iter().map(|finding| {
+            let finding = finding.in_state(building.state_id);
+            let finding = match province {
+                Some(province) => finding.in_province(province),
+                None => finding,
+            };
+            match pixel {
+                Some((x, y)) => finding.at_pixel(x, y),
+                None => finding,
+            }
+        }));
+    }
+    findings
+}
+
+/// The pixel of the provinces image a building model stands on, or `None` if it is off the map
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn building_pixel(
+    building: &StateBuilding,
+    (width, height): (u32, u32),
+) -> Option<(u32, u32)> {
+    // The vertical position is counted from the bottom of the map
+    let image_y = height as f32 - building.z;
+    (building.x >= 0.0 && image_y >= 0.0 && building.x < width as f32 && image_y < height as f32)
+        .then_some((building.x as u32, image_y as u32))
+}
+
+/// Describes a row of `buildings.txt` by its building type, state and line
+fn describe(building: &StateBuilding, line: Option<usize>) -> String {
+    let row = format!(
+        "{} of state {} at ({:.2}, {:.2})",
+        building.building_id, building.state_id, building.x, building.z
+    );
+    match line {
+        Some(line) => format!("{row} on line {line}"),
+        None => row,
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashSet;
+
+    fn definition(id: i32, province_type: ProvinceType) -> Definition {
+        DefinitionBuilder::new(id)
+            .province_type(province_type)
+            .build()
+    }
+
+    fn state(id: i32, provinces: &[i32]) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: vec![Manpower(1000)],
+            state_category: vec![StateCategoryName("rural".to_owned())],
+            history: None,
+            provinces: provinces.iter().map(|p| ProvinceId(*p)).collect(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    fn building(state: i32, id: &str, x: f32, y: f32, z: f32, sea: i32) -> StateBuilding {
+        StateBuilding {
+            state_id: StateId(state),
+            building_id: BuildingId(id.to_owned()),
+            x,
+            y,
+            z,
+            rotation: 0.0,
+            adjacent_sea_province: ProvinceId(sea),
+        }
+    }
+
+    #[test]
+    fn it_reports_each_problem_of_each_building() {
+        // Provinces 1 and 2 are land on the left, province 3 is sea and province 4 is a lake
+        let provinces = RgbImage::from_fn(20, 10, |x, y| match (x, y) {
+            (0..=4, _) => Rgb([0, 0, 1]),
+            (5..=9, _) => Rgb([0, 0, 2]),
+            (_, 0..=4) => Rgb([0, 0, 3]),
+            _ => Rgb([0, 0, 4]),
+        });
+        let provinces_by_color = (1..=4).map(|id| (Rgb([0, 0, id]), ProvinceId(id.into())));
+        let provinces_by_color = provinces_by_color.collect::<HashMap<_, _>>();
+        let province_index =
+            ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let heightmap = RgbImage::from_pixel(20, 10, Rgb([100, 100, 100]));
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, ProvinceType::Land)),
+                (ProvinceId(2), definition(2, ProvinceType::Land)),
+                (ProvinceId(3), definition(3, ProvinceType::Sea)),
+                (ProvinceId(4), definition(4, ProvinceType::Lake)),
+            ]),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        };
+        let states = HashMap::from([(StateId(1), state(1, &[1])), (StateId(2), state(2, &[2]))]);
+        let buildings = Buildings {
+            types: HashSet::new(),
+            buildings: vec![
+                building(1, "bunker", 2.0, 10.0, 5.0, 0),
+                building(2, "bunker", 2.0, 10.0, 5.0, 0),
+                building(2, "naval_base", 8.0, 10.0, 8.0, 3),
+                building(2, "naval_base", 8.0, 10.0, 4.0, 4),
+                building(1, "naval_base", 2.0, 10.0, 5.0, 3),
+                building(2, "floating_harbor", 12.0, 9.5, 8.0, 3),
+                building(2, "arms_factory", 8.0, 15.0, 5.0, 0),
+                building(9, "naval_base", 8.0, 10.0, 5.0, 0),
+            ],
+            lines: (1..=8).collect(),
+        };
+
+        let findings = verify_buildings(
+            &buildings,
+            &states,
+            &definitions,
+            &province_index,
+            &heightmap,
+            Wrapping::None,
+        );
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::MisplacedBuilding,
+                FindingKind::InvalidBuildingSea,
+                FindingKind::InvalidBuildingSea,
+                FindingKind::BuildingHeightMismatch,
+                FindingKind::BuildingInUndefinedState,
+                FindingKind::InvalidBuildingSea,
+            ]
+        );
+        assert!(findings[0].message.contains("line 2"));
+        assert_eq!(findings[0].province, Some(ProvinceId(1)));
+        assert_eq!(findings[0].pixel, Some((2, 5)));
+        assert!(findings[1].message.contains("not a sea province"));
+        assert!(findings[2].message.contains("does not border"));
+        assert!(findings[3].message.contains("15.00"));
+        assert!(findings[5].message.contains("does not name"));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::components::prelude::*;
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::fixes::AutoFix;
+use crate::validation::railways::province_neighbors;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::BTreeSet;
+
+/// Checks that every land province bordering a sea province on the provinces image is marked as
+/// coastal in the definitions, as the game does not allow naval bases in provinces that are not.
+#[inline]
+#[must_use]
+pub fn verify_coastal_flags(
+    definitions: &Definitions,
+    province_index: &ProvinceIndex,
+    wrapping: Wrapping,
+) -> Vec<Finding> {
+    let province_type = |id: ProvinceId| definitions.definitions.get(&id).map(|d| d.province_type);
+    let mut missing = BTreeSet::new();
+    for (a, b) in province_neighbors(province_index, wrapping) {
+        for (land, sea) in [(a, b), (b, a)] {
+            if province_type(land) == Some(ProvinceType::Land)
+                && province_type(sea) == Some(ProvinceType::Sea)
+            {
+                missing.insert(land);
+            }
+        }
+    }
+    missing
+        .into_iter()
+        .filter(|id| {
+            definitions
+                .definitions
+                .get(id)
+                .map_or(false, |d| !d.coastal.0)
+        })
+        .map(|id| {
+            Finding::new(
+                Severity::Warning,
+                FindingKind::MissingCoastalFlag,
+                "Land province borders a sea province but is not marked as coastal",
+            )
+            .in_province(id)
+            .with_fix("Mark the province as coastal in the definitions")
+            .with_auto_fix(AutoFix::SetCoastal(id, true))
+        })
+        .collect()
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_reports_land_bordering_the_sea_without_the_coastal_flag() {
+        // The provinces are columns of land 1, 2 and 3, then the sea province 4
+        let provinces = RgbImage::from_fn(4, 2, |x, _y| {
+            Rgb([0, 0, u8::try_from(x + 1).expect("Failed to convert x")])
+        });
+        let provinces_by_color = (1..=4)
+            .map(|id| (Rgb([0, 0, id]), ProvinceId(i32::from(id))))
+            .collect::<HashMap<_, _>>();
+        let province_index =
+            ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let definition = |id: i32, province_type: ProvinceType, coastal: bool| {
+            DefinitionBuilder::new(id)
+                .province_type(province_type)
+                .coastal(coastal)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: [
+                definition(1, ProvinceType::Land, false),
+                definition(2, ProvinceType::Land, true),
+                definition(3, ProvinceType::Land, false),
+                definition(4, ProvinceType::Sea, false),
+            ]
+            .into_iter()
+            .map(|d| (d.id, d))
+            .collect(),
+            terrain: std::collections::HashSet::new(),
+        };
+
+        let findings = verify_coastal_flags(&definitions, &province_index, Wrapping::None);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].province, Some(ProvinceId(3)));
+        assert_eq!(
+            findings[0].auto_fix,
+            Some(AutoFix::SetCoastal(ProvinceId(3), true))
+        );
+
+        // Province 1 borders the sea across the edge of a wrapping map
+        let findings = verify_coastal_flags(&definitions, &province_index, Wrapping::Horizontal);
+        let reported = findings.iter().map(|f| f.province).collect::<Vec<_>>();
+        assert_eq!(reported, vec![Some(ProvinceId(1)), Some(ProvinceId(3))]);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::components::country_history::CountryHistories;
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::HashMap;
+
+/// Checks the owners of the states against the countries of `history/countries`.  Every owner and
+/// controller of a state must be a country with a history, and the capital of each country must
+/// be a state that exists and that the country owns, as the game otherwise moves the capital to
+/// another of its states, or leaves the country without one.  Without any country histories
+/// loaded there is nothing to check against, so no findings are made.
+#[inline]
+#[must_use]
+pub fn verify_countries(
+    states: &HashMap<StateId, State>,
+    histories: &CountryHistories,
+) -> Vec<Finding> {
+    if histories.countries.is_empty() {
+        return Vec::new();
+    }
+    let mut findings = Vec::new();
+    let mut ids = states.keys().copied().collect::<Vec<_>>();
+    ids.sort_unstable();
+    for state in ids.iter().filter_map(|id| states.get(id)) {
+        let history = match &state.history {
+            Some(history) => history,
+            None => continue,
+        };
+        let tags = [
+            ("owned", Some(&history.owner)),
+            ("controlled", history.controller.as_ref()),
+        ];
+        for (relation, tag) in tags {
+            if let Some(tag) = tag.filter(|t| !histories.contains(t)) {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::UndefinedCountry,
+                        format!("State is {relation} by {tag}, which history/countries lacks"),
+                    )
+                    .in_state(state.id)
+                    .with_fix(format!(
+                        "Add a history/countries/{tag} file, or fix the tag"
+                    )),
+                );
+            }
+        }
+    }
+    let mut countries = histories.countries.values().collect::<Vec<_>>();
+    countries.sort_unstable_by(|a, b| a.tag.cmp(&b.tag));
+    for country in countries {
+        let capital = match country.capital {
+            Some(capital) => capital,
+            None => continue,
+        };
+        let owner = states
+            .get(&capital)
+            .map(|state| state.history.as_ref().map(|history| &history.owner));
+        match owner {
+            None => findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::UndefinedCapitalState,
+                    format!(
+                        "The capital of {} is state {capital}, which does not exist",
+                        country.tag
+                    ),
+                )
+                .with_fix("Set the capital to a state of the country"),
+            ),
+            Some(owner) if owner != Some(&country.tag) => findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::CapitalNotOwned,
+                    format!(
+                        "The capital of {} is a state it does not own, so the game moves it",
+                        country.tag
+                    ),
+                )
+                .in_state(capital)
+                .with_fix("Set the capital to a state of the country, or give it the state"),
+            ),
+            Some(_owner) => {}
+        }
+    }
+    findings
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::country_history::CountryHistory;
+    use crate::components::state::StateHistory;
+    use std::collections::HashSet;
+
+    fn state(id: i32, owner: &str, controller: Option<&str>) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: Vec::new(),
+            state_category: Vec::new(),
+            history: Some(StateHistory {
+                owner: CountryTag(owner.to_owned()),
+                controller: controller.map(|c| CountryTag(c.to_owned())),
+                victory_points: Vec::new(),
+            }),
+            provinces: HashSet::new(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    fn history(tag: &str, capital: i32) -> (CountryTag, CountryHistory) {
+        let history = CountryHistory {
+            tag: CountryTag(tag.to_owned()),
+            capital: Some(StateId(capital)),
+        };
+        (history.tag.clone(), history)
+    }
+
+    #[test]
+    fn it_reports_undefined_countries_and_misplaced_capitals() {
+        let states = HashMap::from([
+            (StateId(1), state(1, "GER", None)),
+            (StateId(2), state(2, "GER", Some("XXX"))),
+            (StateId(3), state(3, "YYY", None)),
+        ]);
+        assert!(verify_countries(&states, &CountryHistories::default()).is_empty());
+
+        let histories = CountryHistories {
+            countries: HashMap::from([history("GER", 1), history("POL", 2), history("SOV", 9)]),
+        };
+        let findings = verify_countries(&states, &histories);
+        let kinds = findings
+            .iter()
+            .map(|f| (f.kind, f.state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (FindingKind::UndefinedCountry, Some(StateId(2))),
+                (FindingKind::UndefinedCountry, Some(StateId(3))),
+                (FindingKind::CapitalNotOwned, Some(StateId(2))),
+                (FindingKind::UndefinedCapitalState, None),
+            ]
+        );
+        assert!(findings[0].message.contains("controlled by XXX"));
+        assert!(findings[1].message.contains("owned by YYY"));
+        assert!(findings[3].message.contains("capital of SOV"));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::components::prelude::*;
+use crate::validation::report::ValidationReport;
+use derive_more::Display;
+
+/// A change to the map that fixes the problem of a finding without any choices left to make, so
+/// that it can be applied with one click or together with the fixes of other findings.
+#[derive(Clone, Debug, Display, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AutoFix {
+    /// Sets whether a province is coastal in the definitions
+    #[display(fmt = "Set province {} to coastal {}", _0, _1)]
+    SetCoastal(ProvinceId, bool),
+    /// Moves a province to a continent in the definitions
+    #[display(fmt = "Move province {} to continent {}", _0, _1)]
+    SetContinent(ProvinceId, ContinentIndex),
+    /// Appends the row the game stops reading adjacencies.csv at
+    #[display(fmt = "Add the terminating row to the adjacencies")]
+    TerminateAdjacencies,
+    /// Keeps only the last of the `manpower` entries of a state, which is the one the game uses
+    #[display(fmt = "Keep only the last manpower entry of state {}", _0)]
+    DeduplicateManpower(StateId),
+}
+
+impl ValidationReport {
+    /// The fixes of the findings that can be fixed automatically, without duplicates
+    #[inline]
+    #[must_use]
+    pub fn auto_fixes(&self) -> Vec<AutoFix> {
+        let mut fixes = Vec::new();
+        for fix in self.findings.iter().filter_map(|f| f.auto_fix.as_ref()) {
+            if !fixes.contains(fix) {
+                fixes.push(fix.clone());
+            }
+        }
+        fixes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::report::{Finding, FindingKind, Severity};
+
+    #[test]
+    fn it_collects_each_fix_once() {
+        let mut report = ValidationReport::default();
+        let fix = AutoFix::SetContinent(ProvinceId(1), ContinentIndex(0));
+        report.extend([
+            Finding::new(Severity::Error, FindingKind::SeaProvinceOnContinent, "a")
+                .with_auto_fix(fix.clone()),
+            Finding::new(Severity::Warning, FindingKind::XCrossing, "b"),
+            Finding::new(Severity::Error, FindingKind::SeaProvinceOnContinent, "c")
+                .with_auto_fix(fix.clone()),
+            Finding::new(Severity::Warning, FindingKind::UnterminatedAdjacencies, "d")
+                .with_auto_fix(AutoFix::TerminateAdjacencies),
+        ]);
+        assert_eq!(
+            report.auto_fixes(),
+            vec![fix, AutoFix::TerminateAdjacencies]
+        );
+    }
+}
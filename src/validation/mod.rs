@@ -0,0 +1,42 @@
+/// Holds the checks of the adjacencies against the provinces and adjacency rules
+pub mod adjacencies;
+/// Holds the checks of the building positions against the states, provinces and heightmap
+pub mod buildings;
+/// Holds the check of the coastal flags of the provinces
+pub mod coastal;
+/// Holds the checks of the owners of the states and the capitals of the countries
+pub mod countries;
+/// Holds the changes that fix findings automatically
+pub mod fixes;
+/// Holds the profiles that change the severities of the validation rules
+pub mod profile;
+/// Holds the check of the bounding boxes of provinces
+pub mod province_bounds;
+/// Holds the search for provinces split into separate areas
+pub mod province_contiguity;
+/// Holds the checks of province types against the heightmap
+pub mod province_heights;
+/// Holds the check of the number of pixels in each province
+pub mod province_sizes;
+/// Holds the checks of the continents and terrain of provinces against their types
+pub mod province_types;
+/// Holds the checks of the railways against the provinces they run through
+pub mod railways;
+/// Holds the report of every problem found while validating a map
+pub mod report;
+/// Holds the classification of the borders between provinces crossed by rivers
+pub mod river_crossings;
+/// Holds the checks of the rivers image
+pub mod rivers;
+/// Holds the checks of the states against the provinces and strategic regions
+pub mod states;
+/// Holds the search for straits between land provinces
+pub mod straits;
+/// Holds the analysis of where supply reaches over land, adjacencies and railways
+pub mod supply;
+/// Holds the checks of the weather periods of the strategic regions
+pub mod weather_periods;
+/// Holds the checks of the weather positions against the strategic regions
+pub mod weather_positions;
+/// Holds the detection of corners where four provinces meet
+pub mod x_crossings;
@@ -0,0 +1,190 @@
+use crate::validation::report::{FindingKind, Severity, ValidationReport};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The severity a rule's findings are reported with, or whether they are not reported at all.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSeverity {
+    /// The findings are reported as errors
+    Error,
+    /// The findings are reported as warnings
+    Warning,
+    /// The findings are not reported
+    Ignore,
+}
+
+impl RuleSeverity {
+    /// The severity findings are reported with, or `None` if they are not reported
+    #[inline]
+    #[must_use]
+    pub const fn severity(self) -> Option<Severity> {
+        match self {
+            Self::Error => Some(Severity::Error),
+            Self::Warning => Some(Severity::Warning),
+            Self::Ignore => None,
+        }
+    }
+}
+
+/// A set of severities for the validation rules, picked to suit how far along a map is.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ValidationProfile {
+    /// Anything the game complains about is an error
+    VanillaStrict,
+    /// Only what crashes the game is an error, and content that is usually added last, such as
+    /// weather positions, is not checked
+    EarlyDevelopment,
+    /// The severities the checks report with
+    #[default]
+    Release,
+}
+
+impl ValidationProfile {
+    /// The severity the profile gives a finding that its check reported with the given
+    /// severity, or `None` if the profile does not report it.  Information is reported as it is.
+    #[inline]
+    #[must_use]
+    pub const fn severity(self, kind: FindingKind, reported: Severity) -> Option<Severity> {
+        match (self, reported) {
+            (_, Severity::Info) | (Self::Release, _) => Some(reported),
+            (Self::VanillaStrict, _) => Some(Severity::Error),
+            (Self::EarlyDevelopment, _) if crashes_game(kind) => Some(Severity::Error),
+            (Self::EarlyDevelopment, _) if is_finishing_touch(kind) => None,
+            (Self::EarlyDevelopment, _) => Some(Severity::Warning),
+        }
+    }
+}
+
+/// Whether the game crashes on launch with a finding of the kind
+const fn crashes_game(kind: FindingKind) -> bool {
+    matches!(
+        kind,
+        FindingKind::UndefinedProvinceColor
+            | FindingKind::DuplicateProvinceColor
+            | FindingKind::DiscontiguousProvince
+            | FindingKind::UndefinedTerrain
+            | FindingKind::UndefinedStateProvince
+            | FindingKind::DuplicateStateId
+            | FindingKind::StateAcrossStrategicRegions
+            | FindingKind::UndefinedAdjacencyProvince
+            | FindingKind::UndefinedRailwayProvince
+    )
+}
+
+/// Whether a finding of the kind is about content that is usually only added once the provinces
+/// and states are settled
+const fn is_finishing_touch(kind: FindingKind) -> bool {
+    matches!(
+        kind,
+        FindingKind::SmallProvince
+            | FindingKind::XCrossing
+            | FindingKind::River(_)
+            | FindingKind::ProvinceWithoutStrategicRegion
+            | FindingKind::StrategicRegionWithoutWeatherPosition
+            | FindingKind::WeatherPeriodGap
+            | FindingKind::MisplacedWeatherPosition
+            | FindingKind::MisplacedBuilding
+            | FindingKind::BuildingHeightMismatch
+            | FindingKind::UnsuppliedState
+            | FindingKind::UnsuppliedProvince
+            | FindingKind::DisconnectedRailwayNetwork
+            | FindingKind::MissingCoastalFlag
+    )
+}
+
+impl ValidationReport {
+    /// Changes the severities of the findings to those of the profile, dropping the findings of
+    /// ignored rules.  A rule named in the overrides gets the severity given there instead,
+    /// whatever the profile.  Findings reported as information are kept as they are.
+    #[inline]
+    pub fn apply_profile(
+        &mut self,
+        profile: ValidationProfile,
+        overrides: &BTreeMap<String, RuleSeverity>,
+    ) {
+        let findings = std::mem::take(&mut self.findings);
+        self.findings = findings
+            .into_iter()
+            .filter_map(|mut finding| {
+                if finding.severity == Severity::Info {
+                    return Some(finding);
+                }
+                finding.severity = match overrides.get(finding.kind.rule()) {
+                    Some(rule) => rule.severity(),
+                    None => profile.severity(finding.kind, finding.severity),
+                }?;
+                Some(finding)
+            })
+            .collect();
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::report::Finding;
+
+    fn report() -> ValidationReport {
+        let mut report = ValidationReport::default();
+        report.extend([
+            Finding::new(
+                Severity::Error,
+                FindingKind::UndefinedProvinceColor,
+                "crash",
+            ),
+            Finding::new(
+                Severity::Error,
+                FindingKind::VictoryPointsOutsideState,
+                "error",
+            ),
+            Finding::new(Severity::Warning, FindingKind::XCrossing, "warning"),
+        ]);
+        report
+    }
+
+    fn severities(report: &ValidationReport) -> Vec<Severity> {
+        report.findings.iter().map(|f| f.severity).collect()
+    }
+
+    #[test]
+    fn it_changes_severities_by_profile() {
+        let mut strict = report();
+        strict.apply_profile(ValidationProfile::VanillaStrict, &BTreeMap::new());
+        assert_eq!(severities(&strict), vec![Severity::Error; 3]);
+
+        let mut release = report();
+        release.apply_profile(ValidationProfile::Release, &BTreeMap::new());
+        assert_eq!(release, report());
+
+        let mut early = report();
+        early.apply_profile(ValidationProfile::EarlyDevelopment, &BTreeMap::new());
+        assert_eq!(severities(&early), vec![Severity::Error, Severity::Warning]);
+    }
+
+    #[test]
+    fn it_lets_rules_override_the_profile() {
+        let overrides = BTreeMap::from([
+            ("x_crossing".to_owned(), RuleSeverity::Error),
+            ("undefined_province_color".to_owned(), RuleSeverity::Ignore),
+        ]);
+        let mut report = report();
+        report.apply_profile(ValidationProfile::EarlyDevelopment, &overrides);
+        let kinds = report.findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::VictoryPointsOutsideState,
+                FindingKind::XCrossing
+            ]
+        );
+        assert_eq!(
+            severities(&report),
+            vec![Severity::Warning, Severity::Error]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::components::prelude::*;
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::{Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// The fraction of the map's width or height a province's bounding box may span before the game
+/// warns that it is "TOO LARGE BOX".
+const MAXIMUM_BOX_FRACTION: u32 = 8;
+
+/// The pixels a color spans on the provinces image.  The columns are also tracked shifted by half
+/// of the map's width, so that a province crossing the edge of a wrapping map is not mistaken for
+/// one spanning the whole map.
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    first_pixel: (u32, u32),
+    min_x: u32,
+    max_x: u32,
+    min_shifted_x: u32,
+    max_shifted_x: u32,
+    min_y: u32,
+    max_y: u32,
+}
+
+impl Bounds {
+    const fn new(x: u32, shifted_x: u32, y: u32) -> Self {
+        Self {
+            first_pixel: (x, y),
+            min_x: x,
+            max_x: x,
+            min_shifted_x: shifted_x,
+            max_shifted_x: shifted_x,
+            min_y: y,
+            max_y: y,
+        }
+    }
+
+    fn include(&mut self, x: u32, shifted_x: u32, y: u32) {
+        self.min_x = self.min_x.min(x);
+        self.max_x = self.max_x.max(x);
+        self.min_shifted_x = self.min_shifted_x.min(shifted_x);
+        self.max_shifted_x = self.max_shifted_x.max(shifted_x);
+        self.min_y = self.min_y.min(y);
+        self.max_y = self.max_y.max(y);
+    }
+
+    #[allow(clippy::integer_arithmetic)]
+    fn size(&self, wrapping: Wrapping) -> (u32, u32) {
+        let mut width = self.max_x - self.min_x;
+        if wrapping.wraps_horizontally() {
+            width = width.min(self.max_shifted_x - self.min_shifted_x);
+        }
+        (width + 1, self.max_y - self.min_y + 1)
+    }
+}
+
+/// Checks the bounding box of every province on the provinces image, reporting the provinces
+/// whose box spans more than an eighth of the map's width or height.  The game warns about these
+/// as "TOO LARGE BOX", and they are usually two separate provinces sharing a color.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn verify_province_bounds(
+    provinces: &RgbImage,
+    provinces_by_color: &HashMap<Rgb<u8>, ProvinceId>,
+    wrapping: Wrapping,
+) -> Vec<Finding> {
+    let (width, height) = provinces.dimensions();
+    let mut bounds: HashMap<Rgb<u8>, Bounds> = HashMap::new();
+    for (x, y, color) in provinces.enumerate_pixels() {
+        let shifted_x = (x + width / 2) % width;
+        bounds
+            .entry(*color)
+            .and_modify(|b| b.include(x, shifted_x, y))
+            .or_insert_with(|| Bounds::new(x, shifted_x, y));
+    }
+    let max_width = width / MAXIMUM_BOX_FRACTION;
+    let max_height = height / MAXIMUM_BOX_FRACTION;
+    let mut oversized = bounds
+        .into_iter()
+        .filter_map(|(color, b)| {
+            let (box_width, box_height) = b.size(wrapping);
+            (box_width > max_width || box_height > max_height).then_some((
+                color,
+                b.first_pixel,
+                box_width,
+                box_height,
+            ))
+        })
+        .collect::<Vec<_>>();
+    oversized.sort_unstable_by_key(|(_color, (x, y), _width, _height)| (*y, *x));
+    oversized
+        .into_iter()
+        .map(|(color, (x, y), box_width, box_height)| {
+            let finding = Finding::new(
+                Severity::Warning,
+                FindingKind::OversizeProvince,
+                format!(
+                    "Color {:?} spans a box of {box_width}x{box_height} pixels, more than an \
+                     eighth of the map",
+                    color.0
+                ),
+            )
+            .at_pixel(x, y)
+            .with_fix("Give each separate area of the color its own color and definition");
+            match provinces_by_color.get(&color) {
+                Some(province) => finding.in_province(*province),
+                None => finding,
+            }
+        })
+        .collect()
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reported(findings: &[Finding]) -> Vec<Option<ProvinceId>> {
+        findings.iter().map(|f| f.province).collect()
+    }
+
+    #[test]
+    fn it_reports_provinces_spanning_too_much_of_the_map() {
+        // A 32x32 map allows boxes of 4x4 pixels, so only the small provinces can pass
+        let mut provinces = RgbImage::from_pixel(32, 32, Rgb([0, 0, 1]));
+        for (x, y) in [(0, 0), (1, 0), (30, 0), (31, 1)] {
+            provinces.put_pixel(x, y, Rgb([0, 0, 2]));
+        }
+        for (x, y) in [(10, 10), (10, 16)] {
+            provinces.put_pixel(x, y, Rgb([0, 0, 3]));
+        }
+        provinces.put_pixel(20, 20, Rgb([0, 0, 4]));
+        let provinces_by_color = HashMap::from([
+            (Rgb([0, 0, 1]), ProvinceId(1)),
+            (Rgb([0, 0, 2]), ProvinceId(2)),
+            (Rgb([0, 0, 3]), ProvinceId(3)),
+        ]);
+
+        let findings = verify_province_bounds(&provinces, &provinces_by_color, Wrapping::None);
+        assert_eq!(
+            reported(&findings),
+            vec![
+                Some(ProvinceId(2)),
+                Some(ProvinceId(1)),
+                Some(ProvinceId(3))
+            ]
+        );
+
+        // Province 2 only crosses the edge of a wrapping map
+        let findings =
+            verify_province_bounds(&provinces, &provinces_by_color, Wrapping::Horizontal);
+        assert_eq!(
+            reported(&findings),
+            vec![Some(ProvinceId(1)), Some(ProvinceId(3))]
+        );
+        assert_eq!(findings[1].pixel, Some((10, 10)));
+    }
+}
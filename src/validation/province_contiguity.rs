@@ -0,0 +1,181 @@
+use crate::components::prelude::*;
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// A connected area of pixels of one province on the provinces image
+#[derive(Copy, Clone, Debug)]
+struct Blob {
+    first_pixel: (u32, u32),
+    size: u32,
+    sum_x: u64,
+    sum_y: u64,
+}
+
+impl Blob {
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    const fn centroid(&self) -> (u32, u32) {
+        let size = if self.size == 0 { 1 } else { self.size as u64 };
+        ((self.sum_x / size) as u32, (self.sum_y / size) as u32)
+    }
+}
+
+/// Splits every province of the provinces image into its connected areas, reporting the provinces
+/// whose pixels form more than one area, which crashes the game.  Each finding lists the size and
+/// centroid of every area, largest first, and is placed at the first pixel of the largest stray
+/// area.  The findings are ordered by the number of pixels outside the largest area of their
+/// province, so that the worst offenders come first.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+pub fn verify_province_contiguity(
+    province_index: &ProvinceIndex,
+    wrapping: Wrapping,
+) -> Vec<Finding> {
+    let (width, height) = province_index.dimensions();
+    let pixels = province_index.provinces().collect::<Vec<_>>();
+    let mut visited = vec![false; pixels.len()];
+    let mut blobs: HashMap<ProvinceId, Vec<Blob>> = HashMap::new();
+    let mut stack = Vec::new();
+    for (start, province) in pixels.iter().enumerate() {
+        let province = match *province {
+            Some(province) => province,
+            None => continue,
+        };
+        match visited.get_mut(start) {
+            Some(v) if !*v => *v = true,
+            _ => continue,
+        }
+        let mut blob = Blob {
+            first_pixel: (start as u32 % width, start as u32 / width),
+            size: 0,
+            sum_x: 0,
+            sum_y: 0,
+        };
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            blob.size += 1;
+            blob.sum_x += u64::from(i as u32 % width);
+            blob.sum_y += u64::from(i as u32 / width);
+            for n in wrapping.neighbors(i, width, height).into_iter().flatten() {
+                if pixels.get(n) != Some(&Some(province)) {
+                    continue;
+                }
+                if let Some(v) = visited.get_mut(n) {
+                    if !*v {
+                        *v = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        blobs.entry(province).or_default().push(blob);
+    }
+
+    let mut split = blobs
+        .into_iter()
+        .filter_map(|(province, mut blobs)| {
+            blobs.sort_by_key(|b| (Reverse(b.size), b.first_pixel.1, b.first_pixel.0));
+            let (largest, stray_blob) = match *blobs.as_slice() {
+                [largest, stray_blob, ..] => (largest, stray_blob),
+                _ => return None,
+            };
+            let stray = blobs.iter().skip(1).map(|b| b.size).sum::<u32>();
+            Some((
+                province,
+                stray,
+                largest.first_pixel,
+                stray_blob.first_pixel,
+                blobs,
+            ))
+        })
+        .collect::<Vec<_>>();
+    split.sort_unstable_by_key(|(_province, stray, (x, y), _stray_pixel, _blobs)| {
+        (Reverse(*stray), *y, *x)
+    });
+    split
+        .into_iter()
+        .map(|(province, stray, _first_pixel, (x, y), blobs)| {
+            let areas = blobs
+                .iter()
+                .map(|b| {
+                    let (x, y) = b.centroid();
+                    format!("{} pixels around ({x}, {y})", b.size)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            Finding::new(
+                Severity::Error,
+                FindingKind::DiscontiguousProvince,
+                format!(
+                    "Province {province} is split into {} separate areas with {stray} stray \
+                     pixels: {areas}",
+                    blobs.len()
+                ),
+            )
+            .in_province(province)
+            .at_pixel(x, y)
+            .with_fix("Join the areas, or give each stray area its own color and definition")
+        })
+        .collect()
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn it_reports_provinces_split_into_separate_areas() {
+        let mut provinces = RgbImage::from_pixel(8, 4, Rgb([0, 0, 1]));
+        // Province 2 has a stray pixel, and province 3 has a stray area of two pixels
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (5, 3)] {
+            provinces.put_pixel(x, y, Rgb([0, 0, 2]));
+        }
+        for (x, y) in [(3, 0), (3, 1), (3, 2), (6, 0), (6, 1)] {
+            provinces.put_pixel(x, y, Rgb([0, 0, 3]));
+        }
+        // Province 4 only crosses the edge of the map
+        for y in 0..4 {
+            provinces.put_pixel(7, y, Rgb([0, 0, 4]));
+        }
+        provinces.put_pixel(0, 3, Rgb([0, 0, 4]));
+        let provinces_by_color = HashMap::from([
+            (Rgb([0, 0, 1]), ProvinceId(1)),
+            (Rgb([0, 0, 2]), ProvinceId(2)),
+            (Rgb([0, 0, 3]), ProvinceId(3)),
+            (Rgb([0, 0, 4]), ProvinceId(4)),
+        ]);
+
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+
+        let findings = verify_province_contiguity(&index, Wrapping::Horizontal);
+        let reported = findings.iter().map(|f| f.province).collect::<Vec<_>>();
+        assert_eq!(reported, vec![Some(ProvinceId(3)), Some(ProvinceId(2))]);
+        assert_eq!(findings[0].pixel, Some((6, 0)));
+        assert!(findings[0]
+            .message
+            .contains("3 pixels around (3, 1), 2 pixels around (6, 0)"));
+        assert_eq!(findings[1].pixel, Some((5, 3)));
+
+        let findings = verify_province_contiguity(&index, Wrapping::None);
+        let reported = findings.iter().map(|f| f.province).collect::<Vec<_>>();
+        assert_eq!(
+            reported,
+            vec![
+                Some(ProvinceId(3)),
+                Some(ProvinceId(2)),
+                Some(ProvinceId(4))
+            ]
+        );
+    }
+}
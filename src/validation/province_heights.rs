@@ -0,0 +1,304 @@
+use crate::components::prelude::*;
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::RgbImage;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// The height on the heightmap at which land meets the sea.
+pub const SEA_LEVEL: u8 = 95;
+
+/// The fraction of a province's pixels that must be on the wrong side of sea level before the
+/// province is reported.
+const MISMATCH_THRESHOLD: f32 = 0.5;
+
+/// The way a province's type disagrees with the heightmap.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProvinceHeightIssueKind {
+    /// A sea province lying mostly above sea level
+    SeaAboveSeaLevel,
+    /// A land province lying mostly below sea level
+    LandBelowSeaLevel,
+    /// A lake province bordering a sea province
+    LakeNotEnclosed,
+}
+
+/// A province whose type disagrees with the heightmap.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct ProvinceHeightIssue {
+    /// The province with the issue
+    pub province: ProvinceId,
+    /// The kind of issue
+    pub kind: ProvinceHeightIssueKind,
+    /// The average height of the province on the heightmap
+    pub average_height: f32,
+    /// How confident the check is that the province is wrong, from 0.0 to 1.0.
+    /// For height issues this is the fraction of pixels on the wrong side of sea level, and for
+    /// lakes it is the fraction of the lake's border touching the sea.
+    pub confidence: f32,
+}
+
+impl From<ProvinceHeightIssue> for Finding {
+    #[inline]
+    fn from(issue: ProvinceHeightIssue) -> Self {
+        let (message, fix) = match issue.kind {
+            ProvinceHeightIssueKind::SeaAboveSeaLevel => (
+                "Sea province lies mostly above sea level",
+                "Lower the heightmap under the province or make it a land province",
+            ),
+            ProvinceHeightIssueKind::LandBelowSeaLevel => (
+                "Land province lies mostly below sea level",
+                "Raise the heightmap under the province or make it a sea province",
+            ),
+            ProvinceHeightIssueKind::LakeNotEnclosed => (
+                "Lake province borders the sea",
+                "Make the province a sea province or separate it from the sea with land",
+            ),
+        };
+        Finding::new(
+            Severity::Warning,
+            FindingKind::ProvinceHeight(issue.kind),
+            format!(
+                "{message} (average height {:.1}, confidence {:.2})",
+                issue.average_height, issue.confidence
+            ),
+        )
+        .in_province(issue.province)
+        .with_fix(fix)
+    }
+}
+
+/// The pixel statistics gathered for a single province.
+#[derive(Default, Debug)]
+struct ProvinceStats {
+    pixels: u32,
+    below_sea_level: u32,
+    total_height: u64,
+    border_pixels: u32,
+    sea_border_pixels: u32,
+}
+
+/// Cross-checks the type of each province against the heightmap.  Sea provinces should lie mostly
+/// below sea level, land provinces mostly above it, and lakes should be enclosed by land.
+/// The issues are returned with the worst offenders first.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+pub fn verify_province_heights(
+    province_index: &ProvinceIndex,
+    heightmap: &RgbImage,
+    definitions: &Definitions,
+    wrapping: Wrapping,
+) -> Vec<ProvinceHeightIssue> {
+    let province_type = |id: &ProvinceId| {
+        definitions
+            .definitions
+            .get(id)
+            .map(|definition| definition.province_type)
+    };
+    let (width, _height) = province_index.dimensions();
+    let mut stats: HashMap<ProvinceId, ProvinceStats> = HashMap::new();
+    for (position, province) in (0_u32..).zip(province_index.provinces()) {
+        let id = match province {
+            Some(id) => id,
+            None => continue,
+        };
+        let (x, y) = (position % width, position / width);
+        let entry = stats.entry(id).or_default();
+        if let Some(height) = heightmap.get_pixel_checked(x, y).map(|p| p.0[0]) {
+            entry.pixels += 1;
+            entry.total_height += u64::from(height);
+            if height < SEA_LEVEL {
+                entry.below_sea_level += 1;
+            }
+        }
+        if province_type(&id) != Some(ProvinceType::Lake) {
+            continue;
+        }
+        let neighbors = neighboring_provinces(province_index, wrapping, (x, y), id);
+        if !neighbors.is_empty() {
+            entry.border_pixels += 1;
+            if neighbors
+                .iter()
+                .any(|n| province_type(n) == Some(ProvinceType::Sea))
+            {
+                entry.sea_border_pixels += 1;
+            }
+        }
+    }
+
+    let mut issues = stats
+        .into_iter()
+        .filter(|(_id, s)| s.pixels > 0)
+        .filter_map(|(id, s)| {
+            let average_height = s.total_height as f32 / s.pixels as f32;
+            let below = s.below_sea_level as f32 / s.pixels as f32;
+            let (kind, confidence) = match province_type(&id)? {
+                ProvinceType::Sea => (ProvinceHeightIssueKind::SeaAboveSeaLevel, 1.0 - below),
+                ProvinceType::Land => (ProvinceHeightIssueKind::LandBelowSeaLevel, below),
+                ProvinceType::Lake if s.sea_border_pixels > 0 => (
+                    ProvinceHeightIssueKind::LakeNotEnclosed,
+                    s.sea_border_pixels as f32 / s.border_pixels as f32,
+                ),
+                ProvinceType::Lake => return None,
+            };
+            let reported = match kind {
+                ProvinceHeightIssueKind::LakeNotEnclosed => true,
+                ProvinceHeightIssueKind::SeaAboveSeaLevel
+                | ProvinceHeightIssueKind::LandBelowSeaLevel => confidence > MISMATCH_THRESHOLD,
+            };
+            reported.then_some(ProvinceHeightIssue {
+                province: id,
+                kind,
+                average_height,
+                confidence,
+            })
+        })
+        .collect::<Vec<_>>();
+    issues.sort_by(|a, b| {
+        b.confidence
+            .partial_cmp(&a.confidence)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.province.cmp(&b.province))
+    });
+    issues
+}
+
+/// Gets the provinces other than `id` that touch the given pixel.
+fn neighboring_provinces(
+    province_index: &ProvinceIndex,
+    wrapping: Wrapping,
+    (x, y): (u32, u32),
+    id: ProvinceId,
+) -> HashSet<ProvinceId> {
+    let mut neighbors = HashSet::new();
+    let (width, height) = province_index.dimensions();
+    let candidates = wrapping.neighbor_points(x, y, width, height);
+    for (nx, ny) in candidates.into_iter().flatten() {
+        if let Some(neighbor) = province_index.province_at(nx, ny) {
+            if neighbor != id {
+                neighbors.insert(neighbor);
+            }
+        }
+    }
+    neighbors
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use image::Rgb;
+
+    const LAND: Rgb<u8> = Rgb([10, 10, 10]);
+    const SEA: Rgb<u8> = Rgb([20, 20, 20]);
+    const LAKE: Rgb<u8> = Rgb([30, 30, 30]);
+
+    fn definition(id: i32, color: Rgb<u8>, province_type: ProvinceType) -> Definition {
+        DefinitionBuilder::new(id)
+            .color(color)
+            .province_type(province_type)
+            .build()
+    }
+
+    fn definitions() -> (Definitions, HashMap<Rgb<u8>, ProvinceId>) {
+        let definitions = [
+            definition(1, LAND, ProvinceType::Land),
+            definition(2, SEA, ProvinceType::Sea),
+            definition(3, LAKE, ProvinceType::Lake),
+        ];
+        let by_color = definitions
+            .iter()
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), d.id))
+            .collect();
+        let definitions = Definitions {
+            definitions: definitions.into_iter().map(|d| (d.id, d)).collect(),
+            terrain: HashSet::new(),
+        };
+        (definitions, by_color)
+    }
+
+    #[test]
+    fn it_accepts_consistent_provinces() {
+        let (definitions, by_color) = definitions();
+        // Left half is land, right half is sea, with a lake in the middle of the land.
+        let provinces = RgbImage::from_fn(8, 8, |x, y| match (x, y) {
+            (1..=2, 3..=4) => LAKE,
+            (0..=3, _) => LAND,
+            _ => SEA,
+        });
+        let heightmap = RgbImage::from_fn(8, 8, |x, _y| {
+            if x < 4 {
+                Rgb([120, 120, 120])
+            } else {
+                Rgb([60, 60, 60])
+            }
+        });
+        let index = ProvinceIndex::new(&provinces, &by_color).expect("Failed to index");
+        let issues = verify_province_heights(&index, &heightmap, &definitions, Wrapping::None);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn it_reports_provinces_on_the_wrong_side_of_sea_level() {
+        let (definitions, by_color) = definitions();
+        let provinces = RgbImage::from_fn(8, 8, |x, _y| if x < 4 { LAND } else { SEA });
+        // All the land is below sea level, but only a quarter of the sea is above it.
+        let heightmap = RgbImage::from_fn(8, 8, |x, _y| {
+            if x < 7 {
+                Rgb([60, 60, 60])
+            } else {
+                Rgb([120, 120, 120])
+            }
+        });
+        let index = ProvinceIndex::new(&provinces, &by_color).expect("Failed to index");
+        let issues = verify_province_heights(&index, &heightmap, &definitions, Wrapping::None);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].province, ProvinceId(1));
+        assert_eq!(issues[0].kind, ProvinceHeightIssueKind::LandBelowSeaLevel);
+        assert!((issues[0].confidence - 1.0).abs() < f32::EPSILON);
+        assert!((issues[0].average_height - 60.0).abs() < f32::EPSILON);
+
+        let heightmap = RgbImage::from_fn(8, 8, |x, _y| {
+            if x < 5 {
+                Rgb([120, 120, 120])
+            } else {
+                Rgb([60, 60, 60])
+            }
+        });
+        let index = ProvinceIndex::new(&provinces, &by_color).expect("Failed to index");
+        let issues = verify_province_heights(&index, &heightmap, &definitions, Wrapping::None);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn it_reports_lakes_touching_the_sea() {
+        let (definitions, by_color) = definitions();
+        let provinces = RgbImage::from_fn(8, 8, |x, y| match (x, y) {
+            (4..=5, 3..=4) => LAKE,
+            (0..=3, _) => LAND,
+            _ => SEA,
+        });
+        let heightmap = RgbImage::from_fn(8, 8, |x, _y| {
+            if x < 4 {
+                Rgb([120, 120, 120])
+            } else {
+                Rgb([60, 60, 60])
+            }
+        });
+        let index = ProvinceIndex::new(&provinces, &by_color).expect("Failed to index");
+        let issues = verify_province_heights(&index, &heightmap, &definitions, Wrapping::None);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].province, ProvinceId(3));
+        assert_eq!(issues[0].kind, ProvinceHeightIssueKind::LakeNotEnclosed);
+        assert!(issues[0].confidence > 0.0);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::components::prelude::*;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::{Rgb, RgbImage};
+use std::collections::HashMap;
+
+/// The number of pixels below which the game warns that a province is too small, as set by the
+/// define of the same name.
+pub const MINIMUM_PROVINCE_SIZE_IN_PIXELS: u32 = 8;
+
+/// Counts the pixels of every province on the provinces image, reporting the provinces with fewer
+/// than `minimum_size` pixels.  Each finding is placed at the first pixel of its province.
+#[inline]
+#[must_use]
+pub fn verify_province_sizes(
+    provinces: &RgbImage,
+    provinces_by_color: &HashMap<Rgb<u8>, ProvinceId>,
+    minimum_size: u32,
+) -> Vec<Finding> {
+    let mut sizes: HashMap<Rgb<u8>, ((u32, u32), u32)> = HashMap::new();
+    for (x, y, color) in provinces.enumerate_pixels() {
+        let (_first_pixel, size) = sizes.entry(*color).or_insert(((x, y), 0));
+        *size = size.saturating_add(1);
+    }
+    let mut small = sizes
+        .into_iter()
+        .filter(|(_color, (_first_pixel, size))| *size < minimum_size)
+        .collect::<Vec<_>>();
+    small.sort_unstable_by_key(|(_color, ((x, y), _size))| (*y, *x));
+    small
+        .into_iter()
+        .map(|(color, ((x, y), size))| {
+            let finding = Finding::new(
+                Severity::Warning,
+                FindingKind::SmallProvince,
+                format!("Province has {size} pixels, fewer than the minimum of {minimum_size}"),
+            )
+            .at_pixel(x, y)
+            .with_fix("Grow the province or merge it into a neighboring province");
+            match provinces_by_color.get(&color) {
+                Some(province) => finding.in_province(*province),
+                None => finding,
+            }
+        })
+        .collect()
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reports_provinces_smaller_than_the_minimum() {
+        let mut provinces = RgbImage::from_pixel(8, 8, Rgb([0, 0, 1]));
+        for (x, y) in [(6, 2), (7, 2), (6, 3)] {
+            provinces.put_pixel(x, y, Rgb([0, 0, 2]));
+        }
+        for x in 0..8 {
+            provinces.put_pixel(x, 7, Rgb([0, 0, 3]));
+        }
+        let provinces_by_color = HashMap::from([
+            (Rgb([0, 0, 1]), ProvinceId(1)),
+            (Rgb([0, 0, 2]), ProvinceId(2)),
+            (Rgb([0, 0, 3]), ProvinceId(3)),
+        ]);
+
+        let findings = verify_province_sizes(
+            &provinces,
+            &provinces_by_color,
+            MINIMUM_PROVINCE_SIZE_IN_PIXELS,
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].province, Some(ProvinceId(2)));
+        assert_eq!(findings[0].pixel, Some((6, 2)));
+
+        let findings = verify_province_sizes(&provinces, &provinces_by_color, 9);
+        let reported = findings.iter().map(|f| f.province).collect::<Vec<_>>();
+        assert_eq!(reported, vec![Some(ProvinceId(2)), Some(ProvinceId(3))]);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::components::prelude::*;
+use crate::validation::fixes::AutoFix;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::HashSet;
+
+/// Checks the continents and terrain of the provinces against their types.  Sea provinces must be
+/// on the continent 0, land provinces on one of the continents of the continent list, and sea and
+/// lake provinces must have a water terrain.  The province 0 is not checked.
+#[inline]
+#[must_use]
+pub fn verify_province_types(
+    definitions: &Definitions,
+    continents: usize,
+    water_terrain: &HashSet<Terrain>,
+) -> Vec<Finding> {
+    let mut provinces = definitions
+        .definitions
+        .values()
+        .filter(|d| d.id.is_province())
+        .collect::<Vec<_>>();
+    provinces.sort_unstable_by_key(|d| d.id);
+    let mut findings = Vec::new();
+    for definition in provinces {
+        let continent = definition.continent.0;
+        match definition.province_type {
+            ProvinceType::Sea if continent != 0 => findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::SeaProvinceOnContinent,
+                    format!("Sea province is on the continent {continent}"),
+                )
+                .in_province(definition.id)
+                .with_fix("Set the continent of the province to 0")
+                .with_auto_fix(AutoFix::SetContinent(definition.id, ContinentIndex(0))),
+            ),
+            ProvinceType::Land if continent == 0 || continent > continents => findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::LandProvinceWithoutContinent,
+                    format!(
+                        "Land province is on the continent {continent}, which is not one of the \
+                         {continents} continents of the continent list"
+                    ),
+                )
+                .in_province(definition.id)
+                .with_fix("Set the continent of the province to one of the continent list"),
+            ),
+            ProvinceType::Sea | ProvinceType::Land | ProvinceType::Lake => {}
+        }
+        let water = match definition.province_type {
+            ProvinceType::Sea => "Sea",
+            ProvinceType::Lake => "Lake",
+            ProvinceType::Land => continue,
+        };
+        if !water_terrain.contains(&definition.terrain) {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::WaterProvinceWithLandTerrain,
+                    format!(
+                        "{water} province has the terrain {}, which is not a naval terrain",
+                        definition.terrain
+                    ),
+                )
+                .in_province(definition.id)
+                .with_fix("Give the province a terrain marked with is_water or naval_terrain"),
+            );
+        }
+    }
+    findings
+}
+
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::DefinitionBuilder;
+    use std::collections::HashMap;
+
+    fn definition(
+        id: i32,
+        province_type: ProvinceType,
+        terrain: &str,
+        continent: usize,
+    ) -> Definition {
+        DefinitionBuilder::new(id)
+            .province_type(province_type)
+            .terrain(terrain)
+            .continent(continent)
+            .build()
+    }
+
+    #[test]
+    fn it_reports_every_province_breaking_the_rules_of_its_type() {
+        let definitions = Definitions {
+            definitions: [
+                definition(0, ProvinceType::Land, "unknown", 0),
+                definition(1, ProvinceType::Land, "plains", 1),
+                definition(2, ProvinceType::Land, "plains", 0),
+                definition(3, ProvinceType::Land, "plains", 3),
+                definition(4, ProvinceType::Sea, "ocean", 0),
+                definition(5, ProvinceType::Sea, "plains", 2),
+                definition(6, ProvinceType::Lake, "lakes", 1),
+                definition(7, ProvinceType::Lake, "forest", 0),
+            ]
+            .into_iter()
+            .map(|d| (d.id, d))
+            .collect::<HashMap<_, _>>(),
+            terrain: HashSet::new(),
+        };
+        let water_terrain =
+            HashSet::from([Terrain("ocean".to_owned()), Terrain("lakes".to_owned())]);
+
+        let findings = verify_province_types(&definitions, 2, &water_terrain);
+        let reported = findings
+            .iter()
+            .map(|f| (f.province, f.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reported,
+            vec![
+                (
+                    Some(ProvinceId(2)),
+                    FindingKind::LandProvinceWithoutContinent
+                ),
+                (
+                    Some(ProvinceId(3)),
+                    FindingKind::LandProvinceWithoutContinent
+                ),
+                (Some(ProvinceId(5)), FindingKind::SeaProvinceOnContinent),
+                (
+                    Some(ProvinceId(5)),
+                    FindingKind::WaterProvinceWithLandTerrain
+                ),
+                (
+                    Some(ProvinceId(7)),
+                    FindingKind::WaterProvinceWithLandTerrain
+                ),
+            ]
+        );
+    }
+}
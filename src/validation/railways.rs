@@ -0,0 +1,222 @@
+use crate::components::adjacency::{Adjacencies, AdjacencyType};
+use crate::components::prelude::*;
+use crate::components::railway::{Railway, Railways};
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::{BTreeMap, HashSet};
+
+/// The lowest and highest level a railway may have, alone or summed with the railways it overlaps
+const RAIL_LEVELS: (i32, i32) = (1, 5);
+
+/// Checks the railways against the provinces they run through.  Every province of a railway must
+/// be defined, consecutive provinces must share a border on the provinces image or a passable
+/// adjacency, and the levels of the railways running between the same two provinces must sum to
+/// between 1 and 5.
+#[inline]
+#[must_use]
+pub fn verify_railways(
+    railways: &Railways,
+    adjacencies: &Adjacencies,
+    definitions: &Definitions,
+    province_index: &ProvinceIndex,
+    wrapping: Wrapping,
+) -> Vec<Finding> {
+    let mut connected = province_neighbors(province_index, wrapping);
+    for adjacency in adjacencies
+        .adjacencies
+        .iter()
+        .filter(|a| a.adjacency_type != Some(AdjacencyType::Impassable))
+    {
+        connected.insert(segment(adjacency.from, adjacency.to));
+    }
+
+    let mut findings = Vec::new();
+    let mut levels: BTreeMap<(ProvinceId, ProvinceId), (i32, Vec<usize>)> = BTreeMap::new();
+    for (index, railway) in railways.railways.iter().enumerate() {
+        let row = describe(index, railway);
+        if !(RAIL_LEVELS.0..=RAIL_LEVELS.1).contains(&railway.level.0) {
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::InvalidRailLevel,
+                    format!("The {row} has level {}", railway.level),
+                )
+                .with_fix("Set the level of the railway to between 1 and 5"),
+            );
+        }
+        for province in &railway.provinces {
+            if !definitions.definitions.contains_key(province) {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::UndefinedRailwayProvince,
+                        format!(
+                            "The {row} runs through province {province}, which has no \
+                             definition"
+                        ),
+                    )
+                    .in_province(*province)
+                    .with_fix("Remove the province from the railway or add its definition"),
+                );
+            }
+        }
+        for pair in railway.provinces.windows(2) {
+            if let [from, to] = *pair {
+                if from != to && !connected.contains(&segment(from, to)) {
+                    findings.push(
+                        Finding::new(
+                            Severity::Error,
+                            FindingKind::DisconnectedRailway,
+                            format!(
+                                "The {row} jumps from province {from} to {to}, which are not \
+                                 adjacent"
+                            ),
+                        )
+                        .in_province(from)
+                        .with_fix("Add the provinces between the two to the railway"),
+                    );
+                }
+                let (level, rows) = levels.entry(segment(from, to)).or_default();
+                *level = level.saturating_add(railway.level.0);
+                rows.push(index);
+            }
+        }
+    }
+
+    // A single railway above the highest level has already been reported
+    for ((from, to), (level, rows)) in levels {
+        if level > RAIL_LEVELS.1 && rows.len() > 1 {
+            let rows = rows
+                .iter()
+                .map(|i| i.saturating_add(1).to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::InvalidRailLevel,
+                    format!(
+                        "The railways {rows} overlap between provinces {from} and {to} with a \
+                         total level of {level}"
+                    ),
+                )
+                .in_province(from)
+                .with_fix("Lower the levels of the overlapping railways to sum to at most 5"),
+            );
+        }
+    }
+    findings
+}
+
+/// The pairs of provinces that share a border on the provinces image, each with the lower id first
+pub(crate) fn province_neighbors(
+    province_index: &ProvinceIndex,
+    wrapping: Wrapping,
+) -> HashSet<(ProvinceId, ProvinceId)> {
+    let (width, height) = province_index.dimensions();
+    let pixel_provinces = province_index.provinces().collect::<Vec<_>>();
+    let mut neighbors = HashSet::new();
+    for (i, province) in pixel_provinces.iter().enumerate() {
+        let province = match province {
+            Some(p) => *p,
+            None => continue,
+        };
+        // Looking right and down finds every border once
+        let [_left, right, _up, down] = wrapping.neighbors(i, width, height);
+        for n in [right, down].into_iter().flatten() {
+            if let Some(Some(neighbor)) = pixel_provinces.get(n) {
+                if *neighbor != province {
+                    neighbors.insert(segment(province, *neighbor));
+                }
+            }
+        }
+    }
+    neighbors
+}
+
+/// The provinces at the ends of a segment of railway, in the same order whichever way it runs
+fn segment(from: ProvinceId, to: ProvinceId) -> (ProvinceId, ProvinceId) {
+    (from.min(to), from.max(to))
+}
+
+/// Describes a railway by its line in railways.txt and the provinces it runs between
+fn describe(index: usize, railway: &Railway) -> String {
+    let line = index.saturating_add(1);
+    match (railway.provinces.first(), railway.provinces.last()) {
+        (Some(first), Some(last)) => format!("railway {line} ({first} to {last})"),
+        _ => format!("railway {line}"),
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::adjacency::Adjacency;
+    use crate::fixtures::DefinitionBuilder;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashMap;
+
+    fn railway(level: i32, provinces: &[i32]) -> Railway {
+        Railway {
+            level: RailLevel(level),
+            length: provinces.len(),
+            provinces: provinces.iter().map(|p| ProvinceId(*p)).collect(),
+        }
+    }
+
+    #[test]
+    fn it_reports_disconnected_undefined_and_overleveled_railways() {
+        // The provinces are columns from left to right, with the sea province 5 between 3 and 4
+        let columns = [1, 2, 3, 3, 5, 5, 4, 4];
+        let provinces = RgbImage::from_fn(8, 2, |x, _y| Rgb([0, 0, columns[x as usize]]));
+        let provinces_by_color = (1..=5)
+            .map(|id| (Rgb([0, 0, id]), ProvinceId(i32::from(id))))
+            .collect::<HashMap<_, _>>();
+        let province_index =
+            ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let definition = |id: i32| DefinitionBuilder::new(id).build();
+        let definitions = Definitions {
+            definitions: (1..=5).map(|id| (ProvinceId(id), definition(id))).collect(),
+            terrain: HashSet::new(),
+        };
+        let adjacencies = Adjacencies {
+            adjacencies: vec![Adjacency::sea(ProvinceId(3), ProvinceId(4), ProvinceId(5))],
+            lines: Vec::new(),
+        };
+        let railways = Railways {
+            railways: vec![
+                railway(3, &[1, 2, 3, 4]),
+                railway(3, &[3, 2]),
+                railway(1, &[1, 3]),
+                railway(6, &[4, 9]),
+            ],
+        };
+
+        let findings = verify_railways(
+            &railways,
+            &adjacencies,
+            &definitions,
+            &province_index,
+            Wrapping::None,
+        );
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::DisconnectedRailway,
+                FindingKind::InvalidRailLevel,
+                FindingKind::UndefinedRailwayProvince,
+                FindingKind::DisconnectedRailway,
+                FindingKind::InvalidRailLevel,
+            ]
+        );
+        assert!(findings[0].message.contains("railway 3 (1 to 3)"));
+        assert_eq!(findings[2].province, Some(ProvinceId(9)));
+        assert!(findings[4].message.contains("railways 1, 2 overlap"));
+        assert!(findings[4].message.contains("total level of 6"));
+    }
+}
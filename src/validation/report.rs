@@ -0,0 +1,410 @@
+use crate::components::prelude::*;
+use crate::validation::fixes::AutoFix;
+use crate::validation::province_heights::ProvinceHeightIssueKind;
+use crate::validation::rivers::RiverIssueKind;
+use derive_more::Display;
+use std::fmt;
+use std::fmt::Formatter;
+
+/// How serious a finding is.  Findings are ordered with the most serious first.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// A problem that crashes the game or breaks the map
+    #[display(fmt = "error")]
+    Error,
+    /// A problem that the game tolerates but that is most likely a mistake
+    #[display(fmt = "warning")]
+    Warning,
+    /// Something worth knowing about that is not a problem
+    #[display(fmt = "info")]
+    Info,
+}
+
+/// The check that produced a finding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FindingKind {
+    /// A color on the provinces image without a province definition
+    UndefinedProvinceColor,
+    /// A province definition whose color is not on the provinces image
+    MissingProvinceColor,
+    /// A province definition with the same color as another
+    DuplicateProvinceColor,
+    /// A province whose bounding box spans too much of the map
+    OversizeProvince,
+    /// A province whose pixels form more than one separate area
+    DiscontiguousProvince,
+    /// A province with fewer pixels than the minimum province size
+    SmallProvince,
+    /// A province with a terrain not defined in `common/terrain`
+    UndefinedTerrain,
+    /// A province whose type disagrees with the heightmap
+    ProvinceHeight(ProvinceHeightIssueKind),
+    /// A problem on the rivers image
+    River(RiverIssueKind),
+    /// A province that does not belong to any strategic region
+    ProvinceWithoutStrategicRegion,
+    /// A state listing a province without a definition
+    UndefinedStateProvince,
+    /// A state whose provinces belong to more than one strategic region
+    StateAcrossStrategicRegions,
+    /// Victory points on a province outside of the state that sets them
+    VictoryPointsOutsideState,
+    /// A state with more than one `manpower` entry
+    DuplicateManpower,
+    /// A state whose category is not defined in `common/state_category`
+    UndefinedStateCategory,
+    /// A state owned or controlled by a country without a history in `history/countries`
+    UndefinedCountry,
+    /// A country whose capital is a state that does not exist
+    UndefinedCapitalState,
+    /// A country whose capital is a state it does not own
+    CapitalNotOwned,
+    /// A state listing a sea or lake province
+    NonLandStateProvince,
+    /// A state id defined by more than one file
+    DuplicateStateId,
+    /// A state file whose name does not start with the id of the state in it
+    StateFileNameMismatch,
+    /// State ids missing between 1 and the highest state id
+    StateIdGap,
+    /// A sea adjacency without a province it passes through
+    SeaAdjacencyWithoutThrough,
+    /// An adjacency naming a province without a definition
+    UndefinedAdjacencyProvince,
+    /// An adjacency referencing an adjacency rule that does not exist
+    UndefinedAdjacencyRule,
+    /// An adjacencies file without the row the game stops reading at
+    UnterminatedAdjacencies,
+    /// A building of a state that does not exist
+    BuildingInUndefinedState,
+    /// A building placed outside of the provinces of its state
+    MisplacedBuilding,
+    /// A building whose height differs from the heightmap under it
+    BuildingHeightMismatch,
+    /// A building with a missing sea province, or one it does not border
+    InvalidBuildingSea,
+    /// A weather period that ends before it starts
+    WeatherPeriodOutOfOrder,
+    /// A weather period whose lowest temperature is above its highest
+    InvertedTemperature,
+    /// A weather period giving a weight to a weather state that `common/weather.txt` does not
+    /// define
+    UnknownWeatherState,
+    /// Days of the year not covered by the weather periods of a strategic region
+    WeatherPeriodGap,
+    /// A strategic region without a weather position
+    StrategicRegionWithoutWeatherPosition,
+    /// A weather position for a strategic region that does not exist
+    OrphanedWeatherPosition,
+    /// A weather position outside of the bounding box of its strategic region
+    MisplacedWeatherPosition,
+    /// A railway running through a province without a definition
+    UndefinedRailwayProvince,
+    /// A railway between two provinces that are not adjacent
+    DisconnectedRailway,
+    /// A railway, or railways overlapping each other, with a level outside of 1 to 5
+    InvalidRailLevel,
+    /// A state whose provinces cannot reach a supply node or a naval base
+    UnsuppliedState,
+    /// A province that cannot reach a supply node or a naval base, in a state that can
+    UnsuppliedProvince,
+    /// Railways connected to each other that reach no supply node or naval base
+    DisconnectedRailwayNetwork,
+    /// A corner of the provinces image where four provinces meet
+    XCrossing,
+    /// A sea province on a continent other than 0
+    SeaProvinceOnContinent,
+    /// A land province on the continent 0 or a continent missing from the continent list
+    LandProvinceWithoutContinent,
+    /// A sea or lake province without a water terrain
+    WaterProvinceWithLandTerrain,
+    /// A land province bordering a sea province without being marked as coastal
+    MissingCoastalFlag,
+}
+
+impl FindingKind {
+    /// The name of the rule the check enforces, as used to change its severity in the project
+    /// file
+    #[inline]
+    #[must_use]
+    pub const fn rule(self) -> &'static str {
+        match self {
+            Self::UndefinedProvinceColor => "undefined_province_color",
+            Self::MissingProvinceColor => "missing_province_color",
+            Self::DuplicateProvinceColor => "duplicate_province_color",
+            Self::OversizeProvince => "oversize_province",
+            Self::DiscontiguousProvince => "discontiguous_province",
+            Self::SmallProvince => "small_province",
+            Self::UndefinedTerrain => "undefined_terrain",
+            Self::ProvinceHeight(ProvinceHeightIssueKind::SeaAboveSeaLevel) => {
+                "sea_above_sea_level"
+            }
+            Self::ProvinceHeight(ProvinceHeightIssueKind::LandBelowSeaLevel) => {
+                "land_below_sea_level"
+            }
+            Self::ProvinceHeight(ProvinceHeightIssueKind::LakeNotEnclosed) => "lake_not_enclosed",
+            Self::River(RiverIssueKind::InvalidColor) => "river_invalid_color",
+            Self::River(RiverIssueKind::ThickRiver) => "thick_river",
+            Self::River(RiverIssueKind::DetachedMarker) => "detached_river_marker",
+            Self::River(RiverIssueKind::MissingMarker) => "missing_river_marker",
+            Self::ProvinceWithoutStrategicRegion => "province_without_strategic_region",
+            Self::UndefinedStateProvince => "undefined_state_province",
+            Self::StateAcrossStrategicRegions => "state_across_strategic_regions",
+            Self::VictoryPointsOutsideState => "victory_points_outside_state",
+            Self::DuplicateManpower => "duplicate_manpower",
+            Self::UndefinedStateCategory => "undefined_state_category",
+            Self::UndefinedCountry => "undefined_country",
+            Self::UndefinedCapitalState => "undefined_capital_state",
+            Self::CapitalNotOwned => "capital_not_owned",
+            Self::NonLandStateProvince => "non_land_state_province",
+            Self::DuplicateStateId => "duplicate_state_id",
+            Self::StateFileNameMismatch => "state_file_name_mismatch",
+            Self::StateIdGap => "state_id_gap",
+            Self::SeaAdjacencyWithoutThrough => "sea_adjacency_without_through",
+            Self::UndefinedAdjacencyProvince => "undefined_adjacency_province",
+            Self::UndefinedAdjacencyRule => "undefined_adjacency_rule",
+            Self::UnterminatedAdjacencies => "unterminated_adjacencies",
+            Self::BuildingInUndefinedState => "building_in_undefined_state",
+            Self::MisplacedBuilding => "misplaced_building",
+            Self::BuildingHeightMismatch => "building_height_mismatch",
+            Self::InvalidBuildingSea => "invalid_building_sea",
+            Self::WeatherPeriodOutOfOrder => "weather_period_out_of_order",
+            Self::InvertedTemperature => "inverted_temperature",
+            Self::UnknownWeatherState => "unknown_weather_state",
+            Self::WeatherPeriodGap => "weather_period_gap",
+            Self::StrategicRegionWithoutWeatherPosition => {
+                "strategic_region_without_weather_position"
+            }
+            Self::OrphanedWeatherPosition => "orphaned_weather_position",
+            Self::MisplacedWeatherPosition => "misplaced_weather_position",
+            Self::UndefinedRailwayProvince => "undefined_railway_province",
+            Self::DisconnectedRailway => "disconnected_railway",
+            Self::InvalidRailLevel => "invalid_rail_level",
+            Self::UnsuppliedState => "unsupplied_state",
+            Self::UnsuppliedProvince => "unsupplied_province",
+            Self::DisconnectedRailwayNetwork => "disconnected_railway_network",
+            Self::XCrossing => "x_crossing",
+            Self::SeaProvinceOnContinent => "sea_province_on_continent",
+            Self::LandProvinceWithoutContinent => "land_province_without_continent",
+            Self::WaterProvinceWithLandTerrain => "water_province_with_land_terrain",
+            Self::MissingCoastalFlag => "missing_coastal_flag",
+        }
+    }
+}
+
+/// A problem found while validating the map.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Finding {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The check that found the problem
+    pub kind: FindingKind,
+    /// A description of the problem
+    pub message: String,
+    /// The pixel of the map images where the problem is, if it is at one
+    pub pixel: Option<(u32, u32)>,
+    /// The province with the problem, if any
+    pub province: Option<ProvinceId>,
+    /// The state with the problem, if any
+    pub state: Option<StateId>,
+    /// How the problem can be fixed, if there is a clear way to
+    pub suggested_fix: Option<String>,
+    /// The change to the map that fixes the problem, if it can be fixed automatically
+    pub auto_fix: Option<AutoFix>,
+}
+
+impl Finding {
+    /// Creates a new finding that is not tied to a place on the map
+    #[inline]
+    #[must_use]
+    pub fn new(severity: Severity, kind: FindingKind, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            kind,
+            message: message.into(),
+            pixel: None,
+            province: None,
+            state: None,
+            suggested_fix: None,
+            auto_fix: None,
+        }
+    }
+
+    /// Places the finding at a pixel of the map images
+    #[inline]
+    #[must_use]
+    pub const fn at_pixel(mut self, x: u32, y: u32) -> Self {
+        self.pixel = Some((x, y));
+        self
+    }
+
+    /// Ties the finding to a province
+    #[inline]
+    #[must_use]
+    pub const fn in_province(mut self, province: ProvinceId) -> Self {
+        self.province = Some(province);
+        self
+    }
+
+    /// Ties the finding to a state
+    #[inline]
+    #[must_use]
+    pub const fn in_state(mut self, state: StateId) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Suggests how to fix the problem
+    #[inline]
+    #[must_use]
+    pub fn with_fix(mut self, fix: impl Into<String>) -> Self {
+        self.suggested_fix = Some(fix.into());
+        self
+    }
+
+    /// Gives the change to the map that fixes the problem automatically
+    #[inline]
+    #[must_use]
+    pub fn with_auto_fix(mut self, fix: AutoFix) -> Self {
+        self.auto_fix = Some(fix);
+        self
+    }
+}
+
+impl fmt::Display for Finding {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if let Some(province) = self.province {
+            write!(f, " [province {province}]")?;
+        }
+        if let Some(state) = self.state {
+            write!(f, " [state {state}]")?;
+        }
+        if let Some((x, y)) = self.pixel {
+            write!(f, " at ({x}, {y})")?;
+        }
+        if let Some(fix) = &self.suggested_fix {
+            write!(f, " - {fix}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Every problem found while validating the map, rather than only the first.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct ValidationReport {
+    /// The problems found, with the most serious first once sorted
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Adds a finding to the report
+    #[inline]
+    pub fn push(&mut self, finding: Finding) {
+        self.findings.push(finding);
+    }
+
+    /// Whether any of the findings is an error
+    #[inline]
+    #[must_use]
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    /// The number of findings of the given severity
+    #[inline]
+    #[must_use]
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    /// The findings produced by the given check
+    #[inline]
+    pub fn of_kind(&self, kind: FindingKind) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.kind == kind)
+    }
+
+    /// Sorts the findings with the most serious first, then by state and province
+    #[inline]
+    pub fn sort(&mut self) {
+        self.findings
+            .sort_by_key(|f| (f.severity, f.state, f.province, f.pixel));
+    }
+}
+
+impl Extend<Finding> for ValidationReport {
+    #[inline]
+    fn extend<T: IntoIterator<Item = Finding>>(&mut self, iter: T) {
+        self.findings.extend(iter);
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} errors, {} warnings",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )?;
+        for finding in &self.findings {
+            writeln!(f, "  {finding}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_sorts_the_most_serious_findings_first() {
+        let mut report = ValidationReport::default();
+        report.push(
+            Finding::new(Severity::Warning, FindingKind::UndefinedTerrain, "warning")
+                .in_province(ProvinceId(1)),
+        );
+        report.extend([
+            Finding::new(Severity::Error, FindingKind::UndefinedTerrain, "second")
+                .in_province(ProvinceId(2)),
+            Finding::new(Severity::Error, FindingKind::UndefinedTerrain, "first")
+                .in_province(ProvinceId(1)),
+        ]);
+        report.sort();
+
+        let messages = report
+            .findings
+            .iter()
+            .map(|f| f.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["first", "second", "warning"]);
+        assert!(report.has_errors());
+        assert_eq!(report.count(Severity::Error), 2);
+        assert_eq!(report.of_kind(FindingKind::UndefinedTerrain).count(), 3);
+    }
+
+    #[test]
+    fn it_describes_where_a_finding_is_and_how_to_fix_it() {
+        let finding = Finding::new(
+            Severity::Error,
+            FindingKind::UndefinedProvinceColor,
+            "Color (1, 2, 3) has no definition",
+        )
+        .at_pixel(4, 5)
+        .with_fix("Add a definition for the color");
+        assert_eq!(
+            finding.to_string(),
+            "error: Color (1, 2, 3) has no definition at (4, 5) - Add a definition for the color"
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use crate::components::prelude::*;
+use crate::components::river::RiverColor;
+use crate::province_index::ProvinceIndex;
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// The narrowest river width the game treats as a large river, which is index 7 of the rivers
+/// palette.  Indexes up to 6, including the source and flow markers, are small rivers.
+pub const LARGE_RIVER_WIDTH: u8 = 4;
+
+/// How a river crosses the border between two provinces, which sets the penalty for attacking
+/// across it
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RiverCrossing {
+    /// Only small rivers lie on the border
+    Small,
+    /// A large river lies somewhere on the border
+    Large,
+}
+
+impl RiverCrossing {
+    /// The crossing a river pixel makes, or `None` if the color is not part of a river
+    #[inline]
+    #[must_use]
+    pub const fn of(color: RiverColor) -> Option<Self> {
+        match color {
+            RiverColor::River(width) if width >= LARGE_RIVER_WIDTH => Some(Self::Large),
+            RiverColor::Land | RiverColor::Water => None,
+            _ => Some(Self::Small),
+        }
+    }
+}
+
+/// Finds the pairs of neighboring provinces whose border a river crosses, with the lower id first.
+/// A border is crossed where a river pixel lies on either side of it, and a border crossed by a
+/// small and a large river is a large crossing.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn find_river_crossings(
+    province_index: &ProvinceIndex,
+    rivers: &RgbImage,
+) -> HashMap<(ProvinceId, ProvinceId), RiverCrossing> {
+    let (width, height) = province_index.dimensions();
+    let crossing_at = |x: u32, y: u32| {
+        rivers
+            .get_pixel_checked(x, y)
+            .and_then(|color| RiverColor::from_rgb(*color))
+            .and_then(RiverCrossing::of)
+    };
+    let mut crossings: HashMap<(ProvinceId, ProvinceId), RiverCrossing> = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let province = match province_index.province_at(x, y) {
+                Some(p) => p,
+                None => continue,
+            };
+            let neighbors = [(x + 1, y), (x, y + 1)];
+            for (nx, ny) in neighbors
+                .into_iter()
+                .filter(|(nx, ny)| *nx < width && *ny < height)
+            {
+                let neighbor = match province_index.province_at(nx, ny) {
+                    Some(n) if n != province => n,
+                    _ => continue,
+                };
+                if let Some(crossing) = crossing_at(x, y).max(crossing_at(nx, ny)) {
+                    let pair = (province.min(neighbor), province.max(neighbor));
+                    let kind = crossings.entry(pair).or_insert(crossing);
+                    *kind = (*kind).max(crossing);
+                }
+            }
+        }
+    }
+    crossings
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn it_classifies_the_rivers_crossing_province_borders() {
+        // Provinces 1, 2 and 3 are the left, middle and right thirds of the map
+        let provinces = RgbImage::from_fn(6, 3, |x, _| Rgb([(x / 2 + 1) as u8, 0, 0]));
+        let provinces_by_color = (1..=3)
+            .map(|id| (Rgb([id as u8, 0, 0]), ProvinceId(id)))
+            .collect::<HashMap<_, _>>();
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let mut rivers = RgbImage::from_pixel(6, 3, RiverColor::Land.to_rgb());
+        // A small river runs down the border of 1 and 2, widening into a large river at the end
+        rivers.put_pixel(1, 0, RiverColor::River(0).to_rgb());
+        rivers.put_pixel(1, 1, RiverColor::River(1).to_rgb());
+        rivers.put_pixel(2, 2, RiverColor::River(LARGE_RIVER_WIDTH).to_rgb());
+        // A small river runs through the middle of 3 without touching its border with 2
+        rivers.put_pixel(5, 1, RiverColor::River(0).to_rgb());
+
+        let crossings = find_river_crossings(&index, &rivers);
+        assert_eq!(
+            crossings.get(&(ProvinceId(1), ProvinceId(2))),
+            Some(&RiverCrossing::Large)
+        );
+        assert_eq!(crossings.len(), 1);
+
+        rivers.put_pixel(2, 2, RiverColor::Land.to_rgb());
+        rivers.put_pixel(4, 0, RiverColor::Source.to_rgb());
+        let crossings = find_river_crossings(&index, &rivers);
+        assert_eq!(
+            crossings.get(&(ProvinceId(1), ProvinceId(2))),
+            Some(&RiverCrossing::Small)
+        );
+        assert_eq!(
+            crossings.get(&(ProvinceId(2), ProvinceId(3))),
+            Some(&RiverCrossing::Small)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use crate::components::river::RiverColor;
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::math::Rect;
+use image::RgbImage;
+use std::collections::{HashSet, VecDeque};
+
+/// The way a part of the rivers image is invalid.
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RiverIssueKind {
+    /// A pixel with a color outside of the rivers palette
+    InvalidColor,
+    /// A 2x2 block of river pixels, making the river more than 1 pixel wide
+    ThickRiver,
+    /// A marker that does not touch a river
+    DetachedMarker,
+    /// A river without any source, flow-in or flow-out marker
+    MissingMarker,
+}
+
+/// A problem found on the rivers image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RiverIssue {
+    /// The x coordinate of the pixel with the issue
+    pub x: u32,
+    /// The y coordinate of the pixel with the issue
+    pub y: u32,
+    /// The kind of issue
+    pub kind: RiverIssueKind,
+}
+
+impl RiverIssue {
+    /// Creates a new river issue
+    #[inline]
+    #[must_use]
+    pub const fn new(x: u32, y: u32, kind: RiverIssueKind) -> Self {
+        Self { x, y, kind }
+    }
+
+    /// Whether the issue lies within the given area
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    pub const fn is_within(&self, area: &Rect) -> bool {
+        self.x >= area.x
+            && self.y >= area.y
+            && self.x < area.x + area.width
+            && self.y < area.y + area.height
+    }
+}
+
+impl From<RiverIssue> for Finding {
+    #[inline]
+    fn from(issue: RiverIssue) -> Self {
+        let (message, fix) = match issue.kind {
+            RiverIssueKind::InvalidColor => (
+                "Pixel on the rivers image is not a river color",
+                "Paint the pixel with a river color or the background",
+            ),
+            RiverIssueKind::ThickRiver => (
+                "River is more than 1 pixel wide",
+                "Erase pixels until the river is 1 pixel wide",
+            ),
+            RiverIssueKind::DetachedMarker => (
+                "River marker does not touch a river",
+                "Move the marker onto the end of a river",
+            ),
+            RiverIssueKind::MissingMarker => (
+                "River has no source, flow-in or flow-out marker",
+                "Place a source marker at the start of the river",
+            ),
+        };
+        Finding::new(Severity::Warning, FindingKind::River(issue.kind), message)
+            .at_pixel(issue.x, issue.y)
+            .with_fix(fix)
+    }
+}
+
+/// Verifies the whole rivers image, including that every river has a marker.
+#[inline]
+#[must_use]
+pub fn verify_rivers(rivers: &RgbImage, wrapping: Wrapping) -> Vec<RiverIssue> {
+    let area = Rect {
+        x: 0,
+        y: 0,
+        width: rivers.width(),
+        height: rivers.height(),
+    };
+    let mut issues = verify_rivers_in(rivers, &area, wrapping);
+    issues.extend(find_rivers_without_markers(rivers, wrapping));
+    issues
+}
+
+/// Verifies the pixels of the rivers image within the given area.  Only the checks that can be
+/// made locally are run, so rivers missing markers are not reported.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn verify_rivers_in(rivers: &RgbImage, area: &Rect, wrapping: Wrapping) -> Vec<RiverIssue> {
+    let (width, height) = rivers.dimensions();
+    let mut issues = Vec::new();
+    let max_x = area.x.saturating_add(area.width).min(rivers.width());
+    let max_y = area.y.saturating_add(area.height).min(rivers.height());
+    for y in area.y..max_y {
+        for x in area.x..max_x {
+            let color = match RiverColor::from_rgb(*rivers.get_pixel(x, y)) {
+                Some(c) => c,
+                None => {
+                    issues.push(RiverIssue::new(x, y, RiverIssueKind::InvalidColor));
+                    continue;
+                }
+            };
+            if !color.is_river() {
+                continue;
+            }
+            let neighbors = wrapping.neighbor_points(x, y, width, height);
+            // Only report each block once, from its top left pixel.
+            if let (Some((right, _y)), true) = (neighbors.get(1).copied().flatten(), y + 1 < height)
+            {
+                if is_river(rivers, right, y)
+                    && is_river(rivers, x, y + 1)
+                    && is_river(rivers, right, y + 1)
+                {
+                    issues.push(RiverIssue::new(x, y, RiverIssueKind::ThickRiver));
+                }
+            }
+            if color.is_marker()
+                && !neighbors
+                    .iter()
+                    .flatten()
+                    .any(|(nx, ny)| is_river(rivers, *nx, *ny))
+            {
+                issues.push(RiverIssue::new(x, y, RiverIssueKind::DetachedMarker));
+            }
+        }
+    }
+    issues
+}
+
+/// Verifies the rivers again after the pixels within an area were edited, updating the issues
+/// found before.  The issues within the area are replaced with those `verify_rivers_in` finds,
+/// and every river touching the area is checked for a marker again, as an edit can join a river
+/// to one with a marker or cut a river off from its marker.
+#[inline]
+#[allow(clippy::integer_arithmetic)]
+pub fn reverify_rivers_in(
+    rivers: &RgbImage,
+    issues: &mut Vec<RiverIssue>,
+    area: &Rect,
+    wrapping: Wrapping,
+) {
+    let (width, height) = rivers.dimensions();
+    let max_x = area.x.saturating_add(area.width).min(width);
+    let max_y = area.y.saturating_add(area.height).min(height);
+    let mut walked = HashSet::new();
+    let mut missing = Vec::new();
+    for y in area.y..max_y {
+        for x in area.x..max_x {
+            // The neighbors reach the rivers across a wrapping edge of the map
+            let neighbors = wrapping.neighbor_points(x, y, width, height);
+            for (sx, sy) in std::iter::once((x, y)).chain(neighbors.into_iter().flatten()) {
+                if walked.contains(&(sx, sy)) || !is_river(rivers, sx, sy) {
+                    continue;
+                }
+                // The whole image reports a river at its first pixel in reading order
+                let mut first = (sy, sx);
+                let has_marker = walk_river(rivers, (sx, sy), wrapping, |px, py| {
+                    first = first.min((py, px));
+                    walked.insert((px, py))
+                });
+                if !has_marker {
+                    missing.push(RiverIssue::new(
+                        first.1,
+                        first.0,
+                        RiverIssueKind::MissingMarker,
+                    ));
+                }
+            }
+        }
+    }
+    issues.retain(|issue| {
+        let rechecked =
+            issue.kind == RiverIssueKind::MissingMarker && walked.contains(&(issue.x, issue.y));
+        !issue.is_within(area) && !rechecked
+    });
+    issues.extend(verify_rivers_in(rivers, area, wrapping));
+    issues.extend(missing);
+}
+
+/// Finds the rivers without any markers, reporting the first pixel of each.
+#[allow(clippy::integer_arithmetic)]
+#[allow(clippy::as_conversions)]
+fn find_rivers_without_markers(rivers: &RgbImage, wrapping: Wrapping) -> Vec<RiverIssue> {
+    let (width, height) = rivers.dimensions();
+    let mut visited = vec![false; width as usize * height as usize];
+    let mut issues = Vec::new();
+    for (x, y, _pixel) in rivers.enumerate_pixels() {
+        let index = y as usize * width as usize + x as usize;
+        if visited.get(index).copied().unwrap_or(true) || !is_river(rivers, x, y) {
+            continue;
+        }
+        let has_marker = walk_river(rivers, (x, y), wrapping, |px, py| {
+            match visited.get_mut(py as usize * width as usize + px as usize) {
+                Some(v) if !*v => {
+                    *v = true;
+                    true
+                }
+                _ => false,
+            }
+        });
+        if !has_marker {
+            issues.push(RiverIssue::new(x, y, RiverIssueKind::MissingMarker));
+        }
+    }
+    issues
+}
+
+/// Walks every pixel of the river a river pixel belongs to, returning whether the river has a
+/// marker.  `visit` marks a pixel as walked, returning `false` if it already was.
+fn walk_river<F>(rivers: &RgbImage, start: (u32, u32), wrapping: Wrapping, mut visit: F) -> bool
+where
+    F: FnMut(u32, u32) -> bool,
+{
+    let (width, height) = rivers.dimensions();
+    let mut has_marker = false;
+    visit(start.0, start.1);
+    let mut queue = VecDeque::from([start]);
+    while let Some((x, y)) = queue.pop_front() {
+        has_marker |=
+            RiverColor::from_rgb(*rivers.get_pixel(x, y)).map_or(false, RiverColor::is_marker);
+        let neighbors = wrapping.neighbor_points(x, y, width, height);
+        for (nx, ny) in neighbors.into_iter().flatten() {
+            if is_river(rivers, nx, ny) && visit(nx, ny) {
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    has_marker
+}
+
+/// Whether the pixel is part of a river
+fn is_river(rivers: &RgbImage, x: u32, y: u32) -> bool {
+    rivers
+        .get_pixel_checked(x, y)
+        .and_then(|p| RiverColor::from_rgb(*p))
+        .map_or(false, RiverColor::is_river)
+}
+
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn it_accepts_a_valid_river() {
+        let mut rivers = RgbImage::from_pixel(8, 8, RiverColor::Land.to_rgb());
+        for x in 1..6 {
+            rivers.put_pixel(x, 3, RiverColor::River(0).to_rgb());
+        }
+        rivers.put_pixel(0, 3, RiverColor::Source.to_rgb());
+        assert!(verify_rivers(&rivers, Wrapping::None).is_empty());
+    }
+
+    #[test]
+    fn it_reports_invalid_rivers() {
+        let mut rivers = RgbImage::from_pixel(8, 8, RiverColor::Land.to_rgb());
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            rivers.put_pixel(x, y, RiverColor::River(4).to_rgb());
+        }
+        rivers.put_pixel(6, 6, RiverColor::FlowIn.to_rgb());
+        rivers.put_pixel(4, 6, Rgb([1, 2, 3]));
+        let issues = verify_rivers(&rivers, Wrapping::None);
+        assert_eq!(issues.len(), 4);
+        assert!(issues.contains(&RiverIssue::new(4, 6, RiverIssueKind::InvalidColor)));
+        assert!(issues.contains(&RiverIssue::new(1, 1, RiverIssueKind::ThickRiver)));
+        assert!(issues.contains(&RiverIssue::new(6, 6, RiverIssueKind::DetachedMarker)));
+        assert!(issues.contains(&RiverIssue::new(1, 1, RiverIssueKind::MissingMarker)));
+    }
+
+    #[test]
+    fn it_follows_rivers_across_a_wrapping_edge() {
+        let mut rivers = RgbImage::from_pixel(8, 8, RiverColor::Land.to_rgb());
+        rivers.put_pixel(6, 3, RiverColor::Source.to_rgb());
+        rivers.put_pixel(7, 3, RiverColor::River(0).to_rgb());
+        rivers.put_pixel(0, 3, RiverColor::River(0).to_rgb());
+        rivers.put_pixel(1, 3, RiverColor::River(0).to_rgb());
+        assert!(verify_rivers(&rivers, Wrapping::Horizontal).is_empty());
+        let issues = verify_rivers(&rivers, Wrapping::None);
+        assert_eq!(
+            issues,
+            vec![RiverIssue::new(0, 3, RiverIssueKind::MissingMarker)]
+        );
+    }
+
+    #[test]
+    fn it_checks_the_markers_of_every_river_an_edit_touches() {
+        let mut rivers = RgbImage::from_pixel(16, 8, RiverColor::Land.to_rgb());
+        rivers.put_pixel(0, 3, RiverColor::Source.to_rgb());
+        for x in 1..6 {
+            rivers.put_pixel(x, 3, RiverColor::River(0).to_rgb());
+        }
+        for x in 7..16 {
+            rivers.put_pixel(x, 3, RiverColor::River(0).to_rgb());
+        }
+        let mut issues = verify_rivers(&rivers, Wrapping::None);
+        assert_eq!(
+            issues,
+            vec![RiverIssue::new(7, 3, RiverIssueKind::MissingMarker)]
+        );
+
+        // Joining the rivers gives the one on the right the source of the one on the left
+        rivers.put_pixel(6, 3, RiverColor::River(0).to_rgb());
+        let area = Rect {
+            x: 5,
+            y: 2,
+            width: 3,
+            height: 3,
+        };
+        reverify_rivers_in(&rivers, &mut issues, &area, Wrapping::None);
+        assert!(issues.is_empty(), "{issues:?}");
+
+        // Cutting the river off from its source far from the edit reports it again
+        rivers.put_pixel(11, 3, RiverColor::Land.to_rgb());
+        let area = Rect {
+            x: 10,
+            y: 2,
+            width: 3,
+            height: 3,
+        };
+        reverify_rivers_in(&rivers, &mut issues, &area, Wrapping::None);
+        assert_eq!(
+            issues,
+            vec![RiverIssue::new(12, 3, RiverIssueKind::MissingMarker)]
+        );
+        assert_eq!(issues, verify_rivers(&rivers, Wrapping::None));
+    }
+}
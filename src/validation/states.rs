@@ -0,0 +1,402 @@
+use crate::components::prelude::*;
+use crate::components::state::State;
+use crate::components::state_category::StateCategories;
+use crate::format::SourceFile;
+use crate::validation::fixes::AutoFix;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::{BTreeSet, HashMap};
+
+/// Checks the states against the province definitions and the strategic regions.  Every province
+/// of a state must be defined and be land, a state must lie within a single strategic region, as
+/// the game crashes on launch otherwise, and a state may only give victory points to its own
+/// provinces.
+/// A state should also set its manpower once, as the game only uses the last entry.
+#[inline]
+#[must_use]
+pub fn verify_states(
+    states: &HashMap<StateId, State>,
+    definitions: &Definitions,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (id, state) in states {
+        let mut provinces = state.provinces.iter().copied().collect::<Vec<_>>();
+        provinces.sort_unstable();
+        let mut regions = BTreeSet::new();
+        for province in provinces {
+            match definitions.definitions.get(&province) {
+                None => findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::UndefinedStateProvince,
+                        "State lists a province without a definition",
+                    )
+                    .in_state(*id)
+                    .in_province(province)
+                    .with_fix("Remove the province from the state or add its definition"),
+                ),
+                Some(definition) if definition.province_type != ProvinceType::Land => {
+                    let kind = if definition.province_type == ProvinceType::Sea {
+                        "sea"
+                    } else {
+                        "lake"
+                    };
+                    findings.push(
+                        Finding::new(
+                            Severity::Error,
+                            FindingKind::NonLandStateProvince,
+                            format!("State lists the {kind} province {province}"),
+                        )
+                        .in_state(*id)
+                        .in_province(province)
+                        .with_fix("Remove the province from the state"),
+                    );
+                }
+                Some(_) => {}
+            }
+            if let Some(region) = strategic_regions_by_province.get(&province) {
+                regions.insert(*region);
+            }
+        }
+        if regions.len() > 1 {
+            let regions = regions
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            findings.push(
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::StateAcrossStrategicRegions,
+                    format!("State spans the strategic regions {regions}"),
+                )
+                .in_state(*id)
+                .with_fix("Move the provinces of the state into one strategic region"),
+            );
+        }
+        if state.manpower.len() > 1 {
+            let entries = state
+                .manpower
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::DuplicateManpower,
+                    format!("State has the manpower entries {entries}, of which the last is used"),
+                )
+                .in_state(*id)
+                .with_fix("Remove all but the last manpower entry")
+                .with_auto_fix(AutoFix::DeduplicateManpower(*id)),
+            );
+        }
+        let victory_points = state.history.iter().flat_map(|h| h.victory_points.iter());
+        for (province, _points) in victory_points {
+            if !state.provinces.contains(province) {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::VictoryPointsOutsideState,
+                        "State gives victory points to a province it does not own",
+                    )
+                    .in_state(*id)
+                    .in_province(*province)
+                    .with_fix("Move the victory points to the state that owns the province"),
+                );
+            }
+        }
+    }
+    findings
+}
+
+/// Checks the ids of the states against the files they were loaded from.  A state id must be
+/// defined by only one file, the game expects the name of a state file to start with the id of
+/// the state in it, like `12-Paris.txt`, and the ids should run from 1 to the number of states
+/// without gaps, as the game fills the gaps with empty states.  States without a source file have
+/// not been saved yet, and are written to a file named after their id.
+#[inline]
+#[must_use]
+#[allow(clippy::integer_arithmetic)]
+pub fn verify_state_files(
+    states: &HashMap<StateId, State>,
+    sources: &HashMap<StateId, SourceFile>,
+    duplicates: &[(StateId, String)],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (id, file_name) in duplicates {
+        let loaded = sources
+            .get(id)
+            .map_or_else(|| "another file".to_owned(), |s| s.file_name.clone());
+        findings.push(
+            Finding::new(
+                Severity::Error,
+                FindingKind::DuplicateStateId,
+                format!("{file_name} defines the state {id} already defined by {loaded}"),
+            )
+            .in_state(*id)
+            .with_fix("Give one of the states an unused id"),
+        );
+    }
+
+    let mut sources = sources.iter().collect::<Vec<_>>();
+    sources.sort_unstable_by_key(|(id, _source)| **id);
+    for (id, source) in sources {
+        let file_id = source
+            .file_name
+            .split(['-', ' ', '.'])
+            .next()
+            .and_then(|prefix| prefix.parse::<StateId>().ok());
+        if file_id != Some(*id) {
+            findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::StateFileNameMismatch,
+                    format!(
+                        "The state {id} is loaded from {}, whose name does not start with its id",
+                        source.file_name
+                    ),
+                )
+                .in_state(*id)
+                .with_fix(format!("Rename the file to start with {id}-")),
+            );
+        }
+    }
+
+    let mut ids = states.keys().map(|id| id.0).collect::<Vec<_>>();
+    ids.sort_unstable();
+    let mut gaps = Vec::new();
+    let mut expected = 1;
+    for id in ids {
+        if id > expected {
+            gaps.push(if id - 1 == expected {
+                expected.to_string()
+            } else {
+                format!("{expected} to {}", id - 1)
+            });
+        }
+        expected = expected.max(id.saturating_add(1));
+    }
+    if !gaps.is_empty() {
+        findings.push(
+            Finding::new(
+                Severity::Warning,
+                FindingKind::StateIdGap,
+                format!("The state ids {} are missing", gaps.join(", ")),
+            )
+            .with_fix("Renumber the states so that their ids run from 1 without gaps"),
+        );
+    }
+    findings
+}
+
+/// Checks that every province belongs to a strategic region.
+#[inline]
+#[must_use]
+pub fn verify_strategic_region_coverage(
+    definitions: &Definitions,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+) -> Vec<Finding> {
+    let mut provinces = definitions
+        .definitions
+        .keys()
+        .filter(|id| id.is_province() && !strategic_regions_by_province.contains_key(id))
+        .copied()
+        .collect::<Vec<_>>();
+    provinces.sort_unstable();
+    provinces
+        .into_iter()
+        .map(|province| {
+            Finding::new(
+                Severity::Warning,
+                FindingKind::ProvinceWithoutStrategicRegion,
+                "Province does not belong to a strategic region",
+            )
+            .in_province(province)
+            .with_fix("Add the province to the strategic region around it")
+        })
+        .collect()
+}
+
+/// Checks that the category of every state is defined in `common/state_category`.  Without any
+/// categories loaded there is nothing to check against, so no findings are made.
+#[inline]
+#[must_use]
+pub fn verify_state_categories(
+    states: &HashMap<StateId, State>,
+    categories: &StateCategories,
+) -> Vec<Finding> {
+    if categories.categories.is_empty() {
+        return Vec::new();
+    }
+    let mut undefined = states
+        .values()
+        .filter_map(|state| Some((state.id, state.current_category()?)))
+        .filter(|(_id, category)| categories.get(category).is_none())
+        .collect::<Vec<_>>();
+    undefined.sort_unstable_by_key(|(id, _category)| *id);
+    undefined
+        .into_iter()
+        .map(|(id, category)| {
+            Finding::new(
+                Severity::Error,
+                FindingKind::UndefinedStateCategory,
+                format!("State has the category {category}, which common/state_category lacks"),
+            )
+            .in_state(id)
+            .with_fix("Pick a defined category, or define it in common/state_category")
+        })
+        .collect()
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::state::StateHistory;
+    use crate::components::state_category::StateCategory;
+    use crate::fixtures::DefinitionBuilder;
+    use crate::format::FileFormat;
+    use std::collections::HashSet;
+
+    fn definition(id: i32) -> Definition {
+        DefinitionBuilder::new(id).build()
+    }
+
+    fn state(id: i32, provinces: &[i32], victory_points: &[i32]) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: vec![Manpower(1000)],
+            state_category: vec![StateCategoryName("rural".to_owned())],
+            history: Some(StateHistory {
+                owner: CountryTag("TST".to_owned()),
+                controller: None,
+                victory_points: victory_points
+                    .iter()
+                    .map(|p| (ProvinceId(*p), VictoryPoints(1.0)))
+                    .collect(),
+            }),
+            provinces: provinces.iter().map(|p| ProvinceId(*p)).collect(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    #[test]
+    fn it_reports_every_problem_with_the_states() {
+        let mut definitions = Definitions {
+            definitions: (1..=5).map(|id| (ProvinceId(id), definition(id))).collect(),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        };
+        if let Some(sea) = definitions.definitions.get_mut(&ProvinceId(5)) {
+            sea.province_type = ProvinceType::Sea;
+        }
+        let regions = HashMap::from([
+            (ProvinceId(1), StrategicRegionId(1)),
+            (ProvinceId(2), StrategicRegionId(1)),
+            (ProvinceId(3), StrategicRegionId(2)),
+        ]);
+        let states = HashMap::from([
+            (StateId(1), state(1, &[1, 2], &[1])),
+            (StateId(2), state(2, &[2, 3, 5, 9], &[4])),
+        ]);
+
+        let mut findings = verify_states(&states, &definitions, &regions);
+        findings.sort_by_key(|f| (f.state, f.province));
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::StateAcrossStrategicRegions,
+                FindingKind::VictoryPointsOutsideState,
+                FindingKind::NonLandStateProvince,
+                FindingKind::UndefinedStateProvince,
+            ]
+        );
+        assert!(findings.iter().all(|f| f.state == Some(StateId(2))));
+
+        let uncovered = verify_strategic_region_coverage(&definitions, &regions);
+        assert_eq!(uncovered.len(), 2);
+        assert_eq!(uncovered[0].province, Some(ProvinceId(4)));
+    }
+
+    #[test]
+    fn it_reports_states_setting_their_manpower_more_than_once() {
+        let definitions = Definitions {
+            definitions: (1..=2).map(|id| (ProvinceId(id), definition(id))).collect(),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        };
+        let mut duplicated = state(1, &[1], &[]);
+        duplicated.manpower.push(Manpower(2000));
+        let states = HashMap::from([(StateId(1), duplicated), (StateId(2), state(2, &[2], &[]))]);
+
+        let findings = verify_states(&states, &definitions, &HashMap::new());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::DuplicateManpower);
+        assert!(findings[0].message.contains("1000, 2000"));
+        assert_eq!(
+            findings[0].auto_fix,
+            Some(AutoFix::DeduplicateManpower(StateId(1)))
+        );
+    }
+
+    #[test]
+    fn it_reports_states_with_undefined_categories() {
+        let mut town = state(2, &[2], &[]);
+        town.state_category
+            .push(StateCategoryName("town".to_owned()));
+        let states = HashMap::from([(StateId(1), state(1, &[1], &[])), (StateId(2), town)]);
+        assert!(verify_state_categories(&states, &StateCategories::default()).is_empty());
+
+        let rural = StateCategory {
+            name: StateCategoryName("rural".to_owned()),
+            building_slots: Some(4),
+            color: None,
+        };
+        let categories = StateCategories {
+            categories: HashMap::from([(rural.name.clone(), rural)]),
+        };
+        let findings = verify_state_categories(&states, &categories);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::UndefinedStateCategory);
+        assert_eq!(findings[0].state, Some(StateId(2)));
+        assert!(findings[0].message.contains("category town"));
+    }
+
+    #[test]
+    fn it_reports_state_ids_that_disagree_with_their_files() {
+        let states = [1, 2, 3, 6, 7, 9]
+            .into_iter()
+            .map(|id| (StateId(id), state(id, &[], &[])))
+            .collect::<HashMap<_, _>>();
+        let source = |name: &str| SourceFile::new(name.to_owned(), FileFormat::detect(""));
+        let sources = HashMap::from([
+            (StateId(1), source("1-Paris.txt")),
+            (StateId(2), source("2 - Lyon.txt")),
+            (StateId(3), source("30-Nice.txt")),
+            (StateId(6), source("Brest.txt")),
+        ]);
+        let duplicates = vec![(StateId(1), "1-Paris copy.txt".to_owned())];
+
+        let findings = verify_state_files(&states, &sources, &duplicates);
+        let kinds = findings
+            .iter()
+            .map(|f| (f.kind, f.state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (FindingKind::DuplicateStateId, Some(StateId(1))),
+                (FindingKind::StateFileNameMismatch, Some(StateId(3))),
+                (FindingKind::StateFileNameMismatch, Some(StateId(6))),
+                (FindingKind::StateIdGap, None),
+            ]
+        );
+        assert!(findings[0].message.contains("1-Paris.txt"));
+        assert!(findings[3].message.contains("4 to 5, 8"));
+    }
+}
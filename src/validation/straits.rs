@@ -0,0 +1,266 @@
+use crate::components::adjacency::{Adjacencies, Adjacency};
+use crate::components::prelude::*;
+use crate::topology::Wrapping;
+use image::{Rgb, RgbImage};
+use std::collections::{HashMap, HashSet};
+
+/// The directions a crossing is searched in from each land pixel.  The opposite directions are
+/// covered by the search from the land pixel on the other side.
+const DIRECTIONS: [(i64, i64); 4] = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+
+/// A narrow stretch of sea between two land provinces that are not connected yet, along with the
+/// adjacency that would connect them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StraitCandidate {
+    /// The proposed sea adjacency, passing through the sea province in the middle of the strait
+    pub adjacency: Adjacency,
+    /// The number of sea pixels crossed by the strait
+    pub width: u32,
+    /// The x coordinate of the land pixel the strait starts from
+    pub x: u32,
+    /// The y coordinate of the land pixel the strait starts from
+    pub y: u32,
+}
+
+/// Finds land provinces separated by no more than `max_width` pixels of sea, so that they can be
+/// connected with sea adjacencies.
+///
+/// Straits are searched along rows, columns and diagonals of the provinces image.  Provinces that
+/// already share a border or an adjacency are never suggested, and only the narrowest strait
+/// between each pair of provinces is kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StraitFinder {
+    /// The widest strait in pixels that is suggested
+    pub max_width: u32,
+    /// Whether straits continue across the left and right edges of the map
+    pub wrapping: Wrapping,
+}
+
+impl Default for StraitFinder {
+    #[inline]
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl StraitFinder {
+    /// Creates a new strait finder
+    #[inline]
+    #[must_use]
+    pub const fn new(max_width: u32) -> Self {
+        Self {
+            max_width,
+            wrapping: Wrapping::None,
+        }
+    }
+
+    /// Finds the straits between land provinces that are not connected yet, with the narrowest
+    /// first.  Each adjacency goes from the lower province id to the higher.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::integer_arithmetic)]
+    #[allow(clippy::as_conversions)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_possible_wrap)]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn find(
+        &self,
+        provinces: &RgbImage,
+        definitions: &Definitions,
+        adjacencies: &Adjacencies,
+    ) -> Vec<StraitCandidate> {
+        let (width, height) = provinces.dimensions();
+        let kinds = definitions
+            .definitions
+            .values()
+            .filter(|d| d.id.0 != 0_i32)
+            .map(|d| (Rgb([d.r.0, d.g.0, d.b.0]), (d.id, d.province_type)))
+            .collect::<HashMap<_, _>>();
+        let pixel_provinces = provinces
+            .pixels()
+            .map(|p| kinds.get(p).copied())
+            .collect::<Vec<_>>();
+        let province_at = |x: i64, y: i64| {
+            let x = if self.wrapping.wraps_horizontally() {
+                x.rem_euclid(i64::from(width))
+            } else {
+                x
+            };
+            if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+                return None;
+            }
+            pixel_provinces
+                .get((y * i64::from(width) + x) as usize)
+                .copied()
+                .flatten()
+        };
+        let connected = self.connected(&pixel_provinces, width, height, adjacencies);
+
+        let mut straits: HashMap<(ProvinceId, ProvinceId), StraitCandidate> = HashMap::new();
+        for (i, province) in pixel_provinces.iter().enumerate() {
+            let start = match province {
+                Some((id, ProvinceType::Land)) => *id,
+                _ => continue,
+            };
+            let x = (i % width as usize) as u32;
+            let y = (i / width as usize) as u32;
+            for (dx, dy) in DIRECTIONS {
+                let mut seas = Vec::new();
+                for step in 1..=i64::from(self.max_width) + 1 {
+                    match province_at(i64::from(x) + dx * step, i64::from(y) + dy * step) {
+                        Some((id, ProvinceType::Sea)) => seas.push(id),
+                        Some((end, ProvinceType::Land)) => {
+                            let pair = province_pair(start, end);
+                            let width = seas.len() as u32;
+                            let suggest = pair.0 != pair.1
+                                && !connected.contains(&pair)
+                                && straits.get(&pair).map_or(true, |s| width < s.width);
+                            if let (Some(through), true) = (seas.get(seas.len() / 2), suggest) {
+                                let adjacency = Adjacency::sea(pair.0, pair.1, *through);
+                                let strait = StraitCandidate {
+                                    adjacency,
+                                    width,
+                                    x,
+                                    y,
+                                };
+                                straits.insert(pair, strait);
+                            }
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+
+        let mut straits = straits.into_values().collect::<Vec<_>>();
+        straits.sort_by_key(|s| (s.width, s.adjacency.from, s.adjacency.to));
+        straits
+    }
+
+    /// Finds the pairs of land provinces that share a border or an adjacency
+    fn connected(
+        self,
+        pixel_provinces: &[Option<(ProvinceId, ProvinceType)>],
+        width: u32,
+        height: u32,
+        adjacencies: &Adjacencies,
+    ) -> HashSet<(ProvinceId, ProvinceId)> {
+        let mut connected = adjacencies
+            .adjacencies
+            .iter()
+            .map(|a| province_pair(a.from, a.to))
+            .collect::<HashSet<_>>();
+        for (i, province) in pixel_provinces.iter().enumerate() {
+            let id = match province {
+                Some((id, ProvinceType::Land)) => *id,
+                _ => continue,
+            };
+            for n in self
+                .wrapping
+                .neighbors(i, width, height)
+                .into_iter()
+                .flatten()
+            {
+                if let Some(Some((other, ProvinceType::Land))) = pixel_provinces.get(n) {
+                    if *other != id {
+                        connected.insert(province_pair(id, *other));
+                    }
+                }
+            }
+        }
+        connected
+    }
+}
+
+/// Orders a pair of provinces with the lower id first
+fn province_pair(a: ProvinceId, b: ProvinceId) -> (ProvinceId, ProvinceId) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::adjacency::AdjacencyType;
+    use crate::fixtures::DefinitionBuilder;
+
+    /// Two islands separated by a 3 pixel strait, with open sea below them
+    fn map() -> (RgbImage, Definitions) {
+        let provinces = RgbImage::from_fn(20, 20, |x, y| {
+            if y >= 10 {
+                Rgb([3, 0, 0])
+            } else if x < 8 {
+                Rgb([1, 0, 0])
+            } else if x >= 11 {
+                Rgb([2, 0, 0])
+            } else {
+                Rgb([3, 0, 0])
+            }
+        });
+        let definition = |id: i32, province_type: ProvinceType| {
+            DefinitionBuilder::new(id)
+                .color(Rgb([id as u8, 0, 0]))
+                .province_type(province_type)
+                .coastal(true)
+                .build()
+        };
+        let definitions = Definitions {
+            definitions: HashMap::from([
+                (ProvinceId(1), definition(1, ProvinceType::Land)),
+                (ProvinceId(2), definition(2, ProvinceType::Land)),
+                (ProvinceId(3), definition(3, ProvinceType::Sea)),
+            ]),
+            terrain: HashSet::new(),
+        };
+        (provinces, definitions)
+    }
+
+    #[test]
+    fn it_suggests_straits_between_nearby_land() {
+        let (provinces, definitions) = map();
+        let adjacencies = Adjacencies {
+            adjacencies: Vec::new(),
+            lines: Vec::new(),
+        };
+        let straits = StraitFinder::new(4).find(&provinces, &definitions, &adjacencies);
+        assert_eq!(straits.len(), 1);
+        assert_eq!(straits[0].width, 3);
+        let adjacency = &straits[0].adjacency;
+        assert_eq!(adjacency.from, ProvinceId(1));
+        assert_eq!(adjacency.to, ProvinceId(2));
+        assert_eq!(adjacency.adjacency_type, Some(AdjacencyType::Sea));
+        assert_eq!(adjacency.through_province(), Some(ProvinceId(3)));
+    }
+
+    #[test]
+    fn it_skips_wide_or_connected_straits() {
+        let (provinces, definitions) = map();
+        let mut adjacencies = Adjacencies {
+            adjacencies: Vec::new(),
+            lines: Vec::new(),
+        };
+        let finder = StraitFinder::new(2);
+        assert!(finder
+            .find(&provinces, &definitions, &adjacencies)
+            .is_empty());
+        adjacencies
+            .adjacencies
+            .push(Adjacency::sea(ProvinceId(2), ProvinceId(1), ProvinceId(3)));
+        let finder = StraitFinder::new(4);
+        assert!(finder
+            .find(&provinces, &definitions, &adjacencies)
+            .is_empty());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::components::adjacency::{Adjacencies, AdjacencyType};
+use crate::components::prelude::*;
+use crate::components::railway::Railways;
+use crate::components::state::State;
+use crate::province_index::ProvinceIndex;
+use crate::topology::Wrapping;
+use crate::validation::buildings::building_pixel;
+use crate::validation::railways::province_neighbors;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// The building type that brings supply by sea to the province it is in
+const NAVAL_BASE: &str = "naval_base";
+
+/// The connections supply travels along between the land provinces of the map.  Supply starts at
+/// the supply nodes and the provinces with naval bases, and passes over the borders between land
+/// provinces, the adjacencies that are not impassable, and the railways.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct SupplyGraph {
+    /// The land provinces each land province passes supply to
+    pub connections: HashMap<ProvinceId, BTreeSet<ProvinceId>>,
+    /// The provinces each province is connected to by railways
+    pub railways: HashMap<ProvinceId, BTreeSet<ProvinceId>>,
+    /// The provinces supply starts from
+    pub sources: BTreeSet<ProvinceId>,
+}
+
+impl SupplyGraph {
+    /// Builds the supply graph of a map
+    #[inline]
+    #[must_use]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        definitions: &Definitions,
+        province_index: &ProvinceIndex,
+        wrapping: Wrapping,
+        adjacencies: &Adjacencies,
+        railways: &Railways,
+        supply_nodes: &SupplyNodes,
+        buildings: &Buildings,
+    ) -> Self {
+        let is_land = |id: &ProvinceId| {
+            definitions
+                .definitions
+                .get(id)
+                .map_or(false, |d| d.province_type == ProvinceType::Land)
+        };
+        let mut borders = province_neighbors(province_index, wrapping);
+        for adjacency in adjacencies
+            .adjacencies
+            .iter()
+            .filter(|a| !a.is_terminator())
+        {
+            let pair = (
+                adjacency.from.min(adjacency.to),
+                adjacency.from.max(adjacency.to),
+            );
+            if adjacency.adjacency_type == Some(AdjacencyType::Impassable) {
+                borders.remove(&pair);
+            } else {
+                borders.insert(pair);
+            }
+        }
+
+        let mut graph = Self::default();
+        for (a, b) in borders
+            .into_iter()
+            .filter(|(a, b)| is_land(a) && is_land(b))
+        {
+            graph.connections.entry(a).or_default().insert(b);
+            graph.connections.entry(b).or_default().insert(a);
+        }
+        for railway in &railways.railways {
+            for pair in railway.provinces.windows(2) {
+                if let [a, b] = *pair {
+                    graph.connections.entry(a).or_default().insert(b);
+                    graph.connections.entry(b).or_default().insert(a);
+                    graph.railways.entry(a).or_default().insert(b);
+                    graph.railways.entry(b).or_default().insert(a);
+                }
+            }
+        }
+
+        graph.sources.extend(supply_nodes.nodes.iter().copied());
+        let ports = buildings
+            .buildings
+            .iter()
+            .filter(|b| b.building_id.0 == NAVAL_BASE)
+            .filter_map(|b| building_pixel(b, province_index.dimensions()))
+            .filter_map(|(x, y)| province_index.province_at(x, y))
+            .filter(|id| is_land(id));
+        graph.sources.extend(ports);
+        graph
+    }
+
+    /// The provinces supply reaches from any of the sources
+    #[inline]
+    #[must_use]
+    pub fn reachable(&self) -> HashSet<ProvinceId> {
+        let mut reached = self.sources.iter().copied().collect::<HashSet<_>>();
+        let mut queue = self.sources.iter().copied().collect::<Vec<_>>();
+        while let Some(province) = queue.pop() {
+            for next in self.connections.get(&province).into_iter().flatten() {
+                if reached.insert(*next) {
+                    queue.push(*next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The provinces of each network of railways connected to each other, ordered by their lowest
+    /// province
+    #[inline]
+    #[must_use]
+    pub fn railway_networks(&self) -> Vec<BTreeSet<ProvinceId>> {
+        let mut starts = self.railways.keys().copied().collect::<Vec<_>>();
+        starts.sort_unstable();
+        let mut visited = HashSet::new();
+        let mut networks = Vec::new();
+        for start in starts {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut network = BTreeSet::from([start]);
+            let mut queue = vec![start];
+            while let Some(province) = queue.pop() {
+                for next in self.railways.get(&province).into_iter().flatten() {
+                    if visited.insert(*next) {
+                        network.insert(*next);
+                        queue.push(*next);
+                    }
+                }
+            }
+            networks.push(network);
+        }
+        networks
+    }
+}
+
+/// Checks that supply reaches every land province of the states, and that every network of
+/// railways connects to a supply node or a naval base.  A state that supply cannot reach is
+/// reported once, and the provinces supply cannot reach in other states one by one.
+#[inline]
+#[must_use]
+pub fn verify_supply(
+    graph: &SupplyGraph,
+    definitions: &Definitions,
+    states: &HashMap<StateId, State>,
+) -> Vec<Finding> {
+    let reachable = graph.reachable();
+    let mut findings = Vec::new();
+    let states = states.iter().collect::<BTreeMap<_, _>>();
+    for (id, state) in states {
+        let land = state
+            .provinces
+            .iter()
+            .filter(|&p| {
+                definitions
+                    .definitions
+                    .get(p)
+                    .map_or(false, |d| d.province_type == ProvinceType::Land)
+            })
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let unreached = land
+            .iter()
+            .filter(|&p| !reachable.contains(p))
+            .copied()
+            .collect::<Vec<_>>();
+        match unreached.first() {
+            None => {}
+            Some(first) if unreached.len() == land.len() => findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::UnsuppliedState,
+                    format!(
+                        "None of the {} provinces of the state can reach a supply node or a \
+                         naval base",
+                        land.len()
+                    ),
+                )
+                .in_state(*id)
+                .in_province(*first)
+                .with_fix("Add a supply node or a naval base, or a railway to a supply node"),
+            ),
+            Some(_) => findings.extend(unreached.into_iter().map(|province| {
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::UnsuppliedProvince,
+                    "Province cannot reach a supply node or a naval base",
+                )
+                .in_state(*id)
+                .in_province(province)
+                .with_fix("Connect the province to the rest of its state")
+            })),
+        }
+    }
+
+    for network in graph.railway_networks() {
+        if network.iter().any(|p| graph.sources.contains(p)) {
+            continue;
+        }
+        let listed = network
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let finding = Finding::new(
+            Severity::Warning,
+            FindingKind::DisconnectedRailwayNetwork,
+            format!(
+                "The railways through the provinces {listed} reach no supply node or naval base"
+            ),
+        )
+        .with_fix("Connect the railways to a supply node, or add a supply node on them");
+        findings.push(match network.first() {
+            Some(first) => finding.in_province(*first),
+            None => finding,
+        });
+    }
+    findings
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::indexing_slicing)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::railway::Railway;
+    use crate::fixtures::DefinitionBuilder;
+    use image::{Rgb, RgbImage};
+
+    fn definition(id: i32, province_type: ProvinceType) -> Definition {
+        DefinitionBuilder::new(id)
+            .province_type(province_type)
+            .build()
+    }
+
+    fn state(id: i32, provinces: &[i32]) -> State {
+        State {
+            id: StateId(id),
+            name: StateName(format!("STATE_{id}")),
+            manpower: vec![Manpower(1000)],
+            state_category: vec![StateCategoryName("rural".to_owned())],
+            history: None,
+            provinces: provinces.iter().map(|p| ProvinceId(*p)).collect(),
+            local_supplies: None,
+            impassable: None,
+            buildings_max_level_factor: None,
+        }
+    }
+
+    fn railway(provinces: &[i32]) -> Railway {
+        Railway {
+            level: RailLevel(1),
+            length: provinces.len(),
+            provinces: provinces.iter().map(|p| ProvinceId(*p)).collect(),
+        }
+    }
+
+    #[test]
+    fn it_reports_provinces_and_railways_supply_cannot_reach() {
+        // Provinces 1 to 3 are a row of land, 4 is sea, and 5 and 6 are an island across it
+        let provinces = RgbImage::from_fn(12, 2, |x, y| match (x / 2, y) {
+            (3, _) | (4, _) => Rgb([0, 0, 4]),
+            (5, _) => Rgb([0, 0, 5 + u8::try_from(y).unwrap_or_default()]),
+            (column, _) => Rgb([0, 0, u8::try_from(column).unwrap_or_default() + 1]),
+        });
+        let provinces_by_color = (1..=6)
+            .map(|id| (Rgb([0, 0, id]), ProvinceId(id.into())))
+            .collect::<HashMap<_, _>>();
+        let province_index =
+            ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let definitions = Definitions {
+            definitions: (1..=6)
+                .map(|id| {
+                    let province_type = if id == 4 {
+                        ProvinceType::Sea
+                    } else {
+                        ProvinceType::Land
+                    };
+                    (ProvinceId(id), definition(id, province_type))
+                })
+                .collect(),
+            terrain: HashSet::from([Terrain("plains".to_owned())]),
+        };
+        let states = HashMap::from([
+            (StateId(1), state(1, &[1, 2])),
+            (StateId(2), state(2, &[3, 5])),
+            (StateId(3), state(3, &[6])),
+        ]);
+        let supply_nodes = SupplyNodes {
+            nodes: HashSet::from([ProvinceId(1)]),
+        };
+        let railways = Railways {
+            railways: vec![railway(&[1, 2]), railway(&[5, 6])],
+        };
+        let buildings = Buildings {
+            types: HashSet::new(),
+            buildings: Vec::new(),
+            lines: Vec::new(),
+        };
+        let adjacencies = Adjacencies {
+            adjacencies: Vec::new(),
+            lines: Vec::new(),
+        };
+
+        let graph = SupplyGraph::new(
+            &definitions,
+            &province_index,
+            Wrapping::None,
+            &adjacencies,
+            &railways,
+            &supply_nodes,
+            &buildings,
+        );
+        let findings = verify_supply(&graph, &definitions, &states);
+        let kinds = findings
+            .iter()
+            .map(|f| (f.kind, f.state, f.province))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (
+                    FindingKind::UnsuppliedProvince,
+                    Some(StateId(2)),
+                    Some(ProvinceId(5))
+                ),
+                (
+                    FindingKind::UnsuppliedState,
+                    Some(StateId(3)),
+                    Some(ProvinceId(6))
+                ),
+                (
+                    FindingKind::DisconnectedRailwayNetwork,
+                    None,
+                    Some(ProvinceId(5))
+                ),
+            ]
+        );
+        assert!(findings[2].message.contains("provinces 5, 6 reach"));
+
+        // A strait lets supply cross to the island, but its railways still reach no supply node
+        let mut adjacencies = adjacencies;
+        let mut strait = Adjacency::terminator();
+        strait.from = ProvinceId(3);
+        strait.to = ProvinceId(5);
+        strait.adjacency_type = Some(AdjacencyType::Sea);
+        strait.through = Some(ProvinceId(4));
+        adjacencies.adjacencies.push(strait);
+        let graph = SupplyGraph::new(
+            &definitions,
+            &province_index,
+            Wrapping::None,
+            &adjacencies,
+            &railways,
+            &supply_nodes,
+            &buildings,
+        );
+        let findings = verify_supply(&graph, &definitions, &states);
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![FindingKind::DisconnectedRailwayNetwork]);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::components::day_month::{DayMonth, DAYS_IN_YEAR};
+use crate::components::strategic_region::{StrategicRegions, Weather};
+use crate::components::weather::WeatherStates;
+use crate::validation::report::{Finding, FindingKind, Severity};
+
+/// The ranges of days of the year no weather period covers, as the first and last day of each
+/// range.  Periods that end before they start cover no days.
+#[inline]
+#[must_use]
+pub fn uncovered_days(weather: &Weather) -> Vec<(DayMonth, DayMonth)> {
+    let mut covered = vec![false; usize::from(DAYS_IN_YEAR)];
+    for period in &weather.period {
+        let [start, end] = period.between;
+        let days = usize::from(start.day_of_year())..=usize::from(end.day_of_year());
+        for day in covered
+            .iter_mut()
+            .take(days.end().saturating_add(1))
+            .skip(*days.start())
+        {
+            *day = true;
+        }
+    }
+
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (day, is_covered) in (0_u16..).zip(covered.iter().chain([&true])) {
+        match (start, *is_covered) {
+            (None, false) => start = Some(day),
+            (Some(first), true) => {
+                let range = DayMonth::from_day_of_year(first)
+                    .zip(DayMonth::from_day_of_year(day.saturating_sub(1)));
+                ranges.extend(range);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// Checks the weather periods of every strategic region.  A period must not end before it
+/// starts, its lowest temperature must not be above its highest, its weights should be given to
+/// the weather states of `common/weather.txt`, and the periods of a region should cover every day
+/// of the year, as the game has no weather for the days they leave out.
+#[inline]
+#[must_use]
+pub fn verify_weather_periods(
+    strategic_regions: &StrategicRegions,
+    weather_states: &WeatherStates,
+) -> Vec<Finding> {
+    let mut regions = strategic_regions
+        .strategic_regions
+        .values()
+        .collect::<Vec<_>>();
+    regions.sort_unstable_by_key(|r| r.id);
+    let mut findings = Vec::new();
+    for region in regions {
+        for (index, period) in region.weather.period.iter().enumerate() {
+            let number = index.saturating_add(1);
+            let [start, end] = period.between;
+            if start.day_of_year() > end.day_of_year() {
+                findings.push(
+                    Finding::new(
+                        Severity::Error,
+                        FindingKind::WeatherPeriodOutOfOrder,
+                        format!(
+                            "Period {number} of strategic region {} ends on {end}, before it \
+                             starts on {start}",
+                            region.id
+                        ),
+                    )
+                    .with_fix("Swap the dates, or split the period at the end of the year"),
+                );
+            }
+            let [low, high] = period.temperature;
+            if low > high {
+                findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::InvertedTemperature,
+                        format!(
+                            "Period {number} of strategic region {} has the lowest temperature \
+                             {low} above the highest {high}",
+                            region.id
+                        ),
+                    )
+                    .with_fix("Swap the temperatures"),
+                );
+            }
+            let mut unknown = period
+                .weather_effects
+                .keys()
+                .filter(|effect| !weather_states.contains(effect))
+                .map(|effect| effect.0.as_str())
+                .collect::<Vec<_>>();
+            if !unknown.is_empty() {
+                unknown.sort_unstable();
+                findings.push(
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::UnknownWeatherState,
+                        format!(
+                            "Period {number} of strategic region {} gives weights to the \
+                             undefined weather states {}",
+                            region.id,
+                            unknown.join(", ")
+                        ),
+                    )
+                    .with_fix("Define the weather states in common/weather.txt, or rename them"),
+                );
+            }
+        }
+
+        let uncovered = uncovered_days(&region.weather);
+        if !uncovered.is_empty() {
+            let ranges = uncovered
+                .iter()
+                .map(|(first, last)| {
+                    if first == last {
+                        first.to_string()
+                    } else {
+                        format!("{first} to {last}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            findings.push(
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::WeatherPeriodGap,
+                    format!(
+                        "The weather periods of strategic region {} leave out {ranges}",
+                        region.id
+                    ),
+                )
+                .with_fix("Extend the periods around the days, or add periods for them"),
+            );
+        }
+    }
+    findings
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::prelude::*;
+    use crate::components::strategic_region::{Period, StrategicRegion};
+    use std::collections::{HashMap, HashSet};
+
+    fn period(start: (u8, u8), end: (u8, u8), temperature: [f32; 2]) -> Period {
+        Period {
+            between: [
+                DayMonth {
+                    day: start.0,
+                    month: start.1,
+                },
+                DayMonth {
+                    day: end.0,
+                    month: end.1,
+                },
+            ],
+            temperature: temperature.map(Temperature),
+            temperature_day_night: None,
+            weather_effects: HashMap::new(),
+            min_snow_level: SnowLevel(0.0),
+        }
+    }
+
+    #[test]
+    fn it_reports_gaps_and_inverted_periods() {
+        // January, the first half of February, and March to the end of the year are covered,
+        // but the last period runs backwards
+        let weather = Weather {
+            period: vec![
+                period((0, 0), (30, 0), [-5.0, 5.0]),
+                period((0, 1), (13, 1), [5.0, -5.0]),
+                period((0, 2), (29, 11), [0.0, 10.0]),
+                period((30, 11), (0, 11), [0.0, 10.0]),
+            ],
+        };
+        let uncovered = uncovered_days(&weather);
+        let uncovered = uncovered
+            .iter()
+            .map(|(first, last)| (first.to_string(), last.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uncovered,
+            vec![
+                ("15/2".to_owned(), "28/2".to_owned()),
+                ("31/12".to_owned(), "31/12".to_owned())
+            ]
+        );
+
+        let region = StrategicRegion {
+            id: StrategicRegionId(4),
+            name: StrategicRegionName("REGION_4".to_owned()),
+            provinces: HashSet::new(),
+            weather,
+        };
+        let strategic_regions = StrategicRegions {
+            strategic_regions: HashMap::from([(region.id, region)]),
+            sources: HashMap::new(),
+        };
+        let findings = verify_weather_periods(&strategic_regions, &WeatherStates::default());
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::InvertedTemperature,
+                FindingKind::WeatherPeriodOutOfOrder,
+                FindingKind::WeatherPeriodGap,
+            ]
+        );
+        assert!(findings[0]
+            .message
+            .contains("Period 2 of strategic region 4"));
+        assert!(findings[2].message.contains("15/2 to 28/2, 31/12"));
+    }
+
+    #[test]
+    fn it_finds_no_gaps_in_a_full_year() {
+        let weather = Weather {
+            period: vec![period((0, 0), (30, 11), [0.0, 10.0])],
+        };
+        assert!(uncovered_days(&weather).is_empty());
+    }
+
+    #[test]
+    fn it_reports_undefined_weather_states() {
+        let mut custom = period((0, 0), (30, 11), [0.0, 10.0]);
+        for effect in ["no_phenomenon", "monsoon", "acid_rain"] {
+            custom
+                .weather_effects
+                .insert(WeatherEffect(effect.to_owned()), Weight(0.5));
+        }
+        let region = StrategicRegion {
+            id: StrategicRegionId(7),
+            name: StrategicRegionName("REGION_7".to_owned()),
+            provinces: HashSet::new(),
+            weather: Weather {
+                period: vec![custom],
+            },
+        };
+        let strategic_regions = StrategicRegions {
+            strategic_regions: HashMap::from([(region.id, region)]),
+            sources: HashMap::new(),
+        };
+        let findings = verify_weather_periods(&strategic_regions, &WeatherStates::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::UnknownWeatherState);
+        assert!(findings[0]
+            .message
+            .ends_with("weather states acid_rain, monsoon"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::components::prelude::*;
+use crate::components::strategic_region::StrategicRegions;
+use crate::components::weather_position::{WeatherPosition, WeatherPositions};
+use crate::province_index::ProvinceIndex;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use std::collections::{BTreeMap, HashMap};
+
+/// How far in pixels a position may be from the bounding box of its region, as the positions are
+/// written with fractions of a pixel
+const BOUNDS_TOLERANCE: f32 = 1.0;
+
+/// Checks the weather positions against the strategic regions.  Every strategic region should have
+/// a position, every position must belong to an existing region, and a position should lie within
+/// the bounding box of the provinces of its region.
+#[inline]
+#[must_use]
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+pub fn verify_weather_positions(
+    weather_positions: &WeatherPositions,
+    strategic_regions: &StrategicRegions,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+    province_index: &ProvinceIndex,
+) -> Vec<Finding> {
+    let (width, height) = province_index.dimensions();
+    let bounds = region_bounds(province_index, strategic_regions_by_province);
+    let mut findings = Vec::new();
+    for position in &weather_positions.positions {
+        let row = describe(position);
+        // The vertical position is counted from the bottom of the map
+        let image_y = height as f32 - position.z;
+        let pixel = (position.x >= 0.0
+            && image_y >= 0.0
+            && position.x < width as f32
+            && image_y < height as f32)
+            .then_some((position.x as u32, image_y as u32));
+        let finding = if strategic_regions
+            .strategic_regions
+            .contains_key(&position.id)
+        {
+            match bounds.get(&position.id) {
+                Some(&(min_x, min_y, max_x, max_y))
+                    if position.x < min_x as f32 - BOUNDS_TOLERANCE
+                        || position.x > max_x as f32 + BOUNDS_TOLERANCE
+                        || image_y < min_y as f32 - BOUNDS_TOLERANCE
+                        || image_y > max_y as f32 + BOUNDS_TOLERANCE =>
+                {
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::MisplacedWeatherPosition,
+                        format!(
+                            "The {row} is outside of the region, which spans ({min_x}, {min_y}) \
+                             to ({max_x}, {max_y})"
+                        ),
+                    )
+                    .with_fix("Move the position into the provinces of its region")
+                }
+                _ => continue,
+            }
+        } else {
+            Finding::new(
+                Severity::Error,
+                FindingKind::OrphanedWeatherPosition,
+                format!("The {row} belongs to a strategic region that does not exist"),
+            )
+            .with_fix("Remove the row or change it to an existing strategic region")
+        };
+        findings.push(match pixel {
+            Some((x, y)) => finding.at_pixel(x, y),
+            None => finding,
+        });
+    }
+
+    let mut regions = strategic_regions
+        .strategic_regions
+        .keys()
+        .filter(|id| !weather_positions.positions.iter().any(|p| p.id == **id))
+        .copied()
+        .collect::<Vec<_>>();
+    regions.sort_unstable();
+    findings.extend(regions.into_iter().map(|id| {
+        Finding::new(
+            Severity::Warning,
+            FindingKind::StrategicRegionWithoutWeatherPosition,
+            format!("Strategic region {id} has no weather position"),
+        )
+        .with_fix("Add a row for the region to weatherpositions.txt")
+    }));
+    findings
+}
+
+/// The bounding box of the pixels of each strategic region, as the minimum and maximum columns
+/// and rows
+#[allow(clippy::integer_arithmetic)]
+fn region_bounds(
+    province_index: &ProvinceIndex,
+    strategic_regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+) -> BTreeMap<StrategicRegionId, (u32, u32, u32, u32)> {
+    let (width, _height) = province_index.dimensions();
+    let mut bounds: BTreeMap<StrategicRegionId, (u32, u32, u32, u32)> = BTreeMap::new();
+    for (position, province) in (0_u32..).zip(province_index.provinces()) {
+        let region = province.and_then(|p| strategic_regions_by_province.get(&p));
+        if let Some(region) = region {
+            let (x, y) = (position % width, position / width);
+            let (min_x, min_y, max_x, max_y) = bounds.entry(*region).or_insert((x, y, x, y));
+            *min_x = (*min_x).min(x);
+            *min_y = (*min_y).min(y);
+            *max_x = (*max_x).max(x);
+            *max_y = (*max_y).max(y);
+        }
+    }
+    bounds
+}
+
+/// Describes the row of a weather position by its region and its position on the map
+fn describe(position: &WeatherPosition) -> String {
+    format!(
+        "weather position of region {} at ({:.2}, {:.2})",
+        position.id, position.x, position.z
+    )
+}
+
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[allow(clippy::expect_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::strategic_region::{StrategicRegion, Weather};
+    use crate::components::weather_position::WeatherType;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashSet;
+
+    fn position(id: i32, x: f32, z: f32) -> WeatherPosition {
+        WeatherPosition {
+            id: StrategicRegionId(id),
+            x,
+            y: 1.0,
+            z,
+            weather_type: WeatherType::Small,
+        }
+    }
+
+    #[test]
+    fn it_reports_orphaned_missing_and_misplaced_positions() {
+        // Region 1 covers the left half of the map, and region 2 the right half
+        let provinces = RgbImage::from_fn(20, 10, |x, _y| {
+            if x < 10 {
+                Rgb([1, 0, 0])
+            } else {
+                Rgb([2, 0, 0])
+            }
+        });
+        let provinces_by_color = HashMap::from([
+            (Rgb([1, 0, 0]), ProvinceId(1)),
+            (Rgb([2, 0, 0]), ProvinceId(2)),
+        ]);
+        let strategic_regions_by_province = HashMap::from([
+            (ProvinceId(1), StrategicRegionId(1)),
+            (ProvinceId(2), StrategicRegionId(2)),
+        ]);
+        let region = |id: i32| StrategicRegion {
+            id: StrategicRegionId(id),
+            name: StrategicRegionName(format!("REGION_{id}")),
+            provinces: HashSet::from([ProvinceId(id)]),
+            weather: Weather { period: Vec::new() },
+        };
+        let strategic_regions = StrategicRegions {
+            strategic_regions: HashMap::from([
+                (StrategicRegionId(1), region(1)),
+                (StrategicRegionId(2), region(2)),
+                (StrategicRegionId(3), region(3)),
+            ]),
+            sources: HashMap::new(),
+        };
+        let weather_positions = WeatherPositions {
+            positions: vec![
+                position(1, 4.0, 5.0),
+                position(2, 3.0, 5.0),
+                position(9, 15.0, 5.0),
+            ],
+        };
+
+        let findings = verify_weather_positions(
+            &weather_positions,
+            &strategic_regions,
+            &strategic_regions_by_province,
+            &ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index"),
+        );
+        let kinds = findings.iter().map(|f| f.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::MisplacedWeatherPosition,
+                FindingKind::OrphanedWeatherPosition,
+                FindingKind::StrategicRegionWithoutWeatherPosition,
+            ]
+        );
+        assert_eq!(findings[0].pixel, Some((3, 5)));
+        assert!(findings[0].message.contains("region 2 at (3.00, 5.00)"));
+        assert!(findings[1].message.contains("region 9"));
+        assert!(findings[2].message.contains("region 3"));
+    }
+}
@@ -0,0 +1,251 @@
+use crate::topology::Wrapping;
+use crate::validation::report::{Finding, FindingKind, Severity};
+use image::{Rgb, RgbImage};
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// The most times the provinces image is searched for X crossings when fixing them, as fixing
+/// one corner can create another next to it
+const MAX_FIX_PASSES: usize = 8;
+
+/// A 2x2 block of pixels on the provinces image where four provinces meet, which the game's debug
+/// mode reports as "Map invalid X crossing".
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct XCrossing {
+    /// The x coordinate of the top left pixel of the block
+    pub x: u32,
+    /// The y coordinate of the top left pixel of the block
+    pub y: u32,
+}
+
+impl XCrossing {
+    /// Creates a new X crossing at the top left pixel of its block
+    #[inline]
+    #[must_use]
+    pub const fn new(x: u32, y: u32) -> Self {
+        Self { x, y }
+    }
+
+    /// The pixels of the block, from the top left to the bottom right.  On a horizontally
+    /// wrapping map the block may continue onto the left edge.
+    #[allow(clippy::integer_arithmetic)]
+    fn pixels(self, width: u32) -> [(u32, u32); 4] {
+        let right = (self.x + 1) % width;
+        [
+            (self.x, self.y),
+            (right, self.y),
+            (self.x, self.y + 1),
+            (right, self.y + 1),
+        ]
+    }
+}
+
+impl From<XCrossing> for Finding {
+    #[inline]
+    fn from(crossing: XCrossing) -> Self {
+        Finding::new(
+            Severity::Warning,
+            FindingKind::XCrossing,
+            "Four provinces meet at a corner",
+        )
+        .at_pixel(crossing.x, crossing.y)
+        .with_fix("Give one of the pixels of the corner to a neighboring province")
+    }
+}
+
+/// How an X crossing can be fixed, by giving one of its pixels the province of a pixel next to it
+#[allow(clippy::exhaustive_enums)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CrossingFix<T> {
+    /// The four pixels of the block no longer differ, such as after fixing a neighboring corner
+    Resolved,
+    /// Giving the pixel the province keeps a province of the same type connected
+    Pixel((u32, u32), T),
+    /// No pixel could be given to a province of the same type, so giving the pixel the province
+    /// changes the type of the pixel
+    Unsolved((u32, u32), T),
+}
+
+/// Finds every 2x2 block of a grid where the four cells differ, reading the province of each cell
+/// with `province`.  A horizontally wrapping grid has blocks across its left and right edges.
+#[inline]
+#[must_use]
+pub fn find_x_crossings_in<T: Eq + Hash>(
+    width: u32,
+    height: u32,
+    wrapping: Wrapping,
+    province: impl Fn(u32, u32) -> T,
+) -> Vec<XCrossing> {
+    let columns = if wrapping.wraps_horizontally() {
+        width
+    } else {
+        width.saturating_sub(1)
+    };
+    let mut crossings = Vec::new();
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..columns {
+            let crossing = XCrossing::new(x, y);
+            let cells = crossing.pixels(width).map(|(px, py)| province(px, py));
+            if cells.iter().collect::<HashSet<_>>().len() == 4 {
+                crossings.push(crossing);
+            }
+        }
+    }
+    crossings
+}
+
+/// Finds every 2x2 block of pixels on the provinces image with four different colors.
+#[inline]
+#[must_use]
+pub fn find_x_crossings(provinces: &RgbImage, wrapping: Wrapping) -> Vec<XCrossing> {
+    let (width, height) = provinces.dimensions();
+    find_x_crossings_in(width, height, wrapping, |x, y| *provinces.get_pixel(x, y))
+}
+
+/// The fix of an X crossing in a grid of `width` cells, reading the province of each cell with
+/// `province`.  The top right or bottom left pixel of the block is given the province of a pixel
+/// next to it in the block, so that both provinces stay connected, preferring a province of the
+/// same type as given by `same_type`.
+#[inline]
+#[must_use]
+pub fn crossing_fix<T: Copy + Eq + Hash>(
+    crossing: XCrossing,
+    width: u32,
+    province: impl Fn(u32, u32) -> T,
+    same_type: impl Fn(T, T) -> bool,
+) -> CrossingFix<T> {
+    let [top_left, top_right, bottom_left, bottom_right] = crossing.pixels(width);
+    let [a, b, c, d] =
+        [top_left, top_right, bottom_left, bottom_right].map(|(x, y)| province(x, y));
+    if [a, b, c, d].iter().collect::<HashSet<_>>().len() < 4 {
+        return CrossingFix::Resolved;
+    }
+    let options = [
+        (top_right, a, b),
+        (top_right, d, b),
+        (bottom_left, a, c),
+        (bottom_left, d, c),
+    ];
+    match options
+        .into_iter()
+        .find(|(_pixel, to, from)| same_type(*to, *from))
+    {
+        Some((pixel, to, _from)) => CrossingFix::Pixel(pixel, to),
+        None => CrossingFix::Unsolved(top_right, a),
+    }
+}
+
+/// Fixes an X crossing on the provinces image as `crossing_fix` describes, giving the pixel to a
+/// province of another type when there is no other way.  Returns whether the block was changed.
+#[inline]
+pub fn fix_x_crossing(
+    provinces: &mut RgbImage,
+    crossing: XCrossing,
+    same_type: impl Fn(Rgb<u8>, Rgb<u8>) -> bool,
+) -> bool {
+    let width = provinces.width();
+    let fix = crossing_fix(
+        crossing,
+        width,
+        |x, y| *provinces.get_pixel(x, y),
+        same_type,
+    );
+    match fix {
+        CrossingFix::Resolved => false,
+        CrossingFix::Pixel((x, y), color) | CrossingFix::Unsolved((x, y), color) => {
+            provinces.put_pixel(x, y, color);
+            true
+        }
+    }
+}
+
+/// Fixes every X crossing on the provinces image, returning the number of corners changed.  As
+/// fixing one corner can create another next to it, the image is searched again after each pass
+/// until no crossings are left, for at most `MAX_FIX_PASSES` passes.
+#[inline]
+pub fn fix_x_crossings(
+    provinces: &mut RgbImage,
+    wrapping: Wrapping,
+    same_type: impl Fn(Rgb<u8>, Rgb<u8>) -> bool,
+) -> usize {
+    let mut fixed = 0_usize;
+    for _pass in 0..MAX_FIX_PASSES {
+        let crossings = find_x_crossings(provinces, wrapping);
+        if crossings.is_empty() {
+            break;
+        }
+        for crossing in crossings {
+            if fix_x_crossing(provinces, crossing, &same_type) {
+                fixed = fixed.saturating_add(1);
+            }
+        }
+    }
+    fixed
+}
+
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 image with four quadrants of different colors, which meet at (1, 1)
+    fn quadrants() -> RgbImage {
+        RgbImage::from_fn(4, 4, |x, y| match (x < 2, y < 2) {
+            (true, true) => Rgb([1, 0, 0]),
+            (false, true) => Rgb([2, 0, 0]),
+            (true, false) => Rgb([3, 0, 0]),
+            (false, false) => Rgb([4, 0, 0]),
+        })
+    }
+
+    #[test]
+    fn it_finds_corners_where_four_provinces_meet() {
+        let provinces = quadrants();
+        assert_eq!(
+            find_x_crossings(&provinces, Wrapping::None),
+            vec![XCrossing::new(1, 1)]
+        );
+        // The left and right edges meet in another corner when the map wraps
+        assert_eq!(
+            find_x_crossings(&provinces, Wrapping::Horizontal),
+            vec![XCrossing::new(1, 1), XCrossing::new(3, 1)]
+        );
+    }
+
+    #[test]
+    fn it_fixes_a_corner_with_a_province_of_the_same_type() {
+        let mut provinces = quadrants();
+        let water = [Rgb([1, 0, 0]), Rgb([3, 0, 0])];
+        let same_type = |a: Rgb<u8>, b: Rgb<u8>| water.contains(&a) == water.contains(&b);
+        assert!(fix_x_crossing(
+            &mut provinces,
+            XCrossing::new(1, 1),
+            same_type
+        ));
+        assert_eq!(*provinces.get_pixel(1, 2), Rgb([1, 0, 0]));
+        assert!(find_x_crossings(&provinces, Wrapping::None).is_empty());
+        assert!(!fix_x_crossing(
+            &mut provinces,
+            XCrossing::new(1, 1),
+            same_type
+        ));
+    }
+
+    #[test]
+    fn it_fixes_the_corners_created_by_a_fix() {
+        // Fixing the only corner gives (2, 0) to the province on its left, which makes a new
+        // corner of the provinces around (3, 0)
+        let mut provinces = RgbImage::from_fn(4, 2, |x, y| match (x, y) {
+            (0 | 1, 0) => Rgb([1, 0, 0]),
+            (_, 0) => Rgb([2, 0, 0]),
+            (0 | 1, _) => Rgb([3, 0, 0]),
+            (2, _) => Rgb([4, 0, 0]),
+            _ => Rgb([6, 0, 0]),
+        });
+        assert_eq!(find_x_crossings(&provinces, Wrapping::None).len(), 1);
+        let fixed = fix_x_crossings(&mut provinces, Wrapping::None, |_a, _b| true);
+        assert_eq!(fixed, 2);
+        assert!(find_x_crossings(&provinces, Wrapping::None).is_empty());
+    }
+}
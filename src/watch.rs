@@ -0,0 +1,174 @@
+use crate::MapError;
+use log::error;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+
+/// A file of the root directory that the map reloads on its own when it changes on disk
+#[allow(clippy::exhaustive_enums)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WatchedFile {
+    /// A state file of `history/states/`, which reloads only that state
+    State(PathBuf),
+    /// A file of `map/strategicregions/`, which reloads every strategic region
+    StrategicRegions,
+    /// The adjacencies file named by `map/default.map`
+    Adjacencies,
+    /// `map/supply_nodes.txt`
+    SupplyNodes,
+    /// `map/railways.txt`
+    Railways,
+    /// `map/buildings.txt` or a file of the building types of `common/buildings/`
+    Buildings,
+    /// `map/unitstacks.txt`
+    UnitStacks,
+    /// `map/weatherpositions.txt`
+    WeatherPositions,
+    /// `map/airports.txt`
+    Airports,
+    /// `map/rocketsites.txt`
+    RocketSites,
+}
+
+impl WatchedFile {
+    /// The watched file a path is, if the map reloads it when it changes
+    /// # Arguments
+    /// * `root_path` - the root directory being watched
+    /// * `adjacencies_path` - the adjacencies file of the map, relative to the root directory
+    /// * `path` - the path that changed
+    #[inline]
+    #[must_use]
+    pub fn classify(root_path: &Path, adjacencies_path: &Path, path: &Path) -> Option<Self> {
+        let relative = path.strip_prefix(root_path).ok()?;
+        if relative == adjacencies_path {
+            return Some(Self::Adjacencies);
+        }
+        let is_text = relative
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("txt"));
+        let parent = relative.parent()?;
+        if is_text && parent == Path::new("history/states") {
+            return Some(Self::State(path.to_path_buf()));
+        }
+        if is_text && parent == Path::new("map/strategicregions") {
+            return Some(Self::StrategicRegions);
+        }
+        if is_text && parent == Path::new("common/buildings") {
+            return Some(Self::Buildings);
+        }
+        [
+            ("map/supply_nodes.txt", Self::SupplyNodes),
+            ("map/railways.txt", Self::Railways),
+            ("map/buildings.txt", Self::Buildings),
+            ("map/unitstacks.txt", Self::UnitStacks),
+            ("map/weatherpositions.txt", Self::WeatherPositions),
+            ("map/airports.txt", Self::Airports),
+            ("map/rocketsites.txt", Self::RocketSites),
+        ]
+        .into_iter()
+        .find(|(file, _watched)| relative == Path::new(file))
+        .map(|(_file, watched)| watched)
+    }
+}
+
+/// Watches a root directory for changes to the files the map reloads.  The directory is watched
+/// until the watcher is dropped.
+#[non_exhaustive]
+pub struct FileWatcher {
+    /// The root directory being watched, made absolute so that it matches the changed paths
+    root_path: PathBuf,
+    /// The watcher of the file system, kept so that it keeps watching
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    /// Starts watching a root directory, calling `on_change` from the watcher's thread with each
+    /// watched file that is created or modified
+    /// # Errors
+    /// * If the root directory does not exist
+    /// * If the root directory cannot be watched
+    #[inline]
+    pub fn new<F>(root_path: &Path, adjacencies_path: &Path, on_change: F) -> Result<Self, MapError>
+    where
+        F: Fn(WatchedFile) + Send + 'static,
+    {
+        let root = root_path.canonicalize()?;
+        let watched_root = root.clone();
+        let adjacencies = adjacencies_path.to_path_buf();
+        let mut watcher =
+            notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    event
+                        .paths
+                        .iter()
+                        .filter_map(|path| WatchedFile::classify(&watched_root, &adjacencies, path))
+                        .for_each(&on_change);
+                }
+                Ok(_) => {}
+                Err(e) => error!("Failed to watch {}: {e}", watched_root.display()),
+            })?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        Ok(Self {
+            root_path: root,
+            _watcher: watcher,
+        })
+    }
+
+    /// The root directory being watched
+    #[inline]
+    #[must_use]
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+}
+
+impl Debug for FileWatcher {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher")
+            .field("root_path", &self.root_path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_classifies_the_files_the_map_reloads() {
+        let root = Path::new("/mods/world");
+        let adjacencies = Path::new("map/adjacencies.csv");
+        let classify = |path: &str| WatchedFile::classify(root, adjacencies, &root.join(path));
+        assert_eq!(
+            classify("history/states/1-France.txt"),
+            Some(WatchedFile::State(root.join("history/states/1-France.txt")))
+        );
+        assert_eq!(
+            classify("map/strategicregions/1-Europe.txt"),
+            Some(WatchedFile::StrategicRegions)
+        );
+        assert_eq!(
+            classify("map/adjacencies.csv"),
+            Some(WatchedFile::Adjacencies)
+        );
+        assert_eq!(
+            classify("common/buildings/00_buildings.txt"),
+            Some(WatchedFile::Buildings)
+        );
+        assert_eq!(
+            classify("common/buildings/01_custom.txt"),
+            Some(WatchedFile::Buildings)
+        );
+        assert_eq!(
+            classify("map/unitstacks.txt"),
+            Some(WatchedFile::UnitStacks)
+        );
+        assert_eq!(classify("history/states/notes.md"), None);
+        assert_eq!(classify("map/provinces.bmp"), None);
+        assert_eq!(
+            WatchedFile::classify(root, adjacencies, Path::new("/other/map/railways.txt")),
+            None
+        );
+    }
+}
@@ -0,0 +1,292 @@
+use crate::components::day_month::DayMonth;
+use crate::components::prelude::*;
+use crate::components::strategic_region::{Period, StrategicRegions, Weather};
+use crate::legend::{Legend, LegendEntry};
+use crate::province_index::ProvinceIndex;
+use derive_more::Display;
+use image::{Rgb, RgbImage};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The color of the strategic regions without a weather period on the date
+const NO_WEATHER: Rgb<u8> = Rgb([48, 48, 48]);
+
+/// The temperatures of the colors of the temperature map, from the coldest to the hottest
+const TEMPERATURE_STOPS: [(f32, Rgb<u8>); 6] = [
+    (-30.0, Rgb([96, 32, 160])),
+    (-15.0, Rgb([48, 96, 224])),
+    (0.0, Rgb([224, 240, 255])),
+    (15.0, Rgb([96, 192, 64])),
+    (30.0, Rgb([240, 176, 32])),
+    (45.0, Rgb([192, 32, 32])),
+];
+
+/// The colors given to the weather effects in the order of their names, repeated when there are
+/// more effects than colors
+const WEATHER_COLORS: [Rgb<u8>; 8] = [
+    Rgb([240, 208, 64]),
+    Rgb([64, 128, 224]),
+    Rgb([224, 240, 255]),
+    Rgb([128, 128, 144]),
+    Rgb([208, 96, 32]),
+    Rgb([32, 176, 160]),
+    Rgb([176, 64, 176]),
+    Rgb([144, 192, 64]),
+];
+
+/// What the weather map shows of the weather period of each strategic region
+#[allow(clippy::exhaustive_enums)]
+#[derive(Default, Display, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WeatherMeasure {
+    /// The middle of the temperature range of the period
+    #[default]
+    Temperature,
+    /// The weather effect with the greatest weight in the period
+    #[display(fmt = "Dominant weather")]
+    DominantWeather,
+}
+
+/// The date and measure the weather map is drawn for
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WeatherView {
+    /// The zero-indexed day of the year
+    pub day: u16,
+    /// What is shown of the weather on the day
+    pub measure: WeatherMeasure,
+}
+
+impl WeatherView {
+    /// Creates a new view of the weather on a zero-indexed day of the year
+    #[inline]
+    #[must_use]
+    pub const fn new(day: u16, measure: WeatherMeasure) -> Self {
+        Self { day, measure }
+    }
+
+    /// The date the weather map is drawn for
+    #[inline]
+    #[must_use]
+    pub fn date(&self) -> DayMonth {
+        DayMonth::from_day_of_year(self.day).unwrap_or_default()
+    }
+
+    /// Draws each strategic region in the color of its weather on the day, along with a legend
+    /// of the colors.  Regions without a period on the day are dark grey, and pixels without a
+    /// province or region are black.
+    #[inline]
+    #[must_use]
+    pub fn render(
+        &self,
+        strategic_regions: &StrategicRegions,
+        regions_by_province: &HashMap<ProvinceId, StrategicRegionId>,
+        province_index: &ProvinceIndex,
+    ) -> (RgbImage, Legend) {
+        let periods = strategic_regions
+            .strategic_regions
+            .iter()
+            .filter_map(|(id, region)| Some((*id, period_on(&region.weather, self.day)?)));
+        let (region_colors, legend) = match self.measure {
+            WeatherMeasure::Temperature => {
+                let colors = periods
+                    .map(|(id, period)| (id, temperature_color(mean_temperature(period))))
+                    .collect::<HashMap<_, _>>();
+                (colors, temperature_legend())
+            }
+            WeatherMeasure::DominantWeather => {
+                let effect_colors = weather_colors(strategic_regions);
+                let colors = periods
+                    .filter_map(|(id, period)| {
+                        let effect = dominant_weather(period)?;
+                        Some((id, *effect_colors.get(effect)?))
+                    })
+                    .collect::<HashMap<_, _>>();
+                let legend = Legend::new(
+                    effect_colors
+                        .iter()
+                        .zip(1..)
+                        .map(|((effect, color), id)| LegendEntry::new(id, effect.0.clone(), *color))
+                        .collect(),
+                );
+                (colors, legend)
+            }
+        };
+        let map = province_index.paint(|id| {
+            let region = regions_by_province.get(&id)?;
+            Some(region_colors.get(region).copied().unwrap_or(NO_WEATHER))
+        });
+        (map, legend)
+    }
+}
+
+/// The first weather period covering a zero-indexed day of the year.  Periods that end before
+/// they start cover no days.
+#[inline]
+#[must_use]
+pub fn period_on(weather: &Weather, day: u16) -> Option<&Period> {
+    weather.period.iter().find(|period| {
+        let [start, end] = period.between;
+        (start.day_of_year()..=end.day_of_year()).contains(&day)
+    })
+}
+
+/// The middle of the temperature range of a period
+fn mean_temperature(period: &Period) -> f32 {
+    let [low, high] = period.temperature;
+    (low.0 + high.0) / 2.0
+}
+
+/// The weather effect with the greatest weight in a period, taking the first by name of those
+/// with the same weight so that the map is the same every time it is drawn
+fn dominant_weather(period: &Period) -> Option<&WeatherEffect> {
+    period
+        .weather_effects
+        .iter()
+        .max_by(|(a, a_weight), (b, b_weight)| {
+            a_weight
+                .0
+                .total_cmp(&b_weight.0)
+                .then_with(|| b.0.cmp(&a.0))
+        })
+        .map(|(effect, _weight)| effect)
+}
+
+/// The color of each weather effect of the map, given in the order of their names so that an
+/// effect keeps its color from one day to the next
+fn weather_colors(strategic_regions: &StrategicRegions) -> BTreeMap<WeatherEffect, Rgb<u8>> {
+    let effects = strategic_regions
+        .strategic_regions
+        .values()
+        .flat_map(|region| &region.weather.period)
+        .flat_map(|period| period.weather_effects.keys())
+        .collect::<BTreeSet<_>>();
+    effects
+        .into_iter()
+        .zip(WEATHER_COLORS.iter().cycle())
+        .map(|(effect, color)| (effect.clone(), *color))
+        .collect()
+}
+
+/// The color of a temperature, blended between the two stops around it
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+#[allow(clippy::cast_sign_loss)]
+fn temperature_color(temperature: f32) -> Rgb<u8> {
+    let mut color = TEMPERATURE_STOPS
+        .first()
+        .map_or(NO_WEATHER, |(_stop, c)| *c);
+    for stops in TEMPERATURE_STOPS.windows(2) {
+        if let [(low, from), (high, to)] = stops {
+            if temperature > *low {
+                let weight = ((temperature - low) / (high - low)).min(1.0);
+                for (channel, (a, b)) in color.0.iter_mut().zip(from.0.iter().zip(to.0)) {
+                    let value = (f32::from(b) - f32::from(*a)).mul_add(weight, f32::from(*a));
+                    *channel = value.round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+    }
+    color
+}
+
+/// The legend of the temperature map, with an entry for the temperature of each color stop
+#[allow(clippy::as_conversions)]
+#[allow(clippy::cast_possible_truncation)]
+fn temperature_legend() -> Legend {
+    Legend::new(
+        TEMPERATURE_STOPS
+            .iter()
+            .map(|(temperature, color)| {
+                LegendEntry::new(*temperature as i32, format!("{temperature}°C"), *color)
+            })
+            .collect(),
+    )
+}
+
+#[allow(clippy::expect_used)]
+#[allow(clippy::indexing_slicing)]
+#[allow(clippy::default_numeric_fallback)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::strategic_region::StrategicRegion;
+    use std::collections::HashSet;
+
+    fn period(
+        start: (u8, u8),
+        end: (u8, u8),
+        temperature: [f32; 2],
+        effects: &[(&str, f32)],
+    ) -> Period {
+        Period {
+            between: [
+                DayMonth {
+                    day: start.0,
+                    month: start.1,
+                },
+                DayMonth {
+                    day: end.0,
+                    month: end.1,
+                },
+            ],
+            temperature: temperature.map(Temperature),
+            temperature_day_night: None,
+            weather_effects: effects
+                .iter()
+                .map(|(effect, weight)| (WeatherEffect((*effect).to_owned()), Weight(*weight)))
+                .collect(),
+            min_snow_level: SnowLevel(0.0),
+        }
+    }
+
+    #[test]
+    fn it_colors_the_regions_by_the_weather_of_the_day() {
+        let winter = period(
+            (0, 0),
+            (30, 2),
+            [-30.0, -30.0],
+            &[("snow", 0.6), ("no_phenomenon", 0.4)],
+        );
+        let summer = period((0, 3), (30, 11), [45.0, 45.0], &[("no_phenomenon", 0.9)]);
+        let weather = Weather {
+            period: vec![winter, summer],
+        };
+        assert_eq!(period_on(&weather, 0).map(mean_temperature), Some(-30.0));
+        assert_eq!(period_on(&weather, 200).map(mean_temperature), Some(45.0));
+
+        let region = StrategicRegion {
+            id: StrategicRegionId(1),
+            name: StrategicRegionName("STRATEGICREGION_1".to_owned()),
+            provinces: HashSet::from([ProvinceId(1)]),
+            weather,
+        };
+        let strategic_regions = StrategicRegions {
+            strategic_regions: HashMap::from([(StrategicRegionId(1), region)]),
+            sources: HashMap::new(),
+        };
+        let provinces = RgbImage::from_pixel(2, 2, Rgb([1, 0, 0]));
+        let provinces_by_color = HashMap::from([(Rgb([1, 0, 0]), ProvinceId(1))]);
+        let index = ProvinceIndex::new(&provinces, &provinces_by_color).expect("Failed to index");
+        let regions_by_province = HashMap::from([(ProvinceId(1), StrategicRegionId(1))]);
+
+        let (map, legend) = WeatherView::new(0, WeatherMeasure::Temperature).render(
+            &strategic_regions,
+            &regions_by_province,
+            &index,
+        );
+        assert_eq!(*map.get_pixel(0, 0), TEMPERATURE_STOPS[0].1);
+        assert_eq!(legend.entries.len(), TEMPERATURE_STOPS.len());
+
+        let (map, legend) = WeatherView::new(0, WeatherMeasure::DominantWeather).render(
+            &strategic_regions,
+            &regions_by_province,
+            &index,
+        );
+        let snow = legend
+            .entries
+            .iter()
+            .find(|e| e.name == "snow")
+            .expect("Snow is not in the legend");
+        assert_eq!(*map.get_pixel(1, 1), snow.color);
+        assert_eq!(legend.entries.len(), 2);
+    }
+}
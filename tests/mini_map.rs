@@ -0,0 +1,342 @@
+//! Loads, edits, saves and reloads each component of the mini map, a small world generated by
+//! `world_gen::fixtures::write_mini_map`, so that the loaders and writers can be tested without
+//! the files of the base game.
+#![allow(clippy::expect_used)]
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::indexing_slicing)]
+
+use indicatif::InMemoryTerm;
+use std::fs;
+use std::path::{Path, PathBuf};
+use world_gen::cache::ComponentCache;
+use world_gen::components::prelude::*;
+use world_gen::components::state::States;
+use world_gen::fixtures::write_mini_map;
+use world_gen::map::{Map, MapLoadOptions};
+use world_gen::validation::report::{Finding, FindingKind, Severity};
+use world_gen::{CsvParsing, LoadObject, MapError};
+
+/// A mini map written to a directory of its own, removed again when the test ends
+struct MiniMap {
+    root: PathBuf,
+}
+
+impl MiniMap {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("world_gen_mini_map_{name}"));
+        let _result = fs::remove_dir_all(&root);
+        write_mini_map(&root).expect("Failed to write mini map");
+        Self { root }
+    }
+
+    fn map_file(&self, name: &str) -> PathBuf {
+        self.root.join("map").join(name)
+    }
+
+    fn load(&self) -> Map {
+        self.load_with(MapLoadOptions::new())
+    }
+
+    fn load_with(&self, options: MapLoadOptions) -> Map {
+        self.try_load_with(options)
+            .expect("Failed to load mini map")
+    }
+
+    fn try_load_with(&self, options: MapLoadOptions) -> Result<Map, MapError> {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let root = self.root.clone();
+        let handle = rt.spawn_blocking(move || Map::new::<InMemoryTerm>(&root, &None, &options));
+        rt.block_on(handle).unwrap()
+    }
+}
+
+impl Drop for MiniMap {
+    fn drop(&mut self) {
+        let _result = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn terrain_path(root: &Path) -> PathBuf {
+    root.join("common/terrain/00_terrain.txt")
+}
+
+#[test]
+fn it_loads_the_mini_map_without_errors() {
+    let mini_map = MiniMap::new("load");
+    let map = mini_map.load();
+    assert!(!map.states.is_empty());
+    assert!(!map.strategic_regions.strategic_regions.is_empty());
+    let report = map.validate();
+    assert!(!report.has_errors(), "{report}");
+}
+
+#[test]
+fn it_saves_and_reloads_edited_states() {
+    let mini_map = MiniMap::new("states");
+    let mut map = mini_map.load();
+    let mut ids = map.states.keys().copied().collect::<Vec<_>>();
+    ids.sort_unstable();
+    let id = ids[0];
+    let manpower = Manpower::from(123_456);
+    map.states.get_mut(&id).unwrap().manpower = vec![manpower];
+    map.save_states(&mini_map.root)
+        .expect("Failed to save states");
+
+    let states = States::from_dir(&mini_map.root.join("history/states"), None)
+        .expect("Failed to reload states");
+    assert_eq!(states.states.len(), ids.len());
+    assert_eq!(states.states[&id].manpower, vec![manpower]);
+    assert!(states.duplicates.is_empty());
+    let reloaded = mini_map.load();
+    assert!(!reloaded.validate().has_errors());
+}
+
+#[test]
+fn it_saves_and_reloads_edited_strategic_regions() {
+    let mini_map = MiniMap::new("strategic_regions");
+    let mut map = mini_map.load();
+    let id = *map
+        .strategic_regions
+        .strategic_regions
+        .keys()
+        .min()
+        .unwrap();
+    let name = "REGION_RENAMED".parse::<StrategicRegionName>().unwrap();
+    map.strategic_regions
+        .strategic_regions
+        .get_mut(&id)
+        .unwrap()
+        .name = name.clone();
+    map.save_strategic_regions(&mini_map.root)
+        .expect("Failed to save strategic regions");
+
+    let regions = StrategicRegions::from_dir(&mini_map.map_file("strategicregions"), None)
+        .expect("Failed to reload strategic regions");
+    assert_eq!(regions.strategic_regions[&id].name, name);
+    assert_eq!(
+        regions.strategic_regions[&id].provinces,
+        map.strategic_regions.strategic_regions[&id].provinces
+    );
+}
+
+#[test]
+fn it_saves_and_reloads_edited_definitions() {
+    let mini_map = MiniMap::new("definitions");
+    let path = mini_map.map_file("definition.csv");
+    let terrain = terrain_path(&mini_map.root);
+    let mut definitions =
+        Definitions::from_files(&path, &terrain, CsvParsing::Strict, None).expect("Failed to load");
+    let id = *definitions.definitions.keys().max().unwrap();
+    let coastal = !definitions.definitions[&id].coastal.0;
+    definitions.definitions.get_mut(&id).unwrap().coastal.0 = coastal;
+    definitions
+        .to_file(&path)
+        .expect("Failed to save definitions");
+
+    let reloaded = Definitions::from_files(&path, &terrain, CsvParsing::Strict, None)
+        .expect("Failed to reload");
+    assert_eq!(reloaded.definitions.len(), definitions.definitions.len());
+    assert_eq!(reloaded.definitions[&id].coastal.0, coastal);
+}
+
+#[test]
+fn it_saves_and_reloads_edited_continents() {
+    let mini_map = MiniMap::new("continents");
+    let path = mini_map.map_file("continent.txt");
+    let mut continents = Continents::load_object(&path, None).expect("Failed to load continents");
+    let continent = "atlantis".parse::<Continent>().unwrap();
+    continents.continents.push(continent.clone());
+    continents
+        .to_file(&path)
+        .expect("Failed to save continents");
+
+    let reloaded = Continents::load_object(&path, None).expect("Failed to reload continents");
+    assert_eq!(reloaded.continents, continents.continents);
+    assert_eq!(reloaded.continents.last(), Some(&continent));
+}
+
+#[test]
+fn it_saves_and_reloads_edited_adjacencies() {
+    let mini_map = MiniMap::new("adjacencies");
+    let path = mini_map.map_file("adjacencies.csv");
+    let mut adjacencies = Adjacencies::from_file(&path, CsvParsing::Strict, None)
+        .expect("Failed to load adjacencies");
+    assert!(adjacencies.is_terminated());
+    let mut adjacency = Adjacency::terminator();
+    adjacency.from = "1".parse().unwrap();
+    adjacency.to = "2".parse().unwrap();
+    adjacency.through = None;
+    adjacency.adjacency_type = Some(AdjacencyType::Impassable);
+    adjacencies.push(adjacency);
+    adjacencies
+        .to_file(&path)
+        .expect("Failed to save adjacencies");
+
+    let reloaded = Adjacencies::from_file(&path, CsvParsing::Strict, None)
+        .expect("Failed to reload adjacencies");
+    assert!(reloaded.is_terminated());
+    assert_eq!(reloaded.adjacencies.len(), 2);
+    assert_eq!(
+        reloaded.adjacencies[0].adjacency_type,
+        Some(AdjacencyType::Impassable)
+    );
+}
+
+#[test]
+fn it_reads_unchanged_components_from_the_cache() {
+    let mini_map = MiniMap::new("cache");
+    let cache = ComponentCache::new(mini_map.root.join("cache"));
+    let map = mini_map.load_with(MapLoadOptions::new().with_cache(cache.clone()));
+    for name in [
+        "definitions",
+        "adjacencies",
+        "buildings",
+        "weather_positions",
+    ] {
+        assert!(cache.dir().join(format!("{name}.bin")).exists());
+    }
+
+    let cached = mini_map.load_with(MapLoadOptions::new().with_cache(cache.clone()));
+    assert_eq!(cached.definitions, map.definitions);
+    assert_eq!(cached.adjacencies, map.adjacencies);
+    assert_eq!(cached.adjacencies.lines, map.adjacencies.lines);
+    assert_eq!(
+        cached.weather_positions.positions,
+        map.weather_positions.positions
+    );
+
+    let path = mini_map.map_file("adjacencies.csv");
+    let mut adjacencies = Adjacencies::from_file(&path, CsvParsing::Strict, None)
+        .expect("Failed to load adjacencies");
+    let mut adjacency = Adjacency::terminator();
+    adjacency.from = "1".parse().unwrap();
+    adjacency.to = "2".parse().unwrap();
+    adjacency.through = None;
+    adjacency.adjacency_type = Some(AdjacencyType::Impassable);
+    adjacencies.push(adjacency);
+    adjacencies
+        .to_file(&path)
+        .expect("Failed to save adjacencies");
+    let changed = mini_map.load_with(MapLoadOptions::new().with_cache(cache));
+    assert_eq!(
+        changed.adjacencies.adjacencies.len(),
+        map.adjacencies.adjacencies.len() + 1
+    );
+}
+
+#[test]
+fn it_skips_unreadable_rows_only_when_loading_leniently() {
+    let mini_map = MiniMap::new("lenient");
+    let path = mini_map.map_file("weatherpositions.txt");
+    let positions = fs::read_to_string(&path).expect("Failed to read weather positions");
+    fs::write(&path, format!("{positions}not;a;weather;position\n"))
+        .expect("Failed to write weather positions");
+    let cache = ComponentCache::new(mini_map.root.join("cache"));
+
+    let lenient = mini_map.load_with(
+        MapLoadOptions::new()
+            .with_cache(cache.clone())
+            .with_csv_parsing(CsvParsing::Lenient),
+    );
+    assert_eq!(
+        lenient.weather_positions.positions.len(),
+        positions.lines().count()
+    );
+    // The lenient parse is cached, but a strict load must not be served it
+    let strict = mini_map.try_load_with(MapLoadOptions::new().with_cache(cache));
+    assert!(strict.is_err());
+}
+
+#[test]
+fn it_saves_and_reloads_edited_supply_nodes_and_railways() {
+    let mini_map = MiniMap::new("supply");
+    let nodes_path = mini_map.map_file("supply_nodes.txt");
+    let railways_path = mini_map.map_file("railways.txt");
+    let mut nodes = SupplyNodes::from_file(&nodes_path, None).expect("Failed to load supply nodes");
+    let mut railways = Railways::from_file(&railways_path, None).expect("Failed to load railways");
+    assert!(!railways.railways.is_empty());
+    let removed = *nodes.nodes.iter().min().unwrap();
+    nodes.nodes.remove(&removed);
+    nodes
+        .to_file(&nodes_path)
+        .expect("Failed to save supply nodes");
+    let level = railways.railways[0].level.0 % 5 + 1;
+    railways.railways[0].level = level.to_string().parse().unwrap();
+    railways
+        .to_file(&railways_path)
+        .expect("Failed to save railways");
+
+    let reloaded =
+        SupplyNodes::from_file(&nodes_path, None).expect("Failed to reload supply nodes");
+    assert_eq!(reloaded.nodes, nodes.nodes);
+    let reloaded = Railways::from_file(&railways_path, None).expect("Failed to reload railways");
+    assert_eq!(reloaded.railways, railways.railways);
+    assert_eq!(reloaded.railways[0].level.0, level);
+}
+
+#[test]
+fn it_saves_and_reloads_edited_weather_positions() {
+    let mini_map = MiniMap::new("weather_positions");
+    let path = mini_map.map_file("weatherpositions.txt");
+    let mut positions = WeatherPositions::from_file(&path, CsvParsing::Strict, None)
+        .expect("Failed to load positions");
+    positions.positions[0].x = 12.5;
+    positions.to_file(&path).expect("Failed to save positions");
+
+    let reloaded = WeatherPositions::from_file(&path, CsvParsing::Strict, None)
+        .expect("Failed to reload positions");
+    assert_eq!(reloaded.positions.len(), positions.positions.len());
+    assert!((reloaded.positions[0].x - 12.5).abs() < f32::EPSILON);
+}
+
+#[test]
+fn it_saves_and_reloads_edited_airports_and_rocket_sites() {
+    let mini_map = MiniMap::new("airports");
+    let airports_path = mini_map.map_file("airports.txt");
+    let rocket_sites_path = mini_map.map_file("rocketsites.txt");
+    let mut airports = Airports::from_file(&airports_path, None).expect("Failed to load airports");
+    let mut rocket_sites =
+        RocketSites::from_file(&rocket_sites_path, None).expect("Failed to load rocket sites");
+    let state = *airports.airports.keys().min().unwrap();
+    airports.airports.remove(&state);
+    airports
+        .to_file(&airports_path)
+        .expect("Failed to save airports");
+    rocket_sites.rocket_sites.remove(&state);
+    rocket_sites
+        .to_file(&rocket_sites_path)
+        .expect("Failed to save rocket sites");
+
+    let reloaded = Airports::from_file(&airports_path, None).expect("Failed to reload airports");
+    assert_eq!(reloaded.airports, airports.airports);
+    let reloaded =
+        RocketSites::from_file(&rocket_sites_path, None).expect("Failed to reload rocket sites");
+    assert_eq!(reloaded.rocket_sites, rocket_sites.rocket_sites);
+}
+
+#[test]
+fn it_locates_findings_on_their_province_or_state() {
+    let mini_map = MiniMap::new("locate");
+    let map = mini_map.load();
+    let finding = || Finding::new(Severity::Warning, FindingKind::UnsuppliedProvince, "test");
+    assert_eq!(map.locate_finding(&finding().at_pixel(3, 4)), Some((3, 4)));
+    assert_eq!(map.locate_finding(&finding()), None);
+
+    let mut ids = map.states.keys().copied().collect::<Vec<_>>();
+    ids.sort_unstable();
+    let state = &map.states[&ids[0]];
+    let province = *state.provinces.iter().min().unwrap();
+    for finding in [
+        finding().in_province(province),
+        finding().in_state(state.id),
+    ] {
+        let (x, y) = map
+            .locate_finding(&finding)
+            .expect("Failed to locate finding");
+        let color = map.provinces.get_pixel(x, y);
+        assert_eq!(map.provinces_by_color.get(color), Some(&province));
+    }
+}